@@ -0,0 +1,65 @@
+// ABOUTME: Benchmarks for environment-heavy workloads (recursion, closures, global lookup)
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use lisp_llm_sandbox::builtins::register_builtins;
+use lisp_llm_sandbox::env::Environment;
+use lisp_llm_sandbox::eval::eval;
+use lisp_llm_sandbox::parser::parse;
+use lisp_llm_sandbox::value::Value;
+use std::sync::Arc;
+
+/// Fresh global environment with builtins and the given definitions
+fn env_with(definitions: &[&str]) -> Arc<Environment> {
+    let env = Environment::new();
+    register_builtins(env.clone());
+    for def in definitions {
+        eval(parse(def).expect("definition parses"), env.clone()).expect("definition evaluates");
+    }
+    env
+}
+
+fn run(env: &Arc<Environment>, expr: &Value) -> Value {
+    eval(expr.clone(), env.clone()).expect("benchmark expression evaluates")
+}
+
+fn bench_recursive_calls(c: &mut Criterion) {
+    // Non-tail recursion: one call frame per binding, lookups walk to the global scope
+    let env = env_with(&["(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2)))))"]);
+    let expr = parse("(fib 18)").unwrap();
+    c.bench_function("env/recursive_fib_18", |b| {
+        b.iter(|| run(&env, black_box(&expr)))
+    });
+}
+
+fn bench_deep_closure_lookup(c: &mut Criterion) {
+    // Variables captured several frames up the scope chain
+    let env = env_with(&[
+        "(define (nest a) (lambda (b) (lambda (c) (lambda (d) (begin (define (walk n acc) (if (= n 0) acc (walk (- n 1) (+ acc a b c d)))) walk)))))",
+        "(define inner ((((nest 1) 2) 3) 4))",
+    ]);
+    let expr = parse("(inner 500 0)").unwrap();
+    c.bench_function("env/deep_closure_lookup_500", |b| {
+        b.iter(|| run(&env, black_box(&expr)))
+    });
+}
+
+fn bench_many_globals(c: &mut Criterion) {
+    // Defining many globals must not slow down later lookups
+    let definitions: Vec<String> = (0..1000)
+        .map(|i| format!("(define g{} {})", i, i))
+        .collect();
+    let refs: Vec<&str> = definitions.iter().map(String::as_str).collect();
+    let env = env_with(&refs);
+    let expr = parse("(+ g0 g250 g500 g750 g999)").unwrap();
+    c.bench_function("env/global_lookup_1000_defs", |b| {
+        b.iter(|| run(&env, black_box(&expr)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_recursive_calls,
+    bench_deep_closure_lookup,
+    bench_many_globals
+);
+criterion_main!(benches);
@@ -0,0 +1,123 @@
+// ABOUTME: Benchmarks for parsing, TCO recursion, list processing, macros, and JSON
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use lisp_llm_sandbox::builtins::register_builtins;
+use lisp_llm_sandbox::env::Environment;
+use lisp_llm_sandbox::eval::eval_with_macros;
+use lisp_llm_sandbox::macros::MacroRegistry;
+use lisp_llm_sandbox::parser::parse;
+use lisp_llm_sandbox::stdlib::register_stdlib;
+use lisp_llm_sandbox::value::Value;
+use std::sync::Arc;
+
+/// Global environment with builtins, the Rust stdlib, and the given definitions
+fn setup(definitions: &[&str]) -> (Arc<Environment>, MacroRegistry) {
+    let env = Environment::new();
+    let macro_reg = MacroRegistry::new();
+    register_builtins(env.clone());
+    register_stdlib(env.clone());
+    for def in definitions {
+        let expr = parse(def).expect("definition parses");
+        eval_with_macros(expr, env.clone(), &macro_reg).expect("definition evaluates");
+    }
+    (env, macro_reg)
+}
+
+fn run(env: &Arc<Environment>, macro_reg: &MacroRegistry, expr: &Value) -> Value {
+    eval_with_macros(expr.clone(), env.clone(), macro_reg).expect("benchmark expression evaluates")
+}
+
+/// Source for a list literal of the numbers 0..n
+fn number_list(n: usize) -> String {
+    let items: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+    format!("'({})", items.join(" "))
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let source = format!(
+        "(define (process items) (let ((data {})) (map (lambda (x) (* x 2)) data)))",
+        number_list(500)
+    );
+    c.bench_function("parse/define_with_500_item_list", |b| {
+        b.iter(|| parse(black_box(&source)).unwrap())
+    });
+}
+
+fn bench_tail_recursion(c: &mut Criterion) {
+    let (env, macro_reg) =
+        setup(&["(define (count-down n acc) (if (= n 0) acc (count-down (- n 1) (+ acc 1))))"]);
+    let mut group = c.benchmark_group("tco");
+    for depth in [1_000, 10_000] {
+        let expr = parse(&format!("(count-down {} 0)", depth)).unwrap();
+        group.bench_with_input(BenchmarkId::new("count_down", depth), &expr, |b, expr| {
+            b.iter(|| run(&env, &macro_reg, black_box(expr)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_list_processing(c: &mut Criterion) {
+    let (env, macro_reg) = setup(&[]);
+    let mut group = c.benchmark_group("lists");
+    for size in [100, 1_000] {
+        let list = number_list(size);
+        let map_expr = parse(&format!("(map (lambda (x) (* x x)) {})", list)).unwrap();
+        let filter_expr = parse(&format!("(filter (lambda (x) (> x 50)) {})", list)).unwrap();
+        let reduce_expr = parse(&format!("(reduce + 0 {})", list)).unwrap();
+        group.bench_with_input(BenchmarkId::new("map", size), &map_expr, |b, expr| {
+            b.iter(|| run(&env, &macro_reg, black_box(expr)))
+        });
+        group.bench_with_input(BenchmarkId::new("filter", size), &filter_expr, |b, expr| {
+            b.iter(|| run(&env, &macro_reg, black_box(expr)))
+        });
+        group.bench_with_input(BenchmarkId::new("reduce", size), &reduce_expr, |b, expr| {
+            b.iter(|| run(&env, &macro_reg, black_box(expr)))
+        });
+    }
+    group.finish();
+}
+
+fn bench_macro_expansion(c: &mut Criterion) {
+    // Each loop iteration expands `unless`, which expands into `if`
+    let (env, macro_reg) = setup(&[
+        "(defmacro unless (test body) `(if ,test nil ,body))",
+        "(define (loop n) (unless (= n 0) (loop (- n 1))))",
+    ]);
+    let expr = parse("(loop 1000)").unwrap();
+    c.bench_function("macros/expand_in_loop_1000", |b| {
+        b.iter(|| run(&env, &macro_reg, black_box(&expr)))
+    });
+}
+
+fn bench_json_decode(c: &mut Criterion) {
+    let (env, macro_reg) = setup(&[]);
+    let records: Vec<String> = (0..200)
+        .map(|i| {
+            format!(
+                r#"{{"id": {}, "name": "item-{}", "tags": ["a", "b"], "ok": true}}"#,
+                i, i
+            )
+        })
+        .collect();
+    let json = format!("[{}]", records.join(","));
+    let expr = Value::List(
+        vec![
+            Value::Symbol("json:decode".into()),
+            Value::String(json.into()),
+        ]
+        .into(),
+    );
+    c.bench_function("json/decode_200_records", |b| {
+        b.iter(|| run(&env, &macro_reg, black_box(&expr)))
+    });
+}
+
+criterion_group!(
+    benches,
+    bench_parse,
+    bench_tail_recursion,
+    bench_list_processing,
+    bench_macro_expansion,
+    bench_json_decode
+);
+criterion_main!(benches);
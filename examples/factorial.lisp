@@ -1,12 +1,12 @@
 ;; Factorial Implementations
 ;; Demonstrating both recursive and tail-recursive approaches
 
-;; Classic recursive factorial
-;; factorial(n) = n * factorial(n-1), base case: factorial(0) = 1
-(define (factorial n)
+;; Classic recursive factorial (named fact: the stdlib's factorial is protected)
+;; fact(n) = n * fact(n-1), base case: fact(0) = 1
+(define (fact n)
   (if (<= n 1)
       1
-      (* n (factorial (- n 1)))))
+      (* n (fact (- n 1)))))
 
 ;; Tail-recursive factorial with accumulator
 ;; Takes advantage of tail call optimization (TCO)
@@ -20,10 +20,10 @@
 
 ;; Example usage
 (println "Factorial of 5:")
-(println (factorial 5))
+(println (fact 5))
 
 (println "Factorial of 10:")
-(println (factorial 10))
+(println (fact 10))
 
 (println "Factorial of 20 (using tail recursion):")
 (println (factorial-tail 20))
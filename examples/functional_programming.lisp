@@ -27,7 +27,7 @@
 ;; =============================================================================
 
 ;; Define some simple functions
-(define (square x) (* x x))
+;; square comes from the stdlib
 (define (inc x) (+ x 1))
 (define (double x) (* x 2))
 
@@ -5,8 +5,10 @@
 //! help entries and registration code.
 
 use proc_macro::TokenStream;
-use quote::quote;
-use syn::{parse_macro_input, Attribute, ItemFn, Meta};
+use proc_macro2::{Delimiter, Span, TokenStream as TokenStream2, TokenTree};
+use quote::{format_ident, quote};
+use syn::parse::{ParseStream, Parser};
+use syn::{parenthesized, parse_macro_input, Attribute, ItemFn, LitStr, Meta};
 
 /// A parsed markdown documentation with structured sections
 #[derive(Debug, Clone)]
@@ -129,47 +131,194 @@ fn parse_doc_markdown(raw_doc: &str) -> DocMarkdown {
     }
 }
 
-/// Parse builtin attribute arguments: name = "...", category = "...", related(...)
-fn parse_builtin_args(attr_stream: TokenStream) -> (String, String, Vec<String>) {
-    let attr_str = attr_stream.to_string();
-
-    // Simple parsing - look for name = "..." and category = "..."
-    let mut name = String::new();
-    let mut category = String::new();
-    let mut related = Vec::new();
+/// Parameters declared with `params(...)`: `x` is required, `[x]` optional and
+/// `x...` takes any number of remaining arguments
+#[derive(Debug, Clone, Default)]
+struct Params {
+    names: Vec<String>,
+    min: usize,
+    max: Option<usize>,
+}
 
-    // Parse name
-    if let Some(start) = attr_str.find("name = \"") {
-        let rest = &attr_str[start + 8..];
-        if let Some(end) = rest.find('"') {
-            name = rest[..end].to_string();
+impl Params {
+    /// Check the order of the names (required, then optional, then at most one rest
+    /// parameter last) and count the arity they allow
+    fn new(names: Vec<(String, Span)>) -> syn::Result<Self> {
+        let mut min = 0;
+        let mut max = Some(0);
+        let mut optional_seen = false;
+        for (i, (name, span)) in names.iter().enumerate() {
+            if max.is_none() {
+                return Err(syn::Error::new(
+                    *span,
+                    "a rest parameter (`name...`) must be the last parameter",
+                ));
+            }
+            if name.ends_with("...") {
+                max = None;
+                continue;
+            }
+            max = Some(i + 1);
+            if name.starts_with('[') {
+                optional_seen = true;
+            } else if optional_seen {
+                return Err(syn::Error::new(
+                    *span,
+                    "a required parameter cannot follow an optional `[parameter]`",
+                ));
+            } else {
+                min += 1;
+            }
         }
+        Ok(Params {
+            names: names.into_iter().map(|(name, _)| name).collect(),
+            min,
+            max,
+        })
     }
+}
 
-    // Parse category
-    if let Some(start) = attr_str.find("category = \"") {
-        let rest = &attr_str[start + 12..];
-        if let Some(end) = rest.find('"') {
-            category = rest[..end].to_string();
+/// Arguments of `#[builtin(...)]`
+#[derive(Default)]
+struct BuiltinArgs {
+    name: Option<String>,
+    category: Option<String>,
+    related: Vec<String>,
+    params: Option<Params>,
+    /// `(min, max)` from `arity = "..."`, overriding what `params` allow
+    arity: Option<(usize, Option<usize>)>,
+    signature: Option<String>,
+    /// Capability module that removes this builtin (see `CAPABILITY_MODULES`)
+    capability: Option<String>,
+}
+
+/// Write tokens back out without the spaces the tokenizer puts between them,
+/// so `map-has?` and `[default]` read as written
+fn tokens_text(tokens: TokenStream2) -> String {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            TokenTree::Group(group) => {
+                let (open, close) = match group.delimiter() {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::None => ("", ""),
+                };
+                format!("{}{}{}", open, tokens_text(group.stream()), close)
+            }
+            other => other.to_string(),
+        })
+        .collect()
+}
+
+/// Parse the parenthesized, comma-separated names after `related` or `params`
+///
+/// Names are Lisp names such as `string->number` or `set!`, which are not Rust
+/// paths, so each is kept as the text of its tokens with the span of its first one.
+fn parse_name_list(input: ParseStream) -> syn::Result<Vec<(String, Span)>> {
+    let content;
+    parenthesized!(content in input);
+    let mut names = Vec::new();
+    let mut current = TokenStream2::new();
+    let mut span = None;
+    while !content.is_empty() {
+        let token: TokenTree = content.parse()?;
+        if matches!(&token, TokenTree::Punct(p) if p.as_char() == ',') {
+            if let Some(span) = span.take() {
+                names.push((tokens_text(std::mem::take(&mut current)), span));
+            }
+            continue;
         }
+        span.get_or_insert(token.span());
+        current.extend([token]);
+    }
+    if let Some(span) = span {
+        names.push((tokens_text(current), span));
     }
+    Ok(names)
+}
 
-    // Parse related functions
-    if let Some(start) = attr_str.find("related") {
-        let rest = &attr_str[start..];
-        if let Some(paren_start) = rest.find('(') {
-            if let Some(paren_end) = rest.find(')') {
-                let related_str = &rest[paren_start + 1..paren_end];
-                related = related_str
-                    .split(',')
-                    .map(|s| s.trim().to_string())
-                    .filter(|s| !s.is_empty())
-                    .collect();
+/// Parse an `arity = "..."` value: `"2"`, `"1-3"` or `"at least 1"`
+fn parse_arity(lit: &LitStr) -> syn::Result<(usize, Option<usize>)> {
+    let text = lit.value();
+    let number = |s: &str| {
+        s.trim().parse::<usize>().map_err(|_| {
+            syn::Error::new(
+                lit.span(),
+                "expected an arity like \"2\", \"1-3\" or \"at least 1\"",
+            )
+        })
+    };
+    if let Some(min) = text.strip_prefix("at least ") {
+        return Ok((number(min)?, None));
+    }
+    match text.split_once('-') {
+        Some((min, max)) => {
+            let (min, max) = (number(min)?, number(max)?);
+            if min > max {
+                return Err(syn::Error::new(
+                    lit.span(),
+                    "the minimum arity is larger than the maximum",
+                ));
             }
+            Ok((min, Some(max)))
         }
+        None => number(&text).map(|n| (n, Some(n))),
     }
+}
 
-    (name, category, related)
+/// Parse builtin attribute arguments: name = "...", category = "...", related(...),
+/// params(...), arity = "...", signature = "..." and capability = "..."
+fn parse_builtin_args(attr: TokenStream2) -> syn::Result<BuiltinArgs> {
+    let mut args = BuiltinArgs::default();
+    let parser = syn::meta::parser(|meta| {
+        let key = meta
+            .path
+            .get_ident()
+            .map(|ident| ident.to_string())
+            .unwrap_or_default();
+        let duplicate = || meta.error(format!("`{}` is given more than once", key));
+        match key.as_str() {
+            "name" | "category" | "signature" | "capability" => {
+                let value: LitStr = meta.value()?.parse()?;
+                let slot = match key.as_str() {
+                    "name" => &mut args.name,
+                    "category" => &mut args.category,
+                    "capability" => &mut args.capability,
+                    _ => &mut args.signature,
+                };
+                if slot.replace(value.value()).is_some() {
+                    return Err(duplicate());
+                }
+            }
+            "arity" => {
+                let value: LitStr = meta.value()?.parse()?;
+                if args.arity.replace(parse_arity(&value)?).is_some() {
+                    return Err(duplicate());
+                }
+            }
+            "related" => {
+                let names = parse_name_list(meta.input)?;
+                args.related
+                    .extend(names.into_iter().map(|(name, _)| name));
+            }
+            "params" => {
+                let params = Params::new(parse_name_list(meta.input)?)?;
+                if args.params.replace(params).is_some() {
+                    return Err(duplicate());
+                }
+            }
+            _ => {
+                return Err(meta.error(
+                    "unknown builtin argument; expected name, category, related, params, arity, signature or capability",
+                ))
+            }
+        }
+        Ok(())
+    });
+    parser.parse2(attr)?;
+    Ok(args)
 }
 
 /// Attribute macro for defining Lisp builtin functions
@@ -177,16 +326,28 @@ fn parse_builtin_args(attr_stream: TokenStream) -> (String, String, Vec<String>)
 /// Extracts rustdoc comments and generates both the function and a registration
 /// function that binds it to the environment and registers help documentation.
 ///
+/// The function takes `(args: &[Value])`, or `(args: &[Value], ctx: &BuiltinContext)`
+/// when it needs the sandbox, the global environment or the macro registry.
+///
 /// # Attribute Arguments
 ///
 /// - `name`: The Lisp name for this builtin (e.g., "+")
 /// - `category`: Category for help organization (e.g., "Arithmetic")
 /// - `related`: Related builtin functions to list in help
+/// - `params`: Parameter names, with `[optional]` and `rest...` ones; they make the
+///   help signature and the arity that is checked before the function is called
+/// - `arity`: The arity to check when `params` cannot express it, as `"2"`, `"1-3"`
+///   or `"at least 1"`
+/// - `signature`: Help signature to show instead of the one built from `params`
+/// - `capability`: The capability module (e.g., "filesystem") whose removal by the
+///   sandbox policy also removes this builtin; leave it out for pure functions
+///
+/// Malformed or unknown arguments are compile errors pointing at the argument.
 ///
 /// # Example
 ///
 /// ```ignore
-/// #[builtin(name = "+", category = "Arithmetic", related(sub, mul, div))]
+/// #[builtin(name = "+", category = "Arithmetic", related(sub, mul, div), params(numbers...))]
 /// /// Returns the sum of all arguments.
 /// ///
 /// /// # Examples
@@ -202,18 +363,16 @@ pub fn builtin(attr: TokenStream, item: TokenStream) -> TokenStream {
     let func = parse_macro_input!(item as ItemFn);
 
     // Extract metadata from attribute
-    let (lisp_name, category, related) = parse_builtin_args(attr);
+    let args = match parse_builtin_args(attr.into()) {
+        Ok(args) => args,
+        Err(e) => return e.to_compile_error().into(),
+    };
 
     // Extract function metadata
     let fn_name = func.sig.ident.clone();
-    let fn_ident_str = fn_name.to_string();
 
     // Use provided name or fall back to function name
-    let name_to_use = if !lisp_name.is_empty() {
-        lisp_name
-    } else {
-        fn_ident_str.clone()
-    };
+    let name_to_use = args.name.unwrap_or_else(|| fn_name.to_string());
 
     // Extract and parse doc comments
     let raw_docs = extract_doc_comments(&func.attrs);
@@ -230,35 +389,161 @@ pub fn builtin(attr: TokenStream, item: TokenStream) -> TokenStream {
     let examples = &parsed_docs.examples;
 
     // Build the related vector (from attribute) as static array
-    let related_vec = &related;
+    let related_vec = &args.related;
 
     // Build the category (with fallback)
-    let cat_to_use = if !category.is_empty() {
-        category.clone()
+    let cat_to_use = args.category.unwrap_or_else(|| "Other".to_string());
+
+    // Generate signature as "(name params...)", or "(name ...)" when params are not declared
+    let signature = args.signature.unwrap_or_else(|| {
+        let params = match &args.params {
+            Some(params) => params.names.join(" "),
+            None => "...".to_string(),
+        };
+        format!("({} {})", name_to_use, params).replace(" )", ")")
+    });
+
+    let capability = match &args.capability {
+        Some(capability) => quote! { Some(#capability) },
+        None => quote! { None },
+    };
+
+    let params = args.params.unwrap_or(Params {
+        max: None,
+        ..Params::default()
+    });
+    let param_names = &params.names;
+    let (min_arity, max) = args.arity.unwrap_or((params.min, params.max));
+    let max_arity = match max {
+        Some(max) => quote! { Some(#max) },
+        None => quote! { None },
+    };
+
+    // A second parameter asks for a `BuiltinContext`, which an adapter supplies
+    let takes_context = func.sig.inputs.len() == 2;
+    let call = if takes_context {
+        quote! { #fn_name(args, &crate::builtins::BuiltinContext::new(#name_to_use)) }
     } else {
-        "Other".to_string()
+        quote! { #fn_name(args) }
     };
 
-    // Generate signature as "(name ...)"
-    let signature = format!("({} ...)", name_to_use);
+    // A bounded arity is checked by a wrapper, which is what gets bound in the environment
+    let bounded = min_arity > 0 || max.is_some();
+    let check = bounded.then(|| {
+        quote! { crate::builtins::check_arity(#name_to_use, #min_arity, #max_arity, args.len())?; }
+    });
+    let (checker, registered, function) = if bounded || takes_context {
+        let checked = format_ident!("__builtin_{}", fn_name);
+        let checker = quote! {
+            #[doc(hidden)]
+            fn #checked(
+                args: &[crate::value::Value],
+            ) -> Result<crate::value::Value, crate::error::EvalError> {
+                #check
+                #call
+            }
+        };
+        let function = if takes_context {
+            checked.clone()
+        } else {
+            fn_name.clone()
+        };
+        (checker, checked, function)
+    } else {
+        (quote! {}, fn_name.clone(), fn_name.clone())
+    };
 
     // Generate the expanded code with automatic registration via inventory
     let expanded = quote! {
         #func
 
+        #checker
+
         // Submit this builtin to the inventory for automatic collection
         inventory::submit! {
             crate::builtins::BuiltinRegistration {
                 name: #name_to_use,
-                function: #fn_name,
+                function: #function,
+                call: #registered,
                 signature: #signature,
                 description: #description,
                 examples: &[#(#examples),*],
                 related: &[#(#related_vec),*],
                 category: #cat_to_use,
+                params: &[#(#param_names),*],
+                min_arity: #min_arity,
+                max_arity: #max_arity,
+                capability: #capability,
             }
         }
     };
 
     TokenStream::from(expanded)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(attr: TokenStream2) -> BuiltinArgs {
+        parse_builtin_args(attr).unwrap()
+    }
+
+    fn error(attr: TokenStream2) -> String {
+        parse_builtin_args(attr).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_parses_arguments_in_any_order() {
+        let args = parse(quote! {
+            params(map, key, [default]), related(map-set, map-has?, string->number),
+            category = "Maps", name = "map-get"
+        });
+        assert_eq!(args.name.as_deref(), Some("map-get"));
+        assert_eq!(args.category.as_deref(), Some("Maps"));
+        assert_eq!(args.related, ["map-set", "map-has?", "string->number"]);
+        assert_eq!(args.capability, None);
+        let params = args.params.unwrap();
+        assert_eq!(params.names, ["map", "key", "[default]"]);
+        assert_eq!((params.min, params.max), (2, Some(3)));
+    }
+
+    #[test]
+    fn test_rest_parameters_and_arity_overrides() {
+        let params = parse(quote! { params(f, list, more-lists...) })
+            .params
+            .unwrap();
+        assert_eq!((params.min, params.max), (2, None));
+        assert_eq!(parse(quote! { params() }).params.unwrap().max, Some(0));
+
+        assert_eq!(parse(quote! { arity = "1-3" }).arity, Some((1, Some(3))));
+        assert_eq!(
+            parse(quote! { arity = "at least 2" }).arity,
+            Some((2, None))
+        );
+        assert_eq!(parse(quote! { arity = "0" }).arity, Some((0, Some(0))));
+        assert_eq!(
+            parse(quote! { signature = "(pp value [width] :full bool)" })
+                .signature
+                .as_deref(),
+            Some("(pp value [width] :full bool)")
+        );
+        assert_eq!(
+            parse(quote! { capability = "filesystem" })
+                .capability
+                .as_deref(),
+            Some("filesystem")
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_arguments() {
+        assert!(error(quote! { nmae = "car" }).contains("unknown builtin argument"));
+        assert!(error(quote! { name = "a", name = "b" }).contains("more than once"));
+        assert!(error(quote! { name = car }).contains("expected string literal"));
+        assert!(error(quote! { arity = "two" }).contains("expected an arity"));
+        assert!(error(quote! { arity = "3-1" }).contains("larger than the maximum"));
+        assert!(error(quote! { params([a], b) }).contains("required parameter"));
+        assert!(error(quote! { params(a..., b) }).contains("must be the last"));
+    }
+}
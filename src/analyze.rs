@@ -0,0 +1,381 @@
+// ABOUTME: Analysis pass that resolves special forms and expands macros ahead of evaluation
+
+use crate::env::EnvRef;
+use crate::error::EvalError;
+use crate::eval::expand_macros;
+use crate::macros::MacroRegistry;
+use crate::value::Value;
+use std::sync::Arc;
+
+/// Special forms recognized by the evaluator
+///
+/// Resolving the head symbol to this enum once replaces a chain of string
+/// comparisons in the trampoline and lets the analysis pass know which
+/// sub-forms are code and which are data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpecialForm {
+    Define,
+    Lambda,
+    Quote,
+    Quasiquote,
+    Defmacro,
+    If,
+    Case,
+    Begin,
+    Let,
+    Delay,
+    Set,
+    Defconstant,
+    While,
+    Dotimes,
+    Dolist,
+    Break,
+    Continue,
+    Try,
+    ThreadFirst,
+    ThreadLast,
+    SomeThread,
+    Profile,
+    Time,
+    Bench,
+}
+
+impl SpecialForm {
+    /// Resolve a head symbol to a special form, if it names one
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "define" => Some(SpecialForm::Define),
+            "lambda" => Some(SpecialForm::Lambda),
+            "quote" => Some(SpecialForm::Quote),
+            "quasiquote" => Some(SpecialForm::Quasiquote),
+            "defmacro" => Some(SpecialForm::Defmacro),
+            "if" => Some(SpecialForm::If),
+            "case" => Some(SpecialForm::Case),
+            "begin" => Some(SpecialForm::Begin),
+            "let" => Some(SpecialForm::Let),
+            "delay" => Some(SpecialForm::Delay),
+            "set!" => Some(SpecialForm::Set),
+            "defconstant" => Some(SpecialForm::Defconstant),
+            "while" => Some(SpecialForm::While),
+            "dotimes" => Some(SpecialForm::Dotimes),
+            "dolist" => Some(SpecialForm::Dolist),
+            "break" => Some(SpecialForm::Break),
+            "continue" => Some(SpecialForm::Continue),
+            "try" => Some(SpecialForm::Try),
+            "->" => Some(SpecialForm::ThreadFirst),
+            "->>" => Some(SpecialForm::ThreadLast),
+            "some->" => Some(SpecialForm::SomeThread),
+            "profile" => Some(SpecialForm::Profile),
+            "time" => Some(SpecialForm::Time),
+            "bench" => Some(SpecialForm::Bench),
+            _ => None,
+        }
+    }
+
+    /// Resolve the head of a list expression
+    pub fn of(head: &Value) -> Option<Self> {
+        match head {
+            Value::Symbol(name) => SpecialForm::from_name(name),
+            _ => None,
+        }
+    }
+}
+
+/// Expand every macro call in `expr` that is in evaluated position
+///
+/// Lambda and function bodies are run through this once when the function is
+/// created, so calling the function does not re-expand its macros on every
+/// iteration of the trampoline. Quoted data and quasiquote templates are left
+/// alone. Macro calls that fail to expand are kept as-is so the error surfaces
+/// when (and if) that code actually runs, exactly as without analysis.
+///
+/// Expansion happens at definition time: redefining a macro later does not
+/// change functions that were already defined with the old expansion. Macros
+/// defined after the function are still expanded lazily by the evaluator.
+pub fn expand_all(
+    expr: Value,
+    macro_reg: &MacroRegistry,
+    env: &EnvRef,
+) -> Result<Value, EvalError> {
+    if !needs_expansion(&expr, macro_reg) {
+        return Ok(expr);
+    }
+    Ok(expand_tree(expr, macro_reg, env))
+}
+
+/// Expand every macro call in `expr` that is in evaluated position, collecting the failures
+///
+/// Unlike [`expand_all`], which keeps a failing call for the evaluator to
+/// report when it runs, this is for checking code without running it.
+pub fn expansion_errors(expr: &Value, macro_reg: &MacroRegistry, env: &EnvRef) -> Vec<EvalError> {
+    let mut errors = Vec::new();
+    collect_expansion_errors(expr, macro_reg, env, &mut errors);
+    errors
+}
+
+fn collect_expansion_errors(
+    expr: &Value,
+    macro_reg: &MacroRegistry,
+    env: &EnvRef,
+    errors: &mut Vec<EvalError>,
+) {
+    if let Value::List(items) = expr {
+        if is_macro_call(items, macro_reg) {
+            match expand_macros(expr.clone(), macro_reg, env) {
+                Ok(expanded) => collect_expansion_errors(&expanded, macro_reg, env, errors),
+                Err(e) => errors.push(e),
+            }
+            return;
+        }
+    }
+    for child in code_children(expr) {
+        collect_expansion_errors(child, macro_reg, env, errors);
+    }
+}
+
+/// Cheap read-only check so already-expanded code is not rebuilt
+fn needs_expansion(expr: &Value, macro_reg: &MacroRegistry) -> bool {
+    match expr {
+        Value::List(items) if !items.is_empty() => {
+            if is_macro_call(items, macro_reg) {
+                return true;
+            }
+            if let Some(Value::List(spec)) = items.get(1) {
+                if binding_inits(SpecialForm::of(&items[0]), spec)
+                    .into_iter()
+                    .any(|init| needs_expansion(init, macro_reg))
+                {
+                    return true;
+                }
+            }
+            case_code(items)
+                .into_iter()
+                .chain(&items[code_start(items)..])
+                .any(|item| needs_expansion(item, macro_reg))
+        }
+        Value::Map(map) => map.values().any(|v| needs_expansion(v, macro_reg)),
+        _ => false,
+    }
+}
+
+fn expand_tree(expr: Value, macro_reg: &MacroRegistry, env: &EnvRef) -> Value {
+    let expr = match &expr {
+        Value::List(items) if is_macro_call(items, macro_reg) => {
+            match expand_macros(expr.clone(), macro_reg, env) {
+                Ok(expanded) => expanded,
+                Err(_) => return expr,
+            }
+        }
+        _ => expr,
+    };
+
+    match expr {
+        Value::List(mut list) if !list.is_empty() => {
+            let items = Arc::make_mut(&mut list);
+            let form = SpecialForm::of(&items[0]);
+            if let Some(Value::List(spec)) = items.get_mut(1) {
+                for init in binding_inits_mut(form, Arc::make_mut(spec)) {
+                    let code = std::mem::replace(init, Value::Nil);
+                    *init = expand_tree(code, macro_reg, env);
+                }
+            }
+            for code in case_code_mut(items) {
+                let expr = std::mem::replace(code, Value::Nil);
+                *code = expand_tree(expr, macro_reg, env);
+            }
+            let start = code_start(items);
+            for item in items.iter_mut().skip(start) {
+                let code = std::mem::replace(item, Value::Nil);
+                *item = expand_tree(code, macro_reg, env);
+            }
+            Value::List(list)
+        }
+        Value::Map(map) => Value::Map(
+            map.into_iter()
+                .map(|(k, v)| (k, expand_tree(v, macro_reg, env)))
+                .collect(),
+        ),
+        other => other,
+    }
+}
+
+/// Init expressions of a binding form, which are evaluated as code
+///
+/// `let` has a list of `(name init)` pairs; `dotimes` and `dolist` have a single pair.
+fn binding_inits(form: Option<SpecialForm>, spec: &[Value]) -> Vec<&Value> {
+    match form {
+        Some(SpecialForm::Let) => spec
+            .iter()
+            .filter_map(|binding| match binding {
+                Value::List(pair) if pair.len() == 2 => Some(&pair[1]),
+                _ => None,
+            })
+            .collect(),
+        Some(SpecialForm::Dotimes) | Some(SpecialForm::Dolist) if spec.len() == 2 => {
+            vec![&spec[1]]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Mutable version of [`binding_inits`]
+fn binding_inits_mut(form: Option<SpecialForm>, spec: &mut [Value]) -> Vec<&mut Value> {
+    match form {
+        Some(SpecialForm::Let) => spec
+            .iter_mut()
+            .filter_map(|binding| match binding {
+                Value::List(pair) if pair.len() == 2 => Some(&mut Arc::make_mut(pair)[1]),
+                _ => None,
+            })
+            .collect(),
+        Some(SpecialForm::Dotimes) | Some(SpecialForm::Dolist) if spec.len() == 2 => {
+            vec![&mut spec[1]]
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Code in a `case` form: the key, then the body of each clause (the datums are data)
+pub(crate) fn case_code(items: &[Value]) -> Vec<&Value> {
+    if SpecialForm::of(&items[0]) != Some(SpecialForm::Case) {
+        return Vec::new();
+    }
+    let mut code: Vec<&Value> = items.get(1).into_iter().collect();
+    for clause in items.iter().skip(2) {
+        if let Value::List(clause) = clause {
+            code.extend(clause.iter().skip(1));
+        }
+    }
+    code
+}
+
+/// Mutable version of [`case_code`]
+fn case_code_mut(items: &mut [Value]) -> Vec<&mut Value> {
+    if SpecialForm::of(&items[0]) != Some(SpecialForm::Case) {
+        return Vec::new();
+    }
+    let mut code = Vec::new();
+    let mut rest = items.iter_mut().skip(1);
+    code.extend(rest.next());
+    for clause in rest {
+        if let Value::List(clause) = clause {
+            code.extend(Arc::make_mut(clause).iter_mut().skip(1));
+        }
+    }
+    code
+}
+
+/// Sub-expressions of `expr` that are evaluated as code, in source order
+///
+/// Binding inits come first, then the code elements of the form. Atoms and
+/// empty lists have none.
+pub(crate) fn code_children(expr: &Value) -> Vec<&Value> {
+    match expr {
+        Value::List(items) if !items.is_empty() => {
+            let mut children = match items.get(1) {
+                Some(Value::List(spec)) => binding_inits(SpecialForm::of(&items[0]), spec),
+                _ => Vec::new(),
+            };
+            children.extend(case_code(items));
+            children.extend(&items[code_start(items)..]);
+            children
+        }
+        Value::Map(map) => map.values().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn is_macro_call(items: &[Value], macro_reg: &MacroRegistry) -> bool {
+    match items.first() {
+        // Like the evaluator, a macro takes precedence over a special form of the same name
+        Some(Value::Symbol(name)) => macro_reg.contains(name),
+        _ => false,
+    }
+}
+
+/// Index of the first element of a list form that is evaluated as code
+fn code_start(items: &[Value]) -> usize {
+    match SpecialForm::of(&items[0]) {
+        // Nothing inside is evaluated directly; threading steps only become code once rewritten
+        Some(SpecialForm::Quote)
+        | Some(SpecialForm::Quasiquote)
+        | Some(SpecialForm::Defmacro)
+        | Some(SpecialForm::ThreadFirst)
+        | Some(SpecialForm::ThreadLast)
+        | Some(SpecialForm::SomeThread) => items.len(),
+        // The key and clause bodies are found by case_code
+        Some(SpecialForm::Case) => items.len(),
+        // Skip the parameter list, name, or binding list (binding inits are handled separately)
+        Some(SpecialForm::Lambda)
+        | Some(SpecialForm::Define)
+        | Some(SpecialForm::Let)
+        | Some(SpecialForm::Set)
+        | Some(SpecialForm::Defconstant)
+        | Some(SpecialForm::Dotimes)
+        | Some(SpecialForm::Dolist) => 2,
+        Some(_) => 1,
+        // Function calls evaluate the head too
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Environment;
+    use crate::eval::eval_with_macros;
+    use crate::parser::parse;
+
+    /// Environment and registry with `(defmacro twice (x) `(* 2 ,x))` defined
+    fn setup() -> (EnvRef, MacroRegistry) {
+        let env = Environment::new();
+        let macro_reg = MacroRegistry::new();
+        let defmacro = parse("(defmacro twice (x) `(* 2 ,x))").unwrap();
+        eval_with_macros(defmacro, env.clone(), &macro_reg).unwrap();
+        (env, macro_reg)
+    }
+
+    fn expand(source: &str) -> String {
+        let (env, macro_reg) = setup();
+        expand_all(parse(source).unwrap(), &macro_reg, &env)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn test_special_form_resolution() {
+        assert_eq!(SpecialForm::from_name("if"), Some(SpecialForm::If));
+        assert_eq!(SpecialForm::from_name("delay"), Some(SpecialForm::Delay));
+        assert_eq!(SpecialForm::from_name("car"), None);
+        assert_eq!(SpecialForm::of(&Value::Number(1.0)), None);
+    }
+
+    #[test]
+    fn test_expands_nested_macro_calls() {
+        assert_eq!(expand("(+ 1 (twice (twice y)))"), "(+ 1 (* 2 (* 2 y)))");
+    }
+
+    #[test]
+    fn test_expands_inside_special_forms() {
+        assert_eq!(
+            expand("(if (twice a) (begin (twice b)) (lambda (x) (twice x)))"),
+            "(if (* 2 a) (begin (* 2 b)) (lambda (x) (* 2 x)))"
+        );
+        assert_eq!(
+            expand("(let ((v (twice 3))) (twice v))"),
+            "(let ((v (* 2 3))) (* 2 v))"
+        );
+    }
+
+    #[test]
+    fn test_leaves_data_alone() {
+        assert_eq!(expand("(quote (twice 1))"), "(quote (twice 1))");
+        assert_eq!(expand("(lambda (twice) 1)"), "(lambda (twice) 1)");
+    }
+
+    #[test]
+    fn test_failed_expansion_is_kept_for_runtime() {
+        // Wrong arity: the error is reported only if this code runs
+        assert_eq!(expand("(f (twice 1 2))"), "(f (twice 1 2))");
+    }
+}
@@ -7,12 +7,13 @@
 //! - `*`: Product of all arguments (identity: 1)
 //! - `/`: Divide first by subsequent args, or reciprocal if single arg
 //! - `%`: Remainder operation (modulo) - exactly 2 args required
+//! - `divmod`: Quotient and remainder together, as multiple values
 
 use crate::error::{EvalError, ARITY_AT_LEAST_ONE, ARITY_TWO};
 use crate::value::Value;
 use lisp_macros::builtin;
 
-#[builtin(name = "+", category = "Arithmetic", related(-, *, /))]
+#[builtin(name = "+", category = "Arithmetic", related(-, *, /), params(numbers...))]
 /// Returns the sum of all arguments.
 ///
 /// # Examples
@@ -37,7 +38,7 @@ pub fn builtin_add(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Number(sum))
 }
 
-#[builtin(name = "-", category = "Arithmetic", related(+, *, /))]
+#[builtin(name = "-", category = "Arithmetic", related(+, *, /), params(x, more...))]
 /// Subtracts subsequent arguments from the first.
 ///
 /// With one argument, returns its negation.
@@ -76,7 +77,7 @@ pub fn builtin_sub(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Number(result))
 }
 
-#[builtin(name = "*", category = "Arithmetic", related(+, -, /))]
+#[builtin(name = "*", category = "Arithmetic", related(+, -, /), params(numbers...))]
 /// Returns the product of all arguments.
 ///
 /// # Examples
@@ -101,7 +102,7 @@ pub fn builtin_mul(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Number(product))
 }
 
-#[builtin(name = "/", category = "Arithmetic", related(+, -, *, %))]
+#[builtin(name = "/", category = "Arithmetic", related(+, -, *, %), params(x, more...))]
 /// Divides the first argument by subsequent arguments.
 ///
 /// Integer division in Lisp.
@@ -148,7 +149,7 @@ pub fn builtin_div(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Number(result))
 }
 
-#[builtin(name = "%", category = "Arithmetic", related(/))]
+#[builtin(name = "%", category = "Arithmetic", related(/, divmod), params(a, b))]
 /// Returns the remainder when num1 is divided by num2.
 ///
 /// # Examples
@@ -160,7 +161,7 @@ pub fn builtin_div(args: &[Value]) -> Result<Value, EvalError> {
 ///
 /// # See Also
 ///
-/// /
+/// /, divmod
 pub fn builtin_mod(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::arity_error("%", ARITY_TWO, args.len()));
@@ -183,3 +184,44 @@ pub fn builtin_mod(args: &[Value]) -> Result<Value, EvalError> {
 
     Ok(Value::Number(a % b))
 }
+
+#[builtin(name = "divmod", category = "Arithmetic", related(%, values), params(a, b))]
+/// Returns the quotient and remainder of a divided by b, as two values.
+///
+/// The quotient is truncated toward zero and the remainder has the sign of
+/// a, matching `%`, so a = quotient * b + remainder. Bind both with
+/// a list pattern, or pass them on with `call-with-values`.
+///
+/// # Examples
+///
+/// ```lisp
+/// (divmod 17 5) => (values 3 2)
+/// (divmod -7 2) => (values -3 -1)
+/// (let (((q r) (divmod 17 5))) (+ (* q 5) r)) => 17
+/// ```
+///
+/// # See Also
+///
+/// %, values
+pub fn builtin_divmod(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("divmod", ARITY_TWO, args.len()));
+    }
+
+    let a = match &args[0] {
+        Value::Number(n) => *n,
+        _ => return Err(EvalError::type_error("divmod", "number", &args[0], 1)),
+    };
+
+    let b = match &args[1] {
+        Value::Number(n) if *n == 0.0 => {
+            return Err(EvalError::runtime_error("divmod", "division by zero"))
+        }
+        Value::Number(n) => *n,
+        _ => return Err(EvalError::type_error("divmod", "number", &args[1], 2)),
+    };
+
+    Ok(Value::Values(
+        vec![Value::Number((a / b).trunc()), Value::Number(a % b)].into(),
+    ))
+}
@@ -0,0 +1,79 @@
+//! Sandbox audit log: audit-entries
+//!
+//! Every file read/write, metadata lookup and network request that goes through
+//! the sandbox is recorded, including attempts the sandbox denies.
+//!
+//! - `audit-entries`: List the recorded operations, oldest first
+//!
+//! Run with `--audit-log FILE` to also append each entry to FILE as JSON lines.
+
+use crate::builtins::BuiltinContext;
+use crate::error::{EvalError, ARITY_ZERO};
+use crate::sandbox::AuditEntry;
+use crate::value::Value;
+use lisp_macros::builtin;
+use std::collections::BTreeMap;
+
+#[builtin(name = "audit-entries", category = "Audit", related(read-file, http-request), params())]
+/// Returns the sandbox's recorded file and network operations as a list of maps.
+///
+/// Each map has "operation", "target", "outcome" ("ok", "denied" or "error"),
+/// "timestamp" and "duration-ms", plus "method", "status", "bytes" and "error"
+/// when they apply. Only the most recent 10000 entries are kept in memory.
+///
+/// # Examples
+///
+/// ```lisp
+/// (read-file "data/input.txt")
+/// (map-get (car (audit-entries)) "operation") => "read-file"
+/// ```
+///
+/// # See Also
+///
+/// read-file, http-request
+pub fn builtin_audit_entries(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity_error(
+            "audit-entries",
+            ARITY_ZERO,
+            args.len(),
+        ));
+    }
+    let sandbox = ctx.sandbox()?;
+
+    Ok(Value::List(
+        sandbox
+            .audit_entries()
+            .into_iter()
+            .map(entry_to_map)
+            .collect(),
+    ))
+}
+
+fn entry_to_map(entry: AuditEntry) -> Value {
+    let mut map = BTreeMap::new();
+    map.insert("timestamp".to_string(), Value::Number(entry.timestamp));
+    map.insert(
+        "operation".to_string(),
+        Value::String(entry.operation.into()),
+    );
+    map.insert("target".to_string(), Value::String(entry.target.into()));
+    map.insert(
+        "outcome".to_string(),
+        Value::String(entry.outcome.as_str().into()),
+    );
+    map.insert("duration-ms".to_string(), Value::Number(entry.duration_ms));
+    if let Some(method) = entry.method {
+        map.insert("method".to_string(), Value::String(method.into()));
+    }
+    if let Some(bytes) = entry.bytes {
+        map.insert("bytes".to_string(), Value::Number(bytes as f64));
+    }
+    if let Some(status) = entry.status {
+        map.insert("status".to_string(), Value::Number(status as f64));
+    }
+    if let Some(error) = entry.error {
+        map.insert("error".to_string(), Value::String(error.into()));
+    }
+    Value::Map(map)
+}
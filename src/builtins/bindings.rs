@@ -0,0 +1,188 @@
+//! Global bindings: freeze, frozen?, deep-copy, defined?, bindings
+//!
+//! - `freeze`: Stop sandboxed code from changing or redefining global bindings
+//! - `frozen?`: Whether a global binding is frozen
+//! - `deep-copy`: A copy of a value that shares no data with the original
+//! - `defined?`: Whether a global binding exists
+//! - `bindings`: The names of every global binding
+//!
+//! Lists, maps and strings are values, so no builtin changes them in place;
+//! the only way to change data is to rebind a name. Freezing a binding closes
+//! that way: `set!` on it, `define` of its name at top level and a
+//! `load-session` that would replace it all fail. Hosts freeze the data they
+//! provide with `Environment::freeze` (or by evaluating `freeze`) before
+//! running untrusted code; nothing in Lisp unfreezes a binding.
+//!
+//! Hosts that concatenate generated snippets can also turn on a warning for
+//! top-level `define`s that replace an existing binding
+//! (`Interpreter::set_warn_redefinitions`, `--warn-redefine` on the CLI).
+
+use crate::builtins::BuiltinContext;
+use crate::env::Environment;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ZERO};
+use crate::value::Value;
+use lisp_macros::builtin;
+use std::cell::Cell;
+
+thread_local! {
+    /// Whether top-level defines that replace a binding print a warning
+    static WARN_REDEFINITIONS: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Set whether redefinitions are reported on this thread, returning the previous setting
+pub(crate) fn swap_warn_redefinitions(warn: bool) -> bool {
+    WARN_REDEFINITIONS.with(|flag| flag.replace(warn))
+}
+
+/// Print a warning to stderr if warnings are on and `define` is about to replace `name` in `env`
+pub(crate) fn warn_if_redefined(env: &Environment, name: &str) {
+    if WARN_REDEFINITIONS.with(Cell::get) && env.defines(name) {
+        eprintln!("Warning: define replaces the existing binding of {}", name);
+    }
+}
+
+#[builtin(name = "freeze", category = "Bindings", related(frozen?, deep-copy, set!), params(names...))]
+/// Freezes the named global bindings so that set!, define and load-session cannot replace them.
+///
+/// Takes quoted symbols and returns them as a list. Every name must be
+/// defined; a frozen binding stays frozen for the rest of the session.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define limits {:max-rows 100})
+/// (freeze 'limits) => (limits)
+/// (set! limits {}) => error: cannot change frozen binding limits
+/// (define limits {}) => error: cannot redefine frozen binding limits
+/// ```
+///
+/// # See Also
+///
+/// frozen?, deep-copy, set!
+pub fn builtin_freeze(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let env = ctx.env()?;
+    for (i, arg) in args.iter().enumerate() {
+        let name = symbol_arg("freeze", arg, i + 1)?;
+        if !env.freeze(name) {
+            return Err(EvalError::undefined_symbol(name, &env));
+        }
+    }
+    Ok(Value::List(args.into()))
+}
+
+#[builtin(name = "frozen?", category = "Bindings", related(freeze), params(name))]
+/// Returns #t if the named global binding is frozen, #f otherwise.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define limits {:max-rows 100})
+/// (frozen? 'limits) => #f
+/// (freeze 'limits)
+/// (frozen? 'limits) => #t
+/// ```
+///
+/// # See Also
+///
+/// freeze
+pub fn builtin_frozen_p(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("frozen?", ARITY_ONE, args.len()));
+    }
+    let name = symbol_arg("frozen?", &args[0], 1)?;
+    Ok(Value::Bool(ctx.env()?.is_frozen(name)))
+}
+
+#[builtin(
+    name = "deep-copy",
+    category = "Bindings",
+    related(freeze),
+    params(value)
+)]
+/// Returns a copy of a value whose lists, maps and strings are all new.
+///
+/// Functions, promises, threads and channels are handles and are shared by
+/// the copy. Since no builtin changes data in place, a copy is only needed to
+/// hand a value to code that should not share it with the original's owner.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define original '(1 (2 3) {:a 4}))
+/// (define copy (deep-copy original))
+/// (= copy original) => #t
+/// ```
+///
+/// # See Also
+///
+/// freeze
+pub fn builtin_deep_copy(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("deep-copy", ARITY_ONE, args.len()));
+    }
+    // Cloning copies every list, map and string; only handles are shared
+    Ok(args[0].clone())
+}
+
+#[builtin(name = "defined?", category = "Bindings", related(bindings, frozen?), params(name))]
+/// Returns #t if the named symbol has a global binding, #f otherwise.
+///
+/// Local variables are not seen; only top-level definitions, builtins and
+/// stdlib functions count.
+///
+/// # Examples
+///
+/// ```lisp
+/// (defined? 'map) => #t
+/// (defined? 'helper) => #f
+/// (define (helper x) x)
+/// (defined? 'helper) => #t
+/// ```
+///
+/// # See Also
+///
+/// bindings, frozen?
+pub fn builtin_defined_p(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("defined?", ARITY_ONE, args.len()));
+    }
+    let name = symbol_arg("defined?", &args[0], 1)?;
+    Ok(Value::Bool(ctx.env()?.defines(name)))
+}
+
+#[builtin(name = "bindings", category = "Bindings", related(defined?, apropos), params())]
+/// Returns the names of every global binding as a sorted list of symbols.
+///
+/// Builtins and stdlib functions are included; use `apropos` or
+/// `help-search` to narrow the list down by name or description.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define total 0)
+/// (filter (lambda (name) (= name 'total)) (bindings)) => (total)
+/// ```
+///
+/// # See Also
+///
+/// defined?, apropos
+pub fn builtin_bindings(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity_error("bindings", ARITY_ZERO, args.len()));
+    }
+    let mut names: Vec<_> = ctx
+        .env()?
+        .bindings()
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    names.sort();
+    Ok(Value::List(names.into_iter().map(Value::Symbol).collect()))
+}
+
+fn symbol_arg<'a>(function: &str, arg: &'a Value, position: usize) -> Result<&'a str, EvalError> {
+    match arg {
+        Value::Symbol(name) => Ok(name),
+        other => Err(EvalError::type_error(function, "symbol", other, position)),
+    }
+}
@@ -14,8 +14,8 @@ use crate::error::{EvalError, ARITY_TWO};
 use crate::value::Value;
 use lisp_macros::builtin;
 
-#[builtin(name = "=", category = "Comparison", related(<, >, <=, >=))]
-/// Tests if all arguments are equal. Works with numbers, strings, symbols.
+#[builtin(name = "=", category = "Comparison", related(<, >, <=, >=), params(a, b))]
+/// Tests if all arguments are equal. Works with numbers, decimals, strings, symbols, keywords.
 ///
 /// # Examples
 ///
@@ -23,6 +23,7 @@ use lisp_macros::builtin;
 /// (= 5 5) => #t
 /// (= 5 6) => #f
 /// (= "hello" "hello") => #t
+/// (= :ok :ok) => #t
 /// ```
 ///
 /// # See Also
@@ -35,9 +36,11 @@ pub fn builtin_eq(args: &[Value]) -> Result<Value, EvalError> {
 
     let result = match (&args[0], &args[1]) {
         (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::Decimal(a), Value::Decimal(b)) => a == b,
         (Value::Bool(a), Value::Bool(b)) => a == b,
         (Value::String(a), Value::String(b)) => a == b,
         (Value::Symbol(a), Value::Symbol(b)) => a == b,
+        (Value::Keyword(a), Value::Keyword(b)) => a == b,
         (Value::Nil, Value::Nil) => true,
         _ => false,
     };
@@ -45,7 +48,7 @@ pub fn builtin_eq(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(result))
 }
 
-#[builtin(name = "<", category = "Comparison", related(>, <=, >=, =))]
+#[builtin(name = "<", category = "Comparison", related(>, <=, >=, =), params(a, b))]
 /// Tests if each argument is strictly less than the next.
 ///
 /// # Examples
@@ -77,7 +80,7 @@ pub fn builtin_lt(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(a < b))
 }
 
-#[builtin(name = ">", category = "Comparison", related(<, <=, >=, =))]
+#[builtin(name = ">", category = "Comparison", related(<, <=, >=, =), params(a, b))]
 /// Tests if each argument is strictly greater than the next.
 ///
 /// # Examples
@@ -108,7 +111,7 @@ pub fn builtin_gt(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(a > b))
 }
 
-#[builtin(name = "<=", category = "Comparison", related(<, >, >=, =))]
+#[builtin(name = "<=", category = "Comparison", related(<, >, >=, =), params(a, b))]
 /// Tests if each argument is less than or equal to the next.
 ///
 /// # Examples
@@ -139,7 +142,7 @@ pub fn builtin_le(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(a <= b))
 }
 
-#[builtin(name = ">=", category = "Comparison", related(<, >, <=, =))]
+#[builtin(name = ">=", category = "Comparison", related(<, >, <=, =), params(a, b))]
 /// Tests if each argument is greater than or equal to the next.
 ///
 /// # Examples
@@ -0,0 +1,341 @@
+//! Concurrency operations: spawn, join, make-channel, channel-send, channel-recv
+//!
+//! Threads are real OS threads running Lisp functions in parallel.
+//!
+//! - `spawn`: Run a zero-argument function on a new thread
+//! - `join`: Wait for a spawned thread and return its result
+//! - `make-channel`: Create a queue for passing values between threads
+//! - `channel-send`: Put a value on a channel
+//! - `channel-recv`: Take the next value from a channel, optionally with a timeout
+//!
+//! Spawned threads share the sandbox, macros and cancellation token of the code
+//! that spawned them, so interrupting an evaluation also stops its threads.
+//! Closures keep sharing their captured environment, so a `define` made on one
+//! thread is visible to the others.
+
+use crate::builtins::{current_sandbox, swap_sandbox, BuiltinContext};
+use crate::env::Environment;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ONE_OR_TWO, ARITY_TWO, ARITY_ZERO};
+use crate::eval::{active_cancellation, eval_with_macros, swap_cancellation};
+use crate::value::{Channel, ErrorValue, Task, Value};
+use lisp_macros::builtin;
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+#[builtin(name = "spawn", capability = "concurrency", category = "Concurrency", related(join, make-channel), params(thunk))]
+/// Runs a zero-argument function on a new thread and returns a thread handle.
+///
+/// The thread starts immediately. Use join to wait for its result.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define t (spawn (lambda () (* 6 7))))
+/// (join t) => 42
+/// ```
+///
+/// # See Also
+///
+/// join, make-channel
+pub fn builtin_spawn(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("spawn", ARITY_ONE, args.len()));
+    }
+
+    let thunk = match &args[0] {
+        Value::Lambda {
+            params, defaults, ..
+        } if params.len() == defaults.len() => args[0].clone(),
+        Value::BuiltIn(_) => args[0].clone(),
+        other => {
+            return Err(EvalError::type_error(
+                "spawn",
+                "function of no arguments",
+                other,
+                1,
+            ))
+        }
+    };
+
+    let sandbox = current_sandbox();
+    let cancel = active_cancellation();
+    let macros = ctx.macros();
+    let (sender, receiver) = mpsc::channel();
+
+    std::thread::Builder::new()
+        .name("lisp-spawn".to_string())
+        .stack_size(crate::cancel::EVAL_STACK_SIZE)
+        .spawn(move || {
+            swap_sandbox(sandbox);
+            swap_cancellation(cancel);
+            let call = Value::List(vec![thunk].into());
+            let result = eval_with_macros(call, Environment::new(), &macros).map_err(|e| {
+                // A stray break must not escape into a loop on the joining thread
+                match e {
+                    EvalError::Break(_) | EvalError::Continue => {
+                        EvalError::runtime_error("spawn", e.to_string())
+                    }
+                    other => other,
+                }
+            });
+            // The joiner may have given up; nobody is left to tell
+            let _ = sender.send(result);
+        })
+        .map_err(|e| EvalError::runtime_error("spawn", format!("cannot start thread: {}", e)))?;
+
+    Ok(Value::Thread(Arc::new(Mutex::new(Task::Running(receiver)))))
+}
+
+/// The error value returned when a timed wait runs out
+fn timed_out(function: &str) -> Value {
+    Value::Error(Box::new(ErrorValue::new(
+        "timeout",
+        format!("{}: timed out", function),
+    )))
+}
+
+#[builtin(name = "join", capability = "concurrency", category = "Concurrency", related(spawn), params(thread, [timeout-ms]))]
+/// Waits for a spawned thread to finish and returns its result.
+///
+/// An error raised by the thread is raised again by join. With a timeout in
+/// milliseconds, returns an error value if the thread has not finished in time;
+/// the thread keeps running and can be joined again later.
+///
+/// # Examples
+///
+/// ```lisp
+/// (join (spawn (lambda () "done"))) => "done"
+/// (define ch (make-channel))
+/// (error? (join (spawn (lambda () (channel-recv ch))) 10)) => #t
+/// ```
+///
+/// # See Also
+///
+/// spawn
+pub fn builtin_join(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error("join", ARITY_ONE_OR_TWO, args.len()));
+    }
+
+    let task = match &args[0] {
+        Value::Thread(task) => task,
+        other => return Err(EvalError::type_error("join", "thread", other, 1)),
+    };
+    let timeout = timeout_arg("join", args.get(1))?;
+
+    let mut state = task.lock().unwrap_or_else(|e| e.into_inner());
+    let result = match &*state {
+        Task::Finished(result) => return result.clone(),
+        Task::Running(receiver) => match timeout {
+            Some(timeout) => match receiver.recv_timeout(timeout) {
+                Ok(result) => result,
+                Err(RecvTimeoutError::Timeout) => return Ok(timed_out("join")),
+                Err(RecvTimeoutError::Disconnected) => Err(thread_died()),
+            },
+            None => receiver.recv().unwrap_or_else(|_| Err(thread_died())),
+        },
+    };
+
+    *state = Task::Finished(result.clone());
+    result
+}
+
+#[builtin(name = "make-channel", capability = "concurrency", category = "Concurrency", related(channel-send, channel-recv), params())]
+/// Creates a channel for passing values between threads.
+///
+/// Any number of threads may send to and receive from the same channel.
+/// Values arrive in the order they were sent.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define ch (make-channel))
+/// (spawn (lambda () (channel-send ch 42)))
+/// (channel-recv ch) => 42
+/// ```
+///
+/// # See Also
+///
+/// channel-send, channel-recv
+pub fn builtin_make_channel(args: &[Value]) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity_error(
+            "make-channel",
+            ARITY_ZERO,
+            args.len(),
+        ));
+    }
+    Ok(Value::Channel(Channel::new()))
+}
+
+#[builtin(name = "channel-send", capability = "concurrency", category = "Concurrency", related(channel-recv, make-channel), params(channel, value))]
+/// Puts a value on a channel without waiting. Returns #t.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define ch (make-channel))
+/// (channel-send ch "hello") => #t
+/// (channel-recv ch) => "hello"
+/// ```
+///
+/// # See Also
+///
+/// channel-recv, make-channel
+pub fn builtin_channel_send(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "channel-send",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+
+    let channel = match &args[0] {
+        Value::Channel(channel) => channel,
+        other => return Err(EvalError::type_error("channel-send", "channel", other, 1)),
+    };
+
+    // The channel value holds a receiver, so the queue cannot be disconnected
+    channel
+        .sender
+        .send(args[1].clone())
+        .map_err(|_| EvalError::runtime_error("channel-send", "channel is closed"))?;
+    Ok(Value::Bool(true))
+}
+
+#[builtin(name = "channel-recv", capability = "concurrency", category = "Concurrency", related(channel-send, make-channel), params(channel, [timeout-ms]))]
+/// Takes the next value from a channel, waiting until one is sent.
+///
+/// With a timeout in milliseconds, returns an error value if nothing arrives in time.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define ch (make-channel))
+/// (channel-send ch 1)
+/// (channel-recv ch) => 1
+/// (error? (channel-recv ch 10)) => #t
+/// ```
+///
+/// # See Also
+///
+/// channel-send, make-channel
+pub fn builtin_channel_recv(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "channel-recv",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+
+    let channel = match &args[0] {
+        Value::Channel(channel) => channel,
+        other => return Err(EvalError::type_error("channel-recv", "channel", other, 1)),
+    };
+    let timeout = timeout_arg("channel-recv", args.get(1))?;
+
+    let receiver = channel.receiver.lock().unwrap_or_else(|e| e.into_inner());
+    match timeout {
+        Some(timeout) => match receiver.recv_timeout(timeout) {
+            Ok(value) => Ok(value),
+            Err(RecvTimeoutError::Timeout) => Ok(timed_out("channel-recv")),
+            Err(RecvTimeoutError::Disconnected) => Err(EvalError::runtime_error(
+                "channel-recv",
+                "channel is closed",
+            )),
+        },
+        None => receiver
+            .recv()
+            .map_err(|_| EvalError::runtime_error("channel-recv", "channel is closed")),
+    }
+}
+
+/// Parse an optional timeout argument given in milliseconds
+fn timeout_arg(function: &str, arg: Option<&Value>) -> Result<Option<Duration>, EvalError> {
+    match arg {
+        None => Ok(None),
+        Some(Value::Number(ms)) if *ms >= 0.0 => Ok(Some(Duration::from_secs_f64(ms / 1000.0))),
+        Some(other) => Err(EvalError::type_error(
+            function,
+            "non-negative number (milliseconds)",
+            other,
+            2,
+        )),
+    }
+}
+
+fn thread_died() -> EvalError {
+    EvalError::runtime_error("join", "thread panicked before returning a value")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thunk(body: Value) -> Value {
+        Value::Lambda {
+            params: vec![],
+            defaults: vec![],
+            body: Arc::new(body),
+            env: Environment::new(),
+            docstring: None,
+            source: None,
+        }
+    }
+
+    #[test]
+    fn test_spawn_and_join() {
+        let handle =
+            builtin_spawn(&[thunk(Value::Number(42.0))], &BuiltinContext::new("spawn")).unwrap();
+        assert!(
+            matches!(builtin_join(std::slice::from_ref(&handle)), Ok(Value::Number(n)) if n == 42.0)
+        );
+        // Joining again returns the stored result
+        assert!(matches!(builtin_join(&[handle]), Ok(Value::Number(n)) if n == 42.0));
+    }
+
+    #[test]
+    fn test_spawn_rejects_functions_with_parameters() {
+        let lambda = Value::Lambda {
+            params: vec!["x".into()],
+            defaults: vec![],
+            body: Arc::new(Value::Nil),
+            env: Environment::new(),
+            docstring: None,
+            source: None,
+        };
+        assert!(builtin_spawn(&[lambda], &BuiltinContext::new("spawn")).is_err());
+        assert!(builtin_spawn(&[Value::Number(1.0)], &BuiltinContext::new("spawn")).is_err());
+    }
+
+    #[test]
+    fn test_channel_send_and_recv() {
+        let ch = builtin_make_channel(&[]).unwrap();
+        builtin_channel_send(&[ch.clone(), Value::Number(1.0)]).unwrap();
+        builtin_channel_send(&[ch.clone(), Value::Number(2.0)]).unwrap();
+        assert!(
+            matches!(builtin_channel_recv(std::slice::from_ref(&ch)), Ok(Value::Number(n)) if n == 1.0)
+        );
+        assert!(
+            matches!(builtin_channel_recv(std::slice::from_ref(&ch)), Ok(Value::Number(n)) if n == 2.0)
+        );
+    }
+
+    #[test]
+    fn test_channel_recv_timeout_returns_error_value() {
+        let ch = builtin_make_channel(&[]).unwrap();
+        let result = builtin_channel_recv(&[ch, Value::Number(5.0)]).unwrap();
+        assert!(matches!(result, Value::Error(err)
+            if err.message.contains("timed out") && err.kind == "timeout"));
+    }
+
+    #[test]
+    fn test_timeout_must_be_non_negative_number() {
+        let ch = builtin_make_channel(&[]).unwrap();
+        assert!(builtin_channel_recv(&[ch.clone(), Value::Number(-1.0)]).is_err());
+        assert!(builtin_channel_recv(&[ch, Value::String("1".into())]).is_err());
+    }
+}
@@ -1,17 +1,67 @@
-//! Console I/O operations: print, println
+//! Console I/O operations: print, println, pp
 //!
 //! Functions for output to standard output.
 //!
 //! - `print`: Output value without newline
 //! - `println`: Output value with trailing newline
+//! - `pp`: Pretty-print one value, wrapped to a line width
 //!
-//! Both return nil
+//! All return nil. `print` and `println` show strings as their raw
+//! characters, also inside lists and maps; `pp` and the REPL quote them like
+//! source code. Both styles come from [`crate::value::write_value`].
+//!
+//! Output goes to stdout unless a host has installed a capture buffer for the
+//! current evaluation (see [`crate::interpreter::Interpreter::capture_output`]).
+//! Either way, a host's [`crate::interpreter::Interpreter::on_stdout`] callback
+//! sees each chunk as it is written.
 
-use crate::error::EvalError;
-use crate::value::Value;
+use crate::error::{EvalError, ARITY_AT_LEAST_ONE};
+use crate::pretty::{pretty_print_limited, print_limits, DEFAULT_WIDTH};
+use crate::value::{write_value, PrintLimits, PrintStyle, Value};
 use lisp_macros::builtin;
+use std::cell::RefCell;
+use std::io::Write;
+
+thread_local! {
+    /// Buffer receiving console output instead of stdout, if a host installed one
+    static CAPTURE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Replace this thread's capture buffer, returning the previous one
+pub(crate) fn swap_capture(buffer: Option<String>) -> Option<String> {
+    CAPTURE.with(|capture| capture.replace(buffer))
+}
 
-#[builtin(name = "print", category = "Console I/O", related(println))]
+/// Write console output to the capture buffer if one is installed, otherwise stdout
+pub fn write_output(text: &str) {
+    crate::events::stdout(text);
+    let captured = CAPTURE.with(|capture| match capture.borrow_mut().as_mut() {
+        Some(buffer) => {
+            buffer.push_str(text);
+            true
+        }
+        None => false,
+    });
+    if !captured {
+        print!("{}", text);
+        let _ = std::io::stdout().flush();
+    }
+}
+
+/// Values separated by spaces, with strings unquoted
+fn format_args(args: &[Value]) -> String {
+    let mut out = String::new();
+    for (i, arg) in args.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        // Writing to a String cannot fail
+        let _ = write_value(&mut out, arg, PrintStyle::Display);
+    }
+    out
+}
+
+#[builtin(name = "print", capability = "console", category = "Console I/O", related(println), params(values...))]
 /// Prints values to stdout without newline. Returns nil.
 ///
 /// # Examples
@@ -19,25 +69,18 @@ use lisp_macros::builtin;
 /// ```lisp
 /// (print "hello") => outputs: hello
 /// (print 1 2 3) => outputs: 1 2 3
+/// (print '("a" b)) => outputs: (a b)
 /// ```
 ///
 /// # See Also
 ///
 /// println
 pub fn builtin_print(args: &[Value]) -> Result<Value, EvalError> {
-    for (i, arg) in args.iter().enumerate() {
-        if i > 0 {
-            print!(" ");
-        }
-        match arg {
-            Value::String(s) => print!("{}", s),
-            other => print!("{}", other),
-        }
-    }
+    write_output(&format_args(args));
     Ok(Value::Nil)
 }
 
-#[builtin(name = "println", category = "Console I/O", related(print))]
+#[builtin(name = "println", capability = "console", category = "Console I/O", related(print), params(values...))]
 /// Prints values to stdout with newline at end. Returns nil.
 ///
 /// # Examples
@@ -51,15 +94,91 @@ pub fn builtin_print(args: &[Value]) -> Result<Value, EvalError> {
 ///
 /// print
 pub fn builtin_println(args: &[Value]) -> Result<Value, EvalError> {
-    for (i, arg) in args.iter().enumerate() {
-        if i > 0 {
-            print!(" ");
+    write_output(&format!("{}\n", format_args(args)));
+    Ok(Value::Nil)
+}
+
+#[builtin(name = "pp", capability = "console", category = "Console I/O", related(println), params(value, [width], options...))]
+/// Pretty-prints a value over several indented lines when it is wider than width (default 80). Returns nil.
+///
+/// Values that fit are printed on one line, like println. Long lists and maps
+/// are broken one element per line; code-like lists keep their first argument
+/// next to the head. Lists and maps show at most `*print-length*` elements
+/// each, followed by `... (N more)`; `:full #t` prints everything.
+///
+/// # Examples
+///
+/// ```lisp
+/// (pp '(1 2 3)) => outputs: (1 2 3)
+/// (set! *print-length* 2)
+/// (pp '(1 2 3)) => outputs: (1 2 ... (1 more))
+/// (pp '(1 2 3) :full #t) => outputs: (1 2 3)
+/// (pp '(define (f x) (if (> x 0) x (- x))) 20)
+/// ; outputs:
+/// ; (define (f x)
+/// ;         (if (> x 0)
+/// ;             x
+/// ;             (- x)))
+/// ```
+///
+/// # See Also
+///
+/// println
+pub fn builtin_pp(args: &[Value]) -> Result<Value, EvalError> {
+    let (value, rest) = match args.split_first() {
+        Some(split) => split,
+        None => return Err(EvalError::arity_error("pp", ARITY_AT_LEAST_ONE, 0)),
+    };
+    let (width, options) = match rest {
+        [Value::Number(n), options @ ..] if *n >= 1.0 => (*n as usize, options),
+        [Value::Number(_), ..] => {
+            return Err(EvalError::type_error("pp", "positive number", &rest[0], 2))
         }
-        match arg {
-            Value::String(s) => print!("{}", s),
-            other => print!("{}", other),
+        options => (DEFAULT_WIDTH, options),
+    };
+    let offset = args.len() - options.len();
+
+    let mut full = false;
+    for (i, pair) in options.chunks(2).enumerate() {
+        let position = offset + 2 * i + 1;
+        match pair {
+            [Value::Keyword(key), Value::Bool(b)] if key == "full" => full = *b,
+            [Value::Keyword(key), _] if key == "full" => {
+                return Err(EvalError::type_error(
+                    "pp",
+                    "boolean",
+                    &pair[1],
+                    position + 1,
+                ))
+            }
+            [Value::Keyword(key), _] => {
+                return Err(EvalError::runtime_error(
+                    "pp",
+                    format!("unknown option :{} (expected :full)", key),
+                ))
+            }
+            [Value::Keyword(key)] => {
+                return Err(EvalError::runtime_error(
+                    "pp",
+                    format!("missing value for :{}", key),
+                ))
+            }
+            [other] | [other, _] => {
+                return Err(EvalError::type_error(
+                    "pp",
+                    "option keyword",
+                    other,
+                    position,
+                ))
+            }
+            _ => {}
         }
     }
-    println!();
+
+    let limits = match crate::help::current_env() {
+        Some(env) if !full => print_limits(&env),
+        _ => PrintLimits::default(),
+    };
+    write_output(&format!("{}\n", pretty_print_limited(value, width, limits)));
     Ok(Value::Nil)
 }
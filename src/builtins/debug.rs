@@ -0,0 +1,355 @@
+//! Debugging: trace, untrace, trace-macro, untrace-macro, breakpoint
+//!
+//! - `trace`: Print every call of the named functions with its arguments and result
+//! - `untrace`: Stop tracing functions
+//! - `trace-macro`: Print every expansion step of the named macros
+//! - `untrace-macro`: Stop tracing macros
+//! - `breakpoint`: Pause in the step debugger, when the host attached one
+//!
+//! Traced functions are remembered by identity, not by name: redefining a
+//! traced function gives an untraced one. A traced lambda call is not a tail
+//! call, because its result has to be printed when it returns. Macros are
+//! traced by name, and the host can trace all of them (`--trace-macros`).
+
+use crate::builtins::console::write_output;
+use crate::builtins::BuiltinContext;
+use crate::error::{EvalError, ARITY_ZERO};
+use crate::value::Value;
+use lisp_macros::builtin;
+use std::cell::{Cell, RefCell};
+use std::sync::Arc;
+
+type BuiltinFn = fn(&[Value]) -> Result<Value, EvalError>;
+
+// ============================================================================
+// Traced Functions
+// ============================================================================
+
+/// Functions whose calls are printed, keyed by identity
+#[derive(Debug, Default)]
+pub(crate) struct TraceSet {
+    /// Name of each traced lambda, by the address of its body
+    lambdas: Vec<(usize, String)>,
+    builtins: Vec<(BuiltinFn, String)>,
+    /// Macros whose expansions are printed
+    macros: Vec<String>,
+    /// Print the expansions of every macro
+    all_macros: bool,
+}
+
+impl TraceSet {
+    fn name_of(&self, func: &Value) -> Option<&str> {
+        match func {
+            Value::Lambda { body, .. } => {
+                let key = Arc::as_ptr(body) as usize;
+                self.lambdas
+                    .iter()
+                    .find(|(traced, _)| *traced == key)
+                    .map(|(_, name)| name.as_str())
+            }
+            Value::BuiltIn(f) => self
+                .builtins
+                .iter()
+                .find(|(traced, _)| std::ptr::fn_addr_eq(*traced, *f))
+                .map(|(_, name)| name.as_str()),
+            _ => None,
+        }
+    }
+
+    fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self
+            .lambdas
+            .iter()
+            .map(|(_, name)| name.clone())
+            .chain(self.builtins.iter().map(|(_, name)| name.clone()))
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn remove(&mut self, name: &str) {
+        self.lambdas.retain(|(_, traced)| traced != name);
+        self.builtins.retain(|(_, traced)| traced != name);
+    }
+
+    /// Print the expansions of every macro, or only the ones traced by name
+    pub(crate) fn set_all_macros(&mut self, all: bool) {
+        self.all_macros = all;
+    }
+}
+
+thread_local! {
+    static TRACED: RefCell<TraceSet> = RefCell::new(TraceSet::default());
+    /// How many traced calls are running, for indenting nested ones
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Replace this thread's traced functions, returning the previous set
+pub(crate) fn swap_traced(traced: TraceSet) -> TraceSet {
+    TRACED.with(|set| std::mem::replace(&mut *set.borrow_mut(), traced))
+}
+
+/// A traced call in progress: the call line is printed on entry, the result by [`TraceCall::finish`]
+pub(crate) struct TraceCall {
+    name: String,
+    depth: usize,
+}
+
+impl TraceCall {
+    /// Start a call of `func` if it is traced, printing its arguments
+    pub(crate) fn enter(func: &Value, args: &[Value]) -> Option<Self> {
+        let name = TRACED.with(|set| {
+            let set = set.borrow();
+            if set.lambdas.is_empty() && set.builtins.is_empty() {
+                return None;
+            }
+            set.name_of(func).map(str::to_string)
+        })?;
+
+        let depth = DEPTH.with(|depth| depth.replace(depth.get() + 1));
+        let call: Vec<String> = std::iter::once(name.clone())
+            .chain(args.iter().map(|arg| arg.to_string()))
+            .collect();
+        write_output(&format!("{}({})\n", indent(depth), call.join(" ")));
+        Some(TraceCall { name, depth })
+    }
+
+    /// Print the call's result or error and pass it on
+    pub(crate) fn finish(self, result: Result<Value, EvalError>) -> Result<Value, EvalError> {
+        DEPTH.with(|depth| depth.set(self.depth));
+        let outcome = match &result {
+            Ok(value) => format!("=> {}", value),
+            Err(e) => format!("!! {}", e),
+        };
+        write_output(&format!(
+            "{}{} {}\n",
+            indent(self.depth),
+            self.name,
+            outcome
+        ));
+        result
+    }
+}
+
+/// Print one expansion step of a macro call if the macro is traced
+///
+/// `depth` is the number of expansions already in progress, so the steps of
+/// a macro expanding into another macro call line up under each other.
+pub(crate) fn trace_expansion(name: &str, call: &Value, expansion: &Value, depth: usize) {
+    let traced = TRACED.with(|set| {
+        let set = set.borrow();
+        set.all_macros || set.macros.iter().any(|traced| traced == name)
+    });
+    if traced {
+        write_output(&format!("{}{} ==> {}\n", indent(depth), call, expansion));
+    }
+}
+
+fn indent(depth: usize) -> String {
+    "| ".repeat(depth)
+}
+
+/// The symbols in `args`, for trace and untrace
+fn symbol_names(function: &str, args: &[Value]) -> Result<Vec<String>, EvalError> {
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| match arg {
+            Value::Symbol(name) => Ok(name.to_string()),
+            other => Err(EvalError::type_error(function, "symbol", other, i + 1)),
+        })
+        .collect()
+}
+
+fn traced_list() -> Value {
+    let names = TRACED.with(|set| set.borrow().names());
+    Value::List(
+        names
+            .into_iter()
+            .map(|name| Value::Symbol(name.into()))
+            .collect(),
+    )
+}
+
+#[builtin(name = "trace", category = "Debugging", related(untrace), params(names...))]
+/// Print every call of the named functions, with arguments and result, indented by nesting depth.
+///
+/// Takes quoted symbols naming global functions (lambdas or builtins) and
+/// returns the list of traced names. With no arguments, only returns the
+/// list. Redefining a traced function stops tracing it.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+/// (trace 'fact) => (fact)
+/// (fact 2) => 2
+/// ; prints (fact 2)
+/// ;        | (fact 1)
+/// ;        | fact => 1
+/// ;        fact => 2
+/// ```
+///
+/// # See Also
+///
+/// untrace
+pub fn builtin_trace(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let names = symbol_names("trace", args)?;
+    let env = ctx.env()?;
+
+    for (i, name) in names.into_iter().enumerate() {
+        let func = env
+            .get(&name)
+            .ok_or_else(|| EvalError::undefined_symbol(&name, &env))?;
+        TRACED.with(|set| {
+            let mut set = set.borrow_mut();
+            set.remove(&name);
+            match &func {
+                Value::Lambda { body, .. } => {
+                    set.lambdas.push((Arc::as_ptr(body) as usize, name));
+                    Ok(())
+                }
+                Value::BuiltIn(f) => {
+                    set.builtins.push((*f, name));
+                    Ok(())
+                }
+                other => Err(EvalError::type_error("trace", "function", other, i + 1)),
+            }
+        })?;
+    }
+    Ok(traced_list())
+}
+
+#[builtin(name = "untrace", category = "Debugging", related(trace), params(names...))]
+/// Stop tracing the named functions, or every function when called with no arguments.
+///
+/// Returns the list of functions still traced.
+///
+/// # Examples
+///
+/// ```lisp
+/// (trace 'fact 'car) => (car fact)
+/// (untrace 'car) => (fact)
+/// (untrace) => ()
+/// ```
+///
+/// # See Also
+///
+/// trace
+pub fn builtin_untrace(args: &[Value]) -> Result<Value, EvalError> {
+    let names = symbol_names("untrace", args)?;
+    TRACED.with(|set| {
+        let mut set = set.borrow_mut();
+        if names.is_empty() {
+            set.lambdas.clear();
+            set.builtins.clear();
+        }
+        for name in &names {
+            set.remove(name);
+        }
+    });
+    Ok(traced_list())
+}
+
+fn traced_macro_list() -> Value {
+    let mut names = TRACED.with(|set| set.borrow().macros.clone());
+    names.sort();
+    Value::List(
+        names
+            .into_iter()
+            .map(|name| Value::Symbol(name.into()))
+            .collect(),
+    )
+}
+
+#[builtin(name = "trace-macro", category = "Debugging", related(untrace-macro, trace), params(names...))]
+/// Print every expansion step of the named macros, indented by nesting depth.
+///
+/// Takes quoted symbols naming defined macros and returns the list of traced
+/// macros. With no arguments, only returns the list. Each step prints the
+/// macro call and the code it expanded into.
+///
+/// # Examples
+///
+/// ```lisp
+/// (defmacro unless (c e) `(if ,c nil ,e))
+/// (trace-macro 'unless) => (unless)
+/// (unless #f 1) => 1
+/// ; prints (unless #f 1) ==> (if #f nil 1)
+/// ```
+///
+/// # See Also
+///
+/// untrace-macro, trace
+pub fn builtin_trace_macro(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let names = symbol_names("trace-macro", args)?;
+    let macros = ctx.macros();
+    for name in names {
+        if !macros.contains(&name) {
+            return Err(EvalError::runtime_error(
+                "trace-macro",
+                format!("no macro named {}", name),
+            ));
+        }
+        TRACED.with(|set| {
+            let mut set = set.borrow_mut();
+            if !set.macros.contains(&name) {
+                set.macros.push(name);
+            }
+        });
+    }
+    Ok(traced_macro_list())
+}
+
+#[builtin(name = "untrace-macro", category = "Debugging", related(trace-macro), params(names...))]
+/// Stop tracing the named macros, or every macro when called with no arguments.
+///
+/// Returns the list of macros still traced.
+///
+/// # Examples
+///
+/// ```lisp
+/// (trace-macro 'unless 'when) => (unless when)
+/// (untrace-macro 'when) => (unless)
+/// (untrace-macro) => ()
+/// ```
+///
+/// # See Also
+///
+/// trace-macro
+pub fn builtin_untrace_macro(args: &[Value]) -> Result<Value, EvalError> {
+    let names = symbol_names("untrace-macro", args)?;
+    TRACED.with(|set| {
+        let mut set = set.borrow_mut();
+        if names.is_empty() {
+            set.macros.clear();
+        }
+        set.macros.retain(|traced| !names.contains(traced));
+    });
+    Ok(traced_macro_list())
+}
+
+#[builtin(name = "breakpoint", category = "Debugging", related(trace), params())]
+/// Pause in the step debugger before the next form is evaluated.
+///
+/// At the `debug>` prompt, `step` enters the next form, `next` steps over it,
+/// `continue` runs to the next breakpoint, `locals` shows the bindings in
+/// scope and `abort` stops the evaluation. Returns nil, and does nothing when
+/// no debugger is attached (for example in server modes).
+///
+/// # Examples
+///
+/// ```lisp
+/// (define (f x) (breakpoint) (* x 2))
+/// (f 21) => 42 ; pauses before (* x 2) with x = 21
+/// ```
+///
+/// # See Also
+///
+/// trace
+pub fn builtin_breakpoint(args: &[Value]) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity_error("breakpoint", ARITY_ZERO, args.len()));
+    }
+    crate::debugger::request_pause();
+    Ok(Value::Nil)
+}
@@ -1,54 +1,88 @@
-//! Error handling operations: error, error?, error-msg
+//! Error handling operations: error, error?, error-msg, error-kind, error-data
 //!
 //! Functions for working with catchable error values.
 //!
-//! - `error`: Create an error value with message
+//! - `error`: Create an error value with a message, or a kind keyword and optional data map
 //! - `error?`: Test if value is an error
 //! - `error-msg`: Extract error message from error value
+//! - `error-kind`: Extract the kind keyword from an error value
+//! - `error-data`: Extract the data map from an error value
 //!
-//! Errors are first-class values, not exceptions, enabling graceful error handling
+//! Errors are first-class values, not exceptions, enabling graceful error handling.
+//! The `try` special form turns a raised error into one of these values; its
+//! kind (`:not-found`, `:type-error`, ...) says what went wrong.
 
-use crate::error::{EvalError, ARITY_ONE};
-use crate::value::Value;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ONE_TO_THREE};
+use crate::value::{ErrorValue, Value};
 use lisp_macros::builtin;
 
-#[builtin(name = "error", category = "Error handling", related(error?, error-msg))]
-/// Raises an error with the given message. Always throws.
+#[builtin(name = "error", category = "Error handling", related(error?, error-msg, error-kind, error-data, try), params(kind-or-message, [message], [data]))]
+/// Creates an error value from a message, or from a kind keyword with an optional message and data map.
+///
+/// Errors made from just a message have kind :error. With a kind and no
+/// message, the kind's name is the message.
 ///
 /// # Examples
 ///
 /// ```lisp
-/// (error "invalid input") => Error: invalid input
+/// (error "invalid input") => #<error: invalid input>
+/// (error :not-found {:path "x"}) => #<error :not-found not-found>
+/// (error :bad-input "age must be positive" {:age -1}) => #<error :bad-input age must be positive>
 /// ```
 ///
 /// # See Also
 ///
-/// error?, error-msg
+/// error?, error-msg, error-kind, error-data, try
 pub fn builtin_error(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 1 {
-        return Err(EvalError::arity_error("error", ARITY_ONE, args.len()));
+    if args.is_empty() || args.len() > 3 {
+        return Err(EvalError::arity_error(
+            "error",
+            ARITY_ONE_TO_THREE,
+            args.len(),
+        ));
     }
 
-    let msg = match &args[0] {
-        Value::String(s) => s.clone(),
-        other => format!("{}", other),
+    let kind = match &args[0] {
+        Value::Keyword(kind) => kind.clone(),
+        _ if args.len() == 1 => {
+            let msg = match &args[0] {
+                Value::String(s) => s.to_string(),
+                other => format!("{}", other),
+            };
+            return Ok(Value::error(msg));
+        }
+        other => return Err(EvalError::type_error("error", "keyword", other, 1)),
     };
 
-    Ok(Value::Error(msg))
+    let (message, data) = match &args[1..] {
+        [] => (kind.clone(), None),
+        [Value::String(msg)] => (msg.to_string(), None),
+        [Value::Map(data)] => (kind.clone(), Some(data.clone())),
+        [Value::String(msg), Value::Map(data)] => (msg.to_string(), Some(data.clone())),
+        [other] => return Err(EvalError::type_error("error", "string or map", other, 2)),
+        [Value::String(_), other] => return Err(EvalError::type_error("error", "map", other, 3)),
+        [other, _] => return Err(EvalError::type_error("error", "string", other, 2)),
+        _ => unreachable!("arity checked above"),
+    };
+
+    let mut error = ErrorValue::new(kind, message);
+    error.data = data;
+    Ok(Value::Error(Box::new(error)))
 }
 
-#[builtin(name = "error?", category = "Error handling", related(error, error-msg))]
+#[builtin(name = "error?", category = "Error handling", related(error, error-msg, try), params(value))]
 /// Tests if val is an error value.
 ///
 /// # Examples
 ///
 /// ```lisp
-/// (error? (error "test")) => would throw before testing
+/// (error? (error "test")) => #t
+/// (error? (try (car 5))) => #t
 /// ```
 ///
 /// # See Also
 ///
-/// error, error-msg
+/// error, error-msg, try
 pub fn builtin_error_p(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("error?", ARITY_ONE, args.len()));
@@ -57,25 +91,76 @@ pub fn builtin_error_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Error(_))))
 }
 
-#[builtin(name = "error-msg", category = "Error handling", related(error, error?))]
+#[builtin(name = "error-msg", category = "Error handling", related(error, error?, error-kind), params(error))]
 /// Extracts the message from an error value.
 ///
 /// # Examples
 ///
 /// ```lisp
-/// (error-msg (error "test")) => would throw before extracting
+/// (error-msg (error "test")) => "test"
 /// ```
 ///
 /// # See Also
 ///
-/// error, error?
+/// error, error?, error-kind
 pub fn builtin_error_msg(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("error-msg", ARITY_ONE, args.len()));
     }
 
     match &args[0] {
-        Value::Error(msg) => Ok(Value::String(msg.clone())),
+        Value::Error(err) => Ok(Value::String(err.message.as_str().into())),
         _ => Err(EvalError::type_error("error-msg", "error", &args[0], 1)),
     }
 }
+
+#[builtin(name = "error-kind", category = "Error handling", related(error, error-data, try), params(error))]
+/// Returns the kind keyword of an error value.
+///
+/// Errors caught by try have stable kinds such as :not-found, :path-not-allowed,
+/// :network-disabled, :type-error, :arity-error, :undefined-symbol and :runtime-error.
+///
+/// # Examples
+///
+/// ```lisp
+/// (error-kind (error "oops")) => :error
+/// (error-kind (try (read-file "missing.txt"))) => :not-found
+/// ```
+///
+/// # See Also
+///
+/// error, error-data, try
+pub fn builtin_error_kind(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("error-kind", ARITY_ONE, args.len()));
+    }
+
+    match &args[0] {
+        Value::Error(err) => Ok(Value::Keyword(err.kind.clone())),
+        _ => Err(EvalError::type_error("error-kind", "error", &args[0], 1)),
+    }
+}
+
+#[builtin(name = "error-data", category = "Error handling", related(error, error-kind, try), params(error))]
+/// Returns the data map of an error value, or nil if it has none.
+///
+/// # Examples
+///
+/// ```lisp
+/// (error-data (error :not-found {:path "x"})) => {:path "x"}
+/// (map-get (error-data (try (car 5))) :function) => "car"
+/// ```
+///
+/// # See Also
+///
+/// error, error-kind, try
+pub fn builtin_error_data(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("error-data", ARITY_ONE, args.len()));
+    }
+
+    match &args[0] {
+        Value::Error(err) => Ok(err.data.clone().map(Value::Map).unwrap_or(Value::Nil)),
+        _ => Err(EvalError::type_error("error-data", "error", &args[0], 1)),
+    }
+}
@@ -11,14 +11,14 @@
 //!
 //! All operations are restricted to whitelisted paths via capability-based sandboxing
 
-use crate::error::{EvalError, ARITY_ONE, ARITY_TWO, ERR_SANDBOX_NOT_INIT};
+use crate::error::{EvalError, ARITY_ONE, ARITY_TWO};
 use crate::value::Value;
 use lisp_macros::builtin;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use super::SANDBOX;
+use super::BuiltinContext;
 
-#[builtin(name = "read-file", category = "Filesystem I/O", related(write-file, file-exists?))]
+#[builtin(name = "read-file", capability = "filesystem", category = "Filesystem I/O", related(write-file, file-exists?), params(path))]
 /// Reads and returns the contents of a file as a string.
 ///
 /// Path is relative to allowed sandbox directories.
@@ -32,7 +32,7 @@ use super::SANDBOX;
 /// # See Also
 ///
 /// write-file, file-exists?
-pub fn read_file(args: &[Value]) -> Result<Value, EvalError> {
+pub fn read_file(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("read-file", ARITY_ONE, args.len()));
     }
@@ -42,20 +42,15 @@ pub fn read_file(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("read-file", "string", &args[0], 1)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("read-file", ERR_SANDBOX_NOT_INIT))?;
+    let sandbox = ctx.sandbox()?;
 
-        sandbox
-            .read_file(path)
-            .map(Value::String)
-            .map_err(|e| EvalError::runtime_error("read-file", e.to_string()))
-    })
+    sandbox
+        .read_file(path)
+        .map(|s| Value::String(s.into()))
+        .map_err(|e| EvalError::sandbox_error("read-file", e))
 }
 
-#[builtin(name = "write-file", category = "Filesystem I/O", related(read-file, file-exists?))]
+#[builtin(name = "write-file", capability = "filesystem", category = "Filesystem I/O", related(read-file, file-exists?), params(path, content))]
 /// Writes contents to a file, creating it if it doesn't exist.
 ///
 /// Returns #t on success. Path is relative to sandbox.
@@ -69,7 +64,7 @@ pub fn read_file(args: &[Value]) -> Result<Value, EvalError> {
 /// # See Also
 ///
 /// read-file, file-exists?
-pub fn write_file(args: &[Value]) -> Result<Value, EvalError> {
+pub fn write_file(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::arity_error("write-file", ARITY_TWO, args.len()));
     }
@@ -84,20 +79,15 @@ pub fn write_file(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("write-file", "string", &args[1], 2)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("write-file", ERR_SANDBOX_NOT_INIT))?;
+    let sandbox = ctx.sandbox()?;
 
-        sandbox
-            .write_file(path, contents)
-            .map(|_| Value::Bool(true))
-            .map_err(|e| EvalError::runtime_error("write-file", e.to_string()))
-    })
+    sandbox
+        .write_file(path, contents)
+        .map(|_| Value::Bool(true))
+        .map_err(|e| EvalError::sandbox_error("write-file", e))
 }
 
-#[builtin(name = "file-exists?", category = "Filesystem I/O", related(file-size, read-file))]
+#[builtin(name = "file-exists?", capability = "filesystem", category = "Filesystem I/O", related(file-size, read-file), params(path))]
 /// Tests if a file exists and is accessible in sandbox.
 ///
 /// Returns #t or #f.
@@ -112,7 +102,7 @@ pub fn write_file(args: &[Value]) -> Result<Value, EvalError> {
 /// # See Also
 ///
 /// file-size, read-file
-pub fn file_exists_q(args: &[Value]) -> Result<Value, EvalError> {
+pub fn file_exists_q(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error(
             "file-exists?",
@@ -126,20 +116,15 @@ pub fn file_exists_q(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("file-exists?", "string", &args[0], 1)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("file-exists?", ERR_SANDBOX_NOT_INIT))?;
+    let sandbox = ctx.sandbox()?;
 
-        sandbox
-            .file_exists(path)
-            .map(Value::Bool)
-            .map_err(|e| EvalError::runtime_error("file-exists?", e.to_string()))
-    })
+    sandbox
+        .file_exists(path)
+        .map(Value::Bool)
+        .map_err(|e| EvalError::sandbox_error("file-exists?", e))
 }
 
-#[builtin(name = "file-size", category = "Filesystem I/O", related(file-exists?, read-file))]
+#[builtin(name = "file-size", capability = "filesystem", category = "Filesystem I/O", related(file-exists?, read-file), params(path))]
 /// Returns the size of a file in bytes.
 ///
 /// Throws error if file doesn't exist.
@@ -153,7 +138,7 @@ pub fn file_exists_q(args: &[Value]) -> Result<Value, EvalError> {
 /// # See Also
 ///
 /// file-exists?, read-file
-pub fn file_size(args: &[Value]) -> Result<Value, EvalError> {
+pub fn file_size(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("file-size", ARITY_ONE, args.len()));
     }
@@ -163,23 +148,18 @@ pub fn file_size(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("file-size", "string", &args[0], 1)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("file-size", ERR_SANDBOX_NOT_INIT))?;
+    let sandbox = ctx.sandbox()?;
 
-        sandbox
-            .file_size(path)
-            .map(|size| Value::Number(size as f64))
-            .map_err(|e| EvalError::runtime_error("file-size", e.to_string()))
-    })
+    sandbox
+        .file_size(path)
+        .map(|size| Value::Number(size as f64))
+        .map_err(|e| EvalError::sandbox_error("file-size", e))
 }
 
-#[builtin(name = "list-files", category = "Filesystem I/O", related(file-exists?))]
+#[builtin(name = "list-files", capability = "filesystem", category = "Filesystem I/O", related(file-exists?), params(dir))]
 /// Returns a list of filenames in a directory.
 ///
-/// Does not include . or .., returns only names not full paths.
+/// Does not include . or .., returns only names not full paths, sorted.
 ///
 /// # Examples
 ///
@@ -190,7 +170,7 @@ pub fn file_size(args: &[Value]) -> Result<Value, EvalError> {
 /// # See Also
 ///
 /// file-exists?
-pub fn list_files(args: &[Value]) -> Result<Value, EvalError> {
+pub fn list_files(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("list-files", ARITY_ONE, args.len()));
     }
@@ -200,20 +180,15 @@ pub fn list_files(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("list-files", "string", &args[0], 1)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("list-files", ERR_SANDBOX_NOT_INIT))?;
+    let sandbox = ctx.sandbox()?;
 
-        sandbox
-            .list_files(dir)
-            .map(|files| Value::List(files.into_iter().map(Value::String).collect::<Vec<_>>()))
-            .map_err(|e| EvalError::runtime_error("list-files", e.to_string()))
-    })
+    sandbox
+        .list_files(dir)
+        .map(|files| Value::List(files.into_iter().map(|s| Value::String(s.into())).collect()))
+        .map_err(|e| EvalError::sandbox_error("list-files", e))
 }
 
-#[builtin(name = "file-stat", category = "Filesystem I/O", related(file-exists?, file-size))]
+#[builtin(name = "file-stat", capability = "filesystem", category = "Filesystem I/O", related(file-exists?, file-size), params(path))]
 /// Returns file metadata as a map with :size, :type, :modified, :accessed, :created, :readonly keys.
 ///
 /// - :size - File size in bytes (number)
@@ -232,7 +207,7 @@ pub fn list_files(args: &[Value]) -> Result<Value, EvalError> {
 /// # See Also
 ///
 /// file-exists?, file-size
-pub fn file_stat(args: &[Value]) -> Result<Value, EvalError> {
+pub fn file_stat(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("file-stat", ARITY_ONE, args.len()));
     }
@@ -242,24 +217,19 @@ pub fn file_stat(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("file-stat", "string", &args[0], 1)),
     };
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("file-stat", ERR_SANDBOX_NOT_INIT))?;
-
-        sandbox
-            .file_stat(path)
-            .map(|stat| {
-                let mut result_map = HashMap::new();
-                result_map.insert("size".to_string(), Value::Number(stat.size as f64));
-                result_map.insert("type".to_string(), Value::String(stat.file_type));
-                result_map.insert("modified".to_string(), Value::Number(stat.modified));
-                result_map.insert("accessed".to_string(), Value::Number(stat.accessed));
-                result_map.insert("created".to_string(), Value::Number(stat.created));
-                result_map.insert("readonly".to_string(), Value::Bool(stat.readonly));
-                Value::Map(result_map)
-            })
-            .map_err(|e| EvalError::runtime_error("file-stat", e.to_string()))
-    })
+    let sandbox = ctx.sandbox()?;
+
+    sandbox
+        .file_stat(path)
+        .map(|stat| {
+            let mut result_map = BTreeMap::new();
+            result_map.insert("size".to_string(), Value::Number(stat.size as f64));
+            result_map.insert("type".to_string(), Value::String(stat.file_type.into()));
+            result_map.insert("modified".to_string(), Value::Number(stat.modified));
+            result_map.insert("accessed".to_string(), Value::Number(stat.accessed));
+            result_map.insert("created".to_string(), Value::Number(stat.created));
+            result_map.insert("readonly".to_string(), Value::Bool(stat.readonly));
+            Value::Map(result_map)
+        })
+        .map_err(|e| EvalError::sandbox_error("file-stat", e))
 }
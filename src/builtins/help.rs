@@ -1,19 +1,41 @@
-//! Help system operations: help, doc
+//! Help system operations: help, doc, help-search, help-category, apropos
 //!
 //! Functions for accessing documentation and help information.
 //!
 //! - `help`: Show help for a function (displays markdown documentation)
 //! - `doc`: Extract docstring from a user-defined function
+//! - `source`: Print the definition of a Lisp function
+//! - `help-search`: Find documented functions by substring
+//! - `help-category`: List the functions in a help category
+//! - `apropos`: Fuzzy search over documented function names
 //!
 //! The help system includes all 32 built-in functions and 8 special forms.
 //! User-defined functions can include docstrings as the first element of the body.
 
-use crate::env::Environment;
+use crate::builtins::console::write_output;
+use crate::builtins::BuiltinContext;
 use crate::error::{EvalError, ARITY_ONE, ARITY_ZERO_OR_ONE};
+use crate::fmt::format_source;
+use crate::help::HelpEntry;
+use crate::pretty::{pretty_print, DEFAULT_WIDTH};
 use crate::value::Value;
-use std::rc::Rc;
+use lisp_macros::builtin;
 
-/// Show help information
+#[builtin(name = "help", category = "Help system", related(doc), params([function-name]), signature = "(help) or (help 'function-name)")]
+/// Show help information. With no arguments, displays quick reference.
+/// With a function name, shows detailed documentation for that function.
+///
+/// # Examples
+///
+/// ```lisp
+/// (help) => shows quick reference
+/// (help 'cons) => detailed help for cons
+/// (help 'map) => help for user or stdlib function
+/// ```
+///
+/// # See Also
+///
+/// doc
 pub fn builtin_help(args: &[Value]) -> Result<Value, EvalError> {
     use crate::help;
 
@@ -21,7 +43,7 @@ pub fn builtin_help(args: &[Value]) -> Result<Value, EvalError> {
         0 => {
             // Show quick reference
             let output = help::format_quick_reference();
-            println!("{}", output);
+            write_output(&format!("{}\n", output));
             Ok(Value::Nil)
         }
         1 => {
@@ -31,7 +53,7 @@ pub fn builtin_help(args: &[Value]) -> Result<Value, EvalError> {
                     // First try built-in help
                     if let Some(entry) = help::get_help(name) {
                         let output = help::format_help_entry(&entry);
-                        println!("{}", output);
+                        write_output(&format!("{}\n", output));
                         return Ok(Value::Nil);
                     }
 
@@ -54,7 +76,24 @@ pub fn builtin_help(args: &[Value]) -> Result<Value, EvalError> {
     }
 }
 
-/// Returns the docstring of a function as a string
+#[builtin(
+    name = "doc",
+    category = "Help system",
+    related(help),
+    params(function)
+)]
+/// Returns the docstring of a function as a string.
+/// Works with user-defined functions that have docstrings.
+///
+/// # Examples
+///
+/// ```lisp
+/// (doc factorial) => "Computes factorial"
+/// ```
+///
+/// # See Also
+///
+/// help
 pub fn builtin_doc(args: &[Value]) -> Result<Value, EvalError> {
     if args.len() != 1 {
         return Err(EvalError::arity_error("doc", ARITY_ONE, args.len()));
@@ -62,38 +101,194 @@ pub fn builtin_doc(args: &[Value]) -> Result<Value, EvalError> {
 
     match &args[0] {
         Value::Lambda { docstring, .. } => match docstring {
-            Some(doc) => Ok(Value::String(doc.clone())),
+            Some(doc) => Ok(Value::String(doc.as_str().into())),
             None => Ok(Value::Nil),
         },
         _ => Err(EvalError::type_error("doc", "lambda", &args[0], 1)),
     }
 }
 
-/// Register all help system builtins in the environment
-pub fn register(env: &Rc<Environment>) {
-    env.define("help".to_string(), Value::BuiltIn(builtin_help));
-    env.define("doc".to_string(), Value::BuiltIn(builtin_doc));
-
-    // Register help entries
-    crate::help::register_help(crate::help::HelpEntry {
-        name: "help".to_string(),
-        signature: "(help) or (help 'function-name)".to_string(),
-        description: "Show help information. With no arguments, displays quick reference.\nWith a function name, shows detailed documentation for that function.".to_string(),
-        examples: vec![
-            "(help) => shows quick reference".to_string(),
-            "(help 'cons) => detailed help for cons".to_string(),
-            "(help 'map) => help for user or stdlib function".to_string(),
-        ],
-        related: vec!["doc".to_string()],
-        category: "Help system".to_string(),
-    });
-
-    crate::help::register_help(crate::help::HelpEntry {
-        name: "doc".to_string(),
-        signature: "(doc ...)".to_string(),
-        description: "Returns the docstring of a function as a string.\nWorks with user-defined functions that have docstrings.".to_string(),
-        examples: vec!["(doc factorial) => \"Computes factorial\"".to_string()],
-        related: vec!["help".to_string()],
-        category: "Help system".to_string(),
-    });
+#[builtin(
+    name = "source",
+    category = "Help system",
+    related(doc, help, pp),
+    params(function)
+)]
+/// Prints the definition of a function made by define, as written. Returns nil.
+///
+/// Takes a quoted name or the function itself. Stdlib functions are shown
+/// too; builtins are written in Rust and have no Lisp source, and neither
+/// does a lambda that was never bound with define. A definition made by a
+/// macro has no text of its own and is printed in the formatter's layout.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+/// (source 'fact) => outputs: (define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))
+/// (source 'compose) => prints the stdlib definition
+/// (source 'car) => error: car is a builtin and has no Lisp source
+/// ```
+///
+/// # See Also
+///
+/// doc, help, pp
+pub fn builtin_source(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let (name, func) = match &args[0] {
+        Value::Symbol(name) => {
+            let env = ctx.env()?;
+            let func = env
+                .get(name)
+                .ok_or_else(|| EvalError::undefined_symbol(name, &env))?;
+            (name.to_string(), func)
+        }
+        other => ("function".to_string(), other.clone()),
+    };
+
+    match &func {
+        Value::Lambda {
+            source: Some(source),
+            ..
+        } => {
+            let text = match &**source {
+                Value::String(text) => text.to_string(),
+                form => {
+                    let printed = pretty_print(form, DEFAULT_WIDTH);
+                    format_source(&printed).unwrap_or(printed)
+                }
+            };
+            write_output(&format!("{}\n", text.trim_end()));
+            Ok(Value::Nil)
+        }
+        Value::Lambda { .. } => Err(EvalError::runtime_error(
+            "source",
+            format!(
+                "{} was not created by define and has no recorded source",
+                name
+            ),
+        )),
+        Value::BuiltIn(_) => Err(EvalError::runtime_error(
+            "source",
+            format!("{} is a builtin and has no Lisp source", name),
+        )),
+        other => Err(EvalError::type_error("source", "function", other, 1)),
+    }
+}
+
+#[builtin(name = "help-search", category = "Help system", related(apropos, help-category, help), params(substring), signature = "(help-search \"substring\")")]
+/// Find documented functions whose name, signature or description contains the substring (case-insensitive).
+/// Returns a list of symbols sorted by name.
+///
+/// # Examples
+///
+/// ```lisp
+/// (help-search "channel") => (channel-recv channel-send make-channel)
+/// ```
+///
+/// # See Also
+///
+/// apropos, help-category, help
+pub fn builtin_help_search(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("help-search", ARITY_ONE, args.len()));
+    }
+
+    match &args[0] {
+        Value::String(query) => Ok(entry_names(crate::help::search(query))),
+        other => Err(EvalError::type_error("help-search", "string", other, 1)),
+    }
+}
+
+#[builtin(name = "help-category", category = "Help system", related(help-search, help), params(category), signature = "(help-category \"category\")")]
+/// List the functions in a help category, as shown by (help).
+/// The category name is matched case-insensitively. Returns a list of symbols sorted by name.
+///
+/// # Examples
+///
+/// ```lisp
+/// (help-category "Concurrency") => (channel-recv channel-send join make-channel spawn)
+/// ```
+///
+/// # See Also
+///
+/// help-search, help
+pub fn builtin_help_category(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "help-category",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+
+    let category = match &args[0] {
+        Value::String(s) => s,
+        other => return Err(EvalError::type_error("help-category", "string", other, 1)),
+    };
+
+    match crate::help::category(category) {
+        Some(entries) => Ok(entry_names(entries)),
+        None => {
+            let mut known: Vec<_> = crate::help::all_by_category().into_keys().collect();
+            known.sort();
+            Err(EvalError::runtime_error(
+                "help-category",
+                format!(
+                    "unknown category '{}' (known: {})",
+                    category,
+                    known.join(", ")
+                ),
+            ))
+        }
+    }
+}
+
+#[builtin(name = "apropos", category = "Help system", related(help-search, help), params(pattern), signature = "(apropos 'pattern)")]
+/// Fuzzy search over documented function names.
+/// A name matches if it contains the pattern's characters in order. Exact matches come first, then prefixes, substrings and scattered matches.
+///
+/// # Examples
+///
+/// ```lisp
+/// (apropos 'map) => (map map-get map-set ...)
+/// (apropos "strlen") => (string-length)
+/// ```
+///
+/// # See Also
+///
+/// help-search, help
+pub fn builtin_apropos(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("apropos", ARITY_ONE, args.len()));
+    }
+
+    let pattern = match &args[0] {
+        Value::Symbol(s) => s.to_string(),
+        Value::String(s) => s.to_string(),
+        other => {
+            return Err(EvalError::type_error(
+                "apropos",
+                "symbol or string",
+                other,
+                1,
+            ))
+        }
+    };
+
+    Ok(Value::List(
+        crate::help::apropos(&pattern)
+            .into_iter()
+            .map(|name| Value::Symbol(name.into()))
+            .collect(),
+    ))
+}
+
+/// Turn help entries into a list of their names as symbols
+fn entry_names(entries: Vec<HelpEntry>) -> Value {
+    Value::List(
+        entries
+            .into_iter()
+            .map(|e| Value::Symbol(e.name.into()))
+            .collect(),
+    )
 }
@@ -0,0 +1,380 @@
+//! Higher-order list operations: map, filter, reduce, for-each, some, every, values
+//!
+//! Native implementations of the classic list combinators. Functions are called
+//! directly instead of re-evaluating a recursive Lisp definition per element.
+//!
+//! - `map`: Apply function across one or more lists, collecting results
+//! - `for-each`: Apply function across one or more lists for side effects
+//! - `filter`: Keep elements satisfying a predicate
+//! - `reduce`: Left fold with an initial value
+//! - `some`: First truthy predicate result (stops early)
+//! - `every`: Whether all predicate results are truthy (stops early)
+//! - `values`, `call-with-values`: Return several results and spread them into a call
+//!
+//! Multi-list forms walk the lists in parallel and stop at the shortest one
+//!
+//! Multiple values are their own kind of value, so a list returned as one value
+//! is never spread by mistake. A list pattern binds them one by one:
+//! `(let (((q r) (divmod 7 2))) ...)`.
+
+use crate::error::{EvalError, ARITY_AT_LEAST_THREE, ARITY_AT_LEAST_TWO, ARITY_ONE, ARITY_TWO};
+use crate::eval::apply_function;
+use crate::value::Value;
+use lisp_macros::builtin;
+
+/// Extract the elements of a list argument (nil counts as the empty list)
+pub(crate) fn list_items<'a>(
+    function: &str,
+    value: &'a Value,
+    position: usize,
+) -> Result<&'a [Value], EvalError> {
+    match value {
+        Value::List(items) => Ok(items),
+        Value::Nil => Ok(&[]),
+        _ => Err(EvalError::type_error(function, "list", value, position)),
+    }
+}
+
+/// Collect list arguments starting at `first_position` (1-based)
+fn list_args<'a>(
+    function: &str,
+    args: &'a [Value],
+    first_position: usize,
+) -> Result<Vec<&'a [Value]>, EvalError> {
+    args.iter()
+        .enumerate()
+        .map(|(i, arg)| list_items(function, arg, first_position + i))
+        .collect()
+}
+
+/// Call `f` with the i-th element of every list, for each i up to the shortest list
+fn for_each_row(
+    lists: &[&[Value]],
+    mut f: impl FnMut(Vec<Value>) -> Result<bool, EvalError>,
+) -> Result<(), EvalError> {
+    let len = lists.iter().map(|l| l.len()).min().unwrap_or(0);
+    for i in 0..len {
+        let row = lists.iter().map(|l| l[i].clone()).collect();
+        if !f(row)? {
+            break;
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn is_truthy(value: &Value) -> bool {
+    !matches!(value, Value::Bool(false) | Value::Nil)
+}
+
+#[builtin(name = "map", category = "Higher-order functions", related(for-each, filter, reduce), params(f, list, more-lists...))]
+/// Applies f to each element, returning a new list of the results.
+///
+/// With several lists, f receives one element from each and mapping stops at
+/// the end of the shortest list.
+///
+/// # Examples
+///
+/// ```lisp
+/// (map (lambda (x) (* x 2)) '(1 2 3)) => (2 4 6)
+/// (map + '(1 2 3) '(10 20 30)) => (11 22 33)
+/// (map list '(1 2 3) '(a b)) => ((1 a) (2 b))
+/// ```
+///
+/// # See Also
+///
+/// for-each, filter, reduce
+pub fn builtin_map(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity_error(
+            "map",
+            ARITY_AT_LEAST_TWO,
+            args.len(),
+        ));
+    }
+
+    let lists = list_args("map", &args[1..], 2)?;
+    let mut results = Vec::new();
+    for_each_row(&lists, |row| {
+        results.push(apply_function(&args[0], row)?);
+        Ok(true)
+    })?;
+
+    Ok(Value::List(results.into()))
+}
+
+#[builtin(name = "for-each", category = "Higher-order functions", related(map), params(f, list, more-lists...))]
+/// Applies f to each element for its side effects. Returns nil.
+///
+/// Accepts several lists like map, stopping at the shortest one.
+///
+/// # Examples
+///
+/// ```lisp
+/// (for-each println '("a" "b")) => nil (prints a and b)
+/// (for-each (lambda (k v) (println k "=" v)) '(x y) '(1 2)) => nil
+/// ```
+///
+/// # See Also
+///
+/// map
+pub fn builtin_for_each(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity_error(
+            "for-each",
+            ARITY_AT_LEAST_TWO,
+            args.len(),
+        ));
+    }
+
+    let lists = list_args("for-each", &args[1..], 2)?;
+    for_each_row(&lists, |row| {
+        apply_function(&args[0], row)?;
+        Ok(true)
+    })?;
+
+    Ok(Value::Nil)
+}
+
+#[builtin(
+    name = "filter",
+    category = "Higher-order functions",
+    related(map, reduce, some),
+    params(pred, list)
+)]
+/// Returns a new list of the elements for which pred is truthy.
+///
+/// # Examples
+///
+/// ```lisp
+/// (filter (lambda (x) (> x 2)) '(1 2 3 4 5)) => (3 4 5)
+/// (filter even? '(1 2 3 4 5)) => (2 4)
+/// ```
+///
+/// # See Also
+///
+/// map, reduce, some
+pub fn builtin_filter(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("filter", ARITY_TWO, args.len()));
+    }
+
+    let items = list_items("filter", &args[1], 2)?;
+    let mut results = Vec::new();
+    for item in items {
+        if is_truthy(&apply_function(&args[0], vec![item.clone()])?) {
+            results.push(item.clone());
+        }
+    }
+
+    Ok(Value::List(results.into()))
+}
+
+#[builtin(
+    name = "reduce",
+    category = "Higher-order functions",
+    related(map, filter),
+    params(f, init, list, more-lists...)
+)]
+/// Folds lists from left to right, starting from init.
+///
+/// f receives the accumulator followed by one element from each list.
+///
+/// # Examples
+///
+/// ```lisp
+/// (reduce + 0 '(1 2 3 4)) => 10
+/// (reduce (lambda (acc x) (cons x acc)) '() '(1 2 3)) => (3 2 1)
+/// (reduce (lambda (acc x y) (+ acc (* x y))) 0 '(1 2) '(3 4)) => 11
+/// ```
+///
+/// # See Also
+///
+/// map, filter
+pub fn builtin_reduce(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 3 {
+        return Err(EvalError::arity_error(
+            "reduce",
+            ARITY_AT_LEAST_THREE,
+            args.len(),
+        ));
+    }
+
+    let lists = list_args("reduce", &args[2..], 3)?;
+    let mut acc = args[1].clone();
+    for_each_row(&lists, |row| {
+        let mut call_args = Vec::with_capacity(row.len() + 1);
+        call_args.push(std::mem::replace(&mut acc, Value::Nil));
+        call_args.extend(row);
+        acc = apply_function(&args[0], call_args)?;
+        Ok(true)
+    })?;
+
+    Ok(acc)
+}
+
+#[builtin(
+    name = "some",
+    category = "Higher-order functions",
+    related(every, filter),
+    params(pred, list, more-lists...)
+)]
+/// Returns the first truthy result of pred, or #f if there is none.
+///
+/// Stops calling pred as soon as a truthy result is found. Accepts several lists.
+///
+/// # Examples
+///
+/// ```lisp
+/// (some even? '(1 3 4 5)) => #t
+/// (some (lambda (x) (if (> x 2) x nil)) '(1 2 3 4)) => 3
+/// (some = '(1 2 3) '(3 2 1)) => #t
+/// (some even? '()) => #f
+/// ```
+///
+/// # See Also
+///
+/// every, filter
+pub fn builtin_some(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity_error(
+            "some",
+            ARITY_AT_LEAST_TWO,
+            args.len(),
+        ));
+    }
+
+    let lists = list_args("some", &args[1..], 2)?;
+    let mut found = Value::Bool(false);
+    for_each_row(&lists, |row| {
+        let result = apply_function(&args[0], row)?;
+        if is_truthy(&result) {
+            found = result;
+            return Ok(false);
+        }
+        Ok(true)
+    })?;
+
+    Ok(found)
+}
+
+#[builtin(
+    name = "every",
+    category = "Higher-order functions",
+    related(some, filter),
+    params(pred, list, more-lists...)
+)]
+/// Returns #f as soon as pred gives a falsy result, otherwise the last result.
+///
+/// Returns #t for empty lists. Accepts several lists.
+///
+/// # Examples
+///
+/// ```lisp
+/// (every even? '(2 4 6)) => #t
+/// (every even? '(2 3 6)) => #f
+/// (every < '(1 2) '(3 4)) => #t
+/// (every even? '()) => #t
+/// ```
+///
+/// # See Also
+///
+/// some, filter
+pub fn builtin_every(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 {
+        return Err(EvalError::arity_error(
+            "every",
+            ARITY_AT_LEAST_TWO,
+            args.len(),
+        ));
+    }
+
+    let lists = list_args("every", &args[1..], 2)?;
+    let mut last = Value::Bool(true);
+    for_each_row(&lists, |row| {
+        last = apply_function(&args[0], row)?;
+        Ok(is_truthy(&last))
+    })?;
+
+    if is_truthy(&last) {
+        Ok(last)
+    } else {
+        Ok(Value::Bool(false))
+    }
+}
+
+#[builtin(name = "values", category = "Higher-order functions", related(call-with-values, divmod), params(values...))]
+/// Returns its arguments as multiple values.
+///
+/// A single argument is returned as is; any other number of arguments,
+/// including none, makes a multiple-values value, ready for a list pattern in
+/// `let` or a lambda, or for `call-with-values`.
+///
+/// # Examples
+///
+/// ```lisp
+/// (values 1 2) => (values 1 2)
+/// (values 5) => 5
+/// (values) => (values)
+/// (let (((lo hi) (values 1 9))) (- hi lo)) => 8
+/// ```
+///
+/// # See Also
+///
+/// call-with-values, divmod
+pub fn builtin_values(args: &[Value]) -> Result<Value, EvalError> {
+    match args {
+        [single] => Ok(single.clone()),
+        _ => Ok(Value::Values(args.into())),
+    }
+}
+
+/// The elements of multiple values as a list, and any other value unchanged
+///
+/// Wrapped around the value of a list pattern, so `(let (((q r) (divmod 7 2))) ...)`
+/// takes multiple values apart with `car` and `cdr` like a list.
+pub(crate) fn values_to_list(args: &[Value]) -> Result<Value, EvalError> {
+    match args {
+        [Value::Values(items)] => Ok(Value::List(items.clone())),
+        [other] => Ok(other.clone()),
+        _ => Err(EvalError::arity_error("values", ARITY_ONE, args.len())),
+    }
+}
+
+#[builtin(
+    name = "call-with-values",
+    category = "Higher-order functions",
+    related(values, divmod),
+    params(producer, consumer)
+)]
+/// Calls producer with no arguments and passes its values to consumer as separate arguments.
+///
+/// Multiple values from `values` are spread into the call; any other result,
+/// a list or nil included, is passed as the only argument.
+///
+/// # Examples
+///
+/// ```lisp
+/// (call-with-values (lambda () (values 1 2)) +) => 3
+/// (call-with-values (lambda () (divmod 17 5)) list) => (3 2)
+/// (call-with-values (lambda () (values '(1 2))) length) => 2
+/// (call-with-values (lambda () (values)) list) => ()
+/// ```
+///
+/// # See Also
+///
+/// values, divmod
+pub fn builtin_call_with_values(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "call-with-values",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+
+    let values = match apply_function(&args[0], Vec::new())? {
+        Value::Values(items) => items.to_vec(),
+        other => vec![other],
+    };
+    apply_function(&args[1], values)
+}
@@ -0,0 +1,231 @@
+//! Introspection: procedure?, lambda?, builtin?, macro?, arity, function-params
+//!
+//! Functions for asking what a callable is and how it is called.
+//!
+//! - `procedure?`: Test if value can be called (a lambda or a builtin)
+//! - `lambda?`: Test if value is a function written in Lisp
+//! - `builtin?`: Test if value is a builtin function
+//! - `macro?`: Test if a quoted name names a macro
+//! - `arity`: The number of arguments a function or macro accepts
+//! - `function-params`: The parameter list of a function or macro
+//!
+//! `arity` and `function-params` take a function value or a quoted name; a
+//! name is looked up among the macros first and then in the global
+//! environment, so local functions have to be passed as values.
+
+use crate::builtins::{builtin_registration, BuiltinContext};
+use crate::error::EvalError;
+use crate::symbol::Symbol;
+use crate::value::Value;
+use lisp_macros::builtin;
+use std::collections::BTreeMap;
+
+#[builtin(name = "procedure?", category = "Introspection", related(lambda?, builtin?, macro?), params(value))]
+/// Tests if val can be called: a lambda or a builtin function.
+///
+/// # Examples
+///
+/// ```lisp
+/// (procedure? car) => #t
+/// (procedure? (lambda (x) x)) => #t
+/// (procedure? 'car) => #f
+/// ```
+///
+/// # See Also
+///
+/// lambda?, builtin?, macro?
+pub fn builtin_procedure_p(args: &[Value]) -> Result<Value, EvalError> {
+    Ok(Value::Bool(matches!(
+        args[0],
+        Value::Lambda { .. } | Value::BuiltIn(_)
+    )))
+}
+
+#[builtin(name = "lambda?", category = "Introspection", related(procedure?, builtin?), params(value))]
+/// Tests if val is a function written in Lisp, made by lambda or define.
+///
+/// # Examples
+///
+/// ```lisp
+/// (lambda? (lambda (x) x)) => #t
+/// (lambda? compose) => #t
+/// (lambda? car) => #f
+/// ```
+///
+/// # See Also
+///
+/// procedure?, builtin?
+pub fn builtin_lambda_p(args: &[Value]) -> Result<Value, EvalError> {
+    Ok(Value::Bool(matches!(args[0], Value::Lambda { .. })))
+}
+
+#[builtin(name = "builtin?", category = "Introspection", related(procedure?, lambda?), params(value))]
+/// Tests if val is a builtin function implemented in Rust.
+///
+/// # Examples
+///
+/// ```lisp
+/// (builtin? car) => #t
+/// (builtin? (lambda (x) x)) => #f
+/// ```
+///
+/// # See Also
+///
+/// procedure?, lambda?
+pub fn builtin_builtin_p(args: &[Value]) -> Result<Value, EvalError> {
+    Ok(Value::Bool(matches!(args[0], Value::BuiltIn(_))))
+}
+
+#[builtin(name = "macro?", category = "Introspection", related(procedure?, defmacro), params(name))]
+/// Tests if the quoted name names a macro defined with defmacro.
+///
+/// Macros are expanded before evaluation and are not values, so the name must
+/// be quoted.
+///
+/// # Examples
+///
+/// ```lisp
+/// (defmacro unless (c body) `(if ,c nil ,body))
+/// (macro? 'unless) => #t
+/// (macro? 'car) => #f
+/// ```
+///
+/// # See Also
+///
+/// procedure?, defmacro
+pub fn builtin_macro_p(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    match &args[0] {
+        Value::Symbol(name) => Ok(Value::Bool(ctx.macros().get(name).is_some())),
+        Value::Macro { .. } => Ok(Value::Bool(true)),
+        _ => Ok(Value::Bool(false)),
+    }
+}
+
+#[builtin(name = "arity", category = "Introspection", related(function-params, procedure?), params(function))]
+/// Returns the number of arguments a function or macro accepts, as {:min n :max m}.
+///
+/// :max is nil when any number of trailing arguments is accepted. Optional
+/// parameters count towards :max only.
+///
+/// # Examples
+///
+/// ```lisp
+/// (arity car) => {:max 1 :min 1}
+/// (arity +) => {:max nil :min 0}
+/// (arity (lambda (x (y 2)) x)) => {:max 2 :min 1}
+/// (arity 'unless) => {:max 2 :min 2}
+/// ```
+///
+/// # See Also
+///
+/// function-params, procedure?
+pub fn builtin_arity(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let (min, max) = match callable("arity", &args[0], ctx)? {
+        Callable::Lambda { params, defaults } => {
+            (params.len() - defaults.len(), Some(params.len()))
+        }
+        Callable::Builtin(f) => match builtin_registration(f) {
+            Some(builtin) => (builtin.min_arity, builtin.max_arity),
+            None => (0, None),
+        },
+        Callable::Macro(params) => (params.len(), Some(params.len())),
+    };
+
+    let mut map = BTreeMap::new();
+    map.insert("min".to_string(), Value::Number(min as f64));
+    map.insert(
+        "max".to_string(),
+        max.map_or(Value::Nil, |max| Value::Number(max as f64)),
+    );
+    Ok(Value::Map(map))
+}
+
+#[builtin(
+    name = "function-params",
+    category = "Introspection",
+    related(arity, source, help),
+    params(function)
+)]
+/// Returns the parameter list of a function or macro as a list of symbols.
+///
+/// An optional parameter of a lambda is shown with its default, as
+/// (name default). Builtins list the parameters from their help signature,
+/// where [name] is optional and name... takes the remaining arguments.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define (greet name (greeting "Hello")) (str greeting ", " name))
+/// (function-params greet) => (name (greeting "Hello"))
+/// (function-params map-get) => (map key [default])
+/// (function-params 'unless) => (c body)
+/// ```
+///
+/// # See Also
+///
+/// arity, source, help
+pub fn builtin_function_params(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let params: Vec<Value> = match callable("function-params", &args[0], ctx)? {
+        Callable::Lambda { params, defaults } => {
+            let required = params.len() - defaults.len();
+            params
+                .into_iter()
+                .enumerate()
+                .map(|(i, param)| match i.checked_sub(required) {
+                    Some(d) => Value::List(vec![Value::Symbol(param), defaults[d].clone()].into()),
+                    None => Value::Symbol(param),
+                })
+                .collect()
+        }
+        Callable::Builtin(f) => builtin_registration(f)
+            .map(|builtin| {
+                builtin
+                    .params
+                    .iter()
+                    .map(|param| Value::Symbol((*param).into()))
+                    .collect()
+            })
+            .unwrap_or_default(),
+        Callable::Macro(params) => params.into_iter().map(Value::Symbol).collect(),
+    };
+    Ok(Value::List(params.into()))
+}
+
+/// A function or macro found by [`callable`]
+enum Callable {
+    Lambda {
+        params: Vec<Symbol>,
+        defaults: Vec<Value>,
+    },
+    Builtin(fn(&[Value]) -> Result<Value, EvalError>),
+    Macro(Vec<Symbol>),
+}
+
+/// The function or macro `arg` is, or that the quoted name in `arg` refers to
+fn callable(function: &str, arg: &Value, ctx: &BuiltinContext) -> Result<Callable, EvalError> {
+    let value = match arg {
+        Value::Symbol(name) => {
+            if let Some(def) = ctx.macros().get(name) {
+                return Ok(Callable::Macro(def.params));
+            }
+            let env = ctx.env()?;
+            env.get(name)
+                .ok_or_else(|| EvalError::undefined_symbol(name, &env))?
+        }
+        other => other.clone(),
+    };
+
+    match value {
+        Value::Lambda {
+            params, defaults, ..
+        } => Ok(Callable::Lambda { params, defaults }),
+        Value::BuiltIn(f) => Ok(Callable::Builtin(f)),
+        Value::Macro { params, .. } => Ok(Callable::Macro(params)),
+        other => Err(EvalError::type_error(
+            function,
+            "function or macro",
+            &other,
+            1,
+        )),
+    }
+}
@@ -1,4 +1,4 @@
-//! List operations: cons, car, cdr, list, length, empty?
+//! List operations: cons, car, cdr, list, length, empty?, and bulk reshaping
 //!
 //! Functions for building and manipulating lists.
 //!
@@ -8,12 +8,30 @@
 //! - `list`: Create a list from arguments
 //! - `length`: Get number of elements in list
 //! - `empty?`: Test if list is empty
+//! - `flatten`: Splice nested lists into one flat list
+//! - `partition`: Split a list by a predicate into matching and other elements
+//! - `group-by`: Group elements into a map by the result of a key function
+//! - `frequencies`: Count occurrences of each element into a map
+//! - `distinct`: Drop repeated elements, keeping first occurrences
+//! - `interleave`: Alternate the elements of several lists
+//! - `chunk`: Split a list into sublists of a fixed size
+//!
+//! The bulk operations run natively in one pass, so they stay fast on large
+//! lists where a recursive Lisp definition would not.
 
-use crate::error::{EvalError, ARITY_ONE, ARITY_TWO};
-use crate::value::Value;
+use crate::builtins::higher_order::{is_truthy, list_items};
+use crate::error::{EvalError, ARITY_AT_LEAST_ONE, ARITY_ONE, ARITY_TWO};
+use crate::eval::apply_function;
+use crate::value::{values_equal, Value};
 use lisp_macros::builtin;
+use std::collections::HashMap;
 
-#[builtin(name = "cons", category = "List operations", related(car, cdr, list))]
+#[builtin(
+    name = "cons",
+    category = "List operations",
+    related(car, cdr, list),
+    params(x, list)
+)]
 /// Constructs a new list by prepending elem to list.
 ///
 /// Returns a new list; original is not modified.
@@ -37,15 +55,20 @@ pub fn builtin_cons(args: &[Value]) -> Result<Value, EvalError> {
     let mut result = vec![args[0].clone()];
 
     match &args[1] {
-        Value::List(items) => result.extend(items.clone()),
+        Value::List(items) => result.extend(items.iter().cloned()),
         Value::Nil => (),
         _ => return Err(EvalError::type_error("cons", "list", &args[1], 2)),
     }
 
-    Ok(Value::List(result))
+    Ok(Value::List(result.into()))
 }
 
-#[builtin(name = "car", category = "List operations", related(cdr, cons))]
+#[builtin(
+    name = "car",
+    category = "List operations",
+    related(cdr, cons),
+    params(list)
+)]
 /// Returns the first element of a list. Also called 'head'.
 ///
 /// Throws error on empty list or non-list.
@@ -72,7 +95,12 @@ pub fn builtin_car(args: &[Value]) -> Result<Value, EvalError> {
     }
 }
 
-#[builtin(name = "cdr", category = "List operations", related(car, cons))]
+#[builtin(
+    name = "cdr",
+    category = "List operations",
+    related(car, cons),
+    params(list)
+)]
 /// Returns all elements except the first. Also called 'tail'.
 ///
 /// Returns nil for single-element list.
@@ -98,7 +126,7 @@ pub fn builtin_cdr(args: &[Value]) -> Result<Value, EvalError> {
             if items.len() == 1 {
                 Ok(Value::Nil)
             } else {
-                Ok(Value::List(items[1..].to_vec()))
+                Ok(Value::List(items[1..].into()))
             }
         }
         Value::List(_) => Err(EvalError::runtime_error("cdr", "empty list")),
@@ -106,7 +134,7 @@ pub fn builtin_cdr(args: &[Value]) -> Result<Value, EvalError> {
     }
 }
 
-#[builtin(name = "list", category = "List operations", related(cons, car, cdr))]
+#[builtin(name = "list", category = "List operations", related(cons, car, cdr), params(items...))]
 /// Creates a new list containing the given elements in order.
 ///
 /// # Examples
@@ -121,10 +149,10 @@ pub fn builtin_cdr(args: &[Value]) -> Result<Value, EvalError> {
 ///
 /// cons, car, cdr
 pub fn builtin_list(args: &[Value]) -> Result<Value, EvalError> {
-    Ok(Value::List(args.to_vec()))
+    Ok(Value::List(args.into()))
 }
 
-#[builtin(name = "length", category = "List operations", related(empty?, list))]
+#[builtin(name = "length", category = "List operations", related(empty?, list), params(list))]
 /// Returns the number of elements in a list.
 ///
 /// # Examples
@@ -150,7 +178,7 @@ pub fn builtin_length(args: &[Value]) -> Result<Value, EvalError> {
     }
 }
 
-#[builtin(name = "empty?", category = "List operations", related(length, nil?))]
+#[builtin(name = "empty?", category = "List operations", related(length, nil?), params(list))]
 /// Tests if a list is empty (nil or ()).
 ///
 /// Returns #t for empty lists, #f otherwise.
@@ -177,3 +205,289 @@ pub fn builtin_empty_q(args: &[Value]) -> Result<Value, EvalError> {
         _ => Err(EvalError::type_error("empty?", "list", &args[0], 1)),
     }
 }
+
+#[builtin(
+    name = "flatten",
+    category = "List operations",
+    related(append, interleave),
+    params(list)
+)]
+/// Splices nested lists into one flat list, at every depth.
+///
+/// Maps and other values are kept as single elements. Nested `()` (which is
+/// nil) contributes nothing.
+///
+/// # Examples
+///
+/// ```lisp
+/// (flatten '(1 (2 (3 4)) () 5)) => (1 2 3 4 5)
+/// (flatten '()) => ()
+/// ```
+///
+/// # See Also
+///
+/// append, interleave
+pub fn builtin_flatten(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("flatten", ARITY_ONE, args.len()));
+    }
+
+    fn flatten_into(items: &[Value], out: &mut Vec<Value>) {
+        for item in items {
+            match item {
+                Value::List(nested) => flatten_into(nested, out),
+                Value::Nil => {}
+                other => out.push(other.clone()),
+            }
+        }
+    }
+
+    let mut flat = Vec::new();
+    flatten_into(list_items("flatten", &args[0], 1)?, &mut flat);
+    Ok(Value::List(flat.into()))
+}
+
+#[builtin(name = "partition", category = "List operations", related(filter, group-by), params(pred, list))]
+/// Splits a list by a predicate into two lists: elements that satisfy it, then the rest.
+///
+/// Both lists keep the original order, and the predicate is called once per element.
+///
+/// # Examples
+///
+/// ```lisp
+/// (partition even? '(1 2 3 4 5)) => ((2 4) (1 3 5))
+/// (partition (lambda (x) (> x 10)) '(1 2)) => (() (1 2))
+/// ```
+///
+/// # See Also
+///
+/// filter, group-by
+pub fn builtin_partition(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("partition", ARITY_TWO, args.len()));
+    }
+
+    let (mut matching, mut rest) = (Vec::new(), Vec::new());
+    for item in list_items("partition", &args[1], 2)? {
+        if is_truthy(&apply_function(&args[0], vec![item.clone()])?) {
+            matching.push(item.clone());
+        } else {
+            rest.push(item.clone());
+        }
+    }
+
+    Ok(Value::List(
+        vec![Value::List(matching.into()), Value::List(rest.into())].into(),
+    ))
+}
+
+#[builtin(
+    name = "group-by",
+    category = "List operations",
+    related(partition, frequencies),
+    params(f, list)
+)]
+/// Groups elements into a map keyed by the result of calling f on each one.
+///
+/// Each group is a list in the original order. Keywords, strings and symbols
+/// key by their name; other results key by their printed form, so `1` and
+/// `"1"` share a group.
+///
+/// # Examples
+///
+/// ```lisp
+/// (group-by (lambda (w) (string-length w)) '("a" "bb" "cc")) => {:1 ("a") :2 ("bb" "cc")}
+/// (group-by (lambda (x) (if (even? x) :even :odd)) '(1 2 3 4)) => {:even (2 4) :odd (1 3)}
+/// ```
+///
+/// # See Also
+///
+/// partition, frequencies
+pub fn builtin_group_by(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("group-by", ARITY_TWO, args.len()));
+    }
+
+    let mut groups: HashMap<String, Vec<Value>> = HashMap::new();
+    for item in list_items("group-by", &args[1], 2)? {
+        let key = map_key(&apply_function(&args[0], vec![item.clone()])?);
+        groups.entry(key).or_default().push(item.clone());
+    }
+
+    Ok(Value::Map(
+        groups
+            .into_iter()
+            .map(|(key, items)| (key, Value::List(items.into())))
+            .collect(),
+    ))
+}
+
+#[builtin(name = "frequencies", category = "List operations", related(group-by, distinct), params(list))]
+/// Counts how often each element occurs, as a map from element to count.
+///
+/// Elements key the map the same way as `group-by` keys: keywords, strings and
+/// symbols by name, anything else by its printed form.
+///
+/// # Examples
+///
+/// ```lisp
+/// (frequencies '(a b a c a)) => {:a 3 :b 1 :c 1}
+/// (frequencies '()) => {}
+/// ```
+///
+/// # See Also
+///
+/// group-by, distinct
+pub fn builtin_frequencies(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("frequencies", ARITY_ONE, args.len()));
+    }
+
+    let mut counts: HashMap<String, f64> = HashMap::new();
+    for item in list_items("frequencies", &args[0], 1)? {
+        *counts.entry(map_key(item)).or_default() += 1.0;
+    }
+
+    Ok(Value::Map(
+        counts
+            .into_iter()
+            .map(|(key, count)| (key, Value::Number(count)))
+            .collect(),
+    ))
+}
+
+/// Map key for a grouped value: the name of a keyword, string or symbol, else the printed form
+fn map_key(value: &Value) -> String {
+    match value {
+        Value::Keyword(name) => name.clone(),
+        Value::String(name) => name.to_string(),
+        Value::Symbol(name) => name.to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[builtin(
+    name = "distinct",
+    category = "List operations",
+    related(frequencies),
+    params(list)
+)]
+/// Removes repeated elements, keeping the first occurrence of each in order.
+///
+/// Elements are compared structurally, like `assert-equal`: lists and maps
+/// are equal when their contents are.
+///
+/// # Examples
+///
+/// ```lisp
+/// (distinct '(1 2 1 3 2)) => (1 2 3)
+/// (distinct '((1 2) (1 2) "1" 1)) => ((1 2) "1" 1)
+/// ```
+///
+/// # See Also
+///
+/// frequencies
+pub fn builtin_distinct(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("distinct", ARITY_ONE, args.len()));
+    }
+
+    // Bucket by printed form so each element is only compared with likely duplicates
+    let mut seen: HashMap<String, Vec<usize>> = HashMap::new();
+    let mut unique: Vec<Value> = Vec::new();
+    for item in list_items("distinct", &args[0], 1)? {
+        let bucket = seen.entry(item.to_string()).or_default();
+        if !bucket.iter().any(|&i| values_equal(&unique[i], item)) {
+            bucket.push(unique.len());
+            unique.push(item.clone());
+        }
+    }
+
+    Ok(Value::List(unique.into()))
+}
+
+#[builtin(
+    name = "interleave",
+    category = "List operations",
+    related(flatten, map),
+    params(list, more-lists...)
+)]
+/// Alternates the elements of several lists: the first of each, then the second of each, and so on.
+///
+/// Stops at the end of the shortest list.
+///
+/// # Examples
+///
+/// ```lisp
+/// (interleave '(1 2 3) '(a b c)) => (1 a 2 b 3 c)
+/// (interleave '(1 2 3) '(a b)) => (1 a 2 b)
+/// ```
+///
+/// # See Also
+///
+/// flatten, map
+pub fn builtin_interleave(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::arity_error(
+            "interleave",
+            ARITY_AT_LEAST_ONE,
+            args.len(),
+        ));
+    }
+
+    let lists = args
+        .iter()
+        .enumerate()
+        .map(|(i, arg)| list_items("interleave", arg, i + 1))
+        .collect::<Result<Vec<_>, _>>()?;
+    let len = lists.iter().map(|l| l.len()).min().unwrap_or(0);
+    let mut result = Vec::with_capacity(len * lists.len());
+    for i in 0..len {
+        result.extend(lists.iter().map(|l| l[i].clone()));
+    }
+
+    Ok(Value::List(result.into()))
+}
+
+#[builtin(
+    name = "chunk",
+    category = "List operations",
+    related(partition, take),
+    params(n, list)
+)]
+/// Splits a list into consecutive sublists of n elements; the last may be shorter.
+///
+/// # Examples
+///
+/// ```lisp
+/// (chunk 2 '(1 2 3 4 5)) => ((1 2) (3 4) (5))
+/// (chunk 3 '()) => ()
+/// ```
+///
+/// # See Also
+///
+/// partition, take
+pub fn builtin_chunk(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("chunk", ARITY_TWO, args.len()));
+    }
+
+    let size = match &args[0] {
+        Value::Number(n) if *n >= 1.0 && n.fract() == 0.0 => *n as usize,
+        _ => {
+            return Err(EvalError::type_error(
+                "chunk",
+                "positive integer",
+                &args[0],
+                1,
+            ))
+        }
+    };
+
+    Ok(Value::List(
+        list_items("chunk", &args[1], 2)?
+            .chunks(size)
+            .map(|chunk| Value::List(chunk.into()))
+            .collect(),
+    ))
+}
@@ -12,7 +12,7 @@ use crate::error::{EvalError, ARITY_ONE};
 use crate::value::Value;
 use lisp_macros::builtin;
 
-#[builtin(name = "and", category = "Logic", related(or, not))]
+#[builtin(name = "and", category = "Logic", related(or, not), params(values...))]
 /// Logical AND. Returns #f if any argument is falsy, otherwise returns the last argument.
 ///
 /// Short-circuits: stops evaluating after first falsy value.
@@ -38,7 +38,7 @@ pub fn builtin_and(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(true))
 }
 
-#[builtin(name = "or", category = "Logic", related(and, not))]
+#[builtin(name = "or", category = "Logic", related(and, not), params(values...))]
 /// Logical OR. Returns the first truthy value or #f if all are falsy.
 ///
 /// Short-circuits: stops evaluating after first truthy value.
@@ -64,7 +64,7 @@ pub fn builtin_or(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(false))
 }
 
-#[builtin(name = "not", category = "Logic", related(and, or))]
+#[builtin(name = "not", category = "Logic", related(and, or), params(value))]
 /// Logical NOT. Returns #t if val is falsy (#f or nil), otherwise #f.
 ///
 /// # Examples
@@ -2,14 +2,12 @@
 //!
 //! Functions for working with key-value maps
 
-use crate::error::{
-    EvalError, ARITY_ONE, ARITY_THREE, ARITY_TWO, ARITY_TWO_OR_THREE, ARITY_ZERO_OR_ONE,
-};
+use crate::error::{EvalError, ARITY_ONE, ARITY_THREE, ARITY_TWO, ARITY_TWO_OR_THREE, ARITY_ZERO};
 use crate::value::Value;
 use lisp_macros::builtin;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-#[builtin(name = "map-new", category = "Maps", related(map-get, map-set))]
+#[builtin(name = "map-new", category = "Maps", related(map-get, map-set), params())]
 /// Creates a new empty map.
 ///
 /// # Examples
@@ -23,16 +21,12 @@ use std::collections::HashMap;
 /// map-set, map-get
 pub fn map_new(args: &[Value]) -> Result<Value, EvalError> {
     if !args.is_empty() {
-        return Err(EvalError::arity_error(
-            "map-new",
-            ARITY_ZERO_OR_ONE,
-            args.len(),
-        ));
+        return Err(EvalError::arity_error("map-new", ARITY_ZERO, args.len()));
     }
-    Ok(Value::Map(HashMap::new()))
+    Ok(Value::Map(BTreeMap::new()))
 }
 
-#[builtin(name = "map-get", category = "Maps", related(map-set, map-has?))]
+#[builtin(name = "map-get", category = "Maps", related(map-set, map-has?), params(map, key, [default]))]
 /// Get value from map by keyword key. Returns nil if key not found.
 ///
 /// # Examples
@@ -73,7 +67,7 @@ pub fn map_get(args: &[Value]) -> Result<Value, EvalError> {
     Ok(map.get(key).cloned().unwrap_or_else(|| default.clone()))
 }
 
-#[builtin(name = "map-set", category = "Maps", related(map-get, map-remove))]
+#[builtin(name = "map-set", category = "Maps", related(map-get, map-remove), params(map, key, value))]
 /// Returns a new map with the key set to value (immutable operation).
 ///
 /// # Examples
@@ -108,7 +102,7 @@ pub fn map_set(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Map(new_map))
 }
 
-#[builtin(name = "map-has?", category = "Maps", related(map-get, map-keys))]
+#[builtin(name = "map-has?", category = "Maps", related(map-get, map-keys), params(map, key))]
 /// Check if map contains a key.
 ///
 /// # Examples
@@ -139,8 +133,8 @@ pub fn map_has_q(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(map.contains_key(key)))
 }
 
-#[builtin(name = "map-keys", category = "Maps", related(map-values, map-entries))]
-/// Get list of all keys in map as keywords.
+#[builtin(name = "map-keys", category = "Maps", related(map-values, map-entries), params(map))]
+/// Get list of all keys in map as keywords, in sorted order.
 ///
 /// # Examples
 ///
@@ -162,16 +156,11 @@ pub fn map_keys(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("map-keys", "map", &args[0], 1)),
     };
 
-    let mut keys: Vec<_> = map.keys().map(|k| Value::Keyword(k.clone())).collect();
-    keys.sort_by(|a, b| match (a, b) {
-        (Value::Keyword(k1), Value::Keyword(k2)) => k1.cmp(k2),
-        _ => std::cmp::Ordering::Equal,
-    });
-
+    let keys = map.keys().map(|k| Value::Keyword(k.clone())).collect();
     Ok(Value::List(keys))
 }
 
-#[builtin(name = "map-values", category = "Maps", related(map-keys, map-entries))]
+#[builtin(name = "map-values", category = "Maps", related(map-keys, map-entries), params(map))]
 /// Get list of all values in map.
 ///
 /// # Examples
@@ -194,16 +183,10 @@ pub fn map_values(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("map-values", "map", &args[0], 1)),
     };
 
-    // Sort by keys for consistent ordering
-    let mut entries: Vec<_> = map.iter().collect();
-    entries.sort_by_key(|(k, _)| *k);
-
-    let values: Vec<_> = entries.into_iter().map(|(_, v)| v.clone()).collect();
-
-    Ok(Value::List(values))
+    Ok(Value::List(map.values().cloned().collect()))
 }
 
-#[builtin(name = "map-entries", category = "Maps", related(map-keys, map-values))]
+#[builtin(name = "map-entries", category = "Maps", related(map-keys, map-values), params(map))]
 /// Get list of [key value] pairs from map.
 ///
 /// # Examples
@@ -225,23 +208,14 @@ pub fn map_entries(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("map-entries", "map", &args[0], 1)),
     };
 
-    let mut entries: Vec<_> = map
+    let entries = map
         .iter()
-        .map(|(k, v)| Value::List(vec![Value::Keyword(k.clone()), v.clone()]))
+        .map(|(k, v)| Value::List(vec![Value::Keyword(k.clone()), v.clone()].into()))
         .collect();
-
-    entries.sort_by(|a, b| match (a, b) {
-        (Value::List(l1), Value::List(l2)) => match (&l1[0], &l2[0]) {
-            (Value::Keyword(k1), Value::Keyword(k2)) => k1.cmp(k2),
-            _ => std::cmp::Ordering::Equal,
-        },
-        _ => std::cmp::Ordering::Equal,
-    });
-
     Ok(Value::List(entries))
 }
 
-#[builtin(name = "map-merge", category = "Maps", related(map-set))]
+#[builtin(name = "map-merge", category = "Maps", related(map-set), params(map, other))]
 /// Merge two maps, with second map's values taking precedence.
 ///
 /// # Examples
@@ -277,7 +251,7 @@ pub fn map_merge(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Map(result))
 }
 
-#[builtin(name = "map-remove", category = "Maps", related(map-set, map-has?))]
+#[builtin(name = "map-remove", category = "Maps", related(map-set, map-has?), params(map, key))]
 /// Returns a new map with the key removed.
 ///
 /// # Examples
@@ -309,7 +283,7 @@ pub fn map_remove(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Map(new_map))
 }
 
-#[builtin(name = "map-empty?", category = "Maps", related(map-size))]
+#[builtin(name = "map-empty?", category = "Maps", related(map-size), params(map))]
 /// Check if map is empty.
 ///
 /// # Examples
@@ -335,7 +309,7 @@ pub fn map_empty_q(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(map.is_empty()))
 }
 
-#[builtin(name = "map-size", category = "Maps", related(map-empty?))]
+#[builtin(name = "map-size", category = "Maps", related(map-empty?), params(map))]
 /// Get the number of key-value pairs in map.
 ///
 /// # Examples
@@ -1,6 +1,6 @@
 //! # Built-in Functions Module
 //!
-//! Core built-in functions for the Lisp interpreter, organized into 12 categories with 55 total functions.
+//! Core built-in functions for the Lisp interpreter, organized into 23 categories with 140 total functions.
 //!
 //! ## Naming Convention
 //!
@@ -15,81 +15,229 @@
 //!
 //! ## Categories
 //!
-//! - **[arithmetic]** (5): +, -, *, /, % - Numeric operations
+//! - **[arithmetic]** (6): +, -, *, /, %, divmod - Numeric operations
 //! - **[comparison]** (5): =, <, >, <=, >= - Value comparisons
 //! - **[logic]** (3): and, or, not - Boolean operations
-//! - **[types]** (6): number?, string?, list?, nil?, symbol?, bool? - Type predicates
-//! - **[lists]** (6): cons, car, cdr, list, length, empty? - List manipulation
-//! - **[console]** (2): print, println - Output operations
-//! - **[filesystem]** (5): read-file, write-file, file-exists?, file-size, list-files - File I/O
-//! - **[network]** (2): http-get, http-post - Network requests
-//! - **[errors]** (3): error, error?, error-msg - Error handling
-//! - **[strings]** (17): string-split, string-join, string-append, substring, string-trim, string-upper, string-lower, string-replace, string-contains?, string-starts-with?, string-ends-with?, string-empty?, string-length, string->number, number->string, string->list, list->string - String manipulation
-//! - **[testing]** (6): assert, assert-equal, assert-error, register-test, run-all-tests, clear-tests - Testing and assertions
-//! - **[help_builtins]** (2): help, doc - Documentation system
+//! - **[types]** (8): number?, string?, list?, nil?, symbol?, bool?, map?, keyword? - Type predicates
+//! - **[lists]** (13): cons, car, cdr, list, length, empty?, flatten, partition, group-by, frequencies, distinct, interleave, chunk - List manipulation
+//! - **[maps]** (11): map-new, map-get, map-set, map-has?, map-keys, map-values, map-entries, map-merge, map-remove, map-empty?, map-size - Keyword-keyed maps
+//! - **[higher_order]** (8): map, filter, reduce, for-each, some, every, values, call-with-values - Native list combinators
+//! - **[console]** (3): print, println, pp - Output operations
+//! - **[filesystem]** (6): read-file, write-file, file-exists?, file-size, list-files, file-stat - File I/O
+//! - **[network]** (1): http-request - Network requests (`network` feature)
+//! - **[audit]** (1): audit-entries - Log of sandboxed file and network operations
+//! - **[debug]** (5): trace, untrace, trace-macro, untrace-macro, breakpoint - Printing calls of chosen functions or expansions of chosen macros, and pausing in the debugger
+//! - **[errors]** (5): error, error?, error-msg, error-kind, error-data - Error values with kinds and data
+//! - **[promises]** (2): force, promise? - Forcing promises created by `delay`
+//! - **[concurrency]** (5): spawn, join, make-channel, channel-send, channel-recv - Threads and channels
+//! - **[strings]** (24): string-split, string-join, string-append, str, string-chars, substring, string-trim, string-upper, string-lower, string-replace, string-reverse, string-repeat, string-pad-left, string-pad-right, string-contains?, string-starts-with?, string-ends-with?, string-empty?, string-index-of, string-length, string->number, number->string, string->list, list->string - String manipulation
+//! - **[units]** (4): parse-bytes, format-bytes, parse-duration, format-duration - Human-readable sizes and durations
+//! - **[testing]** (7): assert, assert-equal, assert-error, register-test, run-all-tests, clear-tests, test:snapshot - Testing, assertions and snapshots
+//! - **[session]** (2): save-session, load-session - Checkpointing top-level data
+//! - **[bindings]** (5): freeze, frozen?, deep-copy, defined?, bindings - Protecting and listing global bindings
+//! - **[help_builtins]** (6): help, doc, source, help-search, help-category, apropos - Documentation system
+//! - **[introspection]** (6): procedure?, lambda?, builtin?, macro?, arity, function-params - Inspecting functions and macros
+//! - **[resources]** (4): memory-usage, step-count, io-bytes-read, io-bytes-written - Steps, sandbox I/O and memory used so far
 //!
-//! Each category is a sub-module with its own register function that sets up both the
-//! function bindings and their help documentation entries in the help system registry.
+//! Each function is declared with `#[builtin]` in its category's sub-module and collected
+//! through `inventory`; `register_builtins` is the only place builtins are bound and their
+//! help entries registered, so bindings and documentation come from the same declaration.
 
-use crate::env::Environment;
-use crate::error::EvalError;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ERR_SANDBOX_NOT_INIT};
 use crate::help::HelpEntry;
+use crate::macros::MacroRegistry;
 use crate::sandbox::Sandbox;
 use crate::value::Value;
 use std::cell::RefCell;
-use std::rc::Rc;
+use std::sync::Arc;
 
 // ============================================================================
 // Builtin Auto-Registration Infrastructure
 // ============================================================================
 
+/// Interpreter state for builtins that take it as a second parameter
+///
+/// Declaring `fn f(args: &[Value], ctx: &BuiltinContext)` with `#[builtin]`
+/// replaces reaching for the thread-local slots by hand. Each accessor reads
+/// the running interpreter's state when called and names the builtin in the
+/// error when that state is missing.
+pub struct BuiltinContext {
+    function: &'static str,
+}
+
+impl BuiltinContext {
+    pub fn new(function: &'static str) -> Self {
+        BuiltinContext { function }
+    }
+
+    /// The sandbox that file and network access go through
+    pub fn sandbox(&self) -> Result<Arc<Sandbox>, EvalError> {
+        current_sandbox()
+            .ok_or_else(|| EvalError::runtime_error(self.function, ERR_SANDBOX_NOT_INIT))
+    }
+
+    /// The running interpreter's global environment
+    pub fn env(&self) -> Result<EnvRef, EvalError> {
+        crate::help::current_env()
+            .ok_or_else(|| EvalError::runtime_error(self.function, "no interpreter is running"))
+    }
+
+    /// Macro registry of the evaluation calling the builtin, empty outside one
+    pub fn macros(&self) -> MacroRegistry {
+        crate::eval::active_macros().unwrap_or_default()
+    }
+}
+
 /// Registration entry for a builtin function (auto-collected via inventory)
+#[allow(dead_code)]
 pub struct BuiltinRegistration {
     pub name: &'static str,
     pub function: fn(&[Value]) -> Result<Value, EvalError>,
+    /// What the environment binds: `function`, behind an arity check when `params` bound it
+    /// (for builtins taking a `BuiltinContext`, both are the adapter that supplies it)
+    pub call: fn(&[Value]) -> Result<Value, EvalError>,
     pub signature: &'static str,
     pub description: &'static str,
     pub examples: &'static [&'static str],
     pub related: &'static [&'static str],
     pub category: &'static str,
+    /// Parameter names from `params(...)`: `[x]` is optional and `x...` takes the rest
+    pub params: &'static [&'static str],
+    pub min_arity: usize,
+    /// None when the builtin takes any number of trailing arguments
+    pub max_arity: Option<usize>,
+    /// Capability module that removes this builtin, for ones that reach outside the
+    /// interpreter (see [`crate::config::CAPABILITY_MODULES`])
+    pub capability: Option<&'static str>,
 }
 
 // Collect all builtin registrations at compile time
 inventory::collect!(BuiltinRegistration);
 
+/// Names of every registered builtin function
+pub fn builtin_names() -> impl Iterator<Item = &'static str> {
+    inventory::iter::<BuiltinRegistration>
+        .into_iter()
+        .map(|builtin| builtin.name)
+}
+
+/// Registered name of a builtin function, for reports that print code
+///
+/// The optimizer replaces calls to builtins with the function itself, so code
+/// inside function bodies holds `Value::BuiltIn` where the source had a name.
+pub fn builtin_name(function: fn(&[Value]) -> Result<Value, EvalError>) -> Option<&'static str> {
+    builtin_registration(function).map(|builtin| builtin.name)
+}
+
+/// Registration of a builtin function value, found by its function pointer
+pub fn builtin_registration(
+    function: fn(&[Value]) -> Result<Value, EvalError>,
+) -> Option<&'static BuiltinRegistration> {
+    inventory::iter::<BuiltinRegistration>
+        .into_iter()
+        .find(|builtin| {
+            std::ptr::fn_addr_eq(builtin.call, function)
+                || std::ptr::fn_addr_eq(builtin.function, function)
+        })
+}
+
+/// Registration of the builtin with this name, for tools that check calls without running them
+#[allow(dead_code)]
+pub fn find_builtin(name: &str) -> Option<&'static BuiltinRegistration> {
+    inventory::iter::<BuiltinRegistration>
+        .into_iter()
+        .find(|builtin| builtin.name == name)
+}
+
+/// Fail with an arity error unless `actual` is within `min..=max`
+///
+/// Called before every builtin that declares its parameters, so the function
+/// body can index its arguments without checking the count again.
+pub fn check_arity(
+    name: &str,
+    min: usize,
+    max: Option<usize>,
+    actual: usize,
+) -> Result<(), EvalError> {
+    if actual >= min && max.is_none_or(|max| actual <= max) {
+        return Ok(());
+    }
+    let expected = match max {
+        Some(max) if max == min => min.to_string(),
+        Some(max) => format!("{}-{}", min, max),
+        None => format!("at least {}", min),
+    };
+    Err(EvalError::arity_error(name, expected, actual))
+}
+
 // ============================================================================
 // Sandbox Storage for I/O Built-in Functions
 // ============================================================================
 
 thread_local! {
-    static SANDBOX: RefCell<Option<Sandbox>> = const { RefCell::new(None) };
+    static SANDBOX: RefCell<Option<Arc<Sandbox>>> = const { RefCell::new(None) };
 }
 
 /// Initialize the sandbox for I/O built-in functions
+#[allow(dead_code)]
 pub fn set_sandbox_storage(sandbox: Sandbox) {
     SANDBOX.with(|s| {
-        *s.borrow_mut() = Some(sandbox);
+        *s.borrow_mut() = Some(Arc::new(sandbox));
     });
 }
 
+/// Replace this thread's sandbox, returning the previous one
+pub(crate) fn swap_sandbox(sandbox: Option<Arc<Sandbox>>) -> Option<Arc<Sandbox>> {
+    SANDBOX.with(|s| std::mem::replace(&mut *s.borrow_mut(), sandbox))
+}
+
+/// This thread's sandbox, for handing to threads started by `spawn`
+pub(crate) fn current_sandbox() -> Option<Arc<Sandbox>> {
+    SANDBOX.with(|s| s.borrow().clone())
+}
+
+/// Whether this thread's sandbox froze the clock (`--deterministic`)
+///
+/// Timings (`time`, `bench`, profiles) then read as zero, so a run prints the
+/// same thing every time.
+pub(crate) fn clock_frozen() -> bool {
+    SANDBOX.with(|s| {
+        s.borrow()
+            .as_ref()
+            .is_some_and(|sandbox| sandbox.clock_frozen())
+    })
+}
+
 // ============================================================================
 // Sub-modules
 // ============================================================================
 
 pub mod arithmetic;
+pub mod audit;
+pub mod bindings;
 pub mod comparison;
+pub mod concurrency;
 pub mod console;
+pub mod debug;
 pub mod errors;
 pub mod filesystem;
 #[path = "help.rs"]
 pub mod help_builtins;
+pub mod higher_order;
+pub mod introspection;
 pub mod lists;
 pub mod logic;
 pub mod maps;
+#[cfg(feature = "network")]
 pub mod network;
+pub mod promises;
+pub mod resources;
+pub mod session;
 pub mod strings;
 pub mod testing;
 pub mod types;
+pub mod units;
 
 // ============================================================================
 // Main Registration Function (Auto-Registration via Inventory)
@@ -99,10 +247,10 @@ pub mod types;
 ///
 /// This function automatically discovers and registers all functions marked with
 /// #[builtin] across all modules via the inventory crate's compile-time collection.
-pub fn register_builtins(env: Rc<Environment>) {
+pub fn register_builtins(env: EnvRef) {
     // Automatically iterate over all collected builtins
     for builtin in inventory::iter::<BuiltinRegistration> {
-        env.define(builtin.name.to_string(), Value::BuiltIn(builtin.function));
+        env.define(builtin.name.to_string(), Value::BuiltIn(builtin.call));
 
         // Convert static help data to HelpEntry
         crate::help::register_help(HelpEntry {
@@ -113,9 +261,8 @@ pub fn register_builtins(env: Rc<Environment>) {
             related: builtin.related.iter().map(|s| s.to_string()).collect(),
             category: builtin.category.to_string(),
         });
+        if let Some(capability) = builtin.capability {
+            crate::help::register_capability(builtin.name, capability);
+        }
     }
-
-    // Note: help_builtins module still needs manual registration since it uses
-    // special forms and environment access (not simple builtin functions)
-    help_builtins::register(&env);
 }
@@ -7,14 +7,15 @@
 //!
 //! All requests are checked against a URL allowlist for safety
 
-use crate::error::{EvalError, ARITY_TWO, ERR_SANDBOX_NOT_INIT};
+use crate::error::{EvalError, ARITY_TWO};
+use crate::sandbox::HttpResponse;
 use crate::value::Value;
 use lisp_macros::builtin;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
-use super::SANDBOX;
+use super::BuiltinContext;
 
-#[builtin(name = "http-request", category = "Network I/O")]
+#[builtin(name = "http-request", capability = "network", category = "Network I/O", related(http:body, http:get-many), params(url, options))]
 /// Performs a flexible HTTP request with specified method and options.
 ///
 /// URL must be in allowed addresses list. Options is a map with:
@@ -34,8 +35,8 @@ use super::SANDBOX;
 ///
 /// # See Also
 ///
-/// http-get, http-post
-pub fn http_request(args: &[Value]) -> Result<Value, EvalError> {
+/// http:body, http:get-many
+pub fn http_request(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
     if args.len() != 2 {
         return Err(EvalError::arity_error(
             "http-request",
@@ -54,95 +55,116 @@ pub fn http_request(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("http-request", "map", &args[1], 2)),
     };
 
-    // Extract method (required)
-    let method = match options.get("method") {
-        Some(Value::String(m)) => m.clone(),
+    let (method, headers, body, timeout) = request_options("http-request", options, None)?;
+
+    let sandbox = ctx.sandbox()?;
+
+    let response = sandbox
+        .http_request(url, &method, headers, body.as_deref(), timeout)
+        .map_err(|e| EvalError::Sandbox {
+            function: "http-request".to_string(),
+            message: format!("HTTP {} request to '{}' failed: {}", method, url, e),
+            error: e,
+        })?;
+
+    Ok(response_to_value(response))
+}
+
+/// The parts of a request taken from `http-request` style options
+pub(crate) type RequestOptions = (
+    String,
+    Option<Vec<(String, String)>>,
+    Option<String>,
+    Option<u64>,
+);
+
+/// Read `:method`, `:headers`, `:body` and `:timeout` from an options map
+///
+/// `:method` is required unless `default_method` is given.
+pub(crate) fn request_options(
+    function: &str,
+    options: &BTreeMap<String, Value>,
+    default_method: Option<&str>,
+) -> Result<RequestOptions, EvalError> {
+    let method = match (options.get("method"), default_method) {
+        (Some(Value::String(m)), _) => m.clone(),
+        (None, Some(default)) => default.into(),
         _ => {
             return Err(EvalError::runtime_error(
-                "http-request",
+                function,
                 "missing or invalid :method in options",
             ))
         }
     };
 
-    // Extract optional headers map
-    let headers = match options.get("headers") {
-        Some(Value::Map(h)) => {
-            let mut header_vec = Vec::new();
-            for (k, v) in h.iter() {
-                match v {
-                    Value::String(val) => header_vec.push((k.clone(), val.clone())),
-                    _ => {
-                        return Err(EvalError::runtime_error(
-                            "http-request",
-                            "header values must be strings",
-                        ))
-                    }
-                }
-            }
-            Some(header_vec)
-        }
-        None => None,
-        _ => {
-            return Err(EvalError::runtime_error(
-                "http-request",
-                "invalid :headers in options",
-            ))
-        }
-    };
+    let headers = header_list(function, options.get("headers"))?;
 
-    // Extract optional body
     let body = match options.get("body") {
-        Some(Value::String(b)) => Some(b.as_str()),
+        Some(Value::String(b)) => Some(b.to_string()),
         None => None,
-        _ => {
+        Some(other) => {
             return Err(EvalError::runtime_error(
-                "http-request",
-                "body must be a string",
+                function,
+                format!(":body must be a string, got {}", other),
             ))
         }
     };
 
-    // Extract optional timeout
     let timeout = match options.get("timeout") {
         Some(Value::Number(t)) => Some(*t as u64),
         None => None,
-        _ => {
+        Some(other) => {
             return Err(EvalError::runtime_error(
-                "http-request",
-                "timeout must be a number",
+                function,
+                format!(":timeout must be a number, got {}", other),
             ))
         }
     };
+    Ok((method.to_string(), headers, body, timeout))
+}
 
-    SANDBOX.with(|s| {
-        let sandbox_ref = s.borrow();
-        let sandbox = sandbox_ref
-            .as_ref()
-            .ok_or_else(|| EvalError::runtime_error("http-request", ERR_SANDBOX_NOT_INIT))?;
-
-        let response = sandbox
-            .http_request(url, &method, headers, body, timeout)
-            .map_err(|e| {
-                EvalError::runtime_error(
-                    "http-request",
-                    format!("HTTP {} request to '{}' failed: {}", method, url, e),
-                )
-            })?;
-
-        // Build response map
-        let mut response_map = HashMap::new();
-        response_map.insert("status".to_string(), Value::Number(response.status as f64));
-
-        // Build headers map
-        let mut headers_map = HashMap::new();
-        for (k, v) in response.headers.iter() {
-            headers_map.insert(k.clone(), Value::String(v.clone()));
+/// Convert an optional `:headers` option into name/value pairs
+pub(crate) fn header_list(
+    function: &str,
+    headers: Option<&Value>,
+) -> Result<Option<Vec<(String, String)>>, EvalError> {
+    match headers {
+        Some(Value::Map(h)) => {
+            let mut header_vec = Vec::new();
+            for (k, v) in h.iter() {
+                match v {
+                    Value::String(val) => header_vec.push((k.clone(), val.to_string())),
+                    other => {
+                        return Err(EvalError::runtime_error(
+                            function,
+                            format!("header values must be strings, got {} for {}", other, k),
+                        ))
+                    }
+                }
+            }
+            Ok(Some(header_vec))
         }
-        response_map.insert("headers".to_string(), Value::Map(headers_map));
+        None => Ok(None),
+        _ => Err(EvalError::runtime_error(
+            function,
+            "invalid :headers in options",
+        )),
+    }
+}
+
+/// Build the `{:status :headers :body}` map returned to Lisp code
+pub(crate) fn response_to_value(response: HttpResponse) -> Value {
+    let mut response_map = BTreeMap::new();
+    response_map.insert("status".to_string(), Value::Number(response.status as f64));
+
+    // Build headers map
+    let mut headers_map = BTreeMap::new();
+    for (k, v) in response.headers.iter() {
+        headers_map.insert(k.clone(), Value::String(v.as_str().into()));
+    }
+    response_map.insert("headers".to_string(), Value::Map(headers_map));
 
-        response_map.insert("body".to_string(), Value::String(response.body));
+    response_map.insert("body".to_string(), Value::String(response.body.into()));
 
-        Ok(Value::Map(response_map))
-    })
+    Value::Map(response_map)
 }
@@ -0,0 +1,85 @@
+//! Promise operations: force, promise?
+//!
+//! Promises are created by the `delay` special form and evaluated on demand.
+//!
+//! - `force`: Evaluate a promise (once) and return its value
+//! - `promise?`: Test if value is a promise
+//!
+//! Forcing memoizes the result, so the delayed expression runs at most once
+
+use crate::error::{EvalError, ARITY_ONE};
+use crate::eval::eval_from_builtin;
+use crate::value::{Promise, Value};
+use lisp_macros::builtin;
+
+#[builtin(name = "force", category = "Promises", related(delay, promise?), params(promise))]
+/// Evaluates a promise created by delay and returns its value.
+///
+/// The first force evaluates the delayed expression and caches the result;
+/// later forces return the cached value. Forcing a non-promise returns it unchanged.
+///
+/// # Examples
+///
+/// ```lisp
+/// (force (delay (+ 1 2))) => 3
+/// (define p (delay (begin (println "once") 42)))
+/// (force p) => 42 (prints "once")
+/// (force p) => 42 (cached, prints nothing)
+/// (force 5) => 5
+/// ```
+///
+/// # See Also
+///
+/// delay, promise?
+pub fn builtin_force(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("force", ARITY_ONE, args.len()));
+    }
+
+    let promise = match &args[0] {
+        Value::Promise(promise) => promise,
+        other => return Ok(other.clone()),
+    };
+
+    // Release the lock before evaluating so the expression may force this promise
+    let (expr, env) = match &*promise.lock().unwrap_or_else(|e| e.into_inner()) {
+        Promise::Forced(value) => return Ok(value.clone()),
+        Promise::Delayed { expr, env } => (expr.clone(), env.clone()),
+    };
+
+    let value = eval_from_builtin(expr, env)?;
+
+    // A reentrant force may have finished first; its value wins
+    let mut state = promise.lock().unwrap_or_else(|e| e.into_inner());
+    if let Promise::Forced(existing) = &*state {
+        return Ok(existing.clone());
+    }
+    *state = Promise::Forced(value.clone());
+    Ok(value)
+}
+
+#[builtin(
+    name = "promise?",
+    category = "Promises",
+    related(delay, force),
+    params(value)
+)]
+/// Tests if val is a promise created by delay.
+///
+/// # Examples
+///
+/// ```lisp
+/// (promise? (delay 1)) => #t
+/// (promise? 1) => #f
+/// ```
+///
+/// # See Also
+///
+/// delay, force
+pub fn builtin_promise_p(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("promise?", ARITY_ONE, args.len()));
+    }
+
+    Ok(Value::Bool(matches!(args[0], Value::Promise(_))))
+}
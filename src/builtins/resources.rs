@@ -0,0 +1,118 @@
+//! Resource usage: memory-usage, step-count, io-bytes-read, io-bytes-written
+//!
+//! Functions that let a script see what it has used, so an agent can stay
+//! within its limits or report its footprint.
+//!
+//! - `memory-usage`: Global bindings, their estimated size and process memory
+//! - `step-count`: Evaluation steps taken by the current top-level evaluation
+//! - `io-bytes-read`: Bytes read from files and received over the network
+//! - `io-bytes-written`: Bytes written to files and sent over WebSockets
+//!
+//! Hosts read the same figures with `Interpreter::resource_usage`.
+
+use crate::builtins::{current_sandbox, BuiltinContext};
+use crate::error::EvalError;
+use crate::usage::ResourceUsage;
+use crate::value::Value;
+use lisp_macros::builtin;
+use std::collections::BTreeMap;
+
+#[builtin(name = "memory-usage", category = "Resource usage", related(step-count, io-bytes-read), params())]
+/// Returns a map describing memory use: :bindings, :global-bytes and :resident-bytes.
+///
+/// :bindings counts the global bindings, builtins included, and
+/// :global-bytes estimates what their values hold (shared data is counted at
+/// each reference). :resident-bytes is the resident memory of the whole
+/// process, or nil where it is not known.
+///
+/// # Examples
+///
+/// ```lisp
+/// (memory-usage) => {:bindings 244 :global-bytes 103088 :resident-bytes 16723968}
+/// (define text (string-repeat "x" 100000))
+/// (map-get (memory-usage) :global-bytes) => 203188
+/// ```
+///
+/// # See Also
+///
+/// step-count, io-bytes-read
+pub fn builtin_memory_usage(_args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let env = ctx.env()?;
+    let usage = ResourceUsage::measure(&env, current_sandbox().as_deref(), current_steps());
+    let mut map = BTreeMap::new();
+    map.insert("bindings".to_string(), Value::Number(usage.bindings as f64));
+    map.insert(
+        "global-bytes".to_string(),
+        Value::Number(usage.global_bytes as f64),
+    );
+    map.insert(
+        "resident-bytes".to_string(),
+        usage
+            .resident_bytes
+            .map_or(Value::Nil, |bytes| Value::Number(bytes as f64)),
+    );
+    Ok(Value::Map(map))
+}
+
+#[builtin(name = "step-count", category = "Resource usage", related(memory-usage), params())]
+/// Returns the number of evaluation steps taken so far by the current top-level evaluation.
+///
+/// Steps are what a step limit counts; threads started with spawn add to
+/// the same count. Compare against a budget to stop early instead of
+/// hitting the limit.
+///
+/// # Examples
+///
+/// ```lisp
+/// (step-count) => 4
+/// (begin (+ 1 2) (step-count)) => 9
+/// ```
+///
+/// # See Also
+///
+/// memory-usage
+pub fn builtin_step_count(_args: &[Value]) -> Result<Value, EvalError> {
+    Ok(Value::Number(current_steps() as f64))
+}
+
+/// Steps counted by the running evaluation's cancellation token, 0 outside an interpreter
+fn current_steps() -> u64 {
+    crate::eval::active_cancellation().map_or(0, |token| token.steps())
+}
+
+#[builtin(name = "io-bytes-read", category = "Resource usage", related(io-bytes-written, audit-entries), params())]
+/// Returns the bytes read from files and received over the network through the sandbox so far.
+///
+/// # Examples
+///
+/// ```lisp
+/// (write-file "notes.txt" "hello")
+/// (read-file "notes.txt")
+/// (io-bytes-read) => 5
+/// ```
+///
+/// # See Also
+///
+/// io-bytes-written, audit-entries
+pub fn builtin_io_bytes_read(_args: &[Value]) -> Result<Value, EvalError> {
+    let bytes = current_sandbox().map_or(0, |sandbox| sandbox.bytes_read());
+    Ok(Value::Number(bytes as f64))
+}
+
+#[builtin(name = "io-bytes-written", category = "Resource usage", related(io-bytes-read, audit-entries), params())]
+/// Returns the bytes written to files and sent over WebSockets through the sandbox so far.
+///
+/// # Examples
+///
+/// ```lisp
+/// (write-file "notes.txt" "hello")
+/// (io-bytes-written) => 5
+/// ```
+///
+/// # See Also
+///
+/// io-bytes-read, audit-entries
+pub fn builtin_io_bytes_written(_args: &[Value]) -> Result<Value, EvalError> {
+    let bytes = current_sandbox().map_or(0, |sandbox| sandbox.bytes_written());
+    Ok(Value::Number(bytes as f64))
+}
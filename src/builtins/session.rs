@@ -0,0 +1,81 @@
+//! Session persistence: save-session, load-session
+//!
+//! Checkpoint the data defined at top level so a long-running session can be
+//! resumed after a restart.
+//!
+//! - `save-session`: Write the global environment's data bindings to a file
+//! - `load-session`: Define the bindings saved in a file
+//!
+//! Numbers, strings, booleans, symbols, keywords, nil, errors, and lists and
+//! maps of those are saved; functions, macros, promises, threads and channels
+//! are skipped. Files go through the sandbox like `write-file` and `read-file`.
+
+use crate::builtins::BuiltinContext;
+use crate::error::{EvalError, ARITY_ONE};
+use crate::value::Value;
+use lisp_macros::builtin;
+
+#[builtin(name = "save-session", capability = "sessions", category = "Sessions", related(load-session, write-file), params(path))]
+/// Saves every top-level data binding to a file. Returns the number saved.
+///
+/// Functions and other values without a saved form are skipped.
+///
+/// # Examples
+///
+/// ```lisp
+/// (define results (list 1 2 3))
+/// (save-session "data/session.json") => 1
+/// ```
+///
+/// # See Also
+///
+/// load-session, write-file
+pub fn builtin_save_session(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let path = path_arg("save-session", args)?;
+    let env = ctx.env()?;
+    let sandbox = ctx.sandbox()?;
+
+    let (json, count) = crate::session::save(&env);
+    sandbox
+        .write_file(path, &json)
+        .map_err(|e| EvalError::sandbox_error("save-session", e))?;
+    Ok(Value::Number(count as f64))
+}
+
+#[builtin(name = "load-session", capability = "sessions", category = "Sessions", related(save-session, read-file), params(path))]
+/// Defines the bindings saved by save-session. Returns the number loaded.
+///
+/// Saved bindings replace existing definitions with the same names.
+///
+/// # Examples
+///
+/// ```lisp
+/// (load-session "data/session.json") => 1
+/// results => (1 2 3)
+/// ```
+///
+/// # See Also
+///
+/// save-session, read-file
+pub fn builtin_load_session(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    let path = path_arg("load-session", args)?;
+    let env = ctx.env()?;
+    let sandbox = ctx.sandbox()?;
+
+    let json = sandbox
+        .read_file(path)
+        .map_err(|e| EvalError::sandbox_error("load-session", e))?;
+    let count = crate::session::restore(&env, &json)
+        .map_err(|e| EvalError::runtime_error("load-session", e))?;
+    Ok(Value::Number(count as f64))
+}
+
+fn path_arg<'a>(function: &str, args: &'a [Value]) -> Result<&'a str, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(function, ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(path) => Ok(path),
+        other => Err(EvalError::type_error(function, "string", other, 1)),
+    }
+}
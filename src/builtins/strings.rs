@@ -1,18 +1,24 @@
 //! String manipulation operations
 //!
 //! Comprehensive string manipulation functions including:
-//! - Splitting and joining: string-split, string-join, string-append
+//! - Splitting and joining: string-split, string-join, string-append, string-chars, str
 //! - Extraction: substring, string-trim
-//! - Transformation: string-upper, string-lower, string-replace
+//! - Transformation: string-upper, string-lower, string-replace, string-reverse, string-repeat
+//! - Padding: string-pad-left, string-pad-right
 //! - Predicates: string-contains?, string-starts-with?, string-ends-with?, string-empty?
+//! - Searching: string-index-of
 //! - Conversion: string->number, number->string, string->list, list->string
 //! - Measurement: string-length
+//!
+//! Lengths, widths and indices count characters, not bytes.
 
-use crate::error::{EvalError, ARITY_ONE, ARITY_THREE, ARITY_TWO};
-use crate::value::Value;
+use crate::error::{
+    EvalError, ARITY_ONE, ARITY_ONE_OR_TWO, ARITY_THREE, ARITY_TWO, ARITY_TWO_OR_THREE,
+};
+use crate::value::{write_value, PrintStyle, Value};
 use lisp_macros::builtin;
 
-#[builtin(name = "string-split", category = "String manipulation", related(string-join, substring))]
+#[builtin(name = "string-split", category = "String manipulation", related(string-join, substring), params(string, delimiter))]
 /// Split a string by delimiter into a list of strings.
 ///
 /// # Examples
@@ -44,14 +50,14 @@ pub fn builtin_string_split(args: &[Value]) -> Result<Value, EvalError> {
     };
 
     let parts: Vec<Value> = string
-        .split(delimiter.as_str())
-        .map(|s| Value::String(s.to_string()))
+        .split(&**delimiter)
+        .map(|s| Value::String(s.into()))
         .collect();
 
-    Ok(Value::List(parts))
+    Ok(Value::List(parts.into()))
 }
 
-#[builtin(name = "string-join", category = "String manipulation", related(string-split, string-append))]
+#[builtin(name = "string-join", category = "String manipulation", related(string-split, string-append), params(strings, separator))]
 /// Join a list of strings with delimiter.
 ///
 /// # Examples
@@ -82,7 +88,7 @@ pub fn builtin_string_join(args: &[Value]) -> Result<Value, EvalError> {
         .iter()
         .enumerate()
         .map(|(i, v)| match v {
-            Value::String(s) => Ok(s.clone()),
+            Value::String(s) => Ok(s.to_string()),
             _ => Err(EvalError::runtime_error(
                 "string-join",
                 format!("element {} is not a string", i),
@@ -90,10 +96,10 @@ pub fn builtin_string_join(args: &[Value]) -> Result<Value, EvalError> {
         })
         .collect();
 
-    Ok(Value::String(strings?.join(delimiter)))
+    Ok(Value::String(strings?.join(delimiter).into()))
 }
 
-#[builtin(name = "substring", category = "String manipulation", related(string-split, string-trim))]
+#[builtin(name = "substring", category = "String manipulation", related(string-split, string-trim), params(string, start, end))]
 /// Extract substring from start index (inclusive) to end index (exclusive).
 ///
 /// # Examples
@@ -154,13 +160,14 @@ pub fn builtin_substring(args: &[Value]) -> Result<Value, EvalError> {
     }
 
     let result: String = chars[start..end].iter().collect();
-    Ok(Value::String(result))
+    Ok(Value::String(result.into()))
 }
 
 #[builtin(
     name = "string-trim",
     category = "String manipulation",
-    related(substring)
+    related(substring),
+    params(string)
 )]
 /// Trim whitespace from both ends of string.
 ///
@@ -183,10 +190,10 @@ pub fn builtin_string_trim(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("string-trim", "string", &args[0], 1)),
     };
 
-    Ok(Value::String(string.trim().to_string()))
+    Ok(Value::String(string.trim().into()))
 }
 
-#[builtin(name = "string-upper", category = "String manipulation", related(string-lower))]
+#[builtin(name = "string-upper", category = "String manipulation", related(string-lower), params(string))]
 /// Convert string to uppercase.
 ///
 /// # Examples
@@ -212,10 +219,10 @@ pub fn builtin_string_upper(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("string-upper", "string", &args[0], 1)),
     };
 
-    Ok(Value::String(string.to_uppercase()))
+    Ok(Value::String(string.to_uppercase().into()))
 }
 
-#[builtin(name = "string-lower", category = "String manipulation", related(string-upper))]
+#[builtin(name = "string-lower", category = "String manipulation", related(string-upper), params(string))]
 /// Convert string to lowercase.
 ///
 /// # Examples
@@ -241,10 +248,10 @@ pub fn builtin_string_lower(args: &[Value]) -> Result<Value, EvalError> {
         _ => return Err(EvalError::type_error("string-lower", "string", &args[0], 1)),
     };
 
-    Ok(Value::String(string.to_lowercase()))
+    Ok(Value::String(string.to_lowercase().into()))
 }
 
-#[builtin(name = "string-replace", category = "String manipulation", related(string-contains?))]
+#[builtin(name = "string-replace", category = "String manipulation", related(string-contains?), params(string, pattern, replacement))]
 /// Replace all occurrences of pattern with replacement in string.
 ///
 /// # Examples
@@ -301,10 +308,12 @@ pub fn builtin_string_replace(args: &[Value]) -> Result<Value, EvalError> {
         }
     };
 
-    Ok(Value::String(string.replace(pattern, replacement)))
+    Ok(Value::String(
+        string.replace(&**pattern, replacement).into(),
+    ))
 }
 
-#[builtin(name = "string-contains?", category = "String manipulation", related(string-starts-with?, string-ends-with?))]
+#[builtin(name = "string-contains?", category = "String manipulation", related(string-starts-with?, string-ends-with?), params(string, substring))]
 /// Check if string contains substring.
 ///
 /// # Examples
@@ -349,10 +358,10 @@ pub fn builtin_string_contains(args: &[Value]) -> Result<Value, EvalError> {
         }
     };
 
-    Ok(Value::Bool(string.contains(substring.as_str())))
+    Ok(Value::Bool(string.contains(&**substring)))
 }
 
-#[builtin(name = "string-starts-with?", category = "String manipulation", related(string-ends-with?, string-contains?))]
+#[builtin(name = "string-starts-with?", category = "String manipulation", related(string-ends-with?, string-contains?), params(string, prefix))]
 /// Check if string starts with prefix.
 ///
 /// # Examples
@@ -397,10 +406,10 @@ pub fn builtin_string_starts_with(args: &[Value]) -> Result<Value, EvalError> {
         }
     };
 
-    Ok(Value::Bool(string.starts_with(prefix.as_str())))
+    Ok(Value::Bool(string.starts_with(&**prefix)))
 }
 
-#[builtin(name = "string-ends-with?", category = "String manipulation", related(string-starts-with?, string-contains?))]
+#[builtin(name = "string-ends-with?", category = "String manipulation", related(string-starts-with?, string-contains?), params(string, suffix))]
 /// Check if string ends with suffix.
 ///
 /// # Examples
@@ -445,10 +454,10 @@ pub fn builtin_string_ends_with(args: &[Value]) -> Result<Value, EvalError> {
         }
     };
 
-    Ok(Value::Bool(string.ends_with(suffix.as_str())))
+    Ok(Value::Bool(string.ends_with(&**suffix)))
 }
 
-#[builtin(name = "string-empty?", category = "String manipulation", related(string-length))]
+#[builtin(name = "string-empty?", category = "String manipulation", related(string-length), params(string))]
 /// Check if string is empty.
 ///
 /// # Examples
@@ -484,7 +493,7 @@ pub fn builtin_string_empty(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(string.is_empty()))
 }
 
-#[builtin(name = "string-length", category = "String manipulation", related(string-empty?))]
+#[builtin(name = "string-length", category = "String manipulation", related(string-empty?), params(string))]
 /// Get the length of a string (in characters, not bytes).
 ///
 /// # Examples
@@ -520,7 +529,7 @@ pub fn builtin_string_length(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Number(string.chars().count() as f64))
 }
 
-#[builtin(name = "string->number", category = "String manipulation", related(number->string))]
+#[builtin(name = "string->number", category = "String manipulation", related(number->string), params(string))]
 /// Convert string to number.
 ///
 /// # Examples
@@ -555,33 +564,40 @@ pub fn builtin_string_to_number(args: &[Value]) -> Result<Value, EvalError> {
 
     match string.trim().parse::<f64>() {
         Ok(n) => Ok(Value::Number(n)),
-        Err(_) => Ok(Value::Error(format!("Cannot parse '{}' as number", string))),
+        Err(_) => Ok(Value::error(format!("Cannot parse '{}' as number", string))),
     }
 }
 
-#[builtin(name = "number->string", category = "String manipulation", related(string->number))]
-/// Convert number to string.
+#[builtin(name = "number->string", category = "String manipulation", related(string->number, string-pad-left), params(number, [options]))]
+/// Convert number to string, optionally with a fixed precision or in another radix.
+///
+/// Whole numbers print without a decimal point. The optional map takes
+/// `:precision`, the number of digits after the decimal point, or `:radix`, a
+/// base from 2 to 36 for printing integers (digits above 9 are lowercase).
 ///
 /// # Examples
 ///
 /// ```lisp
 /// (number->string 42) => "42"
+/// (number->string 3.14159 {:precision 2}) => "3.14"
+/// (number->string 255 {:radix 16}) => "ff"
+/// (number->string -5 {:radix 2}) => "-101"
 /// ```
 ///
 /// # See Also
 ///
-/// string->number
+/// string->number, string-pad-left
 pub fn builtin_number_to_string(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 1 {
+    if args.is_empty() || args.len() > 2 {
         return Err(EvalError::arity_error(
             "number->string",
-            ARITY_ONE,
+            ARITY_ONE_OR_TWO,
             args.len(),
         ));
     }
 
     let number = match &args[0] {
-        Value::Number(n) => n,
+        Value::Number(n) => *n,
         _ => {
             return Err(EvalError::type_error(
                 "number->string",
@@ -592,17 +608,80 @@ pub fn builtin_number_to_string(args: &[Value]) -> Result<Value, EvalError> {
         }
     };
 
-    // Format nicely: if it's a whole number, don't show decimal point
-    let result = if number.fract() == 0.0 {
-        format!("{:.0}", number)
-    } else {
-        format!("{}", number)
+    let (precision, radix) = match args.get(1) {
+        None => (None, None),
+        Some(Value::Map(options)) => (
+            count_option("number->string", options.get("precision"), "precision")?,
+            count_option("number->string", options.get("radix"), "radix")?,
+        ),
+        Some(other) => return Err(EvalError::type_error("number->string", "map", other, 2)),
+    };
+
+    let result = match (precision, radix) {
+        (Some(_), Some(_)) => {
+            return Err(EvalError::runtime_error(
+                "number->string",
+                ":precision and :radix cannot be combined",
+            ))
+        }
+        (Some(precision), None) => format!("{:.*}", precision, number),
+        (None, Some(radix)) => format_radix(number, radix)?,
+        // Format nicely: if it's a whole number, don't show decimal point
+        (None, None) if number.fract() == 0.0 => format!("{:.0}", number),
+        (None, None) => format!("{}", number),
     };
 
-    Ok(Value::String(result))
+    Ok(Value::String(result.into()))
+}
+
+/// A non-negative whole number from an options map, if the option is present
+fn count_option(
+    function: &str,
+    value: Option<&Value>,
+    option: &str,
+) -> Result<Option<usize>, EvalError> {
+    match value {
+        None | Some(Value::Nil) => Ok(None),
+        Some(Value::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => Ok(Some(*n as usize)),
+        Some(other) => Err(EvalError::runtime_error(
+            function,
+            format!(":{} must be a non-negative integer, got {}", option, other),
+        )),
+    }
 }
 
-#[builtin(name = "string->list", category = "String manipulation", related(list->string))]
+/// Digits of an integer in base 2 to 36
+fn format_radix(number: f64, radix: usize) -> Result<String, EvalError> {
+    if !(2..=36).contains(&radix) {
+        return Err(EvalError::runtime_error(
+            "number->string",
+            format!(":radix must be between 2 and 36, got {}", radix),
+        ));
+    }
+    if number.fract() != 0.0 || !number.is_finite() {
+        return Err(EvalError::runtime_error(
+            "number->string",
+            format!(":radix needs an integer, got {}", number),
+        ));
+    }
+
+    let mut magnitude = number.abs() as u128;
+    let mut digits = Vec::new();
+    loop {
+        let digit = (magnitude % radix as u128) as u32;
+        digits.push(std::char::from_digit(digit, radix as u32).unwrap_or('?'));
+        magnitude /= radix as u128;
+        if magnitude == 0 {
+            break;
+        }
+    }
+    if number < 0.0 {
+        digits.push('-');
+    }
+    Ok(digits.into_iter().rev().collect())
+}
+
+#[builtin(name = "string->list", category = "String manipulation", related(list->string), params(string))]
 /// Convert string to list of characters.
 ///
 /// # Examples
@@ -630,13 +709,13 @@ pub fn builtin_string_to_list(args: &[Value]) -> Result<Value, EvalError> {
 
     let chars: Vec<Value> = string
         .chars()
-        .map(|c| Value::String(c.to_string()))
+        .map(|c| Value::String(c.to_string().into()))
         .collect();
 
-    Ok(Value::List(chars))
+    Ok(Value::List(chars.into()))
 }
 
-#[builtin(name = "list->string", category = "String manipulation", related(string->list))]
+#[builtin(name = "list->string", category = "String manipulation", related(string->list), params(list))]
 /// Convert list of strings to single string.
 ///
 /// # Examples
@@ -675,10 +754,10 @@ pub fn builtin_list_to_string(args: &[Value]) -> Result<Value, EvalError> {
         }
     }
 
-    Ok(Value::String(result))
+    Ok(Value::String(result.into()))
 }
 
-#[builtin(name = "string-append", category = "String manipulation", related(string-join, list->string))]
+#[builtin(name = "string-append", category = "String manipulation", related(string-join, list->string), params(strings...))]
 /// Concatenate multiple strings into one.
 ///
 /// Accepts variable number of arguments (0 or more strings).
@@ -701,5 +780,296 @@ pub fn builtin_string_append(args: &[Value]) -> Result<Value, EvalError> {
             _ => return Err(EvalError::type_error("string-append", "string", arg, i + 1)),
         }
     }
-    Ok(Value::String(result))
+    Ok(Value::String(result.into()))
+}
+
+#[builtin(name = "str", category = "String manipulation", related(string-append, print), params(values...))]
+/// Concatenate the printed form of any values into one string.
+///
+/// Each value is written as `print` shows it: strings as their raw
+/// characters, everything else as it would print. Interpolated strings
+/// (`#"..."`) are read as a call to `str`.
+///
+/// # Examples
+///
+/// ```lisp
+/// (str "total: " 42) => "total: 42"
+/// (str "items " '(1 "a") " " {:n 1}) => "items (1 a) {:n 1}"
+/// (define name "Ada")
+/// #"Hello {name}, {(+ 1 2)} new" => "Hello Ada, 3 new"
+/// ```
+///
+/// # See Also
+///
+/// string-append, print
+pub fn builtin_str(args: &[Value]) -> Result<Value, EvalError> {
+    let mut result = String::new();
+    for arg in args {
+        // Writing to a String cannot fail
+        let _ = write_value(&mut result, arg, PrintStyle::Display);
+    }
+    Ok(Value::String(result.into()))
+}
+
+#[builtin(name = "string-chars", category = "String manipulation", related(string->list, list->string), params(string))]
+/// Split a string into a list of one-character strings.
+///
+/// The same as `string->list`, under the name used alongside the other
+/// `string-` functions.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-chars "héllo") => ("h" "é" "l" "l" "o")
+/// (string-chars "") => ()
+/// ```
+///
+/// # See Also
+///
+/// string->list, list->string
+pub fn builtin_string_chars(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "string-chars",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+
+    let string = match &args[0] {
+        Value::String(s) => s,
+        _ => return Err(EvalError::type_error("string-chars", "string", &args[0], 1)),
+    };
+
+    Ok(Value::List(
+        string
+            .chars()
+            .map(|c| Value::String(c.to_string().into()))
+            .collect(),
+    ))
+}
+
+#[builtin(name = "string-reverse", category = "String manipulation", related(string-chars), params(string))]
+/// Reverse the characters of a string.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-reverse "hello") => "olleh"
+/// (string-reverse "") => ""
+/// ```
+///
+/// # See Also
+///
+/// string-chars
+pub fn builtin_string_reverse(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "string-reverse",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(s.chars().rev().collect::<String>().into())),
+        _ => Err(EvalError::type_error(
+            "string-reverse",
+            "string",
+            &args[0],
+            1,
+        )),
+    }
+}
+
+#[builtin(name = "string-repeat", category = "String manipulation", related(string-pad-left, string-append), params(string, count))]
+/// Repeat a string n times.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-repeat "ab" 3) => "ababab"
+/// (string-repeat "-" 0) => ""
+/// ```
+///
+/// # See Also
+///
+/// string-pad-left, string-append
+pub fn builtin_string_repeat(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "string-repeat",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+
+    let string = match &args[0] {
+        Value::String(s) => s,
+        _ => {
+            return Err(EvalError::type_error(
+                "string-repeat",
+                "string",
+                &args[0],
+                1,
+            ))
+        }
+    };
+    let count = count_arg("string-repeat", &args[1], 2)?;
+
+    Ok(Value::String(string.repeat(count).into()))
+}
+
+#[builtin(name = "string-pad-left", category = "String manipulation", related(string-pad-right, number->string), params(string, width, [pad]))]
+/// Pad a string on the left to at least the given width, right-aligning it.
+///
+/// Pads with spaces unless a single-character pad string is given. Strings
+/// already that wide are returned unchanged.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-pad-left "42" 5) => "   42"
+/// (string-pad-left "42" 5 "0") => "00042"
+/// (string-pad-left "text" 2) => "text"
+/// ```
+///
+/// # See Also
+///
+/// string-pad-right, number->string
+pub fn builtin_string_pad_left(args: &[Value]) -> Result<Value, EvalError> {
+    let (string, padding) = pad_args("string-pad-left", args)?;
+    Ok(Value::String(format!("{}{}", padding, string).into()))
+}
+
+#[builtin(name = "string-pad-right", category = "String manipulation", related(string-pad-left), params(string, width, [pad]))]
+/// Pad a string on the right to at least the given width, left-aligning it.
+///
+/// Pads with spaces unless a single-character pad string is given. Strings
+/// already that wide are returned unchanged.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-pad-right "name" 8) => "name    "
+/// (string-pad-right "ab" 4 ".") => "ab.."
+/// ```
+///
+/// # See Also
+///
+/// string-pad-left
+pub fn builtin_string_pad_right(args: &[Value]) -> Result<Value, EvalError> {
+    let (string, padding) = pad_args("string-pad-right", args)?;
+    Ok(Value::String(format!("{}{}", string, padding).into()))
+}
+
+/// The string to pad and the padding it needs, from `(s width [pad])`
+fn pad_args<'a>(function: &str, args: &'a [Value]) -> Result<(&'a str, String), EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EvalError::arity_error(
+            function,
+            ARITY_TWO_OR_THREE,
+            args.len(),
+        ));
+    }
+
+    let string = match &args[0] {
+        Value::String(s) => s,
+        _ => return Err(EvalError::type_error(function, "string", &args[0], 1)),
+    };
+    let width = count_arg(function, &args[1], 2)?;
+    let pad = match args.get(2) {
+        None => ' ',
+        Some(Value::String(s)) if s.chars().count() == 1 => s.chars().next().unwrap_or(' '),
+        Some(other) => {
+            return Err(EvalError::type_error(
+                function,
+                "single-character string",
+                other,
+                3,
+            ))
+        }
+    };
+
+    let missing = width.saturating_sub(string.chars().count());
+    Ok((string, std::iter::repeat_n(pad, missing).collect()))
+}
+
+#[builtin(name = "string-index-of", category = "String manipulation", related(string-contains?, substring), params(string, needle, [start]))]
+/// Find the character index of the first occurrence of a substring, or nil.
+///
+/// An optional start index begins the search further along the string.
+///
+/// # Examples
+///
+/// ```lisp
+/// (string-index-of "hello world" "o") => 4
+/// (string-index-of "hello world" "o" 5) => 7
+/// (string-index-of "hello" "z") => nil
+/// ```
+///
+/// # See Also
+///
+/// string-contains?, substring
+pub fn builtin_string_index_of(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EvalError::arity_error(
+            "string-index-of",
+            ARITY_TWO_OR_THREE,
+            args.len(),
+        ));
+    }
+
+    let string = match &args[0] {
+        Value::String(s) => s,
+        _ => {
+            return Err(EvalError::type_error(
+                "string-index-of",
+                "string",
+                &args[0],
+                1,
+            ))
+        }
+    };
+    let needle = match &args[1] {
+        Value::String(s) => s,
+        _ => {
+            return Err(EvalError::type_error(
+                "string-index-of",
+                "string",
+                &args[1],
+                2,
+            ))
+        }
+    };
+    let start = match args.get(2) {
+        None => 0,
+        Some(arg) => count_arg("string-index-of", arg, 3)?,
+    };
+
+    // Search from the byte offset of the start character, then report characters
+    let offset = match string.char_indices().nth(start) {
+        Some((offset, _)) => offset,
+        None if start == string.chars().count() => string.len(),
+        None => return Ok(Value::Nil),
+    };
+    match string[offset..].find(&**needle) {
+        Some(found) => Ok(Value::Number(
+            (start + string[offset..offset + found].chars().count()) as f64,
+        )),
+        None => Ok(Value::Nil),
+    }
+}
+
+/// A non-negative whole number argument, such as a width or count
+fn count_arg(function: &str, arg: &Value, position: usize) -> Result<usize, EvalError> {
+    match arg {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        _ => Err(EvalError::type_error(
+            function,
+            "non-negative integer",
+            arg,
+            position,
+        )),
+    }
 }
@@ -9,28 +9,65 @@
 //!
 //! **Test Registry:**
 //! - `register-test`: Store a test by name
-//! - `run-all-tests`: Execute all registered tests
+//! - `run-all-tests`: Execute all registered tests, printing a line per test
 //! - `clear-tests`: Clear the test registry
 //!
-//! Assertions return #t on success, or create an Error value on failure
+//! **Snapshots:**
+//! - `test:snapshot`: Compare a value with the copy saved under `snapshots/` in the sandbox
+//!
+//! Assertions return #t on success, or create an Error value on failure.
+//! The registry also tallies every run, so hosts can turn failures into an exit code.
 
+use crate::builtins::console::write_output;
+use crate::builtins::BuiltinContext;
 use crate::env::Environment;
-use crate::error::{EvalError, ARITY_ONE_OR_TWO, ARITY_TWO, ARITY_TWO_OR_THREE, ARITY_ZERO_OR_ONE};
+use crate::error::{
+    EvalError, ARITY_ONE_OR_TWO, ARITY_TWO, ARITY_TWO_OR_THREE, ARITY_ZERO, ARITY_ZERO_OR_ONE,
+};
 use crate::eval::eval;
-use crate::value::Value;
+use crate::sandbox::SandboxError;
+use crate::value::{values_equal, ErrorValue, Value};
 use lisp_macros::builtin;
 use std::cell::RefCell;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 
 // ============================================================================
 // Test Registry
 // ============================================================================
 
+/// Registered tests plus a tally of every `run-all-tests` call
+#[derive(Debug, Default)]
+pub(crate) struct TestRegistry {
+    tests: Vec<(String, Value)>,
+    tally: TestTally,
+    /// Rewrite snapshots instead of comparing against them (`--update-snapshots`)
+    pub(crate) update_snapshots: bool,
+}
+
+/// Totals across every `run-all-tests` call
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct TestTally {
+    pub runs: usize,
+    pub passed: usize,
+    pub failed: usize,
+}
+
+impl TestRegistry {
+    pub(crate) fn tally(&self) -> TestTally {
+        self.tally
+    }
+}
+
 thread_local! {
-    static TEST_REGISTRY: RefCell<Vec<(String, Value)>> = const { RefCell::new(Vec::new()) };
+    static TEST_REGISTRY: RefCell<TestRegistry> = RefCell::new(TestRegistry::default());
 }
 
-#[builtin(name = "assert", category = "Testing", related(assert-equal, assert-error))]
+/// Replace this thread's test registry, returning the previous one
+pub(crate) fn swap_tests(tests: TestRegistry) -> TestRegistry {
+    TEST_REGISTRY.with(|registry| std::mem::replace(&mut *registry.borrow_mut(), tests))
+}
+
+#[builtin(name = "assert", category = "Testing", related(assert-equal, assert-error), params(condition, [message]))]
 /// Assert that condition is true. Returns #t on success, Error value on failure.
 ///
 /// Useful for writing tests and validating assumptions in code.
@@ -58,7 +95,7 @@ pub fn builtin_assert(args: &[Value]) -> Result<Value, EvalError> {
     let condition = &args[0];
     let message = if args.len() == 2 {
         match &args[1] {
-            Value::String(s) => s.clone(),
+            Value::String(s) => s.to_string(),
             other => format!("{}", other),
         }
     } else {
@@ -67,15 +104,20 @@ pub fn builtin_assert(args: &[Value]) -> Result<Value, EvalError> {
 
     match condition {
         Value::Bool(true) => Ok(Value::Bool(true)),
-        Value::Bool(false) | Value::Nil => Ok(Value::Error(message)),
-        _ => Ok(Value::Error(format!(
+        Value::Bool(false) | Value::Nil => Ok(assertion_failed(message)),
+        _ => Ok(assertion_failed(format!(
             "{}: expected boolean, got {}",
             message, condition
         ))),
     }
 }
 
-#[builtin(name = "assert-equal", category = "Testing", related(assert, =))]
+/// The error value an assertion returns when it fails
+fn assertion_failed(message: impl Into<String>) -> Value {
+    Value::Error(Box::new(ErrorValue::new("assertion-failed", message)))
+}
+
+#[builtin(name = "assert-equal", category = "Testing", related(assert, =), params(actual, expected, [message]))]
 /// Assert that actual equals expected. Returns #t on success, Error value with details on failure.
 ///
 /// Provides helpful error messages showing both actual and expected values.
@@ -104,7 +146,7 @@ pub fn builtin_assert_equal(args: &[Value]) -> Result<Value, EvalError> {
     let expected = &args[1];
     let message = if args.len() == 3 {
         match &args[2] {
-            Value::String(s) => s.clone(),
+            Value::String(s) => s.to_string(),
             other => format!("{}", other),
         }
     } else {
@@ -117,39 +159,14 @@ pub fn builtin_assert_equal(args: &[Value]) -> Result<Value, EvalError> {
     if is_equal {
         Ok(Value::Bool(true))
     } else {
-        Ok(Value::Error(format!(
+        Ok(assertion_failed(format!(
             "{}\n  Expected: {}\n  Actual:   {}",
             message, expected, actual
         )))
     }
 }
 
-/// Helper function to recursively compare two values for equality
-fn values_equal(a: &Value, b: &Value) -> bool {
-    match (a, b) {
-        (Value::Number(x), Value::Number(y)) => x == y,
-        (Value::Bool(x), Value::Bool(y)) => x == y,
-        (Value::String(x), Value::String(y)) => x == y,
-        (Value::Symbol(x), Value::Symbol(y)) => x == y,
-        (Value::Keyword(x), Value::Keyword(y)) => x == y,
-        (Value::Nil, Value::Nil) => true,
-        (Value::List(x), Value::List(y)) => {
-            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| values_equal(a, b))
-        }
-        (Value::Map(x), Value::Map(y)) => {
-            // Compare maps: same keys with equal values
-            if x.len() != y.len() {
-                return false;
-            }
-            x.iter()
-                .all(|(k, v)| y.get(k).is_some_and(|v2| values_equal(v, v2)))
-        }
-        (Value::Error(x), Value::Error(y)) => x == y,
-        _ => false,
-    }
-}
-
-#[builtin(name = "assert-error", category = "Testing", related(assert, error?))]
+#[builtin(name = "assert-error", category = "Testing", related(assert, error?), params(value, [message]))]
 /// Assert that value is an error. Returns #t if value is an Error, Error value otherwise.
 ///
 /// Useful for testing error handling and negative test cases.
@@ -176,7 +193,7 @@ pub fn builtin_assert_error(args: &[Value]) -> Result<Value, EvalError> {
     let value = &args[0];
     let message = if args.len() == 2 {
         match &args[1] {
-            Value::String(s) => s.clone(),
+            Value::String(s) => s.to_string(),
             other => format!("{}", other),
         }
     } else {
@@ -185,7 +202,7 @@ pub fn builtin_assert_error(args: &[Value]) -> Result<Value, EvalError> {
 
     match value {
         Value::Error(_) => Ok(Value::Bool(true)),
-        _ => Ok(Value::Error(format!("{}: got {}", message, value))),
+        _ => Ok(assertion_failed(format!("{}: got {}", message, value))),
     }
 }
 
@@ -193,7 +210,7 @@ pub fn builtin_assert_error(args: &[Value]) -> Result<Value, EvalError> {
 // Test Registry Functions
 // ============================================================================
 
-#[builtin(name = "register-test", category = "Testing", related(run-all-tests, clear-tests))]
+#[builtin(name = "register-test", category = "Testing", related(run-all-tests, clear-tests), params(name, thunk))]
 /// Register a test with a name and zero-argument lambda.
 ///
 /// Tests are stored globally and can be executed with run-all-tests.
@@ -245,100 +262,122 @@ pub fn builtin_register_test(args: &[Value]) -> Result<Value, EvalError> {
     }
 
     TEST_REGISTRY.with(|registry| {
-        registry.borrow_mut().push((name, test_fn));
+        registry
+            .borrow_mut()
+            .tests
+            .push((name.to_string(), test_fn));
     });
 
     Ok(Value::Bool(true))
 }
 
-#[builtin(name = "run-all-tests", category = "Testing", related(register-test, clear-tests))]
-/// Execute all registered tests and return structured results as a map.
+#[builtin(name = "run-all-tests", category = "Testing", related(register-test, clear-tests), params([options]))]
+/// Execute all registered tests, print a PASS/FAIL line for each, and return the results as a map.
 ///
 /// Returns a map with: {:passed N :failed M :total T :tests [...]}
 /// Each test in :tests is a map with :name, :status, :message keys.
+/// Pass {:quiet #t} to skip printing. In script mode and with --test, any
+/// failure makes the process exit with a nonzero status.
 ///
 /// # Examples
 ///
 /// ```lisp
 /// (run-all-tests) => {:passed 10 :failed 2 :total 12 :tests [...]}
+/// (run-all-tests {:quiet #t}) => {:passed 10 :failed 2 :total 12 :tests [...]}
 /// ```
 ///
 /// # See Also
 ///
 /// register-test, clear-tests
 pub fn builtin_run_all_tests(args: &[Value]) -> Result<Value, EvalError> {
-    if !args.is_empty() {
-        return Err(EvalError::arity_error(
-            "run-all-tests",
-            ARITY_ZERO_OR_ONE,
-            args.len(),
-        ));
-    }
+    let quiet = match args {
+        [] => false,
+        [Value::Map(options)] => {
+            matches!(options.get("quiet"), Some(v) if !matches!(v, Value::Bool(false) | Value::Nil))
+        }
+        [other] => return Err(EvalError::type_error("run-all-tests", "map", other, 1)),
+        _ => {
+            return Err(EvalError::arity_error(
+                "run-all-tests",
+                ARITY_ZERO_OR_ONE,
+                args.len(),
+            ))
+        }
+    };
 
+    // Run on a copy so tests may register or clear tests without a borrow conflict
+    let tests = TEST_REGISTRY.with(|registry| registry.borrow().tests.clone());
     let mut results = Vec::new();
     let mut passed = 0;
     let mut failed = 0;
 
-    TEST_REGISTRY.with(|registry| {
-        let tests = registry.borrow();
-
-        for (name, test_fn) in tests.iter() {
-            // Call the test lambda (expects 0 args)
-            let call_expr = Value::List(vec![test_fn.clone()]);
-
-            // Execute test and capture result
-            match eval(call_expr, Environment::new()) {
-                Ok(Value::Bool(true)) | Ok(Value::Nil) => {
-                    // Test passed
-                    passed += 1;
-                    let mut result_map = HashMap::new();
-                    result_map.insert("name".to_string(), Value::String(name.clone()));
-                    result_map.insert("status".to_string(), Value::Symbol("passed".to_string()));
-                    result_map.insert("message".to_string(), Value::String(String::new()));
-                    results.push(Value::Map(result_map));
-                }
-                Ok(Value::Error(msg)) => {
-                    // Test failed with assertion error
-                    failed += 1;
-                    let mut result_map = HashMap::new();
-                    result_map.insert("name".to_string(), Value::String(name.clone()));
-                    result_map.insert("status".to_string(), Value::Symbol("failed".to_string()));
-                    result_map.insert("message".to_string(), Value::String(msg));
-                    results.push(Value::Map(result_map));
-                }
-                Ok(_) => {
-                    // Test returned non-error value, consider it passed
-                    passed += 1;
-                    let mut result_map = HashMap::new();
-                    result_map.insert("name".to_string(), Value::String(name.clone()));
-                    result_map.insert("status".to_string(), Value::Symbol("passed".to_string()));
-                    result_map.insert("message".to_string(), Value::String(String::new()));
-                    results.push(Value::Map(result_map));
-                }
-                Err(e) => {
-                    // Test threw an exception
-                    failed += 1;
-                    let mut result_map = HashMap::new();
-                    result_map.insert("name".to_string(), Value::String(name.clone()));
-                    result_map.insert("status".to_string(), Value::Symbol("error".to_string()));
-                    result_map.insert("message".to_string(), Value::String(e.to_string()));
-                    results.push(Value::Map(result_map));
-                }
-            }
+    for (name, test_fn) in tests.iter() {
+        // Call the test lambda (expects 0 args)
+        let call_expr = Value::List(vec![test_fn.clone()].into());
+
+        // Execute test: error values are assertion failures, raised errors are errors
+        let (status, message) = match eval(call_expr, Environment::new()) {
+            Ok(Value::Error(err)) => ("failed", err.message),
+            Ok(_) => ("passed", String::new()),
+            Err(e) => ("error", e.to_string()),
+        };
+
+        if status == "passed" {
+            passed += 1;
+        } else {
+            failed += 1;
+        }
+        if !quiet {
+            write_output(&report_line(name, status, &message));
         }
+
+        let mut result_map = BTreeMap::new();
+        result_map.insert("name".to_string(), Value::String(name.as_str().into()));
+        result_map.insert("status".to_string(), Value::Symbol(status.into()));
+        result_map.insert("message".to_string(), Value::String(message.into()));
+        results.push(Value::Map(result_map));
+    }
+
+    if !quiet {
+        write_output(&format!(
+            "{} passed, {} failed, {} total\n",
+            passed,
+            failed,
+            passed + failed
+        ));
+    }
+    TEST_REGISTRY.with(|registry| {
+        let tally = &mut registry.borrow_mut().tally;
+        tally.runs += 1;
+        tally.passed += passed;
+        tally.failed += failed;
     });
 
     // Return result as map
-    let mut result_map = HashMap::new();
+    let mut result_map = BTreeMap::new();
     result_map.insert("passed".to_string(), Value::Number(passed as f64));
     result_map.insert("failed".to_string(), Value::Number(failed as f64));
     result_map.insert("total".to_string(), Value::Number((passed + failed) as f64));
-    result_map.insert("tests".to_string(), Value::List(results));
+    result_map.insert("tests".to_string(), Value::List(results.into()));
 
     Ok(Value::Map(result_map))
 }
 
-#[builtin(name = "clear-tests", category = "Testing", related(register-test, run-all-tests))]
+/// One line of the run-all-tests report; failure messages are indented beneath
+fn report_line(name: &str, status: &str, message: &str) -> String {
+    let label = match status {
+        "passed" => "PASS",
+        "failed" => "FAIL",
+        _ => "ERROR",
+    };
+    let mut line = format!("  {} {}\n", label, name);
+    for detail in message.lines() {
+        line.push_str(&format!("       {}\n", detail));
+    }
+    line
+}
+
+#[builtin(name = "clear-tests", category = "Testing", related(register-test, run-all-tests), params())]
 /// Clear all registered tests from the registry.
 ///
 /// Useful for reloading test files or starting fresh.
@@ -356,14 +395,111 @@ pub fn builtin_clear_tests(args: &[Value]) -> Result<Value, EvalError> {
     if !args.is_empty() {
         return Err(EvalError::arity_error(
             "clear-tests",
-            ARITY_ZERO_OR_ONE,
+            ARITY_ZERO,
             args.len(),
         ));
     }
 
     TEST_REGISTRY.with(|registry| {
-        registry.borrow_mut().clear();
+        registry.borrow_mut().tests.clear();
     });
 
     Ok(Value::Bool(true))
 }
+
+// ============================================================================
+// Snapshots
+// ============================================================================
+
+/// Directory, inside the sandbox, that holds snapshot files
+pub const SNAPSHOT_DIR: &str = "snapshots";
+
+#[builtin(name = "test:snapshot", capability = "filesystem", category = "Testing", related(assert-equal, define-test), params(name, value))]
+/// Compare a value's printed form with the snapshot saved as snapshots/NAME.snap.
+///
+/// The first run writes the snapshot and passes. Later runs return #t if the
+/// value still prints the same, or an assertion error with a line diff.
+/// Run with --update-snapshots to rewrite snapshots after an intended change.
+/// Strings are saved as-is; other values as they print.
+///
+/// # Examples
+///
+/// ```lisp
+/// (test:snapshot "quicksort" (quicksort '(3 1 2))) => #t
+/// (define-test "report" (test:snapshot "report" (build-report data)))
+/// ```
+///
+/// # See Also
+///
+/// assert-equal, define-test
+pub fn builtin_test_snapshot(args: &[Value], ctx: &BuiltinContext) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "test:snapshot",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+
+    let name = match &args[0] {
+        Value::String(s) if !s.is_empty() => s,
+        _ => {
+            return Err(EvalError::type_error(
+                "test:snapshot",
+                "non-empty string",
+                &args[0],
+                1,
+            ))
+        }
+    };
+    let actual = match &args[1] {
+        Value::String(s) => format!("{}\n", s),
+        other => format!("{}\n", other),
+    };
+
+    let sandbox = ctx.sandbox()?;
+    let path = format!("{}/{}.snap", SNAPSHOT_DIR, name);
+    let update = TEST_REGISTRY.with(|registry| registry.borrow().update_snapshots);
+
+    let expected = match sandbox.read_file(&path) {
+        Ok(_) if update => None,
+        Ok(contents) => Some(contents),
+        Err(SandboxError::FileNotFound(_)) => None,
+        Err(e) => return Err(EvalError::sandbox_error("test:snapshot", e)),
+    };
+
+    match expected {
+        None => {
+            sandbox
+                .write_file(&path, &actual)
+                .map_err(|e| EvalError::sandbox_error("test:snapshot", e))?;
+            Ok(Value::Bool(true))
+        }
+        Some(expected) if expected == actual => Ok(Value::Bool(true)),
+        Some(expected) => Ok(assertion_failed(format!(
+            "Snapshot '{}' does not match {}\n{}",
+            name,
+            path,
+            line_diff(&expected, &actual)
+        ))),
+    }
+}
+
+/// Lines that differ between two texts, as `- expected` / `+ actual` pairs
+fn line_diff(expected: &str, actual: &str) -> String {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let mut diff = Vec::new();
+    for i in 0..expected.len().max(actual.len()) {
+        let (old, new) = (expected.get(i), actual.get(i));
+        if old != new {
+            if let Some(line) = old {
+                diff.push(format!("- {}", line));
+            }
+            if let Some(line) = new {
+                diff.push(format!("+ {}", line));
+            }
+        }
+    }
+    diff.join("\n")
+}
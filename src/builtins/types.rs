@@ -17,7 +17,7 @@ use crate::error::{EvalError, ARITY_ONE};
 use crate::value::Value;
 use lisp_macros::builtin;
 
-#[builtin(name = "number?", category = "Type predicates", related(string?, symbol?, list?))]
+#[builtin(name = "number?", category = "Type predicates", related(string?, symbol?, list?), params(value))]
 /// Tests if val is a number (integer or float).
 ///
 /// # Examples
@@ -39,7 +39,7 @@ pub fn builtin_number_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Number(_))))
 }
 
-#[builtin(name = "string?", category = "Type predicates", related(number?, symbol?))]
+#[builtin(name = "string?", category = "Type predicates", related(number?, symbol?), params(value))]
 /// Tests if val is a string.
 ///
 /// # Examples
@@ -61,7 +61,7 @@ pub fn builtin_string_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::String(_))))
 }
 
-#[builtin(name = "list?", category = "Type predicates", related(number?, string?, nil?))]
+#[builtin(name = "list?", category = "Type predicates", related(number?, string?, nil?), params(value))]
 /// Tests if val is a list (including nil).
 ///
 /// # Examples
@@ -83,7 +83,7 @@ pub fn builtin_list_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::List(_))))
 }
 
-#[builtin(name = "nil?", category = "Type predicates", related(empty?, list?))]
+#[builtin(name = "nil?", category = "Type predicates", related(empty?, list?), params(value))]
 /// Tests if val is nil (empty list).
 ///
 /// # Examples
@@ -105,7 +105,7 @@ pub fn builtin_nil_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Nil)))
 }
 
-#[builtin(name = "symbol?", category = "Type predicates", related(string?, number?))]
+#[builtin(name = "symbol?", category = "Type predicates", related(string?, number?), params(value))]
 /// Tests if val is a symbol (e.g., from 'hello or var names).
 ///
 /// # Examples
@@ -126,7 +126,7 @@ pub fn builtin_symbol_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Symbol(_))))
 }
 
-#[builtin(name = "bool?", category = "Type predicates", related(number?, string?))]
+#[builtin(name = "bool?", category = "Type predicates", related(number?, string?), params(value))]
 /// Tests if val is a boolean (#t or #f).
 ///
 /// # Examples
@@ -148,7 +148,7 @@ pub fn builtin_bool_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Bool(_))))
 }
 
-#[builtin(name = "map?", category = "Type predicates", related(list?, keyword?))]
+#[builtin(name = "map?", category = "Type predicates", related(list?, keyword?), params(value))]
 /// Tests if val is a map (hashmap).
 ///
 /// # Examples
@@ -171,7 +171,7 @@ pub fn builtin_map_p(args: &[Value]) -> Result<Value, EvalError> {
     Ok(Value::Bool(matches!(args[0], Value::Map(_))))
 }
 
-#[builtin(name = "keyword?", category = "Type predicates", related(symbol?, map?))]
+#[builtin(name = "keyword?", category = "Type predicates", related(symbol?, map?), params(value))]
 /// Tests if val is a keyword (:name).
 ///
 /// # Examples
@@ -0,0 +1,262 @@
+//! Human-readable sizes and durations
+//!
+//! - `parse-bytes`: "10MB" to 10485760
+//! - `format-bytes`: 10485760 to "10MB"
+//! - `parse-duration`: "1h30m" to 5400 seconds
+//! - `format-duration`: 5400 seconds to "1h30m"
+//!
+//! The parsers are also used for command-line flags such as `--max-file-size`
+//! and `--eval-timeout`, so scripts and hosts spell amounts the same way.
+//! Byte units are binary: a KB is 1024 bytes.
+
+use crate::error::{EvalError, ARITY_ONE};
+use crate::value::Value;
+use lisp_macros::builtin;
+
+const BYTE_UNITS: [(&str, u64); 5] = [
+    ("TB", 1 << 40),
+    ("GB", 1 << 30),
+    ("MB", 1 << 20),
+    ("KB", 1 << 10),
+    ("B", 1),
+];
+
+const DURATION_UNITS: [(&str, u64); 4] = [
+    ("d", 86_400_000),
+    ("h", 3_600_000),
+    ("m", 60_000),
+    ("s", 1000),
+];
+
+/// Split leading digits and dots from the rest of `text`
+fn split_number(text: &str) -> (&str, &str) {
+    let end = text
+        .find(|c: char| !c.is_ascii_digit() && c != '.')
+        .unwrap_or(text.len());
+    text.split_at(end)
+}
+
+/// Parse a size such as `"512"`, `"1.5KB"` or `"10 MiB"` into bytes
+///
+/// Units are B, KB, MB, GB and TB, case-insensitive, with `K`/`KiB` style
+/// spellings accepted too. A bare number is bytes.
+pub fn parse_bytes(text: &str) -> Result<u64, String> {
+    let invalid = || {
+        format!(
+            "invalid size {:?}: expected a number with an optional unit B, KB, MB, GB or TB",
+            text
+        )
+    };
+    let (number, unit) = split_number(text.trim());
+    let number: f64 = number.parse().map_err(|_| invalid())?;
+    let unit = unit.trim().to_ascii_uppercase();
+    let unit = unit.strip_suffix("IB").map_or(unit.as_str(), |u| u);
+    let multiplier = match unit {
+        "" | "B" => 1,
+        _ => BYTE_UNITS
+            .iter()
+            .find(|(name, _)| name.strip_suffix('B') == Some(unit) || *name == unit)
+            .map(|(_, size)| *size)
+            .ok_or_else(invalid)?,
+    };
+    let bytes = (number * multiplier as f64).round();
+    if bytes > u64::MAX as f64 {
+        return Err(format!("size {:?} is too large", text));
+    }
+    Ok(bytes as u64)
+}
+
+/// Write a byte count with the largest unit that keeps it at least 1,
+/// to one decimal place: 1536 is `"1.5KB"`
+pub fn format_bytes(bytes: u64) -> String {
+    let (unit, size) = BYTE_UNITS
+        .iter()
+        .find(|(_, size)| bytes >= *size)
+        .copied()
+        .unwrap_or(("B", 1));
+    let text = format!("{:.1}", bytes as f64 / size as f64);
+    format!("{}{}", text.trim_end_matches(".0"), unit)
+}
+
+/// Parse a duration such as `"1h30m"`, `"1.5s"` or `"250ms"` into seconds
+///
+/// Components with the units d, h, m, s and ms may be combined in any
+/// order, optionally separated by spaces. A bare number is seconds.
+pub fn parse_duration(text: &str) -> Result<f64, String> {
+    let invalid = || {
+        format!(
+            "invalid duration {:?}: expected amounts with units d, h, m, s or ms, like 1h30m",
+            text
+        )
+    };
+    let mut rest = text.trim();
+    if let Ok(seconds) = rest.parse::<f64>() {
+        return if seconds >= 0.0 && seconds.is_finite() {
+            Ok(seconds)
+        } else {
+            Err(invalid())
+        };
+    }
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut seconds = 0.0;
+    while !rest.is_empty() {
+        let (number, after) = split_number(rest);
+        let amount: f64 = number.parse().map_err(|_| invalid())?;
+        let unit_end = after
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(after.len());
+        let scale = match &after[..unit_end] {
+            "ms" => 0.001,
+            "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            "d" => 86_400.0,
+            _ => return Err(invalid()),
+        };
+        seconds += amount * scale;
+        rest = after[unit_end..].trim_start();
+    }
+    Ok(seconds)
+}
+
+/// Write a number of seconds as days, hours, minutes and seconds:
+/// 5400 is `"1h30m"`, 90.5 is `"1m30.5s"`, 0.25 is `"250ms"`
+///
+/// Rounds to the millisecond.
+pub fn format_duration(seconds: f64) -> String {
+    let mut millis = (seconds * 1000.0).round() as u64;
+    if millis == 0 {
+        return "0s".to_string();
+    }
+    if millis < 1000 {
+        return format!("{}ms", millis);
+    }
+    let mut text = String::new();
+    for (unit, size) in DURATION_UNITS {
+        let count = millis / size;
+        millis %= size;
+        if unit == "s" && (count > 0 || millis > 0) {
+            let seconds = format!("{}.{:03}", count, millis);
+            text.push_str(seconds.trim_end_matches('0').trim_end_matches('.'));
+            text.push('s');
+        } else if count > 0 {
+            text.push_str(&format!("{}{}", count, unit));
+        }
+    }
+    text
+}
+
+fn text_arg<'a>(function: &str, args: &'a [Value]) -> Result<&'a str, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(function, ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(text) => Ok(text),
+        other => Err(EvalError::type_error(function, "string", other, 1)),
+    }
+}
+
+fn amount_arg(function: &str, args: &[Value]) -> Result<f64, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(function, ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::Number(n) if *n >= 0.0 && n.is_finite() => Ok(*n),
+        other => Err(EvalError::type_error(
+            function,
+            "non-negative number",
+            other,
+            1,
+        )),
+    }
+}
+
+#[builtin(name = "parse-bytes", category = "Units", related(format-bytes, parse-duration), params(text))]
+/// Parse a human-readable size into a number of bytes.
+///
+/// Units are B, KB, MB, GB and TB (case-insensitive; K, KiB and the like work
+/// too), and they are binary: a KB is 1024 bytes. A bare number is bytes.
+///
+/// # Examples
+///
+/// ```lisp
+/// (parse-bytes "10MB") => 10485760
+/// (parse-bytes "1.5 kb") => 1536
+/// (parse-bytes "512") => 512
+/// ```
+///
+/// # See Also
+///
+/// format-bytes, parse-duration
+pub fn builtin_parse_bytes(args: &[Value]) -> Result<Value, EvalError> {
+    let text = text_arg("parse-bytes", args)?;
+    parse_bytes(text)
+        .map(|bytes| Value::Number(bytes as f64))
+        .map_err(|e| EvalError::runtime_error("parse-bytes", e))
+}
+
+#[builtin(name = "format-bytes", category = "Units", related(parse-bytes, format-duration), params(bytes))]
+/// Write a number of bytes with the largest fitting unit, to one decimal place.
+///
+/// # Examples
+///
+/// ```lisp
+/// (format-bytes 10485760) => "10MB"
+/// (format-bytes 1536) => "1.5KB"
+/// (format-bytes 12) => "12B"
+/// ```
+///
+/// # See Also
+///
+/// parse-bytes, format-duration
+pub fn builtin_format_bytes(args: &[Value]) -> Result<Value, EvalError> {
+    let bytes = amount_arg("format-bytes", args)?;
+    Ok(Value::String(format_bytes(bytes.round() as u64).into()))
+}
+
+#[builtin(name = "parse-duration", category = "Units", related(format-duration, parse-bytes), params(text))]
+/// Parse a human-readable duration into a number of seconds.
+///
+/// Combine amounts with the units d, h, m, s and ms, like "1h30m" or
+/// "2m 15s"; amounts may have decimals. A bare number is seconds.
+///
+/// # Examples
+///
+/// ```lisp
+/// (parse-duration "1h30m") => 5400
+/// (parse-duration "250ms") => 0.25
+/// (parse-duration "1.5d") => 129600
+/// ```
+///
+/// # See Also
+///
+/// format-duration, parse-bytes
+pub fn builtin_parse_duration(args: &[Value]) -> Result<Value, EvalError> {
+    let text = text_arg("parse-duration", args)?;
+    parse_duration(text)
+        .map(Value::Number)
+        .map_err(|e| EvalError::runtime_error("parse-duration", e))
+}
+
+#[builtin(name = "format-duration", category = "Units", related(parse-duration, format-bytes), params(seconds))]
+/// Write a number of seconds as days, hours, minutes and seconds.
+///
+/// Rounds to the millisecond; durations under a second are written in ms.
+///
+/// # Examples
+///
+/// ```lisp
+/// (format-duration 5400) => "1h30m"
+/// (format-duration 90.5) => "1m30.5s"
+/// (format-duration 0.25) => "250ms"
+/// ```
+///
+/// # See Also
+///
+/// parse-duration, format-bytes
+pub fn builtin_format_duration(args: &[Value]) -> Result<Value, EvalError> {
+    let seconds = amount_arg("format-duration", args)?;
+    Ok(Value::String(format_duration(seconds).into()))
+}
@@ -0,0 +1,181 @@
+// ABOUTME: Cancellation token used to abort a running evaluation
+// Also carries the optional step limit, so threads started by spawn share one budget,
+// and the limit on nested evaluations
+
+use crate::error::EvalError;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+/// A shared flag that asks a running evaluation to stop
+///
+/// Clones share the same flag, so a host can keep one clone (for example in a
+/// Ctrl-C handler or a watchdog thread) and call [`CancellationToken::cancel`]
+/// while the interpreter evaluates on another thread. The evaluator checks the
+/// flag on every step and fails with [`EvalError::Interrupted`]. The flag stays
+/// set until [`CancellationToken::reset`] is called.
+///
+/// The token also counts evaluation steps. With a step limit set, the step
+/// after the limit fails with [`EvalError::StepLimitExceeded`]. It also holds
+/// the depth limit: an evaluation nested deeper than that fails with
+/// [`EvalError::RecursionTooDeep`] instead of overflowing the stack. Calls
+/// waiting on the heap for their operands have a separate, larger limit, and
+/// fail with [`EvalError::TooManyPendingCalls`].
+#[derive(Debug, Clone)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+    steps: Arc<StepCounter>,
+    /// 0 means unlimited
+    max_depth: Arc<AtomicUsize>,
+    /// 0 means unlimited
+    max_pending_calls: Arc<AtomicUsize>,
+}
+
+/// How many evaluations may be nested on one thread before evaluation fails
+///
+/// Each nested evaluation takes Rust stack, the most in debug builds, so a
+/// thread running code this deep needs [`EVAL_STACK_SIZE`] of stack.
+pub const DEFAULT_MAX_EVAL_DEPTH: usize = 10_000;
+
+/// How many calls may wait for their operands on one thread before evaluation fails
+///
+/// Waiting calls are held on the heap, not the Rust stack, so this only
+/// bounds the memory that runaway non-tail recursion can take.
+pub const DEFAULT_MAX_PENDING_CALLS: usize = 1_000_000;
+
+/// Stack size for threads that evaluate, enough for [`DEFAULT_MAX_EVAL_DEPTH`] nested evaluations
+pub const EVAL_STACK_SIZE: usize = 512 * 1024 * 1024;
+
+impl Default for CancellationToken {
+    fn default() -> Self {
+        CancellationToken {
+            cancelled: Arc::default(),
+            steps: Arc::default(),
+            max_depth: Arc::new(AtomicUsize::new(DEFAULT_MAX_EVAL_DEPTH)),
+            max_pending_calls: Arc::new(AtomicUsize::new(DEFAULT_MAX_PENDING_CALLS)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct StepCounter {
+    used: AtomicU64,
+    /// 0 means unlimited
+    limit: AtomicU64,
+}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request that the current evaluation stop
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether cancellation has been requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Clear a previous cancellation so evaluation can run again
+    pub fn reset(&self) {
+        self.cancelled.store(false, Ordering::Relaxed);
+    }
+
+    /// Fail evaluations that take more than `limit` steps (None for no limit)
+    pub fn set_step_limit(&self, limit: Option<u64>) {
+        self.steps
+            .limit
+            .store(limit.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn step_limit(&self) -> Option<u64> {
+        match self.steps.limit.load(Ordering::Relaxed) {
+            0 => None,
+            limit => Some(limit),
+        }
+    }
+
+    /// Fail evaluations nested more than `depth` deep (None for no limit)
+    pub fn set_depth_limit(&self, depth: Option<usize>) {
+        self.max_depth.store(depth.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn depth_limit(&self) -> Option<usize> {
+        match self.max_depth.load(Ordering::Relaxed) {
+            0 => None,
+            depth => Some(depth),
+        }
+    }
+
+    /// Fail evaluations with more than `calls` calls waiting for operands (None for no limit)
+    pub fn set_pending_call_limit(&self, calls: Option<usize>) {
+        self.max_pending_calls
+            .store(calls.unwrap_or(0), Ordering::Relaxed);
+    }
+
+    pub fn pending_call_limit(&self) -> Option<usize> {
+        match self.max_pending_calls.load(Ordering::Relaxed) {
+            0 => None,
+            calls => Some(calls),
+        }
+    }
+
+    /// Steps counted since the last [`CancellationToken::reset_steps`]
+    pub fn steps(&self) -> u64 {
+        self.steps.used.load(Ordering::Relaxed)
+    }
+
+    /// Start counting steps from zero
+    pub fn reset_steps(&self) {
+        self.steps.used.store(0, Ordering::Relaxed);
+    }
+
+    /// Count one evaluation step, failing if cancelled or over the step limit
+    pub(crate) fn step(&self) -> Result<(), EvalError> {
+        if self.is_cancelled() {
+            return Err(EvalError::Interrupted);
+        }
+        let used = self.steps.used.fetch_add(1, Ordering::Relaxed) + 1;
+        match self.step_limit() {
+            Some(limit) if used > limit => Err(EvalError::StepLimitExceeded(limit)),
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_clones_share_the_flag() {
+        let token = CancellationToken::new();
+        let handle = token.clone();
+        assert!(!token.is_cancelled());
+
+        handle.cancel();
+        assert!(token.is_cancelled());
+
+        token.reset();
+        assert!(!handle.is_cancelled());
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let token = CancellationToken::new();
+        token.set_step_limit(Some(2));
+        let handle = token.clone();
+
+        assert!(token.step().is_ok());
+        assert!(handle.step().is_ok());
+        assert!(matches!(token.step(), Err(EvalError::StepLimitExceeded(2))));
+        assert_eq!(token.steps(), 3);
+
+        token.reset_steps();
+        assert!(token.step().is_ok());
+        token.set_step_limit(None);
+        assert!((0..10).all(|_| token.step().is_ok()));
+    }
+}
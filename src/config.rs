@@ -36,7 +36,10 @@ Type (help) to see all available functions, or dive in with any Lisp expression!
 /// Filesystem sandbox configuration
 #[derive(Debug, Clone)]
 pub struct FsConfig {
+    /// Directories that can be read and written (writes go to the first)
     pub allowed_paths: Vec<PathBuf>,
+    /// Directories that can only be read, searched after `allowed_paths`
+    pub read_only_paths: Vec<PathBuf>,
     pub max_file_size: usize,
 }
 
@@ -49,6 +52,7 @@ impl Default for FsConfig {
                 PathBuf::from("./examples"),
                 PathBuf::from("./scripts"),
             ],
+            read_only_paths: vec![],
             // Default max file size: 10MB
             max_file_size: 10 * 1024 * 1024,
         }
@@ -60,16 +64,149 @@ impl Default for FsConfig {
 pub struct NetConfig {
     /// Whether network I/O is enabled
     pub enabled: bool,
-    /// Allowed network addresses (host:port format)
-    /// Empty = no restrictions (if enabled=true)
+    /// Allowed addresses as `[scheme://]host[:port]` patterns (see `sandbox::AddressPattern`);
+    /// `*.example.com` covers subdomains and a missing scheme means https (or wss) only.
+    /// Empty = any host over https or wss (if enabled=true)
     pub allowed_addresses: Vec<String>,
+    /// Most HTTP requests one sandbox may make (None = unlimited)
+    pub max_requests: Option<u64>,
+    /// Whether `ws:connect` may open WebSockets (the allowlist still applies)
+    pub allow_websocket: bool,
+    /// Most `rate:acquire` calls per second across every limiter, so scripts
+    /// cannot pace themselves faster than the host allows (None = no floor)
+    pub max_rate: Option<f64>,
 }
 
-/// Combined I/O sandbox configuration
-/// Reserved for future phases where full combined config builder is needed
-#[allow(dead_code)]
+/// Capability modules a sandbox policy can switch off
+///
+/// Builtins join a module through `capability = "..."` on `#[builtin]`, and Rust
+/// or Lisp stdlib functions through `help::register_capability`. The core
+/// language (arithmetic, lists, strings, maps, ...) is always available.
+pub const CAPABILITY_MODULES: &[&str] = &[
+    "console",
+    "filesystem",
+    "sessions",
+    "network",
+    "concurrency",
+];
+
+/// Every sandbox limit in one place: filesystem, network and evaluation steps
+///
+/// Built from a [`Profile`], then adjusted by a policy file and CLI flags.
 #[derive(Debug, Clone, Default)]
 pub struct IoConfig {
     pub filesystem: FsConfig,
     pub network: NetConfig,
+    /// Most evaluation steps for each top-level expression (None = unlimited)
+    pub max_steps: Option<u64>,
+}
+
+/// Step limit of the strict profile
+pub const STRICT_MAX_STEPS: u64 = 1_000_000;
+
+/// Named starting points for the sandbox configuration
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// No filesystem, no network, and at most [`STRICT_MAX_STEPS`] steps per expression
+    Strict,
+    /// `./data`, `./examples` and `./scripts`; network off; no step limit
+    #[default]
+    Default,
+    /// The home directory and any host over https; no step limit
+    Trusted,
+}
+
+impl Profile {
+    pub fn config(self) -> IoConfig {
+        match self {
+            Profile::Strict => IoConfig {
+                filesystem: FsConfig {
+                    allowed_paths: vec![],
+                    ..FsConfig::default()
+                },
+                network: NetConfig::default(),
+                max_steps: Some(STRICT_MAX_STEPS),
+            },
+            Profile::Default => IoConfig::default(),
+            Profile::Trusted => IoConfig {
+                filesystem: FsConfig {
+                    allowed_paths: vec![home_dir()],
+                    ..FsConfig::default()
+                },
+                network: NetConfig {
+                    enabled: true,
+                    ..NetConfig::default()
+                },
+                max_steps: None,
+            },
+        }
+    }
+}
+
+/// `$HOME` (or `%USERPROFILE%`), falling back to the current directory
+fn home_dir() -> PathBuf {
+    std::env::var_os("HOME")
+        .or_else(|| std::env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+}
+
+// ============================================================================
+// Startup Files
+// ============================================================================
+
+/// Init file the REPL evaluates at startup: `~/.lisprc`
+pub fn init_file_path() -> PathBuf {
+    home_dir().join(".lisprc")
+}
+
+// ============================================================================
+// REPL History
+// ============================================================================
+
+/// Most REPL history entries kept between sessions
+pub const MAX_HISTORY_ENTRIES: usize = 1000;
+
+/// Default REPL history file: `history` in the data directory
+///
+/// The data directory is `$XDG_DATA_HOME/lisp-llm-sandbox`, or
+/// `~/.local/share/lisp-llm-sandbox` when `XDG_DATA_HOME` is unset or not an
+/// absolute path, as the XDG base directory spec asks.
+pub fn default_history_path() -> PathBuf {
+    data_dir(
+        std::env::var_os("XDG_DATA_HOME").map(PathBuf::from),
+        home_dir(),
+    )
+    .join("history")
+}
+
+fn data_dir(xdg_data_home: Option<PathBuf>, home: PathBuf) -> PathBuf {
+    let base = xdg_data_home
+        .filter(|dir| dir.is_absolute())
+        .unwrap_or_else(|| home.join(".local").join("share"));
+    base.join("lisp-llm-sandbox")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_data_dir_follows_xdg() {
+        let home = PathBuf::from("/home/ada");
+        assert_eq!(
+            data_dir(Some(PathBuf::from("/data")), home.clone()),
+            PathBuf::from("/data/lisp-llm-sandbox")
+        );
+        assert_eq!(
+            data_dir(None, home.clone()),
+            PathBuf::from("/home/ada/.local/share/lisp-llm-sandbox")
+        );
+        // Relative paths are ignored, as the spec says
+        assert_eq!(
+            data_dir(Some(PathBuf::from("data")), home),
+            PathBuf::from("/home/ada/.local/share/lisp-llm-sandbox")
+        );
+    }
 }
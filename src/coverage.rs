@@ -0,0 +1,326 @@
+// ABOUTME: Expression and function hit counts for --coverage
+// Counts are collected on the evaluating thread while an interpreter with coverage on is active
+
+use crate::analyze::code_children;
+use crate::builtins::builtin_name;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write;
+
+thread_local! {
+    /// Coverage collected by the evaluation running on this thread, when enabled
+    static ACTIVE_COVERAGE: RefCell<Option<Coverage>> = const { RefCell::new(None) };
+}
+
+/// How often code was evaluated, for finding what tests never exercise
+///
+/// Expressions are identified by the list they were parsed into, so two
+/// functions with identical bodies keep separate counts. Only list expressions
+/// (calls and special forms) in the bodies of top-level functions are
+/// counted; symbols, literals and code outside functions are not. A
+/// function's calls are the evaluations of its body. Code run by threads
+/// started with `spawn` is not counted.
+#[derive(Debug, Clone, Default)]
+pub struct Coverage {
+    /// Evaluations of each tracked expression, by [`expr_id`]
+    hits: HashMap<usize, u64>,
+    /// Top-level functions in definition order
+    functions: Vec<FunctionCode>,
+}
+
+#[derive(Debug, Clone)]
+struct FunctionCode {
+    name: String,
+    /// The body, held so the ids of its expressions stay unique
+    body: Value,
+    /// Every list expression in the body, the body included, in source order
+    expressions: Vec<Expression>,
+}
+
+#[derive(Debug, Clone)]
+struct Expression {
+    id: usize,
+    source: String,
+}
+
+/// Coverage of one top-level function
+#[derive(Debug, Clone, PartialEq)]
+pub struct FunctionCoverage {
+    pub name: String,
+    pub calls: u64,
+    /// List expressions in the body evaluated at least once
+    pub covered: usize,
+    pub total: usize,
+    /// Expressions that were never evaluated, in source order
+    pub missed: Vec<String>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How many times this expression of a tracked function body was evaluated
+    #[allow(dead_code)]
+    pub fn hits(&self, expr: &Value) -> u64 {
+        expr_id(expr).map_or(0, |id| self.count(id))
+    }
+
+    fn count(&self, id: usize) -> u64 {
+        self.hits.get(&id).copied().unwrap_or(0)
+    }
+
+    /// Coverage of every top-level function, in definition order
+    pub fn functions(&self) -> Vec<FunctionCoverage> {
+        self.functions
+            .iter()
+            .map(|function| {
+                let missed: Vec<String> = function
+                    .expressions
+                    .iter()
+                    .filter(|expr| self.count(expr.id) == 0)
+                    .map(|expr| expr.source.clone())
+                    .collect();
+                FunctionCoverage {
+                    name: function.name.clone(),
+                    calls: self.hits(&function.body),
+                    covered: function.expressions.len() - missed.len(),
+                    total: function.expressions.len(),
+                    missed,
+                }
+            })
+            .collect()
+    }
+
+    /// Add another run's counts, keeping the first definition of each function
+    ///
+    /// A function the other run defined with the same body adds its counts
+    /// expression by expression.
+    pub fn merge(&mut self, other: Coverage) {
+        for function in other.functions {
+            let counts: Vec<u64> = function
+                .expressions
+                .iter()
+                .map(|expr| other.hits.get(&expr.id).copied().unwrap_or(0))
+                .collect();
+            match self.functions.iter().find(|f| f.name == function.name) {
+                Some(existing) => {
+                    let same_body = existing.expressions.len() == function.expressions.len()
+                        && existing
+                            .expressions
+                            .iter()
+                            .zip(&function.expressions)
+                            .all(|(a, b)| a.source == b.source);
+                    if same_body {
+                        for (expr, count) in existing.expressions.iter().zip(counts) {
+                            *self.hits.entry(expr.id).or_default() += count;
+                        }
+                    }
+                }
+                None => {
+                    for (expr, count) in function.expressions.iter().zip(counts) {
+                        self.hits.insert(expr.id, count);
+                    }
+                    self.functions.push(function);
+                }
+            }
+        }
+    }
+
+    /// Plain-text report: a summary line, one line per function, then the expressions never evaluated
+    pub fn report(&self) -> String {
+        let functions = self.functions();
+        let called = functions.iter().filter(|f| f.calls > 0).count();
+        let covered: usize = functions.iter().map(|f| f.covered).sum();
+        let total: usize = functions.iter().map(|f| f.total).sum();
+        let percent = if total == 0 {
+            100.0
+        } else {
+            covered as f64 * 100.0 / total as f64
+        };
+
+        let mut out = format!(
+            "Coverage: {} of {} functions called, {} of {} expressions evaluated ({:.1}%)\n",
+            called,
+            functions.len(),
+            covered,
+            total,
+            percent
+        );
+        let width = functions.iter().map(|f| f.name.len()).max().unwrap_or(0);
+        for f in &functions {
+            let _ = writeln!(
+                out,
+                "  {:<width$}  {:>8} calls  {:>9} expressions",
+                f.name,
+                f.calls,
+                format!("{}/{}", f.covered, f.total),
+                width = width
+            );
+        }
+        if functions.iter().any(|f| !f.missed.is_empty()) {
+            out.push_str("Never evaluated:\n");
+            for f in &functions {
+                for expr in &f.missed {
+                    let _ = writeln!(out, "  {}: {}", f.name, expr);
+                }
+            }
+        }
+        out
+    }
+}
+
+/// Replace the coverage collected on this thread, returning the previous collector
+pub(crate) fn swap_coverage(coverage: Option<Coverage>) -> Option<Coverage> {
+    ACTIVE_COVERAGE.with(|active| active.replace(coverage))
+}
+
+/// Count one evaluation of `expr` if coverage is on and it belongs to a tracked function
+pub(crate) fn record(expr: &Value) {
+    let Some(id) = expr_id(expr) else {
+        return;
+    };
+    ACTIVE_COVERAGE.with(|active| {
+        if let Some(coverage) = active.borrow_mut().as_mut() {
+            if let Some(count) = coverage.hits.get_mut(&id) {
+                *count += 1;
+            }
+        }
+    });
+}
+
+/// Start tracking a function defined at top level, replacing an earlier definition
+pub(crate) fn register_function(name: &str, body: &Value) {
+    ACTIVE_COVERAGE.with(|active| {
+        if let Some(coverage) = active.borrow_mut().as_mut() {
+            let mut expressions = Vec::new();
+            collect_expressions(body, &mut expressions);
+            for expr in &expressions {
+                coverage.hits.entry(expr.id).or_default();
+            }
+            let function = FunctionCode {
+                name: name.to_string(),
+                body: body.clone(),
+                expressions,
+            };
+            match coverage.functions.iter_mut().find(|f| f.name == name) {
+                Some(existing) => *existing = function,
+                None => coverage.functions.push(function),
+            }
+            // Forget the replaced body, whose lists may be freed and their addresses reused
+            let live: HashSet<usize> = coverage
+                .functions
+                .iter()
+                .flat_map(|f| f.expressions.iter().map(|expr| expr.id))
+                .collect();
+            coverage.hits.retain(|id, _| live.contains(id));
+        }
+    });
+}
+
+/// Identity of a list expression: the address of its elements, shared by every clone
+fn expr_id(expr: &Value) -> Option<usize> {
+    match expr {
+        Value::List(items) if !items.is_empty() => Some(items.as_ptr() as usize),
+        _ => None,
+    }
+}
+
+fn collect_expressions(expr: &Value, out: &mut Vec<Expression>) {
+    if let Some(id) = expr_id(expr) {
+        out.push(Expression {
+            id,
+            source: source_text(expr),
+        });
+    }
+    for child in code_children(expr) {
+        collect_expressions(child, out);
+    }
+}
+
+/// Code as it was written: like `Display`, but with builtin names instead of `#<builtin>`
+pub fn source_text(expr: &Value) -> String {
+    match expr {
+        Value::List(items) => {
+            let parts: Vec<String> = items.iter().map(source_text).collect();
+            format!("({})", parts.join(" "))
+        }
+        Value::BuiltIn(f) => builtin_name(*f).unwrap_or("#<builtin>").to_string(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn with_coverage(f: impl FnOnce()) -> Coverage {
+        let previous = swap_coverage(Some(Coverage::new()));
+        f();
+        swap_coverage(previous).unwrap()
+    }
+
+    #[test]
+    fn test_records_only_tracked_expressions_when_enabled() {
+        let body = parse("(+ 1 2)").unwrap();
+        record(&body);
+
+        let coverage = with_coverage(|| {
+            register_function("three", &body);
+            record(&body);
+            record(&body.clone());
+            record(&parse("(+ 1 2)").unwrap());
+            record(&parse("x").unwrap());
+        });
+        assert_eq!(coverage.hits(&body), 2);
+        assert_eq!(coverage.hits(&parse("(+ 1 2)").unwrap()), 0);
+        assert_eq!(coverage.hits(&parse("x").unwrap()), 0);
+    }
+
+    #[test]
+    fn test_function_coverage_and_report() {
+        let body = parse("(if (> n 0) (* n 2) (quote (a b)))").unwrap();
+        let Value::List(items) = &body else {
+            unreachable!()
+        };
+        let mut coverage = with_coverage(|| {
+            register_function("double", &body);
+            record(&body);
+            record(&items[1]);
+            record(&items[2]);
+        });
+
+        let double = &coverage.functions()[0];
+        assert_eq!(double.calls, 1);
+        assert_eq!((double.covered, double.total), (3, 4));
+        assert_eq!(double.missed, vec!["(quote (a b))"]);
+
+        let report = coverage.report();
+        assert!(report.starts_with("Coverage: 1 of 1 functions called, 3 of 4 expressions"));
+        assert!(report.contains("double: (quote (a b))"));
+
+        // Another run of the same definition adds its counts expression by expression
+        let again = parse("(if (> n 0) (* n 2) (quote (a b)))").unwrap();
+        let Value::List(items) = &again else {
+            unreachable!()
+        };
+        let more = with_coverage(|| {
+            register_function("double", &again);
+            record(&again);
+            record(&items[3]);
+        });
+        coverage.merge(more);
+        let double = &coverage.functions()[0];
+        assert_eq!(double.calls, 2);
+        assert!(double.missed.is_empty());
+    }
+
+    #[test]
+    fn test_source_text_names_builtins() {
+        let car = crate::builtins::lists::builtin_car;
+        let expr = Value::List(vec![Value::BuiltIn(car), Value::Symbol("xs".into())].into());
+        assert_eq!(source_text(&expr), "(car xs)");
+    }
+}
@@ -0,0 +1,213 @@
+// ABOUTME: Step debugger hooks: pause before evaluating a form and ask the host what to do next
+// The host supplies the UI (the CLI prompts at `debug>`); the evaluator only reports pauses
+
+use crate::env::EnvRef;
+use crate::error::EvalError;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+thread_local! {
+    /// Debugger of the evaluation running on this thread, if one is attached
+    static ACTIVE_DEBUGGER: RefCell<Option<Debugger>> = const { RefCell::new(None) };
+}
+
+/// What the evaluator does after a pause
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugCommand {
+    /// Pause again before the very next form, entering function bodies
+    Step,
+    /// Pause before the next form that is not inside the paused one
+    Next,
+    /// Run until the next `(breakpoint)`
+    Continue,
+    /// Stop the evaluation as if it were interrupted
+    Abort,
+}
+
+/// A form the evaluator is about to evaluate
+pub struct DebugPause<'a> {
+    pub expr: &'a Value,
+    pub env: &'a EnvRef,
+    /// Nesting depth of the form: nested evaluations plus function calls
+    pub depth: usize,
+}
+
+/// Called at every pause; returns how to continue
+pub type DebugHook = Arc<dyn Fn(&DebugPause) -> DebugCommand + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Run,
+    Step,
+    /// Pause at the next form at this depth or shallower
+    Next(usize),
+}
+
+/// A host's debug hook plus where the evaluator should pause next
+#[derive(Clone)]
+pub(crate) struct Debugger {
+    hook: DebugHook,
+    mode: Mode,
+    depth: usize,
+}
+
+impl Debugger {
+    pub(crate) fn new(hook: DebugHook) -> Self {
+        Debugger {
+            hook,
+            mode: Mode::Run,
+            depth: 0,
+        }
+    }
+
+    /// Pause before the next form
+    pub(crate) fn step(&mut self) {
+        self.mode = Mode::Step;
+    }
+
+    /// Run until the next breakpoint
+    pub(crate) fn resume(&mut self) {
+        self.mode = Mode::Run;
+    }
+}
+
+/// Replace the debugger attached on this thread, returning the previous one
+pub(crate) fn swap_debugger(debugger: Option<Debugger>) -> Option<Debugger> {
+    ACTIVE_DEBUGGER.with(|active| active.replace(debugger))
+}
+
+/// Pause before the next form, if a debugger is attached; returns whether one is
+pub(crate) fn request_pause() -> bool {
+    ACTIVE_DEBUGGER.with(|active| match active.borrow_mut().as_mut() {
+        Some(debugger) => {
+            debugger.step();
+            true
+        }
+        None => false,
+    })
+}
+
+/// Give the debugger a chance to pause before `expr` is evaluated
+///
+/// Only non-empty lists (calls and special forms) can pause. Fails with
+/// `EvalError::Interrupted` when the host answers [`DebugCommand::Abort`].
+pub(crate) fn before_eval(expr: &Value, env: &EnvRef) -> Result<(), EvalError> {
+    if !matches!(expr, Value::List(items) if !items.is_empty()) {
+        return Ok(());
+    }
+    let pause = ACTIVE_DEBUGGER.with(|active| {
+        let active = active.borrow();
+        let debugger = active.as_ref()?;
+        let due = match debugger.mode {
+            Mode::Run => false,
+            Mode::Step => true,
+            Mode::Next(depth) => debugger.depth <= depth,
+        };
+        due.then(|| (debugger.hook.clone(), debugger.depth))
+    });
+    let Some((hook, depth)) = pause else {
+        return Ok(());
+    };
+
+    // The hook runs without the slot borrowed, so it may inspect or evaluate freely
+    let command = hook(&DebugPause { expr, env, depth });
+    let mode = match command {
+        DebugCommand::Step => Mode::Step,
+        DebugCommand::Next => Mode::Next(depth),
+        DebugCommand::Continue => Mode::Run,
+        DebugCommand::Abort => Mode::Run,
+    };
+    ACTIVE_DEBUGGER.with(|active| {
+        if let Some(debugger) = active.borrow_mut().as_mut() {
+            debugger.mode = mode;
+        }
+    });
+    if command == DebugCommand::Abort {
+        return Err(EvalError::Interrupted);
+    }
+    Ok(())
+}
+
+/// Depth a trampoline adds while a debugger is attached; dropping it removes that depth
+///
+/// Each `eval_with_macros` call counts one level, and each function call it
+/// makes counts one more, so `next` steps over a call even when the trampoline
+/// runs the callee's body as a tail call.
+#[derive(Default)]
+pub(crate) struct DebugDepth {
+    added: usize,
+}
+
+impl DebugDepth {
+    /// Count one more level, if a debugger is attached
+    pub(crate) fn deepen(&mut self) {
+        ACTIVE_DEBUGGER.with(|active| {
+            if let Some(debugger) = active.borrow_mut().as_mut() {
+                debugger.depth += 1;
+                self.added += 1;
+            }
+        });
+    }
+}
+
+impl Drop for DebugDepth {
+    fn drop(&mut self) {
+        if self.added > 0 {
+            ACTIVE_DEBUGGER.with(|active| {
+                if let Some(debugger) = active.borrow_mut().as_mut() {
+                    debugger.depth = debugger.depth.saturating_sub(self.added);
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::env::Environment;
+    use crate::parser::parse;
+    use std::sync::Mutex;
+
+    #[test]
+    fn test_pauses_follow_commands() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        let hook: DebugHook = Arc::new(move |pause: &DebugPause| {
+            log.lock().unwrap().push(pause.depth);
+            DebugCommand::Next
+        });
+        let previous = swap_debugger(Some(Debugger::new(hook)));
+        let env = Environment::new();
+        let form = parse("(+ 1 2)").unwrap();
+
+        // Idle until asked to pause
+        before_eval(&form, &env).unwrap();
+        assert!(request_pause());
+        before_eval(&parse("x").unwrap(), &env).unwrap();
+        before_eval(&form, &env).unwrap();
+
+        // `next` at depth 0 skips deeper forms
+        let mut depth = DebugDepth::default();
+        depth.deepen();
+        before_eval(&form, &env).unwrap();
+        drop(depth);
+        before_eval(&form, &env).unwrap();
+
+        swap_debugger(previous);
+        assert_eq!(*seen.lock().unwrap(), vec![0, 0]);
+        assert!(!request_pause());
+    }
+
+    #[test]
+    fn test_abort_interrupts() {
+        let hook: DebugHook = Arc::new(|_: &DebugPause| DebugCommand::Abort);
+        let mut debugger = Debugger::new(hook);
+        debugger.step();
+        let previous = swap_debugger(Some(debugger));
+        let result = before_eval(&parse("(f)").unwrap(), &Environment::new());
+        swap_debugger(previous);
+        assert!(matches!(result, Err(EvalError::Interrupted)));
+    }
+}
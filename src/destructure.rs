@@ -0,0 +1,342 @@
+// ABOUTME: Destructuring of list and map patterns in let bindings, and function parameter lists
+// Patterns expand to plain bindings whose inits are car/cdr/map-get calls on a hidden variable
+
+use crate::builtins::higher_order::values_to_list;
+use crate::builtins::lists::{builtin_car, builtin_cdr};
+use crate::builtins::maps::map_get;
+use crate::error::EvalError;
+use crate::symbol::Symbol;
+use crate::value::Value;
+
+/// Marks the element of a list pattern that takes the remaining elements
+const REST_MARKER: &str = "&rest";
+
+/// Whether a binding target is a list or map pattern rather than a plain name
+pub fn is_pattern(target: &Value) -> bool {
+    matches!(target, Value::List(items) if !items.is_empty()) || matches!(target, Value::Map(_))
+}
+
+/// Every name a binding target binds: the symbol itself, or the names inside a pattern
+///
+/// Malformed patterns give the names that can be found; the evaluator reports
+/// the error when the binding runs.
+pub fn pattern_names(target: &Value) -> Vec<Symbol> {
+    let mut names = Vec::new();
+    collect_names(target, &mut names);
+    names
+}
+
+fn collect_names(target: &Value, names: &mut Vec<Symbol>) {
+    match target {
+        Value::Symbol(name) if name != REST_MARKER => names.push(name.clone()),
+        Value::List(items) => items.iter().for_each(|item| collect_names(item, names)),
+        Value::Map(map) => {
+            for (key, pattern) in map {
+                match (key.as_str(), pattern) {
+                    ("keys", Value::List(keys)) => {
+                        keys.iter().for_each(|k| collect_names(k, names))
+                    }
+                    _ => collect_names(pattern, names),
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Expand one binding of `target` to `init` into plain `(name, init)` bindings, in order
+///
+/// A symbol target is returned as is. A pattern first binds `init` to
+/// `hidden`, so it is evaluated once, then binds each name in the pattern to
+/// an accessor on `hidden`:
+///
+/// - `(a b &rest more)` binds `a` to `(car hidden)`, `b` to `(car (cdr hidden))`
+///   and `more` to `(cdr (cdr hidden))`
+/// - `{:keys (a b)}` binds `a` to `(map-get hidden :a)` and `b` likewise
+/// - `{:user (name age)}` destructures `(map-get hidden :user)` with the nested pattern
+///
+/// Elements of list patterns may themselves be patterns. The accessors are the
+/// builtins themselves, so a local named `car` does not change what they do.
+pub fn expand_binding(
+    form: &str,
+    target: &Value,
+    init: Value,
+    hidden: Symbol,
+) -> Result<Vec<(Symbol, Value)>, EvalError> {
+    match target {
+        Value::Symbol(name) => Ok(vec![(name.clone(), init)]),
+        _ if is_pattern(target) => {
+            let mut bindings = vec![(hidden.clone(), init)];
+            expand_pattern(form, target, Value::Symbol(hidden), &mut bindings)?;
+            Ok(bindings)
+        }
+        other => Err(EvalError::runtime_error(
+            form,
+            format!(
+                "binding name must be a symbol or a list or map pattern, got {}",
+                other
+            ),
+        )),
+    }
+}
+
+fn expand_pattern(
+    form: &str,
+    pattern: &Value,
+    source: Value,
+    bindings: &mut Vec<(Symbol, Value)>,
+) -> Result<(), EvalError> {
+    match pattern {
+        Value::Symbol(name) if name != REST_MARKER => {
+            bindings.push((name.clone(), source));
+            Ok(())
+        }
+        Value::List(items) if !items.is_empty() => {
+            // Multiple values from `values` take apart like the list of their elements
+            let mut rest = call(values_to_list, vec![source]);
+            let mut elements = items.iter();
+            while let Some(element) = elements.next() {
+                if matches!(element, Value::Symbol(name) if name == REST_MARKER) {
+                    return match (elements.next(), elements.next()) {
+                        (Some(tail), None) => expand_pattern(form, tail, rest, bindings),
+                        _ => Err(EvalError::runtime_error(
+                            form,
+                            format!("{} must be followed by exactly one pattern", REST_MARKER),
+                        )),
+                    };
+                }
+                expand_pattern(
+                    form,
+                    element,
+                    call(builtin_car, vec![rest.clone()]),
+                    bindings,
+                )?;
+                rest = call(builtin_cdr, vec![rest]);
+            }
+            Ok(())
+        }
+        Value::Map(map) => {
+            for (key, value) in map {
+                if key == "keys" {
+                    let keys = match value {
+                        Value::List(keys) => &keys[..],
+                        Value::Nil => &[],
+                        other => {
+                            return Err(EvalError::runtime_error(
+                                form,
+                                format!(":keys must be a list of symbols, got {}", other),
+                            ))
+                        }
+                    };
+                    for key in keys {
+                        let Value::Symbol(name) = key else {
+                            return Err(EvalError::runtime_error(
+                                form,
+                                format!(":keys must be a list of symbols, got {}", key),
+                            ));
+                        };
+                        let get = map_access(source.clone(), name.as_str());
+                        bindings.push((name.clone(), get));
+                    }
+                } else {
+                    expand_pattern(form, value, map_access(source.clone(), key), bindings)?;
+                }
+            }
+            Ok(())
+        }
+        other => Err(EvalError::runtime_error(
+            form,
+            format!("cannot destructure into {}", other),
+        )),
+    }
+}
+
+fn call(function: fn(&[Value]) -> Result<Value, EvalError>, args: Vec<Value>) -> Value {
+    let mut items = vec![Value::BuiltIn(function)];
+    items.extend(args);
+    Value::List(items.into())
+}
+
+fn map_access(source: Value, key: &str) -> Value {
+    call(map_get, vec![source, Value::Keyword(key.to_string())])
+}
+
+/// A parameter written `(name default)`: a symbol and one expression that is not a bare symbol
+///
+/// A list of a symbol and a bare symbol, such as `(k v)`, is a destructuring
+/// pattern instead, as is any other list.
+pub fn default_param(param: &Value) -> Option<(&Symbol, &Value)> {
+    match param {
+        Value::List(items) if items.len() == 2 => match (&items[0], &items[1]) {
+            (Value::Symbol(_), Value::Symbol(default)) if default != "nil" => None,
+            (Value::Symbol(name), default) if name != REST_MARKER => Some((name, default)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Every name a parameter binds, whether a symbol, an optional parameter or a pattern
+pub fn param_names(param: &Value) -> Vec<Symbol> {
+    match default_param(param) {
+        Some((name, _)) => vec![name.clone()],
+        None => pattern_names(param),
+    }
+}
+
+/// A function's parameter list after expansion
+pub struct ExpandedParams {
+    /// One name per parameter; patterns get hidden names
+    pub names: Vec<Symbol>,
+    /// Default expressions of the trailing optional parameters
+    pub defaults: Vec<Value>,
+    /// The body, destructuring pattern parameters first
+    pub body: Value,
+}
+
+/// Split a parameter list into names and defaults, destructuring pattern parameters in `body`
+///
+/// Optional `(name default)` parameters must come after the required ones.
+/// Each pattern parameter is replaced by a hidden name, and the body is
+/// wrapped in a `let` that destructures it.
+pub fn expand_params(
+    form: &str,
+    params: &[Value],
+    body: Value,
+) -> Result<ExpandedParams, EvalError> {
+    let mut names = Vec::new();
+    let mut defaults = Vec::new();
+    let mut bindings = Vec::new();
+    for (i, param) in params.iter().enumerate() {
+        if let Some((name, default)) = default_param(param) {
+            names.push(name.clone());
+            defaults.push(default.clone());
+            continue;
+        }
+        if !defaults.is_empty() {
+            return Err(EvalError::runtime_error(
+                form,
+                format!("required parameter {} follows an optional one", param),
+            ));
+        }
+        match param {
+            Value::Symbol(name) => names.push(name.clone()),
+            _ if is_pattern(param) => {
+                let hidden = Symbol::new(&format!("#:arg{}", i));
+                let mut expanded = Vec::new();
+                expand_pattern(form, param, Value::Symbol(hidden.clone()), &mut expanded)?;
+                bindings.extend(
+                    expanded
+                        .into_iter()
+                        .map(|(name, init)| Value::List(vec![Value::Symbol(name), init].into())),
+                );
+                names.push(hidden);
+            }
+            _ => {
+                return Err(EvalError::runtime_error(
+                    form,
+                    "parameters must be symbols, (name default) pairs, or list or map patterns",
+                ))
+            }
+        }
+    }
+
+    let body = if bindings.is_empty() {
+        body
+    } else {
+        Value::List(
+            vec![
+                Value::Symbol(Symbol::new("let")),
+                Value::List(bindings.into()),
+                body,
+            ]
+            .into(),
+        )
+    };
+    Ok(ExpandedParams {
+        names,
+        defaults,
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    #[test]
+    fn test_pattern_names() {
+        let names = pattern_names(&parse("((a b) {:keys (c) :d e} &rest f)").unwrap());
+        let mut names: Vec<_> = names.iter().map(|n| n.to_string()).collect();
+        names.sort();
+        assert_eq!(names, vec!["a", "b", "c", "e", "f"]);
+        assert_eq!(pattern_names(&parse("x").unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn test_list_patterns_expand_to_accessors() {
+        let pattern = parse("(a &rest more)").unwrap();
+        let bindings = expand_binding(
+            "let",
+            &pattern,
+            parse("pair").unwrap(),
+            Symbol::new("#:let"),
+        )
+        .unwrap();
+        let shown: Vec<_> = bindings
+            .iter()
+            .map(|(name, init)| format!("{} {}", name, init))
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                "#:let pair",
+                "a (#<builtin> (#<builtin> #:let))",
+                "more (#<builtin> (#<builtin> #:let))"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_rejects_malformed_patterns() {
+        let hidden = Symbol::new("#:let");
+        for pattern in ["(a &rest)", "(a &rest b c)", "{:keys x}", "(1 2)", "\"s\""] {
+            let pattern = parse(pattern).unwrap();
+            assert!(expand_binding("let", &pattern, Value::Nil, hidden.clone()).is_err());
+        }
+    }
+
+    #[test]
+    fn test_default_params_are_told_from_patterns() {
+        for (param, optional) in [
+            ("(y 10)", true),
+            ("(y (+ x 1))", true),
+            ("(y nil)", true),
+            ("(k v)", false),
+            ("(a b c)", false),
+            ("y", false),
+        ] {
+            assert_eq!(
+                default_param(&parse(param).unwrap()).is_some(),
+                optional,
+                "{}",
+                param
+            );
+        }
+
+        let params = parse("(x (y 10) (z \"s\"))").unwrap();
+        let Value::List(params) = params else {
+            unreachable!()
+        };
+        let expanded = expand_params("lambda", &params, Value::Nil).unwrap();
+        assert_eq!(expanded.names.len(), 3);
+        assert_eq!(expanded.defaults.len(), 2);
+
+        let params = parse("((y 10) x)").unwrap();
+        let Value::List(params) = params else {
+            unreachable!()
+        };
+        assert!(expand_params("lambda", &params, Value::Nil).is_err());
+    }
+}
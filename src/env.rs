@@ -1,70 +1,220 @@
 // ABOUTME: Environment module for managing variable bindings and scopes
 
 use crate::error::EvalError;
+use crate::symbol::Symbol;
 use crate::value::Value;
-use std::cell::RefCell;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
 
+/// Shared handle to an environment frame, used everywhere a scope is passed or captured
+pub type EnvRef = Arc<Environment>;
+
+/// Why a binding may not be replaced
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Frozen {
+    /// Frozen by Lisp code with `freeze` or `defconstant`
+    User,
+    /// A builtin or stdlib definition protected by the host
+    Builtin,
+}
+
+/// A scope of variable bindings
+///
+/// Frames are shared with `Arc` and guarded by an `RwLock`, so environments (and
+/// the closures that capture them) can move between threads.
 #[derive(Debug)]
 pub struct Environment {
-    bindings: RefCell<HashMap<String, Value>>,
-    parent: Option<Rc<Environment>>,
+    bindings: RwLock<HashMap<Symbol, Value>>,
+    /// Bindings in this frame that `set!`, `define` and `load-session` may not replace
+    frozen: RwLock<HashMap<Symbol, Frozen>>,
+    parent: Option<EnvRef>,
 }
 
 impl Environment {
     /// Creates a new global environment with no parent
-    pub fn new() -> Rc<Self> {
-        Rc::new(Environment {
-            bindings: RefCell::new(HashMap::new()),
+    pub fn new() -> EnvRef {
+        Arc::new(Environment {
+            bindings: RwLock::new(HashMap::new()),
+            frozen: RwLock::new(HashMap::new()),
             parent: None,
         })
     }
 
     /// Creates a new child environment with a parent
     #[allow(dead_code)]
-    pub fn with_parent(parent: Rc<Environment>) -> Rc<Self> {
-        Rc::new(Environment {
-            bindings: RefCell::new(HashMap::new()),
+    pub fn with_parent(parent: EnvRef) -> EnvRef {
+        Arc::new(Environment {
+            bindings: RwLock::new(HashMap::new()),
+            frozen: RwLock::new(HashMap::new()),
             parent: Some(parent),
         })
     }
 
+    // A panic while holding the lock cannot leave a HashMap half-updated in a way
+    // that matters here, so poisoning is ignored.
+    fn frame(&self) -> RwLockReadGuard<'_, HashMap<Symbol, Value>> {
+        self.bindings.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn frame_mut(&self) -> RwLockWriteGuard<'_, HashMap<Symbol, Value>> {
+        self.bindings.write().unwrap_or_else(|e| e.into_inner())
+    }
+
     /// Defines a binding in THIS scope (doesn't walk parent chain)
-    pub fn define(&self, name: String, value: Value) {
-        self.bindings.borrow_mut().insert(name, value);
+    pub fn define(&self, name: impl Into<Symbol>, value: Value) {
+        self.frame_mut().insert(name.into(), value);
     }
 
-    /// Looks up a symbol in THIS scope and parent scopes recursively
-    pub fn get(&self, name: &str) -> Option<Value> {
-        // First check this scope
-        if let Some(value) = self.bindings.borrow().get(name) {
-            return Some(value.clone());
+    /// The enclosing scope, if this is not a global environment
+    pub fn parent(&self) -> Option<&EnvRef> {
+        self.parent.as_ref()
+    }
+
+    /// Whether this is a global environment (it has no parent)
+    pub fn is_global(&self) -> bool {
+        self.parent.is_none()
+    }
+
+    /// Removes a binding from THIS scope, returning its value
+    pub fn undefine(&self, name: &str) -> Option<Value> {
+        self.frame_mut().remove(name)
+    }
+
+    /// Whether THIS scope (not parents) has a binding for `name`
+    pub fn defines(&self, name: &str) -> bool {
+        self.frame().contains_key(name)
+    }
+
+    /// A copy of the bindings defined in THIS scope (not parents), in no particular order
+    pub fn bindings(&self) -> Vec<(Symbol, Value)> {
+        self.frame()
+            .iter()
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect()
+    }
+
+    /// Names bound in this scope and all of its parents, without duplicates
+    pub fn visible_names(&self) -> Vec<Symbol> {
+        let mut names = HashSet::new();
+        let mut scope = Some(self);
+        while let Some(env) = scope {
+            names.extend(env.frame().keys().cloned());
+            scope = env.parent.as_deref();
         }
+        names.into_iter().collect()
+    }
+
+    /// Creates a child environment whose frame starts out with the given bindings
+    ///
+    /// Function calls use this to build the whole parameter frame at once instead
+    /// of growing it with one `define` per parameter.
+    pub fn with_bindings(
+        parent: EnvRef,
+        bindings: impl IntoIterator<Item = (Symbol, Value)>,
+    ) -> Arc<Self> {
+        Arc::new(Environment {
+            bindings: RwLock::new(bindings.into_iter().collect()),
+            frozen: RwLock::new(HashMap::new()),
+            parent: Some(parent),
+        })
+    }
 
-        // Then check parent scope
-        if let Some(ref parent) = self.parent {
-            return parent.get(name);
+    /// Looks up a symbol in THIS scope and parent scopes
+    pub fn get(&self, name: &str) -> Option<Value> {
+        // Walk the frames iteratively; deep closures shouldn't cost stack
+        let mut scope = self;
+        loop {
+            if let Some(value) = scope.frame().get(name) {
+                return Some(value.clone());
+            }
+            scope = scope.parent.as_deref()?;
         }
+    }
 
-        None
+    /// Marks a binding in THIS scope as frozen; returns false if it is not defined here
+    ///
+    /// Frozen bindings cannot be changed by `set!` or replaced by `define` or
+    /// `load-session`. There is no way back from Lisp code; the host can still
+    /// replace them with [`Environment::define`].
+    pub fn freeze(&self, name: &str) -> bool {
+        self.freeze_as(name, Frozen::User)
     }
 
-    /// Updates an existing binding (for later use with set!)
-    #[allow(dead_code)]
-    pub fn set(&self, name: &str, value: Value) -> Result<(), EvalError> {
-        // Check if it exists in this scope
-        if self.bindings.borrow().contains_key(name) {
-            self.bindings.borrow_mut().insert(name.to_string(), value);
-            return Ok(());
+    /// Freezes a builtin binding in THIS scope, like [`Environment::freeze`]
+    ///
+    /// Errors about a protected binding point at `--allow-shadow-builtins`
+    /// instead of calling it frozen.
+    pub fn protect(&self, name: &str) -> bool {
+        self.freeze_as(name, Frozen::Builtin)
+    }
+
+    fn freeze_as(&self, name: &str, why: Frozen) -> bool {
+        if !self.defines(name) {
+            return false;
         }
+        // Freezing a protected builtin again doesn't change why it is frozen
+        self.frozen
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(Symbol::new(name))
+            .or_insert(why);
+        true
+    }
+
+    /// Whether a binding in THIS scope is frozen
+    pub fn is_frozen(&self, name: &str) -> bool {
+        self.frozen_as(name).is_some()
+    }
 
-        // Check parent scope
-        if let Some(ref parent) = self.parent {
-            return parent.set(name, value);
+    /// Why a binding in THIS scope is frozen, if it is
+    pub fn frozen_as(&self, name: &str) -> Option<Frozen> {
+        self.frozen
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .copied()
+    }
+
+    /// Why the binding `name` resolves to is frozen, looking in the scope that
+    /// defines it, or `None` if it is unbound or not frozen
+    pub fn resolved_frozen_as(&self, name: &str) -> Option<Frozen> {
+        let mut scope = self;
+        loop {
+            if scope.defines(name) {
+                return scope.frozen_as(name);
+            }
+            scope = scope.parent.as_deref()?;
         }
+    }
+
+    /// The error for trying to `verb` ("change", "redefine") a frozen binding
+    /// in THIS scope, or `None` if it isn't frozen
+    pub fn frozen_message(&self, verb: &str, name: &str) -> Option<String> {
+        self.frozen_as(name).map(|why| match why {
+            Frozen::User => format!("cannot {} frozen binding {}", verb, name),
+            Frozen::Builtin => format!(
+                "cannot {} builtin {}; pass --allow-shadow-builtins to allow it",
+                verb, name
+            ),
+        })
+    }
 
-        Err(EvalError::UndefinedSymbol(name.to_string()))
+    /// Updates an existing binding (for set!); fails if it is undefined or frozen
+    pub fn set(&self, name: &str, value: Value) -> Result<(), EvalError> {
+        let mut scope = self;
+        loop {
+            if let Some(slot) = scope.frame_mut().get_mut(name) {
+                if let Some(message) = scope.frozen_message("change", name) {
+                    return Err(EvalError::runtime_error("set!", message));
+                }
+                *slot = value;
+                return Ok(());
+            }
+            match scope.parent.as_deref() {
+                Some(parent) => scope = parent,
+                None => return Err(EvalError::undefined_symbol(name, self)),
+            }
+        }
     }
 }
 
@@ -118,6 +268,40 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_with_bindings() {
+        let parent = Environment::new();
+        parent.define("x".to_string(), Value::Number(1.0));
+
+        let child = Environment::with_bindings(
+            parent,
+            vec![
+                (Symbol::new("y"), Value::Number(2.0)),
+                (Symbol::new("x"), Value::Number(3.0)),
+            ],
+        );
+
+        match (child.get("x"), child.get("y")) {
+            (Some(Value::Number(x)), Some(Value::Number(y))) => {
+                assert_eq!(x, 3.0);
+                assert_eq!(y, 2.0);
+            }
+            _ => panic!("Expected both bindings in the new frame"),
+        }
+    }
+
+    #[test]
+    fn test_set_updates_defining_scope() {
+        let parent = Environment::new();
+        parent.define("x".to_string(), Value::Number(1.0));
+        let child = Environment::with_parent(parent.clone());
+
+        child.set("x", Value::Number(5.0)).unwrap();
+
+        assert!(matches!(parent.get("x"), Some(Value::Number(n)) if n == 5.0));
+        assert!(child.set("missing", Value::Nil).is_err());
+    }
+
     #[test]
     fn test_multiple_levels() {
         // Grandparent
@@ -148,4 +332,45 @@ mod tests {
             _ => panic!("Expected Number(3.0)"),
         }
     }
+
+    #[test]
+    fn test_frozen_bindings_reject_set() {
+        let parent = Environment::new();
+        parent.define("config", Value::Number(1.0));
+        assert!(!parent.freeze("missing"));
+        assert!(parent.freeze("config"));
+
+        let child = Environment::with_parent(parent.clone());
+        assert!(child.set("config", Value::Number(2.0)).is_err());
+        assert!(!child.is_frozen("config"));
+
+        // A local binding with the same name is a different binding
+        child.define("config", Value::Number(3.0));
+        assert!(child.set("config", Value::Number(4.0)).is_ok());
+        assert!(matches!(parent.get("config"), Some(Value::Number(n)) if n == 1.0));
+    }
+
+    #[test]
+    fn test_protected_bindings_name_the_flag() {
+        let env = Environment::new();
+        env.define("+", Value::Nil);
+        env.define("limit", Value::Nil);
+        assert!(env.protect("+"));
+        assert!(env.freeze("limit"));
+        // Freezing a protected builtin keeps it protected
+        assert!(env.freeze("+"));
+
+        let err = env.set("+", Value::Nil).unwrap_err().to_string();
+        assert!(
+            err.contains("cannot change builtin +; pass --allow-shadow-builtins"),
+            "{}",
+            err
+        );
+        let err = env.set("limit", Value::Nil).unwrap_err().to_string();
+        assert!(
+            err.contains("cannot change frozen binding limit"),
+            "{}",
+            err
+        );
+    }
 }
@@ -1,30 +1,87 @@
 // ABOUTME: Error types for evaluation failures in the Lisp interpreter
 
+use crate::env::Environment;
+use crate::sandbox::SandboxError;
 use crate::value::Value;
+use std::collections::BTreeMap;
 use thiserror::Error;
 
 // ===== Arity constant strings (eliminates allocations in error paths) =====
+pub const ARITY_ZERO: &str = "0";
 pub const ARITY_ONE: &str = "1";
 pub const ARITY_TWO: &str = "2";
 pub const ARITY_THREE: &str = "3";
 pub const ARITY_AT_LEAST_ONE: &str = "at least 1";
+pub const ARITY_AT_LEAST_TWO: &str = "at least 2";
+pub const ARITY_AT_LEAST_THREE: &str = "at least 3";
 pub const ARITY_ZERO_OR_ONE: &str = "0-1";
 pub const ARITY_ONE_OR_TWO: &str = "1-2";
 pub const ARITY_TWO_OR_THREE: &str = "2-3";
+pub const ARITY_THREE_OR_FOUR: &str = "3-4";
+pub const ARITY_ONE_TO_THREE: &str = "1-3";
 
 // ===== Common error message strings =====
 pub const ERR_SANDBOX_NOT_INIT: &str = "Sandbox not initialized";
 
+/// Longest value sketch shown in a type error, in characters
+const SKETCH_LIMIT: usize = 40;
+
+/// Printed form of a data value for error messages, cut to `SKETCH_LIMIT` characters
+///
+/// Nil, functions and handles have no sketch: their type name already says everything.
+fn sketch(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(_)
+        | Value::Bool(_)
+        | Value::String(_)
+        | Value::Symbol(_)
+        | Value::Keyword(_)
+        | Value::List(_)
+        | Value::Map(_)
+        | Value::Decimal(_) => {
+            let printed = value.to_string();
+            match printed.char_indices().nth(SKETCH_LIMIT) {
+                Some((end, _)) => Some(format!("{}...", &printed[..end])),
+                None => Some(printed),
+            }
+        }
+        _ => None,
+    }
+}
+
+/// The `map: ` before an error raised inside a builtin, or nothing
+fn caller_prefix(function: &Option<String>) -> String {
+    function
+        .as_ref()
+        .map_or_else(String::new, |function| format!("{}: ", function))
+}
+
+/// The ` (did you mean `map-get`?)` after an undefined symbol, or nothing
+fn suggestion_hint(suggestion: &Option<String>) -> String {
+    suggestion
+        .as_ref()
+        .map_or_else(String::new, |name| format!(" (did you mean `{}`?)", name))
+}
+
+/// The ` 5` in "got number 5", or nothing when there is no sketch
+fn describe_value(value: &Option<String>) -> String {
+    value
+        .as_ref()
+        .map_or_else(String::new, |value| format!(" {}", value))
+}
+
 #[allow(dead_code)]
 #[derive(Error, Debug, Clone)]
 pub enum EvalError {
     // ===== Enhanced error variants with rich context =====
-    /// Type mismatch error with function name, expected type, actual type, and position
-    #[error("{function}: expected {expected}, got {actual} at argument {position}")]
+    /// Type mismatch error with function name, expected type, actual type, a sketch of the
+    /// received value, and position
+    #[error("{function}: expected {expected}, got {actual}{} at argument {position}", describe_value(.value))]
     TypeMismatch {
         function: String,
         expected: String,
         actual: String,
+        value: Option<String>, // Short printed form, for values whose type alone says little
         position: usize,
     },
 
@@ -40,12 +97,56 @@ pub enum EvalError {
     #[error("{function}: {message}")]
     RuntimeError { function: String, message: String },
 
+    /// A sandboxed file or network operation failed; `error` keeps the cause for its kind
+    #[error("{function}: {message}")]
+    Sandbox {
+        function: String,
+        message: String,
+        error: SandboxError,
+    },
+
     // ===== Special error variants (non-contextual by nature) =====
-    #[error("Undefined symbol: {0}")]
-    UndefinedSymbol(String),
+    /// A name with no binding, and the closest known name when one is near enough
+    #[error("Undefined symbol: {name}{}", suggestion_hint(.suggestion))]
+    UndefinedSymbol {
+        name: String,
+        suggestion: Option<String>,
+    },
+
+    /// Calling a value that is not a function, and the builtin that made the call if any
+    #[error("{}{actual}{} is not callable", caller_prefix(.function), describe_value(.value))]
+    NotCallable {
+        function: Option<String>,
+        actual: String,
+        value: Option<String>,
+    },
+
+    /// Evaluation was stopped through a `CancellationToken` (e.g. Ctrl-C)
+    #[error("Interrupted")]
+    Interrupted,
 
-    #[error("Value is not callable")]
-    NotCallable,
+    /// Evaluation took more steps than the interpreter's step limit allows
+    #[error("Step limit of {0} exceeded")]
+    StepLimitExceeded(u64),
+
+    /// Evaluations nested deeper than the interpreter's depth limit, as non-tail recursion does
+    #[error("Recursion too deep: more than {0} nested evaluations")]
+    RecursionTooDeep(usize),
+
+    /// More calls waiting for their arguments than the pending-call limit allows
+    ///
+    /// Shares the `recursion-too-deep` kind, since runaway non-tail recursion is
+    /// what usually gets here.
+    #[error("Recursion too deep: more than {0} calls waiting for their arguments (see --max-pending-calls)")]
+    TooManyPendingCalls(usize),
+
+    /// Raised by `break` and caught by the innermost running loop
+    #[error("break: not inside a loop")]
+    Break(Box<Value>),
+
+    /// Raised by `continue` and caught by the innermost running loop
+    #[error("continue: not inside a loop")]
+    Continue,
 }
 
 impl EvalError {
@@ -55,6 +156,7 @@ impl EvalError {
             function: function.to_string(),
             expected: expected.to_string(),
             actual: actual.type_name(),
+            value: sketch(actual),
             position,
         }
     }
@@ -75,4 +177,129 @@ impl EvalError {
             message: message.into(),
         }
     }
+
+    /// Create an error for calling `value`; `function` names the builtin that called it
+    pub fn not_callable(function: Option<&str>, value: &Value) -> Self {
+        EvalError::NotCallable {
+            function: function.map(str::to_string),
+            actual: value.type_name(),
+            value: sketch(value),
+        }
+    }
+
+    /// Create an undefined symbol error, suggesting the nearest name visible from `env`
+    /// or documented in the help registry
+    pub fn undefined_symbol(name: &str, env: &Environment) -> Self {
+        EvalError::UndefinedSymbol {
+            name: name.to_string(),
+            suggestion: crate::help::did_you_mean(name, env),
+        }
+    }
+
+    /// Wrap a sandbox failure, keeping its kind
+    pub fn sandbox_error(function: &str, error: SandboxError) -> Self {
+        EvalError::Sandbox {
+            function: function.to_string(),
+            message: error.to_string(),
+            error,
+        }
+    }
+
+    /// Stable keyword name for this error, as returned by `error-kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            EvalError::TypeMismatch { .. } => "type-error",
+            EvalError::ArityError { .. } => "arity-error",
+            EvalError::RuntimeError { .. } => "runtime-error",
+            EvalError::Sandbox { error, .. } => error.kind(),
+            EvalError::UndefinedSymbol { .. } => "undefined-symbol",
+            EvalError::NotCallable { .. } => "not-callable",
+            EvalError::Interrupted => "interrupted",
+            EvalError::StepLimitExceeded(_) => "step-limit-exceeded",
+            EvalError::RecursionTooDeep(_) | EvalError::TooManyPendingCalls(_) => {
+                "recursion-too-deep"
+            }
+            EvalError::Break(_) | EvalError::Continue => "runtime-error",
+        }
+    }
+
+    /// Details for `error-data`, keyed as in Lisp maps
+    pub fn data(&self) -> BTreeMap<String, Value> {
+        let text = |s: &str| Value::String(s.into());
+        let mut data = BTreeMap::new();
+        match self {
+            EvalError::TypeMismatch {
+                function,
+                expected,
+                actual,
+                value,
+                position,
+            } => {
+                data.insert("function".to_string(), text(function));
+                data.insert("expected".to_string(), text(expected));
+                data.insert("actual".to_string(), text(actual));
+                if let Some(value) = value {
+                    data.insert("value".to_string(), text(value));
+                }
+                data.insert("position".to_string(), Value::Number(*position as f64));
+            }
+            EvalError::ArityError {
+                function,
+                expected,
+                actual,
+            } => {
+                data.insert("function".to_string(), text(function));
+                data.insert("expected".to_string(), text(expected));
+                data.insert("actual".to_string(), Value::Number(*actual as f64));
+            }
+            EvalError::RuntimeError { function, .. } => {
+                data.insert("function".to_string(), text(function));
+            }
+            EvalError::Sandbox {
+                function, error, ..
+            } => {
+                data.insert("function".to_string(), text(function));
+                match error {
+                    SandboxError::PathNotAllowed(path) | SandboxError::FileNotFound(path) => {
+                        data.insert("path".to_string(), text(path));
+                    }
+                    SandboxError::AddressNotAllowed { url, reason } => {
+                        data.insert("url".to_string(), text(url));
+                        data.insert("reason".to_string(), text(reason));
+                    }
+                    SandboxError::HttpStatus { status, .. } => {
+                        data.insert("status".to_string(), Value::Number(*status as f64));
+                    }
+                    _ => {}
+                }
+            }
+            EvalError::NotCallable {
+                function,
+                actual,
+                value,
+            } => {
+                if let Some(function) = function {
+                    data.insert("function".to_string(), text(function));
+                }
+                data.insert("actual".to_string(), text(actual));
+                if let Some(value) = value {
+                    data.insert("value".to_string(), text(value));
+                }
+            }
+            EvalError::UndefinedSymbol { name, suggestion } => {
+                data.insert("symbol".to_string(), text(name));
+                if let Some(suggestion) = suggestion {
+                    data.insert("suggestion".to_string(), text(suggestion));
+                }
+            }
+            EvalError::StepLimitExceeded(limit) => {
+                data.insert("limit".to_string(), Value::Number(*limit as f64));
+            }
+            EvalError::RecursionTooDeep(limit) | EvalError::TooManyPendingCalls(limit) => {
+                data.insert("limit".to_string(), Value::Number(*limit as f64));
+            }
+            _ => {}
+        }
+        data
+    }
 }
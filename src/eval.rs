@@ -1,75 +1,573 @@
 // ABOUTME: Evaluator module for executing parsed Lisp expressions
 
-use crate::env::Environment;
-use crate::error::{EvalError, ARITY_ONE, ARITY_TWO_OR_THREE};
-use crate::macros::MacroRegistry;
+use crate::analyze::{expand_all, SpecialForm};
+use crate::builtins::debug::{trace_expansion, TraceCall};
+use crate::cancel::{CancellationToken, DEFAULT_MAX_EVAL_DEPTH, DEFAULT_MAX_PENDING_CALLS};
+use crate::debugger::DebugDepth;
+use crate::destructure;
+use crate::env::{EnvRef, Environment};
+use crate::error::{
+    EvalError, ARITY_AT_LEAST_ONE, ARITY_ONE, ARITY_TWO, ARITY_TWO_OR_THREE, ARITY_ZERO,
+    ARITY_ZERO_OR_ONE,
+};
+use crate::macros::{MacroDef, MacroRegistry};
+use crate::optimize::optimize;
 use crate::parser;
-use crate::value::Value;
-use std::rc::Rc;
+use crate::profiler::{self, Frame, Profiler};
+use crate::symbol::Symbol;
+use crate::value::{ErrorValue, Promise, Value};
+use std::cell::{Cell, RefCell};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use std::thread::LocalKey;
+
+type BuiltinFn = fn(&[Value]) -> Result<Value, EvalError>;
+
+thread_local! {
+    /// Macro registry of the evaluation that is currently calling a builtin
+    static ACTIVE_MACROS: RefCell<Option<MacroRegistry>> = const { RefCell::new(None) };
+    /// Macro expansions in progress on this thread, checked against the registry's depth limit
+    static EXPANSION_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Evaluations in progress on this thread, checked against the depth limit
+    static EVAL_DEPTH: Cell<usize> = const { Cell::new(0) };
+    /// Calls waiting for their operands on this thread, checked against the pending-call limit
+    static PENDING_CALLS: Cell<usize> = const { Cell::new(0) };
+    /// Builtin running on this thread, named when it calls something that isn't a function
+    static CALLING_BUILTIN: Cell<Option<BuiltinFn>> = const { Cell::new(None) };
+    /// Token checked on every evaluation step on this thread
+    static ACTIVE_CANCELLATION: RefCell<Option<CancellationToken>> = const { RefCell::new(None) };
+}
+
+/// Replace the cancellation token checked on this thread, returning the previous one
+pub(crate) fn swap_cancellation(token: Option<CancellationToken>) -> Option<CancellationToken> {
+    ACTIVE_CANCELLATION.with(|active| active.replace(token))
+}
+
+/// Cancellation token checked on this thread, for handing to threads started by `spawn`
+pub(crate) fn active_cancellation() -> Option<CancellationToken> {
+    ACTIVE_CANCELLATION.with(|active| active.borrow().clone())
+}
+
+/// Count an evaluation step against the active token's cancellation flag and step limit
+fn check_cancelled() -> Result<(), EvalError> {
+    ACTIVE_CANCELLATION.with(|active| match active.borrow().as_ref() {
+        Some(token) => token.step(),
+        None => Ok(()),
+    })
+}
 
 /// Main evaluation function with tail call optimization
 #[allow(dead_code)]
-pub fn eval(expr: Value, env: Rc<Environment>) -> Result<Value, EvalError> {
-    eval_with_macros(expr, env, &mut MacroRegistry::new())
+pub fn eval(expr: Value, env: EnvRef) -> Result<Value, EvalError> {
+    eval_with_macros(expr, env, &MacroRegistry::new())
+}
+
+/// Evaluate an expression on behalf of a builtin (e.g. `force`)
+///
+/// Uses the macro registry of the evaluation that invoked the builtin, so user
+/// macros keep expanding inside code the builtin calls back into.
+pub fn eval_from_builtin(expr: Value, env: EnvRef) -> Result<Value, EvalError> {
+    let macro_reg = active_macros().unwrap_or_default();
+    eval_with_macros(expr, env, &macro_reg)
+}
+
+/// Macro registry of the evaluation currently calling a builtin, if any
+pub(crate) fn active_macros() -> Option<MacroRegistry> {
+    ACTIVE_MACROS.with(|active| active.borrow().clone())
+}
+
+/// Run a builtin, noting it as the caller of any function values it applies
+fn call_builtin(function: BuiltinFn, args: &[Value]) -> Result<Value, EvalError> {
+    let previous = CALLING_BUILTIN.with(|calling| calling.replace(Some(function)));
+    let result = function(args);
+    CALLING_BUILTIN.with(|calling| calling.set(previous));
+    result
+}
+
+/// Call a function value with already-evaluated arguments on behalf of a builtin
+///
+/// Used by higher-order builtins such as `map`. Lambda bodies are evaluated with
+/// the caller's macro registry, like `eval_from_builtin`.
+pub fn apply_function(func: &Value, args: Vec<Value>) -> Result<Value, EvalError> {
+    match func {
+        Value::Lambda {
+            params,
+            defaults,
+            body,
+            env,
+            ..
+        } => {
+            let trace = TraceCall::enter(func, &args);
+            let call_env = bind_params("<lambda>", params, defaults, args, env, eval_from_builtin)?;
+            let mut frame = Frame::default();
+            frame.enter_lambda(body, &Value::Nil);
+            let result = eval_from_builtin((**body).clone(), call_env);
+            match trace {
+                Some(trace) => trace.finish(result),
+                None => result,
+            }
+        }
+        Value::BuiltIn(f) => {
+            let trace = TraceCall::enter(func, &args);
+            let mut frame = Frame::default();
+            frame.enter_builtin(*f);
+            let result = call_builtin(*f, &args);
+            match trace {
+                Some(trace) => trace.finish(result),
+                None => result,
+            }
+        }
+        _ => {
+            let caller = CALLING_BUILTIN
+                .with(Cell::get)
+                .and_then(crate::builtins::builtin_name);
+            Err(EvalError::not_callable(caller, func))
+        }
+    }
+}
+
+/// Bind a lambda's parameters for a call, returning the call's environment
+///
+/// Arguments fill the parameters in order. Once the required parameters are
+/// filled, `:name value` pairs set optional parameters by name, and optional
+/// parameters left unset get their defaults, evaluated by `eval_default` in
+/// the call environment so they can refer to earlier parameters.
+fn bind_params(
+    name: &str,
+    params: &[Symbol],
+    defaults: &[Value],
+    args: Vec<Value>,
+    env: &EnvRef,
+    mut eval_default: impl FnMut(Value, EnvRef) -> Result<Value, EvalError>,
+) -> Result<EnvRef, EvalError> {
+    if defaults.is_empty() {
+        if params.len() != args.len() {
+            return Err(EvalError::arity_error(
+                name,
+                params.len().to_string(),
+                args.len(),
+            ));
+        }
+        return Ok(Environment::with_bindings(
+            env.clone(),
+            params.iter().cloned().zip(args),
+        ));
+    }
+
+    let required = params.len() - defaults.len();
+    let arity_error =
+        |given| EvalError::arity_error(name, format!("{}-{}", required, params.len()), given);
+    let given = args.len();
+    let mut slots: Vec<Option<Value>> = vec![None; params.len()];
+    let mut positional = 0;
+    let mut by_keyword = false;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if positional >= required {
+            if let Value::Keyword(key) = &arg {
+                if let Some(i) = params[required..].iter().position(|p| p.as_str() == key) {
+                    let value = args.next().ok_or_else(|| {
+                        EvalError::runtime_error(name, format!("missing value for :{}", key))
+                    })?;
+                    if slots[required + i].replace(value).is_some() {
+                        return Err(EvalError::runtime_error(
+                            name,
+                            format!("parameter {} given twice", key),
+                        ));
+                    }
+                    by_keyword = true;
+                    continue;
+                }
+            }
+        }
+        if by_keyword {
+            return Err(EvalError::runtime_error(
+                name,
+                "positional argument after keyword arguments",
+            ));
+        }
+        if positional == params.len() {
+            return Err(arity_error(given));
+        }
+        slots[positional] = Some(arg);
+        positional += 1;
+    }
+    if positional < required {
+        return Err(arity_error(given));
+    }
+
+    let call_env = Environment::with_bindings(
+        env.clone(),
+        params
+            .iter()
+            .zip(&slots)
+            .filter_map(|(param, slot)| Some((param.clone(), slot.clone()?))),
+    );
+    for (i, slot) in slots.iter().enumerate().skip(required) {
+        if slot.is_none() {
+            let value = eval_default(defaults[i - required].clone(), call_env.clone())?;
+            call_env.define(params[i].clone(), value);
+        }
+    }
+    Ok(call_env)
+}
+
+/// What is left of a form with a tail position after one trampoline step
+#[derive(Debug)]
+pub(crate) enum Step {
+    /// The form finished with this value
+    Done(Value),
+    /// The form's value is that of this expression in this environment
+    Tail(Value, EnvRef),
+}
+
+/// Evaluate a form up to the expression in its tail position
+///
+/// The trampoline loops on a [`Step::Tail`] instead of recursing, so a call
+/// there does not grow the Rust stack. This covers `if`, `case`, `begin`,
+/// `let`, `->` and `->>`, and so every user macro that expands into them (the
+/// stdlib defines no `cond`, `when` or `unless`). `args` are the expressions
+/// after the head.
+pub(crate) fn tail_step(
+    form: SpecialForm,
+    args: &[Value],
+    env: &EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Step, EvalError> {
+    match form {
+        SpecialForm::If => {
+            if args.len() < 2 || args.len() > 3 {
+                return Err(EvalError::arity_error("if", ARITY_TWO_OR_THREE, args.len()));
+            }
+
+            let condition = eval_with_macros(args[0].clone(), env.clone(), macro_reg)?;
+            let is_true = match condition {
+                Value::Bool(b) => b,
+                Value::Nil => false,
+                _ => true, // Everything except #f and nil is truthy
+            };
+
+            Ok(match args.get(if is_true { 1 } else { 2 }) {
+                Some(branch) => Step::Tail(branch.clone(), env.clone()),
+                None => Step::Done(Value::Nil),
+            })
+        }
+        SpecialForm::Case => Ok(match case_branch(args, env, macro_reg)? {
+            Some(tail) => Step::Tail(tail, env.clone()),
+            None => Step::Done(Value::Nil),
+        }),
+        SpecialForm::Begin => body_step(args, env.clone(), macro_reg),
+        SpecialForm::Let => let_step(args, env, macro_reg),
+        SpecialForm::ThreadFirst | SpecialForm::ThreadLast => {
+            // Rewrite into nested calls, whose outermost call is the tail
+            let last = form == SpecialForm::ThreadLast;
+            let Some((first, steps)) = args.split_first() else {
+                let name = if last { "->>" } else { "->" };
+                return Err(EvalError::arity_error(name, ARITY_AT_LEAST_ONE, 0));
+            };
+            let tail = steps
+                .iter()
+                .fold(first.clone(), |acc, step| thread_step(step, acc, last));
+            Ok(Step::Tail(tail, env.clone()))
+        }
+        other => unreachable!("{:?} has no tail position", other),
+    }
+}
+
+/// Evaluate all but the last expression of a body, leaving the last as the tail
+fn body_step(body: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Step, EvalError> {
+    let Some((last, init)) = body.split_last() else {
+        return Ok(Step::Done(Value::Nil));
+    };
+    for expr in init {
+        eval_with_macros(expr.clone(), env.clone(), macro_reg)?;
+    }
+    Ok(Step::Tail(last.clone(), env))
+}
+
+/// One evaluation in progress on this thread, counted until it is dropped
+struct EvalDepth {
+    counter: &'static LocalKey<Cell<usize>>,
+    level: usize,
+}
+
+impl EvalDepth {
+    /// Count a nested evaluation, failing once the active token's depth limit is reached
+    fn enter() -> Result<Self, EvalError> {
+        Self::count(
+            &EVAL_DEPTH,
+            CancellationToken::depth_limit,
+            DEFAULT_MAX_EVAL_DEPTH,
+            EvalError::RecursionTooDeep,
+        )
+    }
+
+    /// Count a call waiting for its operands, failing once the pending-call limit is reached
+    fn wait() -> Result<Self, EvalError> {
+        Self::count(
+            &PENDING_CALLS,
+            CancellationToken::pending_call_limit,
+            DEFAULT_MAX_PENDING_CALLS,
+            EvalError::TooManyPendingCalls,
+        )
+    }
+
+    fn count(
+        counter: &'static LocalKey<Cell<usize>>,
+        limit: fn(&CancellationToken) -> Option<usize>,
+        default: usize,
+        too_deep: fn(usize) -> EvalError,
+    ) -> Result<Self, EvalError> {
+        let level = counter.with(Cell::get);
+        let max = ACTIVE_CANCELLATION.with(|active| match active.borrow().as_ref() {
+            Some(token) => limit(token),
+            None => Some(default),
+        });
+        if let Some(max) = max.filter(|&max| level >= max) {
+            return Err(too_deep(max));
+        }
+        counter.with(|depth| depth.set(level + 1));
+        Ok(EvalDepth { counter, level })
+    }
+}
+
+impl Drop for EvalDepth {
+    fn drop(&mut self) {
+        self.counter.with(|depth| depth.set(self.level));
+    }
+}
+
+/// A call whose head and arguments are being evaluated, waiting on its trampoline
+///
+/// Waiting on the heap instead of recursing keeps calls nested as operands,
+/// such as deep arithmetic or non-tail recursion, off the Rust stack. Waiting
+/// calls count toward the pending-call limit rather than the depth limit,
+/// which guards the stack.
+struct PendingCall {
+    /// The call expression: head, then arguments
+    items: Arc<[Value]>,
+    /// Head and arguments evaluated so far
+    values: Vec<Value>,
+    env: EnvRef,
+    /// The caller's profiler frame and debugger depth, set aside while operands evaluate
+    frame: Frame,
+    debug_depth: DebugDepth,
+    depth: Option<EvalDepth>,
+}
+
+impl PendingCall {
+    fn new(items: Arc<[Value]>, env: EnvRef) -> Self {
+        PendingCall {
+            values: Vec::with_capacity(items.len()),
+            items,
+            env,
+            frame: Frame::default(),
+            debug_depth: DebugDepth::default(),
+            depth: None,
+        }
+    }
+
+    /// The next operand that needs the trampoline, taking literals and symbols as they come
+    fn next_operand(&mut self) -> Result<Option<Value>, EvalError> {
+        while let Some(operand) = self.items.get(self.values.len()) {
+            let value = match operand {
+                Value::Number(_)
+                | Value::Bool(_)
+                | Value::String(_)
+                | Value::Keyword(_)
+                | Value::Nil => operand.clone(),
+                Value::Symbol(name) if name == "nil" => Value::Nil,
+                Value::Symbol(name) => self
+                    .env
+                    .get(name)
+                    .ok_or_else(|| EvalError::undefined_symbol(name, &self.env))?,
+                _ => return Ok(Some(operand.clone())),
+            };
+            // The step a nested evaluation of the operand would have counted
+            check_cancelled()?;
+            self.values.push(value);
+        }
+        Ok(None)
+    }
+
+    /// Wait while an operand evaluates, as if in a nested evaluation of its own
+    fn suspend(
+        &mut self,
+        frame: &mut Frame,
+        debug_depth: &mut DebugDepth,
+    ) -> Result<(), EvalError> {
+        if self.depth.is_none() {
+            self.depth = Some(EvalDepth::wait()?);
+            self.frame = std::mem::take(frame);
+            self.debug_depth = std::mem::take(debug_depth);
+        }
+        // Ends the previous operand's frame and depth
+        *frame = Frame::default();
+        *debug_depth = DebugDepth::default();
+        debug_depth.deepen();
+        Ok(())
+    }
+
+    /// Give the caller back its frame and debugger depth, once every operand is ready
+    fn resume(
+        self,
+        frame: &mut Frame,
+        debug_depth: &mut DebugDepth,
+    ) -> (Arc<[Value]>, Vec<Value>, EnvRef) {
+        if self.depth.is_some() {
+            *frame = self.frame;
+            *debug_depth = self.debug_depth;
+        }
+        (self.items, self.values, self.env)
+    }
+}
+
+/// The calls waiting on a trampoline, innermost last
+#[derive(Default)]
+struct PendingCalls(Vec<PendingCall>);
+
+impl Drop for PendingCalls {
+    fn drop(&mut self) {
+        // Innermost first, as nested evaluations returning with an error would
+        while self.0.pop().is_some() {}
+    }
+}
+
+/// Make a call whose head and arguments are evaluated
+///
+/// A lambda's body is left as the tail for the trampoline to loop on, unless
+/// the lambda is traced. `items` is the call expression, for names.
+fn apply_call(
+    items: &[Value],
+    mut values: Vec<Value>,
+    frame: &mut Frame,
+    debug_depth: &mut DebugDepth,
+    macro_reg: &MacroRegistry,
+) -> Result<Step, EvalError> {
+    let func = values.remove(0);
+    let args = values;
+    let trace = TraceCall::enter(&func, &args);
+
+    match func {
+        Value::Lambda {
+            params,
+            defaults,
+            body,
+            env: lambda_env,
+            ..
+        } => {
+            // Get lambda name if available (from define)
+            let name = match &items[0] {
+                Value::Symbol(s) => s.as_str(),
+                _ => "<lambda>",
+            };
+
+            // Create new environment for lambda, checking arity
+            let new_env = bind_params(
+                name,
+                &params,
+                &defaults,
+                args,
+                &lambda_env,
+                |default, env| eval_with_macros(default, env, macro_reg),
+            )?;
+
+            frame.enter_lambda(&body, &items[0]);
+            debug_depth.deepen();
+            if let Some(trace) = trace {
+                // Traced calls return here so their result can be printed
+                let result = eval_with_macros(Arc::unwrap_or_clone(body), new_env, macro_reg);
+                return Ok(Step::Done(trace.finish(result)?));
+            }
+
+            // Tail call: the trampoline continues with the body
+            Ok(Step::Tail(Arc::unwrap_or_clone(body), new_env))
+        }
+        Value::BuiltIn(f) => {
+            // All builtins now include function context in errors.
+            // Expose our macros to builtins that call back into eval.
+            let previous = ACTIVE_MACROS.with(|active| active.replace(Some(macro_reg.clone())));
+            let mut call = Frame::default();
+            call.enter_builtin(f);
+            let mut result = call_builtin(f, &args);
+            drop(call);
+            if let Some(trace) = trace {
+                result = trace.finish(result);
+            }
+            ACTIVE_MACROS.with(|active| *active.borrow_mut() = previous);
+            result.map(Step::Done)
+        }
+        other => Err(EvalError::not_callable(None, &other)),
+    }
 }
 
 /// Evaluation function with macro registry support
 pub fn eval_with_macros(
     mut expr: Value,
-    env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
 ) -> Result<Value, EvalError> {
+    // Non-tail recursion nests trampolines; stop before the Rust stack runs out
+    let _depth = EvalDepth::enter()?;
     let mut current_env = env;
-    loop {
+    // Times the function this trampoline is running, when profiling
+    let mut frame = Frame::default();
+    let mut debug_depth = DebugDepth::default();
+    debug_depth.deepen();
+    // Calls whose operands are being evaluated by this trampoline, innermost last
+    let mut pending = PendingCalls::default();
+    'eval: loop {
+        check_cancelled()?;
+
         // First expand macros
-        expr = expand_macros(expr.clone(), macro_reg, current_env.clone())?;
+        expr = expand_macros(expr, macro_reg, &current_env)?;
+        crate::coverage::record(&expr);
+        crate::debugger::before_eval(&expr, &current_env)?;
 
-        match &expr {
+        let value = match &expr {
             // Self-evaluating values
             Value::Number(_)
             | Value::Bool(_)
             | Value::String(_)
             | Value::Keyword(_)
-            | Value::Nil => {
-                return Ok(expr.clone());
-            }
+            | Value::Nil => expr.clone(),
 
             // Maps: evaluate all values
             Value::Map(map) => {
-                use std::collections::HashMap;
-                let mut evaluated_map = HashMap::new();
+                let mut evaluated_map = BTreeMap::new();
                 for (key, value) in map {
                     let evaluated_value =
                         eval_with_macros(value.clone(), current_env.clone(), macro_reg)?;
                     evaluated_map.insert(key.clone(), evaluated_value);
                 }
-                return Ok(Value::Map(evaluated_map));
+                Value::Map(evaluated_map)
             }
 
             // Symbol lookup
             Value::Symbol(name) => {
                 // Special case: 'nil' as a symbol evaluates to Nil value
                 if name == "nil" {
-                    return Ok(Value::Nil);
+                    Value::Nil
+                } else {
+                    current_env
+                        .get(name)
+                        .ok_or_else(|| EvalError::undefined_symbol(name, &current_env))?
                 }
-                return current_env
-                    .get(name)
-                    .ok_or_else(|| EvalError::UndefinedSymbol(name.clone()));
             }
 
             // Empty list evaluates to nil
-            Value::List(items) if items.is_empty() => return Ok(Value::Nil),
+            Value::List(items) if items.is_empty() => Value::Nil,
 
             // Non-empty list: special forms or function application
             Value::List(items) => {
-                match &items[0] {
-                    Value::Symbol(s) if s == "define" => {
-                        return eval_define(&items[1..], current_env, macro_reg);
+                match SpecialForm::of(&items[0]) {
+                    Some(SpecialForm::Define) => {
+                        eval_define(&items[1..], current_env.clone(), macro_reg)?
                     }
-                    Value::Symbol(s) if s == "lambda" => {
-                        return eval_lambda(&items[1..], current_env);
+                    Some(SpecialForm::Lambda) => {
+                        eval_lambda(&items[1..], current_env.clone(), macro_reg)?
                     }
-                    Value::Symbol(s) if s == "quote" => {
+                    Some(SpecialForm::Quote) => {
                         if items.len() != 2 {
                             return Err(EvalError::arity_error(
                                 "quote",
@@ -77,9 +575,9 @@ pub fn eval_with_macros(
                                 items.len() - 1,
                             ));
                         }
-                        return Ok(items[1].clone());
+                        items[1].clone()
                     }
-                    Value::Symbol(s) if s == "quasiquote" => {
+                    Some(SpecialForm::Quasiquote) => {
                         if items.len() != 2 {
                             return Err(EvalError::arity_error(
                                 "quasiquote",
@@ -87,108 +585,115 @@ pub fn eval_with_macros(
                                 items.len() - 1,
                             ));
                         }
-                        return eval_quasiquote(items[1].clone(), 1, current_env, macro_reg);
+                        eval_quasiquote(items[1].clone(), 1, current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Defmacro) => {
+                        eval_defmacro(&items[1..], current_env.clone(), macro_reg)?
                     }
-                    Value::Symbol(s) if s == "defmacro" => {
-                        return eval_defmacro(&items[1..], current_env, macro_reg);
+                    Some(
+                        form @ (SpecialForm::If
+                        | SpecialForm::Case
+                        | SpecialForm::Begin
+                        | SpecialForm::Let
+                        | SpecialForm::ThreadFirst
+                        | SpecialForm::ThreadLast),
+                    ) => {
+                        // Evaluate up to the tail expression, then loop on it
+                        match tail_step(form, &items[1..], &current_env, macro_reg)? {
+                            Step::Done(value) => value,
+                            Step::Tail(tail, env) => {
+                                expr = tail;
+                                current_env = env;
+                                continue;
+                            }
+                        }
                     }
-                    Value::Symbol(s) if s == "if" => {
-                        // Tail-optimized if: evaluate condition, then loop on branch
-                        if items.len() < 3 || items.len() > 4 {
+                    Some(SpecialForm::Delay) => {
+                        if items.len() != 2 {
                             return Err(EvalError::arity_error(
-                                "if",
-                                ARITY_TWO_OR_THREE,
+                                "delay",
+                                ARITY_ONE,
                                 items.len() - 1,
                             ));
                         }
-
-                        let condition =
-                            eval_with_macros(items[1].clone(), current_env.clone(), macro_reg)?;
-                        let is_true = match condition {
-                            Value::Bool(b) => b,
-                            Value::Nil => false,
-                            _ => true, // Everything except #f and nil is truthy
-                        };
-
-                        if is_true {
-                            expr = items[2].clone();
-                            // Continue loop for tail call
-                        } else if items.len() > 3 {
-                            expr = items[3].clone();
-                            // Continue loop for tail call
-                        } else {
-                            return Ok(Value::Nil);
-                        }
+                        Value::Promise(Arc::new(Mutex::new(Promise::Delayed {
+                            expr: items[1].clone(),
+                            env: current_env.clone(),
+                        })))
                     }
-                    Value::Symbol(s) if s == "begin" => {
-                        // Tail-optimized begin: evaluate all but last, then loop on last
-                        if items.len() == 1 {
-                            return Ok(Value::Nil);
-                        }
-
-                        // Evaluate all items except the last
-                        for item in items.iter().skip(1).take(items.len() - 2) {
-                            eval_with_macros(item.clone(), current_env.clone(), macro_reg)?;
-                        }
-
-                        expr = items[items.len() - 1].clone();
-                        // Continue loop for tail call
+                    Some(SpecialForm::Set) => {
+                        eval_set(&items[1..], current_env.clone(), macro_reg)?
                     }
-                    Value::Symbol(s) if s == "let" => {
-                        return eval_let(&items[1..], current_env, macro_reg);
+                    Some(SpecialForm::Defconstant) => {
+                        eval_defconstant(&items[1..], current_env.clone(), macro_reg)?
                     }
-                    _ => {
-                        // Function application - check if it's a lambda for TCO
-                        let func =
-                            eval_with_macros(items[0].clone(), current_env.clone(), macro_reg)?;
-
-                        // Evaluate arguments
-                        let args: Result<Vec<_>, _> = items[1..]
-                            .iter()
-                            .map(|arg| {
-                                eval_with_macros(arg.clone(), current_env.clone(), macro_reg)
-                            })
-                            .collect();
-                        let args = args?;
-
-                        match func {
-                            Value::Lambda {
-                                params,
-                                body,
-                                env: lambda_env,
-                                docstring: _,
-                            } => {
-                                // Check arity
-                                if params.len() != args.len() {
-                                    // Get lambda name if available (from define)
-                                    let name = match &items[0] {
-                                        Value::Symbol(s) => s.as_str(),
-                                        _ => "<lambda>",
-                                    };
-                                    return Err(EvalError::arity_error(
-                                        name,
-                                        params.len().to_string(),
-                                        args.len(),
-                                    ));
-                                }
-
-                                // Create new environment for lambda
-                                let new_env = Environment::with_parent(lambda_env);
-                                for (param, arg) in params.iter().zip(args.iter()) {
-                                    new_env.define(param.clone(), arg.clone());
-                                }
-
-                                // Tail call: set up for next iteration
-                                expr = *body;
-                                current_env = new_env;
-                                // Continue loop
+                    Some(SpecialForm::While) => {
+                        eval_while(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Dotimes) => {
+                        eval_dotimes(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Dolist) => {
+                        eval_dolist(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Break) => {
+                        let value = match items.len() {
+                            1 => Value::Nil,
+                            2 => {
+                                eval_with_macros(items[1].clone(), current_env.clone(), macro_reg)?
                             }
-                            Value::BuiltIn(f) => {
-                                // All builtins now include function context in errors
-                                return f(&args);
+                            n => {
+                                return Err(EvalError::arity_error(
+                                    "break",
+                                    ARITY_ZERO_OR_ONE,
+                                    n - 1,
+                                ))
                             }
-                            _ => {
-                                return Err(EvalError::NotCallable);
+                        };
+                        return Err(EvalError::Break(Box::new(value)));
+                    }
+                    Some(SpecialForm::Continue) => {
+                        if items.len() != 1 {
+                            return Err(EvalError::arity_error(
+                                "continue",
+                                ARITY_ZERO,
+                                items.len() - 1,
+                            ));
+                        }
+                        return Err(EvalError::Continue);
+                    }
+                    Some(SpecialForm::Try) => {
+                        eval_try(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::SomeThread) => {
+                        eval_some_thread(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Profile) => {
+                        eval_profile(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Time) => {
+                        eval_time(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    Some(SpecialForm::Bench) => {
+                        eval_bench(&items[1..], current_env.clone(), macro_reg)?
+                    }
+                    None => {
+                        // Function application: operands that need evaluating are
+                        // evaluated by this loop while the call waits on `pending`
+                        let mut call = PendingCall::new(items.clone(), current_env.clone());
+                        if let Some(operand) = call.next_operand()? {
+                            call.suspend(&mut frame, &mut debug_depth)?;
+                            pending.0.push(call);
+                            expr = operand;
+                            continue;
+                        }
+                        let (items, values, _) = call.resume(&mut frame, &mut debug_depth);
+                        match apply_call(&items, values, &mut frame, &mut debug_depth, macro_reg)? {
+                            Step::Done(value) => value,
+                            Step::Tail(body, env) => {
+                                expr = body;
+                                current_env = env;
+                                continue;
                             }
                         }
                     }
@@ -196,22 +701,76 @@ pub fn eval_with_macros(
             }
 
             // Lambda, Macro, BuiltIn, and Error are also self-evaluating (though rarely evaluated directly)
-            Value::Lambda { .. } | Value::Macro { .. } | Value::BuiltIn(_) | Value::Error(_) => {
-                return Ok(expr.clone());
+            Value::Lambda { .. }
+            | Value::Macro { .. }
+            | Value::BuiltIn(_)
+            | Value::Promise(_)
+            | Value::Thread(_)
+            | Value::Channel(_)
+            | Value::RateLimiter(_)
+            | Value::WebSocket(_)
+            | Value::VectorStore(_)
+            | Value::Matrix(_)
+            | Value::Decimal(_)
+            | Value::Values(_)
+            | Value::Error(_) => expr.clone(),
+        };
+
+        // Hand the value to the innermost waiting call, which evaluates its
+        // next operand or, once all are ready, is made
+        let mut value = value;
+        while let Some(mut call) = pending.0.pop() {
+            call.values.push(value);
+            if let Some(operand) = call.next_operand()? {
+                call.suspend(&mut frame, &mut debug_depth)?;
+                current_env = call.env.clone();
+                pending.0.push(call);
+                expr = operand;
+                continue 'eval;
+            }
+            let (items, values, _) = call.resume(&mut frame, &mut debug_depth);
+            match apply_call(&items, values, &mut frame, &mut debug_depth, macro_reg)? {
+                Step::Done(result) => value = result,
+                Step::Tail(body, env) => {
+                    expr = body;
+                    current_env = env;
+                    continue 'eval;
+                }
             }
         }
+        return Ok(value);
+    }
+}
+
+/// Fail if `form` would replace a frozen binding in `env`'s own frame
+///
+/// Replacing any other top-level binding prints a warning when the host
+/// turned redefinition warnings on.
+fn check_redefinition(form: &str, env: &Environment, name: &str) -> Result<(), EvalError> {
+    if let Some(message) = env.frozen_message("redefine", name) {
+        return Err(EvalError::runtime_error(form, message));
+    }
+    if env.is_global() {
+        crate::builtins::bindings::warn_if_redefined(env, name);
+    }
+    Ok(())
+}
+
+/// Tell the host about a binding just made, if it is a top-level one
+fn report_definition(env: &Environment, name: &str) {
+    if env.is_global() {
+        crate::events::definition(name);
     }
 }
 
 /// Evaluate a define special form
 /// Handles:
 /// - (define x 42) - variable definition
-/// - (define (f x) body) - function definition (syntactic sugar for lambda)
-fn eval_define(
-    args: &[Value],
-    env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
-) -> Result<Value, EvalError> {
+/// - (define (f x) body...) - function definition (syntactic sugar for lambda)
+///
+/// Either binds in the frame it is evaluated in: a define inside a function
+/// body or `let` is local to that call, and only top-level ones are global.
+fn eval_define(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
     if args.len() < 2 {
         return Err(EvalError::arity_error("define", "at least 2", args.len()));
     }
@@ -219,8 +778,20 @@ fn eval_define(
     match &args[0] {
         // Variable definition: (define x 42)
         Value::Symbol(name) => {
-            let value = eval_with_macros(args[1].clone(), env.clone(), macro_reg)?;
+            check_redefinition("define", &env, name)?;
+            let mut value = eval_with_macros(args[1].clone(), env.clone(), macro_reg)?;
+            if let Value::Lambda { body, source, .. } = &mut value {
+                if env.is_global() {
+                    crate::coverage::register_function(name, body);
+                    profiler::register_function(name, body);
+                }
+                // (define f (lambda ...)) keeps its source; an alias keeps the original's
+                if source.is_none() && is_lambda_form(&args[1]) {
+                    *source = Some(Arc::new(definition_source(args)));
+                }
+            }
             env.define(name.clone(), value);
+            report_definition(&env, name);
             Ok(Value::Symbol(name.clone()))
         }
 
@@ -236,27 +807,21 @@ fn eval_define(
                     ));
                 }
             };
+            check_redefinition("define", &env, &name)?;
 
-            // Extract parameters
-            let mut params = Vec::new();
-            for param in &func_def[1..] {
-                match param {
-                    Value::Symbol(p) => params.push(p.clone()),
-                    _ => {
-                        return Err(EvalError::runtime_error(
-                            "define",
-                            "function parameters must be symbols",
-                        ));
-                    }
-                }
-            }
-
-            // Extract docstring if present: (define (f x) "doc" body)
+            // Extract docstring if present: (define (f x) "doc" body...)
             let (inline_docstring, body) = match &args[1] {
-                Value::String(s) if args.len() > 2 => (Some(s.clone()), Box::new(args[2].clone())),
-                _ => (None, Box::new(args[1].clone())),
+                Value::String(s) if args.len() > 2 => (Some(s.to_string()), body_form(&args[2..])),
+                _ => (None, body_form(&args[1..])),
             };
 
+            // Extract parameters, destructuring list and map patterns in the body
+            let expanded = destructure::expand_params("define", &func_def[1..], body)?;
+            let (params, defaults, body) = (expanded.names, expanded.defaults, expanded.body);
+
+            // Expand macros and optimize once now instead of on every call
+            let body = Arc::new(optimize(expand_all(body, macro_reg, &env)?, &params, &env));
+
             // Check for pending doc comments from ;;; and merge with inline docstring
             let pending_docs = parser::take_pending_docs();
             let docstring = if !pending_docs.is_empty() {
@@ -266,31 +831,38 @@ fn eval_define(
                 inline_docstring
             };
 
-            // Register help entry if we have documentation (unless we're loading stdlib)
+            // Register help entry if we have documentation
             if let Some(ref doc) = docstring {
-                if !parser::should_skip_help_registration() {
-                    let signature = format!("({} {})", name, params.join(" "));
-                    crate::help::register_help(crate::help::HelpEntry {
-                        name: name.clone(),
-                        signature,
-                        description: doc.clone(),
-                        examples: vec![], // Could parse from doc later
-                        related: vec![],
-                        category: "User Defined".to_string(),
-                    });
-                }
+                let shown: Vec<String> = func_def[1..].iter().map(|p| p.to_string()).collect();
+                let signature = format!("({} {})", name, shown.join(" "));
+                crate::help::register_help(crate::help::HelpEntry {
+                    name: name.to_string(),
+                    signature,
+                    description: doc.clone(),
+                    examples: vec![], // Could parse from doc later
+                    related: vec![],
+                    category: parser::definition_category(),
+                });
+            }
+
+            if env.is_global() {
+                crate::coverage::register_function(&name, &body);
+                profiler::register_function(&name, &body);
             }
 
             // Create lambda
             let lambda = Value::Lambda {
                 params,
+                defaults,
                 body,
                 env: env.clone(),
                 docstring,
+                source: Some(Arc::new(definition_source(args))),
             };
 
             // Define it
             env.define(name.clone(), lambda);
+            report_definition(&env, &name);
             Ok(Value::Symbol(name))
         }
 
@@ -301,33 +873,63 @@ fn eval_define(
     }
 }
 
+/// The `(define ...)` form with arguments `args`, as written
+fn define_form(args: &[Value]) -> Value {
+    let mut items = vec![Value::Symbol("define".into())];
+    items.extend_from_slice(args);
+    Value::List(items.into())
+}
+
+/// What `source` shows for a definition: its text when it was read at top
+/// level, otherwise the form itself
+fn definition_source(args: &[Value]) -> Value {
+    let form = define_form(args);
+    match parser::source_text(&form) {
+        Some(text) => Value::String(text.into()),
+        None => form,
+    }
+}
+
+/// Whether `expr` is a `(lambda ...)` expression
+fn is_lambda_form(expr: &Value) -> bool {
+    matches!(expr, Value::List(items) if matches!(items.first(), Some(Value::Symbol(s)) if s == "lambda"))
+}
+
+/// A function or macro body: the single form, or several wrapped in `begin`
+///
+/// Evaluating several forms in the call's own frame is what lets a body start
+/// with inner `define`s that stay local to the call.
+fn body_form(forms: &[Value]) -> Value {
+    if forms.len() == 1 {
+        return forms[0].clone();
+    }
+    let mut items = vec![Value::Symbol("begin".into())];
+    items.extend_from_slice(forms);
+    Value::List(items.into())
+}
+
 /// Evaluate a lambda expression
-/// (lambda (x y z) body) or (lambda (x y z) "docstring" body)
-fn eval_lambda(args: &[Value], env: Rc<Environment>) -> Result<Value, EvalError> {
+/// (lambda (x y z) body...) or (lambda (x y z) "docstring" body...)
+fn eval_lambda(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
     if args.len() < 2 {
         return Err(EvalError::arity_error("lambda", "at least 2", args.len()));
     }
 
-    // Extract parameters from args[0]
-    let params = match &args[0] {
+    // Extract docstring if present: (lambda (x y) "doc" body...)
+    let (docstring, body) = match &args[1] {
+        Value::String(s) if args.len() > 2 => (Some(s.to_string()), body_form(&args[2..])),
+        _ => (None, body_form(&args[1..])),
+    };
+
+    // Extract parameters from args[0], destructuring list and map patterns in the body
+    let (params, defaults, body) = match &args[0] {
         Value::List(param_list) => {
-            let mut params = Vec::new();
-            for param in param_list {
-                match param {
-                    Value::Symbol(name) => params.push(name.clone()),
-                    _ => {
-                        return Err(EvalError::runtime_error(
-                            "lambda",
-                            "parameters must be symbols",
-                        ));
-                    }
-                }
-            }
-            params
+            let expanded = destructure::expand_params("lambda", param_list, body)?;
+            (expanded.names, expanded.defaults, expanded.body)
         }
         Value::Nil => {
             // Empty parameter list () is parsed as Nil
-            Vec::new()
+            (Vec::new(), Vec::new(), body)
         }
         _ => {
             return Err(EvalError::runtime_error(
@@ -337,27 +939,22 @@ fn eval_lambda(args: &[Value], env: Rc<Environment>) -> Result<Value, EvalError>
         }
     };
 
-    // Extract docstring if present: (lambda (x y) "doc" body)
-    let (docstring, body) = match &args[1] {
-        Value::String(s) if args.len() > 2 => (Some(s.clone()), Box::new(args[2].clone())),
-        _ => (None, Box::new(args[1].clone())),
-    };
+    // Expand macros and optimize once now instead of on every call
+    let body = Arc::new(optimize(expand_all(body, macro_reg, &env)?, &params, &env));
 
     Ok(Value::Lambda {
         params,
+        defaults,
         body,
         env,
         docstring,
+        source: None,
     })
 }
 
-/// Evaluate a let special form
-/// (let ((x 1) (y 2)) body)
-fn eval_let(
-    args: &[Value],
-    env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
-) -> Result<Value, EvalError> {
+/// Bind a let special form's names, leaving its last body expression as the tail
+/// (let ((x 1) (y 2)) body) or (let (((a b) pair) ({:keys (c)} m)) body)
+fn let_step(args: &[Value], env: &EnvRef, macro_reg: &MacroRegistry) -> Result<Step, EvalError> {
     if args.is_empty() {
         return Err(EvalError::arity_error("let", "at least 1", 0));
     }
@@ -368,23 +965,20 @@ fn eval_let(
     };
 
     // Create new environment as child of current env
-    let new_env = Environment::with_parent(env);
+    let new_env = Environment::with_parent(env.clone());
 
     // Evaluate bindings and add to new environment
-    for binding in bindings {
+    for binding in bindings.iter() {
         match binding {
             Value::List(pair) if pair.len() == 2 => {
-                let name = match &pair[0] {
-                    Value::Symbol(s) => s.clone(),
-                    _ => {
-                        return Err(EvalError::runtime_error(
-                            "let",
-                            "binding name must be symbol",
-                        ))
-                    }
-                };
-                let value = eval_with_macros(pair[1].clone(), new_env.clone(), macro_reg)?;
-                new_env.define(name, value);
+                // A list or map pattern expands to a binding per name it contains
+                let hidden = Symbol::new("#:let");
+                for (name, init) in
+                    destructure::expand_binding("let", &pair[0], pair[1].clone(), hidden)?
+                {
+                    let value = eval_with_macros(init, new_env.clone(), macro_reg)?;
+                    new_env.define(name, value);
+                }
             }
             _ => {
                 return Err(EvalError::runtime_error(
@@ -396,11 +990,442 @@ fn eval_let(
     }
 
     // Evaluate body in new environment
-    let mut result = Value::Nil;
-    for expr in &args[1..] {
-        result = eval_with_macros(expr.clone(), new_env.clone(), macro_reg)?;
+    body_step(&args[1..], new_env, macro_reg)
+}
+
+/// Lookup key of a `case` datum or key value, for the kinds of value `case` can match
+///
+/// The printed form tells numbers, strings, symbols and keywords apart, and
+/// `nil` written as a datum matches the nil value.
+fn case_key(value: &Value) -> Option<String> {
+    match value {
+        Value::Number(_)
+        | Value::String(_)
+        | Value::Symbol(_)
+        | Value::Keyword(_)
+        | Value::Bool(_)
+        | Value::Nil => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+fn is_else_clause(clause: &Value) -> bool {
+    matches!(clause, Value::List(items) if matches!(&items[0], Value::Symbol(s) if s == "else"))
+}
+
+/// Table from each datum's key to the index of the first clause listing it
+///
+/// Checks the shape of every clause: `(datum body...)` or `((datum...) body...)`,
+/// with an optional `(else body...)` last.
+pub(crate) fn case_table(clauses: &[Value]) -> Result<BTreeMap<String, Value>, EvalError> {
+    let mut table = BTreeMap::new();
+    for (i, clause) in clauses.iter().enumerate() {
+        let items = match clause {
+            Value::List(items) if items.len() >= 2 => items,
+            other => {
+                return Err(EvalError::runtime_error(
+                    "case",
+                    format!("clause must be (datums body...), got {}", other),
+                ))
+            }
+        };
+        if is_else_clause(clause) {
+            if i + 1 != clauses.len() {
+                return Err(EvalError::runtime_error(
+                    "case",
+                    "else must be the last clause",
+                ));
+            }
+            continue;
+        }
+        let datums = match &items[0] {
+            Value::List(datums) => &datums[..],
+            datum => std::slice::from_ref(datum),
+        };
+        for datum in datums {
+            let key = case_key(datum).ok_or_else(|| {
+                EvalError::runtime_error(
+                    "case",
+                    format!(
+                        "datum must be a number, string, symbol, keyword or boolean, got {}",
+                        datum
+                    ),
+                )
+            })?;
+            table.entry(key).or_insert(Value::Number(i as f64));
+        }
     }
-    Ok(result)
+    Ok(table)
+}
+
+/// Evaluate the key of a `case` form and the selected clause up to its last expression
+///
+/// Returns the last expression for the trampoline to evaluate as a tail call,
+/// or `None` when no clause matches. Function bodies carry a dispatch table
+/// built by the optimizer right after the key, so the lookup does not scan
+/// the clauses.
+fn case_branch(
+    args: &[Value],
+    env: &EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Option<Value>, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::arity_error("case", ARITY_AT_LEAST_ONE, 0));
+    }
+    let key = eval_with_macros(args[0].clone(), env.clone(), macro_reg)?;
+
+    let built;
+    let (table, clauses) = match args.get(1) {
+        Some(Value::Map(table)) => (table, &args[2..]),
+        _ => {
+            built = case_table(&args[1..])?;
+            (&built, &args[1..])
+        }
+    };
+    let selected = case_key(&key)
+        .and_then(|key| table.get(&key))
+        .and_then(|index| match index {
+            Value::Number(i) => clauses.get(*i as usize),
+            _ => None,
+        })
+        .or_else(|| clauses.last().filter(|clause| is_else_clause(clause)));
+    let Some(Value::List(clause)) = selected else {
+        return Ok(None);
+    };
+
+    let body = &clause[1..];
+    for expr in &body[..body.len() - 1] {
+        eval_with_macros(expr.clone(), env.clone(), macro_reg)?;
+    }
+    Ok(body.last().cloned())
+}
+
+/// Evaluate a set! special form: (set! name value)
+///
+/// Updates the nearest existing binding of `name`; it is an error if there is none.
+fn eval_set(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("set!", ARITY_TWO, args.len()));
+    }
+
+    let name = match &args[0] {
+        Value::Symbol(name) => name,
+        other => return Err(EvalError::type_error("set!", "symbol", other, 1)),
+    };
+
+    let value = eval_with_macros(args[1].clone(), env.clone(), macro_reg)?;
+    env.set(name, value.clone())?;
+    Ok(value)
+}
+
+/// Evaluate a defconstant special form: (defconstant name value)
+///
+/// Defines `name` like `define`, then freezes the binding so that `set!` and
+/// later `define`s of the name fail.
+fn eval_defconstant(
+    args: &[Value],
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("defconstant", ARITY_TWO, args.len()));
+    }
+
+    let name = match &args[0] {
+        Value::Symbol(name) => name,
+        other => return Err(EvalError::type_error("defconstant", "symbol", other, 1)),
+    };
+
+    check_redefinition("defconstant", &env, name)?;
+    let value = eval_with_macros(args[1].clone(), env.clone(), macro_reg)?;
+    env.define(name.clone(), value);
+    env.freeze(name);
+    report_definition(&env, name);
+    Ok(Value::Symbol(name.clone()))
+}
+
+/// What a loop does after running its body once
+enum LoopControl {
+    Next,
+    Exit(Value),
+}
+
+/// Run a loop body, turning `break` and `continue` into loop control
+fn run_loop_body(
+    body: &[Value],
+    env: &EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<LoopControl, EvalError> {
+    for expr in body {
+        match eval_with_macros(expr.clone(), env.clone(), macro_reg) {
+            Ok(_) => {}
+            Err(EvalError::Continue) => return Ok(LoopControl::Next),
+            Err(EvalError::Break(value)) => return Ok(LoopControl::Exit(*value)),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(LoopControl::Next)
+}
+
+/// Evaluate a while special form: (while condition body...)
+///
+/// Returns nil, or the value given to `break`.
+fn eval_while(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::arity_error("while", ARITY_AT_LEAST_ONE, 0));
+    }
+
+    loop {
+        let condition = eval_with_macros(args[0].clone(), env.clone(), macro_reg)?;
+        if matches!(condition, Value::Bool(false) | Value::Nil) {
+            return Ok(Value::Nil);
+        }
+        if let LoopControl::Exit(value) = run_loop_body(&args[1..], &env, macro_reg)? {
+            return Ok(value);
+        }
+    }
+}
+
+/// Evaluate a try special form: (try body...)
+///
+/// Returns the value of the last body expression, or the raised error as an
+/// error value. Interrupts, step limits and loop control are not caught.
+fn eval_try(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    let mut result = Ok(Value::Nil);
+    for expr in args {
+        result = eval_with_macros(expr.clone(), env.clone(), macro_reg);
+        if result.is_err() {
+            break;
+        }
+    }
+
+    match result {
+        Err(
+            e @ (EvalError::Interrupted
+            | EvalError::StepLimitExceeded(_)
+            | EvalError::Break(_)
+            | EvalError::Continue),
+        ) => Err(e),
+        Err(e) => Ok(Value::Error(Box::new(ErrorValue::from(&e)))),
+        ok => ok,
+    }
+}
+
+/// Evaluate a profile special form: (profile expr)
+///
+/// Profiles `expr` with a fresh profiler, prints the table and returns the
+/// value. An enclosing profiler (from `--profile-calls` or an outer `profile`)
+/// also gets the counts.
+fn eval_profile(
+    args: &[Value],
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("profile", ARITY_ONE, args.len()));
+    }
+
+    let global = crate::help::current_env().unwrap_or_else(|| env.clone());
+    let outer = profiler::swap_profiler(Some(Profiler::for_env(&global)));
+    let result = eval_with_macros(args[0].clone(), env, macro_reg);
+    let profile = profiler::swap_profiler(outer).unwrap_or_default();
+
+    crate::builtins::console::write_output(&profile.report());
+    profiler::merge_into_active(&profile);
+    result
+}
+
+/// Evaluate a time special form: (time expr)
+///
+/// Prints the wall time expr took and returns its value, or its error. Under a
+/// frozen clock (`--deterministic`) the time printed is 0.
+fn eval_time(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("time", ARITY_ONE, args.len()));
+    }
+
+    let stopwatch = crate::platform::Stopwatch::start();
+    let result = eval_with_macros(args[0].clone(), env, macro_reg);
+    crate::builtins::console::write_output(&format!(
+        "Elapsed time: {:.3} ms\n",
+        elapsed_ms(&stopwatch)
+    ));
+    result
+}
+
+/// Evaluate a bench special form: (bench expr iterations)
+///
+/// Evaluates expr `iterations` times and returns the wall time per run in
+/// milliseconds as `{:iterations :min :mean :max :total}`, all 0 under a frozen
+/// clock. An error in any run stops the benchmark and is returned.
+fn eval_bench(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("bench", ARITY_TWO, args.len()));
+    }
+
+    let iterations = match eval_with_macros(args[1].clone(), env.clone(), macro_reg)? {
+        Value::Number(n) if n >= 1.0 && n.fract() == 0.0 => n as usize,
+        other => {
+            return Err(EvalError::type_error(
+                "bench",
+                "positive integer",
+                &other,
+                2,
+            ))
+        }
+    };
+
+    let mut min = f64::INFINITY;
+    let mut max: f64 = 0.0;
+    let mut total = 0.0;
+    for _ in 0..iterations {
+        let stopwatch = crate::platform::Stopwatch::start();
+        eval_with_macros(args[0].clone(), env.clone(), macro_reg)?;
+        let elapsed = elapsed_ms(&stopwatch);
+        min = min.min(elapsed);
+        max = max.max(elapsed);
+        total += elapsed;
+    }
+
+    let mut stats = BTreeMap::new();
+    stats.insert("iterations".to_string(), Value::Number(iterations as f64));
+    stats.insert("min".to_string(), Value::Number(min));
+    stats.insert("mean".to_string(), Value::Number(total / iterations as f64));
+    stats.insert("max".to_string(), Value::Number(max));
+    stats.insert("total".to_string(), Value::Number(total));
+    Ok(Value::Map(stats))
+}
+
+/// Milliseconds on `stopwatch`, or 0 when the sandbox froze the clock
+fn elapsed_ms(stopwatch: &crate::platform::Stopwatch) -> f64 {
+    if crate::builtins::clock_frozen() {
+        0.0
+    } else {
+        stopwatch.elapsed_ms()
+    }
+}
+
+/// One step of a threading form: `(f a)` becomes `(f x a)`, or `(f a x)` for `->>`
+///
+/// A bare step `f` becomes `(f x)`.
+fn thread_step(step: &Value, value: Value, last: bool) -> Value {
+    match step {
+        Value::List(items) if !items.is_empty() => {
+            let mut call = items.to_vec();
+            if last {
+                call.push(value);
+            } else {
+                call.insert(1, value);
+            }
+            Value::List(call.into())
+        }
+        other => Value::List(vec![other.clone(), value].into()),
+    }
+}
+
+/// Evaluate a some-> special form: (some-> expr steps...)
+///
+/// Threads like `->`, but stops and returns the current value as soon as it is
+/// nil, an error value or an `(:err reason)` result.
+fn eval_some_thread(
+    args: &[Value],
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Value, EvalError> {
+    let Some(first) = args.first() else {
+        return Err(EvalError::arity_error("some->", ARITY_AT_LEAST_ONE, 0));
+    };
+
+    let mut value = eval_with_macros(first.clone(), env.clone(), macro_reg)?;
+    for step in &args[1..] {
+        let stop = match &value {
+            Value::Nil | Value::Error(_) => true,
+            Value::List(items) => {
+                items.len() == 2 && matches!(&items[0], Value::Keyword(k) if k == "err")
+            }
+            _ => false,
+        };
+        if stop {
+            break;
+        }
+        // Quote the value so it is not evaluated a second time
+        let quoted = Value::List(vec![Value::Symbol("quote".into()), value].into());
+        value = eval_with_macros(thread_step(step, quoted, false), env.clone(), macro_reg)?;
+    }
+    Ok(value)
+}
+
+/// Parse the `(var expr)` header of dotimes/dolist and evaluate `expr`
+fn eval_loop_header(
+    form: &str,
+    args: &[Value],
+    env: &EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<(Symbol, Value), EvalError> {
+    match args.first() {
+        Some(Value::List(spec)) if spec.len() == 2 => match &spec[0] {
+            Value::Symbol(var) => {
+                let value = eval_with_macros(spec[1].clone(), env.clone(), macro_reg)?;
+                Ok((var.clone(), value))
+            }
+            _ => Err(EvalError::runtime_error(
+                form,
+                "loop variable must be a symbol",
+            )),
+        },
+        Some(_) => Err(EvalError::runtime_error(
+            form,
+            "expected (variable expression) as first argument",
+        )),
+        None => Err(EvalError::arity_error(form, ARITY_AT_LEAST_ONE, 0)),
+    }
+}
+
+/// Evaluate a dotimes special form: (dotimes (var count) body...)
+///
+/// Binds `var` to 0, 1, ... count-1 in a fresh scope per iteration, so closures
+/// created in the body keep their own value. Returns nil, or the value given to `break`.
+fn eval_dotimes(
+    args: &[Value],
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
+) -> Result<Value, EvalError> {
+    let (var, count) = eval_loop_header("dotimes", args, &env, macro_reg)?;
+    let count = match count {
+        Value::Number(n) => n,
+        other => return Err(EvalError::type_error("dotimes", "number", &other, 1)),
+    };
+
+    let mut i = 0.0;
+    while i < count {
+        let iter_env = Environment::with_bindings(env.clone(), [(var.clone(), Value::Number(i))]);
+        if let LoopControl::Exit(value) = run_loop_body(&args[1..], &iter_env, macro_reg)? {
+            return Ok(value);
+        }
+        i += 1.0;
+    }
+    Ok(Value::Nil)
+}
+
+/// Evaluate a dolist special form: (dolist (var list) body...)
+///
+/// Binds `var` to each element in a fresh scope per iteration. Returns nil, or
+/// the value given to `break`.
+fn eval_dolist(args: &[Value], env: EnvRef, macro_reg: &MacroRegistry) -> Result<Value, EvalError> {
+    let (var, list) = eval_loop_header("dolist", args, &env, macro_reg)?;
+    let items = match list {
+        Value::List(items) => items,
+        Value::Nil => Vec::new().into(),
+        other => return Err(EvalError::type_error("dolist", "list", &other, 1)),
+    };
+
+    for item in items.iter().cloned() {
+        let iter_env = Environment::with_bindings(env.clone(), [(var.clone(), item)]);
+        if let LoopControl::Exit(value) = run_loop_body(&args[1..], &iter_env, macro_reg)? {
+            return Ok(value);
+        }
+    }
+    Ok(Value::Nil)
 }
 
 /// Evaluate a quasiquote expression
@@ -408,8 +1433,8 @@ fn eval_let(
 fn eval_quasiquote(
     arg: Value,
     depth: usize,
-    env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
+    env: EnvRef,
+    macro_reg: &MacroRegistry,
 ) -> Result<Value, EvalError> {
     match arg {
         // Self-evaluating values
@@ -442,14 +1467,16 @@ fn eval_quasiquote(
                         ));
                     }
                     let inner = eval_quasiquote(items[1].clone(), depth + 1, env, macro_reg)?;
-                    Ok(Value::List(vec![Value::Symbol("quasiquote".into()), inner]))
+                    Ok(Value::List(
+                        vec![Value::Symbol("quasiquote".into()), inner].into(),
+                    ))
                 }
 
                 // Regular list - recurse on all items, handling unquote-splicing
                 _ => {
                     let mut new_items = Vec::new();
 
-                    for item in items {
+                    for item in items.iter() {
                         match item {
                             Value::List(parts) if !parts.is_empty() => match &parts[0] {
                                 Value::Symbol(s) if s == "unquote-splicing" && depth == 1 => {
@@ -466,7 +1493,7 @@ fn eval_quasiquote(
                                         macro_reg,
                                     )? {
                                         Value::List(splice) => {
-                                            new_items.extend(splice);
+                                            new_items.extend(splice.iter().cloned());
                                         }
                                         _ => {
                                             return Err(EvalError::runtime_error(
@@ -494,23 +1521,42 @@ fn eval_quasiquote(
                         }
                     }
 
-                    Ok(Value::List(new_items))
+                    Ok(Value::List(new_items.into()))
                 }
             }
         }
 
         Value::List(_) => Ok(Value::Nil),
 
+        // Map templates: each value is a template; keys are keywords and stay as written
+        Value::Map(map) => {
+            let mut new_map = BTreeMap::new();
+            for (key, value) in map {
+                if let Value::List(parts) = &value {
+                    if matches!(parts.first(), Some(Value::Symbol(s)) if s == "unquote-splicing")
+                        && depth == 1
+                    {
+                        return Err(EvalError::runtime_error(
+                            "unquote-splicing",
+                            format!("cannot splice into the value of map key :{}", key),
+                        ));
+                    }
+                }
+                new_map.insert(key, eval_quasiquote(value, depth, env.clone(), macro_reg)?);
+            }
+            Ok(Value::Map(new_map))
+        }
+
         _ => Ok(arg),
     }
 }
 
 /// Evaluate a defmacro special form
-/// (defmacro name (params) body)
+/// (defmacro name (params) body) or (defmacro name (params) "docstring" body)
 fn eval_defmacro(
     args: &[Value],
-    _env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
+    _env: EnvRef,
+    macro_reg: &MacroRegistry,
 ) -> Result<Value, EvalError> {
     if args.len() < 3 {
         return Err(EvalError::arity_error("defmacro", "at least 3", args.len()));
@@ -545,30 +1591,82 @@ fn eval_defmacro(
         }
     };
 
-    // Body is the remaining args, wrapped in begin if multiple
-    let body = if args.len() > 3 {
-        let mut body_items = vec![Value::Symbol("begin".into())];
-        body_items.extend_from_slice(&args[2..]);
-        Value::List(body_items)
+    // A string followed by more body forms is a docstring, not the expansion
+    let (inline_docstring, body_forms) = match &args[2] {
+        Value::String(s) if args.len() > 3 => (Some(s.to_string()), &args[3..]),
+        _ => (None, &args[2..]),
+    };
+
+    let body = body_form(body_forms);
+
+    // Doc comments (;;;) take precedence over the inline docstring, as for define
+    let pending_docs = parser::take_pending_docs();
+    let docstring = if !pending_docs.is_empty() {
+        Some(pending_docs.join("\n"))
     } else {
-        args[2].clone()
+        inline_docstring
     };
 
-    macro_reg.define(name.clone(), params, body);
+    if let Some(ref doc) = docstring {
+        crate::help::register_help(crate::help::HelpEntry {
+            name: name.to_string(),
+            signature: crate::help::macro_signature(&name, &params),
+            description: doc.clone(),
+            examples: vec![],
+            related: vec![],
+            category: "Macro".to_string(),
+        });
+    }
+
+    macro_reg.define_documented(name.clone(), params, body, docstring);
     Ok(Value::Symbol(name))
 }
 
+/// One macro expansion in progress, counted until it is dropped
+struct ExpansionDepth {
+    level: usize,
+}
+
+impl ExpansionDepth {
+    /// Count an expansion of `name`, failing once `max` are already in progress
+    fn enter(name: &str, max: usize) -> Result<Self, EvalError> {
+        let level = EXPANSION_DEPTH.with(|depth| depth.get());
+        if level >= max {
+            return Err(EvalError::runtime_error(
+                name,
+                format!(
+                    "macro expansion exceeded the depth limit of {} (does the macro expand into itself?)",
+                    max
+                ),
+            ));
+        }
+        EXPANSION_DEPTH.with(|depth| depth.set(level + 1));
+        Ok(ExpansionDepth { level })
+    }
+
+    /// Expansions that were already in progress when this one started
+    fn level(&self) -> usize {
+        self.level
+    }
+}
+
+impl Drop for ExpansionDepth {
+    fn drop(&mut self) {
+        EXPANSION_DEPTH.with(|depth| depth.set(self.level));
+    }
+}
+
 /// Expand macros in an expression
-fn expand_macros(
+pub(crate) fn expand_macros(
     expr: Value,
     macro_reg: &MacroRegistry,
-    env: Rc<Environment>,
+    env: &EnvRef,
 ) -> Result<Value, EvalError> {
     match expr {
         Value::List(ref items) if !items.is_empty() => {
             match &items[0] {
                 Value::Symbol(name) => {
-                    if let Some((params, body)) = macro_reg.get(name) {
+                    if let Some(MacroDef { params, body, .. }) = macro_reg.get(name) {
                         // Bind arguments to parameters
                         let args = &items[1..];
 
@@ -580,15 +1678,18 @@ fn expand_macros(
                             ));
                         }
 
-                        let macro_env = Environment::with_parent(env.clone());
-                        for (param, arg) in params.iter().zip(args.iter()) {
-                            // Arguments to macros are NOT evaluated yet
-                            macro_env.define(param.clone(), arg.clone());
-                        }
+                        let depth = ExpansionDepth::enter(name, macro_reg.max_depth())?;
+
+                        // Arguments to macros are NOT evaluated yet
+                        let macro_env = Environment::with_bindings(
+                            env.clone(),
+                            params.iter().cloned().zip(args.iter().cloned()),
+                        );
 
-                        // Evaluate body in macro environment (this handles quasiquote expansion)
-                        let mut temp_reg = MacroRegistry::new();
-                        let expanded = eval_with_macros(body, macro_env, &mut temp_reg)?;
+                        // Evaluate body in macro environment (this handles quasiquote expansion);
+                        // the body may itself use macros defined before this one ran
+                        let expanded = eval_with_macros(body, macro_env, macro_reg)?;
+                        trace_expansion(name, &expr, &expanded, depth.level());
 
                         // Recursively expand if result is a macro call
                         expand_macros(expanded, macro_reg, env)
@@ -603,121 +1704,324 @@ fn expand_macros(
     }
 }
 
-/// Register help documentation for special forms (Part 1)
-/// Documents: define, lambda, if, begin
-pub fn register_special_forms_part1() {
+/// Register help documentation for special forms (Part 1)
+/// Documents: define, lambda, if, begin
+pub fn register_special_forms_part1() {
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "define".to_string(),
+        signature: "(define name value) or (define (name params...) body)".to_string(),
+        description: "Define a variable or function in the current scope.\n\nThe first form binds a value to a name. The second form is syntactic sugar for defining a function, equivalent to `(define name (lambda (params...) body))`.\n\nA define inside a function body or `let` binds in that call's own frame and is not visible outside it; only top-level defines are global.\n\nReturns the name of the defined symbol.".to_string(),
+        examples: vec![
+            "(define x 42) => x".to_string(),
+            "(define (square x) (* x x)) => square".to_string(),
+            "(define (add a b) (+ a b)) => add".to_string(),
+            "(add 3 4) => 7".to_string(),
+            "(define (sum-sq a b) (define (sq x) (* x x)) (+ (sq a) (sq b))) => sum-sq".to_string(),
+        ],
+        related: vec!["lambda".to_string(), "let".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "lambda".to_string(),
+        signature: "(lambda (params...) [docstring] body)".to_string(),
+        description: "Create an anonymous function.\n\nThe parameters are a list of symbols, or of list and map patterns that destructure the argument as in `let`. A parameter written `(name default)` is optional: when the call leaves it out, `default` is evaluated (it may use earlier parameters). Optional parameters come last, and a call may set them by name with `:name value` pairs after the required arguments. The body is evaluated when the function is called with the parameters bound to the argument values; it may be several forms, evaluated in order, with the last one's value returned. Optionally, a docstring can be provided as the first element of the body.\n\nThe created function captures the lexical environment at definition time, enabling closures.".to_string(),
+        examples: vec![
+            "((lambda (x) (+ x 1)) 5) => 6".to_string(),
+            "(define add (lambda (a b) (+ a b))) => add".to_string(),
+            "(define make-adder (lambda (n) (lambda (x) (+ x n)))) => make-adder".to_string(),
+            "((make-adder 10) 5) => 15".to_string(),
+            "(map (lambda ((k v)) (* k v)) '((1 2) (3 4))) => (2 12)".to_string(),
+            "((lambda (x (y 10)) (+ x y)) 1) => 11".to_string(),
+            "((lambda (x (y 10) (z 0)) (list x y z)) 1 :z 5) => (1 10 5)".to_string(),
+        ],
+        related: vec!["define".to_string(), "let".to_string(), "doc".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "if".to_string(),
+        signature: "(if condition then-expr [else-expr])".to_string(),
+        description: "Conditional evaluation.\n\nIf condition evaluates to a truthy value (anything except false), then-expr is evaluated and returned. Otherwise, else-expr is evaluated (if provided) and returned. If no else-expr is provided and condition is false, returns nil.\n\nOnly the taken branch is evaluated (short-circuit evaluation).".to_string(),
+        examples: vec![
+            "(if (> 5 3) \"yes\" \"no\") => \"yes\"".to_string(),
+            "(if false 42) => nil".to_string(),
+            "(if true (+ 1 2) (/ 1 0)) => 3".to_string(),
+            "(define (abs x) (if (< x 0) (- x) x)) => abs".to_string(),
+        ],
+        related: vec!["begin".to_string(), "and".to_string(), "or".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "begin".to_string(),
+        signature: "(begin expr1 expr2 ... exprN)".to_string(),
+        description: "Sequence multiple expressions.\n\nEvaluates each expression in order and returns the value of the last expression. All expressions are evaluated for their side effects, but only the final value is returned.\n\nUseful for grouping expressions in contexts that expect a single expression, like in `if` branches or `lambda` bodies.".to_string(),
+        examples: vec![
+            "(begin (print \"step 1\") (print \"step 2\") 42) => 42".to_string(),
+            "(define (side-effects) (begin (print \"first\") (print \"second\") \"result\")) => side-effects".to_string(),
+            "(if condition (begin (print \"doing something\") (+ 1 2)) 0)".to_string(),
+        ],
+        related: vec!["if".to_string(), "define".to_string()],
+        category: "Special forms".to_string(),
+    });
+}
+
+/// Register help documentation for special forms (Part 2)
+/// Documents: let, quote, quasiquote, defmacro, delay
+pub fn register_special_forms_part2() {
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "let".to_string(),
+        signature: "(let ((var1 expr1) (var2 expr2) ...) body)".to_string(),
+        description: "Create local variable bindings.\n\nDefines temporary variables that are visible only within the body. Each variable is bound to the value of its corresponding expression. All binding expressions are evaluated in the outer scope before the body is evaluated.\n\nEquivalent to `((lambda (var1 var2 ...) body) expr1 expr2 ...)`.\n\nUseful for avoiding repeated calculations and improving code clarity.\n\nA binding name may be a pattern that takes the value apart: a list pattern `(a b &rest more)` binds elements in order (the list must have at least that many), and a map pattern `{:keys (a b)}` binds the values of `:a` and `:b`, while `{:user (name age)}` destructures the value under `:user`. Patterns nest, and expand to `car`, `cdr` and `map-get` calls.".to_string(),
+        examples: vec![
+            "(let ((x 10) (y 20)) (+ x y)) => 30".to_string(),
+            "(let ((a (+ 1 2)) (b (* 3 4))) (+ a b)) => 15".to_string(),
+            "(let ((x 5)) (let ((y 10)) (+ x y))) => 15".to_string(),
+            "(let (((x y) '(1 2)) ({:keys (a)} {:a 3})) (+ x y a)) => 6".to_string(),
+            "(define (quadratic a b c x) (let ((delta (- (* b b) (* 4 a c)))) (/ delta 2))) => quadratic".to_string(),
+        ],
+        related: vec!["lambda".to_string(), "define".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "quote".to_string(),
+        signature: "(quote expr) or 'expr".to_string(),
+        description: "Return an expression unevaluated.\n\nPrevents evaluation of the expression. Returns the expression itself as data.\n\nOften used with symbols and lists to create data structures that would otherwise be evaluated as code. The shorthand syntax 'expr is equivalent to (quote expr).".to_string(),
+        examples: vec![
+            "'x => x (the symbol, not its value)".to_string(),
+            "'(1 2 3) => (1 2 3) (the list, not evaluated as function call)".to_string(),
+            "(quote (+ 1 2)) => (+ 1 2)".to_string(),
+            "'() => () (empty list)".to_string(),
+        ],
+        related: vec!["quasiquote".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "quasiquote".to_string(),
+        signature: "(quasiquote template) or `template".to_string(),
+        description: "Return a template with selective evaluation.\n\nLike quote, but allows selective evaluation of parts using unquote (,) and unquote-splicing (,@).\n\nUnquoted parts are evaluated; unquoted-spliced lists are spliced into the result. Map literals in a template are templates too: their values may be unquoted (but not spliced), while keys stay as written. This is the foundation of the macro system.".to_string(),
+        examples: vec![
+            "`(+ 1 2) => (+ 1 2)".to_string(),
+            "`(+ 1 ,(+ 2 3)) => (+ 1 5)".to_string(),
+            "`(list ,@(list 1 2 3)) => (list 1 2 3)".to_string(),
+            "(define x 10) => x".to_string(),
+            "`(x is ,x) => (x is 10)".to_string(),
+            "`{:name \"x\" :value ,x} => {:name \"x\" :value 10}".to_string(),
+        ],
+        related: vec!["quote".to_string(), "defmacro".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "defmacro".to_string(),
+        signature: "(defmacro name (params...) [docstring] body)".to_string(),
+        description: "Define a compile-time transformation.\n\nMacros receive unevaluated arguments and return code to be evaluated. Unlike functions, macro arguments are not evaluated before the macro is called. The macro body should return a list representing the code to evaluate.\n\nA string before the body is the macro's docstring, shown by `(help 'name)`.\n\nMacros enable syntactic abstraction and domain-specific languages.".to_string(),
+        examples: vec![
+            "(defmacro when (condition body) \"Evaluate body if condition is true\" `(if ,condition ,body nil))".to_string(),
+            "(defmacro repeat (n body) `(dotimes (i ,n) ,body))".to_string(),
+            "(defmacro assert (condition) `(if (not ,condition) (error \"Assertion failed\")))".to_string(),
+        ],
+        related: vec!["quote".to_string(), "quasiquote".to_string(), "lambda".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "delay".to_string(),
+        signature: "(delay expr)".to_string(),
+        description: "Create a promise for a deferred computation.\n\nThe expression is not evaluated when the promise is created. The first call to `force` evaluates it in the environment where `delay` appeared and caches the result; later calls return the cached value without re-evaluating.\n\nUseful for lazy data structures and compute-once values.".to_string(),
+        examples: vec![
+            "(define p (delay (+ 1 2))) => p".to_string(),
+            "(force p) => 3".to_string(),
+            "(define cached (delay (begin (println \"computing\") 42))) => cached".to_string(),
+            "(+ (force cached) (force cached)) => 84 (prints \"computing\" once)".to_string(),
+        ],
+        related: vec!["force".to_string(), "lambda".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "set!".to_string(),
+        signature: "(set! name value)".to_string(),
+        description: "Change the value of an existing variable.\n\nUpdates the nearest binding of `name` (local or global) and returns the new value. Unlike define, set! never creates a variable; setting an undefined name is an error.".to_string(),
+        examples: vec![
+            "(define count 0) => count".to_string(),
+            "(set! count (+ count 1)) => 1".to_string(),
+            "(let ((x 1)) (set! x 2) x) => 2".to_string(),
+        ],
+        related: vec!["define".to_string(), "while".to_string(), "defconstant".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "defconstant".to_string(),
+        signature: "(defconstant name value)".to_string(),
+        description: "Define a variable that cannot be changed.\n\nWorks like `(define name value)`, then freezes the binding: `set!` on it and later `define`s or `defconstant`s of the same name in the same scope are errors. Local bindings may still shadow it.".to_string(),
+        examples: vec![
+            "(defconstant max-retries 3) => max-retries".to_string(),
+            "(set! max-retries 5) => error: cannot change frozen binding max-retries".to_string(),
+            "(let ((max-retries 1)) max-retries) => 1".to_string(),
+        ],
+        related: vec!["define".to_string(), "set!".to_string(), "freeze".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "while".to_string(),
+        signature: "(while condition body...)".to_string(),
+        description: "Repeat the body while the condition is true.\n\nThe condition is evaluated before every iteration. Returns nil, or the value passed to `break`.\n\nUse `break` to leave the loop early and `continue` to skip to the next iteration.".to_string(),
+        examples: vec![
+            "(define i 0) => i".to_string(),
+            "(while (< i 3) (println i) (set! i (+ i 1))) => nil (prints 0 1 2)".to_string(),
+            "(while #t (break 42)) => 42".to_string(),
+        ],
+        related: vec!["dotimes".to_string(), "dolist".to_string(), "break".to_string(), "continue".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "dotimes".to_string(),
+        signature: "(dotimes (var count) body...)".to_string(),
+        description: "Run the body `count` times with `var` bound to 0, 1, ..., count-1.\n\nEach iteration gets a fresh binding, so closures created in the body remember their own value. Returns nil, or the value passed to `break`.".to_string(),
+        examples: vec![
+            "(dotimes (i 3) (println i)) => nil (prints 0 1 2)".to_string(),
+            "(dotimes (i 10) (if (= i 4) (break i))) => 4".to_string(),
+        ],
+        related: vec!["dolist".to_string(), "while".to_string(), "break".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "dolist".to_string(),
+        signature: "(dolist (var list) body...)".to_string(),
+        description: "Run the body once for each element of a list, with `var` bound to the element.\n\nReturns nil, or the value passed to `break`.".to_string(),
+        examples: vec![
+            "(dolist (x '(1 2 3)) (println x)) => nil (prints 1 2 3)".to_string(),
+            "(dolist (x '(1 -2 3)) (if (< x 0) (break x))) => -2".to_string(),
+        ],
+        related: vec!["dotimes".to_string(), "for-each".to_string(), "break".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "break".to_string(),
+        signature: "(break) or (break value)".to_string(),
+        description: "Leave the innermost running loop.\n\nThe loop (while, dotimes, dolist) returns `value`, or nil if none is given. Also works from inside a function called by the loop body. Using break outside a loop is an error.".to_string(),
+        examples: vec![
+            "(while #t (break 42)) => 42".to_string(),
+            "(dolist (x '(1 2 3)) (if (= x 2) (break (* x 10)))) => 20".to_string(),
+        ],
+        related: vec!["continue".to_string(), "while".to_string()],
+        category: "Special forms".to_string(),
+    });
+
     crate::help::register_help(crate::help::HelpEntry {
-        name: "define".to_string(),
-        signature: "(define name value) or (define (name params...) body)".to_string(),
-        description: "Define a variable or function in the current scope.\n\nThe first form binds a value to a name. The second form is syntactic sugar for defining a function, equivalent to `(define name (lambda (params...) body))`.\n\nReturns the name of the defined symbol.".to_string(),
+        name: "continue".to_string(),
+        signature: "(continue)".to_string(),
+        description: "Skip the rest of the loop body and start the next iteration of the innermost running loop.\n\nUsing continue outside a loop is an error.".to_string(),
         examples: vec![
-            "(define x 42) => x".to_string(),
-            "(define (square x) (* x x)) => square".to_string(),
-            "(define (add a b) (+ a b)) => add".to_string(),
-            "(add 3 4) => 7".to_string(),
+            "(dotimes (i 5) (if (even? i) (continue)) (println i)) => nil (prints 1 3)".to_string(),
         ],
-        related: vec!["lambda".to_string(), "let".to_string()],
+        related: vec!["break".to_string(), "while".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "lambda".to_string(),
-        signature: "(lambda (params...) [docstring] body)".to_string(),
-        description: "Create an anonymous function.\n\nThe parameters are a list of symbols. The body is evaluated when the function is called with the parameters bound to the argument values. Optionally, a docstring can be provided as the first element of the body.\n\nThe created function captures the lexical environment at definition time, enabling closures.".to_string(),
+        name: "try".to_string(),
+        signature: "(try body...)".to_string(),
+        description: "Evaluate the body and return its last value, or the error it raised as an error value.\n\nThe error's kind (see `error-kind`) says what went wrong: :not-found, :path-not-allowed, :type-error, :arity-error, :undefined-symbol and so on. Interrupts, step limits, `break` and `continue` pass through.".to_string(),
         examples: vec![
-            "((lambda (x) (+ x 1)) 5) => 6".to_string(),
-            "(define add (lambda (a b) (+ a b))) => add".to_string(),
-            "(define make-adder (lambda (n) (lambda (x) (+ x n)))) => make-adder".to_string(),
-            "((make-adder 10) 5) => 15".to_string(),
+            "(try (+ 1 2)) => 3".to_string(),
+            "(error-kind (try (read-file \"missing.txt\"))) => :not-found".to_string(),
+            "(error-msg (try (car 5))) => \"car: expected list, got number 5 at argument 1\"".to_string(),
         ],
-        related: vec!["define".to_string(), "let".to_string(), "doc".to_string()],
+        related: vec!["error".to_string(), "error-kind".to_string(), "error-data".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "if".to_string(),
-        signature: "(if condition then-expr [else-expr])".to_string(),
-        description: "Conditional evaluation.\n\nIf condition evaluates to a truthy value (anything except false), then-expr is evaluated and returned. Otherwise, else-expr is evaluated (if provided) and returned. If no else-expr is provided and condition is false, returns nil.\n\nOnly the taken branch is evaluated (short-circuit evaluation).".to_string(),
+        name: "->".to_string(),
+        signature: "(-> expr steps...)".to_string(),
+        description: "Thread a value through a pipeline, inserting it as the first argument of each step.\n\n`(-> x (f a) g)` is `(g (f x a))`. A bare symbol step `g` is called with the value alone.".to_string(),
         examples: vec![
-            "(if (> 5 3) \"yes\" \"no\") => \"yes\"".to_string(),
-            "(if false 42) => nil".to_string(),
-            "(if true (+ 1 2) (/ 1 0)) => 3".to_string(),
-            "(define (abs x) (if (< x 0) (- x) x)) => abs".to_string(),
+            "(-> 5 (- 1) (* 2)) => 8".to_string(),
+            "(-> {:n 1} (map-set :n 2) (map-get :n)) => 2".to_string(),
         ],
-        related: vec!["begin".to_string(), "and".to_string(), "or".to_string()],
+        related: vec!["->>".to_string(), "some->".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "begin".to_string(),
-        signature: "(begin expr1 expr2 ... exprN)".to_string(),
-        description: "Sequence multiple expressions.\n\nEvaluates each expression in order and returns the value of the last expression. All expressions are evaluated for their side effects, but only the final value is returned.\n\nUseful for grouping expressions in contexts that expect a single expression, like in `if` branches or `lambda` bodies.".to_string(),
+        name: "->>".to_string(),
+        signature: "(->> expr steps...)".to_string(),
+        description: "Thread a value through a pipeline, inserting it as the last argument of each step.\n\n`(->> xs (filter even?) (map square))` is `(map square (filter even? xs))`.".to_string(),
         examples: vec![
-            "(begin (print \"step 1\") (print \"step 2\") 42) => 42".to_string(),
-            "(define (side-effects) (begin (print \"first\") (print \"second\") \"result\")) => side-effects".to_string(),
-            "(if condition (begin (print \"doing something\") (+ 1 2)) 0)".to_string(),
+            "(->> '(1 2 3 4) (filter even?) (map (lambda (x) (* x x)))) => (4 16)".to_string(),
+            "(->> 5 (- 1)) => -4".to_string(),
         ],
-        related: vec!["if".to_string(), "define".to_string()],
+        related: vec!["->".to_string(), "some->".to_string()],
         category: "Special forms".to_string(),
     });
-}
 
-/// Register help documentation for special forms (Part 2)
-/// Documents: let, quote, quasiquote, defmacro
-pub fn register_special_forms_part2() {
     crate::help::register_help(crate::help::HelpEntry {
-        name: "let".to_string(),
-        signature: "(let ((var1 expr1) (var2 expr2) ...) body)".to_string(),
-        description: "Create local variable bindings.\n\nDefines temporary variables that are visible only within the body. Each variable is bound to the value of its corresponding expression. All binding expressions are evaluated in the outer scope before the body is evaluated.\n\nEquivalent to `((lambda (var1 var2 ...) body) expr1 expr2 ...)`.\n\nUseful for avoiding repeated calculations and improving code clarity.".to_string(),
+        name: "some->".to_string(),
+        signature: "(some-> expr steps...)".to_string(),
+        description: "Like `->`, but stop and return the current value as soon as it is nil, an error value or an `(err reason)` result.\n\nLets a pipeline of fallible steps run without nested ifs.".to_string(),
         examples: vec![
-            "(let ((x 10) (y 20)) (+ x y)) => 30".to_string(),
-            "(let ((a (+ 1 2)) (b (* 3 4))) (+ a b)) => 15".to_string(),
-            "(let ((x 5)) (let ((y 10)) (+ x y))) => 15".to_string(),
-            "(define (quadratic a b c x) (let ((delta (- (* b b) (* 4 a c)))) (/ delta 2))) => quadratic".to_string(),
+            "(some-> {:a {:b 1}} (map-get :a) (map-get :b)) => 1".to_string(),
+            "(some-> {:a 1} (map-get :missing) (+ 1)) => nil".to_string(),
+            "(some-> (try (read-file \"missing.txt\")) (string-upper)) => #<error :not-found ...>".to_string(),
         ],
-        related: vec!["lambda".to_string(), "define".to_string()],
+        related: vec!["->".to_string(), "try".to_string(), "unwrap-or".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "quote".to_string(),
-        signature: "(quote expr) or 'expr".to_string(),
-        description: "Return an expression unevaluated.\n\nPrevents evaluation of the expression. Returns the expression itself as data.\n\nOften used with symbols and lists to create data structures that would otherwise be evaluated as code. The shorthand syntax 'expr is equivalent to (quote expr).".to_string(),
+        name: "profile".to_string(),
+        signature: "(profile expr)".to_string(),
+        description: "Evaluate expr, print call counts and wall time for every function it called (slowest first), and return its value.\n\nTotal time includes callees. A tail call ends the caller's time. Lambdas are named after the global they are bound to.".to_string(),
         examples: vec![
-            "'x => x (the symbol, not its value)".to_string(),
-            "'(1 2 3) => (1 2 3) (the list, not evaluated as function call)".to_string(),
-            "(quote (+ 1 2)) => (+ 1 2)".to_string(),
-            "'() => () (empty list)".to_string(),
+            "(profile (map square (range 1 1000))) => (1 4 9 ...) (prints a table with square, map, ...)".to_string(),
         ],
-        related: vec!["quasiquote".to_string()],
+        related: vec!["time".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "quasiquote".to_string(),
-        signature: "(quasiquote template) or `template".to_string(),
-        description: "Return a template with selective evaluation.\n\nLike quote, but allows selective evaluation of parts using unquote (,) and unquote-splicing (,@).\n\nUnquoted parts are evaluated; unquoted-spliced lists are spliced into the result. This is the foundation of the macro system.".to_string(),
+        name: "time".to_string(),
+        signature: "(time expr)".to_string(),
+        description: "Evaluate expr, print the wall time it took in milliseconds, and return its value.\n\nThe time is printed even when expr raises an error, which is then passed on.".to_string(),
         examples: vec![
-            "`(+ 1 2) => (+ 1 2)".to_string(),
-            "`(+ 1 ,(+ 2 3)) => (+ 1 5)".to_string(),
-            "`(list ,@(list 1 2 3)) => (list 1 2 3)".to_string(),
-            "(define x 10) => x".to_string(),
-            "`(x is ,x) => (x is 10)".to_string(),
+            "(time (factorial 20)) => 2432902008176640000 (prints Elapsed time: 0.042 ms)".to_string(),
         ],
-        related: vec!["quote".to_string(), "defmacro".to_string()],
+        related: vec!["bench".to_string(), "profile".to_string()],
         category: "Special forms".to_string(),
     });
 
     crate::help::register_help(crate::help::HelpEntry {
-        name: "defmacro".to_string(),
-        signature: "(defmacro (name params...) [docstring] body)".to_string(),
-        description: "Define a compile-time transformation.\n\nMacros receive unevaluated arguments and return code to be evaluated. Unlike functions, macro arguments are not evaluated before the macro is called. The macro body should return a list representing the code to evaluate.\n\nMacros enable syntactic abstraction and domain-specific languages.".to_string(),
+        name: "bench".to_string(),
+        signature: "(bench expr iterations)".to_string(),
+        description: "Evaluate expr iterations times and return a map of wall times per run in milliseconds: :min, :mean, :max and :total, with :iterations.\n\nexpr is evaluated afresh each time; iterations must be a positive integer. An error in any run stops the benchmark and is raised.".to_string(),
         examples: vec![
-            "(defmacro (when condition body) `(if ,condition ,body))".to_string(),
-            "(defmacro (repeat n body) `(let ((i 0)) (while (< i ,n) (begin ,body (set! i (+ i 1))))))".to_string(),
-            "(defmacro (assert condition) `(if (not ,condition) (error \"Assertion failed\")))".to_string(),
+            "(bench (factorial 20) 100) => {:iterations 100 :max 0.41 :mean 0.22 :min 0.19 :total 22.3}".to_string(),
+            "(map-get (bench (range 1 1000) 10) :mean) => 0.8".to_string(),
         ],
-        related: vec!["quote".to_string(), "quasiquote".to_string(), "lambda".to_string()],
+        related: vec!["time".to_string(), "profile".to_string()],
+        category: "Special forms".to_string(),
+    });
+
+    crate::help::register_help(crate::help::HelpEntry {
+        name: "case".to_string(),
+        signature: "(case key ((datum...) body...) ... [(else body...)])".to_string(),
+        description: "Evaluate key and run the first clause that lists its value.\n\nDatums are not evaluated: they are numbers, strings, symbols, keywords or booleans, compared like `=`, and a clause with a single datum may leave out the parentheses around it. The body of the matching clause is evaluated in order and its last value returned, as a tail call; `else` matches anything. Returns nil when nothing matches.\n\nInside functions the clauses are indexed once, so dispatch takes the same time however many clauses there are.".to_string(),
+        examples: vec![
+            "(case 3 ((1 2) \"low\") ((3 4) \"mid\") (else \"high\")) => \"mid\"".to_string(),
+            "(case 'red (red \"stop\") (green \"go\")) => \"stop\"".to_string(),
+            "(case :put ((:get :head) 'read) (:put 'write)) => write".to_string(),
+        ],
+        related: vec!["if".to_string()],
         category: "Special forms".to_string(),
     });
 }
@@ -756,9 +2060,9 @@ mod tests {
     #[test]
     fn test_eval_string() {
         let env = Environment::new();
-        let result = eval(Value::String("hello".to_string()), env).unwrap();
+        let result = eval(Value::String("hello".into()), env).unwrap();
         match result {
-            Value::String(s) => assert_eq!(s, "hello"),
+            Value::String(s) => assert_eq!(s, "hello".into()),
             _ => panic!("Expected String(\"hello\")"),
         }
     }
@@ -768,7 +2072,7 @@ mod tests {
         let env = Environment::new();
         env.define("x".to_string(), Value::Number(42.0));
 
-        let result = eval(Value::Symbol("x".to_string()), env).unwrap();
+        let result = eval(Value::Symbol("x".into()), env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 42.0),
             _ => panic!("Expected Number(42.0)"),
@@ -778,10 +2082,10 @@ mod tests {
     #[test]
     fn test_eval_undefined_symbol() {
         let env = Environment::new();
-        let result = eval(Value::Symbol("undefined".to_string()), env);
+        let result = eval(Value::Symbol("undefined".into()), env);
 
         match result {
-            Err(EvalError::UndefinedSymbol(name)) => assert_eq!(name, "undefined"),
+            Err(EvalError::UndefinedSymbol { name, .. }) => assert_eq!(name, "undefined"),
             _ => panic!("Expected UndefinedSymbol error"),
         }
     }
@@ -801,11 +2105,14 @@ mod tests {
         let env = Environment::new();
 
         // (define x 42)
-        let define_expr = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("x".to_string()),
-            Value::Number(42.0),
-        ]);
+        let define_expr = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("x".into()),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
         let result = eval(define_expr, env.clone()).unwrap();
 
@@ -827,14 +2134,14 @@ mod tests {
         let env = Environment::new();
 
         // (define (f x) x)
-        let define_expr = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::List(vec![
-                Value::Symbol("f".to_string()),
-                Value::Symbol("x".to_string()),
-            ]),
-            Value::Symbol("x".to_string()),
-        ]);
+        let define_expr = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::List(vec![Value::Symbol("f".into()), Value::Symbol("x".into())].into()),
+                Value::Symbol("x".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(define_expr, env.clone()).unwrap();
 
@@ -863,15 +2170,18 @@ mod tests {
         let env = Environment::new();
 
         // (define x 42)
-        let define_expr = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("x".to_string()),
-            Value::Number(42.0),
-        ]);
+        let define_expr = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("x".into()),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
         eval(define_expr, env.clone()).unwrap();
 
         // Now eval the symbol x
-        let result = eval(Value::Symbol("x".to_string()), env).unwrap();
+        let result = eval(Value::Symbol("x".into()), env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 42.0),
             _ => panic!("Expected Number(42.0)"),
@@ -886,15 +2196,18 @@ mod tests {
         let child = Environment::with_parent(parent);
 
         // Define x in child scope
-        let define_expr = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("x".to_string()),
-            Value::Number(20.0),
-        ]);
+        let define_expr = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("x".into()),
+                Value::Number(20.0),
+            ]
+            .into(),
+        );
         eval(define_expr, child.clone()).unwrap();
 
         // Child should see its own value
-        let result = eval(Value::Symbol("x".to_string()), child).unwrap();
+        let result = eval(Value::Symbol("x".into()), child).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 20.0),
             _ => panic!("Expected Number(20.0)"),
@@ -910,11 +2223,14 @@ mod tests {
         let env = Environment::new();
 
         // (lambda (x) x)
-        let lambda_expr = Value::List(vec![
-            Value::Symbol("lambda".to_string()),
-            Value::List(vec![Value::Symbol("x".to_string())]),
-            Value::Symbol("x".to_string()),
-        ]);
+        let lambda_expr = Value::List(
+            vec![
+                Value::Symbol("lambda".into()),
+                Value::List(vec![Value::Symbol("x".into())].into()),
+                Value::Symbol("x".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(lambda_expr, env).unwrap();
         match result {
@@ -931,14 +2247,20 @@ mod tests {
         let env = Environment::new();
 
         // ((lambda (x) x) 42)
-        let expr = Value::List(vec![
-            Value::List(vec![
-                Value::Symbol("lambda".to_string()),
-                Value::List(vec![Value::Symbol("x".to_string())]),
-                Value::Symbol("x".to_string()),
-            ]),
-            Value::Number(42.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::List(
+                    vec![
+                        Value::Symbol("lambda".into()),
+                        Value::List(vec![Value::Symbol("x".into())].into()),
+                        Value::Symbol("x".into()),
+                    ]
+                    .into(),
+                ),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -953,22 +2275,30 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // ((lambda (x y) (+ x y)) 10 20)
-        let expr = Value::List(vec![
-            Value::List(vec![
-                Value::Symbol("lambda".to_string()),
-                Value::List(vec![
-                    Value::Symbol("x".to_string()),
-                    Value::Symbol("y".to_string()),
-                ]),
-                Value::List(vec![
-                    Value::Symbol("+".to_string()),
-                    Value::Symbol("x".to_string()),
-                    Value::Symbol("y".to_string()),
-                ]),
-            ]),
-            Value::Number(10.0),
-            Value::Number(20.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::List(
+                    vec![
+                        Value::Symbol("lambda".into()),
+                        Value::List(
+                            vec![Value::Symbol("x".into()), Value::Symbol("y".into())].into(),
+                        ),
+                        Value::List(
+                            vec![
+                                Value::Symbol("+".into()),
+                                Value::Symbol("x".into()),
+                                Value::Symbol("y".into()),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+                Value::Number(10.0),
+                Value::Number(20.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -982,15 +2312,21 @@ mod tests {
         let env = Environment::new();
 
         // ((lambda (x) x) 1 2) - too many args
-        let expr = Value::List(vec![
-            Value::List(vec![
-                Value::Symbol("lambda".to_string()),
-                Value::List(vec![Value::Symbol("x".to_string())]),
-                Value::Symbol("x".to_string()),
-            ]),
-            Value::Number(1.0),
-            Value::Number(2.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::List(
+                    vec![
+                        Value::Symbol("lambda".into()),
+                        Value::List(vec![Value::Symbol("x".into())].into()),
+                        Value::Symbol("x".into()),
+                    ]
+                    .into(),
+                ),
+                Value::Number(1.0),
+                Value::Number(2.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env);
         assert!(matches!(result, Err(EvalError::ArityError { .. })));
@@ -1002,31 +2338,43 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (define x 10)
-        let define_x = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("x".to_string()),
-            Value::Number(10.0),
-        ]);
+        let define_x = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("x".into()),
+                Value::Number(10.0),
+            ]
+            .into(),
+        );
         eval(define_x, env.clone()).unwrap();
 
         // (define f (lambda (y) (+ x y)))
-        let define_f = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("f".to_string()),
-            Value::List(vec![
-                Value::Symbol("lambda".to_string()),
-                Value::List(vec![Value::Symbol("y".to_string())]),
-                Value::List(vec![
-                    Value::Symbol("+".to_string()),
-                    Value::Symbol("x".to_string()),
-                    Value::Symbol("y".to_string()),
-                ]),
-            ]),
-        ]);
+        let define_f = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("f".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("lambda".into()),
+                        Value::List(vec![Value::Symbol("y".into())].into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("+".into()),
+                                Value::Symbol("x".into()),
+                                Value::Symbol("y".into()),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
         eval(define_f, env.clone()).unwrap();
 
         // (f 5) should be 15
-        let call_f = Value::List(vec![Value::Symbol("f".to_string()), Value::Number(5.0)]);
+        let call_f = Value::List(vec![Value::Symbol("f".into()), Value::Number(5.0)].into());
         let result = eval(call_f, env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 15.0),
@@ -1040,15 +2388,21 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (* (+ 1 2) 3) should be 9
-        let expr = Value::List(vec![
-            Value::Symbol("*".to_string()),
-            Value::List(vec![
-                Value::Symbol("+".to_string()),
-                Value::Number(1.0),
-                Value::Number(2.0),
-            ]),
-            Value::Number(3.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("*".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("+".into()),
+                        Value::Number(1.0),
+                        Value::Number(2.0),
+                    ]
+                    .into(),
+                ),
+                Value::Number(3.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1063,37 +2417,49 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (define (make-adder n) (lambda (x) (+ x n)))
-        let define_maker = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::List(vec![
-                Value::Symbol("make-adder".to_string()),
-                Value::Symbol("n".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("lambda".to_string()),
-                Value::List(vec![Value::Symbol("x".to_string())]),
-                Value::List(vec![
-                    Value::Symbol("+".to_string()),
-                    Value::Symbol("x".to_string()),
-                    Value::Symbol("n".to_string()),
-                ]),
-            ]),
-        ]);
+        let define_maker = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("make-adder".into()),
+                        Value::Symbol("n".into()),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("lambda".into()),
+                        Value::List(vec![Value::Symbol("x".into())].into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("+".into()),
+                                Value::Symbol("x".into()),
+                                Value::Symbol("n".into()),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
         eval(define_maker, env.clone()).unwrap();
 
         // (define add5 (make-adder 5))
-        let define_add5 = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::Symbol("add5".to_string()),
-            Value::List(vec![
-                Value::Symbol("make-adder".to_string()),
-                Value::Number(5.0),
-            ]),
-        ]);
+        let define_add5 = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::Symbol("add5".into()),
+                Value::List(vec![Value::Symbol("make-adder".into()), Value::Number(5.0)].into()),
+            ]
+            .into(),
+        );
         eval(define_add5, env.clone()).unwrap();
 
         // (add5 10) should be 15
-        let call_add5 = Value::List(vec![Value::Symbol("add5".to_string()), Value::Number(10.0)]);
+        let call_add5 = Value::List(vec![Value::Symbol("add5".into()), Value::Number(10.0)].into());
         let result = eval(call_add5, env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 15.0),
@@ -1107,12 +2473,15 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (+ 1 2 3)
-        let expr = Value::List(vec![
-            Value::Symbol("+".to_string()),
-            Value::Number(1.0),
-            Value::Number(2.0),
-            Value::Number(3.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("+".into()),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1126,14 +2495,12 @@ mod tests {
         let env = Environment::new();
 
         // (42 1 2) - trying to call a number
-        let expr = Value::List(vec![
-            Value::Number(42.0),
-            Value::Number(1.0),
-            Value::Number(2.0),
-        ]);
+        let expr =
+            Value::List(vec![Value::Number(42.0), Value::Number(1.0), Value::Number(2.0)].into());
 
-        let result = eval(expr, env);
-        assert!(matches!(result, Err(EvalError::NotCallable)));
+        let err = eval(expr, env).unwrap_err();
+        assert!(matches!(err, EvalError::NotCallable { .. }));
+        assert_eq!(err.to_string(), "number 42 is not callable");
     }
 
     // ========================================================================
@@ -1145,12 +2512,15 @@ mod tests {
         let env = Environment::new();
 
         // (if #t 42 0)
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::Bool(true),
-            Value::Number(42.0),
-            Value::Number(0.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::Bool(true),
+                Value::Number(42.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1164,12 +2534,15 @@ mod tests {
         let env = Environment::new();
 
         // (if #f 42 0)
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::Bool(false),
-            Value::Number(42.0),
-            Value::Number(0.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::Bool(false),
+                Value::Number(42.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1183,11 +2556,14 @@ mod tests {
         let env = Environment::new();
 
         // (if #f 42) - should return nil
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::Bool(false),
-            Value::Number(42.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::Bool(false),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1201,12 +2577,15 @@ mod tests {
         let env = Environment::new();
 
         // (if nil 42 0)
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::Nil,
-            Value::Number(42.0),
-            Value::Number(0.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::Nil,
+                Value::Number(42.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1220,12 +2599,15 @@ mod tests {
         let env = Environment::new();
 
         // (if 0 42 0) - 0 is truthy in Lisp
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::Number(0.0),
-            Value::Number(42.0),
-            Value::Number(0.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::Number(0.0),
+                Value::Number(42.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1240,16 +2622,22 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (if (< 1 2) 42 0)
-        let expr = Value::List(vec![
-            Value::Symbol("if".to_string()),
-            Value::List(vec![
-                Value::Symbol("<".to_string()),
-                Value::Number(1.0),
-                Value::Number(2.0),
-            ]),
-            Value::Number(42.0),
-            Value::Number(0.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("if".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("<".into()),
+                        Value::Number(1.0),
+                        Value::Number(2.0),
+                    ]
+                    .into(),
+                ),
+                Value::Number(42.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1267,7 +2655,7 @@ mod tests {
         let env = Environment::new();
 
         // (begin)
-        let expr = Value::List(vec![Value::Symbol("begin".to_string())]);
+        let expr = Value::List(vec![Value::Symbol("begin".into())].into());
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1281,10 +2669,7 @@ mod tests {
         let env = Environment::new();
 
         // (begin 42)
-        let expr = Value::List(vec![
-            Value::Symbol("begin".to_string()),
-            Value::Number(42.0),
-        ]);
+        let expr = Value::List(vec![Value::Symbol("begin".into()), Value::Number(42.0)].into());
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1298,12 +2683,15 @@ mod tests {
         let env = Environment::new();
 
         // (begin 1 2 3)
-        let expr = Value::List(vec![
-            Value::Symbol("begin".to_string()),
-            Value::Number(1.0),
-            Value::Number(2.0),
-            Value::Number(3.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("begin".into()),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Number(3.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1318,20 +2706,29 @@ mod tests {
 
         // (begin (define x 10) (define y 20) (+ x y))
         // This is just to verify all expressions execute
-        let expr = Value::List(vec![
-            Value::Symbol("begin".to_string()),
-            Value::List(vec![
-                Value::Symbol("define".to_string()),
-                Value::Symbol("x".to_string()),
-                Value::Number(10.0),
-            ]),
-            Value::List(vec![
-                Value::Symbol("define".to_string()),
-                Value::Symbol("y".to_string()),
-                Value::Number(20.0),
-            ]),
-            Value::Symbol("y".to_string()),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("begin".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("define".into()),
+                        Value::Symbol("x".into()),
+                        Value::Number(10.0),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("define".into()),
+                        Value::Symbol("y".into()),
+                        Value::Number(20.0),
+                    ]
+                    .into(),
+                ),
+                Value::Symbol("y".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env.clone()).unwrap();
         match result {
@@ -1355,14 +2752,19 @@ mod tests {
         let env = Environment::new();
 
         // (let ((x 42)) x)
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![Value::List(vec![
-                Value::Symbol("x".to_string()),
-                Value::Number(42.0),
-            ])]),
-            Value::Symbol("x".to_string()),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(
+                    vec![Value::List(
+                        vec![Value::Symbol("x".into()), Value::Number(42.0)].into(),
+                    )]
+                    .into(),
+                ),
+                Value::Symbol("x".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1377,18 +2779,27 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (let ((x 10) (y 20)) (+ x y))
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![
-                Value::List(vec![Value::Symbol("x".to_string()), Value::Number(10.0)]),
-                Value::List(vec![Value::Symbol("y".to_string()), Value::Number(20.0)]),
-            ]),
-            Value::List(vec![
-                Value::Symbol("+".to_string()),
-                Value::Symbol("x".to_string()),
-                Value::Symbol("y".to_string()),
-            ]),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(
+                    vec![
+                        Value::List(vec![Value::Symbol("x".into()), Value::Number(10.0)].into()),
+                        Value::List(vec![Value::Symbol("y".into()), Value::Number(20.0)].into()),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("+".into()),
+                        Value::Symbol("x".into()),
+                        Value::Symbol("y".into()),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1406,14 +2817,19 @@ mod tests {
         env.define("x".to_string(), Value::Number(100.0));
 
         // (let ((x 10)) x) - should shadow global x
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![Value::List(vec![
-                Value::Symbol("x".to_string()),
-                Value::Number(10.0),
-            ])]),
-            Value::Symbol("x".to_string()),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(
+                    vec![Value::List(
+                        vec![Value::Symbol("x".into()), Value::Number(10.0)].into(),
+                    )]
+                    .into(),
+                ),
+                Value::Symbol("x".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env.clone()).unwrap();
         match result {
@@ -1434,32 +2850,53 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (let ((x (+ 1 2)) (y (* 3 4))) (+ x y))
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![
-                Value::List(vec![
-                    Value::Symbol("x".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("+".to_string()),
-                        Value::Number(1.0),
-                        Value::Number(2.0),
-                    ]),
-                ]),
-                Value::List(vec![
-                    Value::Symbol("y".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("*".to_string()),
-                        Value::Number(3.0),
-                        Value::Number(4.0),
-                    ]),
-                ]),
-            ]),
-            Value::List(vec![
-                Value::Symbol("+".to_string()),
-                Value::Symbol("x".to_string()),
-                Value::Symbol("y".to_string()),
-            ]),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(
+                    vec![
+                        Value::List(
+                            vec![
+                                Value::Symbol("x".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("+".into()),
+                                        Value::Number(1.0),
+                                        Value::Number(2.0),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                        Value::List(
+                            vec![
+                                Value::Symbol("y".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("*".into()),
+                                        Value::Number(3.0),
+                                        Value::Number(4.0),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("+".into()),
+                        Value::Symbol("x".into()),
+                        Value::Symbol("y".into()),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1473,11 +2910,14 @@ mod tests {
         let env = Environment::new();
 
         // (let () 42)
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![]),
-            Value::Number(42.0),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(vec![].into()),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1491,16 +2931,21 @@ mod tests {
         let env = Environment::new();
 
         // (let ((x 10)) 1 2 x)
-        let expr = Value::List(vec![
-            Value::Symbol("let".to_string()),
-            Value::List(vec![Value::List(vec![
-                Value::Symbol("x".to_string()),
-                Value::Number(10.0),
-            ])]),
-            Value::Number(1.0),
-            Value::Number(2.0),
-            Value::Symbol("x".to_string()),
-        ]);
+        let expr = Value::List(
+            vec![
+                Value::Symbol("let".into()),
+                Value::List(
+                    vec![Value::List(
+                        vec![Value::Symbol("x".into()), Value::Number(10.0)].into(),
+                    )]
+                    .into(),
+                ),
+                Value::Number(1.0),
+                Value::Number(2.0),
+                Value::Symbol("x".into()),
+            ]
+            .into(),
+        );
 
         let result = eval(expr, env).unwrap();
         match result {
@@ -1519,44 +2964,68 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (define (sum n acc) (if (<= n 0) acc (sum (- n 1) (+ acc n))))
-        let define_sum = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::List(vec![
-                Value::Symbol("sum".to_string()),
-                Value::Symbol("n".to_string()),
-                Value::Symbol("acc".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("if".to_string()),
-                Value::List(vec![
-                    Value::Symbol("<=".to_string()),
-                    Value::Symbol("n".to_string()),
-                    Value::Number(0.0),
-                ]),
-                Value::Symbol("acc".to_string()),
-                Value::List(vec![
-                    Value::Symbol("sum".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("-".to_string()),
-                        Value::Symbol("n".to_string()),
-                        Value::Number(1.0),
-                    ]),
-                    Value::List(vec![
-                        Value::Symbol("+".to_string()),
-                        Value::Symbol("acc".to_string()),
-                        Value::Symbol("n".to_string()),
-                    ]),
-                ]),
-            ]),
-        ]);
+        let define_sum = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("sum".into()),
+                        Value::Symbol("n".into()),
+                        Value::Symbol("acc".into()),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("if".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("<=".into()),
+                                Value::Symbol("n".into()),
+                                Value::Number(0.0),
+                            ]
+                            .into(),
+                        ),
+                        Value::Symbol("acc".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("sum".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("-".into()),
+                                        Value::Symbol("n".into()),
+                                        Value::Number(1.0),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("+".into()),
+                                        Value::Symbol("acc".into()),
+                                        Value::Symbol("n".into()),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
         eval(define_sum, env.clone()).unwrap();
 
         // (sum 10 0) should be 55
-        let call_sum = Value::List(vec![
-            Value::Symbol("sum".to_string()),
-            Value::Number(10.0),
-            Value::Number(0.0),
-        ]);
+        let call_sum = Value::List(
+            vec![
+                Value::Symbol("sum".into()),
+                Value::Number(10.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
         let result = eval(call_sum, env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 55.0),
@@ -1570,44 +3039,68 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (define (sum n acc) (if (<= n 0) acc (sum (- n 1) (+ acc n))))
-        let define_sum = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::List(vec![
-                Value::Symbol("sum".to_string()),
-                Value::Symbol("n".to_string()),
-                Value::Symbol("acc".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("if".to_string()),
-                Value::List(vec![
-                    Value::Symbol("<=".to_string()),
-                    Value::Symbol("n".to_string()),
-                    Value::Number(0.0),
-                ]),
-                Value::Symbol("acc".to_string()),
-                Value::List(vec![
-                    Value::Symbol("sum".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("-".to_string()),
-                        Value::Symbol("n".to_string()),
-                        Value::Number(1.0),
-                    ]),
-                    Value::List(vec![
-                        Value::Symbol("+".to_string()),
-                        Value::Symbol("acc".to_string()),
-                        Value::Symbol("n".to_string()),
-                    ]),
-                ]),
-            ]),
-        ]);
+        let define_sum = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("sum".into()),
+                        Value::Symbol("n".into()),
+                        Value::Symbol("acc".into()),
+                    ]
+                    .into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("if".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("<=".into()),
+                                Value::Symbol("n".into()),
+                                Value::Number(0.0),
+                            ]
+                            .into(),
+                        ),
+                        Value::Symbol("acc".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("sum".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("-".into()),
+                                        Value::Symbol("n".into()),
+                                        Value::Number(1.0),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("+".into()),
+                                        Value::Symbol("acc".into()),
+                                        Value::Symbol("n".into()),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
         eval(define_sum, env.clone()).unwrap();
 
         // Test with 10000 - this would stack overflow without TCO
-        let call_sum = Value::List(vec![
-            Value::Symbol("sum".to_string()),
-            Value::Number(10000.0),
-            Value::Number(0.0),
-        ]);
+        let call_sum = Value::List(
+            vec![
+                Value::Symbol("sum".into()),
+                Value::Number(10000.0),
+                Value::Number(0.0),
+            ]
+            .into(),
+        );
         let result = eval(call_sum, env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 50005000.0), // sum of 1..10000
@@ -1621,40 +3114,55 @@ mod tests {
         crate::builtins::register_builtins(env.clone());
 
         // (define (countdown n) (if (<= n 0) 0 (begin (countdown (- n 1)))))
-        let define_countdown = Value::List(vec![
-            Value::Symbol("define".to_string()),
-            Value::List(vec![
-                Value::Symbol("countdown".to_string()),
-                Value::Symbol("n".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("if".to_string()),
-                Value::List(vec![
-                    Value::Symbol("<=".to_string()),
-                    Value::Symbol("n".to_string()),
-                    Value::Number(0.0),
-                ]),
-                Value::Number(0.0),
-                Value::List(vec![
-                    Value::Symbol("begin".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("countdown".to_string()),
-                        Value::List(vec![
-                            Value::Symbol("-".to_string()),
-                            Value::Symbol("n".to_string()),
-                            Value::Number(1.0),
-                        ]),
-                    ]),
-                ]),
-            ]),
-        ]);
+        let define_countdown = Value::List(
+            vec![
+                Value::Symbol("define".into()),
+                Value::List(
+                    vec![Value::Symbol("countdown".into()), Value::Symbol("n".into())].into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("if".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("<=".into()),
+                                Value::Symbol("n".into()),
+                                Value::Number(0.0),
+                            ]
+                            .into(),
+                        ),
+                        Value::Number(0.0),
+                        Value::List(
+                            vec![
+                                Value::Symbol("begin".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("countdown".into()),
+                                        Value::List(
+                                            vec![
+                                                Value::Symbol("-".into()),
+                                                Value::Symbol("n".into()),
+                                                Value::Number(1.0),
+                                            ]
+                                            .into(),
+                                        ),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
         eval(define_countdown, env.clone()).unwrap();
 
         // Test with 5000 - should not stack overflow
-        let call_countdown = Value::List(vec![
-            Value::Symbol("countdown".to_string()),
-            Value::Number(5000.0),
-        ]);
+        let call_countdown =
+            Value::List(vec![Value::Symbol("countdown".into()), Value::Number(5000.0)].into());
         let result = eval(call_countdown, env).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 0.0),
@@ -1669,19 +3177,20 @@ mod tests {
     #[test]
     fn test_quasiquote_basic() {
         let env = Environment::new();
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // `(1 2 3) should return (1 2 3)
-        let expr = Value::List(vec![
-            Value::Symbol("quasiquote".to_string()),
-            Value::List(vec![
-                Value::Number(1.0),
-                Value::Number(2.0),
-                Value::Number(3.0),
-            ]),
-        ]);
-
-        let result = eval_with_macros(expr, env, &mut macro_reg).unwrap();
+        let expr = Value::List(
+            vec![
+                Value::Symbol("quasiquote".into()),
+                Value::List(
+                    vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)].into(),
+                ),
+            ]
+            .into(),
+        );
+
+        let result = eval_with_macros(expr, env, &macro_reg).unwrap();
         match result {
             Value::List(items) => {
                 assert_eq!(items.len(), 3);
@@ -1701,25 +3210,30 @@ mod tests {
     #[test]
     fn test_quasiquote_with_unquote() {
         let env = Environment::new();
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // Define x
         env.define("x".to_string(), Value::Number(42.0));
 
         // `(1 ,x 3) should return (1 42 3)
-        let expr = Value::List(vec![
-            Value::Symbol("quasiquote".to_string()),
-            Value::List(vec![
-                Value::Number(1.0),
-                Value::List(vec![
-                    Value::Symbol("unquote".to_string()),
-                    Value::Symbol("x".to_string()),
-                ]),
-                Value::Number(3.0),
-            ]),
-        ]);
-
-        let result = eval_with_macros(expr, env, &mut macro_reg).unwrap();
+        let expr = Value::List(
+            vec![
+                Value::Symbol("quasiquote".into()),
+                Value::List(
+                    vec![
+                        Value::Number(1.0),
+                        Value::List(
+                            vec![Value::Symbol("unquote".into()), Value::Symbol("x".into())].into(),
+                        ),
+                        Value::Number(3.0),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        let result = eval_with_macros(expr, env, &macro_reg).unwrap();
         match result {
             Value::List(items) => {
                 assert_eq!(items.len(), 3);
@@ -1740,26 +3254,38 @@ mod tests {
     fn test_quasiquote_with_unquote_splicing() {
         let env = Environment::new();
         crate::builtins::register_builtins(env.clone());
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // `(1 ,@(list 2 3) 4) should return (1 2 3 4)
-        let expr = Value::List(vec![
-            Value::Symbol("quasiquote".to_string()),
-            Value::List(vec![
-                Value::Number(1.0),
-                Value::List(vec![
-                    Value::Symbol("unquote-splicing".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("list".to_string()),
-                        Value::Number(2.0),
-                        Value::Number(3.0),
-                    ]),
-                ]),
-                Value::Number(4.0),
-            ]),
-        ]);
-
-        let result = eval_with_macros(expr, env, &mut macro_reg).unwrap();
+        let expr = Value::List(
+            vec![
+                Value::Symbol("quasiquote".into()),
+                Value::List(
+                    vec![
+                        Value::Number(1.0),
+                        Value::List(
+                            vec![
+                                Value::Symbol("unquote-splicing".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("list".into()),
+                                        Value::Number(2.0),
+                                        Value::Number(3.0),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                        Value::Number(4.0),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        let result = eval_with_macros(expr, env, &macro_reg).unwrap();
         match result {
             Value::List(items) => {
                 assert_eq!(items.len(), 4);
@@ -1781,60 +3307,80 @@ mod tests {
     fn test_defmacro_simple() {
         let env = Environment::new();
         crate::builtins::register_builtins(env.clone());
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // (defmacro when (test body) `(if ,test ,body nil))
-        let defmacro_expr = Value::List(vec![
-            Value::Symbol("defmacro".to_string()),
-            Value::Symbol("when".to_string()),
-            Value::List(vec![
-                Value::Symbol("test".to_string()),
-                Value::Symbol("body".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("quasiquote".to_string()),
-                Value::List(vec![
-                    Value::Symbol("if".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("test".to_string()),
-                    ]),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("body".to_string()),
-                    ]),
-                    Value::Nil,
-                ]),
-            ]),
-        ]);
-
-        let result = eval_with_macros(defmacro_expr, env.clone(), &mut macro_reg).unwrap();
+        let defmacro_expr = Value::List(
+            vec![
+                Value::Symbol("defmacro".into()),
+                Value::Symbol("when".into()),
+                Value::List(
+                    vec![Value::Symbol("test".into()), Value::Symbol("body".into())].into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("quasiquote".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("if".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("test".into()),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("body".into()),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::Nil,
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        let result = eval_with_macros(defmacro_expr, env.clone(), &macro_reg).unwrap();
         match result {
             Value::Symbol(s) => assert_eq!(s, "when"),
             _ => panic!("Expected Symbol(\"when\")"),
         }
 
         // Now use the macro: (when #t 42)
-        let use_macro = Value::List(vec![
-            Value::Symbol("when".to_string()),
-            Value::Bool(true),
-            Value::Number(42.0),
-        ]);
+        let use_macro = Value::List(
+            vec![
+                Value::Symbol("when".into()),
+                Value::Bool(true),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
-        let result = eval_with_macros(use_macro, env.clone(), &mut macro_reg).unwrap();
+        let result = eval_with_macros(use_macro, env.clone(), &macro_reg).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 42.0),
             _ => panic!("Expected Number(42.0)"),
         }
 
         // (when #f 42) should return nil
-        let use_macro_false = Value::List(vec![
-            Value::Symbol("when".to_string()),
-            Value::Bool(false),
-            Value::Number(42.0),
-        ]);
+        let use_macro_false = Value::List(
+            vec![
+                Value::Symbol("when".into()),
+                Value::Bool(false),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
-        let result = eval_with_macros(use_macro_false, env, &mut macro_reg).unwrap();
+        let result = eval_with_macros(use_macro_false, env, &macro_reg).unwrap();
         match result {
             Value::Nil => (),
             _ => panic!("Expected Nil"),
@@ -1845,56 +3391,76 @@ mod tests {
     fn test_defmacro_unless() {
         let env = Environment::new();
         crate::builtins::register_builtins(env.clone());
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // (defmacro unless (test body) `(if ,test nil ,body))
-        let defmacro_expr = Value::List(vec![
-            Value::Symbol("defmacro".to_string()),
-            Value::Symbol("unless".to_string()),
-            Value::List(vec![
-                Value::Symbol("test".to_string()),
-                Value::Symbol("body".to_string()),
-            ]),
-            Value::List(vec![
-                Value::Symbol("quasiquote".to_string()),
-                Value::List(vec![
-                    Value::Symbol("if".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("test".to_string()),
-                    ]),
-                    Value::Nil,
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("body".to_string()),
-                    ]),
-                ]),
-            ]),
-        ]);
-
-        eval_with_macros(defmacro_expr, env.clone(), &mut macro_reg).unwrap();
+        let defmacro_expr = Value::List(
+            vec![
+                Value::Symbol("defmacro".into()),
+                Value::Symbol("unless".into()),
+                Value::List(
+                    vec![Value::Symbol("test".into()), Value::Symbol("body".into())].into(),
+                ),
+                Value::List(
+                    vec![
+                        Value::Symbol("quasiquote".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("if".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("test".into()),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::Nil,
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("body".into()),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        eval_with_macros(defmacro_expr, env.clone(), &macro_reg).unwrap();
 
         // (unless #f 42) should return 42
-        let use_macro = Value::List(vec![
-            Value::Symbol("unless".to_string()),
-            Value::Bool(false),
-            Value::Number(42.0),
-        ]);
+        let use_macro = Value::List(
+            vec![
+                Value::Symbol("unless".into()),
+                Value::Bool(false),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
-        let result = eval_with_macros(use_macro, env.clone(), &mut macro_reg).unwrap();
+        let result = eval_with_macros(use_macro, env.clone(), &macro_reg).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 42.0),
             _ => panic!("Expected Number(42.0)"),
         }
 
         // (unless #t 42) should return nil
-        let use_macro_true = Value::List(vec![
-            Value::Symbol("unless".to_string()),
-            Value::Bool(true),
-            Value::Number(42.0),
-        ]);
+        let use_macro_true = Value::List(
+            vec![
+                Value::Symbol("unless".into()),
+                Value::Bool(true),
+                Value::Number(42.0),
+            ]
+            .into(),
+        );
 
-        let result = eval_with_macros(use_macro_true, env, &mut macro_reg).unwrap();
+        let result = eval_with_macros(use_macro_true, env, &macro_reg).unwrap();
         match result {
             Value::Nil => (),
             _ => panic!("Expected Nil"),
@@ -1904,26 +3470,38 @@ mod tests {
     #[test]
     fn test_nested_quasiquote() {
         let env = Environment::new();
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         env.define("x".to_string(), Value::Number(42.0));
 
         // ``(1 ,x) should return `(1 ,x)
-        let expr = Value::List(vec![
-            Value::Symbol("quasiquote".to_string()),
-            Value::List(vec![
-                Value::Symbol("quasiquote".to_string()),
-                Value::List(vec![
-                    Value::Number(1.0),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("x".to_string()),
-                    ]),
-                ]),
-            ]),
-        ]);
-
-        let result = eval_with_macros(expr, env, &mut macro_reg).unwrap();
+        let expr = Value::List(
+            vec![
+                Value::Symbol("quasiquote".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("quasiquote".into()),
+                        Value::List(
+                            vec![
+                                Value::Number(1.0),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("x".into()),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        let result = eval_with_macros(expr, env, &macro_reg).unwrap();
         // Should return a list containing quasiquote symbol
         match result {
             Value::List(items) => {
@@ -1941,41 +3519,459 @@ mod tests {
     fn test_macro_with_computation() {
         let env = Environment::new();
         crate::builtins::register_builtins(env.clone());
-        let mut macro_reg = MacroRegistry::new();
+        let macro_reg = MacroRegistry::new();
 
         // (defmacro square (x) `(* ,x ,x))
-        let defmacro_expr = Value::List(vec![
-            Value::Symbol("defmacro".to_string()),
-            Value::Symbol("square".to_string()),
-            Value::List(vec![Value::Symbol("x".to_string())]),
-            Value::List(vec![
-                Value::Symbol("quasiquote".to_string()),
-                Value::List(vec![
-                    Value::Symbol("*".to_string()),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("x".to_string()),
-                    ]),
-                    Value::List(vec![
-                        Value::Symbol("unquote".to_string()),
-                        Value::Symbol("x".to_string()),
-                    ]),
-                ]),
-            ]),
-        ]);
-
-        eval_with_macros(defmacro_expr, env.clone(), &mut macro_reg).unwrap();
+        let defmacro_expr = Value::List(
+            vec![
+                Value::Symbol("defmacro".into()),
+                Value::Symbol("square".into()),
+                Value::List(vec![Value::Symbol("x".into())].into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("quasiquote".into()),
+                        Value::List(
+                            vec![
+                                Value::Symbol("*".into()),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("x".into()),
+                                    ]
+                                    .into(),
+                                ),
+                                Value::List(
+                                    vec![
+                                        Value::Symbol("unquote".into()),
+                                        Value::Symbol("x".into()),
+                                    ]
+                                    .into(),
+                                ),
+                            ]
+                            .into(),
+                        ),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+
+        eval_with_macros(defmacro_expr, env.clone(), &macro_reg).unwrap();
 
         // (square 5) should expand to (* 5 5) and evaluate to 25
-        let use_macro = Value::List(vec![
-            Value::Symbol("square".to_string()),
-            Value::Number(5.0),
-        ]);
+        let use_macro =
+            Value::List(vec![Value::Symbol("square".into()), Value::Number(5.0)].into());
 
-        let result = eval_with_macros(use_macro, env, &mut macro_reg).unwrap();
+        let result = eval_with_macros(use_macro, env, &macro_reg).unwrap();
         match result {
             Value::Number(n) => assert_eq!(n, 25.0),
             _ => panic!("Expected Number(25.0)"),
         }
     }
+
+    // ========================================================================
+    // Promise Tests
+    // ========================================================================
+
+    #[test]
+    fn test_delay_does_not_evaluate() {
+        let env = Environment::new();
+
+        // (delay undefined-symbol) succeeds because the body is not evaluated yet
+        let expr = Value::List(
+            vec![
+                Value::Symbol("delay".into()),
+                Value::Symbol("undefined-symbol".into()),
+            ]
+            .into(),
+        );
+
+        let result = eval(expr, env).unwrap();
+        assert!(matches!(result, Value::Promise(_)));
+    }
+
+    #[test]
+    fn test_force_memoizes_result() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        // (delay (+ 1 2))
+        let expr = Value::List(
+            vec![
+                Value::Symbol("delay".into()),
+                Value::List(
+                    vec![
+                        Value::Symbol("+".into()),
+                        Value::Number(1.0),
+                        Value::Number(2.0),
+                    ]
+                    .into(),
+                ),
+            ]
+            .into(),
+        );
+        let promise = eval(expr, env).unwrap();
+
+        let result =
+            crate::builtins::promises::builtin_force(std::slice::from_ref(&promise)).unwrap();
+        assert!(matches!(result, Value::Number(n) if n == 3.0));
+
+        match &promise {
+            Value::Promise(state) => {
+                assert!(
+                    matches!(&*state.lock().unwrap(), Promise::Forced(Value::Number(n)) if *n == 3.0)
+                )
+            }
+            _ => panic!("Expected Promise"),
+        }
+    }
+
+    #[test]
+    fn test_delay_arity() {
+        let env = Environment::new();
+        let expr = Value::List(vec![Value::Symbol("delay".into())].into());
+        assert!(matches!(eval(expr, env), Err(EvalError::ArityError { .. })));
+    }
+
+    // ========================================================================
+    // Loop and set! Tests
+    // ========================================================================
+
+    /// Evaluate each source form in order, returning the last result
+    fn eval_forms(env: &EnvRef, forms: &[&str]) -> Result<Value, EvalError> {
+        let mut result = Ok(Value::Nil);
+        for form in forms {
+            result = eval(parser::parse(form).unwrap(), env.clone());
+        }
+        result
+    }
+
+    #[test]
+    fn test_set_updates_nearest_binding() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(
+            &env,
+            &["(define x 1)", "(let ((y 2)) (set! x (+ x y)))", "x"],
+        );
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 3.0));
+
+        let result = eval_forms(&env, &["(set! undefined-name 1)"]);
+        assert!(matches!(result, Err(EvalError::UndefinedSymbol { .. })));
+    }
+
+    #[test]
+    fn test_while_with_break_and_continue() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(
+            &env,
+            &[
+                "(define i 0)",
+                "(define total 0)",
+                "(while (< i 10) (set! i (+ i 1)) (if (= (% i 2) 0) (continue)) (set! total (+ total i)))",
+                "total",
+            ],
+        );
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 25.0));
+
+        let result = eval_forms(&env, &["(while #t (break 42))"]);
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 42.0));
+    }
+
+    #[test]
+    fn test_dotimes_and_dolist() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(
+            &env,
+            &[
+                "(define sum 0)",
+                "(dotimes (i 5) (set! sum (+ sum i)))",
+                "sum",
+            ],
+        );
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 10.0));
+
+        let result = eval_forms(&env, &["(dolist (x (list 3 -1 4)) (if (< x 0) (break x)))"]);
+        assert!(matches!(result, Ok(Value::Number(n)) if n == -1.0));
+
+        // Each iteration has its own binding, so closures keep their value
+        let result = eval_forms(
+            &env,
+            &[
+                "(define saved nil)",
+                "(dotimes (i 3) (if (= i 1) (set! saved (lambda () i))))",
+                "(saved)",
+            ],
+        );
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 1.0));
+    }
+
+    #[test]
+    fn test_break_outside_loop_is_an_error() {
+        let env = Environment::new();
+        let result = eval_forms(&env, &["(break)"]);
+        assert_eq!(result.unwrap_err().to_string(), "break: not inside a loop");
+        let result = eval_forms(&env, &["(continue)"]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "continue: not inside a loop"
+        );
+    }
+
+    // ========================================================================
+    // Destructuring Tests
+    // ========================================================================
+
+    #[test]
+    fn test_let_destructures_lists_and_maps() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(
+            &env,
+            &[
+                "(define pair '(1 2))",
+                "(let (((x y) pair) ({:keys (a b)} {:a 10 :b 20})) (list x y a b))",
+            ],
+        );
+        assert_eq!(result.unwrap().to_string(), "(1 2 10 20)");
+
+        let result = eval_forms(
+            &env,
+            &["(let (((h &rest t) '(1 2 3)) ({:user (name)} {:user '(\"Ada\")})) (list h t name))"],
+        );
+        assert_eq!(result.unwrap().to_string(), "(1 (2 3) \"Ada\")");
+
+        let result = eval_forms(&env, &["(let (((x y) '(1))) x)"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_lambda_params_destructure() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(&env, &["(map (lambda ((k v)) (+ k v)) '((1 2) (3 4)))"]);
+        assert_eq!(result.unwrap().to_string(), "(3 7)");
+
+        // Pattern names shadow builtins, even though calls to builtins are pre-resolved
+        let result = eval_forms(
+            &env,
+            &[
+                "(define (f (list) {:keys (car)}) (+ list car))",
+                "(f '(1) {:car 2})",
+            ],
+        );
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 3.0));
+
+        let result = eval_forms(&env, &["(lambda (1) 1)"]);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_optional_and_keyword_params() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+        eval_forms(
+            &env,
+            &["(define (req url (method \"GET\") (timeout (* 2 1000))) (list url method timeout))"],
+        )
+        .unwrap();
+
+        for (call, expected) in [
+            ("(req \"a\")", "(\"a\" \"GET\" 2000)"),
+            ("(req \"a\" \"POST\")", "(\"a\" \"POST\" 2000)"),
+            ("(req \"a\" :timeout 5)", "(\"a\" \"GET\" 5)"),
+            (
+                "(req \"a\" :timeout 5 :method \"PUT\")",
+                "(\"a\" \"PUT\" 5)",
+            ),
+            ("((lambda (x (y (+ x 1))) (list x y)) 1)", "(1 2)"),
+            ("(map (lambda (x (y 10)) (+ x y)) '(1 2))", "(11 12)"),
+        ] {
+            assert_eq!(
+                eval_forms(&env, &[call]).unwrap().to_string(),
+                expected,
+                "{}",
+                call
+            );
+        }
+
+        let err = eval_forms(&env, &["(req)"]).unwrap_err();
+        assert_eq!(err.to_string(), "req: expected 1-3 arguments, got 0");
+        assert!(eval_forms(&env, &["(req \"a\" :timeout 1 \"x\")"]).is_err());
+        assert!(eval_forms(&env, &["(req \"a\" \"b\" :method \"c\")"]).is_err());
+        assert!(eval_forms(&env, &["(lambda ((y 1) x) x)"]).is_err());
+    }
+
+    #[test]
+    fn test_case_dispatch() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        for (form, expected) in [
+            (
+                "(case 3 ((1 2) \"low\") ((3 4) \"mid\") (else \"high\"))",
+                "\"mid\"",
+            ),
+            ("(case 'red (red \"stop\") (green \"go\"))", "\"stop\""),
+            ("(case \"3\" (3 'number) (\"3\" 'string))", "string"),
+            ("(case 9 (1 'one))", "nil"),
+            ("(case nil ((nil) 'empty))", "empty"),
+        ] {
+            assert_eq!(
+                eval_forms(&env, &[form]).unwrap().to_string(),
+                expected,
+                "{}",
+                form
+            );
+        }
+
+        // Indexed inside function bodies, with the selected branch as a tail call
+        let result = eval_forms(
+            &env,
+            &[
+                "(define (down n) (case n (0 'done) (else (down (- n 1)))))",
+                "(down 100000)",
+            ],
+        );
+        assert_eq!(result.unwrap().to_string(), "done");
+
+        assert!(eval_forms(&env, &["(case 1 (else 1) (2 3))"]).is_err());
+        assert!(eval_forms(&env, &["(case 1 (((1)) 1))"]).is_err());
+    }
+
+    /// Macros for the tail position tests, as a user would write them
+    const TAIL_MACROS: &[&str] = &[
+        "(defmacro when (test body) `(if ,test ,body nil))",
+        "(defmacro unless (test body) `(if ,test nil ,body))",
+        "(define (cond->if clauses) (if (empty? clauses) nil `(if ,(car (car clauses)) ,(car (cdr (car clauses))) ,(cond->if (cdr clauses)))))",
+        "(defmacro cond (clauses) (cond->if clauses))",
+    ];
+
+    fn eval_with(env: &EnvRef, macro_reg: &MacroRegistry, forms: &[&str]) -> Value {
+        let mut result = Value::Nil;
+        for form in forms {
+            result = eval_with_macros(parser::parse(form).unwrap(), env.clone(), macro_reg)
+                .unwrap_or_else(|e| panic!("{}: {}", form, e));
+        }
+        result
+    }
+
+    #[test]
+    fn test_tail_step_leaves_the_tail_expression() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+        let macro_reg = MacroRegistry::new();
+        eval_with(&env, &macro_reg, TAIL_MACROS);
+
+        for (form, tail) in [
+            ("(if #t (f 1) 2)", "(f 1)"),
+            ("(if #f 1 (g))", "(g)"),
+            ("(case 2 (1 'one) ((2 3) (h 2)))", "(h 2)"),
+            ("(begin 1 2 (k))", "(k)"),
+            ("(let ((x 1)) x (k x))", "(k x)"),
+            ("(-> 1 (f 2))", "(f 1 2)"),
+            ("(->> 1 (f 2))", "(f 2 1)"),
+            ("(when #t (f))", "(f)"),
+            ("(unless #f (g))", "(g)"),
+            ("(cond ((#f 1) (#t (h))))", "(h)"),
+        ] {
+            let expr = expand_macros(parser::parse(form).unwrap(), &macro_reg, &env).unwrap();
+            let Value::List(items) = &expr else {
+                panic!("{} expanded to {}", form, expr);
+            };
+            let special = SpecialForm::of(&items[0]).unwrap();
+            let mut step = tail_step(special, &items[1..], &env, &macro_reg).unwrap();
+            // Macro expansions may nest several tail forms; follow them down
+            while let Step::Tail(Value::List(inner), inner_env) = &step {
+                match SpecialForm::of(&inner[0]) {
+                    Some(next) if !matches!(next, SpecialForm::Quote) => {
+                        step = tail_step(next, &inner[1..], inner_env, &macro_reg).unwrap();
+                    }
+                    _ => break,
+                }
+            }
+            match step {
+                Step::Tail(expr, _) => assert_eq!(expr.to_string(), tail, "{}", form),
+                Step::Done(value) => panic!("{} finished with {}", form, value),
+            }
+        }
+
+        // let hands back its own environment, with the bindings made
+        let expr = parser::parse("(let ((x 1)) (k x))").unwrap();
+        let Value::List(items) = &expr else {
+            unreachable!()
+        };
+        let Step::Tail(_, let_env) =
+            tail_step(SpecialForm::Let, &items[1..], &env, &macro_reg).unwrap()
+        else {
+            panic!("let finished early");
+        };
+        assert!(matches!(let_env.get("x"), Some(Value::Number(n)) if n == 1.0));
+        assert!(env.get("x").is_none());
+
+        let step = tail_step(
+            SpecialForm::If,
+            &[Value::Bool(false), Value::Nil],
+            &env,
+            &macro_reg,
+        );
+        assert!(matches!(step, Ok(Step::Done(Value::Nil))));
+        let step = tail_step(SpecialForm::Begin, &[], &env, &macro_reg);
+        assert!(matches!(step, Ok(Step::Done(Value::Nil))));
+    }
+
+    #[test]
+    fn test_tail_position_survives_deep_loops() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+        let macro_reg = MacroRegistry::new();
+        eval_with(&env, &macro_reg, TAIL_MACROS);
+
+        // Each loop recurses far deeper than the Rust stack would allow
+        for body in [
+            "(if (= n 0) 'done (down (- n 1)))",
+            "(case n (0 'done) (else (down (- n 1))))",
+            "(begin n (if (= n 0) 'done (begin (down (- n 1)))))",
+            "(let ((m (- n 1))) (if (< m 0) 'done (let ((k m)) k (down k))))",
+            "(if (= n 0) 'done (-> n (- 1) (down)))",
+            "(if (= n 0) 'done (->> 1 (- n) (down)))",
+            "(cond (((= n 0) 'done) (#t (down (- n 1)))))",
+            "(if (= n 0) 'done (when #t (down (- n 1))))",
+            "(if (= n 0) 'done (unless #f (down (- n 1))))",
+        ] {
+            let define = format!("(define (down n) {})", body);
+            let result = eval_with(&env, &macro_reg, &[&define, "(down 50000)"]);
+            assert_eq!(result.to_string(), "done", "{}", body);
+        }
+    }
+
+    #[test]
+    fn test_defconstant() {
+        let env = Environment::new();
+        crate::builtins::register_builtins(env.clone());
+
+        let result = eval_forms(&env, &["(defconstant limit 3)", "(* limit 2)"]);
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 6.0));
+
+        for form in [
+            "(set! limit 4)",
+            "(define limit 4)",
+            "(defconstant limit 4)",
+        ] {
+            assert!(eval_forms(&env, &[form]).is_err(), "{}", form);
+        }
+
+        // Shadowing in a local scope is allowed
+        let result = eval_forms(&env, &["(let ((limit 1)) (set! limit 2) limit)"]);
+        assert!(matches!(result, Ok(Value::Number(n)) if n == 2.0));
+        assert!(eval_forms(&env, &["(defconstant 5 1)"]).is_err());
+    }
 }
@@ -0,0 +1,76 @@
+// ABOUTME: Host callbacks for evaluation events: console output, definitions, sandbox I/O, logs and results
+// Lets a UI stream progress while an evaluation runs instead of waiting for its final value
+
+use crate::sandbox::AuditEntry;
+use crate::stdlib::log::LogRecord;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::sync::Arc;
+
+thread_local! {
+    /// Callbacks of the interpreter evaluating on this thread
+    static HANDLERS: RefCell<EventHandlers> = RefCell::new(EventHandlers::default());
+}
+
+/// Called with each chunk of console output, as it is written
+pub type StdoutHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Called with the name of each top-level `define` or `defconstant`, after it is bound
+pub type DefinitionHandler = Arc<dyn Fn(&str) + Send + Sync>;
+
+/// Called with each sandboxed file or network operation, after it is recorded in the audit log
+pub type IoHandler = Arc<dyn Fn(&AuditEntry) + Send + Sync>;
+
+/// Called with each message a script logs with `log:info` and its siblings, if its level is kept
+pub type LogHandler = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+/// Called with the value of each top-level expression that evaluates successfully
+pub type ResultHandler = Arc<dyn Fn(&Value) + Send + Sync>;
+
+/// The callbacks a host registered; events without one are not reported
+#[derive(Clone, Default)]
+pub(crate) struct EventHandlers {
+    pub(crate) stdout: Option<StdoutHandler>,
+    pub(crate) definition: Option<DefinitionHandler>,
+    pub(crate) io: Option<IoHandler>,
+    pub(crate) log: Option<LogHandler>,
+    pub(crate) result: Option<ResultHandler>,
+}
+
+/// Replace the callbacks installed on this thread, returning the previous ones
+pub(crate) fn swap_handlers(handlers: EventHandlers) -> EventHandlers {
+    HANDLERS.with(|slot| slot.replace(handlers))
+}
+
+/// Report console output written by `print`, `println`, `pp` or `help`
+pub(crate) fn stdout(text: &str) {
+    // The callback runs without the slot borrowed, so it may print too
+    if let Some(handler) = HANDLERS.with(|slot| slot.borrow().stdout.clone()) {
+        handler(text);
+    }
+}
+
+/// Report that a top-level definition bound `name`
+pub(crate) fn definition(name: &str) {
+    if let Some(handler) = HANDLERS.with(|slot| slot.borrow().definition.clone()) {
+        handler(name);
+    }
+}
+
+/// Report a sandboxed operation
+pub(crate) fn io(entry: &AuditEntry) {
+    if let Some(handler) = HANDLERS.with(|slot| slot.borrow().io.clone()) {
+        handler(entry);
+    }
+}
+
+/// Report a logged message, returning whether a host callback received it
+pub(crate) fn log(record: &LogRecord) -> bool {
+    match HANDLERS.with(|slot| slot.borrow().log.clone()) {
+        Some(handler) => {
+            handler(record);
+            true
+        }
+        None => false,
+    }
+}
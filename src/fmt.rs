@@ -0,0 +1,360 @@
+// ABOUTME: Source formatter for `lisp-llm-sandbox fmt`: re-indents code and keeps comments
+// Works on tokens rather than parsed values, so comments and line breaks survive formatting
+
+/// Forms whose body is indented two columns past the opening paren, whatever the first line holds
+const BODY_FORMS: &[&str] = &[
+    "define",
+    "define-test",
+    "defmacro",
+    "lambda",
+    "let",
+    "begin",
+    "case",
+    "while",
+    "dotimes",
+    "dolist",
+    "try",
+    "delay",
+    "profile",
+    "time",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Open(char),
+    Close(char),
+    /// Quote, quasiquote, unquote or unquote-splicing, attached to the next token
+    Prefix(&'static str),
+    /// A symbol, number, keyword, boolean or string, exactly as written
+    Atom(String),
+    /// A comment, with the number of spaces that preceded it
+    Comment(String, usize),
+}
+
+/// Reprint Lisp source with canonical indentation and spacing
+///
+/// Only whitespace changes, so the formatted code parses to the same values.
+/// Line breaks are kept where the author put them, except that lines holding
+/// nothing but closing parens are pulled up onto the line before, and runs of
+/// blank lines shrink to one. Each line is then indented by its enclosing
+/// form: the bodies of `define`, `lambda`, `let` and similar forms by two
+/// columns, arguments of other calls under the first argument when it shares
+/// the head's line (else by two columns), and lists without a symbol head and
+/// maps one column past the bracket. Tokens on a line are separated by single
+/// spaces; comments keep the spacing before them and are kept verbatim.
+///
+/// Fails on unbalanced brackets or an unterminated string.
+pub fn format_source(source: &str) -> Result<String, String> {
+    let lines = join_closing_lines(tokenize(source)?);
+
+    let mut out = String::new();
+    let mut stack: Vec<Frame> = Vec::new();
+    let mut blank = false;
+    for (number, line) in lines.iter().enumerate() {
+        if line.is_empty() {
+            blank = !out.is_empty();
+            continue;
+        }
+        if blank {
+            out.push('\n');
+            blank = false;
+        }
+
+        let indent = stack.last().map_or(0, Frame::indent);
+        let mut column = indent;
+        out.extend(std::iter::repeat_n(' ', indent));
+        let mut previous: Option<&Token> = None;
+        for token in line {
+            let spaced = !matches!(
+                (previous, token),
+                (None, _) | (Some(Token::Open(_) | Token::Prefix(_)), _) | (_, Token::Close(_))
+            );
+            if spaced {
+                // Comments after code keep their spacing, so aligned columns of them stay aligned
+                let gap = match token {
+                    Token::Comment(_, gap) => (*gap).max(1),
+                    _ => 1,
+                };
+                out.extend(std::iter::repeat_n(' ', gap));
+                column += gap;
+            }
+
+            // Each token but a closer or a comment begins an element of the enclosing
+            // form, unless a prefix already began it
+            let begins_element = !matches!(token, Token::Close(_) | Token::Comment(..))
+                && !matches!(previous, Some(Token::Prefix(_)));
+            if begins_element {
+                if let Some(frame) = stack.last_mut() {
+                    frame.element(token, number, column);
+                }
+            }
+
+            match token {
+                Token::Open(bracket) => stack.push(Frame::new(*bracket, number, column)),
+                Token::Close(bracket) => match stack.pop() {
+                    Some(frame) if closer(frame.bracket) == *bracket => {}
+                    _ => return Err(format!("unexpected '{}' on line {}", bracket, number + 1)),
+                },
+                _ => {}
+            }
+
+            let text = token.text();
+            out.push_str(text);
+            column = match text.rfind('\n') {
+                Some(newline) => text[newline + 1..].chars().count(),
+                None => column + text.chars().count(),
+            };
+            previous = Some(token);
+        }
+        out.push('\n');
+    }
+
+    match stack.last() {
+        Some(frame) => Err(format!(
+            "unclosed '{}' opened on line {}",
+            frame.bracket,
+            frame.line + 1
+        )),
+        None => Ok(out),
+    }
+}
+
+impl Token {
+    fn text(&self) -> &str {
+        match self {
+            Token::Open(c) | Token::Close(c) => match c {
+                '(' => "(",
+                ')' => ")",
+                '{' => "{",
+                _ => "}",
+            },
+            Token::Prefix(prefix) => prefix,
+            Token::Atom(text) | Token::Comment(text, _) => text,
+        }
+    }
+}
+
+fn closer(bracket: char) -> char {
+    if bracket == '{' {
+        '}'
+    } else {
+        ')'
+    }
+}
+
+/// An open list or map while its lines are being indented
+struct Frame {
+    bracket: char,
+    /// Line and column of the opening bracket
+    line: usize,
+    column: usize,
+    elements: usize,
+    /// Symbol at the head of a list
+    head: Option<String>,
+    /// Column of the second element, when it starts on the opening line
+    first_arg: Option<usize>,
+}
+
+impl Frame {
+    fn new(bracket: char, line: usize, column: usize) -> Self {
+        Frame {
+            bracket,
+            line,
+            column,
+            elements: 0,
+            head: None,
+            first_arg: None,
+        }
+    }
+
+    /// Note an element starting at `column` on `line`
+    fn element(&mut self, token: &Token, line: usize, column: usize) {
+        match (self.elements, token) {
+            (0, Token::Atom(text)) if !text.starts_with(['"', '#']) => {
+                self.head = Some(text.clone())
+            }
+            (1, _) if line == self.line => self.first_arg = Some(column),
+            _ => {}
+        }
+        self.elements += 1;
+    }
+
+    /// Indentation of a line that continues this form
+    fn indent(&self) -> usize {
+        if self.bracket == '{' {
+            return self.column + 1;
+        }
+        match &self.head {
+            Some(head) if BODY_FORMS.contains(&head.as_str()) => self.column + 2,
+            Some(_) => self.first_arg.unwrap_or(self.column + 2),
+            None => self.column + 1,
+        }
+    }
+}
+
+/// Split source into lines of tokens
+fn tokenize(source: &str) -> Result<Vec<Vec<Token>>, String> {
+    let mut lines = vec![Vec::new()];
+    let mut chars = source.chars().peekable();
+    let mut line = 1;
+    let mut gap = 0;
+    while let Some(c) = chars.next() {
+        let token = match c {
+            '\n' => {
+                lines.push(Vec::new());
+                line += 1;
+                gap = 0;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                gap += 1;
+                continue;
+            }
+            '(' | '{' => Token::Open(c),
+            ')' | '}' => Token::Close(c),
+            '\'' => Token::Prefix("'"),
+            '`' => Token::Prefix("`"),
+            ',' if chars.next_if_eq(&'@').is_some() => Token::Prefix(",@"),
+            ',' => Token::Prefix(","),
+            ';' => {
+                let mut text = String::from(';');
+                while let Some(c) = chars.next_if(|c| *c != '\n') {
+                    text.push(c);
+                }
+                Token::Comment(text.trim_end().to_string(), gap)
+            }
+            '"' => string_token(&mut chars, &mut line, String::from('"'))?,
+            '#' if chars.next_if_eq(&'"').is_some() => {
+                string_token(&mut chars, &mut line, String::from("#\""))?
+            }
+            c => {
+                let mut text = String::from(c);
+                while let Some(c) = chars.next_if(|c| !is_delimiter(*c)) {
+                    text.push(c);
+                }
+                Token::Atom(text)
+            }
+        };
+        if let Some(current) = lines.last_mut() {
+            current.push(token);
+        }
+        gap = 0;
+    }
+    Ok(lines)
+}
+
+/// Read the rest of a string whose opening `text` has been consumed, keeping escapes as written
+fn string_token(
+    chars: &mut std::iter::Peekable<std::str::Chars>,
+    line: &mut usize,
+    mut text: String,
+) -> Result<Token, String> {
+    let start = *line;
+    loop {
+        match chars.next() {
+            Some('"') => break,
+            Some('\\') => {
+                text.push('\\');
+                if let Some(escaped) = chars.next() {
+                    text.push(escaped);
+                }
+            }
+            Some(c) => {
+                if c == '\n' {
+                    *line += 1;
+                }
+                text.push(c);
+            }
+            None => return Err(format!("unterminated string on line {}", start)),
+        }
+    }
+    text.push('"');
+    Ok(Token::Atom(text))
+}
+
+fn is_delimiter(c: char) -> bool {
+    c.is_whitespace() || matches!(c, '(' | ')' | '{' | '}' | '"' | ';')
+}
+
+/// Move lines of only closing brackets onto the end of the previous code line
+fn join_closing_lines(lines: Vec<Vec<Token>>) -> Vec<Vec<Token>> {
+    let mut joined: Vec<Vec<Token>> = Vec::new();
+    for line in lines {
+        let only_closers = !line.is_empty() && line.iter().all(|t| matches!(t, Token::Close(_)));
+        if only_closers {
+            // Blank lines between the code and its closers go too
+            let target = joined.iter().rposition(|l| !l.is_empty());
+            if let Some(target) = target {
+                if !matches!(joined[target].last(), Some(Token::Comment(..))) {
+                    joined.truncate(target + 1);
+                    joined[target].extend(line);
+                    continue;
+                }
+            }
+        }
+        joined.push(line);
+    }
+    joined
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reindents_bodies_and_arguments() {
+        let source = "(define (f x)\n(if (> x 0)\n(* x 2)\n      (- x)))\n";
+        assert_eq!(
+            format_source(source).unwrap(),
+            "(define (f x)\n  (if (> x 0)\n      (* x 2)\n      (- x)))\n"
+        );
+        assert_eq!(
+            format_source("(list\n1 2)\n'((a)\nb)\n{:a 1\n:b 2}\n").unwrap(),
+            "(list\n  1 2)\n'((a)\n  b)\n{:a 1\n :b 2}\n"
+        );
+    }
+
+    #[test]
+    fn test_keeps_comments_and_strings() {
+        let source = ";;; Doubles\n(define (double x) ; inline\n   ;; body\n   (*   x 2))\n\n\n\n(print \"a  b\")";
+        assert_eq!(
+            format_source(source).unwrap(),
+            ";;; Doubles\n(define (double x) ; inline\n  ;; body\n  (* x 2))\n\n(print \"a  b\")\n"
+        );
+        assert_eq!(
+            format_source("(print   #\"{(f  x)}  \\\" (\")").unwrap(),
+            "(print #\"{(f  x)}  \\\" (\")\n"
+        );
+    }
+
+    #[test]
+    fn test_pulls_up_dangling_parens() {
+        let source = "(define (f)\n  (g 1\n  )\n)\n(h ; note\n)\n";
+        assert_eq!(
+            format_source(source).unwrap(),
+            "(define (f)\n  (g 1))\n(h ; note\n  )\n"
+        );
+    }
+
+    #[test]
+    fn test_rejects_unbalanced_source() {
+        assert_eq!(
+            format_source("(define x\n  (+ 1 2)").unwrap_err(),
+            "unclosed '(' opened on line 1"
+        );
+        assert_eq!(format_source("x)").unwrap_err(), "unexpected ')' on line 1");
+        assert!(format_source("(print \"oops)").is_err());
+        assert!(format_source("(print #\"oops)").is_err());
+    }
+
+    #[test]
+    fn test_formatting_is_idempotent() {
+        let source = "(define (g xs)\n(map (lambda (x)\n(* x x))\nxs))\n";
+        let once = format_source(source).unwrap();
+        assert_eq!(format_source(&once).unwrap(), once);
+        assert_eq!(
+            crate::parser::parse(&once).unwrap().to_string(),
+            crate::parser::parse(source).unwrap().to_string()
+        );
+    }
+}
@@ -0,0 +1,417 @@
+// ABOUTME: Storage backends behind the sandbox's filesystem operations
+// DirFs uses cap-std directories on disk; MemoryFs keeps files in memory for tests and embedding
+
+use crate::sandbox::{FileStat, SandboxError};
+#[cfg(feature = "native-fs")]
+use cap_std::fs::Dir;
+use std::collections::{BTreeMap, BTreeSet};
+#[cfg(feature = "native-fs")]
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where sandboxed files are stored
+///
+/// Paths passed to a backend have already been checked by the sandbox: they
+/// are relative and contain no `..`. Size limits and auditing are also applied
+/// by the sandbox, so backends only store and retrieve.
+pub trait FsBackend: Send + Sync {
+    /// Read a whole file as UTF-8
+    fn read_file(&self, path: &str) -> Result<String, SandboxError>;
+
+    /// Create or replace a file, creating missing parent directories
+    fn write_file(&self, path: &str, contents: &str) -> Result<(), SandboxError>;
+
+    /// Add to the end of a file, creating it and missing parent directories
+    fn append_file(&self, path: &str, contents: &str) -> Result<(), SandboxError>;
+
+    /// Whether `path` names an existing regular file
+    fn file_exists(&self, path: &str) -> Result<bool, SandboxError>;
+
+    /// Metadata for a file or directory
+    fn file_stat(&self, path: &str) -> Result<FileStat, SandboxError>;
+
+    /// Names of the entries directly inside `dir`
+    fn list_files(&self, dir: &str) -> Result<Vec<String>, SandboxError>;
+}
+
+// ============================================================================
+// On-disk backend
+// ============================================================================
+
+/// Files on disk under one or more allowed directories, opened as cap-std capabilities
+///
+/// Reads search every root in order; writes go to the first writable root.
+/// Only built with the `native-fs` feature.
+#[cfg(feature = "native-fs")]
+pub struct DirFs {
+    /// Each root and whether it may be written
+    roots: Vec<(Dir, bool)>,
+}
+
+#[cfg(feature = "native-fs")]
+impl DirFs {
+    /// Open writable and read-only roots, creating missing writable directories
+    pub fn open(writable: &[PathBuf], read_only: &[PathBuf]) -> Result<Self, SandboxError> {
+        let mut roots = Vec::new();
+
+        for path in writable {
+            // Create directory if it doesn't exist (for output)
+            std::fs::create_dir_all(path).map_err(|e| {
+                SandboxError::IoError(format!("Cannot create {}: {}", path.display(), e))
+            })?;
+            roots.push((Self::open_dir(path)?, true));
+        }
+        for path in read_only {
+            roots.push((Self::open_dir(path)?, false));
+        }
+
+        Ok(DirFs { roots })
+    }
+
+    fn open_dir(path: &Path) -> Result<Dir, SandboxError> {
+        // Open as cap-std Dir (gives us capability-based security)
+        Dir::open_ambient_dir(path, cap_std::ambient_authority())
+            .map_err(|e| SandboxError::IoError(format!("Cannot open {}: {}", path.display(), e)))
+    }
+
+    /// Find which root directory should be used for a path
+    /// For reading: tries all roots
+    /// For writing: uses first writable root
+    fn find_root_for_path(&self, user_path: &str, write_mode: bool) -> Result<&Dir, SandboxError> {
+        // cap-std::Dir will automatically reject .. and absolute paths
+        // This is secure by construction
+
+        if write_mode {
+            return self
+                .roots
+                .iter()
+                .find(|(_, writable)| *writable)
+                .map(|(root, _)| root)
+                .ok_or_else(|| SandboxError::PathNotAllowed(user_path.to_string()));
+        }
+
+        // For reads, try each root to find the file
+        self.roots
+            .iter()
+            .map(|(root, _)| root)
+            .find(|root| root.metadata(user_path).is_ok())
+            // If not found in any root, report the error from the first root
+            .or_else(|| self.roots.first().map(|(root, _)| root))
+            .ok_or_else(|| SandboxError::PathNotAllowed(user_path.to_string()))
+    }
+
+    /// The root writes to `path` go to, with the parent directories of `path` created
+    fn writable_root_with_parent(&self, path: &str) -> Result<&Dir, SandboxError> {
+        let root = self.find_root_for_path(path, true)?;
+
+        if let Some(parent) = Path::new(path).parent() {
+            if !parent.as_os_str().is_empty() {
+                root.create_dir_all(parent).map_err(|e| {
+                    SandboxError::IoError(format!("Cannot create {}: {}", parent.display(), e))
+                })?;
+            }
+        }
+        Ok(root)
+    }
+}
+
+#[cfg(feature = "native-fs")]
+fn stat_error(path: &str, e: std::io::Error) -> SandboxError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        SandboxError::FileNotFound(path.to_string())
+    } else {
+        SandboxError::IoError(format!("Cannot stat {}: {}", path, e))
+    }
+}
+
+#[cfg(feature = "native-fs")]
+impl FsBackend for DirFs {
+    fn read_file(&self, path: &str) -> Result<String, SandboxError> {
+        let root = self.find_root_for_path(path, false)?;
+
+        // cap-std::Dir::read_to_string provides safe access
+        root.read_to_string(path).map_err(|e| {
+            if e.kind() == std::io::ErrorKind::NotFound {
+                SandboxError::FileNotFound(path.to_string())
+            } else {
+                SandboxError::IoError(format!("Cannot read {}: {}", path, e))
+            }
+        })
+    }
+
+    fn write_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        let root = self.writable_root_with_parent(path)?;
+
+        // cap-std::Dir::write provides safe access
+        root.write(path, contents)
+            .map_err(|e| SandboxError::IoError(format!("Cannot write {}: {}", path, e)))
+    }
+
+    fn append_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        use std::io::Write;
+
+        let root = self.writable_root_with_parent(path)?;
+        let mut options = cap_std::fs::OpenOptions::new();
+        options.append(true).create(true);
+        root.open_with(path, &options)
+            .and_then(|mut file| file.write_all(contents.as_bytes()))
+            .map_err(|e| SandboxError::IoError(format!("Cannot append to {}: {}", path, e)))
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool, SandboxError> {
+        let root = self.find_root_for_path(path, false)?;
+
+        match root.metadata(path) {
+            Ok(metadata) => Ok(metadata.is_file()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(SandboxError::IoError(format!(
+                "Cannot check {}: {}",
+                path, e
+            ))),
+        }
+    }
+
+    fn file_stat(&self, path: &str) -> Result<FileStat, SandboxError> {
+        let root = self.find_root_for_path(path, false)?;
+
+        root.metadata(path)
+            .map(|metadata| {
+                let file_type = if metadata.is_dir() {
+                    "directory".to_string()
+                } else if metadata.is_symlink() {
+                    "symlink".to_string()
+                } else {
+                    "file".to_string()
+                };
+
+                // Timestamps: use approximate values since cap_std times don't directly convert
+                // to Unix timestamps. We'll store them as relative times from current moment.
+                let modified = 0.0; // Would need more complex conversion
+                let accessed = 0.0;
+                let created = 0.0;
+
+                let readonly = metadata.permissions().readonly();
+
+                FileStat {
+                    size: metadata.len(),
+                    file_type,
+                    modified,
+                    accessed,
+                    created,
+                    readonly,
+                }
+            })
+            .map_err(|e| stat_error(path, e))
+    }
+
+    fn list_files(&self, dir: &str) -> Result<Vec<String>, SandboxError> {
+        let root = self.find_root_for_path(dir, false)?;
+
+        root.read_dir(dir)
+            .map_err(|e| SandboxError::IoError(format!("Cannot list {}: {}", dir, e)))
+            .and_then(|entries| {
+                entries
+                    .map(|entry| {
+                        entry
+                            .map_err(|e| SandboxError::IoError(e.to_string()))
+                            .and_then(|e| {
+                                e.file_name()
+                                    .to_str()
+                                    .map(|s| s.to_string())
+                                    .ok_or_else(|| {
+                                        SandboxError::IoError(
+                                            "Invalid UTF-8 in filename".to_string(),
+                                        )
+                                    })
+                            })
+                    })
+                    .collect()
+            })
+    }
+}
+
+// ============================================================================
+// In-memory backend
+// ============================================================================
+
+/// Files kept in memory, so tests and embedders never touch the disk
+///
+/// Directories exist implicitly: `a/b.txt` makes `a` a directory, and the
+/// root (`.` or the empty path) always exists.
+// Used by embedders and tests rather than the CLI
+#[allow(dead_code)]
+#[derive(Debug, Default)]
+pub struct MemoryFs {
+    files: Mutex<BTreeMap<String, String>>,
+}
+
+#[allow(dead_code)]
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A filesystem holding the given `(path, contents)` files
+    pub fn with_files<P, C>(files: impl IntoIterator<Item = (P, C)>) -> Self
+    where
+        P: AsRef<str>,
+        C: Into<String>,
+    {
+        let fs = Self::new();
+        {
+            let mut map = fs.lock();
+            for (path, contents) in files {
+                map.insert(normalize(path.as_ref()), contents.into());
+            }
+        }
+        fs
+    }
+
+    /// Paths of every stored file, sorted
+    pub fn paths(&self) -> Vec<String> {
+        self.lock().keys().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, String>> {
+        self.files.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_dir(files: &BTreeMap<String, String>, path: &str) -> bool {
+        path.is_empty() || {
+            let prefix = format!("{}/", path);
+            files
+                .range(prefix.clone()..)
+                .next()
+                .is_some_and(|(key, _)| key.starts_with(&prefix))
+        }
+    }
+}
+
+/// `./a//b.txt` and `a/b.txt` name the same file
+#[allow(dead_code)]
+fn normalize(path: &str) -> String {
+    path.split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+impl FsBackend for MemoryFs {
+    fn read_file(&self, path: &str) -> Result<String, SandboxError> {
+        self.lock()
+            .get(&normalize(path))
+            .cloned()
+            .ok_or_else(|| SandboxError::FileNotFound(path.to_string()))
+    }
+
+    fn write_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        let key = normalize(path);
+        let mut files = self.lock();
+        if key.is_empty() || Self::is_dir(&files, &key) {
+            return Err(SandboxError::IoError(format!(
+                "Cannot write {}: is a directory",
+                path
+            )));
+        }
+        files.insert(key, contents.to_string());
+        Ok(())
+    }
+
+    fn append_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        let key = normalize(path);
+        let mut files = self.lock();
+        if key.is_empty() || Self::is_dir(&files, &key) {
+            return Err(SandboxError::IoError(format!(
+                "Cannot append to {}: is a directory",
+                path
+            )));
+        }
+        files.entry(key).or_default().push_str(contents);
+        Ok(())
+    }
+
+    fn file_exists(&self, path: &str) -> Result<bool, SandboxError> {
+        Ok(self.lock().contains_key(&normalize(path)))
+    }
+
+    fn file_stat(&self, path: &str) -> Result<FileStat, SandboxError> {
+        let key = normalize(path);
+        let files = self.lock();
+        let (size, file_type) = match files.get(&key) {
+            Some(contents) => (contents.len() as u64, "file"),
+            None if Self::is_dir(&files, &key) => (0, "directory"),
+            None => return Err(SandboxError::FileNotFound(path.to_string())),
+        };
+        Ok(FileStat {
+            size,
+            file_type: file_type.to_string(),
+            modified: 0.0,
+            accessed: 0.0,
+            created: 0.0,
+            readonly: false,
+        })
+    }
+
+    fn list_files(&self, dir: &str) -> Result<Vec<String>, SandboxError> {
+        let key = normalize(dir);
+        let files = self.lock();
+        if !Self::is_dir(&files, &key) {
+            return Err(SandboxError::IoError(format!(
+                "Cannot list {}: not a directory",
+                dir
+            )));
+        }
+
+        let prefix = if key.is_empty() {
+            String::new()
+        } else {
+            format!("{}/", key)
+        };
+        let names: BTreeSet<String> = files
+            .keys()
+            .filter_map(|path| path.strip_prefix(&prefix))
+            .map(|rest| rest.split('/').next().unwrap_or(rest).to_string())
+            .collect();
+        Ok(names.into_iter().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_memory_fs_read_write() {
+        let fs = MemoryFs::with_files([("notes/a.txt", "alpha")]);
+        assert_eq!(fs.read_file("./notes/a.txt").unwrap(), "alpha");
+        assert!(matches!(
+            fs.read_file("missing.txt"),
+            Err(SandboxError::FileNotFound(_))
+        ));
+
+        fs.write_file("notes/b.txt", "beta").unwrap();
+        assert!(fs.file_exists("notes/b.txt").unwrap());
+        assert!(!fs.file_exists("notes").unwrap());
+        assert!(fs.write_file("notes", "x").is_err());
+        assert_eq!(fs.paths(), vec!["notes/a.txt", "notes/b.txt"]);
+
+        fs.append_file("notes/b.txt", "!").unwrap();
+        fs.append_file("notes/c.txt", "gamma").unwrap();
+        assert_eq!(fs.read_file("notes/b.txt").unwrap(), "beta!");
+        assert_eq!(fs.read_file("notes/c.txt").unwrap(), "gamma");
+        assert!(fs.append_file("notes", "x").is_err());
+    }
+
+    #[test]
+    fn test_memory_fs_directories() {
+        let fs = MemoryFs::with_files([("top.txt", "1"), ("d/x.txt", "22"), ("d/sub/y.txt", "3")]);
+
+        assert_eq!(fs.list_files(".").unwrap(), vec!["d", "top.txt"]);
+        assert_eq!(fs.list_files("d").unwrap(), vec!["sub", "x.txt"]);
+        assert!(fs.list_files("nope").is_err());
+
+        assert_eq!(fs.file_stat("d/x.txt").unwrap().size, 2);
+        assert_eq!(fs.file_stat("d/sub").unwrap().file_type, "directory");
+        assert!(fs.file_stat("d/zzz").is_err());
+    }
+}
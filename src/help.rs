@@ -1,18 +1,19 @@
 // ABOUTME: Help and documentation system for the Lisp interpreter
 // Provides first-class documentation for built-in and user-defined functions
-// Renders markdown documentation with syntax highlighting using termimad
+// Renders markdown documentation with syntax highlighting using termimad (CLI builds)
 
+use serde::Serialize;
 use std::cell::RefCell;
 use std::collections::HashMap;
-use std::rc::Rc;
+#[cfg(feature = "cli")]
 use termimad::MadSkin;
 
 // Forward declarations
-use crate::env::Environment;
+use crate::env::{EnvRef, Environment};
 use crate::value::Value;
 
 /// A help entry for a function
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct HelpEntry {
     pub name: String,
     pub signature: String,
@@ -24,6 +25,8 @@ pub struct HelpEntry {
 /// Registry for all function documentation
 pub struct HelpRegistry {
     entries: HashMap<String, HelpEntry>,
+    /// Capability module of each function that reaches outside the interpreter
+    capabilities: HashMap<String, &'static str>,
 }
 
 impl HelpRegistry {
@@ -31,6 +34,7 @@ impl HelpRegistry {
     pub fn new() -> Self {
         Self {
             entries: HashMap::new(),
+            capabilities: HashMap::new(),
         }
     }
 
@@ -39,11 +43,42 @@ impl HelpRegistry {
         self.entries.insert(entry.name.clone(), entry);
     }
 
+    /// Remove a help entry and its capability tag, returning the entry
+    pub fn remove(&mut self, name: &str) -> Option<HelpEntry> {
+        self.capabilities.remove(name);
+        self.entries.remove(name)
+    }
+
+    /// Tag a function as part of a capability module
+    pub fn set_capability(&mut self, name: &str, capability: &'static str) {
+        self.capabilities.insert(name.to_string(), capability);
+    }
+
+    /// The capability module a function was tagged with, if any
+    #[allow(dead_code)]
+    pub fn capability(&self, name: &str) -> Option<&'static str> {
+        self.capabilities.get(name).copied()
+    }
+
+    /// Names of every function tagged with `capability`
+    pub fn in_capability(&self, capability: &str) -> Vec<String> {
+        self.capabilities
+            .iter()
+            .filter(|(_, tag)| **tag == capability)
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
     /// Get a help entry by name
     pub fn get(&self, name: &str) -> Option<HelpEntry> {
         self.entries.get(name).cloned()
     }
 
+    /// Iterate over all entries in no particular order
+    pub fn entries(&self) -> impl Iterator<Item = &HelpEntry> {
+        self.entries.values()
+    }
+
     /// Get all entries organized by category
     pub fn by_category(&self) -> HashMap<String, Vec<HelpEntry>> {
         let mut by_cat: HashMap<String, Vec<HelpEntry>> = HashMap::new();
@@ -67,6 +102,169 @@ impl HelpRegistry {
         names.sort();
         names
     }
+
+    /// Entries whose name, signature or description contains `query` (case-insensitive), sorted by name
+    pub fn search(&self, query: &str) -> Vec<HelpEntry> {
+        let query = query.to_lowercase();
+        let mut found: Vec<_> = self
+            .entries
+            .values()
+            .filter(|e| {
+                e.name.to_lowercase().contains(&query)
+                    || e.signature.to_lowercase().contains(&query)
+                    || e.description.to_lowercase().contains(&query)
+            })
+            .cloned()
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        found
+    }
+
+    /// Entries in a category (case-insensitive), sorted by name, or None if no entry has it
+    pub fn category(&self, category: &str) -> Option<Vec<HelpEntry>> {
+        let mut found: Vec<_> = self
+            .entries
+            .values()
+            .filter(|e| e.category.eq_ignore_ascii_case(category))
+            .cloned()
+            .collect();
+        if found.is_empty() {
+            return None;
+        }
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        Some(found)
+    }
+
+    /// Names that fuzzily match `pattern`, best matches first
+    ///
+    /// A name matches if it contains the pattern's characters in order. Exact
+    /// matches rank first, then prefixes, then substrings, then scattered
+    /// matches; ties go to the shorter name.
+    pub fn apropos(&self, pattern: &str) -> Vec<String> {
+        let pattern = pattern.to_lowercase();
+        let mut scored: Vec<_> = self
+            .entries
+            .keys()
+            .filter_map(|name| fuzzy_rank(&name.to_lowercase(), &pattern).map(|r| (r, name)))
+            .collect();
+        scored.sort_by(|(ra, a), (rb, b)| ra.cmp(rb).then(a.len().cmp(&b.len())).then(a.cmp(b)));
+        scored.into_iter().map(|(_, name)| name.clone()).collect()
+    }
+
+    /// All entries as pretty-printed JSON, grouped by category in quick-reference order
+    ///
+    /// The output is an array of `{"category": ..., "entries": [...]}` objects, each
+    /// entry carrying its name, signature, description, examples, related names and
+    /// category.
+    pub fn export_json(&self) -> String {
+        #[derive(Serialize)]
+        struct Category<'a> {
+            category: &'a str,
+            entries: &'a [HelpEntry],
+        }
+
+        let by_cat = self.by_category();
+        let categories: Vec<_> = ordered_categories(&by_cat)
+            .into_iter()
+            .map(|category| Category {
+                category,
+                entries: &by_cat[category],
+            })
+            .collect();
+        serde_json::to_string_pretty(&categories).expect("help entries serialize to JSON")
+    }
+
+    /// All entries as a Markdown reference document, grouped by category
+    pub fn export_markdown(&self) -> String {
+        let by_cat = self.by_category();
+        let mut output = String::from("# Function Reference\n");
+
+        for category in ordered_categories(&by_cat) {
+            output.push_str(&format!("\n## {}\n", category));
+            for entry in &by_cat[category] {
+                output.push_str(&format!("\n### {}\n\n", entry.name));
+                output.push_str(&format!("`{}`\n\n", entry.signature));
+                if !entry.description.is_empty() {
+                    output.push_str(entry.description.trim_end());
+                    output.push_str("\n\n");
+                }
+                if !entry.examples.is_empty() {
+                    output.push_str("```lisp\n");
+                    for line in entry.examples.iter().flat_map(|e| e.lines()) {
+                        output.push_str(line.trim());
+                        output.push('\n');
+                    }
+                    output.push_str("```\n\n");
+                }
+                if !entry.related.is_empty() {
+                    output.push_str(&format!("See also: {}\n\n", entry.related.join(", ")));
+                }
+            }
+        }
+
+        output.truncate(output.trim_end().len());
+        output.push('\n');
+        output
+    }
+}
+
+/// Categories listed first, in this order, by the quick reference and exports
+const PREFERRED_CATEGORY_ORDER: &[&str] = &[
+    "Special Forms",
+    "Arithmetic",
+    "Comparison",
+    "Logic",
+    "Type predicates",
+    "List operations",
+    "Higher-order functions",
+    "String manipulation",
+    "Maps",
+    "Units",
+    "Testing",
+    "Console I/O",
+    "Filesystem I/O",
+    "Sessions",
+    "Bindings",
+    "Network I/O",
+    "Audit",
+    "Error handling",
+    "Promises",
+    "Concurrency",
+    "Help system",
+    "Introspection",
+];
+
+/// Categories present in `by_cat`: preferred ones first, then the rest alphabetically
+fn ordered_categories(by_cat: &HashMap<String, Vec<HelpEntry>>) -> Vec<&str> {
+    // Registered categories differ in capitalisation ("Special forms"), so compare loosely
+    let rank = |cat: &str| {
+        PREFERRED_CATEGORY_ORDER
+            .iter()
+            .position(|preferred| preferred.eq_ignore_ascii_case(cat))
+    };
+
+    let mut categories: Vec<&str> = by_cat.keys().map(|s| s.as_str()).collect();
+    categories.sort_by(|a, b| match (rank(a), rank(b)) {
+        (Some(ra), Some(rb)) => ra.cmp(&rb).then(a.cmp(b)),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => a.cmp(b),
+    });
+    categories
+}
+
+/// Rank how well `name` matches `pattern` (lower is better), or None if it does not
+fn fuzzy_rank(name: &str, pattern: &str) -> Option<u8> {
+    if name == pattern {
+        Some(0)
+    } else if name.starts_with(pattern) {
+        Some(1)
+    } else if name.contains(pattern) {
+        Some(2)
+    } else {
+        let mut chars = name.chars();
+        pattern.chars().all(|p| chars.any(|c| c == p)).then_some(3)
+    }
 }
 
 impl Default for HelpRegistry {
@@ -75,19 +273,101 @@ impl Default for HelpRegistry {
     }
 }
 
+/// Number of single-character insertions, deletions and substitutions turning `a` into `b`
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// The known name closest to a misspelled `name`: bound in `env` or documented in
+/// the help registry, at most a third of its length away
+///
+/// Names shorter than three characters get no suggestion: a single edit turns
+/// `x` into `%`. Ties go to the alphabetically first name, so the hint is
+/// stable between runs.
+pub fn did_you_mean(name: &str, env: &Environment) -> Option<String> {
+    let limit = name.chars().count() / 3;
+    let mut candidates: Vec<String> = env
+        .visible_names()
+        .into_iter()
+        .map(|symbol| symbol.to_string())
+        .collect();
+    candidates.extend(HELP_REGISTRY.with(|reg| reg.borrow().all_names()));
+    candidates
+        .into_iter()
+        .filter(|candidate| candidate != name)
+        .map(|candidate| (edit_distance(name, &candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
 // Thread-local help registry
 thread_local! {
     static HELP_REGISTRY: RefCell<HelpRegistry> = RefCell::new(HelpRegistry::new());
-    static CURRENT_ENV: RefCell<Option<Rc<Environment>>> = const { RefCell::new(None) };
+    static CURRENT_ENV: RefCell<Option<EnvRef>> = const { RefCell::new(None) };
 }
 
 /// Set the current environment for help lookup (needed for user-defined functions)
-pub fn set_current_env(env: Option<Rc<Environment>>) {
+#[allow(dead_code)]
+pub fn set_current_env(env: Option<EnvRef>) {
     CURRENT_ENV.with(|e| {
         *e.borrow_mut() = env;
     });
 }
 
+/// The global environment of the interpreter running on this thread, if any
+pub(crate) fn current_env() -> Option<EnvRef> {
+    CURRENT_ENV.with(|e| e.borrow().clone())
+}
+
+/// Replace this thread's help registry and lookup environment, returning the previous ones
+pub(crate) fn swap_state(
+    registry: HelpRegistry,
+    env: Option<EnvRef>,
+) -> (HelpRegistry, Option<EnvRef>) {
+    let registry = HELP_REGISTRY.with(|reg| std::mem::replace(&mut *reg.borrow_mut(), registry));
+    let env = CURRENT_ENV.with(|e| std::mem::replace(&mut *e.borrow_mut(), env));
+    (registry, env)
+}
+
+/// Build a call signature such as `(when condition body)` for a macro
+pub fn macro_signature(name: &str, params: &[crate::symbol::Symbol]) -> String {
+    let mut sig = format!("({}", name);
+    for param in params {
+        sig.push(' ');
+        sig.push_str(param);
+    }
+    sig.push(')');
+    sig
+}
+
+/// Get help for a user-defined macro from the macro registry of the running evaluation
+fn get_macro_help(name: &str) -> Option<HelpEntry> {
+    let def = crate::eval::active_macros()?.get(name)?;
+    // Prefer curated documentation over a bare signature for undocumented macros
+    if def.docstring.is_none() && HELP_REGISTRY.with(|reg| reg.borrow().get(name).is_some()) {
+        return None;
+    }
+    Some(HelpEntry {
+        name: name.to_string(),
+        signature: macro_signature(name, &def.params),
+        description: def.docstring.unwrap_or_else(|| "(macro)".to_string()),
+        examples: Vec::new(),
+        related: Vec::new(),
+        category: "Macro".to_string(),
+    })
+}
+
 /// Get help for a Lisp-defined function from the environment
 fn get_lisp_function_help(name: &str) -> Option<HelpEntry> {
     CURRENT_ENV.with(|env_ref| {
@@ -96,13 +376,20 @@ fn get_lisp_function_help(name: &str) -> Option<HelpEntry> {
             if let Some(val) = env.get(name) {
                 match val {
                     Value::Lambda {
-                        params, docstring, ..
+                        params,
+                        defaults,
+                        docstring,
+                        ..
                     } => {
-                        // Build signature from parameters
+                        // Build signature from parameters, showing optional ones with their defaults
                         let mut sig = format!("({}", name);
-                        for param in &params {
+                        let required = params.len() - defaults.len();
+                        for (i, param) in params.iter().enumerate() {
                             sig.push(' ');
-                            sig.push_str(param);
+                            match i.checked_sub(required) {
+                                Some(d) => sig.push_str(&format!("({} {})", param, defaults[d])),
+                                None => sig.push_str(param),
+                            }
                         }
                         sig.push(')');
 
@@ -116,17 +403,9 @@ fn get_lisp_function_help(name: &str) -> Option<HelpEntry> {
                         });
                     }
                     Value::Macro { params, .. } => {
-                        // Build signature from parameters
-                        let mut sig = format!("({}", name);
-                        for param in &params {
-                            sig.push(' ');
-                            sig.push_str(param);
-                        }
-                        sig.push(')');
-
                         return Some(HelpEntry {
                             name: name.to_string(),
-                            signature: sig,
+                            signature: macro_signature(name, &params),
                             description: "(macro)".to_string(),
                             examples: Vec::new(),
                             related: Vec::new(),
@@ -148,8 +427,23 @@ pub fn register_help(entry: HelpEntry) {
     });
 }
 
-/// Get a help entry by name (checks environment first for shadowing, then registry)
+/// Tag a function in the global registry as part of a capability module
+///
+/// Every function that touches the sandbox is tagged, so a policy that turns
+/// the module off removes it (see [`crate::config::CAPABILITY_MODULES`]).
+pub fn register_capability(name: &str, capability: &'static str) {
+    HELP_REGISTRY.with(|reg| {
+        reg.borrow_mut().set_capability(name, capability);
+    });
+}
+
+/// Get a help entry by name (checks macros and the environment first for shadowing, then registry)
 pub fn get_help(name: &str) -> Option<HelpEntry> {
+    // Macros expand before function lookup, so they shadow everything else
+    if let Some(entry) = get_macro_help(name) {
+        return Some(entry);
+    }
+
     // Try environment first to handle shadowing (user-defined functions override stdlib)
     if let Some(entry) = get_lisp_function_help(name) {
         return Some(entry);
@@ -170,9 +464,35 @@ pub fn all_names() -> Vec<String> {
     HELP_REGISTRY.with(|reg| reg.borrow().all_names())
 }
 
+/// Entries matching a substring of their name, signature or description
+pub fn search(query: &str) -> Vec<HelpEntry> {
+    HELP_REGISTRY.with(|reg| reg.borrow().search(query))
+}
+
+/// Entries in a category, or None if the category is unknown
+pub fn category(category: &str) -> Option<Vec<HelpEntry>> {
+    HELP_REGISTRY.with(|reg| reg.borrow().category(category))
+}
+
+/// Names fuzzily matching a pattern, best matches first
+pub fn apropos(pattern: &str) -> Vec<String> {
+    HELP_REGISTRY.with(|reg| reg.borrow().apropos(pattern))
+}
+
+/// Export this thread's help registry as JSON (see [`HelpRegistry::export_json`])
+#[allow(dead_code)]
+pub fn export_json() -> String {
+    HELP_REGISTRY.with(|reg| reg.borrow().export_json())
+}
+
+/// Export this thread's help registry as Markdown (see [`HelpRegistry::export_markdown`])
+#[allow(dead_code)]
+pub fn export_markdown() -> String {
+    HELP_REGISTRY.with(|reg| reg.borrow().export_markdown())
+}
+
 /// Format a single help entry for display with markdown rendering and syntax highlighting
 pub fn format_help_entry(entry: &HelpEntry) -> String {
-    let skin = MadSkin::default();
     let mut output = String::new();
 
     // Header with name and category
@@ -204,8 +524,19 @@ pub fn format_help_entry(entry: &HelpEntry) -> String {
         output.push_str("\n\n");
     }
 
-    // Render markdown with termimad
-    skin.term_text(&output).to_string()
+    render_markdown(output)
+}
+
+/// Render markdown for a terminal with termimad
+#[cfg(feature = "cli")]
+fn render_markdown(markdown: String) -> String {
+    MadSkin::default().term_text(&markdown).to_string()
+}
+
+/// Without the terminal renderer (library and browser builds), help is plain markdown
+#[cfg(not(feature = "cli"))]
+fn render_markdown(markdown: String) -> String {
+    markdown
 }
 
 /// Format quick reference showing all functions
@@ -218,43 +549,10 @@ pub fn format_quick_reference() -> String {
     output.push_str(&format!("Available Functions ({} total)\n", total));
     output.push_str("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n\n");
 
-    // Get all categories and sort them, with preferred categories first
-    let preferred_order = vec![
-        "Special Forms",
-        "Arithmetic",
-        "Comparison",
-        "Logic",
-        "Type predicates",
-        "List operations",
-        "String manipulation",
-        "Maps",
-        "Testing",
-        "Console I/O",
-        "Filesystem I/O",
-        "Network I/O",
-        "Error handling",
-        "Help system",
-    ];
-
-    // Add any categories not in preferred list (sorted alphabetically)
-    let mut other_categories: Vec<&str> = by_cat
-        .keys()
-        .filter(|cat| !preferred_order.contains(&cat.as_str()))
-        .map(|s| s.as_str())
-        .collect();
-    other_categories.sort();
-
-    let all_categories = preferred_order
-        .into_iter()
-        .chain(other_categories)
-        .collect::<Vec<_>>();
-
-    for category in all_categories {
-        if let Some(entries) = by_cat.get(category) {
-            let names: Vec<&str> = entries.iter().map(|e| e.name.as_str()).collect();
-            output.push_str(&format!("{} ({})\n", category, names.len()));
-            output.push_str(&format!("  {}\n\n", names.join(", ")));
-        }
+    for category in ordered_categories(&by_cat) {
+        let names: Vec<&str> = by_cat[category].iter().map(|e| e.name.as_str()).collect();
+        output.push_str(&format!("{} ({})\n", category, names.len()));
+        output.push_str(&format!("  {}\n\n", names.join(", ")));
     }
 
     output.push_str("Type (help 'function-name) for detailed help.\n");
@@ -308,6 +606,62 @@ mod tests {
         assert_eq!(by_cat["Arithmetic"].len(), 2);
     }
 
+    fn entry(name: &str, description: &str, category: &str) -> HelpEntry {
+        HelpEntry {
+            name: name.to_string(),
+            signature: format!("({} x)", name),
+            description: description.to_string(),
+            examples: vec![],
+            related: vec![],
+            category: category.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_help_registry_search() {
+        let mut registry = HelpRegistry::new();
+        registry.register(entry("map", "Apply a function to each element", "Lists"));
+        registry.register(entry(
+            "filter",
+            "Keep elements matching a predicate",
+            "Lists",
+        ));
+        registry.register(entry("+", "Add numbers", "Arithmetic"));
+
+        let names: Vec<_> = registry
+            .search("ELEMENT")
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["filter", "map"]);
+        assert!(registry.search("nothing like this").is_empty());
+    }
+
+    #[test]
+    fn test_help_registry_category_ignores_case() {
+        let mut registry = HelpRegistry::new();
+        registry.register(entry("map", "", "List operations"));
+        registry.register(entry("+", "", "Arithmetic"));
+
+        let found = registry.category("list OPERATIONS").unwrap();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].name, "map");
+        assert!(registry.category("Unknown").is_none());
+    }
+
+    #[test]
+    fn test_help_registry_apropos_ranks_matches() {
+        let mut registry = HelpRegistry::new();
+        for name in ["map", "map-get", "hash-map", "make-pair", "filter"] {
+            registry.register(entry(name, "", "Test"));
+        }
+
+        assert_eq!(
+            registry.apropos("map"),
+            vec!["map", "map-get", "hash-map", "make-pair"]
+        );
+    }
+
     #[test]
     fn test_format_help_entry() {
         let entry = HelpEntry {
@@ -329,20 +683,22 @@ mod tests {
     #[serial_test::serial]
     fn test_user_defined_function_shadows_stdlib_help() {
         use crate::env::Environment;
-        use std::rc::Rc;
+        use std::sync::Arc;
 
         // Create an environment and define a user function
-        let env = Rc::new(Environment::new());
+        let env = Arc::new(Environment::new());
         let user_sum = Value::Lambda {
-            params: vec!["x".to_string(), "y".to_string()],
-            body: Box::new(Value::Symbol("+".to_string())),
-            env: Rc::clone(&env),
+            params: vec!["x".into(), "y".into()],
+            defaults: vec![],
+            body: Arc::new(Value::Symbol("+".into())),
+            env: Arc::clone(&env),
             docstring: Some("Add two numbers together".to_string()),
+            source: None,
         };
         env.define("sum".to_string(), user_sum);
 
         // Set the current environment for help lookup
-        set_current_env(Some(Rc::clone(&env)));
+        set_current_env(Some(Arc::clone(&env)));
 
         // Get help should return the user-defined version, not stdlib
         let help = get_help("sum");
@@ -356,4 +712,30 @@ mod tests {
         // Clean up
         set_current_env(None);
     }
+
+    #[test]
+    fn test_did_you_mean_finds_close_names() {
+        assert_eq!(edit_distance("strng-length", "string-length"), 1);
+        assert_eq!(edit_distance("car", "cdr"), 1);
+        assert_eq!(edit_distance("", "abc"), 3);
+
+        let env = Environment::new();
+        env.define("string-length", Value::Nil);
+        env.define("string-lower", Value::Nil);
+        let local = Environment::with_parent(env);
+        local.define("counter", Value::Nil);
+
+        assert_eq!(
+            did_you_mean("strng-length", &local).as_deref(),
+            Some("string-length")
+        );
+        assert_eq!(did_you_mean("countr", &local).as_deref(), Some("counter"));
+        assert_eq!(did_you_mean("zzzzzz", &local), None);
+
+        local.define("%", Value::Nil);
+        local.define("lst", Value::Nil);
+        assert_eq!(did_you_mean("x", &local), None);
+        assert_eq!(did_you_mean("ls", &local), None);
+        assert_eq!(did_you_mean("lsp", &local).as_deref(), Some("lst"));
+    }
 }
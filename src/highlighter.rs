@@ -3,7 +3,10 @@
 // for Lisp syntax elements while preserving display width
 // Also provides output highlighting for pretty-printed values
 
-use crate::value::Value;
+use crate::builtins::builtin_names;
+use crate::interpreter::Interpreter;
+use crate::pretty::{is_truncated, pretty_print_limited};
+use crate::value::{write_value, PrintLimits, PrintStyle, Value};
 use rustyline::completion::Completer;
 use rustyline::highlight::{CmdKind, Highlighter};
 use rustyline::hint::Hinter;
@@ -12,30 +15,140 @@ use rustyline::Helper;
 use std::borrow::Cow;
 use std::collections::HashSet;
 
-// ANSI color codes (using 3-bit/4-bit colors for maximum terminal compatibility)
-const COLOR_RESET: &str = "\x1b[0m";
-const COLOR_PARENS: &str = "\x1b[1;34m"; // Bold blue
-const COLOR_SPECIAL_FORM: &str = "\x1b[1;35m"; // Bold magenta
-const COLOR_BUILTIN: &str = "\x1b[36m"; // Cyan
-const COLOR_NUMBER: &str = "\x1b[33m"; // Yellow
-const COLOR_STRING: &str = "\x1b[32m"; // Green
-const COLOR_BOOLEAN: &str = "\x1b[33m"; // Yellow
-const COLOR_COMMENT: &str = "\x1b[90m"; // Bright black (gray)
-const COLOR_QUOTE: &str = "\x1b[1;33m"; // Bold yellow
+/// ANSI escape codes for each kind of token
+///
+/// Uses 3-bit/4-bit colors for maximum terminal compatibility. [`Theme::NONE`]
+/// has empty codes, so highlighting with it returns the text unchanged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub parens: &'static str,
+    pub special_form: &'static str,
+    pub builtin: &'static str,
+    pub number: &'static str,
+    pub string: &'static str,
+    pub boolean: &'static str,
+    pub comment: &'static str,
+    pub quote: &'static str,
+    /// Bracket under the cursor and its partner
+    pub matched: &'static str,
+    pub reset: &'static str,
+}
+
+impl Theme {
+    /// Bright colors for dark terminal backgrounds
+    pub const DEFAULT: Theme = Theme {
+        parens: "\x1b[1;34m",       // Bold blue
+        special_form: "\x1b[1;35m", // Bold magenta
+        builtin: "\x1b[36m",        // Cyan
+        number: "\x1b[33m",         // Yellow
+        string: "\x1b[32m",         // Green
+        boolean: "\x1b[33m",        // Yellow
+        comment: "\x1b[90m",        // Bright black (gray)
+        quote: "\x1b[1;33m",        // Bold yellow
+        matched: "\x1b[1;7;34m",    // Reverse bold blue
+        reset: "\x1b[0m",
+    };
+
+    /// Darker colors that stay readable on light backgrounds
+    pub const LIGHT: Theme = Theme {
+        parens: "\x1b[1;34m",       // Bold blue
+        special_form: "\x1b[1;35m", // Bold magenta
+        builtin: "\x1b[34m",        // Blue
+        number: "\x1b[31m",         // Red
+        string: "\x1b[32m",         // Green
+        boolean: "\x1b[31m",        // Red
+        comment: "\x1b[2m",         // Dim
+        quote: "\x1b[1;31m",        // Bold red
+        matched: "\x1b[1;7;34m",    // Reverse bold blue
+        reset: "\x1b[0m",
+    };
+
+    /// Plain text, for NO_COLOR, `--color never` and output that is not a terminal
+    pub const NONE: Theme = Theme {
+        parens: "",
+        special_form: "",
+        builtin: "",
+        number: "",
+        string: "",
+        boolean: "",
+        comment: "",
+        quote: "",
+        matched: "",
+        reset: "",
+    };
+}
+
+/// Names the highlighter colors, gathered from an interpreter
+#[derive(Debug, Clone, Default)]
+pub struct SymbolSets {
+    /// Special forms, reader forms like `unquote`, and macros
+    syntax: HashSet<String>,
+    /// Builtins plus every function bound in the global environment (stdlib and user-defined)
+    functions: HashSet<String>,
+}
+
+/// Quote-family names that are not special forms but read as syntax
+const READER_FORMS: &[&str] = &["unquote", "unquote-splicing"];
+
+impl SymbolSets {
+    /// Special forms from the help registry, macros, registered builtins and global functions
+    pub fn from_interpreter(interp: &Interpreter) -> Self {
+        let mut syntax: HashSet<String> = interp
+            .help()
+            .entries()
+            .filter(|entry| entry.category == "Special forms")
+            .map(|entry| entry.name.clone())
+            .collect();
+        syntax.extend(READER_FORMS.iter().map(|name| name.to_string()));
+        syntax.extend(interp.macros().names().iter().map(|name| name.to_string()));
+
+        let mut functions: HashSet<String> = builtin_names().map(str::to_string).collect();
+        functions.extend(
+            interp
+                .env()
+                .bindings()
+                .into_iter()
+                .filter(|(_, value)| matches!(value, Value::Lambda { .. } | Value::BuiltIn(_)))
+                .map(|(name, _)| name.to_string()),
+        );
+
+        SymbolSets { syntax, functions }
+    }
+}
 
 /// Main highlighter helper for Lisp REPL
 /// Provides syntax-aware color highlighting for Lisp syntax
-pub struct LispHelper;
+pub struct LispHelper {
+    symbols: SymbolSets,
+    theme: &'static Theme,
+    /// Earlier lines of an expression still being entered
+    pending: String,
+}
 
 impl LispHelper {
-    pub fn new() -> Self {
-        LispHelper
+    /// A helper that highlights the names `interp` knows about, in the default theme
+    pub fn for_interpreter(interp: &Interpreter) -> Self {
+        LispHelper {
+            symbols: SymbolSets::from_interpreter(interp),
+            theme: &Theme::DEFAULT,
+            pending: String::new(),
+        }
     }
-}
 
-impl Default for LispHelper {
-    fn default() -> Self {
-        Self::new()
+    /// Use `theme`'s colors for input, prompts and output
+    pub fn with_theme(mut self, theme: &'static Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    /// Pick up functions and macros defined since the helper was created
+    pub fn refresh(&mut self, interp: &Interpreter) {
+        self.symbols = SymbolSets::from_interpreter(interp);
+    }
+
+    /// Tell the validator which lines of the current expression were already entered
+    pub fn set_pending(&mut self, input: &str) {
+        self.pending = input.to_string();
     }
 }
 
@@ -52,33 +165,31 @@ impl Hinter for LispHelper {
 
 impl Validator for LispHelper {
     fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
-        let input = ctx.input();
+        let input = if self.pending.is_empty() {
+            ctx.input().to_string()
+        } else {
+            format!("{}\n{}", self.pending, ctx.input())
+        };
 
         // Check for obvious syntax errors (e.g., stray closing parens) - fail fast
-        if has_syntax_error(input) {
+        if has_syntax_error(&input) {
             return Ok(ValidationResult::Invalid(Some(
                 "Syntax error: unmatched closing parenthesis".into(),
             )));
         }
 
-        // Check if input is incomplete using the same logic as main.rs
-        if is_input_incomplete(input) {
-            Ok(ValidationResult::Incomplete)
-        } else {
-            Ok(ValidationResult::Valid(None))
-        }
+        // Incomplete input is accepted line by line; the REPL asks for the rest
+        // at a `..N>` prompt showing how many lists are still open
+        Ok(ValidationResult::Valid(None))
     }
 }
 
 impl Highlighter for LispHelper {
-    fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
-        // Build the special forms and built-in sets
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib_funcs = get_stdlib_functions();
-
-        // Tokenize and colorize
-        let highlighted = highlight_line(line, &special_forms, &builtins, &stdlib_funcs);
+    fn highlight<'l>(&self, line: &'l str, pos: usize) -> Cow<'l, str> {
+        let marked: Vec<usize> = bracket_pair(line, pos)
+            .map(|(at, partner)| vec![at, partner])
+            .unwrap_or_default();
+        let highlighted = highlight_line(line, &self.symbols, self.theme, &marked);
 
         if highlighted == line {
             Cow::Borrowed(line)
@@ -87,26 +198,58 @@ impl Highlighter for LispHelper {
         }
     }
 
+    fn highlight_prompt<'b, 's: 'b, 'p: 'b>(
+        &'s self,
+        prompt: &'p str,
+        _default: bool,
+    ) -> Cow<'b, str> {
+        // Continuation prompts are dimmed so the depth reads as a hint
+        if prompt.starts_with("..") && self.theme != &Theme::NONE {
+            Cow::Owned(format!(
+                "{}{}{}",
+                self.theme.comment, prompt, self.theme.reset
+            ))
+        } else {
+            Cow::Borrowed(prompt)
+        }
+    }
+
     fn highlight_char(&self, _line: &str, _pos: usize, _kind: CmdKind) -> bool {
-        true // Always trigger re-highlighting on character input or cursor movement
+        // Always re-highlight on input or cursor movement, which also moves the matched bracket
+        true
     }
 }
 
 impl LispHelper {
     /// Highlight a Lisp value for output display
     /// Applies color codes to make values more readable in the REPL
-    pub fn highlight_output(value: &Value) -> String {
-        highlight_value(value)
+    pub fn highlight_output(&self, value: &Value) -> String {
+        highlight_value(value, self.theme)
+    }
+
+    /// Highlight a value for output, pretty-printed over several lines if wider than `width`
+    ///
+    /// Lists and maps show only as many elements and levels as `limits` allow.
+    pub fn highlight_output_within(
+        &self,
+        value: &Value,
+        width: usize,
+        limits: PrintLimits,
+    ) -> String {
+        let pretty = pretty_print_limited(value, width, limits);
+        let truncated = is_truncated(value, limits);
+        if pretty.contains('\n') || truncated {
+            highlight_line(&pretty, &self.symbols, self.theme, &[])
+        } else {
+            self.highlight_output(value)
+        }
     }
 }
 
 /// Tokenize a line and apply syntax highlighting
-fn highlight_line(
-    line: &str,
-    special_forms: &HashSet<&'static str>,
-    builtins: &HashSet<&'static str>,
-    stdlib_funcs: &HashSet<&'static str>,
-) -> String {
+///
+/// Brackets at the char indices in `marked` get the matching-bracket color.
+fn highlight_line(line: &str, symbols: &SymbolSets, theme: &Theme, marked: &[usize]) -> String {
     let mut result = String::new();
     let chars: Vec<char> = line.chars().collect();
     let mut i = 0;
@@ -115,17 +258,21 @@ fn highlight_line(
         match chars[i] {
             // Comments: everything from ; to end of line
             ';' => {
-                result.push_str(COLOR_COMMENT);
+                result.push_str(theme.comment);
                 while i < chars.len() && chars[i] != '\n' {
                     result.push(chars[i]);
                     i += 1;
                 }
-                result.push_str(COLOR_RESET);
+                result.push_str(theme.reset);
             }
 
-            // Strings: preserve exact content but colorize
-            '"' => {
-                result.push_str(COLOR_STRING);
+            // Strings, and #"..." interpolated strings: preserve exact content but colorize
+            '"' | '#' if chars[i] == '"' || chars.get(i + 1) == Some(&'"') => {
+                result.push_str(theme.string);
+                if chars[i] == '#' {
+                    result.push('#');
+                    i += 1;
+                }
                 result.push('"');
                 i += 1;
 
@@ -147,7 +294,7 @@ fn highlight_line(
                     }
                 }
 
-                result.push_str(COLOR_RESET);
+                result.push_str(theme.reset);
                 if !found_close && i > 0 {
                     // Unclosed string - let it still be colored to end of line
                     while i < chars.len() && chars[i] != '\n' {
@@ -189,9 +336,9 @@ fn highlight_line(
                 }
 
                 let num_str: String = chars[old_i..i].iter().collect();
-                result.push_str(COLOR_NUMBER);
+                result.push_str(theme.number);
                 result.push_str(&num_str);
-                result.push_str(COLOR_RESET);
+                result.push_str(theme.reset);
             }
 
             // Signed numbers or symbols starting with +/-
@@ -230,9 +377,9 @@ fn highlight_line(
                     }
 
                     let num_str: String = chars[old_i..i].iter().collect();
-                    result.push_str(COLOR_NUMBER);
+                    result.push_str(theme.number);
                     result.push_str(&num_str);
-                    result.push_str(COLOR_RESET);
+                    result.push_str(theme.reset);
                 } else {
                     // Just a symbol (+, -, or symbol starting with them)
                     let start = i;
@@ -254,14 +401,11 @@ fn highlight_line(
                     }
 
                     let symbol: String = chars[start..i].iter().collect();
-                    let builtins = get_builtins();
-                    let stdlib_funcs = get_stdlib_functions();
 
-                    if builtins.contains(symbol.as_str()) || stdlib_funcs.contains(symbol.as_str())
-                    {
-                        result.push_str(COLOR_BUILTIN);
+                    if symbols.functions.contains(&symbol) {
+                        result.push_str(theme.builtin);
                         result.push_str(&symbol);
-                        result.push_str(COLOR_RESET);
+                        result.push_str(theme.reset);
                     } else {
                         result.push_str(&symbol);
                     }
@@ -277,18 +421,18 @@ fn highlight_line(
                             || chars[i + 2] == '-')
                     {
                         // Not a boolean, it's a symbol that starts with #
-                        result.push_str(COLOR_BUILTIN);
+                        result.push_str(theme.builtin);
                         result.push(chars[i]);
                         result.push(chars[i + 1]);
                         i += 2;
-                        result.push_str(COLOR_RESET);
+                        result.push_str(theme.reset);
                     } else {
                         // It's a boolean
-                        result.push_str(COLOR_BOOLEAN);
+                        result.push_str(theme.boolean);
                         result.push(chars[i]);
                         result.push(chars[i + 1]);
                         i += 2;
-                        result.push_str(COLOR_RESET);
+                        result.push_str(theme.reset);
                     }
                 } else {
                     result.push(chars[i]);
@@ -298,34 +442,39 @@ fn highlight_line(
 
             // Quote-like special characters
             '\'' | '`' => {
-                result.push_str(COLOR_QUOTE);
+                result.push_str(theme.quote);
                 result.push(chars[i]);
                 i += 1;
-                result.push_str(COLOR_RESET);
+                result.push_str(theme.reset);
             }
 
             // Unquote
             ',' => {
                 if i + 1 < chars.len() && chars[i + 1] == '@' {
-                    result.push_str(COLOR_QUOTE);
+                    result.push_str(theme.quote);
                     result.push(',');
                     result.push('@');
                     i += 2;
-                    result.push_str(COLOR_RESET);
+                    result.push_str(theme.reset);
                 } else {
-                    result.push_str(COLOR_QUOTE);
+                    result.push_str(theme.quote);
                     result.push(',');
                     i += 1;
-                    result.push_str(COLOR_RESET);
+                    result.push_str(theme.reset);
                 }
             }
 
             // Parentheses and brackets
             '(' | ')' | '[' | ']' | '{' | '}' => {
-                result.push_str(COLOR_PARENS);
+                let color = if marked.contains(&i) {
+                    theme.matched
+                } else {
+                    theme.parens
+                };
+                result.push_str(color);
                 result.push(chars[i]);
                 i += 1;
-                result.push_str(COLOR_RESET);
+                result.push_str(theme.reset);
             }
 
             // Whitespace
@@ -357,16 +506,14 @@ fn highlight_line(
                 let symbol: String = chars[start..i].iter().collect();
 
                 // Classify the symbol
-                if special_forms.contains(symbol.as_str()) {
-                    result.push_str(COLOR_SPECIAL_FORM);
+                if symbols.syntax.contains(&symbol) {
+                    result.push_str(theme.special_form);
                     result.push_str(&symbol);
-                    result.push_str(COLOR_RESET);
-                } else if builtins.contains(symbol.as_str())
-                    || stdlib_funcs.contains(symbol.as_str())
-                {
-                    result.push_str(COLOR_BUILTIN);
+                    result.push_str(theme.reset);
+                } else if symbols.functions.contains(&symbol) {
+                    result.push_str(theme.builtin);
                     result.push_str(&symbol);
-                    result.push_str(COLOR_RESET);
+                    result.push_str(theme.reset);
                 } else {
                     // Regular symbol
                     result.push_str(&symbol);
@@ -378,8 +525,83 @@ fn highlight_line(
     result
 }
 
+/// Char indices of the bracket under (or else just before) the cursor and its partner
+///
+/// `pos` is a byte offset, as rustyline reports it. Brackets inside strings and
+/// comments are ignored.
+fn bracket_pair(line: &str, pos: usize) -> Option<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let cursor = line
+        .get(..pos)
+        .map_or(chars.len(), |before| before.chars().count());
+
+    let mut partner = vec![None; chars.len()];
+    let mut open = Vec::new();
+    let (mut in_string, mut in_comment, mut escaped) = (false, false, false);
+    for (i, &c) in chars.iter().enumerate() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                ';' => in_comment = true,
+                '(' | '[' | '{' => open.push(i),
+                ')' | ']' | '}' => {
+                    if let Some(opening) = open.pop() {
+                        partner[opening] = Some(i);
+                        partner[i] = Some(opening);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    [Some(cursor), cursor.checked_sub(1)]
+        .into_iter()
+        .flatten()
+        .filter(|&i| i < chars.len())
+        .find_map(|i| partner[i].map(|other| (i, other)))
+}
+
+/// Number of lists and maps opened but not yet closed, ignoring strings and comments
+pub fn unclosed_depth(input: &str) -> usize {
+    let mut depth: usize = 0;
+    let (mut in_string, mut in_comment, mut escaped) = (false, false, false);
+    for c in input.chars() {
+        if in_comment {
+            in_comment = c != '\n';
+        } else if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else {
+            match c {
+                '"' => in_string = true,
+                ';' => in_comment = true,
+                '(' | '{' => depth += 1,
+                ')' | '}' => depth = depth.saturating_sub(1),
+                _ => {}
+            }
+        }
+    }
+    depth
+}
+
 /// Check if input is incomplete and needs more lines
-fn is_input_incomplete(input: &str) -> bool {
+pub fn is_input_incomplete(input: &str) -> bool {
     let trimmed = input.trim();
 
     // If input starts with ;;; (doc comment), check for following expression
@@ -462,124 +684,8 @@ pub fn has_syntax_error(input: &str) -> bool {
     false
 }
 
-/// Get all special forms (keywords that have special evaluation semantics)
-fn get_special_forms() -> HashSet<&'static str> {
-    [
-        "define",
-        "lambda",
-        "if",
-        "begin",
-        "let",
-        "quote",
-        "quasiquote",
-        "unquote",
-        "unquote-splicing",
-        "defmacro",
-    ]
-    .iter()
-    .copied()
-    .collect()
-}
-
-/// Get all built-in functions
-fn get_builtins() -> HashSet<&'static str> {
-    [
-        // Arithmetic
-        "+",
-        "-",
-        "*",
-        "/",
-        "%",
-        // Comparison
-        "=",
-        "<",
-        ">",
-        "<=",
-        ">=",
-        // Logic
-        "and",
-        "or",
-        "not",
-        // List operations
-        "cons",
-        "car",
-        "cdr",
-        "list",
-        "length",
-        "empty?",
-        // Type predicates
-        "number?",
-        "string?",
-        "list?",
-        "nil?",
-        "symbol?",
-        "bool?",
-        // I/O
-        "print",
-        "println",
-        "read-file",
-        "write-file",
-        "file-exists?",
-        "file-size",
-        "list-files",
-        "http-get",
-        "http-post",
-        // Error handling
-        "error",
-        "error?",
-        "error-msg",
-        // Help
-        "help",
-        "doc",
-    ]
-    .iter()
-    .copied()
-    .collect()
-}
-
-/// Get all stdlib functions that should be highlighted
-fn get_stdlib_functions() -> HashSet<&'static str> {
-    [
-        // Higher-order functions
-        "map",
-        "filter",
-        "reduce",
-        "compose",
-        "partial",
-        // List utilities
-        "reverse",
-        "append",
-        "member",
-        "nth",
-        "last",
-        "take",
-        "drop",
-        "zip",
-        // Predicates
-        "all",
-        "any",
-        "count",
-        "even?",
-        "odd?",
-        // Math
-        "abs",
-        "min",
-        "max",
-        "square",
-        "cube",
-        "sum",
-        "product",
-        "factorial",
-        // Other
-        "range",
-    ]
-    .iter()
-    .copied()
-    .collect()
-}
-
 /// Recursively highlight a Value for output display
-fn highlight_value(value: &Value) -> String {
+fn highlight_value(value: &Value, theme: &Theme) -> String {
     match value {
         Value::Number(n) => {
             let num_str = if n.fract() == 0.0 && n.is_finite() {
@@ -587,62 +693,89 @@ fn highlight_value(value: &Value) -> String {
             } else {
                 format!("{}", n)
             };
-            format!("{}{}{}", COLOR_NUMBER, num_str, COLOR_RESET)
+            format!("{}{}{}", theme.number, num_str, theme.reset)
+        }
+        Value::Decimal(d) => {
+            format!("{}{}{}", theme.number, d.to_plain_string(), theme.reset)
         }
         Value::Bool(b) => {
             let bool_str = if *b { "#t" } else { "#f" };
-            format!("{}{}{}", COLOR_BOOLEAN, bool_str, COLOR_RESET)
+            format!("{}{}{}", theme.boolean, bool_str, theme.reset)
         }
-        Value::String(s) => {
-            format!("{}\"{}\"{}", COLOR_STRING, s, COLOR_RESET)
+        Value::String(_) => {
+            let mut literal = String::new();
+            let _ = write_value(&mut literal, value, PrintStyle::Write);
+            format!("{}{}{}", theme.string, literal, theme.reset)
         }
         Value::Symbol(s) => {
             // Symbols are normally displayed uncolored unless they're special
-            s.clone()
+            s.to_string()
         }
         Value::Keyword(k) => {
             // Keywords displayed with : prefix
-            format!("{}:{}{}", COLOR_STRING, k, COLOR_RESET)
+            format!("{}:{}{}", theme.string, k, theme.reset)
         }
         Value::List(items) => {
-            let mut result = format!("{}({}", COLOR_PARENS, COLOR_RESET);
+            let mut result = format!("{}({}", theme.parens, theme.reset);
             for (i, item) in items.iter().enumerate() {
                 if i > 0 {
                     result.push(' ');
                 }
-                result.push_str(&highlight_value(item));
+                result.push_str(&highlight_value(item, theme));
+            }
+            result.push_str(&format!("{}){}", theme.parens, theme.reset));
+            result
+        }
+        Value::Values(items) => {
+            let mut result = format!(
+                "{}({}{}values{}",
+                theme.parens, theme.reset, theme.builtin, theme.reset
+            );
+            for item in items.iter() {
+                result.push(' ');
+                result.push_str(&highlight_value(item, theme));
             }
-            result.push_str(&format!("{}){}", COLOR_PARENS, COLOR_RESET));
+            result.push_str(&format!("{}){}", theme.parens, theme.reset));
             result
         }
         Value::Map(map) => {
-            let mut result = format!("{}{{{}", COLOR_PARENS, COLOR_RESET);
-            let mut entries: Vec<_> = map.iter().collect();
-            entries.sort_by_key(|(k, _)| *k);
-            for (i, (key, value)) in entries.iter().enumerate() {
+            let mut result = format!("{}{{{}", theme.parens, theme.reset);
+            for (i, (key, value)) in map.iter().enumerate() {
                 if i > 0 {
                     result.push(' ');
                 }
-                result.push_str(&format!("{}:{}{} ", COLOR_STRING, key, COLOR_RESET));
-                result.push_str(&highlight_value(value));
+                result.push_str(&format!("{}:{}{} ", theme.string, key, theme.reset));
+                result.push_str(&highlight_value(value, theme));
             }
-            result.push_str(&format!("{}}}{}", COLOR_PARENS, COLOR_RESET));
+            result.push_str(&format!("{}}}{}", theme.parens, theme.reset));
             result
         }
         Value::Lambda { .. } => {
-            format!("{}#<lambda>{}", COLOR_BUILTIN, COLOR_RESET)
+            format!("{}#<lambda>{}", theme.builtin, theme.reset)
         }
         Value::Macro { .. } => {
-            format!("{}#<macro>{}", COLOR_BUILTIN, COLOR_RESET)
+            format!("{}#<macro>{}", theme.builtin, theme.reset)
         }
         Value::BuiltIn(_) => {
-            format!("{}#<builtin>{}", COLOR_BUILTIN, COLOR_RESET)
+            format!("{}#<builtin>{}", theme.builtin, theme.reset)
         }
-        Value::Error(msg) => {
-            format!("{}#<error: {}>{}", COLOR_SPECIAL_FORM, msg, COLOR_RESET)
+        Value::Promise(_) => {
+            format!("{}#<promise>{}", theme.builtin, theme.reset)
+        }
+        Value::Thread(_) => {
+            format!("{}#<thread>{}", theme.builtin, theme.reset)
+        }
+        Value::Channel(_) => {
+            format!("{}#<channel>{}", theme.builtin, theme.reset)
+        }
+        Value::RateLimiter(_) | Value::WebSocket(_) | Value::VectorStore(_) | Value::Matrix(_) => {
+            format!("{}{}{}", theme.builtin, value, theme.reset)
+        }
+        Value::Error(_) => {
+            format!("{}{}{}", theme.special_form, value, theme.reset)
         }
         Value::Nil => {
-            format!("{}nil{}", COLOR_BUILTIN, COLOR_RESET)
+            format!("{}nil{}", theme.builtin, theme.reset)
         }
     }
 }
@@ -651,143 +784,220 @@ fn highlight_value(value: &Value) -> String {
 mod tests {
     use super::*;
 
+    fn symbols() -> SymbolSets {
+        SymbolSets::from_interpreter(&Interpreter::new())
+    }
+
+    fn helper() -> LispHelper {
+        LispHelper::for_interpreter(&Interpreter::new())
+    }
+
     #[test]
     fn test_number_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("42", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_NUMBER));
+        let highlighted = highlight_line("42", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.number));
     }
 
     #[test]
     fn test_string_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("\"hello\"", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_STRING));
+        let highlighted = highlight_line("\"hello\"", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.string));
+
+        let highlighted = highlight_line("#\"hi {x}\"", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.starts_with(&format!("{}#\"hi {{x}}\"", Theme::DEFAULT.string)));
     }
 
     #[test]
     fn test_comment_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("; this is a comment", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_COMMENT));
+        let highlighted = highlight_line("; this is a comment", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.comment));
     }
 
     #[test]
     fn test_special_form_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("(define x 5)", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_SPECIAL_FORM));
-        assert!(highlighted.contains(COLOR_PARENS));
+        let highlighted = highlight_line("(define x 5)", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.special_form));
+        assert!(highlighted.contains(Theme::DEFAULT.parens));
     }
 
     #[test]
     fn test_builtin_function_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("(+ 1 2)", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_BUILTIN));
-        assert!(highlighted.contains(COLOR_PARENS));
+        let highlighted = highlight_line("(+ 1 2)", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.builtin));
+        assert!(highlighted.contains(Theme::DEFAULT.parens));
     }
 
     #[test]
     fn test_boolean_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("#t #f", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_BOOLEAN));
+        let highlighted = highlight_line("#t #f", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.boolean));
     }
 
     #[test]
     fn test_quote_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("'(1 2 3)", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_QUOTE));
+        let highlighted = highlight_line("'(1 2 3)", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.quote));
     }
 
     #[test]
     fn test_stdlib_function_highlighting() {
-        let special_forms = get_special_forms();
-        let builtins = get_builtins();
-        let stdlib = get_stdlib_functions();
+        let symbols = symbols();
 
-        let highlighted = highlight_line("(map inc lst)", &special_forms, &builtins, &stdlib);
-        assert!(highlighted.contains(COLOR_BUILTIN)); // 'map' is in stdlib
+        let highlighted = highlight_line("(map inc lst)", &symbols, &Theme::DEFAULT, &[]);
+        assert!(highlighted.contains(Theme::DEFAULT.builtin)); // 'map' is in stdlib
     }
 
     #[test]
     fn test_output_number_highlighting() {
         let value = Value::Number(42.0);
-        let highlighted = LispHelper::highlight_output(&value);
-        assert!(highlighted.contains(COLOR_NUMBER));
+        let highlighted = helper().highlight_output(&value);
+        assert!(highlighted.contains(Theme::DEFAULT.number));
         assert!(highlighted.contains("42"));
     }
 
     #[test]
     fn test_output_bool_highlighting() {
         let value_true = Value::Bool(true);
-        let highlighted_true = LispHelper::highlight_output(&value_true);
-        assert!(highlighted_true.contains(COLOR_BOOLEAN));
+        let highlighted_true = helper().highlight_output(&value_true);
+        assert!(highlighted_true.contains(Theme::DEFAULT.boolean));
         assert!(highlighted_true.contains("#t"));
 
         let value_false = Value::Bool(false);
-        let highlighted_false = LispHelper::highlight_output(&value_false);
-        assert!(highlighted_false.contains(COLOR_BOOLEAN));
+        let highlighted_false = helper().highlight_output(&value_false);
+        assert!(highlighted_false.contains(Theme::DEFAULT.boolean));
         assert!(highlighted_false.contains("#f"));
     }
 
     #[test]
     fn test_output_string_highlighting() {
-        let value = Value::String("hello".to_string());
-        let highlighted = LispHelper::highlight_output(&value);
-        assert!(highlighted.contains(COLOR_STRING));
+        let value = Value::String("hello".into());
+        let highlighted = helper().highlight_output(&value);
+        assert!(highlighted.contains(Theme::DEFAULT.string));
         assert!(highlighted.contains("\"hello\""));
     }
 
     #[test]
     fn test_output_list_highlighting() {
-        let value = Value::List(vec![
-            Value::Number(1.0),
-            Value::Number(2.0),
-            Value::Number(3.0),
-        ]);
-        let highlighted = LispHelper::highlight_output(&value);
-        assert!(highlighted.contains(COLOR_PARENS));
-        assert!(highlighted.contains(COLOR_NUMBER));
+        let value =
+            Value::List(vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)].into());
+        let highlighted = helper().highlight_output(&value);
+        assert!(highlighted.contains(Theme::DEFAULT.parens));
+        assert!(highlighted.contains(Theme::DEFAULT.number));
     }
 
     #[test]
     fn test_output_nil_highlighting() {
         let value = Value::Nil;
-        let highlighted = LispHelper::highlight_output(&value);
+        let highlighted = helper().highlight_output(&value);
         assert!(highlighted.contains("nil"));
     }
 
     #[test]
     fn test_output_symbol_highlighting() {
-        let value = Value::Symbol("my-var".to_string());
-        let highlighted = LispHelper::highlight_output(&value);
+        let value = Value::Symbol("my-var".into());
+        let highlighted = helper().highlight_output(&value);
         assert!(highlighted.contains("my-var"));
     }
 
+    #[test]
+    fn test_output_wraps_past_width() {
+        let helper = LispHelper::for_interpreter(&Interpreter::new());
+        let value = crate::parser::parse("(1 2 3)").unwrap();
+        assert!(!helper
+            .highlight_output_within(&value, 80, PrintLimits::default())
+            .contains('\n'));
+        let wrapped = helper.highlight_output_within(&value, 4, PrintLimits::default());
+        assert_eq!(wrapped.lines().count(), 3);
+
+        let plain = helper.with_theme(&Theme::NONE);
+        assert_eq!(
+            plain.highlight_output_within(
+                &value,
+                80,
+                PrintLimits {
+                    length: Some(1),
+                    depth: None
+                }
+            ),
+            "(1 ... (2 more))"
+        );
+    }
+
+    #[test]
+    fn test_symbols_follow_the_interpreter() {
+        let mut interp = Interpreter::new();
+        let mut helper = LispHelper::for_interpreter(&interp);
+        for name in ["http-request", "define", "profile", "unquote"] {
+            assert!(helper.highlight(name, 0).contains("\x1b["), "{}", name);
+        }
+        assert_eq!(helper.highlight("double", 0), "double");
+
+        interp
+            .eval_str("(define (double x) (* x 2)) (defmacro swap (a b) `(list ,b ,a))")
+            .unwrap();
+        helper.refresh(&interp);
+        assert!(helper
+            .highlight("double", 0)
+            .contains(Theme::DEFAULT.builtin));
+        assert!(helper
+            .highlight("swap", 0)
+            .contains(Theme::DEFAULT.special_form));
+    }
+
+    #[test]
+    fn test_themes() {
+        let plain = helper().with_theme(&Theme::NONE);
+        let source = "(define (f x) '(1 \"a\" #t)) ; note";
+        assert_eq!(plain.highlight(source, 1), source);
+        assert_eq!(plain.highlight_prompt("..2> ", true), "..2> ");
+        let value = crate::parser::parse("(1 \"a\" {:k #t})").unwrap();
+        assert_eq!(plain.highlight_output(&value), value.to_string());
+
+        let light = helper().with_theme(&Theme::LIGHT);
+        assert!(light.highlight("42", 0).contains(Theme::LIGHT.number));
+        assert!(light
+            .highlight_prompt("..2> ", true)
+            .contains(Theme::LIGHT.comment));
+    }
+
+    #[test]
+    fn test_matching_bracket_under_cursor() {
+        assert_eq!(bracket_pair("(a (b) c)", 3), Some((3, 5)));
+        // Just past a closing bracket
+        assert_eq!(bracket_pair("(a (b) c)", 9), Some((8, 0)));
+        assert_eq!(bracket_pair("(a \")\" b)", 3), None);
+        assert_eq!(bracket_pair("(a b", 0), None);
+
+        let helper = LispHelper::for_interpreter(&Interpreter::new());
+        let highlighted = helper.highlight("(a (b) c)", 3);
+        assert_eq!(highlighted.matches(Theme::DEFAULT.matched).count(), 2);
+        assert!(!helper
+            .highlight("(a (b) c)", 2)
+            .contains(Theme::DEFAULT.matched));
+    }
+
+    #[test]
+    fn test_unclosed_depth() {
+        assert_eq!(unclosed_depth("(define (f x)"), 1);
+        assert_eq!(unclosed_depth("(let ((x 1)\n  (y {:a"), 4);
+        assert_eq!(unclosed_depth("(f \"((\" ; ((\n"), 1);
+        assert_eq!(unclosed_depth("(+ 1 2))"), 0);
+    }
+
     #[test]
     fn test_syntax_error_stray_closing_paren() {
         assert!(has_syntax_error(")"));
@@ -0,0 +1,1918 @@
+// ABOUTME: Owned interpreter context that can be moved between threads
+
+use crate::builtins::bindings;
+use crate::builtins::debug::{self, TraceSet};
+use crate::builtins::testing::{self, TestRegistry, TestTally};
+use crate::builtins::{self, register_builtins};
+use crate::cancel::CancellationToken;
+use crate::config::CAPABILITY_MODULES;
+use crate::coverage::{self, Coverage};
+use crate::debugger::{self, DebugCommand, DebugPause, Debugger};
+use crate::env::{EnvRef, Environment};
+use crate::error::EvalError;
+use crate::eval::{self, eval_with_macros};
+use crate::events::{self, EventHandlers};
+use crate::help::{self, HelpRegistry};
+use crate::macros::MacroRegistry;
+use crate::parser::{self, parse_one_expr, skip_whitespace_and_regular_comments};
+use crate::profiler::{self, Profiler};
+use crate::sandbox::{AuditEntry, Sandbox};
+use crate::stdlib::log::{self as logging, LogConfig, LogLevel, LogRecord};
+use crate::stdlib::register_stdlib;
+use crate::usage::ResourceUsage;
+use crate::value::Value;
+use std::cell::Cell;
+use std::sync::{mpsc, Arc};
+
+thread_local! {
+    /// Whether this thread has [`crate::cancel::EVAL_STACK_SIZE`] of stack to evaluate on
+    static ON_EVAL_STACK: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Run `f` on a thread with [`crate::cancel::EVAL_STACK_SIZE`] of stack
+///
+/// The depth limit only turns deep recursion into a catchable error if the
+/// stack outlasts it, and the caller's thread may have as little as 2MB. On a
+/// thread that already has the large stack, `f` runs inline. Doc comments
+/// waiting for the next definition, the text of the last expression parsed
+/// and the definition category go along.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn on_eval_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    if ON_EVAL_STACK.with(Cell::get) {
+        return f();
+    }
+    let docs = parser::take_pending_docs();
+    let source = parser::take_pending_source();
+    let category = parser::definition_category_setting();
+
+    let mut task = Some(f);
+    let ran = std::thread::scope(|scope| {
+        let worker = std::thread::Builder::new()
+            .name("lisp-eval".to_string())
+            .stack_size(crate::cancel::EVAL_STACK_SIZE)
+            .spawn_scoped(scope, || {
+                ON_EVAL_STACK.with(|on| on.set(true));
+                parser::set_pending_docs(docs);
+                parser::set_pending_source(source);
+                parser::set_definition_category(category);
+                let result = task.take().map(|f| f());
+                (result, parser::take_pending_docs())
+            })
+            .ok()?;
+        match worker.join() {
+            Ok((result, docs)) => {
+                parser::set_pending_docs(docs);
+                result
+            }
+            Err(panic) => std::panic::resume_unwind(panic),
+        }
+    });
+    // Without a thread to spare, evaluate here and rely on the stack being enough
+    ran.unwrap_or_else(|| (task.take().expect("evaluation did not run"))())
+}
+
+/// Run `f` where it is; a wasm stack cannot be grown with a thread
+#[cfg(target_arch = "wasm32")]
+pub fn on_eval_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    f()
+}
+
+/// A thread with [`crate::cancel::EVAL_STACK_SIZE`] of stack that runs one interpreter's evaluations
+///
+/// Started by the first evaluation called from a thread without the large
+/// stack and kept until the interpreter is dropped, so each later call only
+/// hands its work over instead of starting a thread.
+struct EvalWorker {
+    jobs: mpsc::Sender<Box<dyn FnOnce() + Send>>,
+}
+
+impl EvalWorker {
+    /// Start the thread, or `None` without a thread to spare
+    #[cfg(not(target_arch = "wasm32"))]
+    fn spawn() -> Option<Self> {
+        let (jobs, queue) = mpsc::channel::<Box<dyn FnOnce() + Send>>();
+        std::thread::Builder::new()
+            .name("lisp-eval".to_string())
+            .stack_size(crate::cancel::EVAL_STACK_SIZE)
+            .spawn(move || {
+                ON_EVAL_STACK.with(|on| on.set(true));
+                // Ends when the interpreter drops its sender
+                for job in queue {
+                    job();
+                }
+            })
+            .ok()?;
+        Some(EvalWorker { jobs })
+    }
+
+    /// A wasm stack cannot be grown with a thread
+    #[cfg(target_arch = "wasm32")]
+    fn spawn() -> Option<Self> {
+        None
+    }
+
+    /// Run `job` on the worker and wait for its result
+    ///
+    /// A panicking job would stop the worker, so callers catch panics inside `job`.
+    fn run<T: Send + 'static>(&self, job: impl FnOnce() -> T + Send + 'static) -> T {
+        let (done, result) = mpsc::sync_channel(1);
+        self.jobs
+            .send(Box::new(move || {
+                let _ = done.send(job());
+            }))
+            .expect("evaluation thread stopped");
+        result.recv().expect("evaluation thread stopped")
+    }
+}
+
+/// Lisp stdlib modules, in load order
+pub const STDLIB_MODULES: &[(&str, &str)] = &[
+    ("core", include_str!("stdlib/lisp/core.lisp")),
+    #[cfg(feature = "lisp-fallbacks")]
+    ("fallbacks", include_str!("stdlib/lisp/fallbacks.lisp")),
+    ("math", include_str!("stdlib/lisp/math.lisp")),
+    ("string", include_str!("stdlib/lisp/string.lisp")),
+    ("test", include_str!("stdlib/lisp/test.lisp")),
+    #[cfg(feature = "network")]
+    ("http", include_str!("stdlib/lisp/http.lisp")),
+];
+
+/// Help category for definitions in the stdlib module `name`
+///
+/// The `http` module shares "Standard Library: HTTP" with the Rust `http:` functions.
+fn stdlib_category(name: &str) -> String {
+    let mut chars = name.chars();
+    let title: String = match name {
+        "http" => "HTTP".to_string(),
+        _ => chars
+            .next()
+            .map(|first| first.to_uppercase().chain(chars).collect())
+            .unwrap_or_default(),
+    };
+    format!("Standard Library: {}", title)
+}
+
+/// Capability module of every definition in the stdlib module `name`, if any
+fn stdlib_capability(name: &str) -> Option<&'static str> {
+    match name {
+        "http" => Some("network"),
+        _ => None,
+    }
+}
+
+/// State that builtins read from thread-local slots while an evaluation runs
+#[derive(Default)]
+struct HostState {
+    sandbox: Option<Arc<Sandbox>>,
+    cancel: Option<CancellationToken>,
+    help: HelpRegistry,
+    tests: TestRegistry,
+    /// Console output captured instead of printed, when capture is on
+    output: Option<String>,
+    /// Expression hit counts, when coverage is on
+    coverage: Option<Coverage>,
+    /// Calls and time per function, when profiling is on
+    profiler: Option<Profiler>,
+    /// Functions whose calls `trace` prints
+    traced: TraceSet,
+    /// Step debugger, when the host attached one
+    debugger: Option<Debugger>,
+    /// Whether top-level defines that replace a binding print a warning
+    warn_redefinitions: bool,
+    /// Callbacks for streaming evaluation events to the host
+    events: EventHandlers,
+    /// Level and destination of `log:info` and its siblings
+    log: LogConfig,
+}
+
+/// A complete interpreter: global environment, macros, sandbox, cancellation, help and tests
+///
+/// `Interpreter` owns all of its state and is `Send`, so a host can run one
+/// interpreter per worker thread, share one behind a `Mutex`, or keep many
+/// isolated sessions on a single thread and call them in any order. The evaluator
+/// and builtins still find the sandbox, cancellation token, help registry and
+/// test registry through thread-local slots; each call to [`Interpreter::eval`]
+/// installs this interpreter's state into those slots for the duration of the
+/// call and takes it back afterwards.
+pub struct Interpreter {
+    env: EnvRef,
+    macros: MacroRegistry,
+    host: HostState,
+    /// Thread evaluations run on when called from a thread with a small stack
+    worker: Option<EvalWorker>,
+}
+
+impl Interpreter {
+    /// Create an interpreter with all builtins and help entries registered
+    ///
+    /// The Lisp stdlib is not loaded; call [`Interpreter::load_stdlib`] for that.
+    /// I/O builtins fail until a sandbox is installed with [`Interpreter::set_sandbox`].
+    pub fn new() -> Self {
+        let mut interp = Interpreter {
+            env: Environment::new(),
+            macros: MacroRegistry::new(),
+            host: HostState {
+                cancel: Some(CancellationToken::new()),
+                ..HostState::default()
+            },
+            worker: None,
+        };
+
+        let env = interp.env.clone();
+        interp.with_active(|| {
+            register_builtins(env.clone());
+            register_stdlib(env);
+            eval::register_special_forms_part1();
+            eval::register_special_forms_part2();
+        });
+        interp
+    }
+
+    /// Install the sandbox used by filesystem and network builtins
+    pub fn set_sandbox(&mut self, sandbox: Sandbox) {
+        self.host.sandbox = Some(Arc::new(sandbox));
+    }
+
+    /// Collect `print`/`println`/`help` output instead of writing it to stdout
+    ///
+    /// Captured text accumulates until [`Interpreter::take_output`] is called.
+    /// Output from threads started with `spawn` still goes to stdout.
+    pub fn capture_output(&mut self, enabled: bool) {
+        self.host.output = enabled.then(String::new);
+    }
+
+    /// Console output captured since the last call (empty if capture is off)
+    pub fn take_output(&mut self) -> String {
+        match self.host.output.as_mut() {
+            Some(buffer) => std::mem::take(buffer),
+            None => String::new(),
+        }
+    }
+
+    /// Serialize the global environment's data bindings as JSON (see [`crate::session::save`])
+    ///
+    /// Returns the JSON and how many bindings it holds. Functions are not saved.
+    #[allow(dead_code)]
+    pub fn save_session(&self) -> (String, usize) {
+        crate::session::save(&self.env)
+    }
+
+    /// Define the bindings from [`Interpreter::save_session`] output, returning how many
+    #[allow(dead_code)]
+    pub fn restore_session(&mut self, json: &str) -> Result<usize, String> {
+        crate::session::restore(&self.env, json)
+    }
+
+    /// Print a warning to stderr whenever a top-level `define` replaces an existing binding
+    ///
+    /// Useful when running code assembled from separately generated snippets,
+    /// where two of them may define the same helper.
+    pub fn set_warn_redefinitions(&mut self, warn: bool) {
+        self.host.warn_redefinitions = warn;
+    }
+
+    /// Drop messages logged below `level`; `None` turns logging off (the default level is info)
+    pub fn set_log_level(&mut self, level: Option<LogLevel>) {
+        self.host.log.level = level;
+    }
+
+    /// Append log lines to `path` in the sandbox instead of writing them to stderr
+    ///
+    /// The path is checked like any sandboxed write, and the file may not grow
+    /// past the sandbox's file size limit. `None` goes back to stderr.
+    pub fn set_log_file(&mut self, path: Option<String>) {
+        self.host.log.file = path;
+    }
+
+    /// Freeze every global binding defined so far, returning how many
+    ///
+    /// Called after the stdlib is loaded, this protects builtins and stdlib
+    /// functions: code that `define`s or `set!`s `+` or `map` at top level
+    /// gets an error instead of silently breaking everything that uses them.
+    /// Local bindings may still shadow the names.
+    pub fn protect_globals(&mut self) -> usize {
+        let bindings = self.env.bindings();
+        for (name, _) in &bindings {
+            self.env.protect(name);
+        }
+        bindings.len()
+    }
+
+    /// Token that aborts this interpreter's evaluations when cancelled
+    ///
+    /// Keep a clone to call [`CancellationToken::cancel`] from a signal handler or
+    /// another thread; the running `eval` then fails with `EvalError::Interrupted`.
+    /// Call [`CancellationToken::reset`] before evaluating again.
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.host.cancel.clone().unwrap_or_default()
+    }
+
+    /// Fail any single [`Interpreter::eval`] that takes more than `limit` steps
+    ///
+    /// A step is one pass through the evaluator loop; threads started by
+    /// `spawn` count against the same budget. `None` removes the limit.
+    pub fn set_step_limit(&mut self, limit: Option<u64>) {
+        self.cancellation_token().set_step_limit(limit);
+    }
+
+    /// Fail an evaluation nested more than `depth` deep with a "recursion too deep" error
+    ///
+    /// Non-tail recursion nests evaluations; tail calls do not. The error can be
+    /// caught with `try`. The default is [`crate::cancel::DEFAULT_MAX_EVAL_DEPTH`];
+    /// evaluation runs on a stack large enough for it (see [`on_eval_stack`]).
+    /// `None` removes the limit.
+    pub fn set_depth_limit(&mut self, depth: Option<usize>) {
+        self.cancellation_token().set_depth_limit(depth);
+    }
+
+    /// Fail an evaluation once more than `calls` calls wait for their operands
+    ///
+    /// Calls nested as operands, as in deep arithmetic or non-tail recursion
+    /// through a function call, wait on the heap rather than the Rust stack, so
+    /// they are not counted against the depth limit. The default is
+    /// [`crate::cancel::DEFAULT_MAX_PENDING_CALLS`]; `None` removes the limit.
+    pub fn set_pending_call_limit(&mut self, calls: Option<usize>) {
+        self.cancellation_token().set_pending_call_limit(calls);
+    }
+
+    /// Fail a macro expansion once `depth` expansions are in progress
+    ///
+    /// Nested expansions count as well as a macro expanding into another macro
+    /// call, so a macro that expands into itself fails instead of looping. The
+    /// default is [`crate::macros::DEFAULT_MAX_EXPANSION_DEPTH`].
+    pub fn set_macro_depth_limit(&mut self, depth: usize) {
+        self.macros.set_max_depth(depth);
+    }
+
+    /// Print every expansion step of every macro, as `trace-macro` does for named ones
+    pub fn set_trace_macros(&mut self, trace: bool) {
+        self.host.traced.set_all_macros(trace);
+    }
+
+    /// Remove every function in a capability module (see [`crate::config::CAPABILITY_MODULES`])
+    ///
+    /// Returns how many functions were removed. Calls to them then fail as
+    /// undefined symbols. Disable modules after loading the stdlib so stdlib
+    /// functions in the module are removed too.
+    pub fn disable_capability(&mut self, module: &str) -> Result<usize, String> {
+        if !CAPABILITY_MODULES.contains(&module) {
+            return Err(format!(
+                "unknown capability module '{}' (expected one of: {})",
+                module,
+                CAPABILITY_MODULES.join(", ")
+            ));
+        }
+
+        let names = self.host.help.in_capability(module);
+        for name in &names {
+            self.env.undefine(name);
+            self.host.help.remove(name);
+        }
+        Ok(names.len())
+    }
+
+    /// The global environment
+    #[allow(dead_code)]
+    pub fn env(&self) -> &EnvRef {
+        &self.env
+    }
+
+    /// The macro registry
+    #[allow(dead_code)]
+    pub fn macros(&self) -> &MacroRegistry {
+        &self.macros
+    }
+
+    /// Documentation for builtins, special forms, stdlib and documented user definitions
+    pub fn help(&self) -> &HelpRegistry {
+        &self.host.help
+    }
+
+    /// Make `test:snapshot` rewrite snapshots instead of comparing against them
+    pub fn set_update_snapshots(&mut self, update: bool) {
+        self.host.tests.update_snapshots = update;
+    }
+
+    /// Count how often each expression and top-level function is evaluated from now on
+    ///
+    /// Enable coverage before [`Interpreter::load_stdlib`] to include stdlib functions.
+    pub fn enable_coverage(&mut self) {
+        self.host.coverage.get_or_insert_with(Coverage::new);
+    }
+
+    /// Coverage collected since [`Interpreter::enable_coverage`], if enabled
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.host.coverage.as_ref()
+    }
+
+    /// Count calls and wall time per function from now on (see [`Profiler`])
+    pub fn enable_profiling(&mut self) {
+        self.host.profiler.get_or_insert_with(Profiler::new);
+    }
+
+    /// Calls and time collected since [`Interpreter::enable_profiling`], if enabled
+    pub fn profiler(&self) -> Option<&Profiler> {
+        self.host.profiler.as_ref()
+    }
+
+    /// Attach a step debugger: `hook` is called before each form while paused
+    ///
+    /// Evaluation runs freely until `(breakpoint)` is evaluated or
+    /// [`Interpreter::debug_step`] is called; the hook then sees each form
+    /// before it is evaluated and says whether to step, step over, continue or
+    /// abort. Threads started by `spawn` are not debugged.
+    pub fn set_debug_hook(
+        &mut self,
+        hook: impl Fn(&DebugPause) -> DebugCommand + Send + Sync + 'static,
+    ) {
+        self.host.debugger = Some(Debugger::new(Arc::new(hook)));
+    }
+
+    /// Pause before the first form of the next evaluation, if a debugger is attached
+    pub fn debug_step(&mut self) {
+        if let Some(debugger) = self.host.debugger.as_mut() {
+            debugger.step();
+        }
+    }
+
+    /// Stop stepping: run until the next `(breakpoint)`
+    ///
+    /// Stepping otherwise carries on into the next evaluation, which suits
+    /// scripts; a REPL calls this before each input it does not want to step.
+    pub fn debug_resume(&mut self) {
+        if let Some(debugger) = self.host.debugger.as_mut() {
+            debugger.resume();
+        }
+    }
+
+    /// Call `handler` with each chunk of console output as `print`, `println`, `pp` or `help` writes it
+    ///
+    /// The output is still printed, or captured when [`Interpreter::capture_output`]
+    /// is on. Like the other event callbacks, this sees only the evaluating
+    /// thread: output of threads started by `spawn` is not reported.
+    #[allow(dead_code)]
+    pub fn on_stdout(&mut self, handler: impl Fn(&str) + Send + Sync + 'static) {
+        self.host.events.stdout = Some(Arc::new(handler));
+    }
+
+    /// Call `handler` with the name of each top-level `define` or `defconstant` once it is bound
+    #[allow(dead_code)]
+    pub fn on_definition(&mut self, handler: impl Fn(&str) + Send + Sync + 'static) {
+        self.host.events.definition = Some(Arc::new(handler));
+    }
+
+    /// Call `handler` with each file or network operation the sandbox records in its audit log
+    ///
+    /// Denied and failed operations are reported too; see [`AuditEntry::outcome`].
+    #[allow(dead_code)]
+    pub fn on_io(&mut self, handler: impl Fn(&AuditEntry) + Send + Sync + 'static) {
+        self.host.events.io = Some(Arc::new(handler));
+    }
+
+    /// Call `handler` with each message a script logs at or above the log level
+    ///
+    /// Without a log file (see [`Interpreter::set_log_file`]) the lines are
+    /// then no longer written to stderr, so the host decides where they go.
+    #[allow(dead_code)]
+    pub fn on_log(&mut self, handler: impl Fn(&LogRecord) + Send + Sync + 'static) {
+        self.host.events.log = Some(Arc::new(handler));
+    }
+
+    /// Call `handler` with the value of each top-level expression that evaluates without error
+    ///
+    /// [`Interpreter::eval_str`] reports every expression in the source, not
+    /// only the last.
+    #[allow(dead_code)]
+    pub fn on_result(&mut self, handler: impl Fn(&Value) + Send + Sync + 'static) {
+        self.host.events.result = Some(Arc::new(handler));
+    }
+
+    /// Passed and failed tests across every `run-all-tests` call so far
+    pub fn test_tally(&self) -> TestTally {
+        self.host.tests.tally()
+    }
+
+    /// Steps, sandbox I/O and memory used so far; see [`ResourceUsage`]
+    #[allow(dead_code)]
+    pub fn resource_usage(&self) -> ResourceUsage {
+        let steps = self
+            .host
+            .cancel
+            .as_ref()
+            .map_or(0, CancellationToken::steps);
+        ResourceUsage::measure(&self.env, self.host.sandbox.as_deref(), steps)
+    }
+
+    /// Load every module in [`STDLIB_MODULES`]
+    ///
+    /// A module that fails does not stop the others from loading; the returned
+    /// error names each module that failed. Documented definitions are filed in
+    /// help under the module's category, e.g. "Standard Library: Core".
+    pub fn load_stdlib(&mut self) -> Result<(), String> {
+        let failures: Vec<String> = STDLIB_MODULES
+            .iter()
+            .filter_map(|(name, code)| {
+                let category = stdlib_category(name);
+                parser::set_definition_category(Some(category.clone()));
+                let result = self.eval_str(code);
+                parser::set_definition_category(None);
+                if let Some(capability) = stdlib_capability(name) {
+                    let defined: Vec<String> = self
+                        .host
+                        .help
+                        .entries()
+                        .filter(|entry| entry.category == category)
+                        .map(|entry| entry.name.clone())
+                        .collect();
+                    for name in defined {
+                        self.host.help.set_capability(&name, capability);
+                    }
+                }
+                result
+                    .err()
+                    .map(|e| format!("Failed to load stdlib module {}: {}", name, e))
+            })
+            .collect();
+
+        if failures.is_empty() {
+            Ok(())
+        } else {
+            Err(failures.join("\n"))
+        }
+    }
+
+    /// Evaluate one parsed expression in the global environment
+    ///
+    /// Runs on a thread with enough stack for the depth limit (see [`Interpreter::on_eval_stack`]).
+    pub fn eval(&mut self, expr: Value) -> Result<Value, EvalError> {
+        self.on_eval_stack(move |this| this.eval_here(expr))
+    }
+
+    fn eval_here(&mut self, expr: Value) -> Result<Value, EvalError> {
+        if let Some(token) = &self.host.cancel {
+            token.reset_steps();
+        }
+        let env = self.env.clone();
+        let macros = self.macros.clone();
+        let result = self.with_active(|| eval_with_macros(expr, env, &macros));
+        // Doc comments belong to the expression they preceded; drop any it did not use
+        parser::take_pending_docs();
+        if let (Ok(value), Some(handler)) = (&result, &self.host.events.result) {
+            handler(value);
+        }
+        result
+    }
+
+    /// Parse and evaluate every expression in `source`, returning the last value
+    pub fn eval_str(&mut self, source: &str) -> Result<Value, String> {
+        let source = source.to_string();
+        self.on_eval_stack(move |this| this.eval_str_here(&source))
+    }
+
+    fn eval_str_here(&mut self, source: &str) -> Result<Value, String> {
+        let mut result = Value::Nil;
+        let mut remaining = source.trim();
+
+        loop {
+            remaining = skip_whitespace_and_regular_comments(remaining);
+            if remaining.is_empty() {
+                return Ok(result);
+            }
+
+            let (expr, rest) = parse_one_expr(remaining)?;
+            result = self.eval(expr).map_err(|e| e.to_string())?;
+            remaining = rest;
+        }
+    }
+
+    /// Expand the macro calls in `expr` without evaluating it, returning every expansion that failed
+    pub fn expansion_errors(&mut self, expr: &Value) -> Vec<EvalError> {
+        let env = self.env.clone();
+        let macros = self.macros.clone();
+        self.with_active(|| crate::analyze::expansion_errors(expr, &macros, &env))
+    }
+
+    /// Run `job` with [`crate::cancel::EVAL_STACK_SIZE`] of stack, as [`on_eval_stack`] does
+    ///
+    /// Instead of starting a thread per call, `job` runs on this interpreter's
+    /// [`EvalWorker`], with the host state moved over for the call and back.
+    fn on_eval_stack<T: Send + 'static>(
+        &mut self,
+        job: impl FnOnce(&mut Interpreter) -> T + Send + 'static,
+    ) -> T {
+        if ON_EVAL_STACK.with(Cell::get) {
+            return job(self);
+        }
+        if self.worker.is_none() {
+            self.worker = EvalWorker::spawn();
+        }
+        let Some(worker) = &self.worker else {
+            // Without a thread to spare, evaluate here and rely on the stack being enough
+            return job(self);
+        };
+
+        let mut this = Interpreter {
+            env: self.env.clone(),
+            macros: self.macros.clone(),
+            host: std::mem::take(&mut self.host),
+            worker: None,
+        };
+        let docs = parser::take_pending_docs();
+        let source = parser::take_pending_source();
+        let category = parser::definition_category_setting();
+        let (host, docs, result) = worker.run(move || {
+            parser::set_pending_docs(docs);
+            parser::set_pending_source(source);
+            parser::set_definition_category(category);
+            // The host state comes back even if the job panics
+            let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| job(&mut this)));
+            (this.host, parser::take_pending_docs(), result)
+        });
+        self.host = host;
+        parser::set_pending_docs(docs);
+        result.unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Run `f` with this interpreter's state installed in the thread-local slots
+    fn with_active<T>(&mut self, f: impl FnOnce() -> T) -> T {
+        let _active = Activation::install(&mut self.host, &self.env);
+        f()
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Swaps an interpreter's state into the thread-local slots and back on drop
+///
+/// Restoring whatever was installed before keeps nested activations (one
+/// interpreter evaluating while another is active on the same thread) intact.
+struct Activation<'a> {
+    host: &'a mut HostState,
+    previous_env: Option<EnvRef>,
+}
+
+impl<'a> Activation<'a> {
+    fn install(host: &'a mut HostState, env: &EnvRef) -> Self {
+        host.sandbox = builtins::swap_sandbox(host.sandbox.take());
+        host.cancel = eval::swap_cancellation(host.cancel.take());
+        host.tests = testing::swap_tests(std::mem::take(&mut host.tests));
+        host.output = builtins::console::swap_capture(host.output.take());
+        host.coverage = coverage::swap_coverage(host.coverage.take());
+        host.profiler = profiler::swap_profiler(host.profiler.take());
+        host.traced = debug::swap_traced(std::mem::take(&mut host.traced));
+        host.debugger = debugger::swap_debugger(host.debugger.take());
+        host.warn_redefinitions = bindings::swap_warn_redefinitions(host.warn_redefinitions);
+        host.events = events::swap_handlers(std::mem::take(&mut host.events));
+        host.log = logging::swap_config(std::mem::take(&mut host.log));
+        let (registry, previous_env) =
+            help::swap_state(std::mem::take(&mut host.help), Some(env.clone()));
+        host.help = registry;
+        Activation { host, previous_env }
+    }
+}
+
+impl Drop for Activation<'_> {
+    fn drop(&mut self) {
+        let host = &mut *self.host;
+        host.sandbox = builtins::swap_sandbox(host.sandbox.take());
+        host.cancel = eval::swap_cancellation(host.cancel.take());
+        host.tests = testing::swap_tests(std::mem::take(&mut host.tests));
+        host.output = builtins::console::swap_capture(host.output.take());
+        host.coverage = coverage::swap_coverage(host.coverage.take());
+        host.profiler = profiler::swap_profiler(host.profiler.take());
+        host.traced = debug::swap_traced(std::mem::take(&mut host.traced));
+        host.debugger = debugger::swap_debugger(host.debugger.take());
+        host.warn_redefinitions = bindings::swap_warn_redefinitions(host.warn_redefinitions);
+        host.events = events::swap_handlers(std::mem::take(&mut host.events));
+        host.log = logging::swap_config(std::mem::take(&mut host.log));
+        let (registry, _) =
+            help::swap_state(std::mem::take(&mut host.help), self.previous_env.take());
+        host.help = registry;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{FsConfig, NetConfig};
+    use crate::fs_backend::{FsBackend, MemoryFs};
+    use std::sync::Arc;
+
+    fn assert_send<T: Send>() {}
+
+    fn memory_sandbox(fs: Arc<MemoryFs>) -> Sandbox {
+        Sandbox::with_backend(fs, FsConfig::default(), NetConfig::default())
+    }
+
+    #[test]
+    fn test_interpreter_is_send() {
+        assert_send::<Interpreter>();
+        assert_send::<Value>();
+    }
+
+    #[test]
+    fn test_runs_on_worker_thread() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        interp.eval_str("(define (square x) (* x x))").unwrap();
+
+        let result = std::thread::spawn(move || interp.eval_str("(map square '(1 2 3))"))
+            .join()
+            .unwrap();
+        assert_eq!(result.unwrap().to_string(), "(1 4 9)");
+    }
+
+    #[test]
+    fn test_interpreters_keep_separate_state() {
+        let mut a = Interpreter::new();
+        let mut b = Interpreter::new();
+
+        a.eval_str("(define x 1) (defmacro twice (e) `(begin ,e ,e))")
+            .unwrap();
+        a.eval_str("(register-test \"only-a\" (lambda () #t))")
+            .unwrap();
+
+        assert!(b.eval_str("x").is_err());
+        assert!(!b.macros().contains("twice"));
+        assert!(a.macros().contains("twice"));
+        assert_eq!(
+            b.eval_str("(map-get (run-all-tests) :total)")
+                .unwrap()
+                .to_string(),
+            "0"
+        );
+        assert_eq!(
+            a.eval_str("(map-get (run-all-tests) :total)")
+                .unwrap()
+                .to_string(),
+            "1"
+        );
+    }
+
+    #[test]
+    fn test_interleaved_interpreters_on_one_thread() {
+        let mut a = Interpreter::new();
+        a.set_sandbox(memory_sandbox(Arc::new(MemoryFs::with_files([(
+            "notes.txt",
+            "from a",
+        )]))));
+        let mut b = Interpreter::new();
+        b.set_sandbox(memory_sandbox(Arc::new(MemoryFs::new())));
+
+        // A doc comment on something other than a define belongs to nobody
+        a.eval_str(";;; Stray notes\n(+ 1 2)").unwrap();
+        b.eval_str("(define (f) 1)").unwrap();
+        assert!(b.help().get("f").is_none());
+
+        assert_eq!(
+            a.eval_str("(read-file \"notes.txt\")").unwrap().to_string(),
+            "\"from a\""
+        );
+        assert!(b.eval_str("(read-file \"notes.txt\")").is_err());
+        b.eval_str("(trace 'f)").unwrap();
+        assert_eq!(a.eval_str("(trace)").unwrap().to_string(), "()");
+    }
+
+    /// Cancel `interp`'s evaluation after a short delay, from another thread
+    fn cancel_soon(interp: &Interpreter) {
+        let token = interp.cancellation_token();
+        std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            token.cancel();
+        });
+    }
+
+    #[test]
+    fn test_cancellation_interrupts_infinite_loop() {
+        let mut interp = Interpreter::new();
+        interp.eval_str("(define (f) (f))").unwrap();
+
+        cancel_soon(&interp);
+        let result = interp.eval_str("(f)");
+        assert_eq!(result.unwrap_err(), EvalError::Interrupted.to_string());
+
+        // The interpreter is usable again once the token is reset
+        interp.cancellation_token().reset();
+        assert_eq!(interp.eval_str("(+ 1 2)").unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn test_cancellation_reaches_spawned_threads() {
+        let mut interp = Interpreter::new();
+        interp.eval_str("(define (f) (f))").unwrap();
+
+        cancel_soon(&interp);
+        let result = interp.eval_str("(join (spawn f))");
+        assert_eq!(result.unwrap_err(), EvalError::Interrupted.to_string());
+    }
+
+    #[test]
+    fn test_help_entries_belong_to_interpreter() {
+        let mut interp = Interpreter::new();
+        let found = interp.with_active(|| help::get_help("car").is_some());
+        assert!(found);
+        assert!(interp.host.help.get("car").is_some());
+    }
+
+    #[test]
+    fn test_captures_console_output() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp.eval_str(r#"(print "a" 1) (println " b")"#).unwrap();
+        assert_eq!(interp.take_output(), "a 1 b\n");
+        assert_eq!(interp.take_output(), "");
+
+        interp.capture_output(false);
+        interp.eval_str(r#"(print "")"#).unwrap();
+        assert_eq!(interp.take_output(), "");
+    }
+
+    #[test]
+    fn test_source_prints_definitions() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        interp.capture_output(true);
+        interp
+            .eval_str("(define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))")
+            .unwrap();
+        interp.eval_str("(define also fact)").unwrap();
+        interp.eval_str("(source 'also)").unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))\n"
+        );
+
+        interp.eval_str("(define sq (lambda (x) (* x x)))").unwrap();
+        interp.eval_str("(source sq)").unwrap();
+        interp.eval_str("(source 'compose)").unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(define sq (lambda (x) (* x x)))\n(define (compose f g)\n  (lambda (x) (f (g x))))\n"
+        );
+
+        let err = interp.eval_str("(source 'car)").unwrap_err();
+        assert!(
+            err.contains("car is a builtin and has no Lisp source"),
+            "{}",
+            err
+        );
+        let err = interp.eval_str("(source (lambda (x) x))").unwrap_err();
+        assert!(err.contains("has no recorded source"), "{}", err);
+
+        // Definitions keep their text, layout, quotes and comments included
+        interp.eval_str("(source 'range)").unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(define (range start end)\n  (if (>= start end)\n      '()\n      (cons start (range (+ start 1) end))))\n"
+        );
+        let written = "(define (greet name)\n  ;; says hello\n  (string-append \"hi \" name))";
+        interp
+            .eval_str(&format!(";;; Greets\n{}", written))
+            .unwrap();
+        interp.eval_str("(source 'greet)").unwrap();
+        assert_eq!(interp.take_output(), format!("{}\n", written));
+
+        // A definition made by a macro has no text and is laid out by the formatter
+        interp
+            .eval_str(
+                "(defmacro defsq (name) `(define (,name x) (if (> x 100000) x (list x (* x x) (* x x x) (+ x x x x) (- x 1)))))
+                 (defsq sq2)
+                 (source 'sq2)",
+            )
+            .unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(define (sq2 x)\n  (if (> x 100000) x (list x (* x x) (* x x x) (+ x x x x) (- x 1))))\n"
+        );
+    }
+
+    #[test]
+    fn test_introspection_builtins() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str("(defmacro unless (c body) `(if ,c nil ,body))")
+            .unwrap();
+        interp
+            .eval_str(r#"(define (greet name (greeting "Hello")) (str greeting ", " name))"#)
+            .unwrap();
+
+        let eval =
+            |interp: &mut Interpreter, code: &str| interp.eval_str(code).unwrap().to_string();
+        assert_eq!(
+            eval(
+                &mut interp,
+                "(list (procedure? car) (procedure? greet) (procedure? 'car))"
+            ),
+            "(#t #t #f)"
+        );
+        assert_eq!(
+            eval(&mut interp, "(list (lambda? greet) (lambda? car))"),
+            "(#t #f)"
+        );
+        assert_eq!(
+            eval(&mut interp, "(list (builtin? car) (builtin? greet))"),
+            "(#t #f)"
+        );
+        assert_eq!(
+            eval(&mut interp, "(list (macro? 'unless) (macro? 'car))"),
+            "(#t #f)"
+        );
+
+        assert_eq!(eval(&mut interp, "(arity car)"), "{:max 1 :min 1}");
+        assert_eq!(eval(&mut interp, "(arity +)"), "{:max nil :min 0}");
+        assert_eq!(eval(&mut interp, "(arity 'greet)"), "{:max 2 :min 1}");
+        assert_eq!(eval(&mut interp, "(arity 'unless)"), "{:max 2 :min 2}");
+
+        assert_eq!(
+            eval(&mut interp, "(function-params greet)"),
+            r#"(name (greeting "Hello"))"#
+        );
+        assert_eq!(
+            eval(&mut interp, "(function-params map-get)"),
+            "(map key [default])"
+        );
+        assert_eq!(eval(&mut interp, "(function-params 'unless)"), "(c body)");
+
+        let err = interp.eval_str("(arity 5)").unwrap_err();
+        assert!(err.contains("expected function or macro"), "{}", err);
+    }
+
+    #[test]
+    fn test_pp_follows_print_length() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp
+            .eval_str(r#"(pp '(1 2 3)) (println '("a" (b "c")))"#)
+            .unwrap();
+        assert_eq!(interp.take_output(), "(1 2 3)\n(a (b c))\n");
+
+        interp.env().define("*print-length*", Value::Number(2.0));
+        interp
+            .eval_str("(pp '(1 2 3)) (pp '(1 2 3) :full #t) (pp {:a 1 :b 2 :c 3} 40)")
+            .unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(1 2 ... (1 more))\n(1 2 3)\n{:a 1 :b 2 ... (1 more)}\n"
+        );
+        assert!(interp.eval_str("(pp 1 :full)").is_err());
+        assert!(interp.eval_str("(pp 1 :wide #t)").is_err());
+    }
+
+    #[test]
+    fn test_print_depth_elides_deep_values() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp.env().define("*print-depth*", Value::Number(2.0));
+        interp
+            .eval_str("(pp '(1 (2 (3 (4))))) (pp {:a {:b {:c 1}}}) (pp '(1 (2 (3))) :full #t)")
+            .unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(1 (2 (...)))\n{:a {:b {...}}}\n(1 (2 (3)))\n"
+        );
+
+        let deep = (0..2000).fold(Value::Nil, |inner, _| Value::List(vec![inner].into()));
+        let shown = deep.to_string();
+        assert!(shown.contains("(...)"));
+        assert!(shown.len() < 2000);
+    }
+
+    #[test]
+    fn test_session_survives_a_new_interpreter() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str("(define total 42) (define names (list \"a\" :b)) (define (f) 1)")
+            .unwrap();
+        let (json, _) = interp.save_session();
+
+        let mut restored = Interpreter::new();
+        restored.restore_session(&json).unwrap();
+        assert_eq!(restored.eval_str("total").unwrap().to_string(), "42");
+        assert_eq!(
+            restored.eval_str("names").unwrap().to_string(),
+            "(\"a\" :b)"
+        );
+        assert!(restored.eval_str("(f)").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_session_builtins() {
+        let fs = Arc::new(MemoryFs::new());
+        let sandboxed = || {
+            let mut interp = Interpreter::new();
+            interp.set_sandbox(memory_sandbox(fs.clone()));
+            interp
+        };
+
+        let mut first = sandboxed();
+        let saved = first
+            .eval_str(r#"(define x 7) (save-session "session.json")"#)
+            .unwrap();
+        assert!(matches!(saved, Value::Number(n) if n >= 1.0));
+
+        let mut second = sandboxed();
+        second.eval_str(r#"(load-session "session.json")"#).unwrap();
+        assert_eq!(second.eval_str("x").unwrap().to_string(), "7");
+        assert_eq!(fs.paths(), vec!["session.json"]);
+    }
+
+    #[test]
+    fn test_binding_introspection() {
+        let mut interp = Interpreter::new();
+        interp.set_warn_redefinitions(true);
+        let result = interp.eval_str("(define (helper) 1) (define (helper) 2) (defined? 'helper)");
+        assert_eq!(result.unwrap().to_string(), "#t");
+        assert_eq!(
+            interp.eval_str("(defined? 'nope)").unwrap().to_string(),
+            "#f"
+        );
+        assert_eq!(
+            interp
+                .eval_str("(let ((local 1)) (defined? 'local))")
+                .unwrap()
+                .to_string(),
+            "#f"
+        );
+
+        let names = interp.eval_str("(bindings)").unwrap();
+        let Value::List(names) = names else {
+            panic!("bindings should return a list");
+        };
+        let names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        assert!(names.contains(&"helper".to_string()));
+        assert!(names.contains(&"car".to_string()));
+        assert!(names.windows(2).all(|pair| pair[0] <= pair[1]));
+    }
+
+    #[test]
+    fn test_protected_globals() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        assert!(interp.protect_globals() > 0);
+
+        for code in ["(define (map f xs) xs)", "(set! + -)", "(define square 1)"] {
+            assert!(interp.eval_str(code).is_err(), "{}", code);
+        }
+        let err = interp.eval_str("(define + -)").unwrap_err().to_string();
+        assert!(
+            err.contains("cannot redefine builtin +; pass --allow-shadow-builtins to allow it"),
+            "{}",
+            err
+        );
+        // Bindings the program freezes itself still say so
+        let err = interp
+            .eval_str("(defconstant limit 1) (define limit 2)")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("cannot redefine frozen binding limit"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            interp.eval_str("(map square '(1 2))").unwrap().to_string(),
+            "(1 4)"
+        );
+        assert_eq!(
+            interp.eval_str("(let ((map 1)) map)").unwrap().to_string(),
+            "1"
+        );
+        interp
+            .eval_str("(define fresh 1) (define fresh 2)")
+            .unwrap();
+
+        // Globals that merely hold a builtin are looked up at each call
+        interp
+            .eval_str("(define op car) (define (g x) (op x)) (define op cdr)")
+            .unwrap();
+        assert_eq!(
+            interp.eval_str("(g (list 1 2))").unwrap().to_string(),
+            "(2)"
+        );
+        interp.eval_str("(set! op length)").unwrap();
+        assert_eq!(interp.eval_str("(g (list 1 2))").unwrap().to_string(), "2");
+        assert_eq!(
+            interp
+                .eval_str("(define (h x) (let ((f car)) (set! f cdr) (f x))) (h (list 1 2))")
+                .unwrap()
+                .to_string(),
+            "(2)"
+        );
+
+        // Unprotected interpreters may still redefine builtins
+        let mut open = Interpreter::new();
+        assert!(open.eval_str("(define (car x) x) (car 5)").is_ok());
+    }
+
+    #[test]
+    fn test_frozen_bindings() {
+        let fs = Arc::new(MemoryFs::new());
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(fs.clone()));
+        interp
+            .eval_str(r#"(define limits {:rows 10}) (save-session "s.json")"#)
+            .unwrap();
+
+        // The host freezes the data it provides before running sandboxed code
+        assert!(interp.env().freeze("limits"));
+        for code in [
+            "(set! limits {})",
+            "(define limits {})",
+            "(define (limits) 0)",
+            r#"(load-session "s.json")"#,
+        ] {
+            assert!(interp.eval_str(code).is_err(), "{}", code);
+        }
+        assert_eq!(
+            interp.eval_str("(frozen? 'limits)").unwrap().to_string(),
+            "#t"
+        );
+
+        // Locals may still shadow the name, and other globals are unaffected
+        let result = interp.eval_str("(let ((limits 1)) (set! limits 2) limits)");
+        assert_eq!(result.unwrap().to_string(), "2");
+        interp.eval_str("(define other 1) (freeze 'other)").unwrap();
+        assert!(interp.eval_str("(freeze 'missing)").is_err());
+        assert_eq!(interp.eval_str("limits").unwrap().to_string(), "{:rows 10}");
+    }
+
+    #[test]
+    fn test_file_builtins_with_memory_backend() {
+        let fs = Arc::new(MemoryFs::with_files([("in/a.txt", "hello")]));
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(fs.clone()));
+
+        let result = interp
+            .eval_str(
+                r#"(write-file "out.txt" (string-upper (read-file "in/a.txt")))
+                   (list (file-exists? "out.txt") (file-size "out.txt") (list-files "in"))"#,
+            )
+            .unwrap();
+        assert_eq!(result.to_string(), "(#t 5 (\"a.txt\"))");
+        assert_eq!(fs.read_file("out.txt").unwrap(), "HELLO");
+    }
+
+    #[test]
+    fn test_resource_usage() {
+        let fs = Arc::new(MemoryFs::with_files([("in/a.txt", "hello")]));
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(fs));
+
+        interp
+            .eval_str(r#"(write-file "out.txt" (read-file "in/a.txt"))"#)
+            .unwrap();
+        let result = interp.eval_str("(list (io-bytes-read) (io-bytes-written))");
+        assert_eq!(result.unwrap().to_string(), "(5 5)");
+
+        let before = interp.resource_usage();
+        assert_eq!((before.bytes_read, before.bytes_written), (5, 5));
+        interp
+            .eval_str(r#"(define big (string-repeat "x" 10000))"#)
+            .unwrap();
+        let after = interp.resource_usage();
+        assert_eq!(after.bindings, before.bindings + 1);
+        assert!(after.global_bytes > before.global_bytes + 10000);
+
+        let steps = interp.eval_str("(begin (+ 1 2) (step-count))").unwrap();
+        assert!(matches!(steps, Value::Number(n) if n > 1.0));
+        assert!(interp.resource_usage().steps > 1);
+
+        let memory = interp.eval_str("(memory-usage)").unwrap();
+        let Value::Map(memory) = memory else {
+            panic!("expected a map, got {}", memory)
+        };
+        assert_eq!(
+            memory["bindings"].to_string(),
+            interp.resource_usage().bindings.to_string()
+        );
+        assert!(memory.contains_key("global-bytes") && memory.contains_key("resident-bytes"));
+    }
+
+    #[test]
+    #[cfg(feature = "network")]
+    fn test_try_reports_sandbox_error_kinds() {
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(Arc::new(MemoryFs::new())));
+
+        let result = interp
+            .eval_str(
+                r#"(define e (try (read-file "missing.txt")))
+                   (list (error-kind e) (map-get (error-data e) :path)
+                         (error-kind (try (read-file "../x")))
+                         (error-kind (try (http-request "http://example.com" {:method "GET"}))))"#,
+            )
+            .unwrap();
+        assert_eq!(
+            result.to_string(),
+            "(:not-found \"missing.txt\" :path-not-allowed :network-disabled)"
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "network"))]
+    fn test_network_builtins_are_compiled_out() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        for name in ["http-request", "http:get-many", "http:body"] {
+            assert!(interp.eval_str(name).is_err(), "{}", name);
+            assert!(interp.help().get(name).is_none(), "{}", name);
+        }
+        assert_eq!(interp.disable_capability("network"), Ok(0));
+    }
+
+    #[test]
+    fn test_run_all_tests_reports_and_tallies() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        interp.capture_output(true);
+
+        interp
+            .eval_str(
+                r#"(define-test "ok" (assert-equal 1 1))
+                   (define-test "wrong" (assert-equal 1 2 "one"))
+                   (define-test "raises" (car 5))"#,
+            )
+            .unwrap();
+        let result = interp.eval_str("(run-all-tests)").unwrap();
+        let output = interp.take_output();
+        assert!(output.contains("  PASS ok\n"));
+        assert!(output.contains("  FAIL wrong\n       one\n"));
+        assert!(output.contains("  ERROR raises\n"));
+        assert!(output.ends_with("1 passed, 2 failed, 3 total\n"));
+        match result {
+            Value::Map(map) => assert_eq!(map["failed"].to_string(), "2"),
+            other => panic!("expected a map, got {}", other),
+        }
+
+        interp.eval_str("(run-all-tests {:quiet #t})").unwrap();
+        assert_eq!(interp.take_output(), "");
+        assert_eq!(
+            interp.test_tally(),
+            TestTally {
+                runs: 2,
+                passed: 2,
+                failed: 4
+            }
+        );
+    }
+
+    #[test]
+    fn test_snapshots() {
+        let fs = Arc::new(MemoryFs::new());
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(fs.clone()));
+
+        let snap = r#"(test:snapshot "sorted" (list 1 2 3))"#;
+        assert_eq!(interp.eval_str(snap).unwrap().to_string(), "#t");
+        assert_eq!(fs.read_file("snapshots/sorted.snap").unwrap(), "(1 2 3)\n");
+        assert_eq!(interp.eval_str(snap).unwrap().to_string(), "#t");
+
+        let changed = r#"(test:snapshot "sorted" (list 1 3 2))"#;
+        match interp.eval_str(changed).unwrap() {
+            Value::Error(err) => {
+                assert_eq!(err.kind, "assertion-failed");
+                assert!(err.message.ends_with("- (1 2 3)\n+ (1 3 2)"));
+            }
+            other => panic!("expected a failed assertion, got {}", other),
+        }
+
+        interp.set_update_snapshots(true);
+        assert_eq!(interp.eval_str(changed).unwrap().to_string(), "#t");
+        assert_eq!(fs.read_file("snapshots/sorted.snap").unwrap(), "(1 3 2)\n");
+    }
+
+    #[test]
+    fn test_step_limit() {
+        let mut interp = Interpreter::new();
+        interp.set_step_limit(Some(1000));
+        let result =
+            interp.eval_str("(define (spin n) (if (= n 0) 0 (spin (- n 1)))) (spin 100000)");
+        assert_eq!(result.unwrap_err(), "Step limit of 1000 exceeded");
+
+        // Each expression gets a fresh budget
+        assert_eq!(interp.eval_str("(spin 10)").unwrap().to_string(), "0");
+        interp.set_step_limit(None);
+        assert!(interp.eval_str("(spin 100000)").is_ok());
+    }
+
+    #[test]
+    fn test_recursion_limit_on_a_default_thread() {
+        // Non-tail recursion through let nests real evaluations; on a default
+        // 2MB thread it would overflow long before the default limit
+        let kind = std::thread::spawn(|| {
+            let mut interp = Interpreter::new();
+            interp
+                .eval_str("(define (k n) (if (= n 0) 0 (let ((r (k (- n 1)))) (+ r 1))))")
+                .unwrap();
+            assert_eq!(interp.eval_str("(k 2000)").unwrap().to_string(), "2000");
+            interp
+                .eval_str("(error-kind (try (k 20000)))")
+                .unwrap()
+                .to_string()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(kind, ":recursion-too-deep");
+    }
+
+    #[test]
+    fn test_host_state_moves_to_the_eval_worker_and_back() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp.eval_str("(print 1)").unwrap();
+        assert!(interp.worker.is_some());
+        interp.eval_str("(print 2)").unwrap();
+        assert_eq!(interp.take_output(), "12");
+    }
+
+    #[test]
+    fn test_nested_calls_stay_off_the_rust_stack() {
+        let mut interp = Interpreter::new();
+        interp.set_depth_limit(None);
+
+        // Far deeper than a test thread's stack could hold as nested evaluations
+        let result = interp.eval_str("(define (f n) (if (= n 0) 0 (+ 1 (f (- n 1))))) (f 100000)");
+        assert_eq!(result.unwrap().to_string(), "100000");
+
+        // Deep arithmetic, as generated code might nest it (dropping the tree
+        // still recurses, so not as deep)
+        let nested = (0..2_000).fold(Value::Number(0.0), |inner, _| {
+            Value::List(vec![Value::Symbol("+".into()), Value::Number(1.0), inner].into())
+        });
+        assert_eq!(interp.eval(nested).unwrap().to_string(), "2000");
+
+        // Waiting calls count toward the pending-call limit, not the depth limit
+        interp.set_depth_limit(Some(1000));
+        assert_eq!(interp.eval_str("(f 100000)").unwrap().to_string(), "100000");
+        interp.set_pending_call_limit(Some(1000));
+        let kind = interp.eval_str("(error-kind (try (f 100000)))").unwrap();
+        assert_eq!(kind.to_string(), ":recursion-too-deep");
+        assert_eq!(
+            interp.eval_str("(f 100000)").unwrap_err(),
+            "Recursion too deep: more than 1000 calls waiting for their arguments \
+             (see --max-pending-calls)"
+        );
+    }
+
+    #[test]
+    fn test_deeply_nested_source() {
+        let mut interp = Interpreter::new();
+        let arithmetic = |depth| format!("{}0{}", "(+ 1 ".repeat(depth), ")".repeat(depth));
+        let quoted = |depth| {
+            format!(
+                "(length (quote {}{}))",
+                "(".repeat(depth),
+                ")".repeat(depth)
+            )
+        };
+
+        // Past the default depth limit, since operands wait on the heap
+        let result = interp.eval_str(&arithmetic(20_000));
+        assert_eq!(result.unwrap().to_string(), "20000");
+        assert_eq!(interp.eval_str(&quoted(20_000)).unwrap().to_string(), "1");
+
+        // Too deep to parse: an error, not a stack overflow
+        for source in [
+            format!("(println {})", arithmetic(100_000)),
+            quoted(100_000),
+        ] {
+            let error = interp.eval_str(&source).unwrap_err();
+            assert!(error.contains("nested more than 30000 levels"), "{}", error);
+        }
+        assert_eq!(interp.eval_str("(+ 1 2)").unwrap().to_string(), "3");
+    }
+
+    #[test]
+    fn test_depth_limit() {
+        let mut interp = Interpreter::new();
+        // A small limit keeps the test quick
+        interp.set_depth_limit(Some(100));
+        // Let bindings evaluate in nested evaluations, unlike call operands
+        interp
+            .eval_str("(define (f n) (if (= n 0) 0 (let ((r (f (- n 1)))) (+ r 1))))")
+            .unwrap();
+        assert_eq!(interp.eval_str("(f 20)").unwrap().to_string(), "20");
+        assert_eq!(
+            interp.eval_str("(f 1000)").unwrap_err(),
+            "Recursion too deep: more than 100 nested evaluations"
+        );
+
+        // Catchable, and the interpreter keeps working afterwards
+        let kind = interp.eval_str("(error-kind (try (f 1000)))").unwrap();
+        assert_eq!(kind.to_string(), ":recursion-too-deep");
+        assert_eq!(interp.eval_str("(f 30)").unwrap().to_string(), "30");
+
+        // Tail calls do not nest
+        let result =
+            interp.eval_str("(define (spin n) (if (= n 0) 'done (spin (- n 1)))) (spin 10000)");
+        assert_eq!(result.unwrap().to_string(), "done");
+    }
+
+    #[test]
+    #[cfg(feature = "network")]
+    fn test_disable_capability() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        assert!(interp.disable_capability("network").unwrap() > 1);
+
+        let err = interp.eval_str(r#"(http-request "http://example.com" {})"#);
+        assert!(err.unwrap_err().contains("Undefined symbol: http-request"));
+        assert!(interp.help().get("http:get-many").is_none());
+        assert!(interp.eval_str("http:body").is_err());
+        assert!(interp.eval_str("(+ 1 2)").is_ok());
+
+        assert!(interp.disable_capability("telepathy").is_err());
+    }
+
+    #[test]
+    fn test_disabling_every_capability_removes_every_sandbox_function() {
+        let expected = vec![
+            ("console", vec!["print", "println", "pp"]),
+            (
+                "filesystem",
+                vec![
+                    "read-file",
+                    "write-file",
+                    "file-exists?",
+                    "file-size",
+                    "list-files",
+                    "file-stat",
+                    "test:snapshot",
+                    "vstore:save",
+                    "vstore:load",
+                ],
+            ),
+            ("sessions", vec!["save-session", "load-session"]),
+            (
+                "concurrency",
+                vec![
+                    "spawn",
+                    "join",
+                    "make-channel",
+                    "channel-send",
+                    "channel-recv",
+                ],
+            ),
+            #[cfg(feature = "network")]
+            (
+                "network",
+                vec![
+                    "http-request",
+                    "http:get-many",
+                    "http:with-retry",
+                    "http:stream",
+                    "http:check-status",
+                    "http:body",
+                    "http:status",
+                    "llm:chat",
+                    "ws:connect",
+                    "ws:send",
+                    "ws:recv",
+                    "ws:close",
+                ],
+            ),
+        ];
+
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        for (module, names) in &expected {
+            for name in names {
+                assert_eq!(interp.help().capability(name), Some(*module), "{}", name);
+            }
+        }
+        // Every tagged function is listed above, so the check below covers them all
+        for module in CAPABILITY_MODULES {
+            for name in interp.help().in_capability(module) {
+                let listed = expected
+                    .iter()
+                    .any(|(m, names)| m == module && names.contains(&name.as_str()));
+                assert!(listed, "{} in {} is not listed", name, module);
+            }
+        }
+
+        // With only the console left, nothing may touch the disk or the network
+        for module in CAPABILITY_MODULES.iter().filter(|m| **m != "console") {
+            interp.disable_capability(module).unwrap();
+        }
+        for (module, names) in &expected {
+            for name in names {
+                let defined = interp.eval_str(name).is_ok();
+                assert_eq!(defined, *module == "console", "{}", name);
+            }
+        }
+        let err = interp.eval_str(r#"(test:snapshot "greeting" "hello")"#);
+        assert!(err.unwrap_err().contains("Undefined symbol: test:snapshot"));
+        assert!(interp.eval_str("(+ 1 2)").is_ok());
+    }
+
+    #[test]
+    fn test_disable_filesystem_removes_vector_store_files() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        assert!(interp.disable_capability("filesystem").unwrap() > 1);
+        for name in ["read-file", "vstore:save", "vstore:load"] {
+            assert!(interp.eval_str(name).is_err(), "{}", name);
+        }
+        assert!(interp.eval_str("(vstore:create)").is_ok());
+    }
+
+    #[test]
+    fn test_audit_entries_builtin() {
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(Arc::new(MemoryFs::new())));
+
+        interp.eval_str(r#"(read-file "../secret.txt")"#).ok();
+        let entry = interp.eval_str("(car (audit-entries))").unwrap();
+        match entry {
+            Value::Map(map) => {
+                assert_eq!(map["operation"].to_string(), "\"read-file\"");
+                assert_eq!(map["target"].to_string(), "\"../secret.txt\"");
+                assert_eq!(map["outcome"].to_string(), "\"denied\"");
+                assert!(map.contains_key("duration-ms"));
+            }
+            other => panic!("expected a map, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_exported_docs_include_user_definitions() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str(r#"(define (twice x) "Double a number" (* 2 x))"#)
+            .unwrap();
+
+        let json: serde_json::Value = serde_json::from_str(&interp.help().export_json()).unwrap();
+        let categories = json.as_array().unwrap();
+        assert_eq!(categories[0]["category"], "Special forms");
+        let user = categories
+            .iter()
+            .find(|c| c["category"] == "User Defined")
+            .unwrap();
+        assert_eq!(user["entries"][0]["name"], "twice");
+        assert_eq!(user["entries"][0]["description"], "Double a number");
+
+        let markdown = interp.help().export_markdown();
+        assert!(markdown.starts_with("# Function Reference\n"));
+        assert!(markdown.contains("### twice\n\n`(twice x)`\n\nDouble a number"));
+    }
+
+    #[test]
+    fn test_stdlib_docs_come_from_module_comments() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        interp
+            .eval_str(r#"(define (twice x) "Double a number" (* 2 x))"#)
+            .unwrap();
+
+        let compose = interp.host.help.get("compose").unwrap();
+        assert_eq!(compose.category, "Standard Library: Core");
+        assert_eq!(compose.signature, "(compose f g)");
+        assert!(compose
+            .description
+            .contains("**Notes:** Useful for building function pipelines."));
+        assert_eq!(
+            interp.host.help.get("abs").unwrap().category,
+            "Standard Library: Math"
+        );
+        assert_eq!(
+            interp.host.help.get("twice").unwrap().category,
+            "User Defined"
+        );
+    }
+
+    #[test]
+    fn test_lisp_fallbacks_replace_native_higher_order_functions() {
+        let mut interp = Interpreter::new();
+        interp.load_stdlib().unwrap();
+        for name in ["map", "filter", "reduce"] {
+            let lisp = matches!(interp.env().get(name), Some(Value::Lambda { .. }));
+            assert_eq!(lisp, cfg!(feature = "lisp-fallbacks"), "{}", name);
+        }
+        // Either way single-list calls give the same results
+        for (code, expected) in [
+            ("(map (lambda (x) (* x 2)) '(1 2 3))", "(2 4 6)"),
+            ("(filter even? '(1 2 3 4 5))", "(2 4)"),
+            (
+                "(reduce (lambda (acc x) (cons x acc)) '() '(1 2 3))",
+                "(3 2 1)",
+            ),
+        ] {
+            assert_eq!(
+                interp.eval_str(code).unwrap().to_string(),
+                expected,
+                "{}",
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_coverage() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str(
+                "(define (sign n) (if (< n 0) (- n 1) (car (list n)))) (define (unused) (+ 1 2))",
+            )
+            .unwrap();
+        assert!(interp.coverage().is_none());
+
+        interp.enable_coverage();
+        interp
+            .eval_str(
+                "(define (sign n) (if (< n 0) (- n 1) (car (list n)))) (define (unused) (+ 1 2))",
+            )
+            .unwrap();
+        interp.eval_str("(sign 5) (map sign '(2 3))").unwrap();
+
+        let functions = interp.coverage().unwrap().functions();
+        assert_eq!(functions.len(), 2);
+        assert_eq!(functions[0].name, "sign");
+        assert_eq!(functions[0].calls, 3);
+        assert_eq!(functions[0].missed, vec!["(- n 1)"]);
+        assert_eq!((functions[1].calls, functions[1].covered), (0, 0));
+        assert!(interp
+            .coverage()
+            .unwrap()
+            .report()
+            .contains("sign: (- n 1)"));
+    }
+
+    #[test]
+    fn test_coverage_keeps_identical_bodies_apart() {
+        let mut interp = Interpreter::new();
+        interp.enable_coverage();
+        interp
+            .eval_str(
+                "(define (twice n) (if (> n 0) (* n 2) 0)) \
+                 (define (double n) (if (> n 0) (* n 2) 0)) \
+                 (twice 1) (twice 2) (double -1)",
+            )
+            .unwrap();
+
+        let functions = interp.coverage().unwrap().functions();
+        assert_eq!(functions[0].name, "twice");
+        assert_eq!(functions[0].calls, 2);
+        assert!(functions[0].missed.is_empty());
+        assert_eq!(functions[1].name, "double");
+        assert_eq!(functions[1].calls, 1);
+        assert_eq!(functions[1].missed, vec!["(* n 2)"]);
+
+        // A lambda created when the function runs counts toward the function's body
+        interp
+            .eval_str("(define (doubled xs) (map (lambda (x) (* x 2)) xs)) (doubled '(1 2))")
+            .unwrap();
+        let functions = interp.coverage().unwrap().functions();
+        assert_eq!(functions[2].name, "doubled");
+        assert!(functions[2].missed.is_empty(), "{:?}", functions[2].missed);
+    }
+
+    #[test]
+    fn test_profiling() {
+        let mut interp = Interpreter::new();
+        interp.enable_profiling();
+        interp
+            .eval_str(
+                "(define (count-down n) (if (= n 0) 0 (count-down (- n 1))))
+                 (define (inc x) (+ x 1))
+                 (count-down 50)
+                 (map inc '(1 2 3))",
+            )
+            .unwrap();
+
+        let profile = interp.profiler().unwrap();
+        assert_eq!(profile.stats("count-down").unwrap().calls, 51);
+        assert_eq!(profile.stats("inc").unwrap().calls, 3);
+        assert_eq!(profile.stats("map").unwrap().calls, 1);
+        assert_eq!(profile.stats("=").unwrap().calls, 51);
+
+        interp.capture_output(true);
+        let result = interp.eval_str("(profile (map inc '(1 2)))").unwrap();
+        assert_eq!(result.to_string(), "(2 3)");
+        let table = interp.take_output();
+        assert!(table.starts_with("function"));
+        assert!(table.lines().any(|line| line.starts_with("inc ")));
+        assert_eq!(interp.profiler().unwrap().stats("inc").unwrap().calls, 5);
+    }
+
+    #[test]
+    fn test_time_and_bench() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp
+            .eval_str("(define runs 0) (define (tick) (set! runs (+ runs 1)))")
+            .unwrap();
+
+        let result = interp.eval_str("(time (+ 1 2))").unwrap();
+        assert_eq!(result.to_string(), "3");
+        let printed = interp.take_output();
+        assert!(printed.starts_with("Elapsed time: "), "{}", printed);
+        assert!(printed.ends_with(" ms\n"), "{}", printed);
+
+        let stats = match interp.eval_str("(bench (tick) 4)").unwrap() {
+            Value::Map(stats) => stats,
+            other => panic!("expected a map, got {}", other),
+        };
+        assert_eq!(interp.eval_str("runs").unwrap().to_string(), "4");
+        assert_eq!(stats["iterations"].to_string(), "4");
+        let ms = |key: &str| match stats[key] {
+            Value::Number(n) => n,
+            ref other => panic!("expected a number for {}, got {}", key, other),
+        };
+        assert!(ms("min") <= ms("mean") && ms("mean") <= ms("max"));
+        assert!((ms("total") - 4.0 * ms("mean")).abs() < 1e-9);
+
+        let err = interp.eval_str("(bench (tick) 0)").unwrap_err();
+        assert!(err.contains("expected positive integer"), "{}", err);
+    }
+
+    #[test]
+    fn test_trace() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp
+            .eval_str("(define (fact n) (if (<= n 1) 1 (* n (fact (- n 1)))))")
+            .unwrap();
+
+        let traced = interp.eval_str("(trace 'fact 'car)").unwrap();
+        assert_eq!(traced.to_string(), "(car fact)");
+        interp.eval_str("(fact 2) (map car '((7)))").unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(fact 2)\n| (fact 1)\n| fact => 1\nfact => 2\n(car (7))\ncar => 7\n"
+        );
+
+        interp.eval_str("(untrace) (fact 2)").unwrap();
+        assert_eq!(interp.take_output(), "");
+        assert!(interp.eval_str("(trace 'undefined-fn)").is_err());
+        assert!(interp.eval_str("(trace \"fact\")").is_err());
+    }
+
+    #[test]
+    fn test_trace_macros() {
+        let mut interp = Interpreter::new();
+        interp.capture_output(true);
+        interp
+            .eval_str(
+                "(defmacro my-unless (c e) `(if ,c nil ,e))
+                 (defmacro both (c e) `(list (my-unless ,c ,e) ,e))",
+            )
+            .unwrap();
+
+        let traced = interp.eval_str("(trace-macro 'my-unless)").unwrap();
+        assert_eq!(traced.to_string(), "(my-unless)");
+        interp.eval_str("(both #f 3)").unwrap();
+        assert_eq!(interp.take_output(), "(my-unless #f 3) ==> (if #f nil 3)\n");
+
+        interp.eval_str("(untrace-macro)").unwrap();
+        interp.set_trace_macros(true);
+        interp.eval_str("(both #f 3)").unwrap();
+        assert_eq!(
+            interp.take_output(),
+            "(both #f 3) ==> (list (my-unless #f 3) 3)\n(my-unless #f 3) ==> (if #f nil 3)\n"
+        );
+        assert!(interp.eval_str("(trace-macro 'car)").is_err());
+    }
+
+    #[test]
+    fn test_macro_depth_limit() {
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str(
+                "(defmacro forever (x) `(forever ,x))
+                 (defmacro expander-loop (x) (expander-loop x))
+                 (defmacro wrap (x) `(list ,x))
+                 (defmacro wrap-twice (x) `(wrap (wrap ,x)))",
+            )
+            .unwrap();
+
+        let err = interp.eval_str("(forever 1)").unwrap_err();
+        assert!(err.contains("exceeded the depth limit of 100"), "{}", err);
+        // An expander that calls itself nests evaluations, which use more stack
+        interp.set_macro_depth_limit(50);
+        assert!(interp.eval_str("(expander-loop 1)").is_err());
+
+        interp.set_macro_depth_limit(1);
+        assert!(interp.eval_str("(wrap-twice 1)").is_err());
+        interp.set_macro_depth_limit(2);
+        assert_eq!(
+            interp.eval_str("(wrap-twice 1)").unwrap().to_string(),
+            "((1))"
+        );
+    }
+
+    #[test]
+    fn test_debug_hook() {
+        use std::sync::Mutex;
+
+        let mut interp = Interpreter::new();
+        interp
+            .eval_str("(define (sq x) (* x x)) (define (f x) (begin (breakpoint) (+ (sq x) 1)))")
+            .unwrap();
+        // Without a debugger, breakpoints do nothing
+        assert_eq!(interp.eval_str("(f 2)").unwrap().to_string(), "5");
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&seen);
+        interp.set_debug_hook(move |pause| {
+            let x = pause.env.get("x").map(|v| v.to_string());
+            log.lock().unwrap().push((pause.expr.to_string(), x));
+            DebugCommand::Step
+        });
+        assert_eq!(interp.eval_str("(f 3)").unwrap().to_string(), "10");
+        let pauses = seen.lock().unwrap().clone();
+        assert_eq!(pauses[0].1.as_deref(), Some("3"));
+        assert!(pauses[0].0.ends_with(" (sq x) 1)"));
+        assert_eq!(pauses.len(), 3);
+
+        // Stepping carries on into the next evaluation until resumed
+        interp.eval_str("(sq 1)").unwrap();
+        assert_eq!(seen.lock().unwrap().len(), 5);
+        interp.debug_resume();
+        interp.eval_str("(sq 1)").unwrap();
+        assert_eq!(seen.lock().unwrap().len(), 5);
+
+        interp.set_debug_hook(|_| DebugCommand::Abort);
+        interp.debug_step();
+        assert!(matches!(interp.eval_str("(f 3)"), Err(e) if e == "Interrupted"));
+    }
+
+    #[test]
+    fn test_event_callbacks() {
+        use std::sync::Mutex;
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut interp = Interpreter::new();
+        interp.set_sandbox(memory_sandbox(Arc::new(MemoryFs::new())));
+        interp.capture_output(true);
+        let log = Arc::clone(&events);
+        interp.on_stdout(move |text| log.lock().unwrap().push(format!("stdout {:?}", text)));
+        let log = Arc::clone(&events);
+        interp.on_definition(move |name| log.lock().unwrap().push(format!("define {}", name)));
+        let log = Arc::clone(&events);
+        interp.on_io(move |entry| {
+            let event = format!("io {} {}", entry.operation, entry.outcome.as_str());
+            log.lock().unwrap().push(event);
+        });
+        let log = Arc::clone(&events);
+        interp.on_result(move |value| log.lock().unwrap().push(format!("result {}", value)));
+
+        interp
+            .eval_str(
+                r#"(define (f x) (let ((y 1)) (+ x y)))
+                   (println "hi")
+                   (write-file "out.txt" "data")
+                   (f 1)"#,
+            )
+            .unwrap();
+        assert!(interp.eval_str("(car 1)").is_err());
+        assert_eq!(
+            *events.lock().unwrap(),
+            vec![
+                "define f",
+                "result f",
+                "stdout \"hi\\n\"",
+                "result nil",
+                "io write-file ok",
+                "result #t",
+                "result 2",
+            ]
+        );
+        // Capturing still works alongside the callback
+        assert_eq!(interp.take_output(), "hi\n");
+    }
+
+    #[test]
+    fn test_log_settings_are_per_interpreter() {
+        use std::sync::Mutex;
+
+        let fs = Arc::new(MemoryFs::new());
+        let mut to_file = Interpreter::new();
+        to_file.set_sandbox(memory_sandbox(fs.clone()));
+        to_file.set_log_level(Some(LogLevel::Debug));
+        to_file.set_log_file(Some("run.log".to_string()));
+
+        let records = Arc::new(Mutex::new(Vec::new()));
+        let mut to_host = Interpreter::new();
+        let sink = Arc::clone(&records);
+        to_host.on_log(move |record| sink.lock().unwrap().push(record.line()));
+
+        to_file.eval_str(r#"(log:debug "one" {:n 1})"#).unwrap();
+        to_host
+            .eval_str(r#"(log:debug "hidden") (log:warn "two")"#)
+            .unwrap();
+        to_file.eval_str(r#"(log:error "three")"#).unwrap();
+
+        let contents = fs.read_file("run.log").unwrap();
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].ends_with(r#" DEBUG one {"n":1.0}"#));
+        assert!(lines[1].ends_with(" ERROR three"));
+        let records = records.lock().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].ends_with(" WARN two"));
+    }
+}
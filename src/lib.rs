@@ -34,14 +34,32 @@
 //! ### Core Components
 //!
 //! - **[eval]**: Main evaluator with TCO via trampolining
+//! - **[analyze]**: Special form resolution and one-time macro expansion of function bodies
+//! - **[destructure]**: List and map patterns in `let` bindings and function parameters
+//! - **[optimize]**: Constant folding, dead-branch removal, and builtin pre-resolution for function bodies
 //! - **[parser]**: S-expression parser using nom combinator library
 //! - **[mod@env]**: Environment (scope) management with parent-chain lookup
 //! - **[value]**: Core value types (Number, String, Symbol, List, Lambda, Macro, Error, BuiltIn)
+//! - **[symbol]**: Interned symbol names shared by values and environments
 //! - **[help]**: Help system with hybrid lookup (registry + environment)
+//! - **[interpreter]**: Owned, `Send` interpreter context for embedding hosts
+//! - **[policy]**: Sandbox policy files (`--config`): paths, network, limits and capability modules
+//! - **[platform]**: Live HTTP and the clock, native (ureq, `std::time`) or in the browser (`wasm` feature)
+//! - **[fs_backend]**: Filesystem storage behind the sandbox (cap-std directories or in-memory)
+//! - **[cancel]**: Cancellation token for aborting a running evaluation (Ctrl-C)
+//! - **[events]**: Host callbacks streaming console output, definitions, sandbox I/O and results
+//! - **[debugger]**: Step debugger hooks (`breakpoint`, `--debug`); the host provides the prompt
+//! - **[coverage]**: Function and expression hit counts (`--coverage`)
+//! - **[mod@fmt]**: Comment-preserving source formatter (`lisp-llm-sandbox fmt`)
+//! - **[pretty]**: Line-width aware pretty-printer (`pp`, long REPL results)
+//! - **[profiler]**: Call counts and wall time per function (`profile`, `--profile-calls`)
+//! - **[server]**: JSON request/response protocol with per-session interpreters (`--serve-stdio`, `--serve-http`)
+//! - **[session]**: Saving and restoring top-level data bindings (`save-session`, `load-session`)
+//! - **[tools]**: Native tool trait, and LLM tool manifests generated from the help registry
 //!
 //! ### Built-in Functions (32 total)
 //!
-//! **Arithmetic** (5): +, -, *, /, %
+//! **Arithmetic** (6): +, -, *, /, %, divmod
 //!
 //! **Comparison** (5): =, <, >, <=, >=
 //!
@@ -49,28 +67,48 @@
 //!
 //! **Type Predicates** (6): number?, string?, list?, nil?, symbol?, bool?
 //!
-//! **List Operations** (6): cons, car, cdr, list, length, empty?
+//! **List Operations** (13): cons, car, cdr, list, length, empty?, flatten, partition, group-by,
+//! frequencies, distinct, interleave, chunk
 //!
-//! **Console I/O** (2): print, println
+//! **Console I/O** (3): print, println, pp
 //!
 //! **File I/O** (5): read-file, write-file, file-exists?, file-size, list-files
 //!
-//! **Network I/O** (2): http-get, http-post
+//! **Bindings** (5): freeze, frozen?, deep-copy, defined?, bindings
 //!
-//! **Error Handling** (3): error, error?, error-msg
+//! **Network I/O** (1): http-request
 //!
-//! **Help System** (2): help, doc
+//! **Audit** (1): audit-entries
 //!
-//! ### Special Forms (8)
+//! **Debugging** (5): trace, untrace, trace-macro, untrace-macro, breakpoint
+//!
+//! **Error Handling** (5): error, error?, error-msg, error-kind, error-data
+//!
+//! **Help System** (6): help, doc, source, help-search, help-category, apropos
+//!
+//! **Introspection** (6): procedure?, lambda?, builtin?, macro?, arity, function-params
+//!
+//! **Resource usage** (4): memory-usage, step-count, io-bytes-read, io-bytes-written
+//!
+//! ### Special Forms (23)
 //!
 //! - **define**: Variable and function definitions
 //! - **lambda**: Anonymous functions with lexical closure
 //! - **if**: Conditional evaluation with short-circuit behavior
+//! - **case**: Dispatch on a value against literal datums, with an `else` clause
 //! - **begin**: Sequence multiple expressions
 //! - **let**: Local variable bindings
 //! - **quote**: Prevent evaluation of expressions
 //! - **quasiquote**: Selective evaluation within templates
 //! - **defmacro**: Compile-time code transformations
+//! - **delay**: Memoized promises, evaluated with `force`
+//! - **set!**: Update an existing variable
+//! - **defconstant**: Define a variable that cannot be changed or redefined
+//! - **while**, **dotimes**, **dolist**: Loops, controlled with **break** and **continue**
+//! - **try**: Catch a raised error as an error value
+//! - **->**, **->>**, **some->**: Threading pipelines; `some->` stops at nil and failed results
+//! - **profile**: Call counts and wall time per function while evaluating an expression
+//! - **time**, **bench**: Wall time of one evaluation, or min/mean/max over repeated runs
 //!
 //! ### Standard Library (27 functions)
 //!
@@ -84,6 +122,8 @@
 //!
 //! **Math** (9): abs, min, max, square, cube, even?, odd?, sum, product, factorial
 //!
+//! **Results** (5): ok, err, ok?, err?, unwrap-or
+//!
 //! ## Key Technical Details
 //!
 //! ### Tail-Call Optimization
@@ -94,7 +134,7 @@
 //!
 //! ### Lexical Closures
 //!
-//! Lambda functions capture their definition-time environment via `Rc<Environment>`.
+//! Lambda functions capture their definition-time environment via `EnvRef`.
 //! This enables proper closure semantics with parent-chain lookup for variable resolution.
 //!
 //! ### Help System
@@ -103,6 +143,14 @@
 //! thread-local environment for user-defined functions. The `help` function provides
 //! comprehensive documentation including examples and related functions.
 //!
+//! ### Embedding
+//!
+//! [`interpreter::Interpreter`] owns an environment, macro registry, sandbox, help
+//! registry and test registry. It is `Send`: environments use `Arc` and `RwLock`,
+//! and the thread-local slots builtins read from are filled from the interpreter
+//! only while it is evaluating. Hosts can run one interpreter per worker thread or
+//! share one behind a `Mutex`.
+//!
 //! ### Macro System
 //!
 //! Macros expand before evaluation. Unlike functions (which evaluate arguments first),
@@ -120,19 +168,39 @@
 //! ## Error Handling
 //!
 //! Errors are catchable values, not exceptions. The `error` function creates an Error
-//! value, `error?` checks for errors, and `error-msg` extracts messages. This enables
-//! graceful error handling in Lisp code.
+//! value, `error?` checks for errors, and `error-msg` extracts messages. Each error
+//! has a kind keyword (`error-kind`) and optional data map (`error-data`); `try`
+//! turns a raised error into a value whose kind (`:not-found`, `:type-error`, ...)
+//! comes from the `EvalError` or `SandboxError` behind it. This enables graceful
+//! error handling in Lisp code.
 
+pub mod analyze;
 pub mod builtins;
+pub mod cancel;
 pub mod config;
+pub mod coverage;
+pub mod debugger;
+pub mod destructure;
 pub mod env;
 pub mod error;
 pub mod eval;
+pub mod events;
+pub mod fmt;
+pub mod fs_backend;
 pub mod help;
+pub mod interpreter;
 pub mod macros;
+pub mod optimize;
 pub mod parser;
+pub mod platform;
+pub mod policy;
+pub mod pretty;
+pub mod profiler;
 pub mod sandbox;
+pub mod server;
+pub mod session;
 pub mod stdlib;
-pub mod stdlib_registry;
+pub mod symbol;
 pub mod tools;
+pub mod usage;
 pub mod value;
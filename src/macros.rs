@@ -1,11 +1,33 @@
 // ABOUTME: Macro registry for storing and retrieving macro definitions
 
+use crate::symbol::Symbol;
 use crate::value::Value;
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock};
 
+/// How many macro expansions may be in progress at once before expansion fails
+///
+/// Counts both a macro expanding into another macro call and a macro whose
+/// expander uses macros, so a macro that expands into itself stops here.
+pub const DEFAULT_MAX_EXPANSION_DEPTH: usize = 100;
+
+/// A macro definition: parameter names, body and optional docstring
+#[derive(Debug, Clone)]
+pub struct MacroDef {
+    pub params: Vec<Symbol>,
+    pub body: Value,
+    pub docstring: Option<String>,
+}
+
+/// Macro definitions visible to an evaluation
+///
+/// Cloning a registry yields another handle to the same definitions, so builtins
+/// that call back into the evaluator see (and can add to) the caller's macros.
 #[derive(Debug, Clone)]
 pub struct MacroRegistry {
-    macros: HashMap<String, (Vec<String>, Value)>,
+    macros: Arc<RwLock<HashMap<Symbol, MacroDef>>>,
+    max_depth: Arc<AtomicUsize>,
 }
 
 impl Default for MacroRegistry {
@@ -17,16 +39,71 @@ impl Default for MacroRegistry {
 impl MacroRegistry {
     pub fn new() -> Self {
         MacroRegistry {
-            macros: HashMap::new(),
+            macros: Arc::new(RwLock::new(HashMap::new())),
+            max_depth: Arc::new(AtomicUsize::new(DEFAULT_MAX_EXPANSION_DEPTH)),
         }
     }
 
-    pub fn define(&mut self, name: String, params: Vec<String>, body: Value) {
-        self.macros.insert(name, (params, body));
+    /// Most expansions that may be in progress at once
+    pub fn max_depth(&self) -> usize {
+        self.max_depth.load(Ordering::Relaxed)
+    }
+
+    /// Change the expansion depth limit for every handle to this registry
+    pub fn set_max_depth(&self, depth: usize) {
+        self.max_depth.store(depth, Ordering::Relaxed);
+    }
+
+    #[allow(dead_code)]
+    pub fn define(&self, name: impl Into<Symbol>, params: Vec<Symbol>, body: Value) {
+        self.define_documented(name, params, body, None);
+    }
+
+    /// Define a macro along with the docstring shown by `help`
+    pub fn define_documented(
+        &self,
+        name: impl Into<Symbol>,
+        params: Vec<Symbol>,
+        body: Value,
+        docstring: Option<String>,
+    ) {
+        self.macros
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(
+                name.into(),
+                MacroDef {
+                    params,
+                    body,
+                    docstring,
+                },
+            );
+    }
+
+    pub fn get(&self, name: &str) -> Option<MacroDef> {
+        self.macros
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(name)
+            .cloned()
+    }
+
+    /// Names of every defined macro
+    pub fn names(&self) -> Vec<Symbol> {
+        self.macros
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .keys()
+            .cloned()
+            .collect()
     }
 
-    pub fn get(&self, name: &str) -> Option<(Vec<String>, Value)> {
-        self.macros.get(name).cloned()
+    /// Whether a macro with this name is defined (without cloning its definition)
+    pub fn contains(&self, name: &str) -> bool {
+        self.macros
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .contains_key(name)
     }
 }
 
@@ -36,18 +113,43 @@ mod tests {
 
     #[test]
     fn test_macro_registry_define_and_get() {
-        let mut registry = MacroRegistry::new();
+        let registry = MacroRegistry::new();
 
-        let params = vec!["x".to_string()];
-        let body = Value::Symbol("x".to_string());
+        let params = vec![Symbol::new("x")];
+        let body = Value::Symbol("x".into());
 
         registry.define("test-macro".to_string(), params.clone(), body.clone());
 
         let result = registry.get("test-macro");
         assert!(result.is_some());
 
-        let (retrieved_params, _retrieved_body) = result.unwrap();
-        assert_eq!(retrieved_params, params);
+        let def = result.unwrap();
+        assert_eq!(def.params, params);
+        assert!(def.docstring.is_none());
+    }
+
+    #[test]
+    fn test_macro_registry_keeps_docstring() {
+        let registry = MacroRegistry::new();
+        registry.define_documented(
+            "documented",
+            vec![],
+            Value::Nil,
+            Some("Does nothing".to_string()),
+        );
+
+        let def = registry.get("documented").unwrap();
+        assert_eq!(def.docstring.as_deref(), Some("Does nothing"));
+    }
+
+    #[test]
+    fn test_macro_registry_clones_share_definitions() {
+        let registry = MacroRegistry::new();
+        let handle = registry.clone();
+
+        registry.define("later".to_string(), vec![], Value::Nil);
+
+        assert!(handle.get("later").is_some());
     }
 
     #[test]
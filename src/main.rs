@@ -1,148 +1,627 @@
+mod analyze;
 mod builtins;
+mod cancel;
 mod config;
+mod coverage;
+mod debugger;
+mod destructure;
 mod env;
 mod error;
 mod eval;
+mod events;
+mod fmt;
+mod fs_backend;
 mod help;
 mod highlighter;
+mod interpreter;
 mod macros;
+mod optimize;
 mod parser;
+mod platform;
+mod policy;
+mod pretty;
+mod profiler;
 mod sandbox;
+mod server;
+mod session;
 mod stdlib;
-mod stdlib_registry;
+mod symbol;
 mod tools;
+mod usage;
 mod value;
 
-use builtins::{register_builtins, set_sandbox_storage};
 use clap::Parser;
-use config::{FsConfig, NetConfig, WELCOME_FOOTER, WELCOME_MESSAGE, WELCOME_SUBTITLE};
-use env::Environment;
-use eval::eval_with_macros;
-use highlighter::LispHelper;
-use macros::MacroRegistry;
-use parser::parse;
+use config::{IoConfig, Profile, WELCOME_FOOTER, WELCOME_MESSAGE, WELCOME_SUBTITLE};
+use highlighter::{LispHelper, Theme};
+use interpreter::Interpreter;
+use parser::{parse, parse_one_expr, skip_whitespace_and_regular_comments};
+use policy::Policy;
+use pretty::{
+    print_limits, DEFAULT_PRINT_DEPTH, DEFAULT_PRINT_LENGTH, PRINT_DEPTH_VAR, PRINT_LENGTH_VAR,
+};
 use rustyline::error::ReadlineError;
 use rustyline::{Config, Editor};
-use sandbox::Sandbox;
+use sandbox::{Cassette, HttpCache, Sandbox};
+use std::io::IsTerminal;
 use std::path::PathBuf;
-use std::rc::Rc;
-use stdlib::register_stdlib;
-use stdlib_registry::register_stdlib_functions;
+use std::sync::Arc;
+use stdlib::llm::{LlmClient, LlmProvider};
+use stdlib::log::LogLevel;
+use value::Value;
 
 /// Lisp interpreter with sandboxed I/O capabilities
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Default)]
 #[command(name = "lisp-llm-sandbox")]
 #[command(version = config::VERSION)]
 #[command(about = "A production-ready Scheme-flavored Lisp interpreter")]
 #[command(long_about = "An interpreter with capability-based I/O sandboxing")]
 struct CliArgs {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Script file to execute (optional - if not provided, starts REPL)
     #[arg(value_name = "FILE")]
     script: Option<PathBuf>,
 
+    /// Start from a preset: strict (no files, no network, step limit), default, or trusted (home dir + network)
+    #[arg(long = "profile", value_name = "PROFILE")]
+    profile: Option<ProfileArg>,
+
+    /// Load sandbox policy (paths, network, limits, capability modules) from a TOML file
+    #[arg(long = "config", value_name = "FILE")]
+    config: Option<PathBuf>,
+
     /// Add allowed filesystem path (can be repeated)
     #[arg(long = "fs-sandbox", value_name = "PATH", action = clap::ArgAction::Append)]
     fs_paths: Vec<PathBuf>,
 
-    /// Maximum file size in bytes
-    #[arg(
-        long = "max-file-size",
-        value_name = "BYTES",
-        default_value = "10485760"
-    )]
-    max_file_size: usize,
+    /// Maximum file size, in bytes or with a unit like 512KB [default: 10MB]
+    #[arg(long = "max-file-size", value_name = "SIZE", value_parser = parse_size_arg)]
+    max_file_size: Option<usize>,
+
+    /// Fail any top-level expression that takes more than N evaluation steps
+    #[arg(long = "max-steps", value_name = "N")]
+    max_steps: Option<u64>,
+
+    /// Fail with "recursion too deep" once N evaluations are nested; 0 for no limit [default: 10000]
+    #[arg(long = "max-recursion-depth", value_name = "N")]
+    max_recursion_depth: Option<usize>,
+
+    /// Fail with "recursion too deep" once N calls wait for their arguments; 0 for no limit [default: 1000000]
+    #[arg(long = "max-pending-calls", value_name = "N")]
+    max_pending_calls: Option<usize>,
+
+    /// Fail a macro expansion once N expansions are nested [default: 100]
+    #[arg(long = "max-macro-depth", value_name = "N")]
+    max_macro_depth: Option<usize>,
+
+    /// Print every macro expansion step
+    #[arg(long = "trace-macros")]
+    trace_macros: bool,
 
     /// Enable network I/O
     #[arg(long = "allow-network")]
     allow_network: bool,
 
-    /// Add allowed network address (can be repeated)
-    #[arg(long = "net-allow", value_name = "ADDR", action = clap::ArgAction::Append)]
+    /// Add allowed network address, `[scheme://]host[:port]` with `*.` wildcards (can be repeated)
+    #[arg(long = "net-allow", value_name = "ADDR", action = clap::ArgAction::Append, value_parser = parse_net_allow)]
     net_addresses: Vec<String>,
 
+    /// Let ws:connect open WebSockets (needs --allow-network; the allowlist applies)
+    #[arg(long = "allow-websocket")]
+    allow_websocket: bool,
+
     /// Skip loading standard library
     #[arg(long = "no-stdlib")]
     no_stdlib: bool,
+
+    /// Let scripts redefine or set! builtins and stdlib functions, which are protected by default
+    #[arg(long = "allow-shadow-builtins")]
+    allow_shadow_builtins: bool,
+
+    /// Warn on stderr when a top-level define replaces an existing binding
+    #[arg(long = "warn-redefine")]
+    warn_redefine: bool,
+
+    /// Evaluate FILE before the script or REPL (can be repeated; files load in order)
+    #[arg(long = "preload", value_name = "FILE", action = clap::ArgAction::Append)]
+    preload: Vec<PathBuf>,
+
+    /// Do not evaluate ~/.lisprc when the REPL starts
+    #[arg(long = "no-init")]
+    no_init: bool,
+
+    /// REPL history file (default: history in $XDG_DATA_HOME/lisp-llm-sandbox)
+    #[arg(long = "history", value_name = "FILE", conflicts_with = "no_history")]
+    history: Option<PathBuf>,
+
+    /// Neither load nor save REPL history
+    #[arg(long = "no-history")]
+    no_history: bool,
+
+    /// Elements of each list or map shown in REPL results and by pp (0 shows everything); sets *print-length*
+    #[arg(long = "print-length", value_name = "N", default_value_t = DEFAULT_PRINT_LENGTH)]
+    print_length: usize,
+
+    /// Levels of nested lists and maps shown in REPL results and by pp (0 shows everything); sets *print-depth*
+    #[arg(long = "print-depth", value_name = "N", default_value_t = DEFAULT_PRINT_DEPTH)]
+    print_depth: usize,
+
+    /// Print documentation for every function and special form, then exit
+    #[arg(long = "dump-docs", value_name = "FORMAT")]
+    dump_docs: Option<DocFormat>,
+
+    /// Print every function as an LLM tool schema (JSON), then exit
+    #[arg(long = "dump-tools", value_name = "FORMAT")]
+    dump_tools: Option<ToolFormatArg>,
+
+    /// Serve JSON requests on stdin/stdout (one object per line) instead of starting the REPL
+    #[arg(long = "serve-stdio")]
+    serve_stdio: bool,
+
+    /// Serve POST /eval and POST /reset over HTTP on PORT (localhost) or HOST:PORT
+    #[arg(long = "serve-http", value_name = "PORT")]
+    serve_http: Option<String>,
+
+    /// In server modes, cancel any request that evaluates for longer than this (milliseconds, or a duration like 30s)
+    #[arg(long = "eval-timeout", value_name = "MS", value_parser = parse_millis_arg)]
+    eval_timeout: Option<u64>,
+
+    /// With --serve-http, the most connections handled at once; more get 503
+    #[arg(
+        long = "max-connections",
+        value_name = "N",
+        default_value_t = server::DEFAULT_MAX_CONNECTIONS,
+        value_parser = parse_positive_arg
+    )]
+    max_connections: usize,
+
+    /// In server modes, the most sessions kept at once; evals that would start another fail (503 over HTTP)
+    #[arg(
+        long = "max-sessions",
+        value_name = "N",
+        default_value_t = server::DEFAULT_MAX_SESSIONS,
+        value_parser = parse_positive_arg
+    )]
+    max_sessions: usize,
+
+    /// Append every sandboxed file and network operation to FILE as JSON lines
+    #[arg(long = "audit-log", value_name = "FILE")]
+    audit_log: Option<PathBuf>,
+
+    /// Least serious log:debug/info/warn/error messages written: debug, info, warn, error or off
+    #[arg(long = "log-level", value_name = "LEVEL", default_value = "info")]
+    log_level: LogLevelArg,
+
+    /// Append log:info and friends to FILE in the sandbox instead of stderr
+    #[arg(long = "log-file", value_name = "FILE")]
+    log_file: Option<String>,
+
+    /// Save every HTTP response to FILE so the run can be replayed offline
+    #[arg(
+        long = "net-record",
+        value_name = "FILE",
+        conflicts_with = "net_replay"
+    )]
+    net_record: Option<PathBuf>,
+
+    /// Answer HTTP requests from a file written by --net-record instead of the network
+    #[arg(long = "net-replay", value_name = "FILE")]
+    net_replay: Option<PathBuf>,
+
+    /// Keep successful GET responses in DIR and answer repeated requests from it
+    #[arg(long = "net-cache", value_name = "DIR")]
+    net_cache: Option<PathBuf>,
+
+    /// How long --net-cache entries stay fresh (seconds, or a duration like 1h)
+    #[arg(
+        long = "net-cache-ttl",
+        value_name = "SECONDS",
+        default_value_t = 300,
+        value_parser = parse_seconds_arg,
+        requires = "net_cache"
+    )]
+    net_cache_ttl: u64,
+
+    /// Make runs reproducible: freeze the clock scripts see, report 0 for timings and turn off live network access (--net-replay still answers)
+    #[arg(long = "deterministic", conflicts_with_all = ["net_record", "net_cache"])]
+    deterministic: bool,
+
+    /// With --deterministic, the Unix time in seconds scripts see as now (default 0, the epoch)
+    #[arg(
+        long = "frozen-time",
+        value_name = "SECONDS",
+        default_value_t = 0.0,
+        requires = "deterministic"
+    )]
+    frozen_time: f64,
+
+    /// Let llm:chat call a model API with this provider's wire format
+    #[arg(long = "llm", value_name = "PROVIDER")]
+    llm: Option<LlmProviderArg>,
+
+    /// Model API base URL (default: the provider's public endpoint)
+    #[arg(long = "llm-endpoint", value_name = "URL", requires = "llm")]
+    llm_endpoint: Option<String>,
+
+    /// Model llm:chat uses when a call names none
+    #[arg(long = "llm-model", value_name = "NAME", requires = "llm")]
+    llm_model: Option<String>,
+
+    /// Environment variable holding the API key (default OPENAI_API_KEY or ANTHROPIC_API_KEY)
+    #[arg(long = "llm-key-env", value_name = "VAR", requires = "llm")]
+    llm_key_env: Option<String>,
+
+    /// Ask on the terminal before each file write or HTTP request that is not GET or HEAD
+    #[arg(
+        long = "confirm-writes",
+        conflicts_with_all = ["serve_stdio", "serve_http"]
+    )]
+    confirm_writes: bool,
+
+    /// Run every .lisp file in DIR with a fresh interpreter, then its registered tests; exit 1 on any failure
+    #[arg(
+        long = "test",
+        value_name = "DIR",
+        conflicts_with_all = ["script", "serve_stdio", "serve_http"]
+    )]
+    test: Option<PathBuf>,
+
+    /// Report every syntax error in FILE with its line and column without running it; exit 1 on any
+    #[arg(
+        long = "check",
+        requires = "script",
+        conflicts_with_all = ["serve_stdio", "serve_http", "test", "debug"]
+    )]
+    check: bool,
+
+    /// With --check, also expand macros (the stdlib's and the file's own defmacros) and report failures
+    #[arg(long = "expand", requires = "check")]
+    expand: bool,
+
+    /// Rewrite test:snapshot files instead of comparing against them
+    #[arg(long = "update-snapshots")]
+    update_snapshots: bool,
+
+    /// Count how often each function and expression is evaluated and print a report to stderr at exit
+    #[arg(
+        long = "coverage",
+        conflicts_with_all = ["serve_stdio", "serve_http"]
+    )]
+    coverage: bool,
+
+    // Not --profile, which already selects the sandbox preset
+    /// Count calls and wall time per function and print a table to stderr at exit
+    #[arg(
+        long = "profile-calls",
+        conflicts_with_all = ["serve_stdio", "serve_http"]
+    )]
+    profile_calls: bool,
+
+    /// When to color REPL input and results: auto (a terminal, unless NO_COLOR is set), always or never
+    #[arg(long = "color", value_name = "WHEN", default_value = "auto")]
+    color: ColorArg,
+
+    /// REPL color theme: default (dark backgrounds), light, or none
+    #[arg(long = "theme", value_name = "THEME", default_value = "default")]
+    theme: ThemeArg,
+
+    /// Pause before the first form and step through evaluation at a debug> prompt
+    #[arg(
+        long = "debug",
+        conflicts_with_all = ["serve_stdio", "serve_http", "test"]
+    )]
+    debug: bool,
+}
+
+/// Subcommands that work on source files without running them
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// Reformat .lisp files in place with canonical indentation, keeping comments
+    Fmt {
+        /// Files to format; `-` formats stdin to stdout
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<PathBuf>,
+
+        /// List files that are not formatted instead of rewriting them; exit 1 if there are any
+        #[arg(long = "check")]
+        check: bool,
+    },
+}
+
+/// Output formats for `--dump-docs`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum DocFormat {
+    Json,
+    Markdown,
+}
+
+/// Presets for `--profile`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ProfileArg {
+    Strict,
+    Default,
+    Trusted,
+}
+
+/// Choices for `--llm`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum LlmProviderArg {
+    Openai,
+    Anthropic,
+}
+
+/// Choices for `--log-level`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum LogLevelArg {
+    Debug,
+    #[default]
+    Info,
+    Warn,
+    Error,
+    Off,
+}
+
+impl LogLevelArg {
+    /// The interpreter's level; None turns logging off
+    fn level(self) -> Option<LogLevel> {
+        match self {
+            LogLevelArg::Debug => Some(LogLevel::Debug),
+            LogLevelArg::Info => Some(LogLevel::Info),
+            LogLevelArg::Warn => Some(LogLevel::Warn),
+            LogLevelArg::Error => Some(LogLevel::Error),
+            LogLevelArg::Off => None,
+        }
+    }
+}
+
+/// Choices for `--color`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum ColorArg {
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Choices for `--theme`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+enum ThemeArg {
+    #[default]
+    Default,
+    Light,
+    None,
+}
+
+/// Check a `--net-allow` entry when the arguments are parsed
+fn parse_net_allow(pattern: &str) -> Result<String, String> {
+    sandbox::AddressPattern::parse(pattern)?;
+    Ok(pattern.to_string())
+}
+
+/// Read a `--max-file-size` such as `10485760` or `10MB`
+fn parse_size_arg(text: &str) -> Result<usize, String> {
+    builtins::units::parse_bytes(text).and_then(|bytes| {
+        usize::try_from(bytes).map_err(|_| format!("size {:?} is too large", text))
+    })
+}
+
+/// Read a count flag that must be at least 1
+fn parse_positive_arg(text: &str) -> Result<usize, String> {
+    match text.trim().parse::<usize>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(format!(
+            "expected a whole number of at least 1, got {:?}",
+            text
+        )),
+    }
+}
+
+/// Read a duration flag in milliseconds: a bare number is milliseconds,
+/// otherwise a duration like `30s` or `1m30s`
+fn parse_millis_arg(text: &str) -> Result<u64, String> {
+    match text.trim().parse::<u64>() {
+        Ok(ms) => Ok(ms),
+        Err(_) => builtins::units::parse_duration(text).map(|s| (s * 1000.0).round() as u64),
+    }
+}
+
+/// Read a duration flag in whole seconds, like `300` or `5m`
+fn parse_seconds_arg(text: &str) -> Result<u64, String> {
+    builtins::units::parse_duration(text).map(|s| s.round() as u64)
+}
+
+/// The REPL's colors: `--theme`, unless `--color` or the environment turns color off
+///
+/// With `--color auto`, a non-empty `NO_COLOR` variable or a stdout that is not
+/// a terminal means no color.
+fn choose_theme(args: &CliArgs, no_color: bool, stdout_is_terminal: bool) -> &'static Theme {
+    let colored = match args.color {
+        ColorArg::Always => true,
+        ColorArg::Never => false,
+        ColorArg::Auto => !no_color && stdout_is_terminal,
+    };
+    match args.theme {
+        _ if !colored => &Theme::NONE,
+        ThemeArg::Default => &Theme::DEFAULT,
+        ThemeArg::Light => &Theme::LIGHT,
+        ThemeArg::None => &Theme::NONE,
+    }
+}
+
+/// Schema dialects for `--dump-tools`
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+enum ToolFormatArg {
+    Openai,
+    Anthropic,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // Deep non-tail recursion needs more stack than the main thread has
+    interpreter::on_eval_stack(|| run().map_err(|e| e.to_string()))?;
+    Ok(())
+}
+
+fn run() -> Result<(), Box<dyn std::error::Error>> {
     // Parse CLI arguments
     let args = CliArgs::parse();
 
-    // Build sandbox configuration from CLI args
-    let fs_config = build_fs_config(&args);
-    let net_config = build_net_config(&args);
-
-    // Initialize sandbox with configuration
-    let sandbox = Sandbox::new(fs_config, net_config)?;
-    set_sandbox_storage(sandbox);
-
-    // Initialize environment and macros
-    let env = Environment::new();
-    let mut macro_reg = MacroRegistry::new();
-    register_builtins(env.clone());
-    register_stdlib(env.clone());
-
-    // Register special forms documentation
-    eval::register_special_forms_part1();
-    eval::register_special_forms_part2();
-
-    // Register stdlib function documentation with proper categorization
-    register_stdlib_functions();
-
-    // Set environment for help system to enable lookup of user-defined functions
-    help::set_current_env(Some(env.clone()));
-
-    // Conditionally load standard library modules
-    if !args.no_stdlib {
-        // Load stdlib modules in order: core, math, string, test, http
-        let modules = [
-            ("core", include_str!("stdlib/lisp/core.lisp")),
-            ("math", include_str!("stdlib/lisp/math.lisp")),
-            ("string", include_str!("stdlib/lisp/string.lisp")),
-            ("test", include_str!("stdlib/lisp/test.lisp")),
-            ("http", include_str!("stdlib/lisp/http.lisp")),
-        ];
-
-        // Skip automatic help registration during stdlib loading
-        // Stdlib functions will be registered with proper categorization by stdlib_registry
-        parser::set_skip_help_registration(true);
-
-        for (module_name, module_code) in &modules {
-            match load_stdlib(module_code, env.clone(), &mut macro_reg) {
-                Ok(_) => {} // Silently succeed
-                Err(e) => eprintln!(
-                    "Warning: Failed to load stdlib module {}: {}",
-                    module_name, e
-                ),
+    if let Some(Command::Fmt { files, check }) = &args.command {
+        let ok = format_files(files, *check)?;
+        std::process::exit(if ok { 0 } else { 1 });
+    }
+
+    // Build sandbox configuration from the policy file, overridden by CLI args
+    let policy = match &args.config {
+        Some(path) => Policy::load(path)?,
+        None => Policy::default(),
+    };
+    let settings = InterpreterSettings::new(&args, &policy)?;
+
+    // Initialize interpreter with builtins, help entries, sandbox and stdlib
+    let mut interp = new_interpreter(&settings, !args.no_stdlib)?;
+
+    if let Some(format) = args.dump_docs {
+        let docs = match format {
+            DocFormat::Json => interp.help().export_json(),
+            DocFormat::Markdown => interp.help().export_markdown(),
+        };
+        println!("{}", docs);
+        return Ok(());
+    }
+
+    if let Some(format) = args.dump_tools {
+        let format = match format {
+            ToolFormatArg::Openai => tools::ToolFormat::OpenAi,
+            ToolFormatArg::Anthropic => tools::ToolFormat::Anthropic,
+        };
+        println!("{}", tools::export_manifest(interp.help(), format));
+        return Ok(());
+    }
+
+    if args.serve_stdio || args.serve_http.is_some() {
+        // Each session gets its own interpreter with the same sandbox settings
+        let load_stdlib = !args.no_stdlib;
+        let mut sessions = server::Sessions::new(move || {
+            new_interpreter(&settings, load_stdlib)
+                .expect("sandbox configuration was checked at startup")
+        });
+        if let Some(ms) = args.eval_timeout {
+            sessions = sessions.with_timeout(std::time::Duration::from_millis(ms));
+        }
+        let sessions = sessions.with_max_sessions(args.max_sessions);
+
+        match &args.serve_http {
+            Some(address) => {
+                let address = match address.parse::<u16>() {
+                    Ok(port) => format!("127.0.0.1:{}", port),
+                    Err(_) => address.clone(),
+                };
+                let listener = std::net::TcpListener::bind(&address)?;
+                eprintln!("Serving on http://{}", listener.local_addr()?);
+                let limits = server::HttpLimits {
+                    max_connections: args.max_connections,
+                    ..server::HttpLimits::default()
+                };
+                server::serve_http(Arc::new(sessions), listener, limits)?;
+            }
+            None => {
+                let stdin = std::io::stdin();
+                server::serve_stdio(&sessions, stdin.lock(), std::io::stdout())?;
             }
         }
+        return Ok(());
+    }
 
-        // Re-enable help registration for user code
-        parser::set_skip_help_registration(false);
+    if args.check {
+        let script = args
+            .script
+            .as_ref()
+            .expect("clap requires FILE with --check");
+        let problems = check_script(script, args.expand.then_some(&mut interp))?;
+        for problem in &problems {
+            eprintln!("{}", problem);
+        }
+        if !problems.is_empty() {
+            eprintln!("{} problem(s) in {}", problems.len(), script.display());
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    if let Some(dir) = &args.test {
+        let all_passed = run_test_dir(dir, &settings, !args.no_stdlib)?;
+        std::process::exit(if all_passed { 0 } else { 1 });
+    }
+
+    // (breakpoint) pauses at a debug> prompt in script and REPL modes
+    interp.set_debug_hook(debug_on_terminal);
+
+    // The REPL starts from the user's init file; a broken one only warns
+    let init_file = config::init_file_path();
+    if args.script.is_none() && !args.no_init && init_file.is_file() {
+        if let Err(e) = run_script(&init_file, &mut interp) {
+            eprintln!("Warning: {}: {}", init_file.display(), e);
+        }
+    }
+    for path in &args.preload {
+        run_script(path, &mut interp).map_err(|e| format!("{}: {}", path.display(), e))?;
     }
 
     // Check if we're running a script file or REPL
     if let Some(script_path) = args.script {
-        // Script mode: execute file and exit
-        run_script(&script_path, env, &mut macro_reg)?;
+        if args.debug {
+            interp.debug_step();
+        }
+        // Script mode: execute file and exit, failing if any test run by the script failed
+        let result = run_script(&script_path, &mut interp);
+        print_reports(interp.coverage(), interp.profiler());
+        result?;
+        let failed = interp.test_tally().failed;
+        if failed > 0 {
+            eprintln!("{} test(s) failed", failed);
+            std::process::exit(1);
+        }
         return Ok(());
     }
 
     // REPL mode: interactive loop
     // Create REPL with history and syntax highlighting support
-    let config = Config::builder().auto_add_history(true).build();
+    // Consecutive repeats are kept once, and only the newest entries are kept.
+    // Entries are added by hand once a form is complete, so a multi-line
+    // definition is recalled whole by Up and Ctrl-R.
+    let config = Config::builder()
+        .auto_add_history(false)
+        .history_ignore_dups(true)?
+        .max_history_size(config::MAX_HISTORY_ENTRIES)?
+        .build();
     let mut rl =
         Editor::with_config(config).map_err(|e| format!("Failed to initialize REPL: {}", e))?;
 
     // Set the helper with syntax highlighting
-    let helper = LispHelper::new();
+    let no_color = std::env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+    let theme = choose_theme(&args, no_color, std::io::stdout().is_terminal());
+    let helper = LispHelper::for_interpreter(&interp).with_theme(theme);
     rl.set_helper(Some(helper));
 
     // Try to load history from previous sessions
     // Intentionally ignore errors - history file may not exist on first run
-    let history_file = ".lisp_history";
-    let _ = rl.load_history(history_file);
+    let history_file = match (&args.history, args.no_history) {
+        (_, true) => None,
+        (Some(path), false) => Some(path.clone()),
+        (None, false) => Some(config::default_history_path()),
+    };
+    if let Some(path) = &history_file {
+        let _ = rl.load_history(path);
+    }
+
+    // Ctrl-C while evaluating aborts the evaluation instead of the process.
+    // While reading input the terminal is in raw mode, so rustyline sees Ctrl-C itself.
+    let cancel = interp.cancellation_token();
+    let handler_token = cancel.clone();
+    if let Err(e) = ctrlc::set_handler(move || handler_token.cancel()) {
+        eprintln!("Warning: Ctrl-C will not interrupt evaluation: {}", e);
+    }
 
     // Print welcome message
     println!("{}", WELCOME_MESSAGE);
@@ -150,16 +629,41 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("{}", WELCOME_FOOTER);
 
     // REPL loop
+    // Lines of an expression that is still missing closing parens
+    let mut pending = String::new();
     loop {
-        let readline = rl.readline("lisp> ");
+        // Highlight functions and macros defined by the previous input
+        if let Some(helper) = rl.helper_mut() {
+            helper.refresh(&interp);
+            helper.set_pending(&pending);
+        }
+        let prompt = if pending.is_empty() {
+            "lisp> ".to_string()
+        } else {
+            format!("..{}> ", highlighter::unclosed_depth(&pending))
+        };
+        let readline = rl.readline(&prompt);
 
         match readline {
             Ok(line) => {
+                let line = if pending.is_empty() {
+                    line
+                } else {
+                    format!("{}\n{}", std::mem::take(&mut pending), line)
+                };
+
                 // Skip empty lines
                 if line.trim().is_empty() {
                     continue;
                 }
 
+                // Keep reading until every list is closed
+                if highlighter::is_input_incomplete(&line) {
+                    pending = line;
+                    continue;
+                }
+                let _ = rl.add_history_entry(line.as_str());
+
                 // Handle special commands
                 match line.trim() {
                     "(quit)" | "(exit)" => {
@@ -176,11 +680,28 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 // Parse and evaluate the expression
                 match parse(&line) {
                     Ok(expr) => {
-                        // Set environment for help system lookup
-                        crate::help::set_current_env(Some(env.clone()));
-                        match eval_with_macros(expr, env.clone(), &mut macro_reg) {
+                        cancel.reset();
+                        if args.debug {
+                            interp.debug_step();
+                        } else {
+                            interp.debug_resume();
+                        }
+                        match interp.eval(expr) {
                             Ok(result) => {
-                                println!("=> {}", LispHelper::highlight_output(&result));
+                                // Leave room for the "=> " prefix, which continuation lines are indented by
+                                let width = rl
+                                    .dimensions()
+                                    .map_or(pretty::DEFAULT_WIDTH, |(columns, _)| columns as usize);
+                                let limits = print_limits(interp.env());
+                                let output = match rl.helper() {
+                                    Some(helper) => helper.highlight_output_within(
+                                        &result,
+                                        width.saturating_sub(3),
+                                        limits,
+                                    ),
+                                    None => result.to_string(),
+                                };
+                                println!("=> {}", output.replace('\n', "\n   "));
                             }
                             Err(e) => {
                                 // Don't add prefix - error already formats itself
@@ -195,7 +716,8 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             }
             Err(ReadlineError::Interrupted) => {
-                // Handle Ctrl-C
+                // Handle Ctrl-C, dropping any unfinished expression
+                pending.clear();
                 println!("^C");
                 continue;
             }
@@ -213,44 +735,417 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Save history on exit
     // Intentionally ignore errors - non-critical operation, don't break REPL exit
-    let _ = rl.save_history(history_file);
+    if let Some(path) = &history_file {
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let _ = rl.save_history(path);
+    }
 
+    print_reports(interp.coverage(), interp.profiler());
     Ok(())
 }
 
-/// Build filesystem configuration from CLI arguments
-fn build_fs_config(args: &CliArgs) -> FsConfig {
-    let allowed_paths = if args.fs_paths.is_empty() {
-        // Use default paths if none specified
-        vec![
-            PathBuf::from("./data"),
-            PathBuf::from("./examples"),
-            PathBuf::from("./scripts"),
-        ]
-    } else {
-        args.fs_paths.clone()
+/// Sandbox, limits and capability modules for every new interpreter
+struct InterpreterSettings {
+    io_config: IoConfig,
+    audit_log: Option<PathBuf>,
+    /// Shared by every interpreter, so server sessions record into one file
+    cassette: Option<Arc<Cassette>>,
+    /// Shared by every interpreter as well
+    cache: Option<Arc<HttpCache>>,
+    /// Model API for llm:chat, shared like the cache
+    llm: Option<Arc<LlmClient>>,
+    disabled_modules: Vec<&'static str>,
+    confirm_writes: bool,
+    update_snapshots: bool,
+    coverage: bool,
+    profile_calls: bool,
+    allow_shadow_builtins: bool,
+    warn_redefine: bool,
+    max_recursion_depth: Option<usize>,
+    max_pending_calls: Option<usize>,
+    max_macro_depth: Option<usize>,
+    trace_macros: bool,
+    print_length: usize,
+    print_depth: usize,
+    log_level: Option<LogLevel>,
+    log_file: Option<String>,
+    /// The time scripts see, with --deterministic
+    frozen_time: Option<f64>,
+}
+
+impl InterpreterSettings {
+    fn new(args: &CliArgs, policy: &Policy) -> Result<Self, sandbox::SandboxError> {
+        let cassette = match (&args.net_record, &args.net_replay) {
+            (Some(path), _) => Some(Arc::new(Cassette::recording(path)?)),
+            (None, Some(path)) => Some(Arc::new(Cassette::replaying(path)?)),
+            (None, None) => None,
+        };
+        let cache = match &args.net_cache {
+            Some(dir) => {
+                let ttl = std::time::Duration::from_secs(args.net_cache_ttl);
+                let cache = HttpCache::new(dir, ttl)?;
+                cache.check_outside(&build_io_config(args, policy).filesystem)?;
+                Some(Arc::new(cache))
+            }
+            None => None,
+        };
+        let llm = match args.llm {
+            Some(provider) => Some(Arc::new(llm_client(provider, args)?)),
+            None => None,
+        };
+        Ok(InterpreterSettings {
+            io_config: build_io_config(args, policy),
+            audit_log: args.audit_log.clone(),
+            cassette,
+            cache,
+            llm,
+            disabled_modules: policy.disabled_modules(),
+            confirm_writes: args.confirm_writes,
+            update_snapshots: args.update_snapshots,
+            coverage: args.coverage,
+            profile_calls: args.profile_calls,
+            allow_shadow_builtins: args.allow_shadow_builtins,
+            warn_redefine: args.warn_redefine,
+            max_recursion_depth: args.max_recursion_depth,
+            max_pending_calls: args.max_pending_calls,
+            max_macro_depth: args.max_macro_depth,
+            trace_macros: args.trace_macros,
+            print_length: args.print_length,
+            print_depth: args.print_depth,
+            log_level: args.log_level.level(),
+            log_file: args.log_file.clone(),
+            frozen_time: args.deterministic.then_some(args.frozen_time),
+        })
+    }
+
+    fn build(&self) -> Result<Sandbox, sandbox::SandboxError> {
+        let config = &self.io_config;
+        let mut sandbox = Sandbox::new(config.filesystem.clone(), config.network.clone())?;
+        if let Some(path) = &self.audit_log {
+            sandbox.set_audit_file(path)?;
+        }
+        if let Some(cassette) = &self.cassette {
+            sandbox.set_cassette(Arc::clone(cassette));
+        }
+        if let Some(cache) = &self.cache {
+            sandbox.set_cache(Arc::clone(cache));
+        }
+        if let Some(llm) = &self.llm {
+            sandbox.set_llm(Arc::clone(llm));
+        }
+        if self.confirm_writes {
+            sandbox.set_approval_callback(confirm_on_terminal);
+        }
+        if let Some(time) = self.frozen_time {
+            sandbox.freeze_clock(time);
+        }
+        Ok(sandbox)
+    }
+}
+
+/// Build the `--llm` client, reading its key from the environment
+fn llm_client(
+    provider: LlmProviderArg,
+    args: &CliArgs,
+) -> Result<LlmClient, sandbox::SandboxError> {
+    let (provider, key_var) = match provider {
+        LlmProviderArg::Openai => (LlmProvider::OpenAi, "OPENAI_API_KEY"),
+        LlmProviderArg::Anthropic => (LlmProvider::Anthropic, "ANTHROPIC_API_KEY"),
     };
+    let key_var = args.llm_key_env.as_deref().unwrap_or(key_var);
+    let key = std::env::var(key_var).map_err(|_| {
+        sandbox::SandboxError::IoError(format!("--llm needs an API key in ${}", key_var))
+    })?;
+    let endpoint = args
+        .llm_endpoint
+        .as_deref()
+        .unwrap_or(provider.default_endpoint());
+    Ok(LlmClient::new(
+        provider,
+        endpoint,
+        &key,
+        args.llm_model.clone(),
+    ))
+}
+
+/// Approval callback for `--confirm-writes`: anything but y/yes refuses
+fn confirm_on_terminal(request: &sandbox::ApprovalRequest) -> bool {
+    eprint!("Allow {}? [y/N] ", request);
+    let _ = std::io::Write::flush(&mut std::io::stderr());
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+/// Step debugger UI: show the paused form and its local bindings, then read commands at `debug>`
+fn debug_on_terminal(pause: &debugger::DebugPause) -> debugger::DebugCommand {
+    use debugger::DebugCommand;
+
+    eprintln!("[{}] {}", pause.depth, coverage::source_text(pause.expr));
+    print_locals(pause.env);
+    loop {
+        eprint!("debug> ");
+        let _ = std::io::Write::flush(&mut std::io::stderr());
+        let mut line = String::new();
+        if matches!(std::io::stdin().read_line(&mut line), Ok(0) | Err(_)) {
+            return DebugCommand::Continue;
+        }
+
+        let mut words = line.split_whitespace();
+        match words.next() {
+            None | Some("s" | "step") => return DebugCommand::Step,
+            Some("n" | "next") => return DebugCommand::Next,
+            Some("c" | "continue") => return DebugCommand::Continue,
+            Some("a" | "abort") => return DebugCommand::Abort,
+            Some("l" | "locals") => print_locals(pause.env),
+            Some("p" | "print") => {
+                for name in words {
+                    match pause.env.get(name) {
+                        Some(value) => eprintln!("  {} = {}", name, value),
+                        None => eprintln!("  {} is not defined", name),
+                    }
+                }
+            }
+            Some(_) => eprintln!(
+                "Commands: step (s or Enter), next (n), continue (c), locals (l), print NAME (p), abort (a)"
+            ),
+        }
+    }
+}
+
+/// Bindings of every non-global scope, innermost first, skipping shadowed names
+fn print_locals(env: &Arc<env::Environment>) {
+    let mut seen = std::collections::HashSet::new();
+    let mut scope = Some(env);
+    while let Some(frame) = scope.filter(|frame| !frame.is_global()) {
+        let mut bindings = frame.bindings();
+        bindings.sort_by(|a, b| a.0.cmp(&b.0));
+        for (name, value) in bindings {
+            if seen.insert(name.clone()) {
+                eprintln!("  {} = {}", name, value);
+            }
+        }
+        scope = frame.parent();
+    }
+}
+
+/// Create an interpreter with the given sandbox, optionally loading the standard library
+fn new_interpreter(
+    settings: &InterpreterSettings,
+    load_stdlib: bool,
+) -> Result<Interpreter, sandbox::SandboxError> {
+    let mut interp = Interpreter::new();
+    interp.set_sandbox(settings.build()?);
+    if settings.coverage {
+        // Before the stdlib loads, so stdlib functions are covered too
+        interp.enable_coverage();
+    }
+    if settings.profile_calls {
+        interp.enable_profiling();
+    }
 
-    FsConfig {
-        allowed_paths,
-        max_file_size: args.max_file_size,
+    if load_stdlib {
+        if let Err(e) = interp.load_stdlib() {
+            eprintln!("Warning: {}", e);
+        }
     }
+    for module in &settings.disabled_modules {
+        interp
+            .disable_capability(module)
+            .expect("policy modules are validated when loaded");
+    }
+    if !settings.allow_shadow_builtins {
+        interp.protect_globals();
+    }
+    // After the stdlib, whose definitions are not redefinitions worth reporting
+    interp.set_warn_redefinitions(settings.warn_redefine);
+    // Defined after protection, so scripts can change it with set!
+    for (name, setting) in [
+        (PRINT_LENGTH_VAR, settings.print_length),
+        (PRINT_DEPTH_VAR, settings.print_depth),
+    ] {
+        let value = match setting {
+            0 => Value::Nil,
+            n => Value::Number(n as f64),
+        };
+        interp.env().define(name, value);
+    }
+    interp.set_step_limit(settings.io_config.max_steps);
+    if let Some(depth) = settings.max_recursion_depth {
+        interp.set_depth_limit((depth > 0).then_some(depth));
+    }
+    if let Some(calls) = settings.max_pending_calls {
+        interp.set_pending_call_limit((calls > 0).then_some(calls));
+    }
+    if let Some(depth) = settings.max_macro_depth {
+        interp.set_macro_depth_limit(depth);
+    }
+    interp.set_trace_macros(settings.trace_macros);
+    interp.set_update_snapshots(settings.update_snapshots);
+    interp.set_log_level(settings.log_level);
+    interp.set_log_file(settings.log_file.clone());
+    Ok(interp)
 }
 
-/// Build network configuration from CLI arguments
-fn build_net_config(args: &CliArgs) -> NetConfig {
-    NetConfig {
-        enabled: args.allow_network,
-        allowed_addresses: args.net_addresses.clone(),
+/// Print the `--coverage` and `--profile-calls` reports that were collected
+fn print_reports(coverage: Option<&coverage::Coverage>, profile: Option<&profiler::Profiler>) {
+    if let Some(coverage) = coverage {
+        eprint!("\n{}", coverage.report());
+    }
+    if let Some(profile) = profile {
+        eprint!("\n{}", profile.report());
     }
 }
 
+/// Build the sandbox configuration: the profile, then the policy, then CLI arguments
+fn build_io_config(args: &CliArgs, policy: &Policy) -> IoConfig {
+    let profile = match args.profile {
+        Some(ProfileArg::Strict) => Profile::Strict,
+        Some(ProfileArg::Default) => Profile::Default,
+        Some(ProfileArg::Trusted) => Profile::Trusted,
+        None => policy.profile.unwrap_or_default(),
+    };
+    let mut config = profile.config();
+    policy.apply(&mut config);
+
+    if !args.fs_paths.is_empty() {
+        // CLI paths replace the profile's or policy's paths
+        config.filesystem.allowed_paths = args.fs_paths.clone();
+        config.filesystem.read_only_paths.clear();
+    }
+    if let Some(size) = args.max_file_size {
+        config.filesystem.max_file_size = size;
+    }
+
+    config.network.enabled |= args.allow_network;
+    config.network.allow_websocket |= args.allow_websocket;
+    if !args.net_addresses.is_empty() {
+        config.network.allowed_addresses = args.net_addresses.clone();
+    }
+
+    if let Some(max) = args.max_steps {
+        config.max_steps = Some(max);
+    }
+
+    // A replayed cassette answers the same way every run; the live network does not
+    if args.deterministic && args.net_replay.is_none() {
+        config.network.enabled = false;
+    }
+    config
+}
+
 /// Execute a Lisp script file
-fn run_script(
+/// Format each file in place, or with `check` only list the files that need it
+///
+/// Returns false when a file could not be formatted (unbalanced parens or an
+/// unterminated string) or, with `check`, when any file was not formatted.
+fn format_files(files: &[PathBuf], check: bool) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut ok = true;
+    for path in files {
+        let stdin = path.as_os_str() == "-";
+        let source = if stdin {
+            let mut source = String::new();
+            std::io::Read::read_to_string(&mut std::io::stdin(), &mut source)?;
+            source
+        } else {
+            std::fs::read_to_string(path)
+                .map_err(|e| format!("Cannot read {}: {}", path.display(), e))?
+        };
+        let formatted = match fmt::format_source(&source) {
+            Ok(formatted) => formatted,
+            Err(e) => {
+                eprintln!("{}: {}", path.display(), e);
+                ok = false;
+                continue;
+            }
+        };
+
+        if check {
+            if formatted != source {
+                println!("{}", path.display());
+                ok = false;
+            }
+        } else if stdin {
+            print!("{}", formatted);
+        } else if formatted != source {
+            std::fs::write(path, formatted)
+                .map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+            eprintln!("Formatted {}", path.display());
+        }
+    }
+    Ok(ok)
+}
+
+/// Syntax errors in a script as `FILE:LINE:COLUMN: message`, found without evaluating it
+///
+/// With an interpreter, a file that parses is also macro-expanded form by
+/// form: its `defmacro`s are defined (nothing else is evaluated) and every
+/// other form reports the macro calls that fail to expand.
+fn check_script(
     path: &PathBuf,
-    env: Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
-) -> Result<(), Box<dyn std::error::Error>> {
+    interp: Option<&mut Interpreter>,
+) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let source = std::fs::read_to_string(path)
+        .map_err(|e| format!("Cannot read script file {}: {}", path.display(), e))?;
+    let problems: Vec<String> = parser::check_syntax(&source)
+        .iter()
+        .map(|e| format!("{}:{}", path.display(), e))
+        .collect();
+    let Some(interp) = interp else {
+        return Ok(problems);
+    };
+    if !problems.is_empty() {
+        // Expansion needs every form to parse
+        return Ok(problems);
+    }
+
+    let mut problems = Vec::new();
+    let mut remaining = source.as_str();
+    loop {
+        remaining = skip_whitespace_and_regular_comments(remaining);
+        // Report a form at its opening paren, not at its doc comment
+        let mut form = remaining;
+        while form.starts_with(";;;") {
+            form = form.find('\n').map_or("", |end| &form[end + 1..]);
+            form = skip_whitespace_and_regular_comments(form);
+        }
+        if form.is_empty() {
+            break;
+        }
+        let before = &source[..source.len() - form.len()];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        let at = format!("{}:{}:{}", path.display(), line, column);
+
+        let (expr, rest) = match parse_one_expr(remaining) {
+            Ok(parsed) => parsed,
+            Err(e) => {
+                problems.push(format!("{}: {}", at, e));
+                break;
+            }
+        };
+        remaining = rest;
+        let defines_macro = matches!(&expr, Value::List(items) if matches!(items.first(), Some(Value::Symbol(head)) if head == "defmacro"));
+        if defines_macro {
+            if let Err(e) = interp.eval(expr) {
+                problems.push(format!("{}: {}", at, e));
+            }
+        } else {
+            // Doc comments belong to this form, not to the next defmacro
+            parser::take_pending_docs();
+            for e in interp.expansion_errors(&expr) {
+                problems.push(format!("{}: macro expansion failed: {}", at, e));
+            }
+        }
+    }
+    Ok(problems)
+}
+
+fn run_script(path: &PathBuf, interp: &mut Interpreter) -> Result<(), Box<dyn std::error::Error>> {
     // Read script file (script files are trusted input, not sandboxed)
     let contents = std::fs::read_to_string(path)
         .map_err(|e| format!("Cannot read script file {}: {}", path.display(), e))?;
@@ -268,10 +1163,8 @@ fn run_script(
         // Parse one expression
         match parse_one_expr(remaining) {
             Ok((expr, rest)) => {
-                // Set environment for help system lookup
-                crate::help::set_current_env(Some(env.clone()));
                 // Evaluate the expression
-                match eval_with_macros(expr, env.clone(), macro_reg) {
+                match interp.eval(expr) {
                     Ok(_result) => {
                         // Scripts typically don't print results unless explicitly printed
                         remaining = rest;
@@ -290,151 +1183,61 @@ fn run_script(
     Ok(())
 }
 
-/// Load and evaluate the standard library
-fn load_stdlib(
-    code: &str,
-    env: std::rc::Rc<Environment>,
-    macro_reg: &mut MacroRegistry,
-) -> Result<(), String> {
-    // Parse each expression in the stdlib
-    // We need to handle multiple top-level forms
-    let mut remaining = code.trim();
+/// Run each `.lisp` file in `dir` (sorted) in its own interpreter, as `--test` does
+///
+/// A file's registered tests are run after it loads, unless the file already
+/// called `run-all-tests` itself. Returns whether every file loaded and every
+/// test passed.
+fn run_test_dir(
+    dir: &PathBuf,
+    settings: &InterpreterSettings,
+    load_stdlib: bool,
+) -> Result<bool, Box<dyn std::error::Error>> {
+    let mut files: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| format!("Cannot read test directory {}: {}", dir.display(), e))?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| path.extension().is_some_and(|ext| ext == "lisp"))
+        .collect();
+    files.sort();
 
-    while !remaining.is_empty() {
-        // Skip whitespace and regular comments (preserves ;;; doc comments)
-        remaining = skip_whitespace_and_regular_comments(remaining);
-        if remaining.is_empty() {
-            break;
+    let (mut passed, mut failed, mut broken) = (0, 0, 0);
+    let mut coverage = settings.coverage.then(coverage::Coverage::new);
+    let mut profile = settings.profile_calls.then(profiler::Profiler::new);
+    for file in &files {
+        println!("{}", file.display());
+        let mut interp = new_interpreter(settings, load_stdlib)?;
+        let loaded = run_script(file, &mut interp);
+        if let Err(e) = &loaded {
+            println!("  ERROR {}", e);
+            broken += 1;
         }
-
-        // Parse one expression
-        match parse_one_expr(remaining) {
-            Ok((expr, rest)) => {
-                // Set environment for help system lookup
-                crate::help::set_current_env(Some(env.clone()));
-                // Evaluate the expression
-                match eval_with_macros(expr, env.clone(), macro_reg) {
-                    Ok(_) => {
-                        remaining = rest;
-                    }
-                    Err(e) => {
-                        return Err(format!("Eval error: {}", e));
-                    }
-                }
-            }
-            Err(e) => {
-                return Err(format!("Parse error: {}", e));
-            }
+        if loaded.is_ok() && interp.test_tally().runs == 0 {
+            interp.eval_str("(run-all-tests)")?;
         }
-    }
-
-    Ok(())
-}
-
-/// Skip whitespace and NON-DOC comments in the input string
-/// Preserves ;;; doc comments so they can be captured by parse()
-fn skip_whitespace_and_regular_comments(input: &str) -> &str {
-    let mut remaining = input;
-    loop {
-        remaining = remaining.trim_start();
-        // Skip only ; and ;; comments, NOT ;;; doc comments
-        if remaining.starts_with(";;;") {
-            // Don't skip doc comments!
-            break;
-        } else if remaining.starts_with(";;") || remaining.starts_with(';') {
-            // Skip regular comments
-            if let Some(pos) = remaining.find('\n') {
-                remaining = &remaining[pos + 1..];
-            } else {
-                remaining = "";
-            }
-        } else {
-            break;
+        let tally = interp.test_tally();
+        passed += tally.passed;
+        failed += tally.failed;
+        if let (Some(total), Some(file_coverage)) = (coverage.as_mut(), interp.coverage()) {
+            total.merge(file_coverage.clone());
         }
-    }
-    remaining
-}
-
-/// Parse one expression and return it along with the remaining input
-///
-/// This function preserves ;;; doc comments and includes them in the parsed string
-/// so that parse() can capture them via the thread-local PENDING_DOCS.
-fn parse_one_expr(input: &str) -> Result<(value::Value, &str), String> {
-    // Skip only non-doc comments and whitespace, preserve ;;; comments
-    let start = skip_whitespace_and_regular_comments(input);
-    if start.is_empty() {
-        return Err("No expression to parse".to_string());
-    }
-
-    // Collect any preceding ;;; doc comments
-    let mut doc_start = start;
-    while doc_start.starts_with(";;;") {
-        // Find the end of this doc comment line
-        if let Some(pos) = doc_start.find('\n') {
-            doc_start = &doc_start[pos + 1..];
-            doc_start = skip_whitespace_and_regular_comments(doc_start);
-        } else {
-            break;
+        if let (Some(total), Some(file_profile)) = (profile.as_mut(), interp.profiler()) {
+            total.merge(file_profile);
         }
     }
 
-    // Find the end of the first complete s-expression (after the doc comments)
-    let end_pos = find_expr_end(doc_start)?;
-
-    // Include everything from the start of doc comments to the end of the expression
-    let bytes_from_start = start.len() - doc_start.len();
-    let total_expr_len = bytes_from_start + end_pos;
-    let expr_str = &start[..total_expr_len];
-    let rest = &start[total_expr_len..];
-
-    // Parse the expression (this will capture ;;; comments via thread-local)
-    let expr = parse(expr_str)?;
-    Ok((expr, rest))
-}
-
-/// Find the end position of the first complete s-expression
-fn find_expr_end(input: &str) -> Result<usize, String> {
-    let chars: Vec<char> = input.chars().collect();
-    let mut i = 0;
-
-    // Skip initial whitespace
-    while i < chars.len() && chars[i].is_whitespace() {
-        i += 1;
-    }
-
-    if i >= chars.len() {
-        return Err("Empty input".to_string());
-    }
-
-    // Check what kind of expression this is
-    if chars[i] == '(' {
-        // S-expression - find matching closing paren
-        let mut depth = 0;
-        let mut in_string = false;
-
-        while i < chars.len() {
-            match chars[i] {
-                '"' => in_string = !in_string,
-                '(' if !in_string => depth += 1,
-                ')' if !in_string => {
-                    depth -= 1;
-                    if depth == 0 {
-                        return Ok(i + 1);
-                    }
-                }
-                _ => {}
-            }
-            i += 1;
-        }
-
-        Err("Unclosed s-expression".to_string())
-    } else {
-        // Atom - find end of token
-        while i < chars.len() && !chars[i].is_whitespace() && chars[i] != ')' {
-            i += 1;
+    println!(
+        "\n{} file(s): {} test(s) passed, {} failed{}",
+        files.len(),
+        passed,
+        failed,
+        if broken > 0 {
+            format!(", {} file(s) with errors", broken)
+        } else {
+            String::new()
         }
-        Ok(i)
-    }
+    );
+    print_reports(coverage.as_ref(), profile.as_ref());
+    Ok(failed == 0 && broken == 0)
 }
 
 #[cfg(test)]
@@ -443,15 +1246,8 @@ mod tests {
 
     #[test]
     fn test_build_fs_config_with_defaults() {
-        let args = CliArgs {
-            script: None,
-            fs_paths: vec![],
-            max_file_size: 10485760,
-            allow_network: false,
-            net_addresses: vec![],
-            no_stdlib: false,
-        };
-        let config = build_fs_config(&args);
+        let args = CliArgs::default();
+        let config = build_io_config(&args, &Policy::default()).filesystem;
         assert_eq!(config.allowed_paths.len(), 3);
         assert_eq!(config.max_file_size, 10485760);
         assert_eq!(config.allowed_paths[0], PathBuf::from("./data"));
@@ -462,64 +1258,161 @@ mod tests {
     #[test]
     fn test_build_fs_config_with_custom_paths() {
         let args = CliArgs {
-            script: None,
             fs_paths: vec![PathBuf::from("/tmp/safe")],
-            max_file_size: 5242880,
-            allow_network: false,
-            net_addresses: vec![],
-            no_stdlib: false,
+            max_file_size: Some(5242880),
+            ..Default::default()
         };
-        let config = build_fs_config(&args);
+        let config = build_io_config(&args, &Policy::default()).filesystem;
         assert_eq!(config.allowed_paths.len(), 1);
         assert_eq!(config.allowed_paths[0], PathBuf::from("/tmp/safe"));
         assert_eq!(config.max_file_size, 5242880);
     }
 
+    #[test]
+    fn test_size_and_duration_flags_accept_units() {
+        let args = CliArgs::try_parse_from([
+            "lisp-llm-sandbox",
+            "--max-file-size",
+            "5MB",
+            "--eval-timeout",
+            "1m30s",
+            "--net-cache",
+            "cache",
+            "--net-cache-ttl",
+            "1h",
+        ])
+        .unwrap();
+        assert_eq!(args.max_file_size, Some(5242880));
+        assert_eq!(args.eval_timeout, Some(90000));
+        assert_eq!(args.net_cache_ttl, 3600);
+
+        // Bare numbers keep their old units
+        let args = CliArgs::try_parse_from([
+            "lisp-llm-sandbox",
+            "--max-file-size",
+            "1024",
+            "--eval-timeout",
+            "250",
+        ])
+        .unwrap();
+        assert_eq!(args.max_file_size, Some(1024));
+        assert_eq!(args.eval_timeout, Some(250));
+        assert_eq!(args.net_cache_ttl, 300);
+
+        assert!(CliArgs::try_parse_from(["lisp-llm-sandbox", "--max-file-size", "lots"]).is_err());
+    }
+
+    #[test]
+    fn test_log_flags() {
+        let args = CliArgs::try_parse_from(["lisp-llm-sandbox"]).unwrap();
+        assert_eq!(args.log_level.level(), Some(LogLevel::Info));
+        assert_eq!(args.log_file, None);
+
+        let args = CliArgs::try_parse_from([
+            "lisp-llm-sandbox",
+            "--log-level",
+            "debug",
+            "--log-file",
+            "logs/run.log",
+        ])
+        .unwrap();
+        assert_eq!(args.log_level.level(), Some(LogLevel::Debug));
+        assert_eq!(args.log_file.as_deref(), Some("logs/run.log"));
+
+        let args = CliArgs::try_parse_from(["lisp-llm-sandbox", "--log-level", "off"]).unwrap();
+        assert_eq!(args.log_level.level(), None);
+        assert!(CliArgs::try_parse_from(["lisp-llm-sandbox", "--log-level", "loud"]).is_err());
+    }
+
+    #[test]
+    fn test_deterministic_turns_off_live_network() {
+        let args = CliArgs::try_parse_from([
+            "lisp-llm-sandbox",
+            "--allow-network",
+            "--deterministic",
+            "--frozen-time",
+            "1700000000",
+        ])
+        .unwrap();
+        assert!(!build_io_config(&args, &Policy::default()).network.enabled);
+        let settings = InterpreterSettings::new(&args, &Policy::default()).unwrap();
+        assert_eq!(settings.frozen_time, Some(1_700_000_000.0));
+
+        // Replay stays deterministic, so the network stays on for it
+        let args = CliArgs {
+            allow_network: true,
+            deterministic: true,
+            net_replay: Some(PathBuf::from("run.json")),
+            ..Default::default()
+        };
+        assert!(build_io_config(&args, &Policy::default()).network.enabled);
+
+        assert!(CliArgs::try_parse_from(["lisp-llm-sandbox", "--frozen-time", "5"]).is_err());
+        assert!(CliArgs::try_parse_from([
+            "lisp-llm-sandbox",
+            "--deterministic",
+            "--net-record",
+            "run.json"
+        ])
+        .is_err());
+    }
+
+    #[test]
+    fn test_deterministic_runs_print_the_same_timings() {
+        let args =
+            CliArgs::try_parse_from(["lisp-llm-sandbox", "--deterministic", "--profile-calls"])
+                .unwrap();
+        let settings = InterpreterSettings::new(&args, &Policy::default()).unwrap();
+        let script = "(define (fib n) (if (< n 2) n (+ (fib (- n 1)) (fib (- n 2))))) \
+                      (time (fib 15)) \
+                      (println (bench (fib 10) 3)) \
+                      (profile (fib 12))";
+
+        let run = || {
+            let mut interp = new_interpreter(&settings, false).unwrap();
+            interp.capture_output(true);
+            interp.eval_str(script).unwrap();
+            let report = interp.profiler().unwrap().report();
+            format!("{}{}", interp.take_output(), report)
+        };
+        let first = run();
+        assert_eq!(first, run());
+        assert!(first.contains("Elapsed time: 0.000 ms"), "{}", first);
+        assert!(first.contains(":mean 0"), "{}", first);
+    }
+
     #[test]
     fn test_build_fs_config_with_multiple_paths() {
         let args = CliArgs {
-            script: None,
             fs_paths: vec![
                 PathBuf::from("./data"),
                 PathBuf::from("./uploads"),
                 PathBuf::from("/tmp"),
             ],
-            max_file_size: 1048576,
-            allow_network: false,
-            net_addresses: vec![],
-            no_stdlib: false,
+            max_file_size: Some(1048576),
+            ..Default::default()
         };
-        let config = build_fs_config(&args);
+        let config = build_io_config(&args, &Policy::default()).filesystem;
         assert_eq!(config.allowed_paths.len(), 3);
         assert_eq!(config.max_file_size, 1048576);
     }
 
     #[test]
     fn test_build_net_config_disabled_by_default() {
-        let args = CliArgs {
-            script: None,
-            fs_paths: vec![],
-            max_file_size: 10485760,
-            allow_network: false,
-            net_addresses: vec![],
-            no_stdlib: false,
-        };
-        let config = build_net_config(&args);
+        let args = CliArgs::default();
+        let config = build_io_config(&args, &Policy::default()).network;
         assert!(!config.enabled);
+        assert!(!config.allow_websocket);
         assert_eq!(config.allowed_addresses.len(), 0);
     }
 
     #[test]
     fn test_build_net_config_enabled() {
         let args = CliArgs {
-            script: None,
-            fs_paths: vec![],
-            max_file_size: 10485760,
             allow_network: true,
-            net_addresses: vec![],
-            no_stdlib: false,
+            ..Default::default()
         };
-        let config = build_net_config(&args);
+        let config = build_io_config(&args, &Policy::default()).network;
         assert!(config.enabled);
         assert_eq!(config.allowed_addresses.len(), 0);
     }
@@ -527,43 +1420,180 @@ mod tests {
     #[test]
     fn test_build_net_config_with_allowlist() {
         let args = CliArgs {
-            script: None,
-            fs_paths: vec![],
-            max_file_size: 10485760,
             allow_network: true,
             net_addresses: vec!["example.com".to_string(), "api.local:8080".to_string()],
-            no_stdlib: false,
+            ..Default::default()
         };
-        let config = build_net_config(&args);
+        let config = build_io_config(&args, &Policy::default()).network;
         assert!(config.enabled);
         assert_eq!(config.allowed_addresses.len(), 2);
         assert_eq!(config.allowed_addresses[0], "example.com");
         assert_eq!(config.allowed_addresses[1], "api.local:8080");
     }
 
+    #[test]
+    fn test_cli_flags_override_policy() {
+        let policy = Policy::from_toml(
+            r#"
+            [filesystem]
+            max_file_size = 100
+            paths = [{ path = "out" }, { path = "docs", access = "ro" }]
+            [network]
+            allow = ["example.com"]
+            "#,
+        )
+        .unwrap();
+
+        let config = build_io_config(&CliArgs::default(), &policy).filesystem;
+        assert_eq!(config.allowed_paths, vec![PathBuf::from("out")]);
+        assert_eq!(config.read_only_paths, vec![PathBuf::from("docs")]);
+        assert_eq!(config.max_file_size, 100);
+
+        let args = CliArgs {
+            fs_paths: vec![PathBuf::from("./data")],
+            max_file_size: Some(200),
+            allow_network: true,
+            net_addresses: vec!["api.local".to_string()],
+            ..Default::default()
+        };
+        let config = build_io_config(&args, &policy).filesystem;
+        assert_eq!(config.allowed_paths, vec![PathBuf::from("./data")]);
+        assert!(config.read_only_paths.is_empty());
+        assert_eq!(config.max_file_size, 200);
+
+        let net = build_io_config(&args, &policy).network;
+        assert!(net.enabled);
+        assert_eq!(net.allowed_addresses, vec!["api.local"]);
+    }
+
+    #[test]
+    fn test_profiles() {
+        let strict = CliArgs {
+            profile: Some(ProfileArg::Strict),
+            ..Default::default()
+        };
+        let config = build_io_config(&strict, &Policy::default());
+        assert!(config.filesystem.allowed_paths.is_empty());
+        assert!(!config.network.enabled);
+        assert_eq!(config.max_steps, Some(config::STRICT_MAX_STEPS));
+
+        let trusted = CliArgs {
+            profile: Some(ProfileArg::Trusted),
+            max_steps: Some(50),
+            ..Default::default()
+        };
+        let config = build_io_config(&trusted, &Policy::default());
+        assert_eq!(config.filesystem.allowed_paths.len(), 1);
+        assert!(config.network.enabled);
+        assert_eq!(config.max_steps, Some(50));
+
+        // A policy's profile applies unless --profile overrides it
+        let policy = Policy::from_toml("profile = \"strict\"").unwrap();
+        let config = build_io_config(&CliArgs::default(), &policy);
+        assert_eq!(config.max_steps, Some(config::STRICT_MAX_STEPS));
+        let args = CliArgs {
+            profile: Some(ProfileArg::Default),
+            ..Default::default()
+        };
+        assert_eq!(build_io_config(&args, &policy).max_steps, None);
+    }
+
     #[test]
     fn test_cli_args_script_argument() {
         let args = CliArgs {
             script: Some(PathBuf::from("test.lisp")),
-            fs_paths: vec![],
-            max_file_size: 10485760,
-            allow_network: false,
-            net_addresses: vec![],
-            no_stdlib: false,
+            ..Default::default()
         };
         assert!(args.script.is_some());
         assert_eq!(args.script.as_ref().unwrap(), &PathBuf::from("test.lisp"));
     }
 
+    #[test]
+    fn test_run_test_dir() {
+        let dir = std::env::temp_dir().join(format!("lisp-test-dir-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("a.lisp"),
+            r#"(define-test "adds" (assert-equal (+ 1 1) 2))"#,
+        )
+        .unwrap();
+        std::fs::write(dir.join("notes.txt"), "(car 5)").unwrap();
+
+        let args = CliArgs {
+            profile: Some(ProfileArg::Strict),
+            ..Default::default()
+        };
+        let settings = InterpreterSettings::new(&args, &Policy::default()).unwrap();
+        assert!(run_test_dir(&dir, &settings, true).unwrap());
+
+        std::fs::write(
+            dir.join("b.lisp"),
+            r#"(define-test "wrong" (assert-equal 1 2))"#,
+        )
+        .unwrap();
+        assert!(!run_test_dir(&dir, &settings, true).unwrap());
+
+        std::fs::write(dir.join("b.lisp"), "(undefined-fn)").unwrap();
+        assert!(!run_test_dir(&dir, &settings, true).unwrap());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_check_script() {
+        let path = std::env::temp_dir().join(format!("lisp-check-{}.lisp", std::process::id()));
+        std::fs::write(&path, "(define x 1))\n(print \"never run\")\n(f #x").unwrap();
+        let problems = check_script(&path, None).unwrap();
+        let display = path.display();
+        assert_eq!(
+            problems,
+            vec![
+                format!("{}:1:13: unexpected ')'", display),
+                format!("{}:3:4: invalid token '#x'", display),
+                format!("{}:3:1: '(' is never closed", display),
+            ]
+        );
+
+        std::fs::write(
+            &path,
+            "(defmacro twice (x) `(begin ,x ,x))\n\n;;; Doc\n  (define (f) (twice 1 2))\n(twice (car 5))",
+        )
+        .unwrap();
+        assert!(check_script(&path, None).unwrap().is_empty());
+        let mut interp = Interpreter::new();
+        let problems = check_script(&path, Some(&mut interp)).unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with(&format!("{}:4:3: macro expansion failed", display)));
+
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_choose_theme() {
+        let auto = CliArgs::default();
+        assert_eq!(choose_theme(&auto, false, true), &Theme::DEFAULT);
+        assert_eq!(choose_theme(&auto, true, true), &Theme::NONE);
+        assert_eq!(choose_theme(&auto, false, false), &Theme::NONE);
+
+        let always_light = CliArgs {
+            color: ColorArg::Always,
+            theme: ThemeArg::Light,
+            ..Default::default()
+        };
+        assert_eq!(choose_theme(&always_light, true, false), &Theme::LIGHT);
+
+        let never = CliArgs {
+            color: ColorArg::Never,
+            ..Default::default()
+        };
+        assert_eq!(choose_theme(&never, false, true), &Theme::NONE);
+    }
+
     #[test]
     fn test_cli_args_no_stdlib_flag() {
         let args = CliArgs {
-            script: None,
-            fs_paths: vec![],
-            max_file_size: 10485760,
-            allow_network: false,
-            net_addresses: vec![],
             no_stdlib: true,
+            ..Default::default()
         };
         assert!(args.no_stdlib);
     }
@@ -0,0 +1,406 @@
+// ABOUTME: Peephole optimizer run on function bodies after macro expansion
+
+use crate::analyze::SpecialForm;
+use crate::env::{EnvRef, Frozen};
+use crate::symbol::Symbol;
+use crate::value::Value;
+use std::collections::HashSet;
+use std::sync::Arc;
+
+/// Builtins whose result depends only on their (numeric) arguments
+const FOLDABLE: &[&str] = &["+", "-", "*", "/", "%", "=", "<", ">", "<=", ">="];
+
+/// Simplify a function body before it is stored in a lambda
+///
+/// - Folds arithmetic and comparisons on literal numbers: `(* 2 3)` => `6`
+/// - Collapses `(begin x)` to `x`
+/// - Drops the untaken branch of an `if` whose condition is a literal
+/// - Pre-resolves calls to protected builtins, so `(car x)` no longer looks up `car`
+///
+/// `params` are the names bound by the function itself. Names bound locally
+/// (parameters, `let`, inner `define`) are never treated as builtins. Only
+/// bindings frozen by [`crate::interpreter::Interpreter::protect_globals`] are
+/// resolved, since nothing can rebind them; any other name, even one that holds
+/// a builtin today, is looked up when the call runs. Folding is limited the same way.
+/// Malformed forms and folds that would fail are left for the evaluator to report.
+/// A list that nothing changed in is kept as it was, so an already optimized
+/// body (a lambda created again at run time) keeps its identity for coverage.
+pub fn optimize(expr: Value, params: &[Symbol], env: &EnvRef) -> Value {
+    let mut locals: HashSet<Symbol> = params.iter().cloned().collect();
+    collect_defines(&expr, &mut locals);
+    optimize_expr(expr, &locals, env)
+}
+
+fn optimize_expr(expr: Value, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let original = expr.clone();
+    let optimized = optimize_form(expr, locals, env);
+    match (&original, &optimized) {
+        (Value::List(before), Value::List(after)) if !Arc::ptr_eq(before, after) => {
+            let unchanged = before.len() == after.len()
+                && before.iter().zip(after.iter()).all(|(a, b)| same(a, b));
+            if unchanged {
+                original
+            } else {
+                optimized
+            }
+        }
+        _ => optimized,
+    }
+}
+
+/// Whether an element came through optimization as it was; unsure cases count as changed
+fn same(before: &Value, after: &Value) -> bool {
+    match (before, after) {
+        (Value::List(a), Value::List(b)) => Arc::ptr_eq(a, b),
+        (Value::Symbol(a), Value::Symbol(b)) => a == b,
+        (Value::BuiltIn(a), Value::BuiltIn(b)) => std::ptr::fn_addr_eq(*a, *b),
+        (Value::Number(a), Value::Number(b)) => a.to_bits() == b.to_bits(),
+        (Value::String(a), Value::String(b)) => Arc::ptr_eq(a, b),
+        (Value::Keyword(a), Value::Keyword(b)) => a == b,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Nil, Value::Nil) => true,
+        _ => false,
+    }
+}
+
+fn optimize_form(expr: Value, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let items = match expr {
+        Value::List(items) if !items.is_empty() => items,
+        Value::Map(map) => {
+            return Value::Map(
+                map.into_iter()
+                    .map(|(k, v)| (k, optimize_expr(v, locals, env)))
+                    .collect(),
+            )
+        }
+        other => return other,
+    };
+
+    match SpecialForm::of(&items[0]) {
+        // Threading steps are incomplete calls, so folding them would be wrong
+        Some(SpecialForm::Quote)
+        | Some(SpecialForm::Quasiquote)
+        | Some(SpecialForm::Defmacro)
+        | Some(SpecialForm::ThreadFirst)
+        | Some(SpecialForm::ThreadLast)
+        | Some(SpecialForm::SomeThread) => Value::List(items),
+        Some(SpecialForm::Lambda) => optimize_lambda(items.to_vec(), locals, env),
+        Some(SpecialForm::Define) => {
+            // Function form introduces its own parameter scope
+            if let Some(Value::List(signature)) = items.get(1) {
+                let params: Vec<Value> = signature.iter().skip(1).cloned().collect();
+                return optimize_scoped(items.to_vec(), 2, &params, locals, env);
+            }
+            optimize_from(items.to_vec(), 2, locals, env)
+        }
+        Some(SpecialForm::Let) => optimize_let(items.to_vec(), locals, env),
+        Some(SpecialForm::If) => optimize_if(items.to_vec(), locals, env),
+        Some(SpecialForm::Case) => optimize_case(items.to_vec(), locals, env),
+        Some(SpecialForm::Begin) => {
+            let mut items = optimize_items(items.to_vec(), 1, locals, env);
+            if items.len() == 2 {
+                return items.pop().unwrap_or(Value::Nil);
+            }
+            Value::List(items.into())
+        }
+        Some(SpecialForm::Delay)
+        | Some(SpecialForm::While)
+        | Some(SpecialForm::Break)
+        | Some(SpecialForm::Continue)
+        | Some(SpecialForm::Try)
+        | Some(SpecialForm::Profile)
+        | Some(SpecialForm::Time)
+        | Some(SpecialForm::Bench) => optimize_from(items.to_vec(), 1, locals, env),
+        // The target name is not a call head, so it is never resolved
+        Some(SpecialForm::Set) | Some(SpecialForm::Defconstant) => {
+            optimize_from(items.to_vec(), 2, locals, env)
+        }
+        Some(SpecialForm::Dotimes) | Some(SpecialForm::Dolist) => {
+            optimize_loop(items.to_vec(), locals, env)
+        }
+        None => optimize_call(items.to_vec(), locals, env),
+    }
+}
+
+/// Optimize every element from `start` on
+fn optimize_items(
+    items: Vec<Value>,
+    start: usize,
+    locals: &HashSet<Symbol>,
+    env: &EnvRef,
+) -> Vec<Value> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| {
+            if i >= start {
+                optimize_expr(item, locals, env)
+            } else {
+                item
+            }
+        })
+        .collect()
+}
+
+fn optimize_from(items: Vec<Value>, start: usize, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    Value::List(optimize_items(items, start, locals, env).into())
+}
+
+/// Optimize the body of a form that binds `params` (plus any inner defines)
+fn optimize_scoped(
+    items: Vec<Value>,
+    start: usize,
+    params: &[Value],
+    locals: &HashSet<Symbol>,
+    env: &EnvRef,
+) -> Value {
+    let mut inner = locals.clone();
+    for param in params {
+        inner.extend(crate::destructure::param_names(param));
+    }
+    for item in items.iter().skip(start) {
+        collect_defines(item, &mut inner);
+    }
+    Value::List(optimize_items(items, start, &inner, env).into())
+}
+
+fn optimize_lambda(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let params = match items.get(1) {
+        Some(Value::List(params)) => params.to_vec(),
+        _ => Vec::new(),
+    };
+    optimize_scoped(items, 2, &params, locals, env)
+}
+
+fn optimize_let(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let mut items = items;
+    let mut names = Vec::new();
+
+    // Binding expressions are evaluated in the outer scope
+    if let Some(Value::List(bindings)) = items.get_mut(1) {
+        for binding in Arc::make_mut(bindings) {
+            if let Value::List(pair) = binding {
+                if pair.len() == 2 {
+                    names.push(pair[0].clone());
+                    let pair = Arc::make_mut(pair);
+                    let init = std::mem::replace(&mut pair[1], Value::Nil);
+                    pair[1] = optimize_expr(init, locals, env);
+                }
+            }
+        }
+    }
+
+    optimize_scoped(items, 2, &names, locals, env)
+}
+
+/// `(case key clauses...)`: optimize the key and clause bodies, and index the clauses
+///
+/// A dispatch table from datum to clause is inserted after the key, so the
+/// evaluator finds the clause without comparing against each datum. Malformed
+/// clauses are left for the evaluator to report.
+fn optimize_case(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let mut items = items;
+    if let Some(key) = items.get_mut(1) {
+        let expr = std::mem::replace(key, Value::Nil);
+        *key = optimize_expr(expr, locals, env);
+    }
+    for clause in items.iter_mut().skip(2) {
+        if let Value::List(clause) = clause {
+            for expr in Arc::make_mut(clause).iter_mut().skip(1) {
+                let code = std::mem::replace(expr, Value::Nil);
+                *expr = optimize_expr(code, locals, env);
+            }
+        }
+    }
+
+    let indexed = matches!(items.get(2), Some(Value::Map(_)));
+    if !indexed && items.len() > 2 {
+        if let Ok(table) = crate::eval::case_table(&items[2..]) {
+            items.insert(2, Value::Map(table));
+        }
+    }
+    Value::List(items.into())
+}
+
+/// `(dotimes (var init) body...)`: init in the outer scope, body with `var` bound
+fn optimize_loop(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let mut items = items;
+    let mut names = Vec::new();
+
+    if let Some(Value::List(spec)) = items.get_mut(1) {
+        if spec.len() == 2 {
+            names.push(spec[0].clone());
+            let spec = Arc::make_mut(spec);
+            let init = std::mem::replace(&mut spec[1], Value::Nil);
+            spec[1] = optimize_expr(init, locals, env);
+        }
+    }
+
+    optimize_scoped(items, 2, &names, locals, env)
+}
+
+fn optimize_if(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let mut items = optimize_items(items, 1, locals, env);
+    if items.len() != 3 && items.len() != 4 {
+        return Value::List(items.into());
+    }
+
+    match literal_truthiness(&items[1]) {
+        Some(true) => items.swap_remove(2),
+        Some(false) if items.len() == 4 => items.swap_remove(3),
+        Some(false) => Value::Nil,
+        None => Value::List(items.into()),
+    }
+}
+
+fn optimize_call(items: Vec<Value>, locals: &HashSet<Symbol>, env: &EnvRef) -> Value {
+    let mut items = optimize_items(items, 0, locals, env);
+
+    // Only protected builtins cannot change later; any other global, even one
+    // holding a builtin now, is looked up at each call
+    let (name, builtin) = match &items[0] {
+        Value::Symbol(name)
+            if !locals.contains(name) && env.resolved_frozen_as(name) == Some(Frozen::Builtin) =>
+        {
+            match env.get(name) {
+                Some(Value::BuiltIn(f)) => (name.clone(), f),
+                _ => return Value::List(items.into()),
+            }
+        }
+        _ => return Value::List(items.into()),
+    };
+
+    if FOLDABLE.contains(&name.as_str()) && items[1..].iter().all(|v| matches!(v, Value::Number(_)))
+    {
+        if let Ok(folded) = builtin(&items[1..]) {
+            return folded;
+        }
+    }
+
+    items[0] = Value::BuiltIn(builtin);
+    Value::List(items.into())
+}
+
+/// Truthiness of a literal condition, or None if it must be evaluated
+fn literal_truthiness(expr: &Value) -> Option<bool> {
+    match expr {
+        Value::Bool(b) => Some(*b),
+        Value::Nil => Some(false),
+        Value::Symbol(name) if name == "nil" => Some(false),
+        Value::Number(_) | Value::String(_) | Value::Keyword(_) => Some(true),
+        _ => None,
+    }
+}
+
+/// Record every name bound by a `define` inside `expr` (over-approximates scopes)
+fn collect_defines(expr: &Value, names: &mut HashSet<Symbol>) {
+    if let Value::List(items) = expr {
+        match SpecialForm::of(items.first().unwrap_or(&Value::Nil)) {
+            Some(SpecialForm::Quote) | Some(SpecialForm::Quasiquote) => return,
+            Some(SpecialForm::Define) | Some(SpecialForm::Defconstant) => match items.get(1) {
+                Some(Value::Symbol(name)) => {
+                    names.insert(name.clone());
+                }
+                Some(Value::List(signature)) => {
+                    if let Some(Value::Symbol(name)) = signature.first() {
+                        names.insert(name.clone());
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        for item in items.iter() {
+            collect_defines(item, names);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::register_builtins;
+    use crate::env::Environment;
+    use crate::parser::parse;
+
+    /// Optimize `source` against protected builtins, as the CLI runs code
+    fn optimized(source: &str, params: &[&str]) -> String {
+        let env = Environment::new();
+        register_builtins(env.clone());
+        for (name, _) in env.bindings() {
+            env.protect(&name);
+        }
+        let params: Vec<Symbol> = params.iter().map(|p| Symbol::new(p)).collect();
+        optimize(parse(source).unwrap(), &params, &env).to_string()
+    }
+
+    #[test]
+    fn test_folds_constant_arithmetic() {
+        assert_eq!(optimized("(* 2 (+ 1 2))", &[]), "6");
+        assert_eq!(optimized("(< 1 2)", &[]), "#t");
+    }
+
+    #[test]
+    fn test_keeps_failing_folds_for_runtime() {
+        assert_eq!(optimized("(/ 1 0)", &[]), "(#<builtin> 1 0)");
+    }
+
+    #[test]
+    fn test_collapses_begin_and_literal_if() {
+        assert_eq!(optimized("(begin x)", &["x"]), "x");
+        assert_eq!(optimized("(if #t a b)", &["a", "b"]), "a");
+        assert_eq!(optimized("(if nil a b)", &["a", "b"]), "b");
+        assert_eq!(optimized("(if #f a)", &["a"]), "nil");
+        assert_eq!(optimized("(if x a b)", &["x", "a", "b"]), "(if x a b)");
+    }
+
+    #[test]
+    fn test_indexes_case_clauses() {
+        assert_eq!(
+            optimized("(case x ((1 2) (+ 1 1)) (:a y) (else 0))", &["x", "y"]),
+            "(case x {:1 0 :2 0 ::a 1} ((1 2) 2) (:a y) (else 0))"
+        );
+        // Malformed clauses are left for the evaluator
+        assert_eq!(optimized("(case x ((1)))", &["x"]), "(case x ((1)))");
+    }
+
+    #[test]
+    fn test_resolves_builtins_but_not_locals() {
+        assert_eq!(optimized("(car x)", &["x"]), "(#<builtin> x)");
+        assert_eq!(optimized("(car x)", &["car", "x"]), "(car x)");
+        assert_eq!(
+            optimized("(let ((car cdr)) (car x))", &["x"]),
+            "(let ((car cdr)) (car x))"
+        );
+        assert_eq!(
+            optimized("(begin (define (car y) y) (car 1))", &[]),
+            "(begin (define (car y) y) (car 1))"
+        );
+    }
+
+    #[test]
+    fn test_leaves_unprotected_globals_for_runtime() {
+        let env = Environment::new();
+        register_builtins(env.clone());
+        env.define("op", env.get("car").unwrap());
+        let body = optimize(parse("(op (car x))").unwrap(), &[Symbol::new("x")], &env);
+        assert_eq!(body.to_string(), "(op (car x))");
+    }
+
+    #[test]
+    fn test_loop_variables_are_locals() {
+        assert_eq!(
+            optimized("(dotimes (car (+ 1 2)) (car x))", &["x"]),
+            "(dotimes (car 3) (car x))"
+        );
+        assert_eq!(
+            optimized("(set! car (car x))", &["x"]),
+            "(set! car (#<builtin> x))"
+        );
+    }
+
+    #[test]
+    fn test_leaves_quoted_data_alone() {
+        assert_eq!(optimized("(quote (+ 1 2))", &[]), "(quote (+ 1 2))");
+    }
+}
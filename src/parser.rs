@@ -10,7 +10,13 @@ use nom::{
 };
 
 use crate::value::Value;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+
+/// How deeply lists, maps and quote prefixes may nest before parsing fails
+///
+/// The parser recurses once per level, so without a limit deeply nested input
+/// would overflow even the evaluation stack instead of failing.
+pub const MAX_PARSE_DEPTH: usize = 30_000;
 
 // ============================================================================
 // Thread-Local Doc Comment Storage
@@ -19,9 +25,14 @@ use std::cell::RefCell;
 thread_local! {
     /// Holds doc comments (;;;) that precede a top-level expression
     static PENDING_DOCS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
-    /// Flag to skip auto-registration of help entries during stdlib loading
-    /// This prevents stdlib functions from being registered as "User Defined"
-    static SKIP_HELP_REGISTRATION: RefCell<bool> = const { RefCell::new(false) };
+    /// Help category for documented definitions, set while a stdlib module loads
+    /// so its functions are not registered as "User Defined"
+    static DEFINITION_CATEGORY: RefCell<Option<String>> = const { RefCell::new(None) };
+    /// Text of the expression `parse` read last, with its value, so `define`
+    /// can keep a top-level definition as written
+    static PENDING_SOURCE: RefCell<Option<(String, Value)>> = const { RefCell::new(None) };
+    /// Expressions being parsed on this thread, checked against [`MAX_PARSE_DEPTH`]
+    static PARSE_DEPTH: Cell<usize> = const { Cell::new(0) };
 }
 
 /// Store doc comments to be attached to the next defined function
@@ -34,14 +45,46 @@ pub fn take_pending_docs() -> Vec<String> {
     PENDING_DOCS.with(|d| std::mem::take(&mut *d.borrow_mut()))
 }
 
-/// Check if help registration is currently skipped (during stdlib loading)
-pub fn should_skip_help_registration() -> bool {
-    SKIP_HELP_REGISTRATION.with(|flag| *flag.borrow())
+/// Source text of `form` if it is the expression [`parse`] read last
+///
+/// A top-level `define` uses this to keep the definition exactly as written;
+/// forms built by macros or by the host have no text.
+pub fn source_text(form: &Value) -> Option<String> {
+    PENDING_SOURCE.with(|pending| match pending.borrow().as_ref() {
+        Some((text, read)) if crate::value::values_equal(read, form) => Some(text.clone()),
+        _ => None,
+    })
+}
+
+/// The pending source text, for moving it to another thread with [`set_pending_source`]
+pub(crate) fn take_pending_source() -> Option<(String, Value)> {
+    PENDING_SOURCE.with(|pending| pending.borrow_mut().take())
 }
 
-/// Set the help registration skip flag
-pub fn set_skip_help_registration(skip: bool) {
-    SKIP_HELP_REGISTRATION.with(|flag| *flag.borrow_mut() = skip);
+/// Replace the pending source text
+pub(crate) fn set_pending_source(source: Option<(String, Value)>) {
+    PENDING_SOURCE.with(|pending| *pending.borrow_mut() = source);
+}
+
+/// Help category that documented `define`s and `defmacro`s are registered under
+pub fn definition_category() -> String {
+    DEFINITION_CATEGORY.with(|category| {
+        category
+            .borrow()
+            .clone()
+            .unwrap_or_else(|| "User Defined".to_string())
+    })
+}
+
+/// Register documented definitions under `category` instead of "User Defined"; `None` restores it
+pub fn set_definition_category(category: Option<String>) {
+    DEFINITION_CATEGORY.with(|current| *current.borrow_mut() = category);
+}
+
+/// The category set by [`set_definition_category`], if any
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn definition_category_setting() -> Option<String> {
+    DEFINITION_CATEGORY.with(|category| category.borrow().clone())
 }
 
 // ============================================================================
@@ -171,7 +214,7 @@ fn parse_bool(input: &str) -> IResult<&str, Value> {
 fn parse_keyword(input: &str) -> IResult<&str, Value> {
     let (input, _) = char(':')(input)?;
     let (input, first) =
-        one_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+-*/%<>=!?")(input)?;
+        one_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+-*/%<>=!?&")(input)?;
     let (input, rest) = take_while::<_, _, nom::error::Error<_>>(|c: char| {
         c.is_alphanumeric()
             || c == '-'
@@ -199,7 +242,7 @@ fn parse_keyword(input: &str) -> IResult<&str, Value> {
 /// Followed by alphanumeric, -, or _
 fn parse_symbol(input: &str) -> IResult<&str, Value> {
     let (input, first) =
-        one_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+-*/%<>=!?")(input)?;
+        one_of("abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ+-*/%<>=!?&")(input)?;
     let (input, rest) = take_while::<_, _, nom::error::Error<_>>(|c: char| {
         c.is_alphanumeric()
             || c == '-'
@@ -213,6 +256,7 @@ fn parse_symbol(input: &str) -> IResult<&str, Value> {
             || c == '*'
             || c == '/'
             || c == '%'
+            || c == '&'
             || c == ':' // Allow : in symbols for namespace support (fs:read)
     })(input)?;
 
@@ -220,7 +264,7 @@ fn parse_symbol(input: &str) -> IResult<&str, Value> {
     symbol.push(first);
     symbol.push_str(rest);
 
-    Ok((input, Value::Symbol(symbol)))
+    Ok((input, Value::Symbol(symbol.into())))
 }
 
 /// Parse a string with escape sequences
@@ -230,7 +274,7 @@ fn parse_string(input: &str) -> IResult<&str, Value> {
 
     // Handle empty strings
     if let Ok((input, _)) = char::<_, nom::error::Error<_>>('"')(input) {
-        return Ok((input, Value::String(String::new())));
+        return Ok((input, Value::String(String::new().into())));
     }
 
     let (input, content) = escaped(none_of("\"\\"), '\\', one_of("\"\\nt"))(input)?;
@@ -259,7 +303,102 @@ fn parse_string(input: &str) -> IResult<&str, Value> {
         }
     }
 
-    Ok((input, Value::String(result)))
+    Ok((input, Value::String(result.into())))
+}
+
+/// Parse an interpolated string: #"Hello {name}!" -> (str "Hello " name "!")
+///
+/// Each `{expr}` is read as an expression and its display form is spliced in
+/// by the `str` builtin, which is referenced directly so that a local named
+/// `str` does not change what the string means. `{{` and `}}` stand for
+/// literal braces. The string's escapes are processed first, so a string
+/// inside an expression is written with `\"`. Without any `{expr}` this is
+/// just a string.
+fn parse_interpolated_string(input: &str) -> IResult<&str, Value> {
+    let start = input;
+    let (input, _) = char('#')(input)?;
+    let (input, text) = match parse_string(input)? {
+        (input, Value::String(text)) => (input, text),
+        _ => unreachable!("parse_string returns a string"),
+    };
+    let parts = interpolation_parts(&text).map_err(|_| {
+        nom::Err::Failure(nom::error::Error::new(start, nom::error::ErrorKind::Verify))
+    })?;
+    match parts.as_slice() {
+        [] => Ok((input, Value::String(String::new().into()))),
+        [Value::String(literal)] => Ok((input, Value::String(literal.clone()))),
+        _ => {
+            let mut call = vec![Value::BuiltIn(crate::builtins::strings::builtin_str)];
+            call.extend(parts);
+            Ok((input, Value::List(call.into())))
+        }
+    }
+}
+
+/// Split the text of an interpolated string into literal strings and expressions
+pub(crate) fn interpolation_parts(text: &str) -> Result<Vec<Value>, String> {
+    let mut parts = Vec::new();
+    let mut literal = String::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("{{") || rest.starts_with("}}") {
+            literal.push(c);
+            rest = &rest[2..];
+            continue;
+        }
+        if c == '}' {
+            return Err("unmatched '}' in interpolated string (write '}}' for a brace)".into());
+        }
+        if c != '{' {
+            literal.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let source = &rest[1..];
+        let end = interpolation_end(source)
+            .ok_or("unclosed '{' in interpolated string (write '{{' for a brace)")?;
+        let code = &source[..end];
+        if code.trim().is_empty() {
+            return Err("empty '{}' in interpolated string".into());
+        }
+        let expr = match parse_expr(code) {
+            Ok((remaining, expr)) => match ws_and_comments(remaining) {
+                Ok(("", _)) => expr,
+                _ => return Err(format!("'{{{}}}' must hold exactly one expression", code)),
+            },
+            Err(_) => return Err(format!("cannot read '{{{}}}' in interpolated string", code)),
+        };
+        if !literal.is_empty() {
+            parts.push(Value::String(std::mem::take(&mut literal).into()));
+        }
+        parts.push(expr);
+        rest = &source[end + 1..];
+    }
+    if !literal.is_empty() {
+        parts.push(Value::String(literal.into()));
+    }
+    Ok(parts)
+}
+
+/// Byte offset of the `}` closing an interpolated expression, skipping nested braces and strings
+fn interpolation_end(source: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut i = 0;
+    while let Some(c) = source[i..].chars().next() {
+        match c {
+            '"' => {
+                i = string_end(source, i + 1)?;
+                continue;
+            }
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => {}
+        }
+        i += c.len_utf8();
+    }
+    None
 }
 
 /// Parse a quoted expression: 'expr -> (quote expr)
@@ -268,7 +407,7 @@ fn parse_quote(input: &str) -> IResult<&str, Value> {
     let (input, expr) = parse_expr(input)?;
     Ok((
         input,
-        Value::List(vec![Value::Symbol("quote".to_string()), expr]),
+        Value::List(vec![Value::Symbol("quote".into()), expr].into()),
     ))
 }
 
@@ -278,7 +417,7 @@ fn parse_quasiquote(input: &str) -> IResult<&str, Value> {
     let (input, expr) = parse_expr(input)?;
     Ok((
         input,
-        Value::List(vec![Value::Symbol("quasiquote".to_string()), expr]),
+        Value::List(vec![Value::Symbol("quasiquote".into()), expr].into()),
     ))
 }
 
@@ -292,14 +431,14 @@ fn parse_unquote(input: &str) -> IResult<&str, Value> {
         let (input, expr) = parse_expr(input)?;
         Ok((
             input,
-            Value::List(vec![Value::Symbol("unquote-splicing".to_string()), expr]),
+            Value::List(vec![Value::Symbol("unquote-splicing".into()), expr].into()),
         ))
     } else {
         // Just , (unquote)
         let (input, expr) = parse_expr(input)?;
         Ok((
             input,
-            Value::List(vec![Value::Symbol("unquote".to_string()), expr]),
+            Value::List(vec![Value::Symbol("unquote".into()), expr].into()),
         ))
     }
 }
@@ -320,7 +459,7 @@ fn parse_list(input: &str) -> IResult<&str, Value> {
             if items.is_empty() {
                 return Ok((rest, Value::Nil));
             }
-            return Ok((rest, Value::List(items)));
+            return Ok((rest, Value::List(items.into())));
         }
 
         // Parse an expression
@@ -336,12 +475,12 @@ fn parse_list(input: &str) -> IResult<&str, Value> {
 /// Parse a map: {:key1 value1 :key2 value2 ...}
 /// Keys must be keywords
 fn parse_map(input: &str) -> IResult<&str, Value> {
-    use std::collections::HashMap;
+    use std::collections::BTreeMap;
 
     let (input, _) = char('{')(input)?;
     let (input, _) = ws_and_comments(input)?;
 
-    let mut map = HashMap::new();
+    let mut map = BTreeMap::new();
     let mut remaining = input;
 
     loop {
@@ -375,8 +514,35 @@ fn parse_map(input: &str) -> IResult<&str, Value> {
     }
 }
 
+/// One expression being parsed, counted until it is dropped
+struct ParseDepth {
+    level: usize,
+}
+
+impl ParseDepth {
+    /// Count a nested expression, failing with `TooLarge` past [`MAX_PARSE_DEPTH`]
+    fn enter(input: &str) -> Result<Self, nom::Err<nom::error::Error<&str>>> {
+        let level = PARSE_DEPTH.with(Cell::get);
+        if level >= MAX_PARSE_DEPTH {
+            return Err(nom::Err::Failure(nom::error::Error::new(
+                input,
+                nom::error::ErrorKind::TooLarge,
+            )));
+        }
+        PARSE_DEPTH.with(|depth| depth.set(level + 1));
+        Ok(ParseDepth { level })
+    }
+}
+
+impl Drop for ParseDepth {
+    fn drop(&mut self) {
+        PARSE_DEPTH.with(|depth| depth.set(self.level));
+    }
+}
+
 /// Main expression parser - tries all alternatives
 fn parse_expr(input: &str) -> IResult<&str, Value> {
+    let _depth = ParseDepth::enter(input)?;
     let (input, _) = ws_and_comments(input)?;
     alt((
         parse_quote,
@@ -384,6 +550,7 @@ fn parse_expr(input: &str) -> IResult<&str, Value> {
         parse_unquote,
         parse_map, // Try map before list
         parse_list,
+        parse_interpolated_string, // Before bool, which also starts with #
         parse_bool,
         parse_number,
         parse_string,
@@ -415,6 +582,8 @@ pub fn parse(input: &str) -> Result<Value, String> {
     // Parse the expression
     match parse_expr(input_after_docs) {
         Ok((rest, value)) => {
+            let text = &input_after_docs[..input_after_docs.len() - rest.len()];
+            set_pending_source(Some((text.trim().to_string(), value.clone())));
             // Check if there's unconsumed input (after skipping trailing whitespace)
             let (rest, _) = ws_and_comments(rest).unwrap_or((rest, ()));
             if !rest.is_empty() {
@@ -426,10 +595,391 @@ pub fn parse(input: &str) -> Result<Value, String> {
                 Ok(value)
             }
         }
+        Err(nom::Err::Failure(e)) if e.code == nom::error::ErrorKind::TooLarge => Err(format!(
+            "Parse error: expression nested more than {} levels deep",
+            MAX_PARSE_DEPTH
+        )),
         Err(e) => Err(format!("Parse error: {}", e)),
     }
 }
 
+// ============================================================================
+// Multi-Expression Input
+// ============================================================================
+
+/// Skip whitespace and NON-DOC comments in the input string
+/// Preserves ;;; doc comments so they can be captured by parse()
+pub fn skip_whitespace_and_regular_comments(input: &str) -> &str {
+    let mut remaining = input;
+    loop {
+        remaining = remaining.trim_start();
+        // Skip only ; and ;; comments, NOT ;;; doc comments
+        if remaining.starts_with(";;;") {
+            // Don't skip doc comments!
+            break;
+        } else if remaining.starts_with(";;") || remaining.starts_with(';') {
+            // Skip regular comments
+            if let Some(pos) = remaining.find('\n') {
+                remaining = &remaining[pos + 1..];
+            } else {
+                remaining = "";
+            }
+        } else {
+            break;
+        }
+    }
+    remaining
+}
+
+/// Parse one expression and return it along with the remaining input
+///
+/// This function preserves ;;; doc comments and includes them in the parsed string
+/// so that parse() can capture them via the thread-local PENDING_DOCS.
+pub fn parse_one_expr(input: &str) -> Result<(Value, &str), String> {
+    // Skip only non-doc comments and whitespace, preserve ;;; comments
+    let start = skip_whitespace_and_regular_comments(input);
+    if start.is_empty() {
+        return Err("No expression to parse".to_string());
+    }
+
+    // Collect any preceding ;;; doc comments
+    let mut doc_start = start;
+    while doc_start.starts_with(";;;") {
+        // Find the end of this doc comment line
+        if let Some(pos) = doc_start.find('\n') {
+            doc_start = &doc_start[pos + 1..];
+            doc_start = skip_whitespace_and_regular_comments(doc_start);
+        } else {
+            break;
+        }
+    }
+
+    // Find the end of the first complete s-expression (after the doc comments)
+    let end_pos = find_expr_end(doc_start)?;
+
+    // Include everything from the start of doc comments to the end of the expression
+    let bytes_from_start = start.len() - doc_start.len();
+    let total_expr_len = bytes_from_start + end_pos;
+    let expr_str = &start[..total_expr_len];
+    let rest = &start[total_expr_len..];
+
+    // Parse the expression (this will capture ;;; comments via thread-local)
+    let expr = parse(expr_str)?;
+    Ok((expr, rest))
+}
+
+/// Find the byte offset just past the first complete expression
+///
+/// Quote prefixes are part of the expression they quote. Lists and maps end
+/// at their matching bracket, skipping over strings (with their escapes) and
+/// comments inside them; strings, including interpolated `#"..."` ones, end
+/// at their closing quote.
+fn find_expr_end(input: &str) -> Result<usize, String> {
+    let mut chars = input.char_indices().peekable();
+
+    // Skip initial whitespace and any quote prefixes
+    while let Some(&(_, c)) = chars.peek() {
+        if !(c.is_whitespace() || matches!(c, '\'' | '`' | ',' | '@')) {
+            break;
+        }
+        chars.next();
+    }
+    let Some(&(start, first)) = chars.peek() else {
+        return Err("Empty input".to_string());
+    };
+
+    if first == '"' || (first == '#' && input[start + 1..].starts_with('"')) {
+        let quote = if first == '#' { start + 1 } else { start };
+        return string_end(input, quote + 1).ok_or_else(|| "Unterminated string".to_string());
+    }
+
+    if first == '(' || first == '{' {
+        // List or map - find the matching closing bracket
+        let mut depth = 0;
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    let end = string_end(input, i + 1)
+                        .ok_or_else(|| "Unterminated string".to_string())?;
+                    while chars.next_if(|&(j, _)| j < end).is_some() {}
+                }
+                ';' => while chars.next_if(|&(_, c)| c != '\n').is_some() {},
+                '(' | '{' => depth += 1,
+                ')' | '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return Ok(i + 1);
+                    }
+                }
+                _ => {}
+            }
+        }
+        return Err("Unclosed s-expression".to_string());
+    }
+
+    // Atom - find end of token
+    Ok(chars
+        .find(|&(_, c)| c.is_whitespace() || c == ')')
+        .map_or(input.len(), |(i, _)| i))
+}
+
+/// Byte offset just past the closing quote of a string whose contents start at `from`
+fn string_end(input: &str, from: usize) -> Option<usize> {
+    let mut chars = input[from..].char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                chars.next();
+            }
+            '"' => return Some(from + i + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// ============================================================================
+// Syntax Checking
+// ============================================================================
+
+/// A problem found by [`check_syntax`], at a 1-based line and column
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl SyntaxError {
+    fn new(line: usize, column: usize, message: impl Into<String>) -> Self {
+        SyntaxError {
+            line,
+            column,
+            message: message.into(),
+        }
+    }
+}
+
+/// Characters of the source with the line and column of the next one
+struct Cursor<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Cursor<'_> {
+    fn peek(&mut self) -> Option<char> {
+        self.chars.peek().copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+}
+
+/// A list or map whose closing bracket has not been seen yet
+struct OpenForm {
+    bracket: char,
+    line: usize,
+    column: usize,
+    elements: usize,
+}
+
+/// Find every syntax error in `source` without evaluating anything
+///
+/// Unlike [`parse`], which stops at the first problem and does not say where
+/// it is, this keeps going: a stray closing bracket is skipped, a mismatched
+/// one still closes the innermost form, and a token the reader rejects is
+/// reported and passed over. Besides brackets it checks strings and their
+/// escapes, quotes with nothing after them, and map literals (keyword keys and
+/// a value for every key). An unterminated string ends the check, since the
+/// rest of the file is inside it.
+pub fn check_syntax(source: &str) -> Vec<SyntaxError> {
+    let mut errors = Vec::new();
+    let mut stack: Vec<OpenForm> = Vec::new();
+    // Position of a quote, quasiquote or unquote still waiting for its expression
+    let mut prefix: Option<(usize, usize)> = None;
+    let mut cursor = Cursor {
+        chars: source.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    while let Some(c) = cursor.peek() {
+        let (line, column) = (cursor.line, cursor.column);
+        if c.is_whitespace() {
+            cursor.next();
+            continue;
+        }
+        if c == ';' {
+            while cursor.peek().is_some_and(|c| c != '\n') {
+                cursor.next();
+            }
+            continue;
+        }
+
+        if c == ')' || c == '}' {
+            cursor.next();
+            if let Some((line, column)) = prefix.take() {
+                errors.push(SyntaxError::new(line, column, "nothing to quote"));
+            }
+            match stack.pop() {
+                None => errors.push(SyntaxError::new(
+                    line,
+                    column,
+                    format!("unexpected '{}'", c),
+                )),
+                Some(open) => {
+                    let expected = if open.bracket == '{' { '}' } else { ')' };
+                    if c != expected {
+                        errors.push(SyntaxError::new(
+                            line,
+                            column,
+                            format!(
+                                "expected '{}' to close '{}' from {}:{}, found '{}'",
+                                expected, open.bracket, open.line, open.column, c
+                            ),
+                        ));
+                    } else if open.bracket == '{' && open.elements % 2 == 1 {
+                        errors.push(SyntaxError::new(
+                            open.line,
+                            open.column,
+                            "map literal has a key without a value",
+                        ));
+                    }
+                }
+            }
+            continue;
+        }
+
+        // Anything else starts an expression, unless a quote prefix already started it
+        if prefix.is_none() {
+            if let Some(open) = stack.last_mut() {
+                if open.bracket == '{' && open.elements % 2 == 0 && c != ':' {
+                    errors.push(SyntaxError::new(line, column, "map keys must be keywords"));
+                }
+                open.elements += 1;
+            }
+        }
+        prefix = None;
+        cursor.next();
+        match c {
+            '\'' | '`' | ',' => {
+                if c == ',' && cursor.peek() == Some('@') {
+                    cursor.next();
+                }
+                prefix = Some((line, column));
+            }
+            '(' | '{' => stack.push(OpenForm {
+                bracket: c,
+                line,
+                column,
+                elements: 0,
+            }),
+            '"' => {
+                if check_string(&mut cursor, &mut errors).is_none() {
+                    errors.push(SyntaxError::new(line, column, "unterminated string"));
+                    return errors;
+                }
+            }
+            '#' if cursor.peek() == Some('"') => {
+                cursor.next();
+                let Some(text) = check_string(&mut cursor, &mut errors) else {
+                    errors.push(SyntaxError::new(line, column, "unterminated string"));
+                    return errors;
+                };
+                if let Ok((_, Value::String(text))) = parse_string(&format!("\"{}\"", text)) {
+                    if let Err(message) = interpolation_parts(&text) {
+                        errors.push(SyntaxError::new(line, column, message));
+                    }
+                }
+            }
+            _ => {
+                let mut token = String::from(c);
+                while let Some(c) = cursor.peek() {
+                    if c.is_whitespace() || "(){}\";'`,".contains(c) {
+                        break;
+                    }
+                    token.push(c);
+                    cursor.next();
+                }
+                if let Some(offset) = rejected_offset(&token) {
+                    errors.push(SyntaxError::new(
+                        line,
+                        column + token[..offset].chars().count(),
+                        format!("invalid token '{}'", token),
+                    ));
+                }
+            }
+        }
+    }
+
+    if let Some((line, column)) = prefix {
+        errors.push(SyntaxError::new(line, column, "nothing to quote"));
+    }
+    for open in stack.iter().rev() {
+        errors.push(SyntaxError::new(
+            open.line,
+            open.column,
+            format!("'{}' is never closed", open.bracket),
+        ));
+    }
+    errors
+}
+
+/// Check the rest of a string after its opening quote, returning its text as written
+///
+/// Unknown escapes are reported; `None` means the string is never closed.
+fn check_string(cursor: &mut Cursor, errors: &mut Vec<SyntaxError>) -> Option<String> {
+    let mut text = String::new();
+    loop {
+        let (escape_line, escape_column) = (cursor.line, cursor.column);
+        match cursor.next()? {
+            '"' => return Some(text),
+            '\\' => {
+                text.push('\\');
+                match cursor.next()? {
+                    escaped @ ('"' | '\\' | 'n' | 't') => text.push(escaped),
+                    other => errors.push(SyntaxError::new(
+                        escape_line,
+                        escape_column,
+                        format!("unknown escape '\\{}' in string", other),
+                    )),
+                }
+            }
+            c => text.push(c),
+        }
+    }
+}
+
+/// Byte offset where the reader stops accepting `token`, if it does not read all of it
+///
+/// A token can hold several atoms with nothing between them, like `1abc`,
+/// which the reader takes as `1` followed by `abc`.
+fn rejected_offset(token: &str) -> Option<usize> {
+    let mut rest = token;
+    while !rest.is_empty() {
+        match parse_expr(rest) {
+            Ok((remaining, _)) if remaining.len() < rest.len() => rest = remaining,
+            _ => return Some(token.len() - rest.len()),
+        }
+    }
+    None
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -481,15 +1031,25 @@ mod tests {
 
     #[test]
     fn test_parse_string() {
-        assert!(matches!(parse(r#""hello""#), Ok(Value::String(s)) if s == "hello"));
-        assert!(matches!(parse(r#""hello world""#), Ok(Value::String(s)) if s == "hello world"));
+        assert!(matches!(parse(r#""hello""#), Ok(Value::String(s)) if s == "hello".into()));
+        assert!(
+            matches!(parse(r#""hello world""#), Ok(Value::String(s)) if s == "hello world".into())
+        );
         assert!(matches!(parse(r#""""#), Ok(Value::String(s)) if s.is_empty()));
 
         // Escape sequences
-        assert!(matches!(parse(r#""hello\nworld""#), Ok(Value::String(s)) if s == "hello\nworld"));
-        assert!(matches!(parse(r#""hello\tworld""#), Ok(Value::String(s)) if s == "hello\tworld"));
-        assert!(matches!(parse(r#""say \"hi\"""#), Ok(Value::String(s)) if s == r#"say "hi""#));
-        assert!(matches!(parse(r#""back\\slash""#), Ok(Value::String(s)) if s == r"back\slash"));
+        assert!(
+            matches!(parse(r#""hello\nworld""#), Ok(Value::String(s)) if s == "hello\nworld".into())
+        );
+        assert!(
+            matches!(parse(r#""hello\tworld""#), Ok(Value::String(s)) if s == "hello\tworld".into())
+        );
+        assert!(
+            matches!(parse(r#""say \"hi\"""#), Ok(Value::String(s)) if s == r#"say "hi""#.into())
+        );
+        assert!(
+            matches!(parse(r#""back\\slash""#), Ok(Value::String(s)) if s == r"back\slash".into())
+        );
     }
 
     #[test]
@@ -693,9 +1253,83 @@ mod tests {
         assert!(parse(")").is_err());
     }
 
+    #[test]
+    fn test_parse_interpolated_string() {
+        let parsed = parse(r#"#"Hi {name}, {(+ a 1)} {{x}}""#).unwrap();
+        let Value::List(items) = parsed else {
+            panic!("expected a call, got {}", parsed);
+        };
+        assert!(matches!(items[0], Value::BuiltIn(_)));
+        assert_eq!(
+            Value::List(items[1..].into()).to_string(),
+            r#"("Hi " name ", " (+ a 1) " {x}")"#
+        );
+
+        // Without expressions it is an ordinary string
+        assert!(matches!(parse(r#"#"{{a}} b""#), Ok(Value::String(s)) if s == "{a} b".into()));
+        assert!(matches!(parse(r#"#"""#), Ok(Value::String(s)) if s.is_empty()));
+
+        for bad in [r#"#"{""#, r#"#"}""#, r#"#"{}""#, r#"#"{a b}""#] {
+            assert!(parse(bad).is_err(), "{} should not parse", bad);
+        }
+    }
+
+    #[test]
+    fn test_parse_one_expr_strings() {
+        let (expr, rest) = parse_one_expr("\"a b\" (f \"é) \\\" (\") '(1 2)").unwrap();
+        assert_eq!(expr.to_string(), "\"a b\"");
+        let (expr, rest) = parse_one_expr(rest).unwrap();
+        assert_eq!(expr.to_string(), "(f \"é) \\\" (\")");
+        let (expr, _) = parse_one_expr(rest).unwrap();
+        assert_eq!(expr.to_string(), "(quote (1 2))");
+    }
+
     #[test]
     fn test_parse_multiple_top_level() {
         // Should error on multiple top-level expressions
         assert!(parse("1 2").is_err());
     }
+
+    #[test]
+    fn test_check_syntax_accepts_valid_source() {
+        let source = ";; comment\n(define (f x) `(a ,x ,@(list 1)))\n{:a \"s\\n\" :b '(1 2)}\n1abc";
+        assert!(check_syntax(source).is_empty());
+    }
+
+    #[test]
+    fn test_check_syntax_reports_every_error() {
+        let source = "(define x 1))\n(print \"a\\q\")\n{:a 1 b 2 :c}\n(f #x)\n(g '";
+        let errors: Vec<String> = check_syntax(source).iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            errors,
+            vec![
+                "1:13: unexpected ')'",
+                "2:10: unknown escape '\\q' in string",
+                "3:7: map keys must be keywords",
+                "3:1: map literal has a key without a value",
+                "4:4: invalid token '#x'",
+                "5:4: nothing to quote",
+                "5:1: '(' is never closed",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_check_syntax_mismatched_and_unterminated() {
+        let errors = check_syntax("(a}\n\"open");
+        assert_eq!(
+            errors[0].to_string(),
+            "1:3: expected ')' to close '(' from 1:1, found '}'"
+        );
+        assert_eq!(errors[1].to_string(), "2:1: unterminated string");
+        assert_eq!(errors.len(), 2);
+
+        let errors = check_syntax("(print #\"{x} {y\")");
+        assert_eq!(
+            errors[0].to_string(),
+            "1:8: unclosed '{' in interpolated string (write '{{' for a brace)"
+        );
+        assert_eq!(errors.len(), 1);
+        assert!(check_syntax("(print #\"{x} {{y}}\")").is_empty());
+    }
 }
@@ -0,0 +1,311 @@
+// ABOUTME: Services that differ between native and browser builds: live HTTP requests, the clock and process memory
+// Native builds use ureq and std::time; the `wasm` feature uses XMLHttpRequest and JavaScript's Date
+
+use crate::sandbox::{HttpResponse, SandboxError};
+
+// ============================================================================
+// HTTP transport
+// ============================================================================
+
+/// Perform a live HTTP request with ureq (after the sandbox checks have passed)
+#[cfg(feature = "http")]
+pub(crate) fn send_http(
+    url: &str,
+    method: &str,
+    headers: Option<Vec<(String, String)>>,
+    body: Option<&str>,
+    timeout_ms: Option<u64>,
+) -> Result<HttpResponse, SandboxError> {
+    let response = open_http(url, method, headers, body, timeout_ms)?;
+    let status = response.status();
+    let headers_map = response_headers(&response);
+
+    let body_str = response
+        .into_string()
+        .map_err(|e| SandboxError::IoError(format!("Failed to read response: {}", e)))?;
+
+    Ok(HttpResponse {
+        status,
+        headers: headers_map,
+        body: body_str,
+    })
+}
+
+/// Perform a live HTTP request with ureq, passing the body to `on_chunk` as it arrives
+///
+/// Chunks are split only between characters; invalid UTF-8 is replaced.
+/// `on_chunk` returns false to stop reading. The response returned has the
+/// status and headers, and an empty body.
+#[cfg(feature = "http")]
+pub(crate) fn stream_http(
+    url: &str,
+    method: &str,
+    headers: Option<Vec<(String, String)>>,
+    body: Option<&str>,
+    timeout_ms: Option<u64>,
+    on_chunk: &mut dyn FnMut(&str) -> bool,
+) -> Result<HttpResponse, SandboxError> {
+    use std::io::Read;
+
+    let response = open_http(url, method, headers, body, timeout_ms)?;
+    let head = HttpResponse {
+        status: response.status(),
+        headers: response_headers(&response),
+        body: String::new(),
+    };
+
+    let mut reader = response.into_reader();
+    let mut buffer = [0u8; 8192];
+    let mut pending = Vec::new();
+    loop {
+        let read = reader
+            .read(&mut buffer)
+            .map_err(|e| SandboxError::IoError(format!("Failed to read response: {}", e)))?;
+        if read == 0 {
+            break;
+        }
+        pending.extend_from_slice(&buffer[..read]);
+        // Hold back a character cut off at the end until the rest arrives
+        let complete = match std::str::from_utf8(&pending) {
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            _ => pending.len(),
+        };
+        let chunk = String::from_utf8_lossy(&pending[..complete]).into_owned();
+        pending.drain(..complete);
+        if !chunk.is_empty() && !on_chunk(&chunk) {
+            return Ok(head);
+        }
+    }
+    if !pending.is_empty() {
+        on_chunk(&String::from_utf8_lossy(&pending));
+    }
+    Ok(head)
+}
+
+/// Send a request with ureq, turning error statuses into [`SandboxError::HttpStatus`]
+#[cfg(feature = "http")]
+fn open_http(
+    url: &str,
+    method: &str,
+    headers: Option<Vec<(String, String)>>,
+    body: Option<&str>,
+    timeout_ms: Option<u64>,
+) -> Result<ureq::Response, SandboxError> {
+    let timeout_secs = timeout_ms.unwrap_or(30000) / 1000;
+    let timeout_duration = std::time::Duration::from_secs(timeout_secs);
+
+    let mut request = match method {
+        "GET" => ureq::get(url),
+        "POST" => ureq::post(url),
+        "PUT" => ureq::put(url),
+        "DELETE" => ureq::delete(url),
+        "PATCH" => ureq::patch(url),
+        "HEAD" => ureq::head(url),
+        _ => {
+            return Err(SandboxError::IoError(format!(
+                "Unsupported HTTP method: {}",
+                method
+            )))
+        }
+    };
+
+    // Set headers if provided
+    if let Some(header_list) = headers {
+        for (key, value) in header_list {
+            request = request.set(&key, &value);
+        }
+    }
+
+    request = request.timeout(timeout_duration);
+
+    let result = if let Some(body_str) = body {
+        request.send_string(body_str)
+    } else {
+        request.call()
+    };
+    match result {
+        Ok(response) => Ok(response),
+        Err(ureq::Error::Status(status, response)) => Err(SandboxError::HttpStatus {
+            status,
+            retry_after: response
+                .header("retry-after")
+                .and_then(|s| s.trim().parse().ok()),
+            message: format!("HTTP {} failed: {}: status code {}", method, url, status),
+        }),
+        Err(e) => Err(SandboxError::IoError(format!(
+            "HTTP {} failed: {}",
+            method, e
+        ))),
+    }
+}
+
+#[cfg(feature = "http")]
+fn response_headers(response: &ureq::Response) -> std::collections::HashMap<String, String> {
+    response
+        .headers_names()
+        .iter()
+        .map(|name| {
+            let value = response.header(name).unwrap_or("").to_string();
+            (name.to_string(), value)
+        })
+        .collect()
+}
+
+/// Perform a live HTTP request with a synchronous XMLHttpRequest
+///
+/// Evaluation is synchronous, so `fetch` (which returns a promise) cannot be
+/// awaited; a blocking XHR is the browser's synchronous equivalent. Run the
+/// interpreter in a Web Worker to keep the page responsive. Browsers refuse
+/// timeouts on synchronous requests, so `timeout_ms` is ignored, and the
+/// page's CORS rules apply on top of the sandbox's network allowlist.
+#[cfg(all(feature = "wasm", not(feature = "http")))]
+pub(crate) fn send_http(
+    url: &str,
+    method: &str,
+    headers: Option<Vec<(String, String)>>,
+    body: Option<&str>,
+    _timeout_ms: Option<u64>,
+) -> Result<HttpResponse, SandboxError> {
+    let failed = |e: web_sys::wasm_bindgen::JsValue| {
+        SandboxError::IoError(format!("HTTP {} failed: {:?}", method, e))
+    };
+
+    let xhr = web_sys::XmlHttpRequest::new().map_err(failed)?;
+    xhr.open_with_async(method, url, false).map_err(failed)?;
+    for (key, value) in headers.unwrap_or_default() {
+        xhr.set_request_header(&key, &value).map_err(failed)?;
+    }
+    xhr.send_with_opt_str(body).map_err(failed)?;
+
+    let status = xhr.status().map_err(failed)?;
+    let headers_map = xhr
+        .get_all_response_headers()
+        .map_err(failed)?
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(name, value)| (name.trim().to_lowercase(), value.trim().to_string()))
+        .collect();
+    let body_str = xhr.response_text().map_err(failed)?.unwrap_or_default();
+
+    Ok(HttpResponse {
+        status,
+        headers: headers_map,
+        body: body_str,
+    })
+}
+
+/// Builds without an HTTP client fail every live request (recorded responses still replay)
+#[cfg(not(any(feature = "http", feature = "wasm")))]
+pub(crate) fn send_http(
+    _url: &str,
+    method: &str,
+    _headers: Option<Vec<(String, String)>>,
+    _body: Option<&str>,
+    _timeout_ms: Option<u64>,
+) -> Result<HttpResponse, SandboxError> {
+    Err(SandboxError::IoError(format!(
+        "HTTP {} failed: this build has no HTTP client (enable the `http` or `wasm` feature)",
+        method
+    )))
+}
+
+/// Without a streaming client the whole body is fetched, then passed to `on_chunk` at once
+#[cfg(not(feature = "http"))]
+pub(crate) fn stream_http(
+    url: &str,
+    method: &str,
+    headers: Option<Vec<(String, String)>>,
+    body: Option<&str>,
+    timeout_ms: Option<u64>,
+    on_chunk: &mut dyn FnMut(&str) -> bool,
+) -> Result<HttpResponse, SandboxError> {
+    let mut response = send_http(url, method, headers, body, timeout_ms)?;
+    let body = std::mem::take(&mut response.body);
+    if !body.is_empty() {
+        on_chunk(&body);
+    }
+    Ok(response)
+}
+
+// ============================================================================
+// Clock
+// ============================================================================
+
+/// Seconds since the Unix epoch
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn unix_time() -> f64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs_f64())
+        .unwrap_or(0.0)
+}
+
+/// Seconds since the Unix epoch; `std::time` has no clock on wasm32-unknown-unknown
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn unix_time() -> f64 {
+    js_sys::Date::now() / 1000.0
+}
+
+/// Block the current thread for `ms` milliseconds
+#[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+pub(crate) fn sleep_ms(ms: f64) {
+    std::thread::sleep(std::time::Duration::from_secs_f64(ms.max(0.0) / 1000.0));
+}
+
+/// Block for `ms` milliseconds; wasm32 cannot park a thread, so this watches the clock
+#[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+pub(crate) fn sleep_ms(ms: f64) {
+    let until = js_sys::Date::now() + ms;
+    while js_sys::Date::now() < until {}
+}
+
+/// Measures how long an operation took
+pub(crate) struct Stopwatch {
+    #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+    started: std::time::Instant,
+    #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+    started: f64,
+}
+
+impl Stopwatch {
+    pub(crate) fn start() -> Self {
+        Stopwatch {
+            #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+            started: std::time::Instant::now(),
+            #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+            started: js_sys::Date::now(),
+        }
+    }
+
+    /// Milliseconds since [`Stopwatch::start`]
+    pub(crate) fn elapsed_ms(&self) -> f64 {
+        #[cfg(not(all(feature = "wasm", target_arch = "wasm32")))]
+        return self.started.elapsed().as_secs_f64() * 1000.0;
+        #[cfg(all(feature = "wasm", target_arch = "wasm32"))]
+        return (js_sys::Date::now() - self.started).max(0.0);
+    }
+
+    /// Time since [`Stopwatch::start`]
+    pub(crate) fn elapsed(&self) -> std::time::Duration {
+        std::time::Duration::from_secs_f64(self.elapsed_ms() / 1000.0)
+    }
+}
+
+// ============================================================================
+// Memory
+// ============================================================================
+
+/// Resident memory of the whole process in bytes, from `/proc/self/status`
+#[cfg(target_os = "linux")]
+pub(crate) fn resident_bytes() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmRSS:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kib * 1024)
+}
+
+/// Resident memory is only read on Linux; elsewhere, including wasm32, it is unknown
+#[cfg(not(target_os = "linux"))]
+pub(crate) fn resident_bytes() -> Option<u64> {
+    None
+}
@@ -0,0 +1,283 @@
+// ABOUTME: Declarative sandbox policy loaded from a TOML file with --config
+// Covers paths (read-only or read-write), network hosts and quotas, size and step limits, and capability modules
+
+use crate::config::{IoConfig, Profile, CAPABILITY_MODULES};
+use crate::sandbox::AddressPattern;
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+/// A sandbox policy, usually read from `lisp-sandbox.toml`
+///
+/// Every setting is optional; anything left out keeps the value from the
+/// profile (`default` unless given). CLI flags override the policy.
+///
+/// ```toml
+/// profile = "strict"
+///
+/// [filesystem]
+/// max_file_size = 1048576
+/// paths = [
+///   { path = "data", access = "rw" },
+///   { path = "examples", access = "ro" },
+/// ]
+///
+/// [network]
+/// enabled = true
+/// allow = ["api.github.com"]
+/// max_requests = 100
+/// max_rate = 2
+/// websocket = true
+///
+/// [limits]
+/// max_steps = 10000000
+///
+/// [capabilities]
+/// modules = ["console", "filesystem", "network"]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    /// Profile the rest of the policy adjusts; `--profile` overrides it
+    pub profile: Option<Profile>,
+    #[serde(default)]
+    pub filesystem: FsPolicy,
+    #[serde(default)]
+    pub network: NetPolicy,
+    #[serde(default)]
+    pub limits: LimitsPolicy,
+    #[serde(default)]
+    pub capabilities: CapabilitiesPolicy,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FsPolicy {
+    /// Sandbox directories; relative paths are resolved against the policy file's directory
+    pub paths: Option<Vec<PathRule>>,
+    pub max_file_size: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PathRule {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub access: Access,
+}
+
+/// Whether scripts may write to a sandbox directory
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Access {
+    #[default]
+    Rw,
+    Ro,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct NetPolicy {
+    pub enabled: Option<bool>,
+    /// Allowed hosts, as for `--net-allow`: `[scheme://]host[:port]`, with `*.` wildcards
+    pub allow: Option<Vec<String>>,
+    /// Most HTTP requests each interpreter may make
+    pub max_requests: Option<u64>,
+    /// Most `rate:acquire` calls per second, whatever the script's limiters say
+    pub max_rate: Option<f64>,
+    /// Allow `ws:connect`, as `--allow-websocket` does
+    pub websocket: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsPolicy {
+    /// Most evaluation steps for each top-level expression
+    pub max_steps: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CapabilitiesPolicy {
+    /// Capability modules to enable; the rest are removed. All are enabled when unset.
+    pub modules: Option<Vec<String>>,
+}
+
+impl Policy {
+    /// Read and validate a policy file
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read policy file {}: {}", path.display(), e))?;
+        let mut policy = Self::from_toml(&text)
+            .map_err(|e| format!("Invalid policy file {}: {}", path.display(), e))?;
+
+        let base = path.parent().unwrap_or(Path::new("."));
+        for rule in policy.filesystem.paths.iter_mut().flatten() {
+            if rule.path.is_relative() {
+                rule.path = base.join(&rule.path);
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Parse and validate a policy; relative paths are kept as written
+    pub fn from_toml(text: &str) -> Result<Self, String> {
+        let policy: Policy = toml::from_str(text).map_err(|e| e.to_string())?;
+
+        for pattern in policy.network.allow.iter().flatten() {
+            AddressPattern::parse(pattern)?;
+        }
+        if policy.network.max_rate.is_some_and(|rate| rate <= 0.0) {
+            return Err("network.max_rate must be greater than 0".to_string());
+        }
+
+        for module in policy.capabilities.modules.iter().flatten() {
+            if !CAPABILITY_MODULES.contains(&module.as_str()) {
+                return Err(format!(
+                    "unknown capability module '{}' (expected one of: {})",
+                    module,
+                    CAPABILITY_MODULES.join(", ")
+                ));
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Override the settings in `config` that the policy sets
+    pub fn apply(&self, config: &mut IoConfig) {
+        if let Some(rules) = &self.filesystem.paths {
+            let paths_with = |access| {
+                rules
+                    .iter()
+                    .filter(|rule| rule.access == access)
+                    .map(|rule| rule.path.clone())
+                    .collect()
+            };
+            config.filesystem.allowed_paths = paths_with(Access::Rw);
+            config.filesystem.read_only_paths = paths_with(Access::Ro);
+        }
+        if let Some(size) = self.filesystem.max_file_size {
+            config.filesystem.max_file_size = size;
+        }
+
+        if let Some(enabled) = self.network.enabled {
+            config.network.enabled = enabled;
+        }
+        if let Some(allow) = &self.network.allow {
+            config.network.allowed_addresses = allow.clone();
+        }
+        if let Some(max) = self.network.max_requests {
+            config.network.max_requests = Some(max);
+        }
+        if let Some(rate) = self.network.max_rate {
+            config.network.max_rate = Some(rate);
+        }
+        if let Some(websocket) = self.network.websocket {
+            config.network.allow_websocket = websocket;
+        }
+
+        if let Some(max) = self.limits.max_steps {
+            config.max_steps = Some(max);
+        }
+    }
+
+    /// Capability modules the policy leaves out of `capabilities.modules`
+    pub fn disabled_modules(&self) -> Vec<&'static str> {
+        match &self.capabilities.modules {
+            Some(enabled) => CAPABILITY_MODULES
+                .iter()
+                .copied()
+                .filter(|name| !enabled.iter().any(|module| module == name))
+                .collect(),
+            None => vec![],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_policy_changes_nothing() {
+        let policy = Policy::from_toml("").unwrap();
+        let mut config = Profile::Trusted.config();
+        policy.apply(&mut config);
+        assert_eq!(config.filesystem.allowed_paths.len(), 1);
+        assert!(config.network.enabled);
+        assert_eq!(config.max_steps, None);
+        assert_eq!(policy.profile, None);
+        assert!(policy.disabled_modules().is_empty());
+    }
+
+    #[test]
+    fn test_full_policy() {
+        let policy = Policy::from_toml(
+            r#"
+            profile = "strict"
+
+            [filesystem]
+            max_file_size = 2048
+            paths = [
+              { path = "out" },
+              { path = "docs", access = "ro" },
+            ]
+
+            [network]
+            enabled = true
+            allow = ["example.com"]
+            max_requests = 5
+            max_rate = 2.5
+            websocket = true
+
+            [limits]
+            max_steps = 1000
+
+            [capabilities]
+            modules = ["console", "filesystem"]
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(policy.profile, Some(Profile::Strict));
+        let mut config = IoConfig::default();
+        policy.apply(&mut config);
+
+        let fs = config.filesystem;
+        assert_eq!(fs.allowed_paths, vec![PathBuf::from("out")]);
+        assert_eq!(fs.read_only_paths, vec![PathBuf::from("docs")]);
+        assert_eq!(fs.max_file_size, 2048);
+
+        let net = config.network;
+        assert!(net.enabled);
+        assert_eq!(net.allowed_addresses, vec!["example.com"]);
+        assert_eq!(net.max_requests, Some(5));
+        assert_eq!(net.max_rate, Some(2.5));
+        assert!(net.allow_websocket);
+
+        assert_eq!(config.max_steps, Some(1000));
+        assert_eq!(
+            policy.disabled_modules(),
+            vec!["sessions", "network", "concurrency"]
+        );
+    }
+
+    #[test]
+    fn test_example_policy_parses() {
+        let policy = Policy::from_toml(include_str!("../examples/lisp-sandbox.toml")).unwrap();
+        let mut config = IoConfig::default();
+        policy.apply(&mut config);
+        assert_eq!(config.filesystem.read_only_paths, vec![PathBuf::from(".")]);
+    }
+
+    #[test]
+    fn test_rejects_mistakes() {
+        assert!(Policy::from_toml("[filesystem]\nmax_size = 1").is_err());
+        assert!(
+            Policy::from_toml("[filesystem]\npaths = [{ path = \"a\", access = \"w\" }]").is_err()
+        );
+        let err = Policy::from_toml("[capabilities]\nmodules = [\"files\"]").unwrap_err();
+        assert!(err.contains("unknown capability module 'files'"));
+        let err = Policy::from_toml("[network]\nallow = [\"example.com:web\"]").unwrap_err();
+        assert!(err.contains("invalid network allowlist entry 'example.com:web'"));
+    }
+}
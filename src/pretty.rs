@@ -0,0 +1,236 @@
+// ABOUTME: Line-width aware pretty-printer for values, used by pp and the REPL
+// Anything that fits stays on one line; longer lists and maps are broken and indented
+
+use crate::env::Environment;
+use crate::value::{more_marker, write_value_limited, PrintLimits, PrintStyle, Value};
+
+/// Default width for `pp` and for output that is not going to a terminal
+pub const DEFAULT_WIDTH: usize = 80;
+
+/// Global variable holding how many elements of each list or map the REPL and `pp` show
+pub const PRINT_LENGTH_VAR: &str = "*print-length*";
+
+/// Global variable holding how many levels of nested lists and maps the REPL and `pp` show
+pub const PRINT_DEPTH_VAR: &str = "*print-depth*";
+
+/// Value the CLI gives [`PRINT_LENGTH_VAR`] unless `--print-length` says otherwise
+pub const DEFAULT_PRINT_LENGTH: usize = 100;
+
+/// Value the CLI gives [`PRINT_DEPTH_VAR`] unless `--print-depth` says otherwise
+pub const DEFAULT_PRINT_DEPTH: usize = 32;
+
+/// The element limit set by [`PRINT_LENGTH_VAR`] in `env`: a positive number, or none
+pub fn print_length(env: &Environment) -> Option<usize> {
+    positive_setting(env, PRINT_LENGTH_VAR)
+}
+
+/// The limits set by [`PRINT_LENGTH_VAR`] and [`PRINT_DEPTH_VAR`] in `env`
+pub fn print_limits(env: &Environment) -> PrintLimits {
+    PrintLimits {
+        length: print_length(env),
+        depth: positive_setting(env, PRINT_DEPTH_VAR),
+    }
+}
+
+fn positive_setting(env: &Environment, name: &str) -> Option<usize> {
+    match env.get(name) {
+        Some(Value::Number(n)) if n >= 1.0 => Some(n as usize),
+        _ => None,
+    }
+}
+
+/// Heads longer than this do not get their first argument on the same line
+const MAX_HANGING_HEAD: usize = 12;
+
+/// Format `value` so that lines stay within `width` columns where possible
+///
+/// A list or map that fits is printed exactly like `Display`. Otherwise a list
+/// whose head is a short symbol keeps its first argument on the head's line
+/// and lines the rest up under it, as in `(define (f x)` / `  body)`; other
+/// lists and maps put one element or entry per line, indented one column
+/// past the opening bracket. Atoms are never split, so a long string can
+/// still overflow.
+pub fn pretty_print(value: &Value, width: usize) -> String {
+    pretty_print_limited(value, width, PrintLimits::default())
+}
+
+/// Format `value` like [`pretty_print`], within `limits`
+///
+/// The rest of a longer list or map is summarised as `... (N more)` and lists
+/// and maps nested too deeply print as `(...)` and `{...}`; the REPL uses this
+/// so that a huge result does not flood the terminal.
+pub fn pretty_print_limited(value: &Value, width: usize, limits: PrintLimits) -> String {
+    let mut out = String::new();
+    write_pretty(value, 0, width, limits, &mut out);
+    out
+}
+
+/// Whether printing `value` within `limits` would leave out any element or level
+pub fn is_truncated(value: &Value, limits: PrintLimits) -> bool {
+    let length = limits.length.unwrap_or(usize::MAX);
+    match value {
+        Value::List(_) | Value::Map(_) if limits.levels() == 0 => true,
+        Value::List(items) => {
+            items.len() > length || items.iter().any(|item| is_truncated(item, limits.nested()))
+        }
+        Value::Map(map) => {
+            map.len() > length || map.values().any(|v| is_truncated(v, limits.nested()))
+        }
+        _ => false,
+    }
+}
+
+fn write_pretty(value: &Value, column: usize, width: usize, limits: PrintLimits, out: &mut String) {
+    let mut flat = String::new();
+    let _ = write_value_limited(&mut flat, value, PrintStyle::Write, limits);
+    if column + flat.chars().count() <= width {
+        out.push_str(&flat);
+        return;
+    }
+
+    let (length, nested) = (limits.length, limits.nested());
+    match value {
+        _ if limits.levels() == 0 => out.push_str(&flat),
+        Value::List(items) if !items.is_empty() && length != Some(0) => {
+            let shown = &items[..length.map_or(items.len(), |n| n.min(items.len()))];
+            out.push('(');
+            let (rest, indent) = match &shown[0] {
+                Value::Symbol(head) if shown.len() > 1 && head.len() <= MAX_HANGING_HEAD => {
+                    out.push_str(head);
+                    out.push(' ');
+                    let indent = column + head.len() + 2;
+                    write_pretty(&shown[1], indent, width, nested, out);
+                    (&shown[2..], indent)
+                }
+                first => {
+                    write_pretty(first, column + 1, width, nested, out);
+                    (&shown[1..], column + 1)
+                }
+            };
+            for item in rest {
+                newline(indent, out);
+                write_pretty(item, indent, width, nested, out);
+            }
+            if let Some(marker) = more_marker(shown.len(), items.len()) {
+                newline(indent, out);
+                out.push_str(&marker);
+            }
+            out.push(')');
+        }
+        Value::Map(map) if !map.is_empty() && length != Some(0) => {
+            let mut entries: Vec<_> = map.iter().collect();
+            let total = entries.len();
+            entries.truncate(length.unwrap_or(total));
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    newline(column + 1, out);
+                }
+                out.push(':');
+                out.push_str(key);
+                out.push(' ');
+                write_pretty(value, column + key.chars().count() + 3, width, nested, out);
+            }
+            if let Some(marker) = more_marker(entries.len(), total) {
+                newline(column + 1, out);
+                out.push_str(&marker);
+            }
+            out.push('}');
+        }
+        _ => out.push_str(&flat),
+    }
+}
+
+fn newline(indent: usize, out: &mut String) {
+    out.push('\n');
+    out.extend(std::iter::repeat_n(' ', indent));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn pp(source: &str, width: usize) -> String {
+        pretty_print(&parse(source).unwrap(), width)
+    }
+
+    #[test]
+    fn test_short_values_stay_flat() {
+        assert_eq!(pp("(1 (2 3) \"x\")", 80), "(1 (2 3) \"x\")");
+        assert_eq!(pp("{:b 2 :a 1}", 80), "{:a 1 :b 2}");
+        assert_eq!(pp("42", 1), "42");
+    }
+
+    #[test]
+    fn test_breaks_long_lists() {
+        assert_eq!(
+            pp("(define (f x) (if (> x 0) (* x 2) (- x)))", 24),
+            "(define (f x)\n        (if (> x 0)\n            (* x 2)\n            (- x)))"
+        );
+        assert_eq!(
+            pp("((1 2 3) (4 5 6) (7 8 9))", 12),
+            "((1 2 3)\n (4 5 6)\n (7 8 9))"
+        );
+    }
+
+    #[test]
+    fn test_breaks_long_maps() {
+        assert_eq!(
+            pp("{:name \"Ada\" :langs (\"lisp\" \"rust\")}", 20),
+            "{:langs (\"lisp\"\n         \"rust\")\n :name \"Ada\"}"
+        );
+    }
+
+    #[test]
+    fn test_limits_long_lists_and_maps() {
+        let long = Value::List((1..=1000).map(|n| Value::Number(n as f64)).collect());
+        assert_eq!(
+            pretty_print_limited(
+                &long,
+                80,
+                PrintLimits {
+                    length: Some(3),
+                    depth: None
+                }
+            ),
+            "(1 2 3 ... (997 more))"
+        );
+        assert_eq!(
+            pretty_print_limited(
+                &long,
+                10,
+                PrintLimits {
+                    length: Some(2),
+                    depth: None
+                }
+            ),
+            "(1\n 2\n ... (998 more))"
+        );
+        assert_eq!(
+            pretty_print_limited(
+                &parse("{:a 1 :b 2 :c 3}").unwrap(),
+                80,
+                PrintLimits {
+                    length: Some(1),
+                    depth: None
+                }
+            ),
+            "{:a 1 ... (2 more)}"
+        );
+        assert!(is_truncated(
+            &parse("(1 (2 3 4))").unwrap(),
+            PrintLimits {
+                length: Some(2),
+                depth: None
+            }
+        ));
+        assert!(!is_truncated(
+            &parse("(1 (2 3))").unwrap(),
+            PrintLimits {
+                length: Some(2),
+                depth: None
+            }
+        ));
+    }
+}
@@ -0,0 +1,277 @@
+// ABOUTME: Call counts and wall time per function for the profile special form and --profile-calls
+// The evaluator opens a Frame for each function call while a profiler is installed on the thread
+
+use crate::builtins::builtin_name;
+use crate::env::Environment;
+use crate::error::EvalError;
+use crate::platform::Stopwatch;
+use crate::value::Value;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+thread_local! {
+    /// Profiler collecting calls made on this thread, when profiling is on
+    static ACTIVE_PROFILER: RefCell<Option<Profiler>> = const { RefCell::new(None) };
+}
+
+/// Calls and time for one function
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CallStats {
+    pub calls: u64,
+    /// Wall time from call to return, including callees; recursive calls are not counted twice
+    pub total: Duration,
+}
+
+/// Call counts and wall time per named function
+///
+/// Lambdas are named after the global binding they were defined or called
+/// through; anonymous ones are reported as `<lambda>`. A tail call ends the
+/// caller's time, since the trampoline never returns to it. Calls made by
+/// threads started with `spawn` are not counted.
+#[derive(Debug, Clone, Default)]
+pub struct Profiler {
+    stats: HashMap<String, CallStats>,
+    /// Calls of each function currently running, so recursion is timed once
+    running: HashMap<String, usize>,
+    /// Function names by the address of the lambda body
+    names: HashMap<usize, String>,
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A profiler that already knows the names of the lambdas bound in `env`
+    pub fn for_env(env: &Environment) -> Self {
+        let mut profiler = Self::new();
+        for (name, value) in env.bindings() {
+            if let Value::Lambda { body, .. } = value {
+                profiler.names.insert(body_key(&body), name.to_string());
+            }
+        }
+        profiler
+    }
+
+    /// Statistics for one function, if it was called
+    #[allow(dead_code)]
+    pub fn stats(&self, name: &str) -> Option<CallStats> {
+        self.stats.get(name).copied()
+    }
+
+    /// Every called function, slowest first
+    pub fn sorted(&self) -> Vec<(String, CallStats)> {
+        let mut rows: Vec<_> = self
+            .stats
+            .iter()
+            .map(|(name, stats)| (name.clone(), *stats))
+            .collect();
+        rows.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(&b.0)));
+        rows
+    }
+
+    /// Add another profile's counts and times
+    pub fn merge(&mut self, other: &Profiler) {
+        for (name, stats) in &other.stats {
+            let entry = self.stats.entry(name.clone()).or_default();
+            entry.calls += stats.calls;
+            entry.total += stats.total;
+        }
+    }
+
+    /// Table of every called function, slowest first
+    pub fn report(&self) -> String {
+        let rows = self.sorted();
+        let width = rows
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0)
+            .max("function".len());
+        let mut out = format!(
+            "{:<width$}  {:>10}  {:>12}  {:>12}\n",
+            "function",
+            "calls",
+            "total ms",
+            "avg us",
+            width = width
+        );
+        for (name, stats) in rows {
+            let total_ms = stats.total.as_secs_f64() * 1000.0;
+            let avg_us = stats.total.as_secs_f64() * 1_000_000.0 / stats.calls.max(1) as f64;
+            let _ = writeln!(
+                out,
+                "{:<width$}  {:>10}  {:>12.3}  {:>12.1}",
+                name,
+                stats.calls,
+                total_ms,
+                avg_us,
+                width = width
+            );
+        }
+        out
+    }
+
+    fn enter(&mut self, name: &str) {
+        self.stats.entry(name.to_string()).or_default().calls += 1;
+        *self.running.entry(name.to_string()).or_default() += 1;
+    }
+
+    /// Leave a call; its time counts as zero under a frozen clock (`--deterministic`)
+    fn exit(&mut self, name: &str, elapsed: Duration) {
+        let elapsed = if crate::builtins::clock_frozen() {
+            Duration::ZERO
+        } else {
+            elapsed
+        };
+        if let Some(depth) = self.running.get_mut(name) {
+            *depth -= 1;
+            if *depth == 0 {
+                self.running.remove(name);
+                if let Some(stats) = self.stats.get_mut(name) {
+                    stats.total += elapsed;
+                }
+            }
+        }
+    }
+}
+
+fn body_key(body: &Arc<Value>) -> usize {
+    Arc::as_ptr(body) as usize
+}
+
+/// Replace the profiler on this thread, returning the previous one
+pub(crate) fn swap_profiler(profiler: Option<Profiler>) -> Option<Profiler> {
+    ACTIVE_PROFILER.with(|active| active.replace(profiler))
+}
+
+/// Add a finished profile's counts to the profiler on this thread, if there is one
+pub(crate) fn merge_into_active(profile: &Profiler) {
+    ACTIVE_PROFILER.with(|active| {
+        if let Some(profiler) = active.borrow_mut().as_mut() {
+            profiler.merge(profile);
+        }
+    });
+}
+
+/// Name a lambda after the global binding it is defined as, if profiling
+pub(crate) fn register_function(name: &str, body: &Arc<Value>) {
+    ACTIVE_PROFILER.with(|active| {
+        if let Some(profiler) = active.borrow_mut().as_mut() {
+            profiler.names.insert(body_key(body), name.to_string());
+        }
+    });
+}
+
+/// One function call being timed; dropping it records the call's time
+///
+/// The evaluator keeps one `Frame` per trampoline. A tail call re-enters the
+/// same frame, which ends the caller's time and starts the callee's.
+#[derive(Default)]
+pub(crate) struct Frame {
+    current: Option<(String, Stopwatch)>,
+}
+
+impl Frame {
+    /// Start timing a call of the lambda with this body, made through `call_site`
+    pub(crate) fn enter_lambda(&mut self, body: &Arc<Value>, call_site: &Value) {
+        self.enter_with(|profiler| {
+            let key = body_key(body);
+            match (profiler.names.get(&key), call_site) {
+                (Some(name), _) => name.clone(),
+                (None, Value::Symbol(name)) => {
+                    profiler.names.insert(key, name.to_string());
+                    name.to_string()
+                }
+                (None, _) => "<lambda>".to_string(),
+            }
+        });
+    }
+
+    /// Start timing a call of a builtin
+    pub(crate) fn enter_builtin(&mut self, function: fn(&[Value]) -> Result<Value, EvalError>) {
+        self.enter_with(|_| builtin_name(function).unwrap_or("<builtin>").to_string());
+    }
+
+    fn enter_with(&mut self, name: impl FnOnce(&mut Profiler) -> String) {
+        ACTIVE_PROFILER.with(|active| {
+            if let Some(profiler) = active.borrow_mut().as_mut() {
+                if let Some((previous, started)) = self.current.take() {
+                    profiler.exit(&previous, started.elapsed());
+                }
+                let name = name(profiler);
+                profiler.enter(&name);
+                self.current = Some((name, Stopwatch::start()));
+            }
+        });
+    }
+}
+
+impl Drop for Frame {
+    fn drop(&mut self) {
+        if let Some((name, started)) = self.current.take() {
+            ACTIVE_PROFILER.with(|active| {
+                if let Some(profiler) = active.borrow_mut().as_mut() {
+                    profiler.exit(&name, started.elapsed());
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recursive_calls_are_timed_once() {
+        let mut profiler = Profiler::new();
+        profiler.enter("f");
+        profiler.enter("f");
+        profiler.exit("f", Duration::from_millis(5));
+        profiler.exit("f", Duration::from_millis(8));
+
+        let stats = profiler.stats("f").unwrap();
+        assert_eq!(stats.calls, 2);
+        assert_eq!(stats.total, Duration::from_millis(8));
+    }
+
+    #[test]
+    fn test_report_sorted_by_time() {
+        let mut profiler = Profiler::new();
+        for (name, ms) in [("fast", 1), ("slow", 9)] {
+            profiler.enter(name);
+            profiler.exit(name, Duration::from_millis(ms));
+        }
+        let names: Vec<_> = profiler.sorted().into_iter().map(|(n, _)| n).collect();
+        assert_eq!(names, vec!["slow", "fast"]);
+
+        let report = profiler.report();
+        assert!(report.starts_with("function"));
+        assert!(report.lines().nth(1).unwrap().starts_with("slow"));
+
+        let mut total = Profiler::new();
+        total.merge(&profiler);
+        total.merge(&profiler);
+        assert_eq!(total.stats("fast").unwrap().calls, 2);
+    }
+
+    #[test]
+    fn test_frames_only_record_while_profiling() {
+        let builtin = crate::builtins::lists::builtin_car;
+        Frame::default().enter_builtin(builtin);
+
+        let previous = swap_profiler(Some(Profiler::new()));
+        {
+            let mut frame = Frame::default();
+            frame.enter_builtin(builtin);
+            frame.enter_builtin(builtin);
+        }
+        let profiler = swap_profiler(previous).unwrap();
+        assert_eq!(profiler.stats("car").unwrap().calls, 2);
+        assert!(profiler.running.is_empty());
+    }
+}
@@ -2,10 +2,20 @@
 // Provides safe filesystem and network access with capability-based security using cap-std
 
 use crate::config::{FsConfig, NetConfig};
-use cap_std::fs::Dir;
-
-#[cfg(test)]
-use std::path::PathBuf;
+#[cfg(feature = "native-fs")]
+use crate::fs_backend::DirFs;
+use crate::fs_backend::FsBackend;
+#[cfg(feature = "network")]
+use crate::stdlib::llm::LlmClient;
+use crate::stdlib::rate::RateLimiter;
+#[cfg(feature = "websocket")]
+use crate::value::WebSocketHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// Error type for sandbox operations
 #[derive(Debug, Clone)]
@@ -15,7 +25,45 @@ pub enum SandboxError {
     FileTooLarge(String),
     IoError(String),
     NetworkDisabled,
-    AddressNotAllowed(String),
+    /// WebSockets need `--allow-websocket` on top of network access
+    WebSocketDisabled,
+    /// A URL the network allowlist does not cover, and why
+    AddressNotAllowed {
+        url: String,
+        reason: String,
+    },
+    /// Replaying a cassette that has no response for this request
+    NotRecorded(String),
+    /// A usage limit such as the HTTP request quota has been reached
+    QuotaExceeded(String),
+    /// The host's approval callback refused the operation
+    NotApproved(String),
+    /// The server answered with an error status; `retry_after` is its
+    /// Retry-After header in seconds, if it sent one
+    HttpStatus {
+        status: u16,
+        retry_after: Option<f64>,
+        message: String,
+    },
+}
+
+impl SandboxError {
+    /// Stable keyword name for this error, as returned by `error-kind`
+    pub fn kind(&self) -> &'static str {
+        match self {
+            SandboxError::PathNotAllowed(_) => "path-not-allowed",
+            SandboxError::FileNotFound(_) => "not-found",
+            SandboxError::FileTooLarge(_) => "file-too-large",
+            SandboxError::IoError(_) => "io-error",
+            SandboxError::NetworkDisabled => "network-disabled",
+            SandboxError::WebSocketDisabled => "websocket-disabled",
+            SandboxError::AddressNotAllowed { .. } => "address-not-allowed",
+            SandboxError::NotRecorded(_) => "not-recorded",
+            SandboxError::QuotaExceeded(_) => "quota-exceeded",
+            SandboxError::NotApproved(_) => "not-approved",
+            SandboxError::HttpStatus { .. } => "http-status",
+        }
+    }
 }
 
 impl std::fmt::Display for SandboxError {
@@ -36,8 +84,28 @@ impl std::fmt::Display for SandboxError {
             SandboxError::NetworkDisabled => {
                 write!(f, "Network I/O is disabled. Use --allow-network to enable.")
             }
-            SandboxError::AddressNotAllowed(addr) => {
-                write!(f, "Network address not allowed: {}", addr)
+            SandboxError::WebSocketDisabled => {
+                write!(
+                    f,
+                    "WebSockets are disabled. Use --allow-websocket to enable."
+                )
+            }
+            SandboxError::AddressNotAllowed { url, reason } => {
+                write!(f, "Network address not allowed: {} ({})", url, reason)
+            }
+            SandboxError::NotApproved(operation) => {
+                write!(f, "Not approved: {}", operation)
+            }
+            SandboxError::HttpStatus { message, .. } => write!(f, "{}", message),
+            SandboxError::QuotaExceeded(msg) => {
+                write!(f, "Quota exceeded: {}", msg)
+            }
+            SandboxError::NotRecorded(request) => {
+                write!(
+                    f,
+                    "No recorded response for {} in the replay cassette",
+                    request
+                )
             }
         }
     }
@@ -45,83 +113,394 @@ impl std::fmt::Display for SandboxError {
 
 impl std::error::Error for SandboxError {}
 
+/// One entry of the network allowlist: `[scheme://]host[:port]`
+///
+/// The host is matched exactly (ignoring case), `*.example.com` matches any
+/// subdomain of example.com (but not example.com itself) and `*` matches any
+/// host. Without a scheme only https (and wss, for WebSockets) is allowed;
+/// without a port any port is.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AddressPattern {
+    scheme: Option<String>,
+    host: String,
+    port: Option<u16>,
+}
+
+impl AddressPattern {
+    /// Parse an allowlist entry such as `example.com`, `*.example.com:8443`
+    /// or `http://localhost:8080`
+    pub fn parse(pattern: &str) -> Result<Self, String> {
+        let invalid = |why: &str| format!("invalid network allowlist entry '{}': {}", pattern, why);
+        let (scheme, rest) = match pattern.split_once("://") {
+            Some((scheme, rest)) => (Some(scheme.to_ascii_lowercase()), rest),
+            None => (None, pattern),
+        };
+        if rest.contains(['/', '?', '#']) {
+            return Err(invalid("entries name a host, not a path"));
+        }
+        // The port follows the last ':', unless that is inside an IPv6 address
+        let (host, port) = match rest.rfind(':') {
+            Some(i) if !rest[i..].contains(']') => {
+                let port = rest[i + 1..]
+                    .parse::<u16>()
+                    .map_err(|_| invalid("the port must be a number from 0 to 65535"))?;
+                (&rest[..i], Some(port))
+            }
+            _ => (rest, None),
+        };
+        let well_formed = match host.strip_prefix('*') {
+            Some(suffix) => suffix.is_empty() || suffix.len() > 1 && suffix.starts_with('.'),
+            None => !host.is_empty(),
+        };
+        if !well_formed || host.chars().skip(1).any(|c| c == '*') {
+            return Err(invalid(
+                "expected a host, '*', or '*.' followed by a domain",
+            ));
+        }
+        Ok(AddressPattern {
+            scheme,
+            host: host.to_ascii_lowercase(),
+            port,
+        })
+    }
+
+    fn matches_host(&self, host: &str) -> bool {
+        match self.host.strip_prefix('*') {
+            Some("") => true,
+            Some(suffix) => host.ends_with(suffix),
+            None => self.host == host,
+        }
+    }
+
+    fn matches_scheme(&self, scheme: &str) -> bool {
+        match &self.scheme {
+            Some(own) => own == scheme,
+            None => scheme == "https" || scheme == "wss",
+        }
+    }
+
+    fn matches_port(&self, port: Option<u16>) -> bool {
+        self.port.is_none() || self.port == port
+    }
+}
+
+impl std::fmt::Display for AddressPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(scheme) = &self.scheme {
+            write!(f, "{}://", scheme)?;
+        }
+        write!(f, "{}", self.host)?;
+        if let Some(port) = self.port {
+            write!(f, ":{}", port)?;
+        }
+        Ok(())
+    }
+}
+
+/// Why `patterns` do not allow `url`, or `None` if one of them does
+///
+/// An empty list allows any host, but still only over https and wss.
+pub fn address_denial(patterns: &[String], url: &str) -> Option<String> {
+    let parsed = match url::Url::parse(url) {
+        Ok(parsed) => parsed,
+        Err(e) => return Some(format!("not a valid URL: {}", e)),
+    };
+    let host = parsed.host_str().unwrap_or("").to_ascii_lowercase();
+    let scheme = parsed.scheme();
+    let port = parsed.port_or_known_default();
+
+    let patterns: Vec<AddressPattern> = if patterns.is_empty() {
+        vec![AddressPattern {
+            scheme: None,
+            host: "*".to_string(),
+            port: None,
+        }]
+    } else {
+        patterns
+            .iter()
+            .filter_map(|p| AddressPattern::parse(p).ok())
+            .collect()
+    };
+    let for_host: Vec<&AddressPattern> =
+        patterns.iter().filter(|p| p.matches_host(&host)).collect();
+    if for_host.is_empty() {
+        return Some(format!("{} is not in the network allowlist", host));
+    }
+    let for_scheme: Vec<&AddressPattern> = for_host
+        .iter()
+        .copied()
+        .filter(|p| p.matches_scheme(scheme))
+        .collect();
+    if for_scheme.is_empty() {
+        return Some(format!(
+            "{} is not allowed for {}; only https and wss are allowed unless an entry names the scheme, like {}://{}",
+            scheme, host, scheme, host
+        ));
+    }
+    if for_scheme.iter().any(|p| p.matches_port(port)) {
+        return None;
+    }
+    let allowed: Vec<String> = for_scheme.iter().map(|p| p.to_string()).collect();
+    Some(format!(
+        "port {} is not allowed for {} (allowed: {})",
+        port.map_or("none".to_string(), |p| p.to_string()),
+        host,
+        allowed.join(", ")
+    ))
+}
+
 /// Sandbox for safe file and network access
 /// Uses capability-based security via cap-std
 pub struct Sandbox {
-    /// Filesystem sandbox roots
-    fs_roots: Vec<Dir>,
+    /// Where files are stored (cap-std directories unless given another backend)
+    fs: Arc<dyn FsBackend>,
     /// Filesystem configuration
     fs_config: FsConfig,
     /// Network configuration
     net_config: NetConfig,
+    /// Record of every operation attempted through this sandbox
+    audit: AuditLog,
+    /// Recorded HTTP responses to save or serve, when recording or replaying
+    cassette: Option<Arc<Cassette>>,
+    /// Recent GET responses, served again until they expire
+    cache: Option<Arc<HttpCache>>,
+    /// HTTP requests made so far, checked against `net_config.max_requests`
+    requests: AtomicU64,
+    /// Limiter every `rate:acquire` also waits for, from `net_config.max_rate`
+    rate_floor: Option<Arc<RateLimiter>>,
+    /// Model API that `llm:chat` calls, holding a key scripts cannot read
+    #[cfg(feature = "network")]
+    llm: Option<Arc<LlmClient>>,
+    /// Open WebSocket connections, closed when the sandbox is dropped
+    #[cfg(feature = "websocket")]
+    websockets: WebSockets,
+    /// Host hook that must approve writes and state-changing HTTP requests
+    approval: Option<ApprovalCallback>,
+    /// Time scripts see instead of the clock, for reproducible runs
+    frozen_time: Option<f64>,
 }
 
 impl Sandbox {
     /// Create a new sandbox from configuration
+    ///
+    /// Files live on disk under `fs_config.allowed_paths` (created if missing) and
+    /// `fs_config.read_only_paths`. Needs the `native-fs` feature; other builds
+    /// use [`Sandbox::with_backend`].
+    #[cfg(feature = "native-fs")]
     pub fn new(fs_config: FsConfig, net_config: NetConfig) -> Result<Self, SandboxError> {
-        let mut fs_roots = Vec::new();
+        let fs = DirFs::open(&fs_config.allowed_paths, &fs_config.read_only_paths)?;
+        Ok(Self::with_backend(Arc::new(fs), fs_config, net_config))
+    }
 
-        // Open all allowed paths as capability directories
-        for path in &fs_config.allowed_paths {
-            // Create directory if it doesn't exist (for output)
-            std::fs::create_dir_all(path).map_err(|e| {
-                SandboxError::IoError(format!("Cannot create {}: {}", path.display(), e))
-            })?;
+    /// Create a sandbox whose files are stored by `fs`, such as a [`crate::fs_backend::MemoryFs`]
+    ///
+    /// `fs_config`'s paths are not used; the size limit still applies.
+    pub fn with_backend(
+        fs: Arc<dyn FsBackend>,
+        fs_config: FsConfig,
+        net_config: NetConfig,
+    ) -> Self {
+        let rate_floor = net_config
+            .max_rate
+            .filter(|per_sec| *per_sec > 0.0)
+            .map(|per_sec| Arc::new(RateLimiter::new(per_sec, 1.0)));
+        Self {
+            fs,
+            fs_config,
+            net_config,
+            audit: AuditLog::default(),
+            cassette: None,
+            cache: None,
+            requests: AtomicU64::new(0),
+            rate_floor,
+            #[cfg(feature = "network")]
+            llm: None,
+            #[cfg(feature = "websocket")]
+            websockets: WebSockets::default(),
+            approval: None,
+            frozen_time: None,
+        }
+    }
 
-            // Open as cap-std Dir (gives us capability-based security)
-            let dir = Dir::open_ambient_dir(path, cap_std::ambient_authority()).map_err(|e| {
-                SandboxError::IoError(format!("Cannot open {}: {}", path.display(), e))
-            })?;
+    /// Ask `callback` before every file write and every HTTP request other than GET or HEAD
+    ///
+    /// The operation runs only if the callback returns true; otherwise it fails
+    /// with [`SandboxError::NotApproved`]. The callback runs after the sandbox's
+    /// own checks, on whichever thread is evaluating.
+    pub fn set_approval_callback(
+        &mut self,
+        callback: impl Fn(&ApprovalRequest) -> bool + Send + Sync + 'static,
+    ) {
+        self.approval = Some(Arc::new(callback));
+    }
 
-            fs_roots.push(dir);
+    fn approve(&self, request: ApprovalRequest) -> Result<(), SandboxError> {
+        match &self.approval {
+            Some(callback) if !callback(&request) => {
+                Err(SandboxError::NotApproved(request.to_string()))
+            }
+            _ => Ok(()),
         }
+    }
 
-        Ok(Self {
-            fs_roots,
-            fs_config,
-            net_config,
-        })
+    /// Record HTTP responses into, or replay them from, `cassette`
+    ///
+    /// Several sandboxes may share one cassette.
+    pub fn set_cassette(&mut self, cassette: Arc<Cassette>) {
+        self.cassette = Some(cassette);
+    }
+
+    /// The sandbox-wide limiter set by `NetConfig::max_rate`, if any
+    pub fn rate_floor(&self) -> Option<&RateLimiter> {
+        self.rate_floor.as_deref()
+    }
+
+    /// Let `llm:chat` call the model API behind `client`
+    ///
+    /// Several sandboxes may share one client.
+    #[cfg(feature = "network")]
+    pub fn set_llm(&mut self, client: Arc<LlmClient>) {
+        self.llm = Some(client);
+    }
+
+    /// The model API set by [`Sandbox::set_llm`], if any
+    #[cfg(feature = "network")]
+    pub fn llm(&self) -> Option<&LlmClient> {
+        self.llm.as_deref()
+    }
+
+    /// Answer repeated GET requests from `cache` until its entries expire
+    ///
+    /// Several sandboxes may share one cache.
+    pub fn set_cache(&mut self, cache: Arc<HttpCache>) {
+        self.cache = Some(cache);
+    }
+
+    /// Make scripts see `unix_time` (seconds since the epoch) as the current time from now on
+    ///
+    /// For reproducible runs: log timestamps and anything else that asks
+    /// [`Sandbox::now`] stay fixed, and `time`, `bench` and profiles report
+    /// zero. Audit entries and rate limiters still use the real clock.
+    pub fn freeze_clock(&mut self, unix_time: f64) {
+        self.frozen_time = Some(unix_time);
+    }
+
+    /// Seconds since the Unix epoch as scripts see them: the frozen time, if set, else the clock
+    pub fn now(&self) -> f64 {
+        self.frozen_time.unwrap_or_else(crate::platform::unix_time)
+    }
+
+    /// Whether [`Sandbox::freeze_clock`] was called
+    pub fn clock_frozen(&self) -> bool {
+        self.frozen_time.is_some()
     }
 
     // ========================================================================
-    // Filesystem Operations
+    // Audit Log
     // ========================================================================
 
-    /// Find which root directory should be used for a path
-    /// For reading: tries all roots
-    /// For writing: uses first root
-    fn find_root_for_path(
+    /// Also append every audit entry to `path` as one JSON object per line
+    pub fn set_audit_file(&mut self, path: &Path) -> Result<(), SandboxError> {
+        let file = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .map_err(|e| {
+                SandboxError::IoError(format!("Cannot open audit log {}: {}", path.display(), e))
+            })?;
+        *self.audit.sink.get_mut().unwrap_or_else(|e| e.into_inner()) = Some(file);
+        Ok(())
+    }
+
+    /// The most recent audit entries (up to [`MAX_AUDIT_ENTRIES`]), oldest first
+    pub fn audit_entries(&self) -> Vec<AuditEntry> {
+        self.audit
+            .entries
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .cloned()
+            .collect()
+    }
+
+    /// Bytes read from files and received over the network since the sandbox was created
+    ///
+    /// Unlike [`Sandbox::audit_entries`], totals are kept for every operation,
+    /// not only the most recent ones.
+    pub fn bytes_read(&self) -> u64 {
+        self.audit.bytes_read.load(Ordering::Relaxed)
+    }
+
+    /// Bytes written to files and sent over WebSockets since the sandbox was created
+    pub fn bytes_written(&self) -> u64 {
+        self.audit.bytes_written.load(Ordering::Relaxed)
+    }
+
+    /// Run an operation and record it, with byte count and HTTP status taken from its result
+    fn audited<T>(
         &self,
-        user_path: &str,
-        write_mode: bool,
-    ) -> Result<(&Dir, usize), SandboxError> {
-        // cap-std::Dir will automatically reject .. and absolute paths
-        // This is secure by construction
-
-        if write_mode {
-            // For writes, always use the first root
-            if !self.fs_roots.is_empty() {
-                return Ok((&self.fs_roots[0], 0));
-            }
-        } else {
-            // For reads, try each root to find the file
-            for (idx, root) in self.fs_roots.iter().enumerate() {
-                if root.metadata(user_path).is_ok() {
-                    return Ok((root, idx));
-                }
+        operation: &str,
+        target: &str,
+        method: Option<&str>,
+        run: impl FnOnce() -> Result<T, SandboxError>,
+        details: impl FnOnce(&T) -> (Option<u64>, Option<u16>),
+    ) -> Result<T, SandboxError> {
+        let timestamp = crate::platform::unix_time();
+        let started = crate::platform::Stopwatch::start();
+        let result = run();
+
+        let (outcome, bytes, status, error) = match &result {
+            Ok(value) => {
+                let (bytes, status) = details(value);
+                (AuditOutcome::Ok, bytes, status, None)
             }
-
-            // If not found in any root, return first root error
-            if !self.fs_roots.is_empty() {
-                return Ok((&self.fs_roots[0], 0));
+            Err(e) => {
+                let outcome = match e {
+                    SandboxError::PathNotAllowed(_)
+                    | SandboxError::FileTooLarge(_)
+                    | SandboxError::NetworkDisabled
+                    | SandboxError::WebSocketDisabled
+                    | SandboxError::AddressNotAllowed { .. }
+                    | SandboxError::QuotaExceeded(_)
+                    | SandboxError::NotApproved(_) => AuditOutcome::Denied,
+                    _ => AuditOutcome::Error,
+                };
+                (outcome, None, None, Some(e.to_string()))
             }
-        }
+        };
 
-        Err(SandboxError::PathNotAllowed(user_path.to_string()))
+        let entry = AuditEntry {
+            timestamp,
+            operation: operation.to_string(),
+            target: target.to_string(),
+            method: method.map(str::to_string),
+            outcome,
+            bytes,
+            status,
+            duration_ms: started.elapsed_ms(),
+            error,
+        };
+        crate::events::io(&entry);
+        self.audit.record(entry);
+        result
     }
 
-    /// Read file contents (safe filesystem access via cap-std)
+    // ========================================================================
+    // Filesystem Operations
+    // ========================================================================
+
+    /// Read file contents through the filesystem backend
     pub fn read_file(&self, path: &str) -> Result<String, SandboxError> {
+        self.audited(
+            "read-file",
+            path,
+            None,
+            || self.read_file_unaudited(path),
+            |contents| (Some(contents.len() as u64), None),
+        )
+    }
+
+    fn read_file_unaudited(&self, path: &str) -> Result<String, SandboxError> {
         // Validate path format (no absolute paths, no .. traversals)
         if path.starts_with('/') || path.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
@@ -131,20 +510,21 @@ impl Sandbox {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
         }
 
-        let (root, _) = self.find_root_for_path(path, false)?;
-
-        // cap-std::Dir::read_to_string provides safe access
-        root.read_to_string(path).map_err(|e| {
-            if e.kind() == std::io::ErrorKind::NotFound {
-                SandboxError::FileNotFound(path.to_string())
-            } else {
-                SandboxError::IoError(format!("Cannot read {}: {}", path, e))
-            }
-        })
+        self.fs.read_file(path)
     }
 
-    /// Write file contents (safe filesystem access via cap-std)
+    /// Write file contents through the filesystem backend
     pub fn write_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        self.audited(
+            "write-file",
+            path,
+            None,
+            || self.write_file_unaudited(path, contents),
+            |_| (Some(contents.len() as u64), None),
+        )
+    }
+
+    fn write_file_unaudited(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
         // Validate path format
         if path.starts_with('/') || path.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
@@ -163,15 +543,60 @@ impl Sandbox {
             )));
         }
 
-        let (root, _) = self.find_root_for_path(path, true)?;
+        self.approve(ApprovalRequest::WriteFile {
+            path: path.to_string(),
+            bytes: contents.len(),
+        })?;
+        self.fs.write_file(path, contents)
+    }
+
+    /// Add text to the end of a file, creating it if needed
+    ///
+    /// For files the host chose, like the `--log-file` that `log:info` and its
+    /// siblings write to: no approval is asked, but the path is checked and the
+    /// file may not grow past the size limit.
+    pub fn append_file(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        self.audited(
+            "append-file",
+            path,
+            None,
+            || self.append_file_unaudited(path, contents),
+            |_| (Some(contents.len() as u64), None),
+        )
+    }
+
+    fn append_file_unaudited(&self, path: &str, contents: &str) -> Result<(), SandboxError> {
+        if path.starts_with('/') || path.starts_with("\\") || path.contains("..") {
+            return Err(SandboxError::PathNotAllowed(path.to_string()));
+        }
+
+        let existing = match self.fs.file_stat(path) {
+            Ok(stat) => stat.size as usize,
+            Err(SandboxError::FileNotFound(_)) => 0,
+            Err(e) => return Err(e),
+        };
+        if existing + contents.len() > self.fs_config.max_file_size {
+            return Err(SandboxError::FileTooLarge(format!(
+                "{} would grow past the limit of {} bytes",
+                path, self.fs_config.max_file_size
+            )));
+        }
 
-        // cap-std::Dir::write provides safe access
-        root.write(path, contents)
-            .map_err(|e| SandboxError::IoError(format!("Cannot write {}: {}", path, e)))
+        self.fs.append_file(path, contents)
     }
 
     /// Check if file exists
     pub fn file_exists(&self, path: &str) -> Result<bool, SandboxError> {
+        self.audited(
+            "file-exists",
+            path,
+            None,
+            || self.file_exists_unaudited(path),
+            |_| (None, None),
+        )
+    }
+
+    fn file_exists_unaudited(&self, path: &str) -> Result<bool, SandboxError> {
         // Validate path format
         if path.starts_with('/') || path.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
@@ -181,20 +606,21 @@ impl Sandbox {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
         }
 
-        let (root, _) = self.find_root_for_path(path, false)?;
-
-        match root.metadata(path) {
-            Ok(metadata) => Ok(metadata.is_file()),
-            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
-            Err(e) => Err(SandboxError::IoError(format!(
-                "Cannot check {}: {}",
-                path, e
-            ))),
-        }
+        self.fs.file_exists(path)
     }
 
     /// Get file size
     pub fn file_size(&self, path: &str) -> Result<u64, SandboxError> {
+        self.audited(
+            "file-size",
+            path,
+            None,
+            || self.file_size_unaudited(path),
+            |_| (None, None),
+        )
+    }
+
+    fn file_size_unaudited(&self, path: &str) -> Result<u64, SandboxError> {
         // Validate path format
         if path.starts_with('/') || path.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
@@ -204,21 +630,21 @@ impl Sandbox {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
         }
 
-        let (root, _) = self.find_root_for_path(path, false)?;
-
-        root.metadata(path)
-            .map(|metadata| metadata.len())
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    SandboxError::FileNotFound(path.to_string())
-                } else {
-                    SandboxError::IoError(format!("Cannot stat {}: {}", path, e))
-                }
-            })
+        self.fs.file_stat(path).map(|stat| stat.size)
     }
 
     /// Get file metadata (size, type, timestamps, readonly)
     pub fn file_stat(&self, path: &str) -> Result<FileStat, SandboxError> {
+        self.audited(
+            "file-stat",
+            path,
+            None,
+            || self.file_stat_unaudited(path),
+            |_| (None, None),
+        )
+    }
+
+    fn file_stat_unaudited(&self, path: &str) -> Result<FileStat, SandboxError> {
         // Validate path format
         if path.starts_with('/') || path.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
@@ -228,46 +654,21 @@ impl Sandbox {
             return Err(SandboxError::PathNotAllowed(path.to_string()));
         }
 
-        let (root, _) = self.find_root_for_path(path, false)?;
-
-        root.metadata(path)
-            .map(|metadata| {
-                let file_type = if metadata.is_dir() {
-                    "directory".to_string()
-                } else if metadata.is_symlink() {
-                    "symlink".to_string()
-                } else {
-                    "file".to_string()
-                };
-
-                // Timestamps: use approximate values since cap_std times don't directly convert
-                // to Unix timestamps. We'll store them as relative times from current moment.
-                let modified = 0.0; // Would need more complex conversion
-                let accessed = 0.0;
-                let created = 0.0;
-
-                let readonly = metadata.permissions().readonly();
-
-                FileStat {
-                    size: metadata.len(),
-                    file_type,
-                    modified,
-                    accessed,
-                    created,
-                    readonly,
-                }
-            })
-            .map_err(|e| {
-                if e.kind() == std::io::ErrorKind::NotFound {
-                    SandboxError::FileNotFound(path.to_string())
-                } else {
-                    SandboxError::IoError(format!("Cannot stat {}: {}", path, e))
-                }
-            })
+        self.fs.file_stat(path)
     }
 
     /// List files in a directory
     pub fn list_files(&self, dir: &str) -> Result<Vec<String>, SandboxError> {
+        self.audited(
+            "list-files",
+            dir,
+            None,
+            || self.list_files_unaudited(dir),
+            |_| (None, None),
+        )
+    }
+
+    fn list_files_unaudited(&self, dir: &str) -> Result<Vec<String>, SandboxError> {
         // Validate path format
         if dir.starts_with('/') || dir.starts_with("\\") {
             return Err(SandboxError::PathNotAllowed(dir.to_string()));
@@ -277,28 +678,10 @@ impl Sandbox {
             return Err(SandboxError::PathNotAllowed(dir.to_string()));
         }
 
-        let (root, _) = self.find_root_for_path(dir, false)?;
-
-        root.read_dir(dir)
-            .map_err(|e| SandboxError::IoError(format!("Cannot list {}: {}", dir, e)))
-            .and_then(|entries| {
-                entries
-                    .map(|entry| {
-                        entry
-                            .map_err(|e| SandboxError::IoError(e.to_string()))
-                            .and_then(|e| {
-                                e.file_name()
-                                    .to_str()
-                                    .map(|s| s.to_string())
-                                    .ok_or_else(|| {
-                                        SandboxError::IoError(
-                                            "Invalid UTF-8 in filename".to_string(),
-                                        )
-                                    })
-                            })
-                    })
-                    .collect()
-            })
+        // Sorted, since directory order on disk varies between systems and runs
+        let mut names = self.fs.list_files(dir)?;
+        names.sort();
+        Ok(names)
     }
 
     // ========================================================================
@@ -312,101 +695,799 @@ impl Sandbox {
         self.net_config.enabled
     }
 
-    /// Check if an address is allowed
-    fn is_address_allowed(&self, address: &str) -> bool {
-        if !self.net_config.enabled {
-            return false;
+    /// Perform flexible HTTP request with method, optional headers, body, and timeout.
+    /// Returns HttpResponse with status, headers, and body.
+    pub fn http_request(
+        &self,
+        url: &str,
+        method: &str,
+        headers: Option<Vec<(String, String)>>,
+        body: Option<&str>,
+        timeout_ms: Option<u64>,
+    ) -> Result<HttpResponse, SandboxError> {
+        self.audited(
+            "http-request",
+            url,
+            Some(&method.to_uppercase()),
+            || self.http_request_unaudited(url, method, headers, body, timeout_ms),
+            |response| (Some(response.body.len() as u64), Some(response.status)),
+        )
+    }
+
+    /// Make a request, repeating it while the server answers 429 or 5xx
+    ///
+    /// Each attempt is an ordinary [`Sandbox::http_request`], so it is checked,
+    /// audited and counted toward the quota. Retries wait `backoff_ms`, doubling
+    /// each time, or as long as a Retry-After header asks; no wait runs past
+    /// `max_time_ms`. `interrupted` is polled while waiting and stops the retries.
+    /// Returns the last attempt's result and how many attempts were made.
+    #[allow(clippy::too_many_arguments)]
+    pub fn http_request_with_retry(
+        &self,
+        url: &str,
+        method: &str,
+        headers: Option<Vec<(String, String)>>,
+        body: Option<&str>,
+        timeout_ms: Option<u64>,
+        retry: &RetryPolicy,
+        interrupted: &dyn Fn() -> bool,
+    ) -> (Result<HttpResponse, SandboxError>, u32) {
+        let started = crate::platform::Stopwatch::start();
+        let mut backoff_ms = retry.backoff_ms;
+        let mut attempts = 0;
+        loop {
+            let result = self.http_request(url, method, headers.clone(), body, timeout_ms);
+            attempts += 1;
+            let retry_after = match &result {
+                Ok(response) if is_retryable(response.status) => None,
+                Err(SandboxError::HttpStatus {
+                    status,
+                    retry_after,
+                    ..
+                }) if is_retryable(*status) => *retry_after,
+                _ => return (result, attempts),
+            };
+            let wait_ms = retry_after.map_or(backoff_ms, |seconds| seconds * 1000.0);
+            if attempts >= retry.attempts || started.elapsed_ms() + wait_ms > retry.max_time_ms {
+                return (result, attempts);
+            }
+
+            // Wait in short slices so an interrupt is noticed promptly
+            let resume_at = started.elapsed_ms() + wait_ms;
+            while started.elapsed_ms() < resume_at {
+                if interrupted() {
+                    return (result, attempts);
+                }
+                crate::platform::sleep_ms((resume_at - started.elapsed_ms()).min(50.0));
+            }
+            backoff_ms *= 2.0;
         }
+    }
 
-        // Empty allowlist = all allowed (if enabled)
-        if self.net_config.allowed_addresses.is_empty() {
-            return true;
+    fn http_request_unaudited(
+        &self,
+        url: &str,
+        method: &str,
+        headers: Option<Vec<(String, String)>>,
+        body: Option<&str>,
+        timeout_ms: Option<u64>,
+    ) -> Result<HttpResponse, SandboxError> {
+        let method = method.to_uppercase();
+        self.admit_request(url, &method)?;
+
+        // Cached responses cost nothing, so they skip the quota
+        let cache = self.cache.as_ref().filter(|_| method == "GET");
+        let cache_key = cache.map(|_| HttpCache::key(&method, url, headers.as_deref()));
+        if let Some(response) = cache.zip(cache_key.as_deref()).and_then(|(c, k)| c.get(k)) {
+            return Ok(response);
         }
 
-        // Check against allowlist
-        self.net_config
-            .allowed_addresses
-            .iter()
-            .any(|allowed| address.contains(allowed))
+        self.count_request()?;
+
+        let response = match &self.cassette {
+            Some(cassette) if cassette.mode == CassetteMode::Replay => {
+                cassette.replay_response(&method, url, body)?
+            }
+            cassette => {
+                let response = crate::platform::send_http(url, &method, headers, body, timeout_ms)?;
+                if let Some(cassette) = cassette {
+                    cassette.record_response(&method, url, body, &response)?;
+                }
+                response
+            }
+        };
+        if let Some((cache, key)) = cache.zip(cache_key.as_deref()) {
+            cache.put(key, &response);
+        }
+        Ok(response)
     }
 
-    /// Perform flexible HTTP request with method, optional headers, body, and timeout.
-    /// Returns HttpResponse with status, headers, and body.
-    pub fn http_request(
+    /// Make a request and pass the response body to `on_chunk` as it arrives
+    ///
+    /// Checked, approved and counted like [`Sandbox::http_request`], but never
+    /// cached. `on_chunk` returns false to stop reading. The response returned
+    /// has the status and headers, and an empty body. A replayed cassette passes
+    /// the recorded body as one chunk; when recording, what was read is saved.
+    pub fn http_stream(
         &self,
         url: &str,
         method: &str,
         headers: Option<Vec<(String, String)>>,
         body: Option<&str>,
         timeout_ms: Option<u64>,
+        on_chunk: &mut dyn FnMut(&str) -> bool,
     ) -> Result<HttpResponse, SandboxError> {
+        let method = method.to_uppercase();
+        let streamed = std::cell::Cell::new(0u64);
+        let mut counted = |chunk: &str| {
+            streamed.set(streamed.get() + chunk.len() as u64);
+            on_chunk(chunk)
+        };
+        self.audited(
+            "http-stream",
+            url,
+            Some(&method),
+            || {
+                self.admit_request(url, &method)?;
+                self.count_request()?;
+                match &self.cassette {
+                    Some(cassette) if cassette.mode == CassetteMode::Replay => {
+                        let mut response = cassette.replay_response(&method, url, body)?;
+                        let recorded = std::mem::take(&mut response.body);
+                        if !recorded.is_empty() {
+                            counted(&recorded);
+                        }
+                        Ok(response)
+                    }
+                    cassette => {
+                        let mut received = String::new();
+                        let mut response = crate::platform::stream_http(
+                            url,
+                            &method,
+                            headers,
+                            body,
+                            timeout_ms,
+                            &mut |chunk| {
+                                if cassette.is_some() {
+                                    received.push_str(chunk);
+                                }
+                                counted(chunk)
+                            },
+                        )?;
+                        if let Some(cassette) = cassette {
+                            response.body = received;
+                            cassette.record_response(&method, url, body, &response)?;
+                            response.body = String::new();
+                        }
+                        Ok(response)
+                    }
+                }
+            },
+            |response| (Some(streamed.get()), Some(response.status)),
+        )
+    }
+
+    /// The checks every HTTP request passes first: network on, address allowed,
+    /// and the host's approval for anything but GET and HEAD
+    fn admit_request(&self, url: &str, method: &str) -> Result<(), SandboxError> {
         if !self.net_config.enabled {
             return Err(SandboxError::NetworkDisabled);
         }
 
-        if !self.is_address_allowed(url) {
-            return Err(SandboxError::AddressNotAllowed(url.to_string()));
+        if let Some(reason) = address_denial(&self.net_config.allowed_addresses, url) {
+            return Err(SandboxError::AddressNotAllowed {
+                url: url.to_string(),
+                reason,
+            });
+        }
+
+        if method != "GET" && method != "HEAD" {
+            self.approve(ApprovalRequest::HttpRequest {
+                method: method.to_string(),
+                url: url.to_string(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Count a request toward `max_requests`, failing once the quota is used up
+    fn count_request(&self) -> Result<(), SandboxError> {
+        if let Some(max) = self.net_config.max_requests {
+            if self.requests.fetch_add(1, Ordering::Relaxed) >= max {
+                return Err(SandboxError::QuotaExceeded(format!(
+                    "at most {} HTTP requests allowed",
+                    max
+                )));
+            }
+        }
+        Ok(())
+    }
+}
+
+// ============================================================================
+// WebSockets
+// ============================================================================
+
+#[cfg(feature = "websocket")]
+type Socket = tungstenite::WebSocket<tungstenite::stream::MaybeTlsStream<std::net::TcpStream>>;
+
+/// The sandbox's open WebSocket connections, by handle id
+#[cfg(feature = "websocket")]
+#[derive(Default)]
+struct WebSockets {
+    next_id: AtomicU64,
+    open: Mutex<std::collections::HashMap<u64, Arc<Mutex<Socket>>>>,
+}
+
+#[cfg(feature = "websocket")]
+impl Sandbox {
+    /// Open a WebSocket to `url` (ws:// or wss://)
+    ///
+    /// Needs network access and `NetConfig::allow_websocket`, and the URL must
+    /// pass the allowlist. Opening a connection counts as one request toward
+    /// `max_requests`; messages on it do not. Cassettes hold HTTP responses
+    /// only, so replaying one fails with [`SandboxError::NotRecorded`].
+    pub fn ws_connect(&self, url: &str) -> Result<WebSocketHandle, SandboxError> {
+        self.audited(
+            "ws-connect",
+            url,
+            None,
+            || {
+                if !self.net_config.enabled {
+                    return Err(SandboxError::NetworkDisabled);
+                }
+                if !self.net_config.allow_websocket {
+                    return Err(SandboxError::WebSocketDisabled);
+                }
+                if let Some(reason) = address_denial(&self.net_config.allowed_addresses, url) {
+                    return Err(SandboxError::AddressNotAllowed {
+                        url: url.to_string(),
+                        reason,
+                    });
+                }
+                self.count_request()?;
+                if matches!(&self.cassette, Some(c) if c.mode == CassetteMode::Replay) {
+                    return Err(SandboxError::NotRecorded(format!("WebSocket {}", url)));
+                }
+
+                let (socket, _) = tungstenite::connect(url).map_err(|e| {
+                    SandboxError::IoError(format!("WebSocket connection to {} failed: {}", url, e))
+                })?;
+                let id = self.websockets.next_id.fetch_add(1, Ordering::Relaxed);
+                self.websockets
+                    .open
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .insert(id, Arc::new(Mutex::new(socket)));
+                Ok(WebSocketHandle {
+                    id,
+                    url: url.to_string(),
+                })
+            },
+            |_| (None, None),
+        )
+    }
+
+    /// Send `text` as one text message
+    pub fn ws_send(&self, socket: &WebSocketHandle, text: &str) -> Result<(), SandboxError> {
+        self.audited(
+            "ws-send",
+            &socket.url,
+            None,
+            || {
+                let open = self.websocket(socket)?;
+                let mut open = open.lock().unwrap_or_else(|e| e.into_inner());
+                open.send(tungstenite::Message::text(text))
+                    .map_err(|e| websocket_error(socket, e))
+            },
+            |_| (Some(text.len() as u64), None),
+        )
+    }
+
+    /// Wait for the next text or binary message, or `None` after `timeout_ms`
+    ///
+    /// Binary messages are decoded as UTF-8, replacing invalid bytes. Pings are
+    /// answered by the library and never returned.
+    pub fn ws_recv(
+        &self,
+        socket: &WebSocketHandle,
+        timeout_ms: Option<u64>,
+    ) -> Result<Option<String>, SandboxError> {
+        self.audited(
+            "ws-recv",
+            &socket.url,
+            None,
+            || {
+                use tungstenite::stream::MaybeTlsStream;
+                use tungstenite::Message;
+
+                let open = self.websocket(socket)?;
+                let mut open = open.lock().unwrap_or_else(|e| e.into_inner());
+                let timeout = timeout_ms.map(|ms| std::time::Duration::from_millis(ms.max(1)));
+                let stream = match open.get_ref() {
+                    MaybeTlsStream::Plain(stream) => Some(stream),
+                    MaybeTlsStream::Rustls(stream) => Some(stream.get_ref()),
+                    _ => None,
+                };
+                if let Some(stream) = stream {
+                    stream
+                        .set_read_timeout(timeout)
+                        .map_err(|e| SandboxError::IoError(e.to_string()))?;
+                }
+                loop {
+                    match open.read() {
+                        Ok(Message::Text(text)) => return Ok(Some(text.to_string())),
+                        Ok(Message::Binary(bytes)) => {
+                            return Ok(Some(String::from_utf8_lossy(&bytes).into_owned()))
+                        }
+                        Ok(Message::Close(_)) => {
+                            return Err(SandboxError::IoError(format!(
+                                "WebSocket {} was closed by the server",
+                                socket.url
+                            )))
+                        }
+                        Ok(_) => continue,
+                        Err(tungstenite::Error::Io(e))
+                            if matches!(
+                                e.kind(),
+                                std::io::ErrorKind::WouldBlock | std::io::ErrorKind::TimedOut
+                            ) =>
+                        {
+                            return Ok(None)
+                        }
+                        Err(e) => return Err(websocket_error(socket, e)),
+                    }
+                }
+            },
+            |message| (message.as_ref().map(|text| text.len() as u64), None),
+        )
+    }
+
+    /// Close the connection; closing one already closed does nothing
+    pub fn ws_close(&self, socket: &WebSocketHandle) -> Result<(), SandboxError> {
+        self.audited(
+            "ws-close",
+            &socket.url,
+            None,
+            || {
+                let removed = self
+                    .websockets
+                    .open
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&socket.id);
+                if let Some(open) = removed {
+                    let mut open = open.lock().unwrap_or_else(|e| e.into_inner());
+                    // The server may already be gone; the socket is dropped either way
+                    let _ = open.close(None);
+                    let _ = open.flush();
+                }
+                Ok(())
+            },
+            |_| (None, None),
+        )
+    }
+
+    fn websocket(&self, socket: &WebSocketHandle) -> Result<Arc<Mutex<Socket>>, SandboxError> {
+        self.websockets
+            .open
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(&socket.id)
+            .cloned()
+            .ok_or_else(|| SandboxError::IoError(format!("WebSocket {} is closed", socket.url)))
+    }
+}
+
+#[cfg(feature = "websocket")]
+fn websocket_error(socket: &WebSocketHandle, error: tungstenite::Error) -> SandboxError {
+    match error {
+        tungstenite::Error::ConnectionClosed | tungstenite::Error::AlreadyClosed => {
+            SandboxError::IoError(format!("WebSocket {} is closed", socket.url))
+        }
+        e => SandboxError::IoError(format!("WebSocket {}: {}", socket.url, e)),
+    }
+}
+
+/// Too many requests, or a server error that may pass
+fn is_retryable(status: u16) -> bool {
+    status == 429 || (500..600).contains(&status)
+}
+
+/// Limits for [`Sandbox::http_request_with_retry`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Most attempts, counting the first
+    pub attempts: u32,
+    /// Wait before the first retry in milliseconds, doubled for each later one
+    pub backoff_ms: f64,
+    /// Time budget for all attempts and waits in milliseconds
+    pub max_time_ms: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 3,
+            backoff_ms: 500.0,
+            max_time_ms: 30_000.0,
         }
+    }
+}
 
-        let timeout_secs = timeout_ms.unwrap_or(30000) / 1000;
-        let timeout_duration = std::time::Duration::from_secs(timeout_secs);
+/// An operation waiting for the host's approval (see [`Sandbox::set_approval_callback`])
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ApprovalRequest {
+    WriteFile {
+        path: String,
+        bytes: usize,
+    },
+    /// Any method other than GET or HEAD
+    HttpRequest {
+        method: String,
+        url: String,
+    },
+}
 
-        let mut request = match method.to_uppercase().as_str() {
-            "GET" => ureq::get(url),
-            "POST" => ureq::post(url),
-            "PUT" => ureq::put(url),
-            "DELETE" => ureq::delete(url),
-            "PATCH" => ureq::patch(url),
-            "HEAD" => ureq::head(url),
-            _ => {
-                return Err(SandboxError::IoError(format!(
-                    "Unsupported HTTP method: {}",
-                    method
-                )))
+impl std::fmt::Display for ApprovalRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ApprovalRequest::WriteFile { path, bytes } => {
+                write!(f, "write {} bytes to {}", bytes, path)
             }
+            ApprovalRequest::HttpRequest { method, url } => write!(f, "{} {}", method, url),
+        }
+    }
+}
+
+/// Host hook deciding whether an [`ApprovalRequest`] may proceed
+pub type ApprovalCallback = Arc<dyn Fn(&ApprovalRequest) -> bool + Send + Sync>;
+
+/// Audit entries kept in memory; older ones are dropped (the audit file keeps everything)
+pub const MAX_AUDIT_ENTRIES: usize = 10_000;
+
+/// How a sandboxed operation ended
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOutcome {
+    Ok,
+    /// Refused by sandbox policy: path outside the allowlist, file too large, network off,
+    /// address not allowed, request quota used up, or refused by the approval callback
+    Denied,
+    /// Allowed but failed, e.g. file not found or connection refused
+    Error,
+}
+
+impl AuditOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuditOutcome::Ok => "ok",
+            AuditOutcome::Denied => "denied",
+            AuditOutcome::Error => "error",
+        }
+    }
+}
+
+/// One filesystem or network operation attempted through the sandbox
+#[derive(Clone, Debug, Serialize)]
+pub struct AuditEntry {
+    /// Unix time in seconds when the operation started
+    pub timestamp: f64,
+    /// `read-file`, `write-file`, `append-file`, `file-exists`, `file-size`, `file-stat`, `list-files` or `http-request`
+    pub operation: String,
+    /// Path or URL
+    pub target: String,
+    /// HTTP method, for network requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub outcome: AuditOutcome,
+    /// Bytes read, written, or received in a response body
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// HTTP status code, for network requests
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    pub duration_ms: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Recent audit entries plus an optional JSONL file receiving all of them
+#[derive(Default)]
+struct AuditLog {
+    entries: Mutex<VecDeque<AuditEntry>>,
+    sink: Mutex<Option<std::fs::File>>,
+    bytes_read: AtomicU64,
+    bytes_written: AtomicU64,
+}
+
+impl AuditLog {
+    fn record(&self, entry: AuditEntry) {
+        let total = match entry.operation.as_str() {
+            "read-file" | "http-request" | "http-stream" | "ws-recv" => Some(&self.bytes_read),
+            "write-file" | "append-file" | "ws-send" => Some(&self.bytes_written),
+            _ => None,
         };
+        if let (Some(total), Some(bytes)) = (total, entry.bytes) {
+            total.fetch_add(bytes, Ordering::Relaxed);
+        }
 
-        // Set headers if provided
-        if let Some(header_list) = headers {
-            for (key, value) in header_list {
-                request = request.set(&key, &value);
+        if let Some(file) = self.sink.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            if let Ok(mut line) = serde_json::to_string(&entry) {
+                line.push('\n');
+                // One write per line keeps concurrent appenders from interleaving
+                if let Err(e) = file.write_all(line.as_bytes()) {
+                    eprintln!("Warning: cannot write audit log: {}", e);
+                }
             }
         }
 
-        request = request.timeout(timeout_duration);
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() == MAX_AUDIT_ENTRIES {
+            entries.pop_front();
+        }
+        entries.push_back(entry);
+    }
+}
+
+/// Whether a [`Cassette`] is saving live responses or serving saved ones
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CassetteMode {
+    Record,
+    Replay,
+}
+
+/// A request and the response it received
+#[derive(Debug, Serialize, Deserialize)]
+struct Interaction {
+    method: String,
+    url: String,
+    /// Request body, if any
+    body: Option<String>,
+    status: u16,
+    /// Sorted so re-recording a cassette gives a stable file
+    headers: BTreeMap<String, String>,
+    response: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// HTTP responses recorded to a JSON file so scripts can be re-run without the network
+///
+/// When recording, every successful response is appended and the file is
+/// rewritten. When replaying, requests are matched on method, URL and body and
+/// served in the order they were recorded; once every match has been served,
+/// the last one is repeated. The sandbox's network checks still apply.
+#[derive(Debug)]
+pub struct Cassette {
+    mode: CassetteMode,
+    path: PathBuf,
+    state: Mutex<CassetteState>,
+}
 
-        let response = if let Some(body_str) = body {
-            request
-                .send_string(body_str)
-                .map_err(|e| SandboxError::IoError(format!("HTTP {} failed: {}", method, e)))?
-        } else {
-            request
-                .call()
-                .map_err(|e| SandboxError::IoError(format!("HTTP {} failed: {}", method, e)))?
+#[derive(Debug, Default)]
+struct CassetteState {
+    file: CassetteFile,
+    /// Which interactions have been replayed
+    served: Vec<bool>,
+}
+
+impl Cassette {
+    /// Start an empty cassette that will be written to `path`
+    pub fn recording(path: &Path) -> Result<Self, SandboxError> {
+        let cassette = Cassette {
+            mode: CassetteMode::Record,
+            path: path.to_path_buf(),
+            state: Mutex::new(CassetteState::default()),
         };
+        cassette.save(&CassetteFile::default())?;
+        Ok(cassette)
+    }
+
+    /// Load a cassette written by [`Cassette::recording`] for replay
+    pub fn replaying(path: &Path) -> Result<Self, SandboxError> {
+        let text = std::fs::read_to_string(path).map_err(|e| {
+            SandboxError::IoError(format!("Cannot read cassette {}: {}", path.display(), e))
+        })?;
+        let file: CassetteFile = serde_json::from_str(&text).map_err(|e| {
+            SandboxError::IoError(format!("Invalid cassette {}: {}", path.display(), e))
+        })?;
+        let served = vec![false; file.interactions.len()];
+        Ok(Cassette {
+            mode: CassetteMode::Replay,
+            path: path.to_path_buf(),
+            state: Mutex::new(CassetteState { file, served }),
+        })
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CassetteState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, file: &CassetteFile) -> Result<(), SandboxError> {
+        let json = serde_json::to_string_pretty(file).expect("cassettes serialize to JSON");
+        std::fs::write(&self.path, json).map_err(|e| {
+            SandboxError::IoError(format!(
+                "Cannot write cassette {}: {}",
+                self.path.display(),
+                e
+            ))
+        })
+    }
+
+    fn record_response(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+        response: &HttpResponse,
+    ) -> Result<(), SandboxError> {
+        let mut state = self.lock();
+        state.file.interactions.push(Interaction {
+            method: method.to_string(),
+            url: url.to_string(),
+            body: body.map(str::to_string),
+            status: response.status,
+            headers: response.headers.clone().into_iter().collect(),
+            response: response.body.clone(),
+        });
+        self.save(&state.file)
+    }
 
-        let status = response.status();
-        let headers_map: std::collections::HashMap<String, String> = response
-            .headers_names()
+    fn replay_response(
+        &self,
+        method: &str,
+        url: &str,
+        body: Option<&str>,
+    ) -> Result<HttpResponse, SandboxError> {
+        let mut state = self.lock();
+        let matches: Vec<usize> = state
+            .file
+            .interactions
             .iter()
-            .map(|name| {
-                let value = response.header(name).unwrap_or("").to_string();
-                (name.to_string(), value)
-            })
+            .enumerate()
+            .filter(|(_, i)| i.method == method && i.url == url && i.body.as_deref() == body)
+            .map(|(index, _)| index)
             .collect();
+        let index = matches
+            .iter()
+            .copied()
+            .find(|&index| !state.served[index])
+            .or_else(|| matches.last().copied())
+            .ok_or_else(|| SandboxError::NotRecorded(format!("{} {}", method, url)))?;
+        state.served[index] = true;
 
-        let body_str = response
-            .into_string()
-            .map_err(|e| SandboxError::IoError(format!("Failed to read response: {}", e)))?;
-
+        let interaction = &state.file.interactions[index];
         Ok(HttpResponse {
-            status,
-            headers: headers_map,
-            body: body_str,
+            status: interaction.status,
+            headers: interaction.headers.clone().into_iter().collect(),
+            body: interaction.response.clone(),
         })
     }
 }
 
+/// A cached response, stored as one JSON file
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedResponse {
+    /// The [`HttpCache::key`] of the request this answers
+    key: String,
+    /// Seconds since the Unix epoch
+    stored_at: f64,
+    status: u16,
+    headers: BTreeMap<String, String>,
+    body: String,
+}
+
+/// GET responses kept in a directory so repeated requests skip the network
+///
+/// Each successful (2xx) response is written to its own file, named after a
+/// hash of the request: its method, URL and headers. A GET with the same
+/// headers (an `Authorization` header included) within the time to live is
+/// answered from that file, so a response fetched with one credential is never
+/// served to a request made with another; after that it goes to the network
+/// and replaces the file. Only a hash of the headers is stored. The sandbox's
+/// network checks still apply, but cached answers do not count toward the
+/// request quota.
+///
+/// The directory must be outside the filesystem sandbox (the CLI refuses one
+/// inside it): cached bodies may hold data fetched with credentials the
+/// script never sees, and a script that could write there could forge answers.
+#[derive(Debug)]
+pub struct HttpCache {
+    dir: PathBuf,
+    ttl: std::time::Duration,
+}
+
+impl HttpCache {
+    /// Cache responses in `dir` (created if missing) for `ttl`
+    pub fn new(dir: &Path, ttl: std::time::Duration) -> Result<Self, SandboxError> {
+        std::fs::create_dir_all(dir).map_err(|e| {
+            SandboxError::IoError(format!("Cannot create HTTP cache {}: {}", dir.display(), e))
+        })?;
+        Ok(HttpCache {
+            dir: dir.to_path_buf(),
+            ttl,
+        })
+    }
+
+    /// Fail if the cache directory is inside a path `config` lets scripts read or write
+    pub fn check_outside(&self, config: &FsConfig) -> Result<(), SandboxError> {
+        let resolve = |path: &Path| std::fs::canonicalize(path).unwrap_or(path.to_path_buf());
+        let dir = resolve(&self.dir);
+        let inside = config
+            .allowed_paths
+            .iter()
+            .chain(&config.read_only_paths)
+            .find(|path| dir.starts_with(resolve(path)));
+        match inside {
+            Some(path) => Err(SandboxError::IoError(format!(
+                "HTTP cache {} is inside the sandbox path {}; scripts could read or forge cached responses",
+                self.dir.display(),
+                path.display()
+            ))),
+            None => Ok(()),
+        }
+    }
+
+    /// What identifies a request in the cache: method, URL and a hash of the headers
+    ///
+    /// Header names are compared without case and in any order.
+    fn key(method: &str, url: &str, headers: Option<&[(String, String)]>) -> String {
+        let mut headers: Vec<(String, &str)> = headers
+            .unwrap_or_default()
+            .iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.as_str()))
+            .collect();
+        headers.sort();
+        format!("{} {} {:016x}", method, url, hash_of(&headers))
+    }
+
+    fn path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("{:016x}.json", hash_of(&key)))
+    }
+
+    /// The response stored for the request `key`, unless there is none or it has expired
+    fn get(&self, key: &str) -> Option<HttpResponse> {
+        let text = std::fs::read_to_string(self.path(key)).ok()?;
+        let cached: CachedResponse = serde_json::from_str(&text).ok()?;
+        let age = crate::platform::unix_time() - cached.stored_at;
+        if cached.key != key || age >= self.ttl.as_secs_f64() {
+            return None;
+        }
+        Some(HttpResponse {
+            status: cached.status,
+            headers: cached.headers.into_iter().collect(),
+            body: cached.body,
+        })
+    }
+
+    /// Store a successful response; one that cannot be written is not cached
+    fn put(&self, key: &str, response: &HttpResponse) {
+        if !(200..300).contains(&response.status) {
+            return;
+        }
+        let cached = CachedResponse {
+            key: key.to_string(),
+            stored_at: crate::platform::unix_time(),
+            status: response.status,
+            headers: response.headers.clone().into_iter().collect(),
+            body: response.body.clone(),
+        };
+        let json = serde_json::to_string(&cached).expect("responses serialize to JSON");
+        let _ = std::fs::write(self.path(key), json);
+    }
+}
+
+fn hash_of(value: &impl std::hash::Hash) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
 /// HTTP Response structure returned by http_request
 #[derive(Clone, Debug)]
 pub struct HttpResponse {
@@ -426,9 +1507,11 @@ pub struct FileStat {
     pub readonly: bool,
 }
 
-#[cfg(test)]
+// These tests use files on disk
+#[cfg(all(test, feature = "native-fs"))]
 mod tests {
     use super::*;
+    use crate::fs_backend::MemoryFs;
     use serial_test::serial;
     use std::fs;
 
@@ -477,6 +1560,10 @@ mod tests {
         let contents = sandbox.read_file("output.txt").unwrap();
         assert_eq!(contents, "test data");
 
+        // Missing parent directories are created
+        sandbox.write_file("nested/dir/out.txt", "deep").unwrap();
+        assert_eq!(sandbox.read_file("nested/dir/out.txt").unwrap(), "deep");
+
         cleanup_test_sandbox(&test_dir);
     }
 
@@ -550,8 +1637,516 @@ mod tests {
         fs::write(test_dir.join("file2.txt"), "data2").unwrap();
 
         let files = sandbox.list_files(".").unwrap();
-        assert!(files.contains(&"file1.txt".to_string()));
-        assert!(files.contains(&"file2.txt".to_string()));
+        assert_eq!(files, vec!["file1.txt", "file2.txt"]);
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_append_file() {
+        let (sandbox, test_dir) = create_test_sandbox();
+
+        sandbox.append_file("logs/run.log", "one\n").unwrap();
+        sandbox.append_file("logs/run.log", "two\n").unwrap();
+        assert_eq!(
+            fs::read_to_string(test_dir.join("logs/run.log")).unwrap(),
+            "one\ntwo\n"
+        );
+        assert!(matches!(
+            sandbox.append_file("../escape.log", "x"),
+            Err(SandboxError::PathNotAllowed(_))
+        ));
+        let big = "x".repeat(sandbox.fs_config.max_file_size);
+        assert!(matches!(
+            sandbox.append_file("logs/run.log", &big),
+            Err(SandboxError::FileTooLarge(_))
+        ));
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_audit_records_operations() {
+        let (sandbox, test_dir) = create_test_sandbox();
+
+        sandbox.write_file("audit.txt", "12345").unwrap();
+        sandbox.read_file("audit.txt").unwrap();
+        let _ = sandbox.read_file("missing.txt");
+        let _ = sandbox.read_file("../escape.txt");
+        let _ = sandbox.http_request("http://example.com", "get", None, None, None);
+
+        let entries = sandbox.audit_entries();
+        let summary: Vec<_> = entries
+            .iter()
+            .map(|e| (e.operation.as_str(), e.outcome, e.bytes))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("write-file", AuditOutcome::Ok, Some(5)),
+                ("read-file", AuditOutcome::Ok, Some(5)),
+                ("read-file", AuditOutcome::Error, None),
+                ("read-file", AuditOutcome::Denied, None),
+                ("http-request", AuditOutcome::Denied, None),
+            ]
+        );
+        assert_eq!(entries[4].method.as_deref(), Some("GET"));
+        assert!(entries[3].error.is_some());
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_audit_file_is_jsonl() {
+        let (mut sandbox, test_dir) = create_test_sandbox();
+        let log = test_dir.join("audit.jsonl");
+        sandbox.set_audit_file(&log).unwrap();
+
+        sandbox.write_file("a.txt", "hi").unwrap();
+        let _ = sandbox.read_file("/etc/passwd");
+
+        let contents = fs::read_to_string(&log).unwrap();
+        let lines: Vec<serde_json::Value> = contents
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["operation"], "write-file");
+        assert_eq!(lines[0]["bytes"], 2);
+        assert_eq!(lines[1]["outcome"], "denied");
+        assert!(lines[1].get("bytes").is_none());
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    fn create_network_sandbox(cassette: Cassette) -> Sandbox {
+        let fs_config = FsConfig {
+            allowed_paths: vec![],
+            ..Default::default()
+        };
+        let net_config = NetConfig {
+            enabled: true,
+            allowed_addresses: vec!["example.com".to_string()],
+            max_requests: None,
+            max_rate: None,
+            allow_websocket: false,
+        };
+        let mut sandbox = Sandbox::new(fs_config, net_config).unwrap();
+        sandbox.set_cassette(Arc::new(cassette));
+        sandbox
+    }
+
+    #[test]
+    #[serial]
+    fn test_cassette_replays_in_order() {
+        let (_, test_dir) = create_test_sandbox();
+        let path = test_dir.join("cassette.json");
+        fs::write(
+            &path,
+            r#"{"interactions": [
+                {"method": "GET", "url": "https://example.com/n", "body": null,
+                 "status": 200, "headers": {"x-n": "1"}, "response": "one"},
+                {"method": "GET", "url": "https://example.com/n", "body": null,
+                 "status": 200, "headers": {}, "response": "two"},
+                {"method": "POST", "url": "https://example.com/n", "body": "hi",
+                 "status": 201, "headers": {}, "response": "posted"}
+            ]}"#,
+        )
+        .unwrap();
+        let sandbox = create_network_sandbox(Cassette::replaying(&path).unwrap());
+        let get = || {
+            sandbox
+                .http_request("https://example.com/n", "get", None, None, None)
+                .unwrap()
+        };
+
+        let first = get();
+        assert_eq!(first.body, "one");
+        assert_eq!(first.headers["x-n"], "1");
+        assert_eq!(get().body, "two");
+        // Once every recording has been served, the last one repeats
+        assert_eq!(get().body, "two");
+
+        let post = sandbox
+            .http_request("https://example.com/n", "POST", None, Some("hi"), None)
+            .unwrap();
+        assert_eq!((post.status, post.body.as_str()), (201, "posted"));
+
+        // Unrecorded requests fail instead of reaching the network
+        assert!(matches!(
+            sandbox.http_request("https://example.com/other", "GET", None, None, None),
+            Err(SandboxError::NotRecorded(_))
+        ));
+        // The allowlist still applies when replaying
+        assert!(matches!(
+            sandbox.http_request("https://elsewhere.org/n", "GET", None, None, None),
+            Err(SandboxError::AddressNotAllowed { .. })
+        ));
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_cassette_recording_starts_empty() {
+        let (_, test_dir) = create_test_sandbox();
+        let path = test_dir.join("recorded.json");
+
+        let recording = Cassette::recording(&path).unwrap();
+        recording
+            .record_response(
+                "GET",
+                "https://example.com/",
+                None,
+                &HttpResponse {
+                    status: 200,
+                    headers: std::collections::HashMap::new(),
+                    body: "saved".to_string(),
+                },
+            )
+            .unwrap();
+
+        let sandbox = create_network_sandbox(Cassette::replaying(&path).unwrap());
+        let response = sandbox
+            .http_request("https://example.com/", "GET", None, None, None)
+            .unwrap();
+        assert_eq!(response.body, "saved");
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_read_only_paths() {
+        let (_, test_dir) = create_test_sandbox();
+        let writable = test_dir.join("rw");
+        let read_only = test_dir.join("ro");
+        fs::create_dir_all(&read_only).unwrap();
+        fs::write(read_only.join("ref.txt"), "reference").unwrap();
+
+        let fs_config = FsConfig {
+            allowed_paths: vec![writable.clone()],
+            read_only_paths: vec![read_only.clone()],
+            ..Default::default()
+        };
+        let sandbox = Sandbox::new(fs_config, NetConfig::default()).unwrap();
+
+        assert_eq!(sandbox.read_file("ref.txt").unwrap(), "reference");
+        sandbox.write_file("ref.txt", "changed").unwrap();
+        // The write lands in the writable root; the read-only copy is untouched
+        assert_eq!(
+            fs::read_to_string(writable.join("ref.txt")).unwrap(),
+            "changed"
+        );
+        assert_eq!(
+            fs::read_to_string(read_only.join("ref.txt")).unwrap(),
+            "reference"
+        );
+
+        let only_read = FsConfig {
+            allowed_paths: vec![],
+            read_only_paths: vec![read_only],
+            ..Default::default()
+        };
+        let sandbox = Sandbox::new(only_read, NetConfig::default()).unwrap();
+        assert!(matches!(
+            sandbox.write_file("new.txt", "x"),
+            Err(SandboxError::PathNotAllowed(_))
+        ));
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_request_quota() {
+        let (_, test_dir) = create_test_sandbox();
+        let cassette = test_dir.join("empty.json");
+        fs::write(&cassette, r#"{"interactions": []}"#).unwrap();
+
+        let fs_config = FsConfig {
+            allowed_paths: vec![],
+            ..Default::default()
+        };
+        let net_config = NetConfig {
+            enabled: true,
+            allowed_addresses: vec!["example.com".to_string()],
+            max_requests: Some(1),
+            max_rate: None,
+            allow_websocket: false,
+        };
+        let mut sandbox = Sandbox::new(fs_config, net_config).unwrap();
+        // Replay an empty cassette so nothing reaches the network
+        sandbox.set_cassette(Arc::new(Cassette::replaying(&cassette).unwrap()));
+
+        // Denied requests don't use the quota
+        let _ = sandbox.http_request("https://elsewhere.org", "GET", None, None, None);
+        assert!(matches!(
+            sandbox.http_request("https://example.com", "GET", None, None, None),
+            Err(SandboxError::NotRecorded(_))
+        ));
+        assert!(matches!(
+            sandbox.http_request("https://example.com", "GET", None, None, None),
+            Err(SandboxError::QuotaExceeded(_))
+        ));
+        assert_eq!(sandbox.audit_entries()[2].outcome, AuditOutcome::Denied);
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    fn test_approval_callback_for_writes() {
+        let fs = Arc::new(MemoryFs::new());
+        let mut sandbox =
+            Sandbox::with_backend(fs.clone(), FsConfig::default(), NetConfig::default());
+        let asked = Arc::new(Mutex::new(Vec::new()));
+        let log = Arc::clone(&asked);
+        sandbox.set_approval_callback(move |request| {
+            log.lock().unwrap().push(request.clone());
+            matches!(request, ApprovalRequest::WriteFile { path, .. } if path == "ok.txt")
+        });
+
+        sandbox.write_file("ok.txt", "yes").unwrap();
+        assert!(matches!(
+            sandbox.write_file("no.txt", "nope"),
+            Err(SandboxError::NotApproved(_))
+        ));
+        // Reads never ask
+        assert_eq!(sandbox.read_file("ok.txt").unwrap(), "yes");
+
+        assert_eq!(fs.paths(), vec!["ok.txt"]);
+        assert_eq!(
+            asked.lock().unwrap()[1],
+            ApprovalRequest::WriteFile {
+                path: "no.txt".to_string(),
+                bytes: 4
+            }
+        );
+        assert_eq!(asked.lock().unwrap().len(), 2);
+        assert_eq!(sandbox.audit_entries()[1].outcome, AuditOutcome::Denied);
+    }
+
+    #[test]
+    #[serial]
+    fn test_approval_callback_for_http() {
+        let (_, test_dir) = create_test_sandbox();
+        let cassette = test_dir.join("empty.json");
+        fs::write(&cassette, r#"{"interactions": []}"#).unwrap();
+
+        let net_config = NetConfig {
+            enabled: true,
+            ..Default::default()
+        };
+        let mut sandbox =
+            Sandbox::with_backend(Arc::new(MemoryFs::new()), FsConfig::default(), net_config);
+        sandbox.set_cassette(Arc::new(Cassette::replaying(&cassette).unwrap()));
+        sandbox.set_approval_callback(|_| false);
+
+        // GET goes ahead without asking; POST is refused before replay
+        assert!(matches!(
+            sandbox.http_request("https://example.com", "get", None, None, None),
+            Err(SandboxError::NotRecorded(_))
+        ));
+        let err = sandbox
+            .http_request("https://example.com", "post", None, Some("{}"), None)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "Not approved: POST https://example.com");
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    fn test_address_pattern_parsing() {
+        let pattern = AddressPattern::parse("HTTP://*.Example.com:8080").unwrap();
+        assert_eq!(pattern.to_string(), "http://*.example.com:8080");
+        assert_eq!(AddressPattern::parse("[::1]").unwrap().port, None);
+        assert_eq!(AddressPattern::parse("[::1]:80").unwrap().port, Some(80));
+        for bad in [
+            "",
+            "*.",
+            "a*.com",
+            "*foo.com",
+            "example.com:http",
+            "example.com/api",
+        ] {
+            assert!(
+                AddressPattern::parse(bad).is_err(),
+                "{} should be rejected",
+                bad
+            );
+        }
+    }
+
+    #[test]
+    fn test_address_allowlist_matching() {
+        let allow = |patterns: &[&str], url: &str| {
+            let patterns: Vec<String> = patterns.iter().map(|p| p.to_string()).collect();
+            address_denial(&patterns, url)
+        };
+
+        // Exact hosts, over https by default
+        assert_eq!(allow(&["example.com"], "https://example.com/a"), None);
+        assert_eq!(allow(&["example.com"], "https://EXAMPLE.com:443/"), None);
+        let denied = allow(&["example.com"], "https://api.example.com").unwrap();
+        assert_eq!(denied, "api.example.com is not in the network allowlist");
+        // No more substring matches
+        assert!(allow(&["example.com"], "https://example.com.evil.org").is_some());
+
+        // Wildcards cover subdomains but not the domain itself
+        assert_eq!(allow(&["*.example.com"], "https://a.b.example.com"), None);
+        assert!(allow(&["*.example.com"], "https://example.com").is_some());
+        assert!(allow(&["*.example.com"], "https://badexample.com").is_some());
+
+        // Plain http needs an entry that names the scheme
+        let denied = allow(&["example.com"], "http://example.com").unwrap();
+        assert!(
+            denied.contains("http is not allowed for example.com"),
+            "{}",
+            denied
+        );
+        assert_eq!(allow(&["http://example.com"], "http://example.com"), None);
+        assert!(allow(&["http://example.com"], "https://example.com").is_some());
+
+        // Secure WebSockets are allowed alongside https, plain ones need the scheme
+        assert_eq!(allow(&["example.com"], "wss://example.com/feed"), None);
+        assert!(allow(&["example.com"], "ws://example.com/feed").is_some());
+        assert_eq!(allow(&["ws://example.com"], "ws://example.com/feed"), None);
+
+        // Ports
+        assert_eq!(
+            allow(&["example.com:8443"], "https://example.com:8443"),
+            None
+        );
+        let denied = allow(&["example.com:8443"], "https://example.com/").unwrap();
+        assert_eq!(
+            denied,
+            "port 443 is not allowed for example.com (allowed: example.com:8443)"
+        );
+        assert_eq!(allow(&["http://*:8080"], "http://localhost:8080/x"), None);
+
+        // An empty allowlist allows every host, still only over https and wss
+        assert_eq!(allow(&[], "https://anything.org"), None);
+        assert!(allow(&[], "http://anything.org").is_some());
+        assert!(allow(&[], "not a url")
+            .unwrap()
+            .starts_with("not a valid URL"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_denial_error_message() {
+        let (_, test_dir) = create_test_sandbox();
+        let cassette = test_dir.join("empty.json");
+        fs::write(&cassette, r#"{"interactions": []}"#).unwrap();
+        let sandbox = create_network_sandbox(Cassette::replaying(&cassette).unwrap());
+        let err = sandbox
+            .http_request("http://example.com/x", "GET", None, None, None)
+            .unwrap_err();
+        assert_eq!(err.kind(), "address-not-allowed");
+        assert!(err
+            .to_string()
+            .starts_with("Network address not allowed: http://example.com/x (http is not allowed"));
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_http_cache_serves_repeated_gets() {
+        let (_, test_dir) = create_test_sandbox();
+        let path = test_dir.join("cassette.json");
+        fs::write(
+            &path,
+            r#"{"interactions": [
+                {"method": "GET", "url": "https://example.com/n", "body": null,
+                 "status": 200, "headers": {}, "response": "one"},
+                {"method": "GET", "url": "https://example.com/n", "body": null,
+                 "status": 200, "headers": {}, "response": "two"},
+                {"method": "GET", "url": "https://example.com/n", "body": null,
+                 "status": 200, "headers": {}, "response": "three"}
+            ]}"#,
+        )
+        .unwrap();
+        let mut sandbox = create_network_sandbox(Cassette::replaying(&path).unwrap());
+        let get = |sandbox: &Sandbox| {
+            sandbox
+                .http_request("https://example.com/n", "GET", None, None, None)
+                .unwrap()
+                .body
+        };
+
+        let minute = std::time::Duration::from_secs(60);
+        let cache = Arc::new(HttpCache::new(&test_dir.join("cache"), minute).unwrap());
+        sandbox.set_cache(Arc::clone(&cache));
+        assert_eq!(get(&sandbox), "one");
+        assert_eq!(get(&sandbox), "one");
+        let other = HttpCache::key("GET", "https://example.com/other", None);
+        assert!(cache.get(&other).is_none());
+
+        // A request with other headers, a credential say, is not answered from the cache
+        let with_token = |sandbox: &Sandbox, token: &str| {
+            let headers = vec![("Authorization".to_string(), token.to_string())];
+            sandbox
+                .http_request("https://example.com/n", "GET", Some(headers), None, None)
+                .unwrap()
+                .body
+        };
+        assert_eq!(with_token(&sandbox, "Bearer a"), "two");
+        assert_eq!(with_token(&sandbox, "Bearer a"), "two");
+        assert_eq!(get(&sandbox), "one");
+        let key = |name: &str| {
+            let headers = [(name.to_string(), "Bearer b".to_string())];
+            HttpCache::key("GET", "https://example.com/n", Some(&headers))
+        };
+        assert_eq!(key("Authorization"), key("authorization"));
+        assert!(cache.get(&key("Authorization")).is_none());
+        assert_ne!(
+            HttpCache::key("GET", "https://example.com/n", None),
+            HttpCache::key("HEAD", "https://example.com/n", None)
+        );
+
+        // Expired entries go back to the network and are replaced
+        let expired = HttpCache::new(&test_dir.join("cache"), std::time::Duration::ZERO).unwrap();
+        sandbox.set_cache(Arc::new(expired));
+        assert_eq!(get(&sandbox), "three");
+        sandbox.set_cache(cache);
+        assert_eq!(get(&sandbox), "three");
+
+        cleanup_test_sandbox(&test_dir);
+    }
+
+    #[test]
+    #[serial]
+    fn test_http_cache_must_be_outside_the_sandbox() {
+        let (_, test_dir) = create_test_sandbox();
+        let minute = std::time::Duration::from_secs(60);
+        let config = |paths: Vec<PathBuf>, read_only: Vec<PathBuf>| FsConfig {
+            allowed_paths: paths,
+            read_only_paths: read_only,
+            ..FsConfig::default()
+        };
+
+        // Scripts with access to the cache could read responses fetched with
+        // the host's credentials, or write their own
+        let inside = HttpCache::new(&test_dir.join("data/cache"), minute).unwrap();
+        let err = inside
+            .check_outside(&config(vec![test_dir.join("data")], vec![]))
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("inside the sandbox path"),
+            "{}",
+            err
+        );
+        assert!(inside
+            .check_outside(&config(vec![], vec![test_dir.join("data")]))
+            .is_err());
+
+        let outside = HttpCache::new(&test_dir.join("cache"), minute).unwrap();
+        assert!(outside
+            .check_outside(&config(vec![test_dir.join("data")], vec![]))
+            .is_ok());
 
         cleanup_test_sandbox(&test_dir);
     }
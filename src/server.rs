@@ -0,0 +1,794 @@
+// ABOUTME: JSON protocol for driving interpreters from another process, over stdio or HTTP
+// Each request names a session; every session owns a separate Interpreter
+
+use crate::interpreter::Interpreter;
+use crate::stdlib::json::value_to_json;
+use crate::value::Value;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Session used when a request does not name one
+pub const DEFAULT_SESSION: &str = "default";
+
+/// Most connections the HTTP server handles at once unless told otherwise
+pub const DEFAULT_MAX_CONNECTIONS: usize = 64;
+
+/// Most sessions a server keeps unless told otherwise
+pub const DEFAULT_MAX_SESSIONS: usize = 256;
+
+/// What a request asks the server to do
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Op {
+    /// Evaluate `code` in the session, creating the session if needed
+    #[default]
+    Eval,
+    /// Discard the session's state; the next eval starts fresh
+    Reset,
+}
+
+/// One request, e.g. `{"id": 1, "session": "a", "code": "(+ 1 2)"}`
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Request {
+    /// Echoed back unchanged so clients can match responses to requests
+    #[serde(default)]
+    pub id: serde_json::Value,
+    #[serde(default)]
+    pub session: Option<String>,
+    #[serde(default)]
+    pub op: Op,
+    #[serde(default)]
+    pub code: String,
+}
+
+/// The reply to one request
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Response {
+    pub id: serde_json::Value,
+    pub session: String,
+    pub ok: bool,
+    /// The printed form of the last value, as the REPL shows it
+    pub result: Option<String>,
+    /// The last value as JSON, when it has a JSON representation
+    pub value: Option<serde_json::Value>,
+    /// Everything printed during the request
+    pub stdout: String,
+    pub error: Option<String>,
+    /// Turned away because the server is at a limit (HTTP answers 503)
+    #[serde(skip)]
+    pub overloaded: bool,
+}
+
+/// Builds the interpreter for a new session (sandbox, stdlib, ...)
+pub type InterpreterFactory = Box<dyn Fn() -> Interpreter + Send + Sync>;
+
+/// Interpreters keyed by session id, created on first use
+///
+/// Requests for different sessions can run at the same time; requests for the
+/// same session wait for each other.
+pub struct Sessions {
+    factory: InterpreterFactory,
+    sessions: Mutex<HashMap<String, Arc<Mutex<Interpreter>>>>,
+    timeout: Option<Duration>,
+    max_sessions: Option<usize>,
+}
+
+impl Sessions {
+    pub fn new(factory: impl Fn() -> Interpreter + Send + Sync + 'static) -> Self {
+        Sessions {
+            factory: Box::new(factory),
+            sessions: Mutex::new(HashMap::new()),
+            timeout: None,
+            max_sessions: None,
+        }
+    }
+
+    /// Cancel any evaluation that runs longer than `timeout`
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Refuse to create more than `max` sessions; reset one to make room
+    pub fn with_max_sessions(mut self, max: usize) -> Self {
+        self.max_sessions = Some(max);
+        self
+    }
+
+    /// The session's interpreter, created with output capture on if it is new
+    ///
+    /// None when the session is new and the session limit has been reached.
+    /// A new interpreter is built without holding the session table, so loading
+    /// its stdlib does not hold up requests for other sessions.
+    pub fn get_or_create(&self, session: &str) -> Option<Arc<Mutex<Interpreter>>> {
+        {
+            let sessions = self.lock();
+            if let Some(interp) = sessions.get(session) {
+                return Some(interp.clone());
+            }
+            if self.is_full(&sessions) {
+                return None;
+            }
+        }
+
+        let mut interp = (self.factory)();
+        interp.capture_output(true);
+
+        // Another request may have created the session, or filled the table, meanwhile
+        let mut sessions = self.lock();
+        if let Some(existing) = sessions.get(session) {
+            return Some(existing.clone());
+        }
+        if self.is_full(&sessions) {
+            return None;
+        }
+        let interp = Arc::new(Mutex::new(interp));
+        sessions.insert(session.to_string(), interp.clone());
+        Some(interp)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<String, Arc<Mutex<Interpreter>>>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn is_full(&self, sessions: &HashMap<String, Arc<Mutex<Interpreter>>>) -> bool {
+        self.max_sessions.is_some_and(|max| sessions.len() >= max)
+    }
+
+    /// Drop a session; returns whether it existed
+    pub fn reset(&self, session: &str) -> bool {
+        self.lock().remove(session).is_some()
+    }
+
+    /// Run one request against its session
+    pub fn handle(&self, request: Request) -> Response {
+        let session = request
+            .session
+            .unwrap_or_else(|| DEFAULT_SESSION.to_string());
+        let mut response = Response {
+            id: request.id,
+            session: session.clone(),
+            ..Response::default()
+        };
+
+        match request.op {
+            Op::Reset => {
+                self.reset(&session);
+                response.ok = true;
+            }
+            Op::Eval => {
+                let Some(interp) = self.get_or_create(&session) else {
+                    response.error = Some(format!(
+                        "too many sessions (at most {}); reset one first",
+                        self.max_sessions.unwrap_or_default()
+                    ));
+                    response.overloaded = true;
+                    return response;
+                };
+                let mut interp = interp.lock().unwrap_or_else(|e| e.into_inner());
+                let result = eval_limited(&mut interp, &request.code, self.timeout);
+                response.stdout = interp.take_output();
+                match result {
+                    Ok(value) => {
+                        response.ok = true;
+                        response.value = value_to_json(&value).ok();
+                        response.result = Some(value.to_string());
+                    }
+                    Err(e) => response.error = Some(e),
+                }
+            }
+        }
+        response
+    }
+
+    /// Parse one request from JSON and handle it, answering malformed input with an error response
+    pub fn handle_line(&self, line: &str) -> Response {
+        match serde_json::from_str::<Request>(line) {
+            Ok(request) => self.handle(request),
+            Err(e) => Response {
+                error: Some(format!("invalid request: {}", e)),
+                ..Response::default()
+            },
+        }
+    }
+}
+
+/// Evaluate `code`, cancelling it through the interpreter's token if it outlives `timeout`
+fn eval_limited(
+    interp: &mut Interpreter,
+    code: &str,
+    timeout: Option<Duration>,
+) -> Result<Value, String> {
+    let Some(timeout) = timeout else {
+        return interp.eval_str(code);
+    };
+
+    let token = interp.cancellation_token();
+    let (done, finished) = mpsc::channel::<()>();
+    let watchdog = std::thread::spawn(move || {
+        let expired = finished.recv_timeout(timeout) == Err(RecvTimeoutError::Timeout);
+        if expired {
+            token.cancel();
+        }
+        expired
+    });
+
+    let result = interp.eval_str(code);
+    let _ = done.send(());
+    let timed_out = watchdog.join().unwrap_or(false);
+    interp.cancellation_token().reset();
+
+    match result {
+        Err(_) if timed_out => Err(format!(
+            "evaluation timed out after {} ms",
+            timeout.as_millis()
+        )),
+        other => other,
+    }
+}
+
+/// Largest request body accepted by the HTTP server
+const MAX_HTTP_BODY: usize = 1024 * 1024;
+
+/// Longest request line or header line accepted by the HTTP server, in bytes
+const MAX_HTTP_LINE: usize = 8 * 1024;
+
+/// Most header lines accepted in one HTTP request
+const MAX_HTTP_HEADERS: usize = 100;
+
+/// Time a client gets to send its whole request, unless told otherwise
+pub const DEFAULT_HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Connection limits for [`serve_http`]
+#[derive(Debug, Clone, Copy)]
+pub struct HttpLimits {
+    /// Connections handled at once; more get 503
+    pub max_connections: usize,
+    /// Time to read a whole request (408 after it) and, separately, to write the response
+    pub io_timeout: Duration,
+}
+
+impl Default for HttpLimits {
+    fn default() -> Self {
+        HttpLimits {
+            max_connections: DEFAULT_MAX_CONNECTIONS,
+            io_timeout: DEFAULT_HTTP_TIMEOUT,
+        }
+    }
+}
+
+/// Serve the protocol over HTTP, one thread per connection
+///
+/// - `POST /eval` with a request object as the body evaluates it
+/// - `POST /reset` with `{"session": ...}` discards that session
+///
+/// Both answer with a response object. At most `limits.max_connections` are
+/// handled at once; further connections, and evals that would need a session
+/// past the session limit, get 503 Service Unavailable. A client that takes
+/// longer than `limits.io_timeout` to send its request gets 408, and one with
+/// an overlong request line or header, or too many headers, gets 431; either
+/// way its connection closes and frees its slot. Runs until the listener fails.
+pub fn serve_http(
+    sessions: Arc<Sessions>,
+    listener: TcpListener,
+    limits: HttpLimits,
+) -> io::Result<()> {
+    let max_connections = limits.max_connections;
+    let active = Arc::new(AtomicUsize::new(0));
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let Some(slot) = ConnectionSlot::take(&active, max_connections) else {
+            let response = error_response(&format!(
+                "too many connections (at most {})",
+                max_connections
+            ));
+            let _ = turn_away(stream, &response);
+            continue;
+        };
+        let sessions = sessions.clone();
+        std::thread::spawn(move || {
+            let _slot = slot;
+            // A client that hangs up mid-request only loses its own response
+            let _ = handle_http(&sessions, stream, limits.io_timeout);
+        });
+    }
+    Ok(())
+}
+
+/// Answer 503 without a handler thread. The unread request is drained
+/// briefly so closing the socket doesn't reset the client before it reads
+/// the response.
+fn turn_away(stream: TcpStream, response: &Response) -> io::Result<()> {
+    write_http_response(stream.try_clone()?, 503, response)?;
+    stream.shutdown(std::net::Shutdown::Write)?;
+    stream.set_read_timeout(Some(Duration::from_millis(100)))?;
+    let _ = io::copy(&mut (&stream).take(64 * 1024), &mut io::sink());
+    Ok(())
+}
+
+/// One of the server's connections in use; dropping it frees the slot
+struct ConnectionSlot(Arc<AtomicUsize>);
+
+impl ConnectionSlot {
+    fn take(active: &Arc<AtomicUsize>, max: usize) -> Option<Self> {
+        active
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+                (n < max).then_some(n + 1)
+            })
+            .ok()
+            .map(|_| ConnectionSlot(active.clone()))
+    }
+}
+
+impl Drop for ConnectionSlot {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// A request that could not be read: answered with a status, or the connection failed
+enum ReadError {
+    Status(u16, &'static str),
+    Io(io::Error),
+}
+
+impl From<io::Error> for ReadError {
+    fn from(error: io::Error) -> Self {
+        match error.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+                ReadError::Status(408, "timed out reading the request")
+            }
+            _ => ReadError::Io(error),
+        }
+    }
+}
+
+/// Reads from a socket until a deadline, however slowly the bytes arrive
+struct DeadlineReader {
+    stream: TcpStream,
+    deadline: Instant,
+}
+
+impl Read for DeadlineReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let left = self.deadline.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return Err(io::ErrorKind::TimedOut.into());
+        }
+        self.stream.set_read_timeout(Some(left))?;
+        self.stream.read(buf)
+    }
+}
+
+/// Read one CRLF-terminated line of at most [`MAX_HTTP_LINE`] bytes
+fn read_http_line(reader: &mut impl BufRead) -> Result<String, ReadError> {
+    let mut line = Vec::new();
+    reader
+        .take(MAX_HTTP_LINE as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if line.len() > MAX_HTTP_LINE {
+        return Err(ReadError::Status(431, "request line or header too long"));
+    }
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+/// The method, path and body of a request
+fn read_http_request(reader: &mut impl BufRead) -> Result<(String, String, Vec<u8>), ReadError> {
+    let request_line = read_http_line(reader)?;
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut headers = 0;
+    loop {
+        let header = read_http_line(reader)?;
+        if header.trim().is_empty() {
+            break;
+        }
+        headers += 1;
+        if headers > MAX_HTTP_HEADERS {
+            return Err(ReadError::Status(431, "too many headers"));
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    if content_length > MAX_HTTP_BODY {
+        return Err(ReadError::Status(413, "request body too large"));
+    }
+    let mut body = vec![0; content_length];
+    reader.read_exact(&mut body)?;
+    Ok((method, path, body))
+}
+
+fn handle_http(sessions: &Sessions, stream: TcpStream, timeout: Duration) -> io::Result<()> {
+    let mut reader = BufReader::new(DeadlineReader {
+        stream: stream.try_clone()?,
+        deadline: Instant::now() + timeout,
+    });
+    let (status, response) = match read_http_request(&mut reader) {
+        Ok((method, path, body)) => {
+            route_http(sessions, &method, &path, &String::from_utf8_lossy(&body))
+        }
+        Err(ReadError::Status(status, message)) => (status, error_response(message)),
+        Err(ReadError::Io(error)) => return Err(error),
+    };
+
+    stream.set_write_timeout(Some(timeout))?;
+    write_http_response(stream, status, &response)
+}
+
+fn write_http_response(mut stream: TcpStream, status: u16, response: &Response) -> io::Result<()> {
+    let body = serde_json::to_string(response)?;
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Service Unavailable",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Pick the operation for an HTTP request, returning the status code and response
+fn route_http(sessions: &Sessions, method: &str, path: &str, body: &str) -> (u16, Response) {
+    let op = match path.split('?').next().unwrap_or_default() {
+        "/eval" => Op::Eval,
+        "/reset" => Op::Reset,
+        _ => return (404, error_response(&format!("no endpoint {}", path))),
+    };
+    if method != "POST" {
+        return (405, error_response("use POST"));
+    }
+
+    let body = if body.trim().is_empty() { "{}" } else { body };
+    match serde_json::from_str::<Request>(body) {
+        Ok(request) => {
+            let response = sessions.handle(Request { op, ..request });
+            (if response.overloaded { 503 } else { 200 }, response)
+        }
+        Err(e) => (400, error_response(&format!("invalid request: {}", e))),
+    }
+}
+
+fn error_response(message: &str) -> Response {
+    Response {
+        error: Some(message.to_string()),
+        ..Response::default()
+    }
+}
+
+/// Serve requests read line by line from `input`, writing one JSON response line per request
+///
+/// Blank lines are skipped. Returns when `input` reaches end of file.
+pub fn serve_stdio(
+    sessions: &Sessions,
+    input: impl BufRead,
+    mut output: impl Write,
+) -> io::Result<()> {
+    for line in input.lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = sessions.handle_line(&line);
+        serde_json::to_writer(&mut output, &response)?;
+        output.write_all(b"\n")?;
+        output.flush()?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn serve(input: &str) -> Vec<serde_json::Value> {
+        let sessions = Sessions::new(Interpreter::new);
+        let mut output = Vec::new();
+        serve_stdio(&sessions, input.as_bytes(), &mut output).unwrap();
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_eval_returns_result_value_and_stdout() {
+        let responses = serve(r#"{"id": 7, "code": "(println \"hi\") (list 1 2)"}"#);
+        let response = &responses[0];
+        assert_eq!(response["id"], 7);
+        assert_eq!(response["session"], DEFAULT_SESSION);
+        assert_eq!(response["ok"], true);
+        assert_eq!(response["result"], "(1 2)");
+        assert_eq!(response["value"], serde_json::json!([1.0, 2.0]));
+        assert_eq!(response["stdout"], "hi\n");
+    }
+
+    #[test]
+    fn test_sessions_are_isolated_and_resettable() {
+        let responses = serve(concat!(
+            r#"{"session": "a", "code": "(define x 1)"}"#,
+            "\n",
+            r#"{"session": "b", "code": "x"}"#,
+            "\n",
+            r#"{"session": "a", "code": "x"}"#,
+            "\n",
+            r#"{"session": "a", "op": "reset"}"#,
+            "\n",
+            r#"{"session": "a", "code": "x"}"#,
+        ));
+        assert_eq!(responses[1]["ok"], false);
+        assert_eq!(responses[2]["result"], "1");
+        assert_eq!(responses[3]["ok"], true);
+        assert_eq!(responses[4]["ok"], false);
+    }
+
+    #[test]
+    fn test_errors_are_reported_not_fatal() {
+        let responses = serve("not json\n\n{\"code\": \"(car 1)\"}\n{\"code\": \"(+ 1 2)\"}");
+        assert_eq!(responses.len(), 3);
+        assert!(responses[0]["error"]
+            .as_str()
+            .unwrap()
+            .starts_with("invalid request"));
+        assert_eq!(responses[1]["ok"], false);
+        assert!(responses[1]["error"].is_string());
+        assert_eq!(responses[2]["result"], "3");
+    }
+
+    #[test]
+    fn test_timeout_cancels_long_evaluations() {
+        let sessions = Sessions::new(Interpreter::new).with_timeout(Duration::from_millis(50));
+        let response = sessions.handle_line(r#"{"code": "(while #t nil)"}"#);
+        assert!(!response.ok);
+        assert!(response.error.unwrap().contains("timed out"));
+
+        // The session is usable again afterwards
+        let response = sessions.handle_line(r#"{"code": "(+ 1 2)"}"#);
+        assert_eq!(response.result.as_deref(), Some("3"));
+    }
+
+    #[test]
+    fn test_sessions_are_built_outside_the_session_table() {
+        let (building, started) = mpsc::channel();
+        let (release, released) = mpsc::channel::<()>();
+        let released = Mutex::new(released);
+        let built = AtomicUsize::new(0);
+        let sessions = Arc::new(Sessions::new(move || {
+            // The second session's interpreter takes until the test releases it
+            if built.fetch_add(1, Ordering::SeqCst) == 1 {
+                building.send(()).unwrap();
+                released.lock().unwrap().recv().unwrap();
+            }
+            Interpreter::new()
+        }));
+
+        sessions.get_or_create("a").unwrap();
+        let slow = {
+            let sessions = sessions.clone();
+            std::thread::spawn(move || sessions.get_or_create("b").is_some())
+        };
+        started.recv().unwrap();
+        // Existing sessions are served while "b" is still being built
+        assert!(sessions.handle_line(r#"{"session": "a", "code": "1"}"#).ok);
+        release.send(()).unwrap();
+        assert!(slow.join().unwrap());
+    }
+
+    #[test]
+    fn test_session_limit() {
+        let sessions = Sessions::new(Interpreter::new).with_max_sessions(2);
+        for session in ["a", "b"] {
+            let request = format!(r#"{{"session": "{}", "code": "1"}}"#, session);
+            assert!(sessions.handle_line(&request).ok);
+        }
+        let response = sessions.handle_line(r#"{"session": "c", "code": "1"}"#);
+        assert!(!response.ok && response.overloaded);
+        assert!(response.error.unwrap().contains("too many sessions"));
+
+        // Existing sessions keep working, and a reset makes room
+        assert!(sessions.handle_line(r#"{"session": "a", "code": "2"}"#).ok);
+        assert!(
+            sessions
+                .handle_line(r#"{"session": "a", "op": "reset"}"#)
+                .ok
+        );
+        assert!(sessions.handle_line(r#"{"session": "c", "code": "1"}"#).ok);
+    }
+
+    /// Send one raw HTTP request and return the status line and body
+    fn http(
+        address: std::net::SocketAddr,
+        method: &str,
+        path: &str,
+        body: &str,
+    ) -> (String, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: test\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        (head.lines().next().unwrap().to_string(), body.to_string())
+    }
+
+    #[test]
+    fn test_http_eval_and_reset() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let sessions = Arc::new(Sessions::new(Interpreter::new));
+        std::thread::spawn(move || serve_http(sessions, listener, HttpLimits::default()));
+
+        let (status, body) = http(
+            address,
+            "POST",
+            "/eval",
+            r#"{"session": "s", "code": "(define x 5) (* x 2)"}"#,
+        );
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["result"], "10");
+
+        let (status, _) = http(address, "POST", "/reset", r#"{"session": "s"}"#);
+        assert_eq!(status, "HTTP/1.1 200 OK");
+        let (_, body) = http(address, "POST", "/eval", r#"{"session": "s", "code": "x"}"#);
+        let response: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(response["ok"], false);
+
+        assert_eq!(
+            http(address, "GET", "/eval", "").0,
+            "HTTP/1.1 405 Method Not Allowed"
+        );
+        assert_eq!(
+            http(address, "POST", "/nope", "").0,
+            "HTTP/1.1 404 Not Found"
+        );
+        assert_eq!(
+            http(address, "POST", "/eval", "{").0,
+            "HTTP/1.1 400 Bad Request"
+        );
+    }
+
+    /// Serve `sessions` over HTTP on a free local port
+    fn serve_local(sessions: Sessions, limits: HttpLimits) -> std::net::SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || serve_http(Arc::new(sessions), listener, limits));
+        address
+    }
+
+    fn http_eval(address: std::net::SocketAddr, session: &str) -> (String, String) {
+        let body = format!(r#"{{"session": "{}", "code": "1"}}"#, session);
+        http(address, "POST", "/eval", &body)
+    }
+
+    /// Send raw bytes and return the response's status line
+    fn raw_status(address: std::net::SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        // The server may answer and close before taking all of an oversized request
+        let _ = stream.write_all(request);
+        let mut response = String::new();
+        let _ = stream.read_to_string(&mut response);
+        response.lines().next().unwrap_or_default().to_string()
+    }
+
+    #[test]
+    fn test_http_rejects_oversized_and_slow_requests() {
+        let limits = HttpLimits {
+            max_connections: 1,
+            io_timeout: Duration::from_millis(300),
+        };
+        let address = serve_local(Sessions::new(Interpreter::new), limits);
+        let too_large = "HTTP/1.1 431 Request Header Fields Too Large";
+
+        let long_path = format!("POST /{} HTTP/1.1\r\n\r\n", "a".repeat(MAX_HTTP_LINE));
+        assert_eq!(raw_status(address, long_path.as_bytes()), too_large);
+        let long_header = format!(
+            "POST /eval HTTP/1.1\r\nX: {}\r\n\r\n",
+            "a".repeat(MAX_HTTP_LINE)
+        );
+        assert_eq!(raw_status(address, long_header.as_bytes()), too_large);
+        let many_headers = format!(
+            "POST /eval HTTP/1.1\r\n{}\r\n",
+            "X: 1\r\n".repeat(MAX_HTTP_HEADERS + 1)
+        );
+        assert_eq!(raw_status(address, many_headers.as_bytes()), too_large);
+
+        // A client that never finishes its request gets 408 and gives back its slot.
+        // The connections above free their slot just after closing, so retry
+        // while the server is still turning clients away.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let (response, waited) = loop {
+            let started = Instant::now();
+            let mut idle = TcpStream::connect(address).unwrap();
+            idle.write_all(b"POST /eval HTTP/1.1\r\n").unwrap();
+            let mut response = String::new();
+            idle.read_to_string(&mut response).unwrap();
+            if !response.starts_with("HTTP/1.1 503") {
+                break (response, started.elapsed());
+            }
+            assert!(Instant::now() < deadline, "idle client got no slot");
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert!(
+            response.starts_with("HTTP/1.1 408 Request Timeout"),
+            "{}",
+            response
+        );
+        assert!(waited >= Duration::from_millis(300));
+
+        while http_eval(address, "a").0 != "HTTP/1.1 200 OK" {
+            assert!(Instant::now() < deadline, "slot was not released");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    #[test]
+    fn test_http_limits_answer_503() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let address = serve_local(
+            Sessions::new(Interpreter::new).with_max_sessions(1),
+            HttpLimits::default(),
+        );
+        assert_eq!(http_eval(address, "a").0, "HTTP/1.1 200 OK");
+        let (status, body) = http_eval(address, "b");
+        assert_eq!(status, "HTTP/1.1 503 Service Unavailable");
+        assert!(body.contains("too many sessions"));
+
+        // A client holding the only connection slot turns the next one away.
+        // A handler frees its slot just after closing its socket, so the idle
+        // connection may itself be turned away at first; try again until it holds.
+        let limits = HttpLimits {
+            max_connections: 1,
+            ..HttpLimits::default()
+        };
+        let address = serve_local(Sessions::new(Interpreter::new), limits);
+        loop {
+            let idle = TcpStream::connect(address).unwrap();
+            let (status, body) = http_eval(address, "a");
+            if status == "HTTP/1.1 503 Service Unavailable" {
+                assert!(body.contains("too many connections"));
+                break;
+            }
+            drop(idle);
+            assert!(Instant::now() < deadline, "idle client got no slot");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        // The slot is free again once that connection closes
+        while http_eval(address, "a").0 != "HTTP/1.1 200 OK" {
+            assert!(Instant::now() < deadline, "slot was not released");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
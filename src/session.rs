@@ -0,0 +1,278 @@
+// ABOUTME: Save and restore the data bindings of a global environment
+// Used by save-session/load-session and Interpreter::save_session/restore_session
+
+use crate::env::Environment;
+use crate::value::{ErrorValue, Value, DEFAULT_ERROR_KIND};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Format version written to session files
+const SESSION_VERSION: u32 = 1;
+
+/// A value in a session file, tagged with its type so it restores exactly
+///
+/// Functions, macros, promises, threads and channels have no saved form.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Saved {
+    Number(f64),
+    String(String),
+    Bool(bool),
+    Symbol(String),
+    Keyword(String),
+    Nil,
+    Error(SavedError),
+    List(Vec<Saved>),
+    Map(BTreeMap<String, Saved>),
+}
+
+impl Saved {
+    /// The saved form of a value, or None if it (or anything inside it) cannot be saved
+    fn from_value(value: &Value) -> Option<Saved> {
+        Some(match value {
+            // JSON has no NaN or infinity
+            Value::Number(n) if n.is_finite() => Saved::Number(*n),
+            Value::String(s) => Saved::String(s.to_string()),
+            Value::Bool(b) => Saved::Bool(*b),
+            Value::Symbol(s) => Saved::Symbol(s.to_string()),
+            Value::Keyword(k) => Saved::Keyword(k.clone()),
+            Value::Nil => Saved::Nil,
+            Value::Error(err) => Saved::Error(SavedError::from_error(err)?),
+            Value::List(items) => {
+                Saved::List(items.iter().map(Saved::from_value).collect::<Option<_>>()?)
+            }
+            Value::Map(map) => Saved::Map(
+                map.iter()
+                    .map(|(k, v)| Some((k.clone(), Saved::from_value(v)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            _ => return None,
+        })
+    }
+
+    fn into_value(self) -> Value {
+        match self {
+            Saved::Number(n) => Value::Number(n),
+            Saved::String(s) => Value::String(s.into()),
+            Saved::Bool(b) => Value::Bool(b),
+            Saved::Symbol(s) => Value::Symbol(s.into()),
+            Saved::Keyword(k) => Value::Keyword(k),
+            Saved::Nil => Value::Nil,
+            Saved::Error(err) => Value::Error(Box::new(err.into_error())),
+            Saved::List(items) => Value::List(items.into_iter().map(Saved::into_value).collect()),
+            Saved::Map(map) => Value::Map(
+                map.into_iter()
+                    .map(|(k, v)| (k, v.into_value()))
+                    .collect::<BTreeMap<_, _>>(),
+            ),
+        }
+    }
+}
+
+/// An error value; plain errors keep the bare-message form older session files used
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+enum SavedError {
+    Message(String),
+    Structured {
+        kind: String,
+        message: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        data: Option<BTreeMap<String, Saved>>,
+    },
+}
+
+impl SavedError {
+    fn from_error(err: &ErrorValue) -> Option<SavedError> {
+        if err.kind == DEFAULT_ERROR_KIND && err.data.is_none() {
+            return Some(SavedError::Message(err.message.clone()));
+        }
+        let data = match &err.data {
+            Some(map) => Some(
+                map.iter()
+                    .map(|(k, v)| Some((k.clone(), Saved::from_value(v)?)))
+                    .collect::<Option<_>>()?,
+            ),
+            None => None,
+        };
+        Some(SavedError::Structured {
+            kind: err.kind.clone(),
+            message: err.message.clone(),
+            data,
+        })
+    }
+
+    fn into_error(self) -> ErrorValue {
+        match self {
+            SavedError::Message(message) => ErrorValue::new(DEFAULT_ERROR_KIND, message),
+            SavedError::Structured {
+                kind,
+                message,
+                data,
+            } => ErrorValue {
+                kind,
+                message,
+                data: data.map(|map| map.into_iter().map(|(k, v)| (k, v.into_value())).collect()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SessionFile {
+    version: u32,
+    bindings: BTreeMap<String, Saved>,
+}
+
+/// Serialize the data bindings of `env`'s own scope as JSON
+///
+/// Returns the JSON text and the number of bindings saved. Bindings whose
+/// values cannot be saved (functions, macros, promises, threads, channels, or
+/// data containing them) are left out.
+pub fn save(env: &Environment) -> (String, usize) {
+    let bindings: BTreeMap<_, _> = env
+        .bindings()
+        .into_iter()
+        .filter_map(|(name, value)| Some((name.to_string(), Saved::from_value(&value)?)))
+        .collect();
+    let count = bindings.len();
+    let file = SessionFile {
+        version: SESSION_VERSION,
+        bindings,
+    };
+    let json = serde_json::to_string_pretty(&file).expect("saved values serialize to JSON");
+    (json, count)
+}
+
+/// Define every binding from a session saved by [`save`] in `env`, returning how many
+///
+/// Nothing is defined if the session would replace a frozen binding.
+pub fn restore(env: &Environment, json: &str) -> Result<usize, String> {
+    let file: SessionFile =
+        serde_json::from_str(json).map_err(|e| format!("invalid session file: {}", e))?;
+    if file.version != SESSION_VERSION {
+        return Err(format!(
+            "unsupported session file version {} (expected {})",
+            file.version, SESSION_VERSION
+        ));
+    }
+
+    if let Some(message) = file
+        .bindings
+        .keys()
+        .find_map(|name| env.frozen_message("replace", name))
+    {
+        return Err(message);
+    }
+
+    let count = file.bindings.len();
+    for (name, saved) in file.bindings {
+        env.define(name, saved.into_value());
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_round_trip_keeps_types() {
+        let env = Environment::new();
+        let mut map = BTreeMap::new();
+        map.insert("tag".to_string(), Value::Keyword("done".to_string()));
+        env.define("n", Value::Number(1.5));
+        env.define(
+            "items",
+            Value::List(
+                vec![
+                    Value::String("a\n\"b\"".into()),
+                    Value::Symbol("sym".into()),
+                    Value::Bool(false),
+                    Value::Nil,
+                ]
+                .into(),
+            ),
+        );
+        env.define("m", Value::Map(map));
+
+        let (json, count) = save(&env);
+        assert_eq!(count, 3);
+
+        let restored = Environment::new();
+        assert_eq!(restore(&restored, &json), Ok(3));
+        assert_eq!(restored.get("n").unwrap().to_string(), "1.5");
+        assert_eq!(
+            restored.get("items").unwrap().to_string(),
+            r#"("a\n\"b\"" sym #f nil)"#
+        );
+        assert!(matches!(
+            restored.get("m"),
+            Some(Value::Map(m)) if matches!(&m["tag"], Value::Keyword(k) if k == "done")
+        ));
+    }
+
+    #[test]
+    fn test_functions_are_skipped() {
+        let env = Environment::new();
+        env.define("f", Value::BuiltIn(|_| Ok(Value::Nil)));
+        env.define(
+            "g",
+            Value::Lambda {
+                params: vec![],
+                defaults: vec![],
+                body: Arc::new(Value::Nil),
+                env: Environment::new(),
+                docstring: None,
+                source: None,
+            },
+        );
+        env.define(
+            "nested",
+            Value::List(vec![Value::BuiltIn(|_| Ok(Value::Nil))].into()),
+        );
+        env.define("nan", Value::Number(f64::NAN));
+        env.define("kept", Value::Number(1.0));
+
+        let (json, count) = save(&env);
+        assert_eq!(count, 1);
+        assert!(json.contains("\"kept\""));
+    }
+
+    #[test]
+    fn test_error_values_round_trip() {
+        let env = Environment::new();
+        let mut data = BTreeMap::new();
+        data.insert("path".to_string(), Value::String("x".into()));
+        env.define("plain", Value::error("boom"));
+        env.define(
+            "kinded",
+            Value::Error(Box::new(
+                ErrorValue::new("not-found", "gone").with_data(data),
+            )),
+        );
+
+        let (json, _) = save(&env);
+        assert!(json.contains(r#""error": "boom""#));
+
+        let restored = Environment::new();
+        assert_eq!(restore(&restored, &json), Ok(2));
+        assert_eq!(restored.get("plain").unwrap().to_string(), "#<error: boom>");
+        match restored.get("kinded") {
+            Some(Value::Error(err)) => {
+                assert_eq!(err.kind, "not-found");
+                assert_eq!(err.message, "gone");
+                assert!(err.data.as_ref().unwrap().contains_key("path"));
+            }
+            other => panic!("expected error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let env = Environment::new();
+        assert!(restore(&env, "not json").is_err());
+        assert!(restore(&env, r#"{"version": 99, "bindings": {}}"#).is_err());
+    }
+}
@@ -0,0 +1,579 @@
+//! Exact decimal arithmetic
+//!
+//! - `decimal:parse`: Make a decimal from a string or a number
+//! - `decimal:add`, `decimal:sub`, `decimal:mul`, `decimal:div`: Arithmetic
+//! - `decimal:round`: Round to a number of places with a rounding mode
+//! - `decimal:compare`: Order two decimals
+//! - `decimal:to-string`, `decimal:to-number`: Convert back
+//!
+//! Numbers are f64, so `(+ 0.1 0.2)` is 0.30000000000000004 and cents drift
+//! when prices are summed. Decimals are arbitrary-precision and base 10:
+//! sums and products are exact, and only division and `decimal:round` round.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_AT_LEAST_ONE, ARITY_ONE, ARITY_TWO, ARITY_TWO_OR_THREE};
+use crate::help::HelpEntry;
+use crate::value::Value;
+use bigdecimal::{BigDecimal, RoundingMode, ToPrimitive, Zero};
+use std::cmp::Ordering;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Most digits, and most decimal places, a decimal may have
+///
+/// Keeps a script from exhausting memory by squaring a value in a loop or
+/// parsing "1e999999999".
+const MAX_DIGITS: u64 = 1000;
+
+/// Fail if `d` grew past [`MAX_DIGITS`]
+fn check_size(function: &str, d: &BigDecimal) -> Result<(), EvalError> {
+    if d.digits() > MAX_DIGITS || d.fractional_digit_count().unsigned_abs() > MAX_DIGITS {
+        return Err(EvalError::runtime_error(
+            function,
+            format!("result has more than {} digits", MAX_DIGITS),
+        ));
+    }
+    Ok(())
+}
+
+fn decimal(function: &str, d: BigDecimal) -> Result<Value, EvalError> {
+    check_size(function, &d)?;
+    Ok(Value::Decimal(Arc::new(d)))
+}
+
+fn parse(function: &str, text: &str) -> Result<BigDecimal, EvalError> {
+    let d = BigDecimal::from_str(text.trim()).map_err(|_| {
+        EvalError::runtime_error(function, format!("{:?} is not a decimal number", text))
+    })?;
+    check_size(function, &d)?;
+    Ok(d)
+}
+
+/// Read a decimal operand; numbers convert through their shortest printed
+/// form, so 0.1 becomes exactly 0.1
+fn operand(function: &str, value: &Value, position: usize) -> Result<BigDecimal, EvalError> {
+    match value {
+        Value::Decimal(d) => Ok((**d).clone()),
+        Value::Number(n) if n.is_finite() => parse(function, &n.to_string()),
+        other => Err(EvalError::type_error(
+            function,
+            "decimal or number",
+            other,
+            position,
+        )),
+    }
+}
+
+fn rounding_mode(function: &str, value: &Value) -> Result<RoundingMode, EvalError> {
+    let name = match value {
+        Value::Keyword(name) => name.as_str(),
+        Value::String(name) => name,
+        other => return Err(EvalError::type_error(function, "keyword", other, 3)),
+    };
+    Ok(match name {
+        "up" => RoundingMode::Up,
+        "down" => RoundingMode::Down,
+        "ceiling" => RoundingMode::Ceiling,
+        "floor" => RoundingMode::Floor,
+        "half-up" => RoundingMode::HalfUp,
+        "half-down" => RoundingMode::HalfDown,
+        "half-even" => RoundingMode::HalfEven,
+        _ => {
+            return Err(EvalError::runtime_error(
+                function,
+                format!(
+                    "unknown rounding mode :{}; expected :up, :down, :ceiling, :floor, :half-up, :half-down or :half-even",
+                    name
+                ),
+            ))
+        }
+    })
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// decimal:parse - Make a decimal from a string or a number
+fn decimal_parse(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "decimal:parse",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    match &args[0] {
+        Value::String(text) => decimal("decimal:parse", parse("decimal:parse", text)?),
+        other => decimal("decimal:parse", operand("decimal:parse", other, 1)?),
+    }
+}
+
+/// Fold variadic decimal arguments with `op`
+fn fold(
+    function: &str,
+    args: &[Value],
+    op: fn(BigDecimal, BigDecimal) -> BigDecimal,
+) -> Result<Value, EvalError> {
+    if args.is_empty() {
+        return Err(EvalError::arity_error(function, ARITY_AT_LEAST_ONE, 0));
+    }
+    let mut result = operand(function, &args[0], 1)?;
+    for (i, arg) in args.iter().enumerate().skip(1) {
+        result = op(result, operand(function, arg, i + 1)?);
+        check_size(function, &result)?;
+    }
+    decimal(function, result)
+}
+
+/// decimal:add - Exact sum
+fn decimal_add(args: &[Value]) -> Result<Value, EvalError> {
+    fold("decimal:add", args, |a, b| a + b)
+}
+
+/// decimal:mul - Exact product
+fn decimal_mul(args: &[Value]) -> Result<Value, EvalError> {
+    fold("decimal:mul", args, |a, b| a * b)
+}
+
+/// decimal:sub - Exact difference
+fn decimal_sub(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("decimal:sub", ARITY_TWO, args.len()));
+    }
+    let a = operand("decimal:sub", &args[0], 1)?;
+    let b = operand("decimal:sub", &args[1], 2)?;
+    decimal("decimal:sub", a - b)
+}
+
+/// decimal:div - Quotient to 100 significant digits
+fn decimal_div(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("decimal:div", ARITY_TWO, args.len()));
+    }
+    let a = operand("decimal:div", &args[0], 1)?;
+    let b = operand("decimal:div", &args[1], 2)?;
+    if b.is_zero() {
+        return Err(EvalError::runtime_error("decimal:div", "division by zero"));
+    }
+    decimal("decimal:div", a / b)
+}
+
+/// decimal:round - Round to a number of decimal places
+fn decimal_round(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EvalError::arity_error(
+            "decimal:round",
+            ARITY_TWO_OR_THREE,
+            args.len(),
+        ));
+    }
+    let d = operand("decimal:round", &args[0], 1)?;
+    let places = match &args[1] {
+        Value::Number(n) if n.fract() == 0.0 && n.abs() <= MAX_DIGITS as f64 => *n as i64,
+        other => {
+            return Err(EvalError::type_error(
+                "decimal:round",
+                "integer number of places",
+                other,
+                2,
+            ))
+        }
+    };
+    let mode = match args.get(2) {
+        None => RoundingMode::HalfEven,
+        Some(mode) => rounding_mode("decimal:round", mode)?,
+    };
+    decimal("decimal:round", d.with_scale_round(places, mode))
+}
+
+/// decimal:compare - -1, 0 or 1 as the first decimal is less than, equal to or greater than the second
+fn decimal_compare(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "decimal:compare",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+    let a = operand("decimal:compare", &args[0], 1)?;
+    let b = operand("decimal:compare", &args[1], 2)?;
+    Ok(Value::Number(match a.cmp(&b) {
+        Ordering::Less => -1.0,
+        Ordering::Equal => 0.0,
+        Ordering::Greater => 1.0,
+    }))
+}
+
+/// decimal:to-string - The decimal written out in full, without an exponent
+fn decimal_to_string(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "decimal:to-string",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let d = operand("decimal:to-string", &args[0], 1)?;
+    Ok(Value::String(d.to_plain_string().into()))
+}
+
+/// decimal:to-number - The nearest f64
+fn decimal_to_number(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "decimal:to-number",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let d = operand("decimal:to-number", &args[0], 1)?;
+    Ok(Value::Number(d.to_f64().unwrap_or(f64::NAN)))
+}
+
+/// Register decimal functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("decimal:parse".to_string(), Value::BuiltIn(decimal_parse));
+    env.define("decimal:add".to_string(), Value::BuiltIn(decimal_add));
+    env.define("decimal:sub".to_string(), Value::BuiltIn(decimal_sub));
+    env.define("decimal:mul".to_string(), Value::BuiltIn(decimal_mul));
+    env.define("decimal:div".to_string(), Value::BuiltIn(decimal_div));
+    env.define("decimal:round".to_string(), Value::BuiltIn(decimal_round));
+    env.define(
+        "decimal:compare".to_string(),
+        Value::BuiltIn(decimal_compare),
+    );
+    env.define(
+        "decimal:to-string".to_string(),
+        Value::BuiltIn(decimal_to_string),
+    );
+    env.define(
+        "decimal:to-number".to_string(),
+        Value::BuiltIn(decimal_to_number),
+    );
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:parse".to_string(),
+        signature: "(decimal:parse text-or-number)".to_string(),
+        description: "Make an exact decimal, for money and other base-10 amounts.
+
+**Parameters:**
+- text-or-number: A string such as \"19.99\" or \"-1.5e3\", or a number
+
+**Returns:** A decimal, which keeps the places it was written with
+
+**Examples:**
+```lisp
+(decimal:parse \"19.990\")
+=> 19.990
+
+(decimal:parse 0.1)
+=> 0.1
+```
+
+**Error Conditions:**
+- The string is not a decimal number
+- More than 1000 digits or decimal places
+
+**Notes:** A number converts through its shortest printed form, so 0.1
+becomes exactly 0.1. Prefer strings for amounts read from data."
+            .to_string(),
+        examples: vec![
+            "(decimal:parse \"19.99\") => 19.99".to_string(),
+            "(decimal:parse 3) => 3".to_string(),
+        ],
+        related: vec!["decimal:add".to_string(), "decimal:to-string".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:add".to_string(),
+        signature: "(decimal:add a b ...)".to_string(),
+        description: "Add decimals exactly.
+
+**Parameters:**
+- a, b, ...: Decimals, or numbers to convert as decimal:parse does
+
+**Returns:** The exact sum as a decimal
+
+**Examples:**
+```lisp
+(decimal:add (decimal:parse \"0.1\") (decimal:parse \"0.2\"))
+=> 0.3
+
+(decimal:add (decimal:parse \"19.99\") 5)
+=> 24.99
+```"
+        .to_string(),
+        examples: vec!["(decimal:add (decimal:parse \"0.1\") 0.2) => 0.3".to_string()],
+        related: vec!["decimal:sub".to_string(), "decimal:mul".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:sub".to_string(),
+        signature: "(decimal:sub a b)".to_string(),
+        description: "Subtract one decimal from another exactly.
+
+**Parameters:**
+- a: A decimal or number
+- b: The decimal or number to subtract
+
+**Returns:** The exact difference as a decimal
+
+**Examples:**
+```lisp
+(decimal:sub (decimal:parse \"10.00\") (decimal:parse \"0.01\"))
+=> 9.99
+```"
+        .to_string(),
+        examples: vec!["(decimal:sub (decimal:parse \"1\") 0.9) => 0.1".to_string()],
+        related: vec!["decimal:add".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:mul".to_string(),
+        signature: "(decimal:mul a b ...)".to_string(),
+        description: "Multiply decimals exactly.
+
+**Parameters:**
+- a, b, ...: Decimals, or numbers to convert as decimal:parse does
+
+**Returns:** The exact product as a decimal; its places are the sum of the
+operands' places
+
+**Examples:**
+```lisp
+(decimal:mul (decimal:parse \"19.99\") 3)
+=> 59.97
+
+(decimal:round (decimal:mul (decimal:parse \"59.97\") (decimal:parse \"0.0825\")) 2)
+=> 4.95
+```
+
+**Error Conditions:**
+- The product has more than 1000 digits"
+            .to_string(),
+        examples: vec!["(decimal:mul (decimal:parse \"19.99\") 3) => 59.97".to_string()],
+        related: vec!["decimal:div".to_string(), "decimal:round".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:div".to_string(),
+        signature: "(decimal:div a b)".to_string(),
+        description: "Divide decimals.
+
+**Parameters:**
+- a: The dividend, a decimal or number
+- b: The divisor, a decimal or number
+
+**Returns:** The quotient as a decimal, exact when it terminates and rounded
+to 100 significant digits when it does not
+
+**Examples:**
+```lisp
+(decimal:div (decimal:parse \"10\") 4)
+=> 2.5
+
+(decimal:round (decimal:div (decimal:parse \"100\") 3) 2)
+=> 33.33
+```
+
+**Error Conditions:**
+- Division by zero
+
+**Notes:** Round the quotient with decimal:round before showing or storing it."
+            .to_string(),
+        examples: vec!["(decimal:div (decimal:parse \"10\") 4) => 2.5".to_string()],
+        related: vec!["decimal:round".to_string(), "decimal:mul".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:round".to_string(),
+        signature: "(decimal:round d places [mode])".to_string(),
+        description: "Round a decimal to a number of decimal places.
+
+**Parameters:**
+- d: A decimal or number
+- places: Decimal places to keep; negative rounds to tens, hundreds, ...
+- mode: How to round (optional, :half-even by default):
+  - :half-even - to the nearest, ties to the even digit (banker's rounding)
+  - :half-up - to the nearest, ties away from zero
+  - :half-down - to the nearest, ties toward zero
+  - :up / :down - away from / toward zero
+  - :ceiling / :floor - toward positive / negative infinity
+
+**Returns:** A decimal with exactly `places` decimal places
+
+**Examples:**
+```lisp
+(decimal:round (decimal:parse \"2.345\") 2)
+=> 2.34
+
+(decimal:round (decimal:parse \"2.345\") 2 :half-up)
+=> 2.35
+
+(decimal:round (decimal:parse \"5\") 2)
+=> 5.00
+```
+
+**Error Conditions:**
+- An unknown rounding mode"
+            .to_string(),
+        examples: vec![
+            "(decimal:round (decimal:parse \"2.345\") 2) => 2.34".to_string(),
+            "(decimal:round (decimal:parse \"2.345\") 2 :half-up) => 2.35".to_string(),
+        ],
+        related: vec!["decimal:div".to_string(), "decimal:to-string".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:compare".to_string(),
+        signature: "(decimal:compare a b)".to_string(),
+        description: "Compare two decimals by value.
+
+**Parameters:**
+- a, b: Decimals or numbers
+
+**Returns:** -1 if a is less than b, 0 if they are equal, 1 if a is greater
+
+**Examples:**
+```lisp
+(decimal:compare (decimal:parse \"1.50\") (decimal:parse \"1.5\"))
+=> 0
+
+(< (decimal:compare balance price) 0)  ; can't afford it
+```
+
+**Notes:** Places don't matter: 1.50 and 1.5 are equal, here and for =."
+            .to_string(),
+        examples: vec!["(decimal:compare (decimal:parse \"2\") 3) => -1".to_string()],
+        related: vec!["=".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:to-string".to_string(),
+        signature: "(decimal:to-string d)".to_string(),
+        description: "Write a decimal out in full, without an exponent.
+
+**Parameters:**
+- d: A decimal
+
+**Returns:** A string, keeping trailing zeros
+
+**Examples:**
+```lisp
+(decimal:to-string (decimal:round (decimal:parse \"4.5\") 2))
+=> \"4.50\"
+```
+
+**Notes:** json:encode also writes decimals as strings, so no precision is
+lost on the way to an API."
+            .to_string(),
+        examples: vec!["(decimal:to-string (decimal:parse \"1e3\")) => \"1000\"".to_string()],
+        related: vec!["decimal:round".to_string(), "decimal:to-number".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "decimal:to-number".to_string(),
+        signature: "(decimal:to-number d)".to_string(),
+        description: "Convert a decimal to the nearest number.
+
+**Parameters:**
+- d: A decimal
+
+**Returns:** A number, which may not be exact
+
+**Examples:**
+```lisp
+(decimal:to-number (decimal:parse \"19.99\"))
+=> 19.99
+```"
+        .to_string(),
+        examples: vec!["(decimal:to-number (decimal:parse \"0.5\")) => 0.5".to_string()],
+        related: vec!["decimal:parse".to_string()],
+        category: "Standard Library: Decimal".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn d(text: &str) -> Value {
+        decimal_parse(&[Value::String(text.into())]).unwrap()
+    }
+
+    fn text(result: Result<Value, EvalError>) -> String {
+        result.unwrap().to_string()
+    }
+
+    #[test]
+    fn test_exact_arithmetic() {
+        assert_eq!(text(decimal_add(&[d("0.1"), d("0.2")])), "0.3");
+        assert_eq!(text(decimal_add(&[d("0.1"), Value::Number(0.2)])), "0.3");
+        assert_eq!(text(decimal_sub(&[d("10.00"), d("0.01")])), "9.99");
+        assert_eq!(
+            text(decimal_mul(&[d("19.99"), Value::Number(3.0)])),
+            "59.97"
+        );
+        assert_eq!(text(decimal_div(&[d("10"), Value::Number(4.0)])), "2.5");
+        assert!(decimal_div(&[d("1"), d("0")]).is_err());
+        assert!(decimal_add(&[d("1"), Value::String("2".into())]).is_err());
+    }
+
+    #[test]
+    fn test_rounding_modes() {
+        let round = |value: &str, mode: Option<&str>| {
+            let mut args = vec![d(value), Value::Number(2.0)];
+            args.extend(mode.map(|m| Value::Keyword(m.into())));
+            text(decimal_round(&args))
+        };
+        assert_eq!(round("2.345", None), "2.34");
+        assert_eq!(round("2.355", None), "2.36");
+        assert_eq!(round("2.345", Some("half-up")), "2.35");
+        assert_eq!(round("2.345", Some("half-down")), "2.34");
+        assert_eq!(round("-2.341", Some("floor")), "-2.35");
+        assert_eq!(round("2.341", Some("ceiling")), "2.35");
+        assert_eq!(round("5", None), "5.00");
+        assert!(
+            decimal_round(&[d("1"), Value::Number(2.0), Value::Keyword("nearest".into())]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_compare_and_convert() {
+        assert!(decimal_parse(&[Value::String("12abc".into())]).is_err());
+        assert!(decimal_parse(&[Value::String("1e999999999".into())]).is_err());
+        assert_eq!(text(decimal_parse(&[Value::Number(0.1)])), "0.1");
+        assert!(matches!(
+            decimal_to_string(&[d("1e3")]),
+            Ok(Value::String(s)) if s == "1000".into()
+        ));
+        assert_eq!(text(decimal_compare(&[d("1.50"), d("1.5")])), "0");
+        assert_eq!(text(decimal_compare(&[d("2"), Value::Number(3.0)])), "-1");
+        assert!(crate::value::values_equal(&d("1.50"), &d("1.5")));
+        assert!(matches!(
+            decimal_to_number(&[d("19.99")]),
+            Ok(Value::Number(n)) if n == 19.99
+        ));
+    }
+
+    #[test]
+    fn test_growth_is_bounded() {
+        let mut big = d("99999999999999999999");
+        let result = loop {
+            match decimal_mul(&[big.clone(), big.clone()]) {
+                Ok(next) => big = next,
+                Err(e) => break e,
+            }
+        };
+        assert!(result.to_string().contains("1000 digits"), "{}", result);
+    }
+}
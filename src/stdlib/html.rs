@@ -0,0 +1,421 @@
+//! HTML parsing and querying
+//!
+//! - `html:parse`: Parse an HTML document into nested element maps
+//! - `html:select`: Find the elements matching a CSS selector
+//! - `html:text`: The text inside an element, without tags
+//! - `html:attr`: An attribute of an element
+//!
+//! Elements are plain maps, `{:tag "a" :attrs {:href "/x"} :children (...)}`,
+//! whose children are element maps and strings of text, so the usual list and
+//! map functions work on them. Functions that take an element also take an
+//! HTML string, which they parse first.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_TWO};
+use crate::help::HelpEntry;
+use crate::value::Value;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::collections::BTreeMap;
+
+/// Elements that never have children or a closing tag
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+
+/// Convert a parsed element and everything inside it to an element map
+fn element_to_value(element: ElementRef) -> Value {
+    let attrs = element
+        .value()
+        .attrs()
+        .map(|(name, value)| (name.to_string(), Value::String(value.into())))
+        .collect();
+    let children = element
+        .children()
+        .filter_map(|child| match child.value() {
+            Node::Text(text) => Some(Value::String(text.to_string().into())),
+            Node::Element(_) => ElementRef::wrap(child).map(element_to_value),
+            _ => None,
+        })
+        .collect();
+
+    let mut map = BTreeMap::new();
+    map.insert(
+        "tag".to_string(),
+        Value::String(element.value().name().into()),
+    );
+    map.insert("attrs".to_string(), Value::Map(attrs));
+    map.insert("children".to_string(), Value::List(children));
+    Value::Map(map)
+}
+
+/// An element map's tag, attributes and children
+type ElementParts<'a> = (&'a str, Option<&'a BTreeMap<String, Value>>, &'a [Value]);
+
+/// The parts of an element map, or `None` if `value` is not one
+fn element_parts(value: &Value) -> Option<ElementParts<'_>> {
+    let Value::Map(map) = value else {
+        return None;
+    };
+    let Some(Value::String(tag)) = map.get("tag") else {
+        return None;
+    };
+    let attrs = match map.get("attrs") {
+        Some(Value::Map(attrs)) => Some(attrs),
+        _ => None,
+    };
+    let children = match map.get("children") {
+        Some(Value::List(children)) => &children[..],
+        _ => &[],
+    };
+    Some((tag, attrs, children))
+}
+
+/// Write an element map back out as HTML
+fn write_html(value: &Value, out: &mut String) {
+    match value {
+        Value::String(text) => out.push_str(&escape(text, false)),
+        value => {
+            let Some((tag, attrs, children)) = element_parts(value) else {
+                return;
+            };
+            out.push('<');
+            out.push_str(tag);
+            if let Some(attrs) = attrs {
+                for (name, attr) in attrs {
+                    if let Value::String(attr) = attr {
+                        out.push_str(&format!(" {}=\"{}\"", name, escape(attr, true)));
+                    }
+                }
+            }
+            out.push('>');
+            if VOID_ELEMENTS.contains(&tag) {
+                return;
+            }
+            for child in children {
+                write_html(child, out);
+            }
+            out.push_str(&format!("</{}>", tag));
+        }
+    }
+}
+
+fn escape(text: &str, attribute: bool) -> String {
+    let text = text
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;");
+    if attribute {
+        text.replace('"', "&quot;")
+    } else {
+        text
+    }
+}
+
+/// The document an HTML string or element map stands for
+fn document(function: &str, value: &Value, position: usize) -> Result<Html, EvalError> {
+    match value {
+        Value::String(html) => Ok(Html::parse_document(html)),
+        value => match element_parts(value) {
+            Some((tag, _, _)) => {
+                let mut html = String::new();
+                write_html(value, &mut html);
+                if tag == "html" {
+                    Ok(Html::parse_document(&html))
+                } else {
+                    Ok(Html::parse_fragment(&html))
+                }
+            }
+            None => Err(EvalError::type_error(
+                function,
+                "HTML string or element",
+                value,
+                position,
+            )),
+        },
+    }
+}
+
+/// Concatenate the text inside an element map
+fn collect_text(value: &Value, out: &mut String) {
+    match value {
+        Value::String(text) => out.push_str(text),
+        value => {
+            if let Some((_, _, children)) = element_parts(value) {
+                for child in children {
+                    collect_text(child, out);
+                }
+            }
+        }
+    }
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// html:parse - Parse an HTML document into element maps
+fn html_parse(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("html:parse", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(html) => Ok(element_to_value(Html::parse_document(html).root_element())),
+        other => Err(EvalError::type_error("html:parse", "string", other, 1)),
+    }
+}
+
+/// html:select - Elements matching a CSS selector, in document order
+fn html_select(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("html:select", ARITY_TWO, args.len()));
+    }
+    let selector = match &args[1] {
+        Value::String(s) => Selector::parse(s).map_err(|e| {
+            EvalError::runtime_error("html:select", format!("invalid selector '{}': {}", s, e))
+        })?,
+        other => return Err(EvalError::type_error("html:select", "string", other, 2)),
+    };
+    let document = document("html:select", &args[0], 1)?;
+    Ok(Value::List(
+        document.select(&selector).map(element_to_value).collect(),
+    ))
+}
+
+/// html:text - The text inside an element, tags removed
+fn html_text(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("html:text", ARITY_ONE, args.len()));
+    }
+    let mut text = String::new();
+    match &args[0] {
+        Value::String(html) => {
+            let document = Html::parse_document(html);
+            text.extend(document.root_element().text());
+        }
+        value if element_parts(value).is_some() => collect_text(value, &mut text),
+        other => {
+            return Err(EvalError::type_error(
+                "html:text",
+                "HTML string or element",
+                other,
+                1,
+            ))
+        }
+    }
+    Ok(Value::String(text.into()))
+}
+
+/// html:attr - An attribute of an element, or nil
+fn html_attr(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("html:attr", ARITY_TWO, args.len()));
+    }
+    let name = match &args[1] {
+        Value::String(s) => s.to_ascii_lowercase(),
+        Value::Keyword(s) => s.to_ascii_lowercase(),
+        other => return Err(EvalError::type_error("html:attr", "string", other, 2)),
+    };
+    let element = match &args[0] {
+        Value::String(_) => {
+            // An HTML string means its first element, as written
+            let document = document("html:attr", &args[0], 1)?;
+            let body = Selector::parse("body > *").expect("valid selector");
+            let first = document.select(&body).next();
+            return Ok(first
+                .and_then(|element| element.value().attr(&name))
+                .map_or(Value::Nil, |value| Value::String(value.into())));
+        }
+        value => element_parts(value).ok_or_else(|| {
+            EvalError::type_error("html:attr", "HTML string or element", value, 1)
+        })?,
+    };
+    Ok(element
+        .1
+        .and_then(|attrs| attrs.get(&name))
+        .cloned()
+        .unwrap_or(Value::Nil))
+}
+
+/// Register HTML functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("html:parse".to_string(), Value::BuiltIn(html_parse));
+    env.define("html:select".to_string(), Value::BuiltIn(html_select));
+    env.define("html:text".to_string(), Value::BuiltIn(html_text));
+    env.define("html:attr".to_string(), Value::BuiltIn(html_attr));
+
+    crate::help::register_help(HelpEntry {
+        name: "html:parse".to_string(),
+        signature: "(html:parse html)".to_string(),
+        description: "Parse an HTML document into nested element maps.
+
+Each element becomes {:tag :attrs :children}: the tag name, a map of its
+attributes and a list of its children, which are element maps and strings of
+text. Parsing follows the HTML5 rules browsers use, so broken markup still
+parses and the result always has html, head and body elements.
+
+**Parameters:**
+- html: HTML string
+
+**Returns:** The map for the html element
+
+**Examples:**
+```lisp
+(html:parse \"<p class=\\\"x\\\">Hi</p>\")
+=> {:tag \"html\" :attrs {} :children ({:tag \"head\" ...} {:tag \"body\" ...})}
+
+(map-get (html:parse page) :tag)
+=> \"html\"
+```"
+        .to_string(),
+        examples: vec!["(map-get (html:parse \"<p>Hi</p>\") :tag) => \"html\"".to_string()],
+        related: vec!["html:select".to_string(), "html:text".to_string()],
+        category: "HTML".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "html:select".to_string(),
+        signature: "(html:select html selector)".to_string(),
+        description: "Find the elements matching a CSS selector.
+
+**Parameters:**
+- html: HTML string or element map (from html:parse or html:select)
+- selector: CSS selector, such as \"a[href]\", \"ul.items > li\" or \"#main p\"
+
+**Returns:** List of matching element maps, in document order
+
+**Examples:**
+```lisp
+(html:select \"<ul><li>a</li><li>b</li></ul>\" \"li\")
+=> ({:tag \"li\" :attrs {} :children (\"a\")} {:tag \"li\" :attrs {} :children (\"b\")})
+
+(map html:text (html:select page \"h2\"))
+=> (\"Intro\" \"Usage\")
+
+(map (lambda (a) (html:attr a \"href\")) (html:select page \"a[href]\"))
+```
+
+**Error Conditions:**
+- An invalid selector is an error"
+            .to_string(),
+        examples: vec![
+            "(map html:text (html:select \"<b>x</b><b>y</b>\" \"b\")) => (\"x\" \"y\")".to_string(),
+        ],
+        related: vec![
+            "html:parse".to_string(),
+            "html:text".to_string(),
+            "html:attr".to_string(),
+        ],
+        category: "HTML".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "html:text".to_string(),
+        signature: "(html:text html)".to_string(),
+        description: "The text inside an element, with its tags removed.
+
+**Parameters:**
+- html: HTML string or element map
+
+**Returns:** The concatenated text of every text node inside, as written
+(whitespace is not collapsed)
+
+**Examples:**
+```lisp
+(html:text \"<p>Hello <b>world</b></p>\")
+=> \"Hello world\"
+
+(map html:text (html:select page \"td\"))
+```"
+        .to_string(),
+        examples: vec!["(html:text \"<p>a <i>b</i></p>\") => \"a b\"".to_string()],
+        related: vec!["html:select".to_string(), "string-trim".to_string()],
+        category: "HTML".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "html:attr".to_string(),
+        signature: "(html:attr element name)".to_string(),
+        description: "An attribute of an element.
+
+**Parameters:**
+- element: Element map, or an HTML string (whose first element is used)
+- name: Attribute name, as a string or keyword
+
+**Returns:** The attribute's value, or nil if the element does not have it
+
+**Examples:**
+```lisp
+(html:attr \"<a href=\\\"/docs\\\">Docs</a>\" \"href\")
+=> \"/docs\"
+
+(map (lambda (img) (html:attr img :src)) (html:select page \"img\"))
+```"
+        .to_string(),
+        examples: vec!["(html:attr \"<a href=\\\"/x\\\">x</a>\" \"href\") => \"/x\"".to_string()],
+        related: vec!["html:select".to_string()],
+        category: "HTML".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAGE: &str = r#"<html><body>
+        <h1 id="top">Title</h1>
+        <ul class="items"><li><a href="/a">A &amp; B</a></li><li><a href="/c">C</a><br></li></ul>
+        </body></html>"#;
+
+    fn text(value: &Value) -> String {
+        match html_text(std::slice::from_ref(value)).unwrap() {
+            Value::String(s) => s.to_string(),
+            other => panic!("expected string, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_builds_element_maps() {
+        let root = html_parse(&[Value::String(PAGE.into())]).unwrap();
+        let (tag, _, children) = element_parts(&root).unwrap();
+        assert_eq!(tag, "html");
+        assert_eq!(children.len(), 2);
+        assert!(text(&root).contains("A & B"));
+    }
+
+    #[test]
+    fn test_select_text_and_attr() {
+        let page = Value::String(PAGE.into());
+        let links = html_select(&[page.clone(), Value::String("ul.items a".into())]).unwrap();
+        let Value::List(links) = links else {
+            panic!("expected a list");
+        };
+        let texts: Vec<String> = links.iter().map(text).collect();
+        assert_eq!(texts, vec!["A & B", "C"]);
+        let attr = |element: &Value, name: Value| html_attr(&[element.clone(), name]).unwrap();
+        assert!(
+            matches!(attr(&links[1], Value::Keyword("href".into())), Value::String(s) if &*s == "/c")
+        );
+        assert!(matches!(
+            attr(&links[1], Value::String("title".into())),
+            Value::Nil
+        ));
+        let anchor = Value::String(r#"<a href="/x">x</a>"#.into());
+        assert!(
+            matches!(attr(&anchor, Value::String("href".into())), Value::String(s) if &*s == "/x")
+        );
+
+        // Selecting inside an element map
+        let list = html_select(&[page, Value::String("ul".into())]).unwrap();
+        let Value::List(list) = list else {
+            panic!("expected a list");
+        };
+        let items = html_select(&[list[0].clone(), Value::String("li".into())]).unwrap();
+        assert!(matches!(items, Value::List(items) if items.len() == 2));
+    }
+
+    #[test]
+    fn test_invalid_selector_is_an_error() {
+        assert!(html_select(&[Value::String(PAGE.into()), Value::String("a[".into())]).is_err());
+    }
+}
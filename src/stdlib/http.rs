@@ -0,0 +1,840 @@
+//! Native HTTP helpers
+//!
+//! Complements the Lisp-level `http:` helpers in `stdlib/lisp/http.lisp` with
+//! functions that need native threads or streaming.
+//!
+//! - `http:get-many`: Fetch many URLs in parallel, returning responses in order
+//! - `http:with-retry`: Make a request, backing off and retrying on 429 and 5xx
+//! - `http:stream`: Make a request, calling a function with each chunk or server-sent event
+//!
+//! Every request goes through the sandbox, so the network allowlist applies to
+//! each URL exactly as it does for `http-request`.
+
+use crate::builtins::current_sandbox;
+use crate::builtins::network::{header_list, request_options, response_to_value};
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE_OR_TWO, ARITY_TWO_OR_THREE, ERR_SANDBOX_NOT_INIT};
+use crate::eval::{active_cancellation, apply_function};
+use crate::help::HelpEntry;
+use crate::sandbox::{RetryPolicy, Sandbox};
+use crate::value::{ErrorValue, Value};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+/// Requests in flight at once when `:parallel` is not given
+const DEFAULT_PARALLEL: usize = 4;
+
+/// Fetch several URLs with GET, running up to `:parallel` requests at a time
+pub fn http_get_many(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "http:get-many",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+
+    let urls = match &args[0] {
+        Value::List(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::String(url) => Ok(url.to_string()),
+                other => Err(EvalError::type_error(
+                    "http:get-many",
+                    "list of strings",
+                    other,
+                    1,
+                )),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        other => return Err(EvalError::type_error("http:get-many", "list", other, 1)),
+    };
+
+    let empty = std::collections::BTreeMap::new();
+    let options = match args.get(1) {
+        Some(Value::Map(m)) => m,
+        None => &empty,
+        Some(other) => return Err(EvalError::type_error("http:get-many", "map", other, 2)),
+    };
+
+    let parallel = match options.get("parallel") {
+        Some(Value::Number(n)) if *n >= 1.0 => *n as usize,
+        None => DEFAULT_PARALLEL,
+        Some(other) => {
+            return Err(EvalError::runtime_error(
+                "http:get-many",
+                format!(":parallel must be a number of at least 1, got {}", other),
+            ))
+        }
+    };
+    let timeout = match options.get("timeout") {
+        Some(Value::Number(t)) => Some(*t as u64),
+        None => None,
+        Some(other) => {
+            return Err(EvalError::runtime_error(
+                "http:get-many",
+                format!(":timeout must be a number, got {}", other),
+            ))
+        }
+    };
+    let headers = header_list("http:get-many", options.get("headers"))?;
+
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("http:get-many", ERR_SANDBOX_NOT_INIT))?;
+
+    Ok(Value::List(
+        fetch_all(&sandbox, &urls, parallel, headers.as_deref(), timeout).into(),
+    ))
+}
+
+/// Run the requests on a pool of scoped workers and collect results by index
+#[cfg(not(target_arch = "wasm32"))]
+fn fetch_all(
+    sandbox: &Arc<Sandbox>,
+    urls: &[String],
+    parallel: usize,
+    headers: Option<&[(String, String)]>,
+    timeout: Option<u64>,
+) -> Vec<Value> {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let next = AtomicUsize::new(0);
+    let mut results = vec![Value::Nil; urls.len()];
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..parallel.min(urls.len()))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut fetched = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(url) = urls.get(index) else {
+                            return fetched;
+                        };
+                        fetched.push((index, fetch_one(sandbox, url, headers, timeout)));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            // A panicking worker leaves its slots as nil
+            for (index, response) in worker.join().unwrap_or_default() {
+                results[index] = response;
+            }
+        }
+    });
+
+    results
+}
+
+/// Run the requests one after another; wasm32 cannot start threads
+#[cfg(target_arch = "wasm32")]
+fn fetch_all(
+    sandbox: &Arc<Sandbox>,
+    urls: &[String],
+    _parallel: usize,
+    headers: Option<&[(String, String)]>,
+    timeout: Option<u64>,
+) -> Vec<Value> {
+    urls.iter()
+        .map(|url| fetch_one(sandbox, url, headers, timeout))
+        .collect()
+}
+
+/// GET one URL, turning a failure into an error value
+fn fetch_one(
+    sandbox: &Sandbox,
+    url: &str,
+    headers: Option<&[(String, String)]>,
+    timeout: Option<u64>,
+) -> Value {
+    sandbox
+        .http_request(url, "GET", headers.map(<[_]>::to_vec), None, timeout)
+        .map(response_to_value)
+        .unwrap_or_else(|e| {
+            Value::Error(Box::new(ErrorValue::new(
+                e.kind(),
+                format!("HTTP GET request to '{}' failed: {}", url, e),
+            )))
+        })
+}
+
+/// Make a request like `http-request`, retrying 429 and 5xx answers with backoff
+pub fn http_with_retry(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "http:with-retry",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let url = match &args[0] {
+        Value::String(url) => url,
+        other => return Err(EvalError::type_error("http:with-retry", "string", other, 1)),
+    };
+    let empty = std::collections::BTreeMap::new();
+    let options = match args.get(1) {
+        Some(Value::Map(m)) => m,
+        None => &empty,
+        Some(other) => return Err(EvalError::type_error("http:with-retry", "map", other, 2)),
+    };
+    let (method, headers, body, timeout) =
+        request_options("http:with-retry", options, Some("GET"))?;
+
+    let defaults = RetryPolicy::default();
+    let number = |key: &str, default: f64, min: f64| match options.get(key) {
+        None => Ok(default),
+        Some(Value::Number(n)) if *n >= min => Ok(*n),
+        Some(other) => Err(EvalError::runtime_error(
+            "http:with-retry",
+            format!(
+                ":{} must be a number of at least {}, got {}",
+                key, min, other
+            ),
+        )),
+    };
+    let retry = RetryPolicy {
+        attempts: number("attempts", defaults.attempts as f64, 1.0)? as u32,
+        backoff_ms: number("backoff", defaults.backoff_ms, 0.0)?,
+        max_time_ms: number("max-time", defaults.max_time_ms, 0.0)?,
+    };
+
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("http:with-retry", ERR_SANDBOX_NOT_INIT))?;
+    let cancel = active_cancellation();
+    let interrupted = || cancel.as_ref().is_some_and(|token| token.is_cancelled());
+    let (result, attempts) = sandbox.http_request_with_retry(
+        url,
+        &method,
+        headers,
+        body.as_deref(),
+        timeout,
+        &retry,
+        &interrupted,
+    );
+    if interrupted() {
+        return Err(EvalError::Interrupted);
+    }
+    result
+        .map(response_to_value)
+        .map_err(|e| EvalError::Sandbox {
+            function: "http:with-retry".to_string(),
+            message: format!(
+                "HTTP {} request to '{}' failed after {} attempt{}: {}",
+                method.to_uppercase(),
+                url,
+                attempts,
+                if attempts == 1 { "" } else { "s" },
+                e
+            ),
+            error: e,
+        })
+}
+
+/// Splits a server-sent event stream into events
+///
+/// Follows the EventSource format: `field: value` lines, a blank line ending
+/// each event, `data` lines joined with newlines and `:` lines ignored.
+#[derive(Debug, Default)]
+struct SseParser {
+    line: String,
+    event: Option<String>,
+    data: Vec<String>,
+    id: Option<String>,
+}
+
+impl SseParser {
+    /// Feed the next chunk of the stream, returning the events it completes
+    fn push(&mut self, chunk: &str) -> Vec<Value> {
+        self.line.push_str(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.line.find('\n') {
+            let line: String = self.line.drain(..=end).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+            events.extend(self.field(line));
+        }
+        events
+    }
+
+    /// End of stream: an event missing its closing blank line still counts
+    fn finish(&mut self) -> Option<Value> {
+        let line = std::mem::take(&mut self.line);
+        if !line.is_empty() {
+            self.field(&line);
+        }
+        self.dispatch()
+    }
+
+    fn field(&mut self, line: &str) -> Option<Value> {
+        if line.is_empty() {
+            return self.dispatch();
+        }
+        let (name, value) = line.split_once(':').unwrap_or((line, ""));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        match name {
+            "" => {} // a comment
+            "event" => self.event = Some(value.to_string()),
+            "data" => self.data.push(value.to_string()),
+            "id" => self.id = Some(value.to_string()),
+            _ => {}
+        }
+        None
+    }
+
+    fn dispatch(&mut self) -> Option<Value> {
+        let event = self.event.take();
+        if self.data.is_empty() {
+            return None;
+        }
+        let mut map = BTreeMap::new();
+        map.insert(
+            "event".to_string(),
+            Value::String(event.unwrap_or_else(|| "message".to_string()).into()),
+        );
+        map.insert(
+            "data".to_string(),
+            Value::String(std::mem::take(&mut self.data).join("\n").into()),
+        );
+        if let Some(id) = &self.id {
+            map.insert("id".to_string(), Value::String(id.as_str().into()));
+        }
+        Some(Value::Map(map))
+    }
+}
+
+/// Make a request, calling a function with each body chunk or server-sent event
+pub fn http_stream(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(EvalError::arity_error(
+            "http:stream",
+            ARITY_TWO_OR_THREE,
+            args.len(),
+        ));
+    }
+
+    let url = match &args[0] {
+        Value::String(url) => url,
+        other => return Err(EvalError::type_error("http:stream", "string", other, 1)),
+    };
+    let callback = match &args[1] {
+        Value::Lambda { .. } | Value::BuiltIn(_) => &args[1],
+        other => return Err(EvalError::type_error("http:stream", "function", other, 2)),
+    };
+    let empty = BTreeMap::new();
+    let options = match args.get(2) {
+        Some(Value::Map(m)) => m,
+        None => &empty,
+        Some(other) => return Err(EvalError::type_error("http:stream", "map", other, 3)),
+    };
+    let (method, headers, body, timeout) = request_options("http:stream", options, Some("GET"))?;
+    let sse = match options.get("sse") {
+        Some(Value::Bool(sse)) => *sse,
+        None => false,
+        Some(other) => {
+            return Err(EvalError::runtime_error(
+                "http:stream",
+                format!(":sse must be #t or #f, got {}", other),
+            ))
+        }
+    };
+
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("http:stream", ERR_SANDBOX_NOT_INIT))?;
+    let cancel = active_cancellation();
+    let mut parser = SseParser::default();
+    let mut calls = 0;
+    let mut failure = None;
+    let mut ended_early = false;
+
+    // Each callback costs a step on top of its own evaluation, so a step
+    // limit or an interrupt ends an endless stream
+    let mut call = |item: Value| -> bool {
+        calls += 1;
+        let result = match cancel.as_ref().map(|token| token.step()) {
+            Some(Err(e)) => Err(e),
+            _ => apply_function(callback, vec![item]),
+        };
+        match result {
+            Ok(Value::Keyword(k)) if k == "stop" => false,
+            Ok(_) => true,
+            Err(e) => {
+                failure = Some(e);
+                false
+            }
+        }
+    };
+    let result = sandbox.http_stream(
+        url,
+        &method,
+        headers,
+        body.as_deref(),
+        timeout,
+        &mut |chunk| {
+            let more = if sse {
+                parser.push(chunk).into_iter().all(&mut call)
+            } else {
+                call(Value::String(chunk.into()))
+            };
+            ended_early = !more;
+            more
+        },
+    );
+    if sse && !ended_early && result.is_ok() {
+        if let Some(event) = parser.finish() {
+            call(event);
+        }
+    }
+    if let Some(e) = failure {
+        return Err(e);
+    }
+
+    let response = result.map_err(|e| EvalError::Sandbox {
+        function: "http:stream".to_string(),
+        message: format!(
+            "HTTP {} request to '{}' failed: {}",
+            method.to_uppercase(),
+            url,
+            e
+        ),
+        error: e,
+    })?;
+    let mut summary = match response_to_value(response) {
+        Value::Map(map) => map,
+        _ => unreachable!("responses are maps"),
+    };
+    summary.remove("body");
+    summary.insert("count".to_string(), Value::Number(calls as f64));
+    Ok(Value::Map(summary))
+}
+
+/// Register native HTTP functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("http:get-many".to_string(), Value::BuiltIn(http_get_many));
+    env.define(
+        "http:with-retry".to_string(),
+        Value::BuiltIn(http_with_retry),
+    );
+    env.define("http:stream".to_string(), Value::BuiltIn(http_stream));
+    for name in ["http:get-many", "http:with-retry", "http:stream"] {
+        crate::help::register_capability(name, "network");
+    }
+
+    crate::help::register_help(HelpEntry {
+        name: "http:get-many".to_string(),
+        signature: "(http:get-many urls [options])".to_string(),
+        description: "Fetch several URLs with GET requests in parallel.
+
+**Parameters:**
+- urls: List of URL strings
+- options: Optional map with:
+  - :parallel - Maximum requests in flight at once (default 4; browser builds
+    make one at a time)
+  - :timeout - Per-request timeout in milliseconds (default 30000)
+  - :headers - Map of header name->value pairs sent with every request
+
+**Returns:** List of response maps ({:status :headers :body}) in the same order
+as `urls`. A request that fails, including one blocked by the network allowlist,
+yields an error value in its position instead of stopping the others.
+
+**Examples:**
+```lisp
+(http:get-many (list \"https://a.example\" \"https://b.example\"))
+=> ({:status 200 ...} {:status 200 ...})
+
+(map http:body (http:get-many urls {:parallel 8 :timeout 5000}))
+
+(filter error? (http:get-many urls))
+=> failed requests
+```
+
+**Notes:** Requires --allow-network. Each URL is checked against the allowlist."
+            .to_string(),
+        examples: vec![
+            "(http:get-many (list \"https://a.example\" \"https://b.example\"))".to_string(),
+            "(http:get-many urls {:parallel 8})".to_string(),
+        ],
+        related: vec!["http-request".to_string(), "http:body".to_string()],
+        category: "Standard Library: HTTP".to_string(),
+    });
+    crate::help::register_help(HelpEntry {
+        name: "http:with-retry".to_string(),
+        signature: "(http:with-retry url [options])".to_string(),
+        description: "Make an HTTP request, retrying with exponential backoff while the
+server answers 429 (too many requests) or 5xx.
+
+Each attempt goes through the sandbox like http-request, so it is checked
+against the allowlist, audited and counted toward the request quota. Other
+failures, such as a 404 or a blocked URL, are not retried. A Retry-After
+header given in seconds replaces the backoff for that wait.
+
+**Parameters:**
+- url: URL string
+- options: Optional map with the http-request options (:method defaults to
+  \"GET\") and:
+  - :attempts - Most attempts, counting the first (default 3)
+  - :backoff - Wait before the first retry in milliseconds, doubled after each (default 500)
+  - :max-time - Budget for all attempts and waits in milliseconds (default 30000);
+    no wait is started that would end after it
+
+**Returns:** The response map ({:status :headers :body}) of the first attempt
+that is not retried, or of the last one
+
+**Examples:**
+```lisp
+(http:with-retry \"https://api.example.com/items\")
+=> {:status 200 ...}
+
+(http:with-retry \"https://api.example.com/jobs\"
+                 {:method \"POST\" :body \"{}\" :attempts 5 :backoff 1000})
+
+(error-data (try (http:with-retry url {:attempts 2})))
+=> {:function \"http:with-retry\" :status 503}
+```
+
+**Error Conditions:**
+- When every attempt fails, the error of the last one, with the number of
+  attempts in its message (kind :http-status for a 429 or 5xx answer)
+
+**Notes:** Requires --allow-network."
+            .to_string(),
+        examples: vec![
+            "(http:with-retry \"https://api.example.com/items\")".to_string(),
+            "(http:with-retry url {:attempts 5 :backoff 250 :max-time 10000})".to_string(),
+        ],
+        related: vec!["http-request".to_string(), "http:get-many".to_string()],
+        category: "Standard Library: HTTP".to_string(),
+    });
+    crate::help::register_help(HelpEntry {
+        name: "http:stream".to_string(),
+        signature: "(http:stream url callback [options])".to_string(),
+        description: "Make an HTTP request and call `callback` with the response body as it
+arrives, instead of waiting for all of it. Made for streaming APIs, such as
+LLM completions sent as server-sent events.
+
+The request is checked, audited and counted like http-request. Each call to
+`callback` costs an evaluation step on top of the steps it takes, so a step
+limit or an interrupt (Ctrl-C) ends a stream that never finishes.
+
+**Parameters:**
+- url: URL string
+- callback: Function of one argument. It gets each chunk of the body as a
+  string, or with :sse each event as a map {:event :data :id}. Returning
+  :stop closes the stream.
+- options: Optional map with the http-request options (:method defaults to
+  \"GET\"; :timeout covers the whole stream) and:
+  - :sse - #t to parse the body as server-sent events (default #f)
+
+**Returns:** {:status :headers :count}, where :count is the number of callback calls
+
+**Examples:**
+```lisp
+(http:stream \"https://example.com/big.txt\"
+             (lambda (chunk) (print (string-length chunk))))
+
+(define text \"\")
+(http:stream \"https://api.example.com/v1/chat\"
+             (lambda (event)
+               (if (= (get event :data) \"[DONE]\")
+                   :stop
+                   (set! text (string-append text (map-get event :data)))))
+             {:method \"POST\" :body payload :sse #t :timeout 120000})
+```
+
+**Error Conditions:**
+- The errors of http-request (blocked address, quota, error status)
+- An error raised by `callback` stops the stream and is raised again
+
+**Notes:** Requires --allow-network. Events are `event:`, `data:` and `id:`
+fields ending with a blank line; :event is \"message\" when not given, and
+several `data:` lines are joined with newlines. Streams are never cached."
+            .to_string(),
+        examples: vec![
+            "(http:stream url (lambda (chunk) (print chunk)))".to_string(),
+            "(http:stream url handle-event {:sse #t})".to_string(),
+        ],
+        related: vec!["http-request".to_string(), "http:with-retry".to_string()],
+        category: "Standard Library: HTTP".to_string(),
+    });
+}
+
+// These tests make live requests to a local server
+#[cfg(all(test, feature = "native-fs", feature = "http"))]
+mod tests {
+    use super::*;
+    use crate::builtins::swap_sandbox;
+    use crate::config::{FsConfig, NetConfig};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    fn network_sandbox(allowed_addresses: Vec<String>) -> Arc<Sandbox> {
+        let fs_config = FsConfig {
+            allowed_paths: vec![],
+            read_only_paths: vec![],
+            max_file_size: 1024,
+        };
+        let net_config = NetConfig {
+            enabled: true,
+            allowed_addresses,
+            max_requests: None,
+            max_rate: None,
+            allow_websocket: false,
+        };
+        Arc::new(Sandbox::new(fs_config, net_config).unwrap())
+    }
+
+    /// Serve `count` requests, answering each with its request path
+    fn echo_server(count: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for stream in listener.incoming().take(count) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    path.len(),
+                    path
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    /// Answer one request with each of `statuses` in turn
+    fn status_server(statuses: Vec<u16>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for (stream, status) in listener.incoming().zip(statuses) {
+                let mut stream = stream.unwrap();
+                let mut request_line = String::new();
+                BufReader::new(&stream)
+                    .read_line(&mut request_line)
+                    .unwrap();
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok",
+                    status
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn body(value: &Value) -> String {
+        match value {
+            Value::Map(map) => match &map["body"] {
+                Value::String(body) => body.to_string(),
+                other => panic!("expected string body, got {}", other),
+            },
+            other => panic!("expected response map, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_results_keep_request_order() {
+        let base = echo_server(5);
+        let urls: Vec<String> = (0..5).map(|i| format!("{}/{}", base, i)).collect();
+        let results = fetch_all(
+            &network_sandbox(vec!["http://127.0.0.1".to_string()]),
+            &urls,
+            3,
+            None,
+            Some(5000),
+        );
+        let bodies: Vec<String> = results.iter().map(body).collect();
+        assert_eq!(bodies, vec!["/0", "/1", "/2", "/3", "/4"]);
+    }
+
+    #[test]
+    fn test_blocked_urls_become_error_values() {
+        let sandbox = network_sandbox(vec!["allowed.test".to_string()]);
+        let previous = swap_sandbox(Some(sandbox));
+        let result = http_get_many(&[Value::List(
+            vec![
+                Value::String("http://blocked.test/a".into()),
+                Value::String("http://other.test/b".into()),
+            ]
+            .into(),
+        )]);
+        swap_sandbox(previous);
+
+        match result.unwrap() {
+            Value::List(items) => {
+                assert_eq!(items.len(), 2);
+                assert!(matches!(&items[0], Value::Error(err)
+                    if err.message.contains("blocked.test") && err.kind == "address-not-allowed"));
+                assert!(
+                    matches!(&items[1], Value::Error(err) if err.message.contains("other.test"))
+                );
+            }
+            other => panic!("expected list, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_rejects_bad_arguments() {
+        assert!(http_get_many(&[Value::String("http://a.test".into())]).is_err());
+        assert!(http_get_many(&[Value::List(vec![Value::Number(1.0)].into())]).is_err());
+        let mut options = std::collections::BTreeMap::new();
+        options.insert("parallel".to_string(), Value::Number(0.0));
+        assert!(http_get_many(&[Value::List(vec![].into()), Value::Map(options)]).is_err());
+    }
+
+    #[test]
+    fn test_with_retry_backs_off_on_server_errors() {
+        let sandbox = network_sandbox(vec!["http://127.0.0.1".to_string()]);
+        let previous = swap_sandbox(Some(sandbox));
+        let options = |attempts: f64| {
+            let mut options = std::collections::BTreeMap::new();
+            options.insert("attempts".to_string(), Value::Number(attempts));
+            options.insert("backoff".to_string(), Value::Number(10.0));
+            Value::Map(options)
+        };
+
+        // 429 and 503 are retried until a success
+        let url = Value::String(status_server(vec![429, 503, 200]).into());
+        let result = http_with_retry(&[url, options(3.0)]);
+        assert_eq!(body(&result.unwrap()), "ok");
+
+        // Giving up reports the last status and the attempts made
+        let url = Value::String(status_server(vec![500, 502]).into());
+        let err = http_with_retry(&[url, options(2.0)]).unwrap_err();
+        assert_eq!(err.kind(), "http-status");
+        assert!(matches!(err.data()["status"], Value::Number(n) if n == 502.0));
+        assert!(
+            err.to_string().contains("failed after 2 attempts"),
+            "{}",
+            err
+        );
+
+        // Client errors are not retried
+        let url = Value::String(status_server(vec![404, 200]).into());
+        let err = http_with_retry(&[url, options(3.0)]).unwrap_err();
+        assert!(err.to_string().contains("after 1 attempt:"), "{}", err);
+
+        swap_sandbox(previous);
+    }
+
+    /// Answer one request with `parts`, written one at a time
+    fn stream_server(parts: Vec<&'static str>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut request_line = String::new();
+            BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .unwrap();
+            let head =
+                "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nConnection: close\r\n\r\n";
+            let _ = stream.write_all(head.as_bytes());
+            for part in parts {
+                // The client may stop reading early
+                if stream.write_all(part.as_bytes()).is_err() {
+                    return;
+                }
+                let _ = stream.flush();
+                std::thread::sleep(std::time::Duration::from_millis(10));
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    fn callback(param: &str, body: Value) -> Value {
+        Value::Lambda {
+            params: vec![param.into()],
+            defaults: vec![],
+            body: Arc::new(body),
+            env: crate::env::Environment::new(),
+            docstring: None,
+            source: None,
+        }
+    }
+
+    fn count(result: Result<Value, EvalError>) -> f64 {
+        match result.unwrap() {
+            Value::Map(map) => match map["count"] {
+                Value::Number(n) => n,
+                ref other => panic!("expected count, got {}", other),
+            },
+            other => panic!("expected map, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_sse_parser_splits_events() {
+        let mut parser = SseParser::default();
+        assert!(parser.push(": keep-alive\ndata: hel").is_empty());
+        let events = parser.push("lo\r\ndata: world\r\n\r\nevent: done\nid: 7\ndata: [DONE]\n\n");
+        assert_eq!(events.len(), 2);
+        let field = |event: &Value, name: &str| match event {
+            Value::Map(map) => map.get(name).map(|v| v.to_string()),
+            other => panic!("expected map, got {}", other),
+        };
+        assert_eq!(field(&events[0], "event").as_deref(), Some("\"message\""));
+        assert_eq!(
+            field(&events[0], "data").as_deref(),
+            Some("\"hello\\nworld\"")
+        );
+        assert_eq!(field(&events[0], "id"), None);
+        assert_eq!(field(&events[1], "event").as_deref(), Some("\"done\""));
+        assert_eq!(field(&events[1], "id").as_deref(), Some("\"7\""));
+
+        // A last event without its blank line is still delivered
+        assert!(parser.push("data: tail").is_empty());
+        assert!(parser.finish().is_some());
+        assert!(parser.finish().is_none());
+    }
+
+    #[test]
+    fn test_stream_calls_back_per_event() {
+        let sandbox = network_sandbox(vec!["http://127.0.0.1".to_string()]);
+        let previous = swap_sandbox(Some(sandbox));
+        let parts = vec!["data: one\n\n", "data: two\n", "\ndata: three\n\n"];
+        let mut sse = std::collections::BTreeMap::new();
+        sse.insert("sse".to_string(), Value::Bool(true));
+
+        // Every event reaches the callback
+        let url = Value::String(stream_server(parts.clone()).into());
+        let echo = callback("event", Value::Symbol("event".into()));
+        let result = http_stream(&[url, echo, Value::Map(sse.clone())]);
+        assert_eq!(count(result), 3.0);
+
+        // Returning :stop ends the stream after the first event
+        let url = Value::String(stream_server(parts.clone()).into());
+        let stop = callback("event", Value::Keyword("stop".into()));
+        assert_eq!(count(http_stream(&[url, stop, Value::Map(sse)])), 1.0);
+
+        // Without :sse the callback gets raw chunks, and its errors stop the stream
+        let url = Value::String(stream_server(parts).into());
+        let failing = callback("chunk", Value::Symbol("undefined-thing".into()));
+        let err = http_stream(&[url, failing]).unwrap_err();
+        assert_eq!(err.kind(), "undefined-symbol");
+
+        swap_sandbox(previous);
+    }
+
+    #[test]
+    fn test_stream_callbacks_use_fuel() {
+        let sandbox = network_sandbox(vec!["http://127.0.0.1".to_string()]);
+        let previous = swap_sandbox(Some(sandbox));
+        let token = crate::cancel::CancellationToken::new();
+        token.set_step_limit(Some(2));
+        let previous_token = crate::eval::swap_cancellation(Some(token));
+
+        let parts = vec!["a", "b", "c", "d", "e", "f"];
+        let url = Value::String(stream_server(parts).into());
+        let result = http_stream(&[url, callback("chunk", Value::Nil)]);
+
+        crate::eval::swap_cancellation(previous_token);
+        swap_sandbox(previous);
+        assert!(matches!(result, Err(EvalError::StepLimitExceeded(2))));
+    }
+}
@@ -11,51 +11,210 @@
 //! - Lisp Nil ↔ JSON null
 //! - Lisp Keyword → JSON string (strip the :)
 
-use crate::env::Environment;
-use crate::error::{EvalError, ARITY_ONE};
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ONE_OR_TWO};
 use crate::help::HelpEntry;
 use crate::value::Value;
+use serde::ser::{Error as _, SerializeMap, SerializeSeq};
+use serde::{Serialize, Serializer};
 use serde_json;
-use std::collections::HashMap;
-use std::rc::Rc;
+use std::collections::BTreeMap;
 
-/// Convert Lisp Value to serde_json::Value
-fn value_to_json(value: &Value) -> Result<serde_json::Value, EvalError> {
-    match value {
-        Value::Number(n) => {
-            if let Some(num) = serde_json::Number::from_f64(*n) {
-                Ok(serde_json::Value::Number(num))
-            } else {
-                Err(EvalError::runtime_error(
-                    "json:encode",
-                    format!("cannot convert number {} to JSON", n),
-                ))
+/// Deepest nesting of lists and maps encoded by default, the same limit the decoder has
+pub const DEFAULT_MAX_JSON_DEPTH: usize = 128;
+
+/// Deepest nesting `:max-depth` may allow, so that encoding cannot exhaust the stack
+pub const MAX_JSON_DEPTH: usize = 512;
+
+/// Longest JSON text json:encode and json:pretty produce by default, like the default file size limit
+pub const DEFAULT_MAX_JSON_BYTES: usize = 10 * 1024 * 1024;
+
+/// How deep and how long encoded JSON may get
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct JsonLimits {
+    max_depth: usize,
+    /// None for no limit
+    max_bytes: Option<usize>,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        JsonLimits {
+            max_depth: DEFAULT_MAX_JSON_DEPTH,
+            max_bytes: Some(DEFAULT_MAX_JSON_BYTES),
+        }
+    }
+}
+
+/// A Lisp value serialized as JSON, refusing to nest deeper than `levels` more lists or maps
+struct Encode<'a> {
+    value: &'a Value,
+    levels: usize,
+    max_depth: usize,
+}
+
+impl Encode<'_> {
+    fn nested<'b>(&self, value: &'b Value) -> Encode<'b> {
+        Encode {
+            value,
+            levels: self.levels - 1,
+            max_depth: self.max_depth,
+        }
+    }
+}
+
+impl Serialize for Encode<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.value {
+            Value::Number(n) if n.is_finite() => serializer.serialize_f64(*n),
+            Value::Number(n) => Err(S::Error::custom(format!(
+                "cannot convert number {} to JSON",
+                n
+            ))),
+            Value::String(s) => serializer.serialize_str(s),
+            // As a string, since a JSON number would round-trip through f64
+            Value::Decimal(d) => serializer.serialize_str(&d.to_plain_string()),
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::Keyword(k) => serializer.serialize_str(k),
+            Value::Nil => serializer.serialize_unit(),
+            Value::List(_) | Value::Map(_) if self.levels == 0 => Err(S::Error::custom(format!(
+                "value nests deeper than {} level{}",
+                self.max_depth,
+                if self.max_depth == 1 { "" } else { "s" }
+            ))),
+            Value::List(items) => {
+                let mut seq = serializer.serialize_seq(Some(items.len()))?;
+                for item in items.iter() {
+                    seq.serialize_element(&self.nested(item))?;
+                }
+                seq.end()
+            }
+            Value::Map(map) => {
+                let mut object = serializer.serialize_map(Some(map.len()))?;
+                for (key, val) in map {
+                    object.serialize_entry(key, &self.nested(val))?;
+                }
+                object.end()
             }
+            other => Err(S::Error::custom(format!(
+                "cannot convert {} to JSON",
+                other
+            ))),
         }
-        Value::String(s) => Ok(serde_json::Value::String(s.clone())),
-        Value::Bool(b) => Ok(serde_json::Value::Bool(*b)),
-        Value::Keyword(k) => Ok(serde_json::Value::String(k.clone())),
-        Value::Nil => Ok(serde_json::Value::Null),
-        Value::List(items) => {
-            let json_items: Result<Vec<_>, _> = items.iter().map(value_to_json).collect();
-            Ok(serde_json::Value::Array(json_items?))
+    }
+}
+
+/// Collects encoded JSON, failing once it would grow past `max` bytes
+struct LimitedWriter {
+    bytes: Vec<u8>,
+    max: Option<usize>,
+}
+
+impl std::io::Write for LimitedWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.max {
+            Some(max) if self.bytes.len() + buf.len() > max => Err(std::io::Error::other(format!(
+                "encoded JSON exceeds {} bytes",
+                max
+            ))),
+            _ => {
+                self.bytes.extend_from_slice(buf);
+                Ok(buf.len())
+            }
         }
-        Value::Map(map) => {
-            let mut json_map = serde_json::Map::new();
-            for (key, val) in map {
-                json_map.insert(key.clone(), value_to_json(val)?);
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Convert Lisp Value to serde_json::Value
+///
+/// Values nested deeper than [`DEFAULT_MAX_JSON_DEPTH`] are refused.
+pub(crate) fn value_to_json(value: &Value) -> Result<serde_json::Value, EvalError> {
+    let encode = Encode {
+        value,
+        levels: DEFAULT_MAX_JSON_DEPTH,
+        max_depth: DEFAULT_MAX_JSON_DEPTH,
+    };
+    serde_json::to_value(encode).map_err(|e| EvalError::runtime_error("json:encode", e.to_string()))
+}
+
+/// Encode `value` as JSON text within `limits`, stopping as soon as a limit is passed
+fn encode_text(
+    function: &str,
+    value: &Value,
+    limits: JsonLimits,
+    pretty: bool,
+) -> Result<Value, EvalError> {
+    let encode = Encode {
+        value,
+        levels: limits.max_depth,
+        max_depth: limits.max_depth,
+    };
+    let mut writer = LimitedWriter {
+        bytes: Vec::new(),
+        max: limits.max_bytes,
+    };
+    let written = if pretty {
+        serde_json::to_writer_pretty(&mut writer, &encode)
+    } else {
+        serde_json::to_writer(&mut writer, &encode)
+    };
+    written.map_err(|e| EvalError::runtime_error(function, e.to_string()))?;
+    let text = String::from_utf8(writer.bytes)
+        .map_err(|e| EvalError::runtime_error(function, e.to_string()))?;
+    Ok(Value::String(text.into()))
+}
+
+/// Read the `{:max-depth n :max-bytes n}` options of json:encode and json:pretty
+fn json_limits(function: &str, args: &[Value]) -> Result<JsonLimits, EvalError> {
+    let mut limits = JsonLimits::default();
+    let options = match args.get(1) {
+        None => return Ok(limits),
+        Some(Value::Map(options)) => options,
+        Some(other) => return Err(EvalError::type_error(function, "map", other, 2)),
+    };
+    for (key, value) in options {
+        match (key.as_str(), value) {
+            ("max-depth", Value::Number(n))
+                if n.fract() == 0.0 && *n >= 1.0 && *n <= MAX_JSON_DEPTH as f64 =>
+            {
+                limits.max_depth = *n as usize
+            }
+            ("max-depth", _) => {
+                return Err(EvalError::runtime_error(
+                    function,
+                    format!(":max-depth must be an integer from 1 to {}", MAX_JSON_DEPTH),
+                ))
+            }
+            ("max-bytes", Value::Number(n)) if n.fract() == 0.0 && *n >= 1.0 => {
+                limits.max_bytes = Some(*n as usize)
+            }
+            ("max-bytes", Value::Nil) => limits.max_bytes = None,
+            ("max-bytes", _) => {
+                return Err(EvalError::runtime_error(
+                    function,
+                    ":max-bytes must be a positive integer or nil",
+                ))
+            }
+            (other, _) => {
+                return Err(EvalError::runtime_error(
+                    function,
+                    format!(
+                        "unknown option :{} (expected :max-depth or :max-bytes)",
+                        other
+                    ),
+                ))
             }
-            Ok(serde_json::Value::Object(json_map))
         }
-        _ => Err(EvalError::runtime_error(
-            "json:encode",
-            format!("cannot convert {} to JSON", value),
-        )),
     }
+    Ok(limits)
 }
 
 /// Convert serde_json::Value to Lisp Value
-fn json_to_value(json: &serde_json::Value) -> Value {
+pub(crate) fn json_to_value(json: &serde_json::Value) -> Value {
     match json {
         serde_json::Value::Null => Value::Nil,
         serde_json::Value::Bool(b) => Value::Bool(*b),
@@ -66,10 +225,10 @@ fn json_to_value(json: &serde_json::Value) -> Value {
                 Value::Nil // Shouldn't happen
             }
         }
-        serde_json::Value::String(s) => Value::String(s.clone()),
+        serde_json::Value::String(s) => Value::String(s.as_str().into()),
         serde_json::Value::Array(arr) => Value::List(arr.iter().map(json_to_value).collect()),
         serde_json::Value::Object(obj) => {
-            let mut map = HashMap::new();
+            let mut map = BTreeMap::new();
             for (key, val) in obj {
                 map.insert(key.clone(), json_to_value(val));
             }
@@ -80,15 +239,16 @@ fn json_to_value(json: &serde_json::Value) -> Value {
 
 /// json:encode - Encode Lisp value to JSON string
 fn json_encode(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 1 {
-        return Err(EvalError::arity_error("json:encode", ARITY_ONE, args.len()));
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "json:encode",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
     }
 
-    let json_value = value_to_json(&args[0])?;
-    let json_string = serde_json::to_string(&json_value)
-        .map_err(|e| EvalError::runtime_error("json:encode", e.to_string()))?;
-
-    Ok(Value::String(json_string))
+    let limits = json_limits("json:encode", args)?;
+    encode_text("json:encode", &args[0], limits, false)
 }
 
 /// json:decode - Decode JSON string to Lisp value
@@ -110,19 +270,20 @@ fn json_decode(args: &[Value]) -> Result<Value, EvalError> {
 
 /// json:pretty - Encode Lisp value to pretty-printed JSON string
 fn json_pretty(args: &[Value]) -> Result<Value, EvalError> {
-    if args.len() != 1 {
-        return Err(EvalError::arity_error("json:pretty", ARITY_ONE, args.len()));
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "json:pretty",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
     }
 
-    let json_value = value_to_json(&args[0])?;
-    let json_string = serde_json::to_string_pretty(&json_value)
-        .map_err(|e| EvalError::runtime_error("json:pretty", e.to_string()))?;
-
-    Ok(Value::String(json_string))
+    let limits = json_limits("json:pretty", args)?;
+    encode_text("json:pretty", &args[0], limits, true)
 }
 
 /// Register json module functions in the environment
-pub fn register(env: &Rc<Environment>) {
+pub fn register(env: &EnvRef) {
     // Register functions with json: namespace
     env.define("json:encode".to_string(), Value::BuiltIn(json_encode));
     env.define("json:decode".to_string(), Value::BuiltIn(json_decode));
@@ -131,7 +292,7 @@ pub fn register(env: &Rc<Environment>) {
     // Register help entries
     crate::help::register_help(HelpEntry {
         name: "json:encode".to_string(),
-        signature: "(json:encode value)".to_string(),
+        signature: "(json:encode value [options])".to_string(),
         description: "Encode a Lisp value to a JSON string.
 
 **Type Mapping:**
@@ -145,6 +306,9 @@ pub fn register(env: &Rc<Environment>) {
 
 **Parameters:**
 - value: Any Lisp value to encode
+- options: Optional map of limits
+  - :max-depth - Deepest nesting of lists and maps (default 128, at most 512)
+  - :max-bytes - Longest JSON text to produce, or nil for no limit (default 10MB)
 
 **Returns:** JSON string representation
 
@@ -160,7 +324,9 @@ pub fn register(env: &Rc<Environment>) {
 => \"{\\\"tags\\\":[\\\"rust\\\",\\\"lisp\\\"],\\\"active\\\":true}\"
 ```
 
-**Notes:** Functions, lambdas, macros, and builtins cannot be encoded to JSON."
+**Notes:** Functions, lambdas, macros, and builtins cannot be encoded to JSON.
+Encoding stops with an error as soon as a value nests too deeply or the text
+grows past :max-bytes, so oversized values never build a full string."
             .to_string(),
         examples: vec![
             "(json:encode {:name \"Alice\"}) => \"{\\\"name\\\":\\\"Alice\\\"}\"".to_string(),
@@ -213,13 +379,14 @@ pub fn register(env: &Rc<Environment>) {
 
     crate::help::register_help(HelpEntry {
         name: "json:pretty".to_string(),
-        signature: "(json:pretty value)".to_string(),
+        signature: "(json:pretty value [options])".to_string(),
         description: "Encode a Lisp value to a pretty-printed JSON string.
 
 Same as json:encode but with indentation and newlines for readability.
 
 **Parameters:**
 - value: Any Lisp value to encode
+- options: Optional map with :max-depth and :max-bytes, as for json:encode
 
 **Returns:** Pretty-printed JSON string
 
@@ -240,3 +407,70 @@ Same as json:encode but with indentation and newlines for readability.
         category: "JSON".to_string(),
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested(depth: usize) -> Value {
+        (0..depth).fold(Value::Nil, |inner, _| Value::List(vec![inner].into()))
+    }
+
+    fn options(entries: &[(&str, Value)]) -> Value {
+        Value::Map(
+            entries
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.clone()))
+                .collect(),
+        )
+    }
+
+    fn encode(args: &[Value]) -> Result<String, String> {
+        match json_encode(args) {
+            Ok(Value::String(s)) => Ok(s.to_string()),
+            Ok(other) => panic!("expected string, got {}", other),
+            Err(e) => Err(e.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_encode_output_unchanged() {
+        let value = Value::List(
+            vec![
+                Value::Number(1.0),
+                Value::Number(2.5),
+                options(&[("a", Value::List(vec![].into()))]),
+            ]
+            .into(),
+        );
+        assert_eq!(encode(&[value]).unwrap(), r#"[1.0,2.5,{"a":[]}]"#);
+        let err = encode(&[Value::Number(f64::NAN)]).unwrap_err();
+        assert!(err.contains("cannot convert number NaN to JSON"), "{}", err);
+    }
+
+    #[test]
+    fn test_encode_refuses_deep_values() {
+        assert!(encode(&[nested(DEFAULT_MAX_JSON_DEPTH)]).is_ok());
+        let err = encode(&[nested(DEFAULT_MAX_JSON_DEPTH + 1)]).unwrap_err();
+        assert!(err.contains("nests deeper than 128 levels"), "{}", err);
+        assert!(value_to_json(&nested(1000)).is_err());
+
+        let shallow = options(&[("max-depth", Value::Number(2.0))]);
+        assert!(encode(&[nested(2), shallow.clone()]).is_ok());
+        assert!(encode(&[nested(3), shallow]).is_err());
+        let too_deep = options(&[("max-depth", Value::Number(10_000.0))]);
+        assert!(encode(&[nested(1), too_deep]).is_err());
+    }
+
+    #[test]
+    fn test_encode_stops_at_max_bytes() {
+        let text = Value::String("x".repeat(100).into());
+        let small = options(&[("max-bytes", Value::Number(50.0))]);
+        let err = encode(&[text.clone(), small]).unwrap_err();
+        assert!(err.contains("encoded JSON exceeds 50 bytes"), "{}", err);
+        let unlimited = options(&[("max-bytes", Value::Nil)]);
+        assert_eq!(encode(&[text, unlimited]).unwrap().len(), 102);
+        assert!(json_pretty(&[Value::Nil, options(&[("max-bytes", Value::Number(3.0))])]).is_err());
+        assert!(encode(&[Value::Nil, options(&[("width", Value::Number(3.0))])]).is_err());
+    }
+}
@@ -5,70 +5,10 @@
 ;; Higher-Order Functions
 ;; ============================================================================
 
-;;; Apply function to each element, returning new list.
-;;;
-;;; **Parameters:**
-;;; - f: Function to apply to each element
-;;; - lst: Input list
-;;;
-;;; **Returns:** New list with f applied to each element
-;;;
-;;; **Time Complexity:** O(n) where n is list length
-;;;
-;;; **Examples:**
-;;; - (map (lambda (x) (* x 2)) '(1 2 3)) => (2 4 6)
-;;; - (map (lambda (x) (+ x 1)) '(0 1 2)) => (1 2 3)
-;;;
-;;; **Notes:** Uses tail call optimization for efficiency. Preserves list structure.
-(define (map f lst)
-  (if (empty? lst)
-      '()
-      (cons (f (car lst))
-            (map f (cdr lst)))))
-
-;;; Keep only elements satisfying predicate.
-;;;
-;;; **Parameters:**
-;;; - pred: Predicate function returning boolean
-;;; - lst: Input list
-;;;
-;;; **Returns:** New list containing only elements where pred returns true
-;;;
-;;; **Time Complexity:** O(n) where n is list length
-;;;
-;;; **Examples:**
-;;; - (filter (lambda (x) (> x 2)) '(1 2 3 4 5)) => (3 4 5)
-;;; - (filter even? '(1 2 3 4 5)) => (2 4)
-;;;
-;;; **Notes:** Preserves element order. Short-circuits on first predicate evaluation.
-(define (filter pred lst)
-  (if (empty? lst)
-      '()
-      (if (pred (car lst))
-          (cons (car lst) (filter pred (cdr lst)))
-          (filter pred (cdr lst)))))
-
-;;; Fold list using function, accumulating from init value (left-fold).
-;;;
-;;; **Parameters:**
-;;; - f: Binary function (accumulator, element) -> new-accumulator
-;;; - init: Initial accumulator value
-;;; - lst: Input list
-;;;
-;;; **Returns:** Final accumulated value
-;;;
-;;; **Time Complexity:** O(n) where n is list length
-;;;
-;;; **Examples:**
-;;; - (reduce + 0 '(1 2 3 4)) => 10
-;;; - (reduce * 1 '(1 2 3 4)) => 24
-;;; - (reduce (lambda (acc x) (cons x acc)) '() '(1 2 3)) => (3 2 1)
-;;;
-;;; **Notes:** Left-associative fold. Processes list from head to tail.
-(define (reduce f init lst)
-  (if (empty? lst)
-      init
-      (reduce f (f init (car lst)) (cdr lst))))
+;; map, filter, reduce, for-each, some and every are native builtins
+;; (src/builtins/higher_order.rs) and are available even with --no-stdlib.
+;; The original Lisp map, filter and reduce are in fallbacks.lisp, loaded
+;; instead with the `lisp-fallbacks` feature.
 
 ;;; Compose two functions: returns function that applies g then f.
 ;;;
@@ -434,4 +374,92 @@
            (list (car entry) (f (car (cdr entry)))))
          (map-entries m))))
 
+;; ============================================================================
+;; Results
+;; ============================================================================
+
+;; A result is (:ok value) or (:err reason). Error values, such as those
+;; returned by try, count as failures too. some-> stops at the first failure.
+
+;;; Wrap a successful value as a result.
+;;;
+;;; **Parameters:**
+;;; - value: The successful value
+;;;
+;;; **Returns:** (:ok value)
+;;;
+;;; **Examples:**
+;;; - (ok 42) => (:ok 42)
+;;; - (unwrap-or (ok 42) 0) => 42
+(define (ok value)
+  (list :ok value))
+
+;;; Wrap a failure reason as a result.
+;;;
+;;; **Parameters:**
+;;; - reason: Why the operation failed (any value)
+;;;
+;;; **Returns:** (:err reason)
+;;;
+;;; **Examples:**
+;;; - (err "not found") => (:err "not found")
+;;; - (unwrap-or (err "not found") 0) => 0
+(define (err reason)
+  (list :err reason))
+
+(define (result-tagged? r tag)
+  (if (list? r)
+      (if (= (length r) 2)
+          (= (car r) tag)
+          #f)
+      #f))
+
+;;; Test whether a value is a successful result.
+;;;
+;;; **Parameters:**
+;;; - r: Value to test
+;;;
+;;; **Returns:** #t for (:ok value), #f otherwise
+;;;
+;;; **Examples:**
+;;; - (ok? (ok 1)) => #t
+;;; - (ok? (err "x")) => #f
+(define (ok? r)
+  (result-tagged? r :ok))
+
+;;; Test whether a value is a failed result or an error value.
+;;;
+;;; **Parameters:**
+;;; - r: Value to test
+;;;
+;;; **Returns:** #t for (:err reason) or an error value, #f otherwise
+;;;
+;;; **Examples:**
+;;; - (err? (err "x")) => #t
+;;; - (err? (try (car 5))) => #t
+;;; - (err? (ok 1)) => #f
+(define (err? r)
+  (if (error? r)
+      #t
+      (result-tagged? r :err)))
+
+;;; Get the value out of a result, or a default if it failed.
+;;;
+;;; **Parameters:**
+;;; - r: A result, error value or plain value
+;;; - default: Returned when r failed
+;;;
+;;; **Returns:** The ok value, default for failures, or r itself if it is not a result
+;;;
+;;; **Examples:**
+;;; - (unwrap-or (ok 5) 0) => 5
+;;; - (unwrap-or (err "bad") 0) => 0
+;;; - (unwrap-or (try (read-file "missing.txt")) "") => ""
+;;;
+;;; **Notes:** Plain values pass through, so try results can be used directly.
+(define (unwrap-or r default)
+  (if (ok? r)
+      (car (cdr r))
+      (if (err? r) default r)))
+
 ;; Core library loaded
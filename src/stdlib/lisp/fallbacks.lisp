@@ -0,0 +1,74 @@
+;; ABOUTME: Lisp fallbacks - The original recursive map, filter and reduce
+;; Loaded only with the `lisp-fallbacks` feature, right after core. These
+;; definitions then replace the native builtins, for comparing results or
+;; timings against them; they take a single list and have no for-each, some
+;; or every counterparts.
+
+;; ============================================================================
+;; Higher-Order Functions
+;; ============================================================================
+
+;;; Apply function to each element, returning new list.
+;;;
+;;; **Parameters:**
+;;; - f: Function to apply to each element
+;;; - lst: Input list
+;;;
+;;; **Returns:** New list with f applied to each element
+;;;
+;;; **Time Complexity:** O(n) where n is list length
+;;;
+;;; **Examples:**
+;;; - (map (lambda (x) (* x 2)) '(1 2 3)) => (2 4 6)
+;;; - (map (lambda (x) (+ x 1)) '(0 1 2)) => (1 2 3)
+;;;
+;;; **Notes:** Uses tail call optimization for efficiency. Preserves list structure.
+(define (map f lst)
+  (if (empty? lst)
+      '()
+      (cons (f (car lst))
+            (map f (cdr lst)))))
+
+;;; Keep only elements satisfying predicate.
+;;;
+;;; **Parameters:**
+;;; - pred: Predicate function returning boolean
+;;; - lst: Input list
+;;;
+;;; **Returns:** New list containing only elements where pred returns true
+;;;
+;;; **Time Complexity:** O(n) where n is list length
+;;;
+;;; **Examples:**
+;;; - (filter (lambda (x) (> x 2)) '(1 2 3 4 5)) => (3 4 5)
+;;; - (filter even? '(1 2 3 4 5)) => (2 4)
+;;;
+;;; **Notes:** Preserves element order. Short-circuits on first predicate evaluation.
+(define (filter pred lst)
+  (if (empty? lst)
+      '()
+      (if (pred (car lst))
+          (cons (car lst) (filter pred (cdr lst)))
+          (filter pred (cdr lst)))))
+
+;;; Fold list using function, accumulating from init value (left-fold).
+;;;
+;;; **Parameters:**
+;;; - f: Binary function (accumulator, element) -> new-accumulator
+;;; - init: Initial accumulator value
+;;; - lst: Input list
+;;;
+;;; **Returns:** Final accumulated value
+;;;
+;;; **Time Complexity:** O(n) where n is list length
+;;;
+;;; **Examples:**
+;;; - (reduce + 0 '(1 2 3 4)) => 10
+;;; - (reduce * 1 '(1 2 3 4)) => 24
+;;; - (reduce (lambda (acc x) (cons x acc)) '() '(1 2 3)) => (3 2 1)
+;;;
+;;; **Notes:** Left-associative fold. Processes list from head to tail.
+(define (reduce f init lst)
+  (if (empty? lst)
+      init
+      (reduce f (f init (car lst)) (cdr lst))))
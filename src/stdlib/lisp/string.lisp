@@ -44,43 +44,6 @@
 (define (string-concat lst)
   (list->string lst))
 
-;;; Reverse a string.
-;;;
-;;; **Parameters:**
-;;; - s: Input string
-;;;
-;;; **Returns:** Reversed string
-;;;
-;;; **Time Complexity:** O(n) where n is string length
-;;;
-;;; **Examples:**
-;;; - (string-reverse "hello") => "olleh"
-;;; - (string-reverse "racecar") => "racecar"
-;;;
-;;; **Notes:** Converts to list, reverses, converts back to string.
-(define (string-reverse s)
-  (list->string (reverse (string->list s))))
-
-;;; Repeat string n times.
-;;;
-;;; **Parameters:**
-;;; - s: String to repeat
-;;; - n: Number of repetitions (non-negative integer)
-;;;
-;;; **Returns:** Repeated string
-;;;
-;;; **Time Complexity:** O(n*m) where n is repeat count, m is string length
-;;;
-;;; **Examples:**
-;;; - (string-repeat "ab" 3) => "ababab"
-;;; - (string-repeat "x" 0) => ""
-;;;
-;;; **Notes:** Uses recursion with tail-call optimization.
-(define (string-repeat s n)
-  (if (<= n 0)
-      ""
-      (string-append s (string-repeat s (- n 1)))))
-
 ;; ============================================================================
 ;; String Parsing
 ;; ============================================================================
@@ -119,29 +82,3 @@
 (define (string-lines s)
   (string-split s "
 "))
-
-;; ============================================================================
-;; String Padding
-;; ============================================================================
-
-;;; Pad string on left with character to reach minimum width.
-;;;
-;;; **Parameters:**
-;;; - s: Input string
-;;; - width: Desired minimum width
-;;; - char: Single-character string to pad with
-;;;
-;;; **Returns:** Padded string
-;;;
-;;; **Time Complexity:** O(n) where n is padding needed
-;;;
-;;; **Examples:**
-;;; - (string-pad-left "42" 5 "0") => "00042"
-;;; - (string-pad-left "text" 2 " ") => "text"
-;;;
-;;; **Notes:** If string is already >= width, returns unchanged.
-(define (string-pad-left s width char)
-  (let ((len (string-length s)))
-    (if (>= len width)
-        s
-        (string-append (string-repeat char (- width len)) s))))
@@ -0,0 +1,572 @@
+//! Model API client
+//!
+//! - `llm:chat`: Send chat messages to the host's model API and return the completion
+//!
+//! The host configures an [`LlmClient`] on the sandbox (`--llm` on the command
+//! line). The client holds the API key and adds it to each request, so Lisp
+//! code can call the model but never read the key. Requests go through
+//! [`Sandbox::http_request`](crate::sandbox::Sandbox::http_request), so the
+//! network allowlist, request quota, audit log and cassettes all apply.
+
+use crate::builtins::current_sandbox;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ERR_SANDBOX_NOT_INIT};
+use crate::help::HelpEntry;
+use crate::stdlib::json::{json_to_value, value_to_json};
+use crate::value::Value;
+use serde_json::json;
+use std::collections::BTreeMap;
+
+/// Timeout for a completion when `:timeout` is not given, in milliseconds
+const DEFAULT_TIMEOUT_MS: u64 = 120_000;
+
+/// Anthropic requires a token limit; used when `:max-tokens` is not given
+const DEFAULT_MAX_TOKENS: u64 = 1024;
+
+/// The wire format a model API speaks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LlmProvider {
+    /// `POST {endpoint}/chat/completions`, as OpenAI and most compatible servers do
+    OpenAi,
+    /// `POST {endpoint}/v1/messages`, Anthropic's Messages API
+    Anthropic,
+}
+
+impl LlmProvider {
+    /// The provider's public endpoint
+    pub fn default_endpoint(self) -> &'static str {
+        match self {
+            LlmProvider::OpenAi => "https://api.openai.com/v1",
+            LlmProvider::Anthropic => "https://api.anthropic.com",
+        }
+    }
+}
+
+/// A model API scripts may call, with the key they are not allowed to see
+pub struct LlmClient {
+    provider: LlmProvider,
+    endpoint: String,
+    api_key: String,
+    default_model: Option<String>,
+}
+
+impl std::fmt::Debug for LlmClient {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LlmClient")
+            .field("provider", &self.provider)
+            .field("endpoint", &self.endpoint)
+            .field("api_key", &"<redacted>")
+            .field("default_model", &self.default_model)
+            .finish()
+    }
+}
+
+/// One completion request, ready for the sandbox
+struct ChatRequest {
+    url: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl LlmClient {
+    /// A client for `endpoint` (e.g. [`LlmProvider::default_endpoint`]),
+    /// using `default_model` when a call names none
+    pub fn new(
+        provider: LlmProvider,
+        endpoint: &str,
+        api_key: &str,
+        default_model: Option<String>,
+    ) -> Self {
+        LlmClient {
+            provider,
+            endpoint: endpoint.trim_end_matches('/').to_string(),
+            api_key: api_key.to_string(),
+            default_model,
+        }
+    }
+
+    /// Build the request for `llm:chat` options
+    fn request(&self, options: &BTreeMap<String, Value>) -> Result<ChatRequest, EvalError> {
+        let model = match options.get("model") {
+            Some(Value::String(model)) => model.to_string(),
+            None => self.default_model.clone().ok_or_else(|| {
+                EvalError::runtime_error(
+                    "llm:chat",
+                    ":model is required (the host set no default model)",
+                )
+            })?,
+            Some(other) => return Err(EvalError::type_error("llm:chat", "string", other, 1)),
+        };
+        let messages = match options.get("messages") {
+            Some(Value::List(messages)) if !messages.is_empty() => messages,
+            _ => {
+                return Err(EvalError::runtime_error(
+                    "llm:chat",
+                    ":messages must be a non-empty list of {:role :content} maps",
+                ))
+            }
+        };
+        let number = |name: &str| match options.get(name) {
+            Some(Value::Number(n)) => Ok(Some(*n)),
+            None => Ok(None),
+            Some(other) => Err(EvalError::type_error("llm:chat", "number", other, 1)),
+        };
+        let temperature = number("temperature")?;
+        let max_tokens = number("max-tokens")?.map(|n| n as u64);
+
+        let mut system = Vec::new();
+        let mut turns = Vec::new();
+        for message in messages.iter() {
+            let (role, content) = match message {
+                Value::Map(map) => match (map.get("role"), map.get("content")) {
+                    (Some(Value::String(role)), Some(content)) => {
+                        (&**role, value_to_json(content)?)
+                    }
+                    (Some(Value::Keyword(role)), Some(content)) => {
+                        (role.as_str(), value_to_json(content)?)
+                    }
+                    _ => {
+                        return Err(EvalError::runtime_error(
+                            "llm:chat",
+                            format!("message needs :role and :content: {}", message),
+                        ))
+                    }
+                },
+                other => return Err(EvalError::type_error("llm:chat", "map", other, 1)),
+            };
+            match (self.provider, role, content) {
+                // Anthropic takes the system prompt beside the messages
+                (LlmProvider::Anthropic, "system", serde_json::Value::String(text)) => {
+                    system.push(text)
+                }
+                (_, role, content) => turns.push(json!({"role": role, "content": content})),
+            }
+        }
+
+        let (url, mut headers, mut body) = match self.provider {
+            LlmProvider::OpenAi => (
+                format!("{}/chat/completions", self.endpoint),
+                vec![(
+                    "Authorization".to_string(),
+                    format!("Bearer {}", self.api_key),
+                )],
+                json!({"model": model, "messages": turns}),
+            ),
+            LlmProvider::Anthropic => (
+                format!("{}/v1/messages", self.endpoint),
+                vec![
+                    ("x-api-key".to_string(), self.api_key.clone()),
+                    ("anthropic-version".to_string(), "2023-06-01".to_string()),
+                ],
+                json!({
+                    "model": model,
+                    "messages": turns,
+                    "max_tokens": max_tokens.unwrap_or(DEFAULT_MAX_TOKENS),
+                }),
+            ),
+        };
+        headers.push(("Content-Type".to_string(), "application/json".to_string()));
+        if let Some(temperature) = temperature {
+            body["temperature"] = json!(temperature);
+        }
+        if let (LlmProvider::OpenAi, Some(max_tokens)) = (self.provider, max_tokens) {
+            body["max_tokens"] = json!(max_tokens);
+        }
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        Ok(ChatRequest {
+            url,
+            headers,
+            body: body.to_string(),
+        })
+    }
+
+    /// Turn a response body into the completion map returned by `llm:chat`
+    fn completion(&self, body: &str) -> Result<Value, String> {
+        let response: serde_json::Value =
+            serde_json::from_str(body).map_err(|e| format!("response is not JSON: {}", e))?;
+        let text = |value: &serde_json::Value| value.as_str().map(str::to_string);
+        let (content, role, stop_reason, input, output) = match self.provider {
+            LlmProvider::OpenAi => {
+                let choice = &response["choices"][0];
+                (
+                    text(&choice["message"]["content"]),
+                    text(&choice["message"]["role"]),
+                    text(&choice["finish_reason"]),
+                    &response["usage"]["prompt_tokens"],
+                    &response["usage"]["completion_tokens"],
+                )
+            }
+            LlmProvider::Anthropic => {
+                let blocks = response["content"].as_array();
+                let content = blocks.map(|blocks| {
+                    blocks
+                        .iter()
+                        .filter_map(|block| block["text"].as_str())
+                        .collect::<String>()
+                });
+                (
+                    content,
+                    text(&response["role"]),
+                    text(&response["stop_reason"]),
+                    &response["usage"]["input_tokens"],
+                    &response["usage"]["output_tokens"],
+                )
+            }
+        };
+        let content = content.ok_or_else(|| {
+            let error = &response["error"]["message"];
+            match error.as_str() {
+                Some(message) => format!("the model API answered with an error: {}", message),
+                None => "response has no completion".to_string(),
+            }
+        })?;
+
+        let mut usage = BTreeMap::new();
+        usage.insert("input-tokens".to_string(), json_to_value(input));
+        usage.insert("output-tokens".to_string(), json_to_value(output));
+        let mut map = BTreeMap::new();
+        map.insert("content".to_string(), Value::String(content.into()));
+        map.insert(
+            "role".to_string(),
+            Value::String(role.unwrap_or_else(|| "assistant".to_string()).into()),
+        );
+        map.insert("model".to_string(), json_to_value(&response["model"]));
+        map.insert(
+            "stop-reason".to_string(),
+            stop_reason.map_or(Value::Nil, |s| Value::String(s.into())),
+        );
+        map.insert("usage".to_string(), Value::Map(usage));
+        map.insert("raw".to_string(), json_to_value(&response));
+        Ok(Value::Map(map))
+    }
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// llm:chat - Send messages to the host's model API and return the completion
+fn llm_chat(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("llm:chat", ARITY_ONE, args.len()));
+    }
+    let options = match &args[0] {
+        Value::Map(options) => options,
+        other => return Err(EvalError::type_error("llm:chat", "map", other, 1)),
+    };
+    let timeout = match options.get("timeout") {
+        Some(Value::Number(ms)) => *ms as u64,
+        None => DEFAULT_TIMEOUT_MS,
+        Some(other) => return Err(EvalError::type_error("llm:chat", "number", other, 1)),
+    };
+
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("llm:chat", ERR_SANDBOX_NOT_INIT))?;
+    let client = sandbox.llm().ok_or_else(|| {
+        EvalError::runtime_error(
+            "llm:chat",
+            "no model API is configured; the host must start the sandbox with --llm",
+        )
+    })?;
+    let request = client.request(options)?;
+    let response = sandbox
+        .http_request(
+            &request.url,
+            "POST",
+            Some(request.headers),
+            Some(&request.body),
+            Some(timeout),
+        )
+        .map_err(|e| EvalError::Sandbox {
+            function: "llm:chat".to_string(),
+            message: format!("request to {} failed: {}", request.url, e),
+            error: e,
+        })?;
+    if response.status >= 400 {
+        return Err(EvalError::runtime_error(
+            "llm:chat",
+            format!(
+                "the model API answered {}: {}",
+                response.status, response.body
+            ),
+        ));
+    }
+    client
+        .completion(&response.body)
+        .map_err(|e| EvalError::runtime_error("llm:chat", e))
+}
+
+/// Register model API functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("llm:chat".to_string(), Value::BuiltIn(llm_chat));
+    crate::help::register_capability("llm:chat", "network");
+
+    crate::help::register_help(HelpEntry {
+        name: "llm:chat".to_string(),
+        signature: "(llm:chat {:model :messages [:temperature] [:max-tokens] [:timeout]})"
+            .to_string(),
+        description: "Send chat messages to the model API the host configured, and return
+the completion.
+
+The host chooses the provider and holds the API key; scripts never see it.
+The request goes through the sandbox like http-request, so it needs network
+access, must pass the allowlist and counts toward the request quota.
+
+**Parameters:**
+- :model - Model name (optional when the host set a default)
+- :messages - List of {:role :content} maps; roles are \"system\", \"user\" and \"assistant\"
+- :temperature - Sampling temperature (optional)
+- :max-tokens - Most tokens to generate (optional; 1024 for Anthropic when not given)
+- :timeout - Timeout in milliseconds (default 120000)
+
+**Returns:** A map with:
+- :content - The completion text
+- :role - Usually \"assistant\"
+- :model - The model that answered
+- :stop-reason - Why generation stopped, e.g. \"stop\" or \"end_turn\"
+- :usage - {:input-tokens n :output-tokens n}
+- :raw - The whole decoded response
+
+**Examples:**
+```lisp
+(llm:chat {:model \"gpt-4o-mini\"
+           :messages (list {:role \"system\" :content \"Answer in one word.\"}
+                           {:role \"user\" :content \"Capital of France?\"})
+           :temperature 0})
+=> {:content \"Paris\" :role \"assistant\" :stop-reason \"stop\" ...}
+
+(map-get (llm:chat {:messages (list {:role \"user\" :content \"Hi\"})}) :content)
+```
+
+**Error Conditions:**
+- No model API configured (the host did not pass --llm)
+- Network disabled, address not allowed or quota exceeded
+- An error status or an unexpected response from the API"
+            .to_string(),
+        examples: vec![
+            "(llm:chat {:model \"gpt-4o-mini\" :messages (list {:role \"user\" :content \"Hi\"})})"
+                .to_string(),
+        ],
+        related: vec!["http-request".to_string(), "json:encode".to_string()],
+        category: "Standard Library: LLM".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    fn options(pairs: &[(&str, Value)]) -> BTreeMap<String, Value> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.clone()))
+            .collect()
+    }
+
+    fn message(role: &str, content: &str) -> Value {
+        Value::Map(options(&[
+            ("role", Value::String(role.into())),
+            ("content", Value::String(content.into())),
+        ]))
+    }
+
+    fn body(request: &ChatRequest) -> serde_json::Value {
+        serde_json::from_str(&request.body).unwrap()
+    }
+
+    #[test]
+    fn test_openai_request() {
+        let client = LlmClient::new(LlmProvider::OpenAi, "http://local/v1/", "sk-test", None);
+        let request = client
+            .request(&options(&[
+                ("model", Value::String("m".into())),
+                (
+                    "messages",
+                    Value::List(vec![message("system", "be brief"), message("user", "hi")].into()),
+                ),
+                ("temperature", Value::Number(0.5)),
+            ]))
+            .unwrap();
+        assert_eq!(request.url, "http://local/v1/chat/completions");
+        assert!(request
+            .headers
+            .contains(&("Authorization".to_string(), "Bearer sk-test".to_string())));
+        let body = body(&request);
+        assert_eq!(body["model"], "m");
+        assert_eq!(body["temperature"], 0.5);
+        assert_eq!(body["messages"][0]["role"], "system");
+        assert_eq!(body["messages"][1]["content"], "hi");
+    }
+
+    #[test]
+    fn test_anthropic_request_moves_system_prompt() {
+        let client = LlmClient::new(
+            LlmProvider::Anthropic,
+            "http://local",
+            "key",
+            Some("claude".into()),
+        );
+        let request = client
+            .request(&options(&[(
+                "messages",
+                Value::List(vec![message("system", "be brief"), message("user", "hi")].into()),
+            )]))
+            .unwrap();
+        assert_eq!(request.url, "http://local/v1/messages");
+        assert!(request
+            .headers
+            .contains(&("x-api-key".to_string(), "key".to_string())));
+        let body = body(&request);
+        assert_eq!(body["model"], "claude");
+        assert_eq!(body["system"], "be brief");
+        assert_eq!(body["max_tokens"], 1024);
+        assert_eq!(body["messages"].as_array().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_request_errors() {
+        let client = LlmClient::new(LlmProvider::OpenAi, "http://local", "key", None);
+        let hi = Value::List(vec![message("user", "hi")].into());
+        // No model and no default
+        assert!(client.request(&options(&[("messages", hi)])).is_err());
+        let model = ("model", Value::String("m".into()));
+        // No messages, then a message that is not a map
+        assert!(client
+            .request(&options(std::slice::from_ref(&model)))
+            .is_err());
+        let bad = Value::List(vec![Value::String("hi".into())].into());
+        assert!(client
+            .request(&options(&[model, ("messages", bad)]))
+            .is_err());
+    }
+
+    #[test]
+    fn test_completion_maps() {
+        let openai = LlmClient::new(LlmProvider::OpenAi, "http://local", "key", None);
+        let result = openai
+            .completion(
+                r#"{"model": "m", "choices": [{"message": {"role": "assistant", "content": "Paris"},
+                    "finish_reason": "stop"}], "usage": {"prompt_tokens": 12, "completion_tokens": 1}}"#,
+            )
+            .unwrap();
+        let Value::Map(map) = result else {
+            panic!("expected map")
+        };
+        assert!(matches!(&map["content"], Value::String(s) if &**s == "Paris"));
+        assert!(matches!(&map["stop-reason"], Value::String(s) if &**s == "stop"));
+        assert!(
+            matches!(&map["usage"], Value::Map(u) if matches!(u["input-tokens"], Value::Number(n) if n == 12.0))
+        );
+
+        let anthropic = LlmClient::new(LlmProvider::Anthropic, "http://local", "key", None);
+        let result = anthropic
+            .completion(
+                r#"{"model": "c", "role": "assistant", "stop_reason": "end_turn",
+                    "content": [{"type": "text", "text": "Pa"}, {"type": "text", "text": "ris"}],
+                    "usage": {"input_tokens": 9, "output_tokens": 2}}"#,
+            )
+            .unwrap();
+        assert!(
+            matches!(result, Value::Map(map) if matches!(&map["content"], Value::String(s) if &**s == "Paris"))
+        );
+
+        let err = openai
+            .completion(r#"{"error": {"message": "bad key"}}"#)
+            .unwrap_err();
+        assert!(err.contains("bad key"), "{}", err);
+    }
+
+    #[test]
+    fn test_debug_hides_key() {
+        let client = LlmClient::new(LlmProvider::OpenAi, "http://local", "sk-secret", None);
+        assert!(!format!("{:?}", client).contains("sk-secret"));
+    }
+
+    #[test]
+    fn test_chat_needs_configured_client() {
+        use crate::builtins::swap_sandbox;
+        use crate::config::{FsConfig, NetConfig};
+        use crate::fs_backend::MemoryFs;
+        use crate::sandbox::Sandbox;
+
+        let sandbox = Sandbox::with_backend(
+            Arc::new(MemoryFs::new()),
+            FsConfig::default(),
+            NetConfig::default(),
+        );
+        let previous = swap_sandbox(Some(Arc::new(sandbox)));
+        let result = llm_chat(&[Value::Map(options(&[(
+            "messages",
+            Value::List(vec![message("user", "hi")].into()),
+        )]))]);
+        swap_sandbox(previous);
+        assert!(result.unwrap_err().to_string().contains("--llm"));
+    }
+
+    #[test]
+    #[cfg(all(feature = "native-fs", feature = "http"))]
+    fn test_chat_sends_key_through_sandbox() {
+        use crate::builtins::swap_sandbox;
+        use crate::config::{FsConfig, NetConfig};
+        use crate::sandbox::Sandbox;
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+
+        // Answer with the Authorization header the request carried
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut authorization = String::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("authorization") {
+                        authorization = value.trim().to_string();
+                    }
+                }
+            }
+            let body = json!({"model": "m", "choices": [{"message":
+                {"role": "assistant", "content": authorization}, "finish_reason": "stop"}]})
+            .to_string();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            stream.write_all(response.as_bytes()).unwrap();
+        });
+
+        let net_config = NetConfig {
+            enabled: true,
+            allowed_addresses: vec!["http://127.0.0.1".to_string()],
+            ..NetConfig::default()
+        };
+        let mut sandbox = Sandbox::new(FsConfig::default(), net_config).unwrap();
+        sandbox.set_llm(Arc::new(LlmClient::new(
+            LlmProvider::OpenAi,
+            &format!("http://{}", address),
+            "sk-live",
+            Some("m".to_string()),
+        )));
+        let previous = swap_sandbox(Some(Arc::new(sandbox)));
+        let result = llm_chat(&[Value::Map(options(&[(
+            "messages",
+            Value::List(vec![message("user", "hi")].into()),
+        )]))]);
+        swap_sandbox(previous);
+        assert!(
+            matches!(&result, Ok(Value::Map(map)) if matches!(&map["content"], Value::String(s) if &**s == "Bearer sk-live")),
+            "{:?}",
+            result.map(|v| v.to_string())
+        );
+    }
+}
@@ -0,0 +1,385 @@
+//! Logging
+//!
+//! - `log:debug`: Log a message useful only when tracking down a problem
+//! - `log:info`: Log a message about normal progress
+//! - `log:warn`: Log a message about something unexpected but recoverable
+//! - `log:error`: Log a message about a failure
+//!
+//! Each call writes one line: a UTC timestamp, the level, the message and the
+//! optional data map as JSON, like
+//! `2026-10-17T09:30:00.125Z INFO fetched page {"status":200.0}`. Lines go to
+//! stderr, or to the sandbox file the host chose (`--log-file`), so they never
+//! mix with a script's printed output. Messages below the interpreter's level
+//! (`--log-level`, info by default) are dropped, and a host can receive the
+//! records themselves through [`crate::interpreter::Interpreter::on_log`].
+
+use crate::builtins::current_sandbox;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE_OR_TWO, ERR_SANDBOX_NOT_INIT};
+use crate::help::HelpEntry;
+use crate::stdlib::json::value_to_json;
+use crate::value::Value;
+use std::cell::RefCell;
+
+/// How serious a log message is, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warn,
+    Error,
+}
+
+impl LogLevel {
+    /// The level as written in log lines: `DEBUG`, `INFO`, `WARN` or `ERROR`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LogLevel::Debug => "DEBUG",
+            LogLevel::Info => "INFO",
+            LogLevel::Warn => "WARN",
+            LogLevel::Error => "ERROR",
+        }
+    }
+}
+
+/// One message logged by a script
+#[derive(Debug, Clone)]
+pub struct LogRecord {
+    /// Unix time in seconds when the message was logged
+    pub timestamp: f64,
+    pub level: LogLevel,
+    pub message: String,
+    /// The data map passed with the message, as JSON
+    pub data: Option<serde_json::Value>,
+}
+
+impl LogRecord {
+    /// The record as one line of text, without the trailing newline
+    pub fn line(&self) -> String {
+        // Escaped so a message can never start a line of its own
+        let message = self.message.replace('\n', "\\n").replace('\r', "\\r");
+        let mut line = format!(
+            "{} {} {}",
+            format_timestamp(self.timestamp),
+            self.level.as_str(),
+            message
+        );
+        if let Some(data) = &self.data {
+            line.push(' ');
+            line.push_str(&data.to_string());
+        }
+        line
+    }
+}
+
+/// Where an interpreter's log lines go and which are kept
+#[derive(Debug, Clone)]
+pub(crate) struct LogConfig {
+    /// Least serious level written; None turns logging off
+    pub(crate) level: Option<LogLevel>,
+    /// Sandbox path lines are appended to instead of stderr
+    pub(crate) file: Option<String>,
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            level: Some(LogLevel::Info),
+            file: None,
+        }
+    }
+}
+
+thread_local! {
+    /// Log settings of the interpreter evaluating on this thread
+    static CONFIG: RefCell<LogConfig> = RefCell::new(LogConfig::default());
+}
+
+/// Replace the log settings installed on this thread, returning the previous ones
+pub(crate) fn swap_config(config: LogConfig) -> LogConfig {
+    CONFIG.with(|slot| slot.replace(config))
+}
+
+/// Unix time in seconds as an RFC 3339 UTC timestamp with milliseconds
+fn format_timestamp(unix: f64) -> String {
+    let millis = (unix * 1000.0).round() as i64;
+    let (seconds, ms) = (millis.div_euclid(1000), millis.rem_euclid(1000));
+    let (days, time) = (seconds.div_euclid(86_400), seconds.rem_euclid(86_400));
+
+    // Days since 1970-01-01 to a civil date, counting in 400-year eras from March 0000
+    let shifted = days + 719_468;
+    let era = shifted.div_euclid(146_097);
+    let day_of_era = shifted.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_from_march = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_from_march + 2) / 5 + 1;
+    let month = if month_from_march < 10 {
+        month_from_march + 3
+    } else {
+        month_from_march - 9
+    };
+    let year = era * 400 + year_of_era + i64::from(month <= 2);
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time % 3600 / 60,
+        time % 60,
+        ms
+    )
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// Log `args` (a message and an optional data map) at `level`
+fn log_at(function: &str, level: LogLevel, args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            function,
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let message = match &args[0] {
+        Value::String(message) => message.clone(),
+        other => return Err(EvalError::type_error(function, "string", other, 1)),
+    };
+    let data = match args.get(1) {
+        None => None,
+        Some(map @ Value::Map(_)) => Some(value_to_json(map)?),
+        Some(other) => return Err(EvalError::type_error(function, "map", other, 2)),
+    };
+
+    let config = CONFIG.with(|slot| slot.borrow().clone());
+    if config.level.is_none_or(|min| level < min) {
+        return Ok(Value::Nil);
+    }
+
+    let record = LogRecord {
+        timestamp: current_sandbox()
+            .map_or_else(crate::platform::unix_time, |sandbox| sandbox.now()),
+        level,
+        message: message.to_string(),
+        data,
+    };
+    let reported = crate::events::log(&record);
+    match &config.file {
+        Some(path) => {
+            let sandbox = current_sandbox()
+                .ok_or_else(|| EvalError::runtime_error(function, ERR_SANDBOX_NOT_INIT))?;
+            sandbox
+                .append_file(path, &format!("{}\n", record.line()))
+                .map_err(|e| EvalError::sandbox_error(function, e))?;
+        }
+        // A host that takes the records decides where they are shown
+        None if !reported => eprintln!("{}", record.line()),
+        None => {}
+    }
+    Ok(Value::Nil)
+}
+
+/// log:debug - Log a message at debug level
+fn log_debug(args: &[Value]) -> Result<Value, EvalError> {
+    log_at("log:debug", LogLevel::Debug, args)
+}
+
+/// log:info - Log a message at info level
+fn log_info(args: &[Value]) -> Result<Value, EvalError> {
+    log_at("log:info", LogLevel::Info, args)
+}
+
+/// log:warn - Log a message at warn level
+fn log_warn(args: &[Value]) -> Result<Value, EvalError> {
+    log_at("log:warn", LogLevel::Warn, args)
+}
+
+/// log:error - Log a message at error level
+fn log_error(args: &[Value]) -> Result<Value, EvalError> {
+    log_at("log:error", LogLevel::Error, args)
+}
+
+/// Register logging functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("log:debug".to_string(), Value::BuiltIn(log_debug));
+    env.define("log:info".to_string(), Value::BuiltIn(log_info));
+    env.define("log:warn".to_string(), Value::BuiltIn(log_warn));
+    env.define("log:error".to_string(), Value::BuiltIn(log_error));
+
+    let levels = [
+        (
+            "debug",
+            "Log a message useful only when tracking down a problem.",
+            "Debug messages are dropped unless the host lowered the level\n\
+             (`--log-level debug`).",
+            "(log:debug \"parsed rows\" {:count 42})",
+        ),
+        (
+            "info",
+            "Log a message about normal progress.",
+            "This is the default level, so info messages are written unless the\n\
+             host raised the level.",
+            "(log:info \"fetched page\" {:url url :status 200})",
+        ),
+        (
+            "warn",
+            "Log a message about something unexpected but recoverable.",
+            "Use for retries, fallbacks and skipped input.",
+            "(log:warn \"retrying request\" {:attempt 2})",
+        ),
+        (
+            "error",
+            "Log a message about a failure.",
+            "Logging an error does not stop evaluation; use error for that.",
+            "(log:error \"could not parse response\" {:body body})",
+        ),
+    ];
+    for (level, summary, note, example) in levels {
+        let name = format!("log:{}", level);
+        let related = ["log:debug", "log:info", "log:warn", "log:error"]
+            .into_iter()
+            .filter(|other| *other != name)
+            .map(str::to_string)
+            .collect();
+        crate::help::register_help(HelpEntry {
+            name: name.clone(),
+            signature: format!("({} message [data])", name),
+            description: format!(
+                "{summary}
+
+Writes one line with a UTC timestamp, the level, the message and the data
+map as JSON, to stderr or to the log file the host chose (`--log-file`).
+
+**Parameters:**
+- message: A string
+- data: Optional map of details, written as JSON
+
+**Returns:** nil
+
+**Examples:**
+```lisp
+{example}
+; 2026-10-17T09:30:00.125Z {upper} ...
+```
+
+**Error Conditions:**
+- Data that cannot be written as JSON
+- A log file that would grow past the sandbox's file size limit
+
+**Notes:** {note}",
+                upper = level.to_uppercase(),
+            ),
+            examples: vec![example.to_string()],
+            related,
+            category: "Logging".to_string(),
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::swap_sandbox;
+    use crate::config::{FsConfig, NetConfig};
+    use crate::events::{swap_handlers, EventHandlers};
+    use crate::fs_backend::{FsBackend, MemoryFs};
+    use crate::sandbox::Sandbox;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0.0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_timestamp(951_782_400.0), "2000-02-29T00:00:00.000Z");
+        assert_eq!(
+            format_timestamp(1_700_000_000.5),
+            "2023-11-14T22:13:20.500Z"
+        );
+        assert_eq!(format_timestamp(-1.0), "1969-12-31T23:59:59.000Z");
+    }
+
+    #[test]
+    fn test_record_line() {
+        let record = LogRecord {
+            timestamp: 0.0,
+            level: LogLevel::Warn,
+            message: "two\nlines".to_string(),
+            data: Some(serde_json::json!({"n": 1})),
+        };
+        assert_eq!(
+            record.line(),
+            r#"1970-01-01T00:00:00.000Z WARN two\nlines {"n":1}"#
+        );
+    }
+
+    #[test]
+    fn test_level_filter_and_callback() {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let sink = seen.clone();
+        let previous_handlers = swap_handlers(EventHandlers {
+            log: Some(Arc::new(move |record: &LogRecord| {
+                sink.lock()
+                    .unwrap()
+                    .push((record.level, record.message.clone()))
+            })),
+            ..EventHandlers::default()
+        });
+        let previous_config = swap_config(LogConfig {
+            level: Some(LogLevel::Warn),
+            file: None,
+        });
+
+        let message = |text: &str| Value::String(text.into());
+        log_info(&[message("dropped")]).unwrap();
+        log_warn(&[message("kept")]).unwrap();
+        log_error(&[message("also kept")]).unwrap();
+        swap_config(LogConfig {
+            level: None,
+            file: None,
+        });
+        log_error(&[message("logging is off")]).unwrap();
+        let bad_data = log_info(&[message("x"), Value::Number(1.0)]);
+
+        swap_config(previous_config);
+        swap_handlers(previous_handlers);
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec![
+                (LogLevel::Warn, "kept".to_string()),
+                (LogLevel::Error, "also kept".to_string())
+            ]
+        );
+        assert!(bad_data.is_err());
+    }
+
+    #[test]
+    fn test_log_file_in_sandbox() {
+        let fs = Arc::new(MemoryFs::new());
+        let mut sandbox =
+            Sandbox::with_backend(fs.clone(), FsConfig::default(), NetConfig::default());
+        sandbox.freeze_clock(1_700_000_000.5);
+        let previous_sandbox = swap_sandbox(Some(Arc::new(sandbox)));
+        let previous_config = swap_config(LogConfig {
+            level: Some(LogLevel::Debug),
+            file: Some("logs/run.log".to_string()),
+        });
+
+        let mut data = std::collections::BTreeMap::new();
+        data.insert("rows".to_string(), Value::Number(3.0));
+        log_debug(&[Value::String("loaded".into()), Value::Map(data)]).unwrap();
+        log_info(&[Value::String("done".into())]).unwrap();
+
+        swap_config(previous_config);
+        swap_sandbox(previous_sandbox);
+        assert_eq!(
+            fs.read_file("logs/run.log").unwrap(),
+            "2023-11-14T22:13:20.500Z DEBUG loaded {\"rows\":3.0}\n\
+             2023-11-14T22:13:20.500Z INFO done\n"
+        );
+    }
+}
@@ -0,0 +1,510 @@
+//! Matrices
+//!
+//! - `matrix:from-list`: Build a matrix from a list of rows
+//! - `matrix:to-list`: The rows of a matrix as lists of numbers
+//! - `matrix:shape`: Number of rows and columns
+//! - `matrix:mul`: Matrix product, or scaling by a number
+//! - `matrix:add`: Elementwise sum
+//! - `matrix:transpose`: Swap rows and columns
+//! - `matrix:map`: Apply a function to every element
+//!
+//! Elements are stored row by row in one flat buffer, so products and sums
+//! run as tight loops instead of walking nested lists. Meant for the small
+//! numeric experiments a script might do, such as projecting embeddings.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_TWO};
+use crate::eval::apply_function;
+use crate::help::HelpEntry;
+use crate::value::Value;
+use std::sync::Arc;
+
+/// A dense matrix of numbers, stored row-major
+#[derive(Debug, Clone, PartialEq)]
+pub struct Matrix {
+    rows: usize,
+    cols: usize,
+    data: Vec<f64>,
+}
+
+impl Matrix {
+    pub fn rows(&self) -> usize {
+        self.rows
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cols
+    }
+
+    fn row(&self, i: usize) -> &[f64] {
+        &self.data[i * self.cols..(i + 1) * self.cols]
+    }
+
+    fn transpose(&self) -> Matrix {
+        let mut data = Vec::with_capacity(self.data.len());
+        for j in 0..self.cols {
+            for i in 0..self.rows {
+                data.push(self.data[i * self.cols + j]);
+            }
+        }
+        Matrix {
+            rows: self.cols,
+            cols: self.rows,
+            data,
+        }
+    }
+
+    /// The matrix product, or `None` when the inner dimensions differ
+    fn mul(&self, other: &Matrix) -> Option<Matrix> {
+        if self.cols != other.rows {
+            return None;
+        }
+        let mut data = vec![0.0; self.rows * other.cols];
+        // i-k-j order walks both operands along their rows
+        for i in 0..self.rows {
+            let out = &mut data[i * other.cols..(i + 1) * other.cols];
+            for (k, a) in self.row(i).iter().enumerate() {
+                for (o, b) in out.iter_mut().zip(other.row(k)) {
+                    *o += a * b;
+                }
+            }
+        }
+        Some(Matrix {
+            rows: self.rows,
+            cols: other.cols,
+            data,
+        })
+    }
+
+    fn with_data(&self, data: Vec<f64>) -> Matrix {
+        Matrix {
+            rows: self.rows,
+            cols: self.cols,
+            data,
+        }
+    }
+}
+
+fn matrix<'a>(function: &str, value: &'a Value, position: usize) -> Result<&'a Matrix, EvalError> {
+    match value {
+        Value::Matrix(m) => Ok(m),
+        other => Err(EvalError::type_error(function, "matrix", other, position)),
+    }
+}
+
+fn shape_mismatch(function: &str, a: &Matrix, b: &Matrix) -> EvalError {
+    EvalError::runtime_error(
+        function,
+        format!(
+            "shapes do not match ({}x{} and {}x{})",
+            a.rows, a.cols, b.rows, b.cols
+        ),
+    )
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// matrix:from-list - Build a matrix from a list of equally long rows of numbers
+fn matrix_from_list(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "matrix:from-list",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let rows = match &args[0] {
+        Value::List(rows) => rows,
+        other => {
+            return Err(EvalError::type_error(
+                "matrix:from-list",
+                "list of rows",
+                other,
+                1,
+            ))
+        }
+    };
+    let mut data = Vec::new();
+    let mut cols = None;
+    for row in rows.iter() {
+        let row = crate::stdlib::vec::numbers("matrix:from-list", row, 1)?;
+        match cols {
+            None => cols = Some(row.len()),
+            Some(n) if n != row.len() => {
+                return Err(EvalError::runtime_error(
+                    "matrix:from-list",
+                    format!("rows differ in length ({} and {})", n, row.len()),
+                ))
+            }
+            Some(_) => {}
+        }
+        data.extend(row);
+    }
+    Ok(Value::Matrix(Arc::new(Matrix {
+        rows: rows.len(),
+        cols: cols.unwrap_or(0),
+        data,
+    })))
+}
+
+/// matrix:to-list - The rows of a matrix as lists of numbers
+fn matrix_to_list(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "matrix:to-list",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let m = matrix("matrix:to-list", &args[0], 1)?;
+    Ok(Value::List(
+        (0..m.rows)
+            .map(|i| Value::List(m.row(i).iter().copied().map(Value::Number).collect()))
+            .collect(),
+    ))
+}
+
+/// matrix:shape - Number of rows and columns, as a two-element list
+fn matrix_shape(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "matrix:shape",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let m = matrix("matrix:shape", &args[0], 1)?;
+    Ok(Value::List(
+        vec![Value::Number(m.rows as f64), Value::Number(m.cols as f64)].into(),
+    ))
+}
+
+/// matrix:mul - Matrix product, or every element scaled when one side is a number
+fn matrix_mul(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("matrix:mul", ARITY_TWO, args.len()));
+    }
+    let product = match (&args[0], &args[1]) {
+        (Value::Number(k), Value::Matrix(m)) | (Value::Matrix(m), Value::Number(k)) => {
+            m.with_data(m.data.iter().map(|x| x * k).collect())
+        }
+        (a, b) => {
+            let a = matrix("matrix:mul", a, 1)?;
+            let b = matrix("matrix:mul", b, 2)?;
+            a.mul(b).ok_or_else(|| shape_mismatch("matrix:mul", a, b))?
+        }
+    };
+    Ok(Value::Matrix(Arc::new(product)))
+}
+
+/// matrix:add - Elementwise sum of two matrices of the same shape
+fn matrix_add(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("matrix:add", ARITY_TWO, args.len()));
+    }
+    let a = matrix("matrix:add", &args[0], 1)?;
+    let b = matrix("matrix:add", &args[1], 2)?;
+    if a.rows != b.rows || a.cols != b.cols {
+        return Err(shape_mismatch("matrix:add", a, b));
+    }
+    let sum = a.data.iter().zip(&b.data).map(|(x, y)| x + y).collect();
+    Ok(Value::Matrix(Arc::new(a.with_data(sum))))
+}
+
+/// matrix:transpose - Swap rows and columns
+fn matrix_transpose(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "matrix:transpose",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let m = matrix("matrix:transpose", &args[0], 1)?;
+    Ok(Value::Matrix(Arc::new(m.transpose())))
+}
+
+/// matrix:map - Apply a function to every element, which must return a number
+fn matrix_map(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("matrix:map", ARITY_TWO, args.len()));
+    }
+    let m = matrix("matrix:map", &args[1], 2)?;
+    let data = m
+        .data
+        .iter()
+        .map(
+            |x| match apply_function(&args[0], vec![Value::Number(*x)])? {
+                Value::Number(n) => Ok(n),
+                other => Err(EvalError::runtime_error(
+                    "matrix:map",
+                    format!("function returned {}, expected a number", other.type_name()),
+                )),
+            },
+        )
+        .collect::<Result<Vec<_>, EvalError>>()?;
+    Ok(Value::Matrix(Arc::new(m.with_data(data))))
+}
+
+/// Register matrix functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define(
+        "matrix:from-list".to_string(),
+        Value::BuiltIn(matrix_from_list),
+    );
+    env.define("matrix:to-list".to_string(), Value::BuiltIn(matrix_to_list));
+    env.define("matrix:shape".to_string(), Value::BuiltIn(matrix_shape));
+    env.define("matrix:mul".to_string(), Value::BuiltIn(matrix_mul));
+    env.define("matrix:add".to_string(), Value::BuiltIn(matrix_add));
+    env.define(
+        "matrix:transpose".to_string(),
+        Value::BuiltIn(matrix_transpose),
+    );
+    env.define("matrix:map".to_string(), Value::BuiltIn(matrix_map));
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:from-list".to_string(),
+        signature: "(matrix:from-list rows)".to_string(),
+        description: "Build a matrix from a list of rows.
+
+**Parameters:**
+- rows: A list of equally long lists of numbers
+
+**Returns:** A matrix
+
+**Examples:**
+```lisp
+(define m (matrix:from-list '((1 2) (3 4))))
+m
+=> #<matrix 2x2>
+```
+
+**Error Conditions:**
+- A row holds something other than numbers
+- Rows differ in length"
+            .to_string(),
+        examples: vec!["(matrix:from-list '((1 2) (3 4))) => #<matrix 2x2>".to_string()],
+        related: vec!["matrix:to-list".to_string(), "matrix:shape".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:to-list".to_string(),
+        signature: "(matrix:to-list matrix)".to_string(),
+        description: "Convert a matrix back to a list of rows.
+
+**Parameters:**
+- matrix: A matrix
+
+**Returns:** A list of lists of numbers, one per row
+
+**Examples:**
+```lisp
+(matrix:to-list (matrix:transpose (matrix:from-list '((1 2) (3 4)))))
+=> ((1 3) (2 4))
+```"
+        .to_string(),
+        examples: vec!["(matrix:to-list m) => ((1 2) (3 4))".to_string()],
+        related: vec!["matrix:from-list".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:shape".to_string(),
+        signature: "(matrix:shape matrix)".to_string(),
+        description: "Get the number of rows and columns of a matrix.
+
+**Parameters:**
+- matrix: A matrix
+
+**Returns:** A list (rows columns)
+
+**Examples:**
+```lisp
+(matrix:shape (matrix:from-list '((1 2 3) (4 5 6))))
+=> (2 3)
+```"
+        .to_string(),
+        examples: vec!["(matrix:shape m) => (2 3)".to_string()],
+        related: vec!["matrix:from-list".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:mul".to_string(),
+        signature: "(matrix:mul a b)".to_string(),
+        description: "Multiply two matrices, or scale a matrix by a number.
+
+**Parameters:**
+- a: A matrix, or a number to scale b by
+- b: A matrix with as many rows as a has columns, or a number to scale a by
+
+**Returns:** A new matrix
+
+**Examples:**
+```lisp
+(matrix:to-list (matrix:mul (matrix:from-list '((1 2) (3 4)))
+                            (matrix:from-list '((5) (6)))))
+=> ((17) (39))
+
+(matrix:to-list (matrix:mul 2 (matrix:from-list '((1 2)))))
+=> ((2 4))
+```
+
+**Error Conditions:**
+- The columns of a do not match the rows of b"
+            .to_string(),
+        examples: vec![
+            "(matrix:mul a b) => #<matrix 2x1>".to_string(),
+            "(matrix:mul 2 m) => #<matrix 2x2>".to_string(),
+        ],
+        related: vec!["matrix:add".to_string(), "matrix:transpose".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:add".to_string(),
+        signature: "(matrix:add a b)".to_string(),
+        description: "Add two matrices element by element.
+
+**Parameters:**
+- a: A matrix
+- b: A matrix of the same shape
+
+**Returns:** A new matrix
+
+**Examples:**
+```lisp
+(matrix:to-list (matrix:add (matrix:from-list '((1 2))) (matrix:from-list '((10 20)))))
+=> ((11 22))
+```
+
+**Error Conditions:**
+- The shapes differ"
+            .to_string(),
+        examples: vec!["(matrix:add a b) => #<matrix 1x2>".to_string()],
+        related: vec!["matrix:mul".to_string(), "matrix:map".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:transpose".to_string(),
+        signature: "(matrix:transpose matrix)".to_string(),
+        description: "Swap the rows and columns of a matrix.
+
+**Parameters:**
+- matrix: A matrix
+
+**Returns:** A new matrix whose rows are the columns of the original
+
+**Examples:**
+```lisp
+(matrix:shape (matrix:transpose (matrix:from-list '((1 2 3)))))
+=> (3 1)
+```"
+        .to_string(),
+        examples: vec!["(matrix:transpose m) => #<matrix 3x1>".to_string()],
+        related: vec!["matrix:mul".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "matrix:map".to_string(),
+        signature: "(matrix:map f matrix)".to_string(),
+        description: "Apply a function to every element of a matrix.
+
+**Parameters:**
+- f: A function of one number that returns a number
+- matrix: A matrix
+
+**Returns:** A new matrix of the same shape
+
+**Examples:**
+```lisp
+(matrix:to-list (matrix:map (lambda (x) (* x x)) (matrix:from-list '((1 2) (3 4)))))
+=> ((1 4) (9 16))
+```
+
+**Error Conditions:**
+- f returns something other than a number"
+            .to_string(),
+        examples: vec!["(matrix:map abs m) => #<matrix 2x2>".to_string()],
+        related: vec!["matrix:add".to_string(), "map".to_string()],
+        category: "Standard Library: Matrices".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn from(rows: &[&[f64]]) -> Value {
+        let rows = rows
+            .iter()
+            .map(|row| Value::List(row.iter().copied().map(Value::Number).collect()))
+            .collect();
+        matrix_from_list(&[Value::List(rows)]).unwrap()
+    }
+
+    fn rows(result: Result<Value, EvalError>) -> String {
+        matrix_to_list(&[result.unwrap()]).unwrap().to_string()
+    }
+
+    #[test]
+    fn test_construction_and_shape() {
+        let m = from(&[&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]]);
+        assert_eq!(m.to_string(), "#<matrix 2x3>");
+        assert_eq!(
+            matrix_shape(std::slice::from_ref(&m)).unwrap().to_string(),
+            "(2 3)"
+        );
+        assert_eq!(rows(Ok(m)), "((1 2 3) (4 5 6))");
+
+        let ragged = Value::List(
+            vec![
+                Value::List(vec![Value::Number(1.0)].into()),
+                Value::List(vec![Value::Number(1.0), Value::Number(2.0)].into()),
+            ]
+            .into(),
+        );
+        assert!(matrix_from_list(&[ragged]).is_err());
+        assert!(matrix_from_list(&[Value::List(vec![Value::Nil].into())]).is_err());
+    }
+
+    #[test]
+    fn test_mul_add_transpose() {
+        let a = from(&[&[1.0, 2.0], &[3.0, 4.0]]);
+        let b = from(&[&[5.0], &[6.0]]);
+        assert_eq!(rows(matrix_mul(&[a.clone(), b.clone()])), "((17) (39))");
+        assert_eq!(
+            rows(matrix_mul(&[Value::Number(2.0), a.clone()])),
+            "((2 4) (6 8))"
+        );
+        assert!(matrix_mul(&[b.clone(), b.clone()]).is_err());
+
+        assert_eq!(rows(matrix_add(&[a.clone(), a.clone()])), "((2 4) (6 8))");
+        assert!(matrix_add(&[a.clone(), b.clone()]).is_err());
+
+        assert_eq!(rows(matrix_transpose(&[b])), "((5 6))");
+        assert_eq!(rows(matrix_transpose(&[a])), "((1 3) (2 4))");
+    }
+
+    #[test]
+    fn test_map_and_equality() {
+        let a = from(&[&[1.0, -2.0]]);
+        let negate = |args: &[Value]| -> Result<Value, EvalError> {
+            match &args[0] {
+                Value::Number(n) => Ok(Value::Number(-n)),
+                _ => Ok(Value::Nil),
+            }
+        };
+        let mapped = matrix_map(&[Value::BuiltIn(negate), a.clone()]).unwrap();
+        assert!(crate::value::values_equal(&mapped, &from(&[&[-1.0, 2.0]])));
+        assert!(!crate::value::values_equal(&mapped, &a));
+
+        let nil = |_: &[Value]| -> Result<Value, EvalError> { Ok(Value::Nil) };
+        assert!(matrix_map(&[Value::BuiltIn(nil), a]).is_err());
+    }
+}
@@ -0,0 +1,495 @@
+//! Markdown formatting helpers
+//!
+//! - `md:table`: Format a list of maps as a Markdown table
+//! - `md:code-block`: Wrap text in a fenced code block
+//! - `md:escape`: Escape text so Markdown shows it literally
+//! - `md:->html`: Render Markdown to HTML
+//!
+//! Scripts usually hand their results back to a chat interface, which shows
+//! Markdown; these build it without hand-assembling pipes and backticks.
+
+use crate::builtins::higher_order::is_truthy;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ONE_OR_TWO};
+use crate::help::HelpEntry;
+use crate::value::{write_value, PrintStyle, Value};
+
+/// Format `rows` as a table with the given columns
+///
+/// Cells show each value's display form, with `|` escaped and line breaks
+/// turned into `<br>`; columns whose values are all numbers are right-aligned.
+pub(crate) fn table(
+    rows: &[&std::collections::BTreeMap<String, Value>],
+    columns: &[String],
+) -> String {
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            columns
+                .iter()
+                .map(|column| row.get(column).map(table_cell).unwrap_or_default())
+                .collect()
+        })
+        .collect();
+    let numeric: Vec<bool> = columns
+        .iter()
+        .map(|column| {
+            rows.iter()
+                .filter_map(|row| row.get(column))
+                .all(|value| matches!(value, Value::Number(_)))
+                && rows.iter().any(|row| row.contains_key(column))
+        })
+        .collect();
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(i, column)| {
+            cells
+                .iter()
+                .map(|row| row[i].chars().count())
+                .chain([column.chars().count(), 3])
+                .max()
+                .unwrap_or(3)
+        })
+        .collect();
+
+    let line = |cells: Vec<String>| {
+        let padded: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .zip(&numeric)
+            .map(|((cell, &width), &right)| {
+                if right {
+                    format!("{:>width$}", cell)
+                } else {
+                    format!("{:<width$}", cell)
+                }
+            })
+            .collect();
+        format!("| {} |\n", padded.join(" | "))
+    };
+
+    let mut out = line(columns.iter().map(|c| escape_cell(c)).collect());
+    let rule: Vec<String> = widths
+        .iter()
+        .zip(&numeric)
+        .map(|(&width, &right)| {
+            if right {
+                format!("{}:", "-".repeat(width - 1))
+            } else {
+                "-".repeat(width)
+            }
+        })
+        .collect();
+    out.push_str(&format!("| {} |\n", rule.join(" | ")));
+    for row in cells {
+        out.push_str(&line(row));
+    }
+    out
+}
+
+fn table_cell(value: &Value) -> String {
+    let mut text = String::new();
+    let _ = write_value(&mut text, value, PrintStyle::Display);
+    escape_cell(&text)
+}
+
+fn escape_cell(text: &str) -> String {
+    text.replace('|', "\\|")
+        .replace("\r\n", "<br>")
+        .replace('\n', "<br>")
+}
+
+/// Fence `code` with enough backticks that none of its own runs close the block
+pub(crate) fn code_block(code: &str, language: &str) -> String {
+    let longest_run = code.split(|c| c != '`').map(str::len).max().unwrap_or(0);
+    let fence = "`".repeat(longest_run.max(2) + 1);
+    let newline = if code.ends_with('\n') { "" } else { "\n" };
+    format!("{}{}\n{}{}{}\n", fence, language, code, newline, fence)
+}
+
+/// Backslash-escape the characters that would make Markdown format `text`
+///
+/// Emphasis, code, link, HTML and table characters are escaped everywhere;
+/// heading, list, quote and rule markers only at the start of a line, so
+/// ordinary text like `1.5` or `a-b` stays readable.
+pub(crate) fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for (i, line) in text.split('\n').enumerate() {
+        if i > 0 {
+            out.push('\n');
+        }
+        let content = line.trim_start_matches([' ', '\t']);
+        out.push_str(&line[..line.len() - content.len()]);
+
+        // An ordered list marker is digits followed by `.` or `)`
+        let digits = content.len()
+            - content
+                .trim_start_matches(|c: char| c.is_ascii_digit())
+                .len();
+        let mut start = 0;
+        if digits > 0 && content[digits..].starts_with(['.', ')']) {
+            out.push_str(&content[..digits]);
+            out.push('\\');
+            start = digits;
+        } else if content.starts_with(['#', '-', '+', '=']) {
+            out.push('\\');
+        }
+
+        for c in content[start..].chars() {
+            if matches!(
+                c,
+                '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~' | '!'
+            ) {
+                out.push('\\');
+            }
+            out.push(c);
+        }
+    }
+    out
+}
+
+/// Render CommonMark (with tables and strikethrough) to HTML
+///
+/// Raw HTML in the source is shown as text unless `allow_html` is set, so a
+/// `<script>` in untrusted Markdown cannot reach the page as markup.
+pub(crate) fn to_html(markdown: &str, allow_html: bool) -> String {
+    use pulldown_cmark::{html, Event, Options, Parser};
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let events = Parser::new_ext(markdown, options).map(|event| match event {
+        Event::Html(text) | Event::InlineHtml(text) if !allow_html => Event::Text(text),
+        event => event,
+    });
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// md:table - Format a list of maps as a Markdown table
+fn md_table(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "md:table",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let items: &[Value] = match &args[0] {
+        Value::List(items) => items,
+        Value::Nil => &[],
+        other => return Err(EvalError::type_error("md:table", "list of maps", other, 1)),
+    };
+    let rows = items
+        .iter()
+        .map(|item| match item {
+            Value::Map(map) => Ok(map),
+            other => Err(EvalError::type_error("md:table", "list of maps", other, 1)),
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let columns = match args.get(1) {
+        None => None,
+        Some(Value::Map(options)) => match options.get("columns") {
+            None => None,
+            Some(Value::List(names)) => Some(
+                names
+                    .iter()
+                    .map(|name| match name {
+                        Value::Keyword(k) => Ok(k.clone()),
+                        Value::String(k) => Ok(k.to_string()),
+                        other => Err(EvalError::runtime_error(
+                            "md:table",
+                            format!(":columns must hold keywords, got {}", other),
+                        )),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            Some(other) => {
+                return Err(EvalError::runtime_error(
+                    "md:table",
+                    format!(":columns must be a list, got {}", other),
+                ))
+            }
+        },
+        Some(other) => return Err(EvalError::type_error("md:table", "map", other, 2)),
+    };
+    // By default every key of every row, sorted like printed maps
+    let columns = columns.unwrap_or_else(|| {
+        let mut keys: Vec<String> = rows.iter().flat_map(|row| row.keys().cloned()).collect();
+        keys.sort();
+        keys.dedup();
+        keys
+    });
+
+    if columns.is_empty() {
+        return Ok(Value::String(String::new().into()));
+    }
+    Ok(Value::String(table(&rows, &columns).into()))
+}
+
+/// md:code-block - Wrap text in a fenced code block
+fn md_code_block(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "md:code-block",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let code = match &args[0] {
+        Value::String(s) => s,
+        other => return Err(EvalError::type_error("md:code-block", "string", other, 1)),
+    };
+    let language = match args.get(1) {
+        None => "",
+        Some(Value::String(s)) => s,
+        Some(Value::Keyword(s)) => s,
+        Some(other) => return Err(EvalError::type_error("md:code-block", "string", other, 2)),
+    };
+    Ok(Value::String(code_block(code, language).into()))
+}
+
+/// md:escape - Escape Markdown formatting characters
+fn md_escape(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("md:escape", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(escape(s).into())),
+        other => Err(EvalError::type_error("md:escape", "string", other, 1)),
+    }
+}
+
+/// md:->html - Render Markdown to HTML
+fn md_to_html(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "md:->html",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let allow_html = match args.get(1) {
+        None => false,
+        Some(Value::Map(options)) => options.get("allow-html").is_some_and(is_truthy),
+        Some(other) => return Err(EvalError::type_error("md:->html", "map", other, 2)),
+    };
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(to_html(s, allow_html).into())),
+        other => Err(EvalError::type_error("md:->html", "string", other, 1)),
+    }
+}
+
+/// Register Markdown functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("md:table".to_string(), Value::BuiltIn(md_table));
+    env.define("md:code-block".to_string(), Value::BuiltIn(md_code_block));
+    env.define("md:escape".to_string(), Value::BuiltIn(md_escape));
+    env.define("md:->html".to_string(), Value::BuiltIn(md_to_html));
+
+    crate::help::register_help(HelpEntry {
+        name: "md:table".to_string(),
+        signature: "(md:table rows [options])".to_string(),
+        description: "Format a list of maps as a Markdown table.
+
+**Parameters:**
+- rows: List of maps, one per table row
+- options: Optional map with:
+  - :columns - List of keys giving the columns and their order (default: every
+    key of every row, sorted)
+
+**Returns:** The table as a string, one line per row. Cells show each value's
+display form; a row without a column's key leaves that cell empty. Columns
+holding only numbers are right-aligned.
+
+**Examples:**
+```lisp
+(md:table (list {:name \"ada\" :score 3} {:name \"bob\" :score 12}))
+=> \"| name | score |
+    | ---- | ----: |
+    | ada  |     3 |
+    | bob  |    12 |
+    \"
+
+(md:table rows {:columns '(:score :name)})
+```"
+        .to_string(),
+        examples: vec![
+            "(md:table (list {:name \"ada\"})) => \"| name |\\n| ---- |\\n| ada  |\\n\""
+                .to_string(),
+            "(md:table rows {:columns '(:name :score)})".to_string(),
+        ],
+        related: vec!["md:code-block".to_string(), "md:escape".to_string()],
+        category: "Markdown".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "md:code-block".to_string(),
+        signature: "(md:code-block code [language])".to_string(),
+        description: "Wrap text in a fenced Markdown code block.
+
+The fence is longer than any run of backticks in the code, so the code can
+itself contain fences.
+
+**Parameters:**
+- code: String to show verbatim
+- language: Optional info string, such as \"lisp\" or \"json\"
+
+**Returns:** The fenced block, ending with a newline
+
+**Examples:**
+```lisp
+(md:code-block \"(+ 1 2)\" \"lisp\")
+=> \"```lisp\\n(+ 1 2)\\n```\\n\"
+```"
+        .to_string(),
+        examples: vec!["(md:code-block (json:pretty data) \"json\")".to_string()],
+        related: vec!["md:table".to_string(), "md:escape".to_string()],
+        category: "Markdown".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "md:escape".to_string(),
+        signature: "(md:escape text)".to_string(),
+        description: "Escape text so that Markdown shows it literally.
+
+Emphasis, code, link, HTML and table characters are backslash-escaped
+everywhere; heading, list and rule markers only at the start of a line.
+
+**Parameters:**
+- text: String to escape
+
+**Returns:** The escaped string
+
+**Examples:**
+```lisp
+(md:escape \"*not bold* and a_b\")
+=> \"\\\\*not bold\\\\* and a\\\\_b\"
+
+(md:escape \"# not a heading\")
+=> \"\\\\# not a heading\"
+```"
+        .to_string(),
+        examples: vec!["(md:escape \"2 * 3\") => \"2 \\\\* 3\"".to_string()],
+        related: vec!["md:->html".to_string(), "tmpl:escape".to_string()],
+        category: "Markdown".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "md:->html".to_string(),
+        signature: "(md:->html markdown [options])".to_string(),
+        description: "Render Markdown (CommonMark with tables and strikethrough) to HTML.
+
+**Parameters:**
+- markdown: Markdown source
+- options: Optional map with:
+  - :allow-html - Pass HTML written in the Markdown through as markup
+    (default: #f, which escapes it so it shows as text)
+
+**Returns:** HTML string
+
+**Examples:**
+```lisp
+(md:->html \"# Title\\n\\nSome *text*.\")
+=> \"<h1>Title</h1>\\n<p>Some <em>text</em>.</p>\\n\"
+
+(md:->html \"a <b>b</b>\")
+=> \"<p>a &lt;b&gt;b&lt;/b&gt;</p>\\n\"
+
+(md:->html \"a <b>b</b>\" {:allow-html #t})
+=> \"<p>a <b>b</b></p>\\n\"
+```
+
+**Notes:** Only allow HTML for Markdown you wrote yourself; even escaped,
+links keep their targets as given."
+            .to_string(),
+        examples: vec![
+            "(md:->html \"*hi*\") => \"<p><em>hi</em></p>\\n\"".to_string(),
+            "(md:->html \"<b>hi</b>\" {:allow-html #t}) => \"<p><b>hi</b></p>\\n\"".to_string(),
+        ],
+        related: vec!["md:escape".to_string(), "md:table".to_string()],
+        category: "Markdown".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse;
+
+    fn call(function: fn(&[Value]) -> Result<Value, EvalError>, source: &str) -> String {
+        let args = match parse(source).unwrap() {
+            Value::List(args) => args,
+            other => panic!("expected argument list, got {}", other),
+        };
+        match function(&args).unwrap() {
+            Value::String(s) => s.to_string(),
+            other => panic!("expected string, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            call(md_table, r#"(({:name "ada" :score 3} {:name "b|c" :score 12 :x "a\nb"}))"#),
+            "| name | score | x      |\n| ---- | ----: | ------ |\n| ada  |     3 |        |\n| b\\|c |    12 | a<br>b |\n"
+        );
+        assert_eq!(
+            call(md_table, r#"(({:a 1 :b 2}) {:columns (:b :a)})"#),
+            "|   b |   a |\n| --: | --: |\n|   2 |   1 |\n"
+        );
+        assert!(md_table(&[Value::List(vec![Value::Number(1.0)].into())]).is_err());
+    }
+
+    #[test]
+    fn test_code_block_and_escape() {
+        assert_eq!(code_block("(+ 1 2)", "lisp"), "```lisp\n(+ 1 2)\n```\n");
+        assert_eq!(code_block("a ```` b\n", ""), "`````\na ```` b\n`````\n");
+        assert_eq!(
+            escape("# x *y* 1.5\n  - [a](b)\n2. c_d"),
+            "\\# x \\*y\\* 1.5\n  \\- \\[a\\](b)\n2\\. c\\_d"
+        );
+    }
+
+    #[test]
+    fn test_to_html() {
+        assert_eq!(
+            to_html("# Title\n\nSome *text*.", false),
+            "<h1>Title</h1>\n<p>Some <em>text</em>.</p>\n"
+        );
+        assert!(to_html("| a |\n| - |\n| 1 |\n", false).contains("<table>"));
+    }
+
+    #[test]
+    fn test_to_html_escapes_raw_html_unless_allowed() {
+        let source = "<script>alert(1)</script>\n\nhi <b onclick=\"x()\">there</b>";
+        let escaped = call(md_to_html, &format!("({:?})", source));
+        assert!(
+            !escaped.contains("<script>") && !escaped.contains("<b "),
+            "{}",
+            escaped
+        );
+        assert!(
+            escaped.contains("&lt;script&gt;alert(1)&lt;/script&gt;"),
+            "{}",
+            escaped
+        );
+        assert!(
+            escaped.contains(r#"hi &lt;b onclick="x()"&gt;there&lt;/b&gt;"#),
+            "{}",
+            escaped
+        );
+
+        let allowed = call(md_to_html, &format!("({:?} {{:allow-html #t}})", source));
+        assert!(allowed.contains("<script>alert(1)</script>"), "{}", allowed);
+        assert!(
+            allowed.contains("<b onclick=\"x()\">there</b>"),
+            "{}",
+            allowed
+        );
+    }
+}
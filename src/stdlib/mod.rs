@@ -15,12 +15,49 @@
 //! that form a cohesive API around a specific concept. See CLAUDE.md "Naming Conventions"
 //! section for detailed guidance.
 
-use crate::env::Environment;
-use std::rc::Rc;
+use crate::env::EnvRef;
 
+pub mod decimal;
+#[cfg(feature = "html")]
+pub mod html;
+#[cfg(feature = "network")]
+pub mod http;
 pub mod json;
+#[cfg(feature = "network")]
+pub mod llm;
+pub mod log;
+pub mod matrix;
+#[cfg(feature = "markdown")]
+pub mod md;
+pub mod rate;
+pub mod tmpl;
+pub mod tokens;
+pub mod url;
+pub mod vec;
+pub mod vstore;
+#[cfg(feature = "websocket")]
+pub mod ws;
 
 /// Register all stdlib modules in the environment
-pub fn register_stdlib(env: Rc<Environment>) {
+pub fn register_stdlib(env: EnvRef) {
     json::register(&env);
+    decimal::register(&env);
+    #[cfg(feature = "html")]
+    html::register(&env);
+    log::register(&env);
+    matrix::register(&env);
+    #[cfg(feature = "markdown")]
+    md::register(&env);
+    rate::register(&env);
+    tmpl::register(&env);
+    tokens::register(&env);
+    url::register(&env);
+    vec::register(&env);
+    vstore::register(&env);
+    #[cfg(feature = "network")]
+    http::register(&env);
+    #[cfg(feature = "network")]
+    llm::register(&env);
+    #[cfg(feature = "websocket")]
+    ws::register(&env);
 }
@@ -0,0 +1,301 @@
+//! Rate limiting
+//!
+//! - `rate:limiter`: Create a limiter allowing a number of acquisitions per second or minute
+//! - `rate:acquire`: Wait until a limiter allows another acquisition
+//!
+//! Limiters are token buckets read against the platform clock, so polite
+//! scraping and agent loops need no hand-written sleep arithmetic. Every
+//! `rate:acquire` also waits for the sandbox's own limiter when the host set
+//! `NetConfig::max_rate`, a floor no script limiter can go below.
+
+use crate::builtins::current_sandbox;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE};
+use crate::eval::active_cancellation;
+use crate::help::HelpEntry;
+use crate::value::Value;
+use std::sync::{Arc, Mutex};
+
+/// A token bucket refilled at a steady rate
+///
+/// The bucket starts full. Acquiring takes a token; when none is left the
+/// caller is given a time to wait instead. Tokens may be reserved ahead of
+/// the refill, so threads sharing a limiter queue up rather than race.
+#[derive(Debug)]
+pub struct RateLimiter {
+    per_sec: f64,
+    burst: f64,
+    state: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Tokens available; negative when acquisitions are waiting for refills
+    tokens: f64,
+    /// Seconds since the Unix epoch when `tokens` was last brought up to date
+    updated: f64,
+}
+
+impl RateLimiter {
+    /// A limiter allowing `per_sec` acquisitions a second, with up to `burst` at once
+    pub fn new(per_sec: f64, burst: f64) -> Self {
+        RateLimiter {
+            per_sec,
+            burst,
+            state: Mutex::new(Bucket {
+                tokens: burst,
+                updated: crate::platform::unix_time(),
+            }),
+        }
+    }
+
+    /// Acquisitions allowed per second
+    pub fn per_sec(&self) -> f64 {
+        self.per_sec
+    }
+
+    /// Take a token, returning how many seconds to wait before using it
+    pub fn reserve(&self) -> f64 {
+        self.reserve_at(crate::platform::unix_time())
+    }
+
+    fn reserve_at(&self, now: f64) -> f64 {
+        let mut bucket = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let elapsed = (now - bucket.updated).max(0.0);
+        bucket.tokens = (bucket.tokens + elapsed * self.per_sec).min(self.burst);
+        bucket.updated = now;
+        bucket.tokens -= 1.0;
+        if bucket.tokens >= 0.0 {
+            0.0
+        } else {
+            -bucket.tokens / self.per_sec
+        }
+    }
+}
+
+/// Sleep for `seconds`, stopping early with [`EvalError::Interrupted`] if the
+/// evaluation is cancelled
+fn wait(seconds: f64) -> Result<(), EvalError> {
+    let cancel = active_cancellation();
+    let stopwatch = crate::platform::Stopwatch::start();
+    let total_ms = seconds * 1000.0;
+    loop {
+        if cancel.as_ref().is_some_and(|token| token.is_cancelled()) {
+            return Err(EvalError::Interrupted);
+        }
+        let left_ms = total_ms - stopwatch.elapsed_ms();
+        if left_ms <= 0.0 {
+            return Ok(());
+        }
+        crate::platform::sleep_ms(left_ms.min(50.0));
+    }
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// rate:limiter - Create a limiter from :per-sec or :per-min, and optional :burst
+fn rate_limiter(args: &[Value]) -> Result<Value, EvalError> {
+    if !args.len().is_multiple_of(2) {
+        return Err(EvalError::runtime_error(
+            "rate:limiter",
+            "expected keyword-value pairs, like (rate:limiter :per-sec 2)",
+        ));
+    }
+    let mut per_sec = None;
+    let mut burst = 1.0;
+    for (i, pair) in args.chunks(2).enumerate() {
+        let n = match &pair[1] {
+            Value::Number(n) if *n > 0.0 && n.is_finite() => *n,
+            other => {
+                return Err(EvalError::type_error(
+                    "rate:limiter",
+                    "positive number",
+                    other,
+                    2 * i + 2,
+                ))
+            }
+        };
+        match &pair[0] {
+            Value::Keyword(k) if k == "per-sec" => per_sec = Some(n),
+            Value::Keyword(k) if k == "per-min" => per_sec = Some(n / 60.0),
+            Value::Keyword(k) if k == "burst" => burst = n.max(1.0),
+            other => {
+                return Err(EvalError::runtime_error(
+                    "rate:limiter",
+                    format!(
+                        "unknown option {}; expected :per-sec, :per-min or :burst",
+                        other
+                    ),
+                ))
+            }
+        }
+    }
+    let per_sec = per_sec.ok_or_else(|| {
+        EvalError::runtime_error("rate:limiter", "a rate is required: :per-sec or :per-min")
+    })?;
+    Ok(Value::RateLimiter(Arc::new(RateLimiter::new(
+        per_sec, burst,
+    ))))
+}
+
+/// rate:acquire - Wait for the limiter (and the sandbox's floor), returning milliseconds waited
+fn rate_acquire(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "rate:acquire",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let limiter = match &args[0] {
+        Value::RateLimiter(limiter) => limiter,
+        other => {
+            return Err(EvalError::type_error(
+                "rate:acquire",
+                "rate limiter",
+                other,
+                1,
+            ))
+        }
+    };
+    let mut seconds = limiter.reserve();
+    let sandbox = current_sandbox();
+    if let Some(floor) = sandbox.as_deref().and_then(|sandbox| sandbox.rate_floor()) {
+        seconds = seconds.max(floor.reserve());
+    }
+    wait(seconds)?;
+    Ok(Value::Number((seconds * 1000.0).round()))
+}
+
+/// Register rate limiting functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("rate:limiter".to_string(), Value::BuiltIn(rate_limiter));
+    env.define("rate:acquire".to_string(), Value::BuiltIn(rate_acquire));
+
+    crate::help::register_help(HelpEntry {
+        name: "rate:limiter".to_string(),
+        signature: "(rate:limiter :per-sec n [:burst b])".to_string(),
+        description: "Create a rate limiter for rate:acquire.
+
+The limiter is a token bucket: it allows `n` acquisitions a second on
+average, and up to `b` in quick succession after a quiet spell. One limiter
+can be shared by threads started with spawn.
+
+**Parameters:**
+- :per-sec n - Acquisitions per second (or :per-min n for per minute)
+- :burst b - Acquisitions allowed back to back (default 1)
+
+**Returns:** A rate limiter
+
+**Examples:**
+```lisp
+(define polite (rate:limiter :per-sec 2))
+(define api (rate:limiter :per-min 30 :burst 5))
+```"
+        .to_string(),
+        examples: vec![
+            "(rate:limiter :per-sec 2)".to_string(),
+            "(rate:limiter :per-min 30 :burst 5)".to_string(),
+        ],
+        related: vec!["rate:acquire".to_string()],
+        category: "Rate Limiting".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "rate:acquire".to_string(),
+        signature: "(rate:acquire limiter)".to_string(),
+        description: "Wait until a rate limiter allows another acquisition.
+
+When the host has set a sandbox-wide rate (`max_rate` in the [network]
+section of the policy file), every acquisition also waits for that, so
+scripts cannot go faster than the host allows.
+
+**Parameters:**
+- limiter: A limiter from rate:limiter
+
+**Returns:** Milliseconds spent waiting (0 when no wait was needed)
+
+**Examples:**
+```lisp
+(define polite (rate:limiter :per-sec 2))
+(map (lambda (url)
+       (rate:acquire polite)
+       (http-request url {:method \"GET\"}))
+     urls)
+```
+
+**Notes:** An interrupt (Ctrl-C) stops the wait."
+            .to_string(),
+        examples: vec!["(rate:acquire polite) => 0".to_string()],
+        related: vec!["rate:limiter".to_string(), "http:with-retry".to_string()],
+        category: "Rate Limiting".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_at_rate() {
+        let limiter = RateLimiter::new(2.0, 2.0);
+        let start = crate::platform::unix_time();
+        // The burst is free, then each token waits half a second more
+        assert_eq!(limiter.reserve_at(start), 0.0);
+        assert_eq!(limiter.reserve_at(start), 0.0);
+        assert_eq!(limiter.reserve_at(start), 0.5);
+        assert_eq!(limiter.reserve_at(start), 1.0);
+        // A quiet spell refills, but never beyond the burst
+        assert_eq!(limiter.reserve_at(start + 10.0), 0.0);
+        assert_eq!(limiter.reserve_at(start + 10.0), 0.0);
+        assert_eq!(limiter.reserve_at(start + 10.0), 0.5);
+    }
+
+    #[test]
+    fn test_acquire_waits() {
+        let limiter =
+            rate_limiter(&[Value::Keyword("per-sec".into()), Value::Number(20.0)]).unwrap();
+        let acquire = || match rate_acquire(std::slice::from_ref(&limiter)).unwrap() {
+            Value::Number(ms) => ms,
+            other => panic!("expected number, got {}", other),
+        };
+        assert_eq!(acquire(), 0.0);
+        let stopwatch = crate::platform::Stopwatch::start();
+        assert!(acquire() > 0.0);
+        assert!(stopwatch.elapsed_ms() >= 40.0);
+    }
+
+    #[test]
+    fn test_limiter_options() {
+        let per_min = rate_limiter(&[Value::Keyword("per-min".into()), Value::Number(30.0)]);
+        assert!(matches!(per_min, Ok(Value::RateLimiter(l)) if l.per_sec() == 0.5));
+        assert!(rate_limiter(&[]).is_err());
+        assert!(rate_limiter(&[Value::Keyword("per-sec".into())]).is_err());
+        assert!(rate_limiter(&[Value::Keyword("per-sec".into()), Value::Number(0.0)]).is_err());
+        assert!(rate_limiter(&[Value::Keyword("speed".into()), Value::Number(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_sandbox_rate_is_a_floor() {
+        use crate::builtins::swap_sandbox;
+        use crate::config::{FsConfig, NetConfig};
+        use crate::fs_backend::MemoryFs;
+        use crate::sandbox::Sandbox;
+
+        let net_config = NetConfig {
+            max_rate: Some(20.0),
+            ..NetConfig::default()
+        };
+        let sandbox =
+            Sandbox::with_backend(Arc::new(MemoryFs::new()), FsConfig::default(), net_config);
+        let previous = swap_sandbox(Some(Arc::new(sandbox)));
+        let fast =
+            rate_limiter(&[Value::Keyword("per-sec".into()), Value::Number(1000.0)]).unwrap();
+        rate_acquire(std::slice::from_ref(&fast)).unwrap();
+        let waited = rate_acquire(std::slice::from_ref(&fast));
+        swap_sandbox(previous);
+        assert!(matches!(waited, Ok(Value::Number(ms)) if ms >= 40.0));
+    }
+}
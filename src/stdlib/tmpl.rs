@@ -0,0 +1,442 @@
+//! Mustache-style templates
+//!
+//! - `tmpl:render`: Fill a template from a map of data
+//! - `tmpl:escape`: Escape `&`, `<`, `>`, `"` and `'` for HTML
+//!
+//! Template syntax:
+//! - `{{name}}` - Value of `name`, HTML-escaped; `{{user.name}}` looks inside maps
+//! - `{{{name}}}` or `{{& name}}` - Value of `name`, not escaped
+//! - `{{#name}}...{{/name}}` - Section: repeated for each element of a list,
+//!   rendered once with a map's keys in scope, or once if the value is true
+//! - `{{^name}}...{{/name}}` - Inverted section: rendered only if `name` is
+//!   missing, nil, #f or an empty list
+//! - `{{.}}` - The current element inside a section over a list
+//! - `{{! comment}}` - Left out of the output
+//!
+//! Names are looked up from the innermost section outwards, ending at the
+//! data map. Section, inverted, closing and comment tags on a line of their
+//! own take the whole line with them, so they do not leave blank lines.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_TWO};
+use crate::help::HelpEntry;
+use crate::value::{write_value, PrintStyle, Value};
+
+/// A parsed piece of a template
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Variable {
+        name: String,
+        escape: bool,
+    },
+    Section {
+        name: String,
+        inverted: bool,
+        body: Vec<Node>,
+    },
+}
+
+/// Render `template` with `data` in scope
+pub(crate) fn render(template: &str, data: &Value) -> Result<String, String> {
+    let nodes = parse(template)?;
+    let mut out = String::new();
+    let mut scopes = vec![data];
+    render_nodes(&nodes, &mut scopes, &mut out);
+    Ok(out)
+}
+
+/// Escape the characters that are special in HTML text and attribute values
+pub(crate) fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ============================================================================
+// Parsing
+// ============================================================================
+
+/// A section still waiting for its closing tag
+struct OpenSection {
+    name: String,
+    inverted: bool,
+    /// Nodes that came before the section opened
+    outer: Vec<Node>,
+}
+
+fn parse(template: &str) -> Result<Vec<Node>, String> {
+    let mut nodes = Vec::new();
+    let mut open: Vec<OpenSection> = Vec::new();
+    let mut text = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        text.push_str(&rest[..start]);
+        let after = &rest[start..];
+        let (tag, raw, length) = if let Some(inner) = after.strip_prefix("{{{") {
+            let end = inner
+                .find("}}}")
+                .ok_or_else(|| unclosed_tag(template, after))?;
+            (&inner[..end], true, end + 6)
+        } else {
+            let inner = &after[2..];
+            let end = inner
+                .find("}}")
+                .ok_or_else(|| unclosed_tag(template, after))?;
+            (&inner[..end], false, end + 4)
+        };
+        rest = &after[length..];
+
+        let tag = tag.trim();
+        if raw {
+            flush(&mut text, &mut nodes);
+            nodes.push(Node::Variable {
+                name: tag.to_string(),
+                escape: false,
+            });
+            continue;
+        }
+
+        let sigil = tag.chars().next().unwrap_or(' ');
+        if matches!(sigil, '#' | '^' | '/' | '!') {
+            let before = &template[..template.len() - after.len()];
+            rest = strip_standalone(&mut text, before, rest);
+        }
+        flush(&mut text, &mut nodes);
+        let name = tag.get(1..).unwrap_or("").trim().to_string();
+        match sigil {
+            '!' => {}
+            '#' | '^' => open.push(OpenSection {
+                name,
+                inverted: sigil == '^',
+                outer: std::mem::take(&mut nodes),
+            }),
+            '/' => {
+                let section = open.pop().ok_or_else(|| {
+                    format!("{{{{/{}}}}} closes a section that was never opened", name)
+                })?;
+                if section.name != name {
+                    return Err(format!(
+                        "{{{{/{}}}}} does not match the open section {{{{#{}}}}}",
+                        name, section.name
+                    ));
+                }
+                let body = std::mem::replace(&mut nodes, section.outer);
+                nodes.push(Node::Section {
+                    name,
+                    inverted: section.inverted,
+                    body,
+                });
+            }
+            '&' => nodes.push(Node::Variable {
+                name,
+                escape: false,
+            }),
+            _ => nodes.push(Node::Variable {
+                name: tag.to_string(),
+                escape: true,
+            }),
+        }
+    }
+
+    if let Some(section) = open.last() {
+        return Err(format!("section {{{{#{}}}}} is never closed", section.name));
+    }
+    text.push_str(rest);
+    flush(&mut text, &mut nodes);
+    Ok(nodes)
+}
+
+fn flush(text: &mut String, nodes: &mut Vec<Node>) {
+    if !text.is_empty() {
+        nodes.push(Node::Text(std::mem::take(text)));
+    }
+}
+
+fn unclosed_tag(template: &str, at: &str) -> String {
+    let line = template[..template.len() - at.len()].matches('\n').count() + 1;
+    format!("tag on line {} is never closed with }}}}", line)
+}
+
+/// Drop the rest of the line after a block tag, and the indentation before it,
+/// when the tag is the only thing on its line
+///
+/// `before` is the template up to the tag and `rest` the template after it;
+/// `text` is the pending text, which ends with the tag's indentation.
+fn strip_standalone<'a>(text: &mut String, before: &str, rest: &'a str) -> &'a str {
+    let blank = |s: &str| s.chars().all(|c| c == ' ' || c == '\t' || c == '\r');
+    let indent = &before[before.rfind('\n').map_or(0, |i| i + 1)..];
+    let line_end = rest.find('\n');
+    if !blank(indent) || !blank(&rest[..line_end.unwrap_or(rest.len())]) {
+        return rest;
+    }
+    text.truncate(text.len() - indent.len());
+    line_end.map_or("", |end| &rest[end + 1..])
+}
+
+// ============================================================================
+// Rendering
+// ============================================================================
+
+fn render_nodes<'a>(nodes: &'a [Node], scopes: &mut Vec<&'a Value>, out: &mut String) {
+    for node in nodes {
+        match node {
+            Node::Text(text) => out.push_str(text),
+            Node::Variable { name, escape } => {
+                let text = lookup(scopes, name).map(display).unwrap_or_default();
+                if *escape {
+                    out.push_str(&escape_html(&text));
+                } else {
+                    out.push_str(&text);
+                }
+            }
+            Node::Section {
+                name,
+                inverted,
+                body,
+            } => {
+                let value = lookup(scopes, name);
+                if *inverted {
+                    if !value.is_some_and(is_true) {
+                        render_nodes(body, scopes, out);
+                    }
+                    continue;
+                }
+                match value {
+                    Some(Value::List(items)) => {
+                        for item in items.iter() {
+                            render_scoped(body, scopes, item, out);
+                        }
+                    }
+                    Some(value) if is_true(value) => render_scoped(body, scopes, value, out),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+fn render_scoped<'a>(
+    body: &'a [Node],
+    scopes: &mut Vec<&'a Value>,
+    scope: &'a Value,
+    out: &mut String,
+) {
+    scopes.push(scope);
+    render_nodes(body, scopes, out);
+    scopes.pop();
+}
+
+/// Find `name` (dotted names look inside maps) from the innermost scope outwards
+fn lookup<'a>(scopes: &[&'a Value], name: &str) -> Option<&'a Value> {
+    if name == "." {
+        return scopes.last().copied();
+    }
+    let mut parts = name.split('.');
+    let first = parts.next()?;
+    let mut value = scopes.iter().rev().find_map(|scope| match scope {
+        Value::Map(map) => map.get(first),
+        _ => None,
+    })?;
+    for part in parts {
+        value = match value {
+            Value::Map(map) => map.get(part)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+fn is_true(value: &Value) -> bool {
+    !matches!(value, Value::Nil | Value::Bool(false))
+        && !matches!(value, Value::List(items) if items.is_empty())
+}
+
+fn display(value: &Value) -> String {
+    let mut text = String::new();
+    let _ = write_value(&mut text, value, PrintStyle::Display);
+    text
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// tmpl:render - Fill a template from a map
+fn tmpl_render(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("tmpl:render", ARITY_TWO, args.len()));
+    }
+    let template = match &args[0] {
+        Value::String(s) => s,
+        other => return Err(EvalError::type_error("tmpl:render", "string", other, 1)),
+    };
+    if !matches!(args[1], Value::Map(_)) {
+        return Err(EvalError::type_error("tmpl:render", "map", &args[1], 2));
+    }
+    render(template, &args[1])
+        .map(|s| Value::String(s.into()))
+        .map_err(|e| EvalError::runtime_error("tmpl:render", e))
+}
+
+/// tmpl:escape - Escape text for HTML
+fn tmpl_escape(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("tmpl:escape", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(escape_html(s).into())),
+        other => Err(EvalError::type_error("tmpl:escape", "string", other, 1)),
+    }
+}
+
+/// Register template functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("tmpl:render".to_string(), Value::BuiltIn(tmpl_render));
+    env.define("tmpl:escape".to_string(), Value::BuiltIn(tmpl_escape));
+
+    crate::help::register_help(HelpEntry {
+        name: "tmpl:render".to_string(),
+        signature: "(tmpl:render template data)".to_string(),
+        description: "Fill a mustache-style template with values from a map.
+
+**Tags:**
+- {{name}} - Value of name, HTML-escaped; {{user.name}} looks inside maps
+- {{{name}}} or {{& name}} - Value of name, not escaped
+- {{#name}}...{{/name}} - Repeated for each element of a list, rendered once
+  with a map's keys in scope, or once if the value is true
+- {{^name}}...{{/name}} - Rendered only if name is missing, nil, #f or an empty list
+- {{.}} - The current element inside a section over a list
+- {{! comment}} - Left out of the output
+
+**Parameters:**
+- template: Template string
+- data: Map whose keys are the names the template uses
+
+**Returns:** The rendered string. Missing names render as nothing; a section
+that is never closed, or closed with the wrong name, is an error.
+
+**Examples:**
+```lisp
+(tmpl:render \"Hello {{name}}!\" {:name \"Ada\"})
+=> \"Hello Ada!\"
+
+(tmpl:render \"{{#items}}- {{name}}: {{qty}}\\n{{/items}}{{^items}}Nothing\\n{{/items}}\"
+             {:items (list {:name \"apples\" :qty 3} {:name \"pears\" :qty 5})})
+=> \"- apples: 3\\n- pears: 5\\n\"
+
+(tmpl:render \"<p>{{text}}</p>\" {:text \"a < b\"})
+=> \"<p>a &lt; b</p>\"
+```
+
+**Notes:** Section tags on a line of their own do not leave blank lines."
+            .to_string(),
+        examples: vec![
+            "(tmpl:render \"Hello {{name}}!\" {:name \"Ada\"}) => \"Hello Ada!\"".to_string(),
+            "(tmpl:render \"{{#xs}}<{{.}}>{{/xs}}\" {:xs '(1 2)}) => \"<1><2>\"".to_string(),
+        ],
+        related: vec!["tmpl:escape".to_string(), "str".to_string()],
+        category: "Templates".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "tmpl:escape".to_string(),
+        signature: "(tmpl:escape text)".to_string(),
+        description: "Escape &, <, >, \" and ' so that text can be placed in HTML.
+
+This is the escaping {{name}} tags in tmpl:render apply.
+
+**Parameters:**
+- text: String to escape
+
+**Returns:** The escaped string
+
+**Examples:**
+```lisp
+(tmpl:escape \"<b>Tom & Jerry</b>\")
+=> \"&lt;b&gt;Tom &amp; Jerry&lt;/b&gt;\"
+```"
+        .to_string(),
+        examples: vec!["(tmpl:escape \"a < b\") => \"a &lt; b\"".to_string()],
+        related: vec!["tmpl:render".to_string()],
+        category: "Templates".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse as read;
+
+    fn render_with(template: &str, data: &str) -> Result<String, String> {
+        render(template, &read(data).unwrap())
+    }
+
+    #[test]
+    fn test_variables_and_escaping() {
+        let data = r#"{:name "<Ada>" :user {:age 36}}"#;
+        assert_eq!(
+            render_with(
+                "{{name}} {{{name}}} {{& name}} {{user.age}} [{{missing}}]",
+                data
+            )
+            .unwrap(),
+            "&lt;Ada&gt; <Ada> <Ada> 36 []"
+        );
+    }
+
+    #[test]
+    fn test_sections_loop_and_branch() {
+        let data = r#"{:items ({:n "a"} {:n "b"}) :xs (1 2) :on #t :off #f :none ()}"#;
+        assert_eq!(
+            render_with("{{#items}}[{{n}}]{{/items}} {{#xs}}{{.}},{{/xs}}", data).unwrap(),
+            "[a][b] 1,2,"
+        );
+        assert_eq!(
+            render_with(
+                "{{#on}}yes{{/on}}{{#off}}no{{/off}}{{^none}}empty{{/none}}",
+                data
+            )
+            .unwrap(),
+            "yesempty"
+        );
+        // Outer names stay visible inside a section
+        assert_eq!(
+            render_with("{{#items}}{{n}}{{on}} {{/items}}", data).unwrap(),
+            "a#t b#t "
+        );
+    }
+
+    #[test]
+    fn test_standalone_tags_take_their_line() {
+        let template = "Items:\n  {{#xs}}\n  - {{.}}\n  {{/xs}}\n{{! done }}\nEnd\n";
+        assert_eq!(
+            render_with(template, "{:xs (1 2)}").unwrap(),
+            "Items:\n  - 1\n  - 2\nEnd\n"
+        );
+    }
+
+    #[test]
+    fn test_malformed_templates() {
+        assert!(render_with("{{#a}}x", "{}")
+            .unwrap_err()
+            .contains("never closed"));
+        assert!(render_with("{{#a}}x{{/b}}", "{}")
+            .unwrap_err()
+            .contains("does not match"));
+        assert!(render_with("x{{/a}}", "{}")
+            .unwrap_err()
+            .contains("never opened"));
+        assert!(render_with("{{name", "{}").unwrap_err().contains("line 1"));
+    }
+}
@@ -0,0 +1,306 @@
+//! Token counting
+//!
+//! - `llm:count-tokens`: Count the tokens in a string or a list of chat messages
+//!
+//! Lets scripts budget a model's context before calling out to it. Exact
+//! counts use OpenAI's BPE encodings through tiktoken-rs, compiled in with
+//! the `tokenizer` feature; every build has the `"approx"` estimate, which
+//! needs no vocabulary.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE_OR_TWO};
+use crate::help::HelpEntry;
+use crate::value::Value;
+
+/// Tokens added for each chat message's framing, as OpenAI's chat models count them
+const TOKENS_PER_MESSAGE: usize = 3;
+
+/// Tokens that prime the model's reply after the last message
+const TOKENS_PER_REPLY: usize = 3;
+
+/// How text is split into tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    /// A vocabulary-free estimate
+    Approx,
+    /// GPT-3.5 and GPT-4
+    #[cfg(feature = "tokenizer")]
+    Cl100kBase,
+    /// GPT-4o and later
+    #[cfg(feature = "tokenizer")]
+    O200kBase,
+}
+
+impl Encoding {
+    /// The encoding used when `:encoding` is not given
+    fn default_for_build() -> Self {
+        #[cfg(feature = "tokenizer")]
+        return Encoding::O200kBase;
+        #[cfg(not(feature = "tokenizer"))]
+        return Encoding::Approx;
+    }
+
+    fn from_name(name: &str) -> Result<Self, String> {
+        match name {
+            "approx" => Ok(Encoding::Approx),
+            #[cfg(feature = "tokenizer")]
+            "cl100k_base" => Ok(Encoding::Cl100kBase),
+            #[cfg(feature = "tokenizer")]
+            "o200k_base" => Ok(Encoding::O200kBase),
+            #[cfg(not(feature = "tokenizer"))]
+            "cl100k_base" | "o200k_base" => Err(format!(
+                "encoding \"{}\" needs a build with the tokenizer feature; use \"approx\"",
+                name
+            )),
+            _ => Err(format!(
+                "unknown encoding \"{}\"; expected \"approx\", \"cl100k_base\" or \"o200k_base\"",
+                name
+            )),
+        }
+    }
+
+    fn count(self, text: &str) -> usize {
+        match self {
+            Encoding::Approx => approx_tokens(text),
+            #[cfg(feature = "tokenizer")]
+            Encoding::Cl100kBase => tiktoken_rs::cl100k_base_singleton()
+                .encode_with_special_tokens(text)
+                .len(),
+            #[cfg(feature = "tokenizer")]
+            Encoding::O200kBase => tiktoken_rs::o200k_base_singleton()
+                .encode_with_special_tokens(text)
+                .len(),
+        }
+    }
+}
+
+/// Estimate a BPE token count without a vocabulary
+///
+/// Mirrors how BPE pre-splits text: a word and the space before it make a
+/// token per six letters, digits go in groups of three, and every other
+/// symbol (punctuation, or a character outside ASCII) is a token of its own.
+/// Usually within a fifth of the real count for English prose and code.
+fn approx_tokens(text: &str) -> usize {
+    let mut tokens = 0;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let mut run: usize = 1;
+        if c.is_ascii_alphabetic() {
+            while chars.next_if(|c| c.is_ascii_alphabetic()).is_some() {
+                run += 1;
+            }
+            tokens += run.div_ceil(6);
+        } else if c.is_ascii_digit() {
+            while chars.next_if(|c| c.is_ascii_digit()).is_some() {
+                run += 1;
+            }
+            tokens += run.div_ceil(3);
+        } else if c.is_whitespace() {
+            // Spaces ride along with the next word; other runs are a token
+            while chars.next_if(|c| c.is_whitespace()).is_some() {
+                run += 1;
+            }
+            if run > 1 || c != ' ' {
+                tokens += 1;
+            }
+        } else {
+            tokens += 1;
+        }
+    }
+    tokens
+}
+
+/// Tokens for a list of `{:role :content}` chat messages, framing included
+fn message_tokens(encoding: Encoding, messages: &[Value]) -> Result<usize, EvalError> {
+    let mut tokens = TOKENS_PER_REPLY;
+    for message in messages {
+        let map = match message {
+            Value::Map(map) => map,
+            other => {
+                return Err(EvalError::type_error(
+                    "llm:count-tokens",
+                    "list of message maps",
+                    other,
+                    1,
+                ))
+            }
+        };
+        tokens += TOKENS_PER_MESSAGE;
+        for key in ["role", "content", "name"] {
+            match map.get(key) {
+                Some(Value::String(text)) => tokens += encoding.count(text),
+                Some(Value::Keyword(text)) => tokens += encoding.count(text),
+                Some(other) => tokens += encoding.count(&other.to_string()),
+                None => {}
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// llm:count-tokens - Count the tokens in a string or a list of chat messages
+fn llm_count_tokens(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "llm:count-tokens",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let encoding = match args.get(1) {
+        None => Encoding::default_for_build(),
+        Some(Value::Map(options)) => match options.get("encoding") {
+            None => Encoding::default_for_build(),
+            Some(Value::String(name)) => Encoding::from_name(name)
+                .map_err(|e| EvalError::runtime_error("llm:count-tokens", e))?,
+            Some(Value::Keyword(name)) => Encoding::from_name(name)
+                .map_err(|e| EvalError::runtime_error("llm:count-tokens", e))?,
+            Some(other) => {
+                return Err(EvalError::type_error(
+                    "llm:count-tokens",
+                    "string",
+                    other,
+                    2,
+                ))
+            }
+        },
+        Some(other) => return Err(EvalError::type_error("llm:count-tokens", "map", other, 2)),
+    };
+    let tokens = match &args[0] {
+        Value::String(text) => encoding.count(text),
+        Value::List(messages) => message_tokens(encoding, messages)?,
+        other => {
+            return Err(EvalError::type_error(
+                "llm:count-tokens",
+                "string or list of messages",
+                other,
+                1,
+            ))
+        }
+    };
+    Ok(Value::Number(tokens as f64))
+}
+
+/// Register token counting functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define(
+        "llm:count-tokens".to_string(),
+        Value::BuiltIn(llm_count_tokens),
+    );
+
+    crate::help::register_help(HelpEntry {
+        name: "llm:count-tokens".to_string(),
+        signature: "(llm:count-tokens text-or-messages [{:encoding name}])".to_string(),
+        description: "Count the tokens a model would see, to budget context before a call.
+
+**Parameters:**
+- text-or-messages: A string, or a list of {:role :content} maps as for
+  llm:chat (each message's framing is counted too)
+- :encoding - \"o200k_base\" (GPT-4o), \"cl100k_base\" (GPT-4) or \"approx\".
+  The default is \"o200k_base\" in builds with the tokenizer feature, and
+  \"approx\" otherwise.
+
+**Returns:** The number of tokens
+
+**Examples:**
+```lisp
+(llm:count-tokens \"Hello, world!\")
+=> 4
+
+(llm:count-tokens (list {:role \"user\" :content \"Hi\"}))
+=> 8
+
+(llm:count-tokens doc {:encoding \"approx\"})
+```
+
+**Error Conditions:**
+- A BPE encoding asked of a build without the tokenizer feature
+
+**Notes:** \"approx\" needs no vocabulary and is usually within a fifth of
+the exact count for English prose and code. Models from other vendors use
+their own tokenizers, so treat any count as an estimate for them."
+            .to_string(),
+        examples: vec![
+            "(llm:count-tokens \"Hello, world!\") => 4".to_string(),
+            "(llm:count-tokens messages {:encoding \"approx\"})".to_string(),
+        ],
+        related: vec!["llm:chat".to_string()],
+        category: "Standard Library: Tokens".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn count(args: &[Value]) -> f64 {
+        match llm_count_tokens(args) {
+            Ok(Value::Number(n)) => n,
+            other => panic!("expected number, got {:?}", other.map(|v| v.to_string())),
+        }
+    }
+
+    fn approx() -> Value {
+        let mut options = BTreeMap::new();
+        options.insert("encoding".to_string(), Value::String("approx".into()));
+        Value::Map(options)
+    }
+
+    #[test]
+    fn test_approx_estimate() {
+        assert_eq!(approx_tokens(""), 0);
+        assert_eq!(approx_tokens("Hello, world!"), 4);
+        assert_eq!(approx_tokens("internationalization"), 4);
+        assert_eq!(approx_tokens("1234567"), 3);
+        assert_eq!(approx_tokens("a\n\nb"), 3);
+        assert_eq!(approx_tokens("日本語"), 3);
+    }
+
+    #[test]
+    fn test_messages_include_framing() {
+        let mut message = BTreeMap::new();
+        message.insert("role".to_string(), Value::String("user".into()));
+        message.insert("content".to_string(), Value::String("Hi".into()));
+        let messages = Value::List(vec![Value::Map(message)].into());
+        // 3 for the reply, 3 for the message, 1 each for "user" and "Hi"
+        assert_eq!(count(&[messages, approx()]), 8.0);
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        assert!(llm_count_tokens(&[]).is_err());
+        assert!(llm_count_tokens(&[Value::Number(1.0)]).is_err());
+        assert!(llm_count_tokens(&[Value::List(vec![Value::Nil].into())]).is_err());
+        let mut options = BTreeMap::new();
+        options.insert("encoding".to_string(), Value::String("p50k".into()));
+        assert!(llm_count_tokens(&[Value::String("x".into()), Value::Map(options)]).is_err());
+    }
+
+    #[test]
+    #[cfg(feature = "tokenizer")]
+    fn test_bpe_encodings() {
+        let encoding = |name: &str| {
+            let mut options = BTreeMap::new();
+            options.insert("encoding".to_string(), Value::String(name.into()));
+            Value::Map(options)
+        };
+        let text = Value::String("Hello, world!".into());
+        assert_eq!(count(&[text.clone(), encoding("cl100k_base")]), 4.0);
+        assert_eq!(count(&[text.clone(), encoding("o200k_base")]), 4.0);
+        assert_eq!(count(std::slice::from_ref(&text)), 4.0);
+    }
+
+    #[test]
+    #[cfg(not(feature = "tokenizer"))]
+    fn test_bpe_needs_feature() {
+        let mut options = BTreeMap::new();
+        options.insert("encoding".to_string(), Value::String("cl100k_base".into()));
+        let err = llm_count_tokens(&[Value::String("x".into()), Value::Map(options)]).unwrap_err();
+        assert!(err.to_string().contains("tokenizer feature"), "{}", err);
+    }
+}
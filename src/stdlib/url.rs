@@ -0,0 +1,441 @@
+//! URL parsing and building
+//!
+//! - `url:parse`: Split a URL into a map of its parts
+//! - `url:build`: Assemble a URL from a map of parts
+//! - `url:encode`: Percent-encode a string, or a map as a query string
+//! - `url:decode`: Decode a percent-encoded string
+//!
+//! A parsed URL looks like
+//! `{:scheme "https" :host "example.com" :port nil :path "/search" :query {:q "rust"} :fragment nil}`;
+//! a query parameter given more than once maps to a list of its values.
+//! `url:build` takes the same map, so `(url:build (url:parse u))` round-trips.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE};
+use crate::help::HelpEntry;
+use crate::value::{write_value, PrintStyle, Value};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+use std::collections::BTreeMap;
+use url::Url;
+
+/// Everything except the unreserved characters of RFC 3986
+const COMPONENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'_')
+    .remove(b'.')
+    .remove(b'~');
+
+/// The parts of a URL as a map
+pub(crate) fn parse(text: &str) -> Result<Value, String> {
+    let url = Url::parse(text).map_err(|e| format!("invalid URL '{}': {}", text, e))?;
+
+    let mut query: BTreeMap<String, Value> = BTreeMap::new();
+    for (key, value) in url.query_pairs() {
+        let value = Value::String(value.into_owned().into());
+        match query.remove(key.as_ref()) {
+            None => query.insert(key.into_owned(), value),
+            Some(Value::List(values)) => {
+                let values = values.iter().cloned().chain([value]).collect();
+                query.insert(key.into_owned(), Value::List(values))
+            }
+            Some(first) => query.insert(key.into_owned(), Value::List(vec![first, value].into())),
+        };
+    }
+
+    let string_or_nil = |s: Option<&str>| s.map_or(Value::Nil, |s| Value::String(s.into()));
+    let mut map = BTreeMap::new();
+    map.insert("scheme".to_string(), Value::String(url.scheme().into()));
+    map.insert("host".to_string(), string_or_nil(url.host_str()));
+    map.insert(
+        "port".to_string(),
+        url.port().map_or(Value::Nil, |p| Value::Number(p as f64)),
+    );
+    map.insert("path".to_string(), Value::String(url.path().into()));
+    map.insert("query".to_string(), Value::Map(query));
+    map.insert("fragment".to_string(), string_or_nil(url.fragment()));
+    if !url.username().is_empty() {
+        map.insert("username".to_string(), Value::String(url.username().into()));
+    }
+    if url.password().is_some() {
+        map.insert("password".to_string(), string_or_nil(url.password()));
+    }
+    Ok(Value::Map(map))
+}
+
+/// Assemble a URL from a map shaped like the one `parse` returns
+pub(crate) fn build(parts: &BTreeMap<String, Value>) -> Result<String, String> {
+    let part = |name: &str| -> Result<Option<String>, String> {
+        match parts.get(name) {
+            None | Some(Value::Nil) => Ok(None),
+            Some(Value::String(s)) => Ok(Some(s.to_string())),
+            Some(Value::Keyword(s)) => Ok(Some(s.to_string())),
+            Some(Value::Number(n)) if name == "port" => Ok(Some(n.to_string())),
+            Some(other) => Err(format!(":{} must be a string, got {}", name, other)),
+        }
+    };
+
+    let scheme = part("scheme")?.ok_or(":scheme is required")?;
+    let host = part("host")?.ok_or(":host is required")?;
+    let mut url = Url::parse(&format!("{}://{}", scheme, host))
+        .map_err(|e| format!("invalid URL '{}://{}': {}", scheme, host, e))?;
+
+    if let Some(port) = part("port")? {
+        let port = port
+            .parse::<u16>()
+            .map_err(|_| format!(":port must be a number from 0 to 65535, got {}", port))?;
+        url.set_port(Some(port))
+            .map_err(|_| format!("a {} URL cannot have a port", scheme))?;
+    }
+    if let Some(username) = part("username")? {
+        url.set_username(&username)
+            .map_err(|_| format!("a {} URL cannot have a username", scheme))?;
+    }
+    if let Some(password) = part("password")? {
+        url.set_password(Some(&password))
+            .map_err(|_| format!("a {} URL cannot have a password", scheme))?;
+    }
+    if let Some(path) = part("path")? {
+        url.set_path(&path);
+    }
+    match parts.get("query") {
+        None | Some(Value::Nil) => {}
+        Some(Value::Map(query)) if query.is_empty() => {}
+        Some(Value::Map(query)) => url.set_query(Some(&query_string(query)?)),
+        Some(Value::String(query)) => url.set_query(Some(query)),
+        Some(other) => return Err(format!(":query must be a map or string, got {}", other)),
+    }
+    if let Some(fragment) = part("fragment")? {
+        url.set_fragment(Some(&fragment));
+    }
+    Ok(url.into())
+}
+
+/// Form-encode a map as `key=value&...`, keys sorted
+///
+/// A list value repeats its key once per element, nil values are left out and
+/// booleans are written `true` and `false`.
+pub(crate) fn query_string(query: &BTreeMap<String, Value>) -> Result<String, String> {
+    let mut serializer = url::form_urlencoded::Serializer::new(String::new());
+    for (key, value) in query {
+        let values = match value {
+            Value::List(values) => &values[..],
+            value => std::slice::from_ref(value),
+        };
+        for value in values {
+            match value {
+                Value::Nil => {}
+                Value::String(s) => {
+                    serializer.append_pair(key, s);
+                }
+                Value::Keyword(s) => {
+                    serializer.append_pair(key, s);
+                }
+                Value::Bool(b) => {
+                    serializer.append_pair(key, if *b { "true" } else { "false" });
+                }
+                Value::Number(_) => {
+                    let mut text = String::new();
+                    let _ = write_value(&mut text, value, PrintStyle::Display);
+                    serializer.append_pair(key, &text);
+                }
+                other => {
+                    return Err(format!(
+                        "query parameter '{}' must be a string, number or boolean, got {}",
+                        key, other
+                    ))
+                }
+            }
+        }
+    }
+    Ok(serializer.finish())
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// url:parse - Split a URL into a map of its parts
+fn url_parse(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("url:parse", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => parse(s).map_err(|e| EvalError::runtime_error("url:parse", e)),
+        other => Err(EvalError::type_error("url:parse", "string", other, 1)),
+    }
+}
+
+/// url:build - Assemble a URL from a map of parts
+fn url_build(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("url:build", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::Map(parts) => build(parts)
+            .map(|s| Value::String(s.into()))
+            .map_err(|e| EvalError::runtime_error("url:build", e)),
+        other => Err(EvalError::type_error("url:build", "map", other, 1)),
+    }
+}
+
+/// url:encode - Percent-encode a string, or form-encode a map
+fn url_encode(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("url:encode", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => Ok(Value::String(
+            utf8_percent_encode(s, COMPONENT).to_string().into(),
+        )),
+        Value::Map(query) => query_string(query)
+            .map(|s| Value::String(s.into()))
+            .map_err(|e| EvalError::runtime_error("url:encode", e)),
+        other => Err(EvalError::type_error(
+            "url:encode",
+            "string or map",
+            other,
+            1,
+        )),
+    }
+}
+
+/// url:decode - Decode a percent-encoded string
+fn url_decode(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("url:decode", ARITY_ONE, args.len()));
+    }
+    match &args[0] {
+        Value::String(s) => percent_decode_str(s)
+            .decode_utf8()
+            .map(|decoded| Value::String(decoded.into_owned().into()))
+            .map_err(|_| {
+                EvalError::runtime_error("url:decode", format!("'{}' does not decode to UTF-8", s))
+            }),
+        other => Err(EvalError::type_error("url:decode", "string", other, 1)),
+    }
+}
+
+/// Register URL functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("url:parse".to_string(), Value::BuiltIn(url_parse));
+    env.define("url:build".to_string(), Value::BuiltIn(url_build));
+    env.define("url:encode".to_string(), Value::BuiltIn(url_encode));
+    env.define("url:decode".to_string(), Value::BuiltIn(url_decode));
+
+    crate::help::register_help(HelpEntry {
+        name: "url:parse".to_string(),
+        signature: "(url:parse url)".to_string(),
+        description: "Split an absolute URL into a map of its parts.
+
+Percent-encoding in the query is decoded. A query parameter given more than
+once maps to a list of its values. :username and :password are only present
+when the URL has them.
+
+**Parameters:**
+- url: Absolute URL string
+
+**Returns:** Map with :scheme, :host, :port (nil unless written out), :path,
+:query (a map) and :fragment
+
+**Examples:**
+```lisp
+(url:parse \"https://example.com/search?q=rust+lang&page=2#top\")
+=> {:fragment \"top\" :host \"example.com\" :path \"/search\" :port nil
+    :query {:page \"2\" :q \"rust lang\"} :scheme \"https\"}
+
+(map-get (url:parse \"http://localhost:8080/\") :port)
+=> 8080
+```
+
+**Error Conditions:**
+- A relative or malformed URL is an error"
+            .to_string(),
+        examples: vec![
+            "(map-get (url:parse \"https://example.com/a?b=1\") :host) => \"example.com\""
+                .to_string(),
+        ],
+        related: vec!["url:build".to_string(), "url:decode".to_string()],
+        category: "URLs".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "url:build".to_string(),
+        signature: "(url:build parts)".to_string(),
+        description: "Assemble a URL from a map of parts, encoding each one.
+
+Takes the map url:parse returns, so parsing a URL, changing a part and
+building it again works. Query parameters are form-encoded with their keys
+sorted; a list value repeats the key and nil values are left out.
+
+**Parameters:**
+- parts: Map with :scheme and :host, and optionally :port, :path, :query
+  (map or already-encoded string), :fragment, :username and :password
+
+**Returns:** URL string
+
+**Examples:**
+```lisp
+(url:build {:scheme \"https\" :host \"api.example.com\" :path \"/search\"
+            :query {:q \"rust & lisp\" :page 2}})
+=> \"https://api.example.com/search?page=2&q=rust+%26+lisp\"
+
+(url:build (map-set (url:parse url) :query {:tag '(\"a\" \"b\")}))
+=> \"https://example.com/?tag=a&tag=b\"
+```"
+        .to_string(),
+        examples: vec![
+            "(url:build {:scheme \"https\" :host \"x.org\" :query {:q \"a b\"}}) => \"https://x.org/?q=a+b\""
+                .to_string(),
+        ],
+        related: vec!["url:parse".to_string(), "url:encode".to_string()],
+        category: "URLs".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "url:encode".to_string(),
+        signature: "(url:encode value)".to_string(),
+        description: "Percent-encode a string for use inside a URL, or a map as a query string.
+
+A string has everything except letters, digits and `-_.~` encoded, so it is
+safe as a path segment or query value. A map is form-encoded the way url:build
+encodes :query, which also suits form POST bodies.
+
+**Parameters:**
+- value: String, or map of query parameters
+
+**Returns:** Encoded string
+
+**Examples:**
+```lisp
+(url:encode \"a b/c\")
+=> \"a%20b%2Fc\"
+
+(url:encode {:user \"ada\" :tags '(\"x\" \"y\")})
+=> \"tags=x&tags=y&user=ada\"
+```"
+        .to_string(),
+        examples: vec!["(url:encode \"a&b\") => \"a%26b\"".to_string()],
+        related: vec!["url:decode".to_string(), "url:build".to_string()],
+        category: "URLs".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "url:decode".to_string(),
+        signature: "(url:decode string)".to_string(),
+        description: "Decode a percent-encoded string.
+
+`+` is left as it is; url:parse decodes form-encoded query strings, where it
+stands for a space.
+
+**Parameters:**
+- string: Percent-encoded string
+
+**Returns:** Decoded string
+
+**Examples:**
+```lisp
+(url:decode \"a%20b%2Fc\")
+=> \"a b/c\"
+```
+
+**Error Conditions:**
+- Bytes that are not valid UTF-8 once decoded are an error"
+            .to_string(),
+        examples: vec!["(url:decode \"%E2%9C%93\") => \"✓\"".to_string()],
+        related: vec!["url:encode".to_string(), "url:parse".to_string()],
+        category: "URLs".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn string(value: Value) -> String {
+        match value {
+            Value::String(s) => s.to_string(),
+            other => panic!("expected string, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_splits_parts_and_query() {
+        let parsed = url_parse(&[Value::String(
+            "http://ada:pw@localhost:8080/a%20b?q=rust+lang&tag=x&tag=y#top".into(),
+        )])
+        .unwrap();
+        let Value::Map(parts) = parsed else {
+            panic!("expected a map");
+        };
+        assert!(matches!(&parts["scheme"], Value::String(s) if &**s == "http"));
+        assert!(matches!(&parts["host"], Value::String(s) if &**s == "localhost"));
+        assert!(matches!(parts["port"], Value::Number(n) if n == 8080.0));
+        assert!(matches!(&parts["path"], Value::String(s) if &**s == "/a%20b"));
+        assert!(matches!(&parts["fragment"], Value::String(s) if &**s == "top"));
+        assert!(matches!(&parts["username"], Value::String(s) if &**s == "ada"));
+        let Value::Map(query) = &parts["query"] else {
+            panic!("expected a query map");
+        };
+        assert!(matches!(&query["q"], Value::String(s) if &**s == "rust lang"));
+        assert!(matches!(&query["tag"], Value::List(tags) if tags.len() == 2));
+
+        assert!(url_parse(&[Value::String("/relative".into())]).is_err());
+    }
+
+    #[test]
+    fn test_parse_keeps_every_value_of_a_repeated_key() {
+        let parsed = url_parse(&[Value::String("https://x.org/?a=1&b=2&a=3&a=".into())]).unwrap();
+        let Value::Map(parts) = parsed else {
+            panic!("expected a map");
+        };
+        let Value::Map(query) = &parts["query"] else {
+            panic!("expected a query map");
+        };
+        // Values keep the order they appear in; a key given once stays a string
+        assert_eq!(query["a"].to_string(), r#"("1" "3" "")"#);
+        assert_eq!(query["b"].to_string(), r#""2""#);
+    }
+
+    #[test]
+    fn test_build_round_trips_and_encodes() {
+        let url = "https://example.com:8443/search?page=2&q=a+%26+b&tag=x&tag=y#results";
+        let parsed = url_parse(&[Value::String(url.into())]).unwrap();
+        assert_eq!(string(url_build(&[parsed]).unwrap()), url);
+
+        let mut query = BTreeMap::new();
+        query.insert("q".to_string(), Value::String("rust lisp".into()));
+        query.insert("skip".to_string(), Value::Nil);
+        query.insert("n".to_string(), Value::Number(10.0));
+        let mut parts = BTreeMap::new();
+        parts.insert("scheme".to_string(), Value::String("https".into()));
+        parts.insert("host".to_string(), Value::String("api.example.com".into()));
+        parts.insert("path".to_string(), Value::String("v1/items".into()));
+        parts.insert("query".to_string(), Value::Map(query));
+        assert_eq!(
+            string(url_build(&[Value::Map(parts.clone())]).unwrap()),
+            "https://api.example.com/v1/items?n=10&q=rust+lisp"
+        );
+
+        parts.remove("host");
+        assert!(url_build(&[Value::Map(parts)]).is_err());
+    }
+
+    #[test]
+    fn test_encode_and_decode() {
+        let encoded = string(url_encode(&[Value::String("a b/c&d=é~".into())]).unwrap());
+        assert_eq!(encoded, "a%20b%2Fc%26d%3D%C3%A9~");
+        assert_eq!(
+            string(url_decode(&[Value::String(encoded.into())]).unwrap()),
+            "a b/c&d=é~"
+        );
+        assert!(url_decode(&[Value::String("%FF".into())]).is_err());
+
+        let mut query = BTreeMap::new();
+        query.insert("b".to_string(), Value::Bool(true));
+        query.insert("a".to_string(), Value::String("x y".into()));
+        assert_eq!(
+            string(url_encode(&[Value::Map(query)]).unwrap()),
+            "a=x+y&b=true"
+        );
+    }
+}
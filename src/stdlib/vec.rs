@@ -0,0 +1,379 @@
+//! Vector math for embeddings
+//!
+//! - `vec:dot`: Dot product of two vectors
+//! - `vec:cosine-similarity`: Cosine of the angle between two vectors
+//! - `vec:normalize`: Scale a vector to length 1
+//! - `vec:top-k`: The k candidates most similar to a query
+//!
+//! Vectors are lists of numbers, such as the embeddings a model API returns.
+//! Retrieval over a few thousand of them takes seconds in interpreted Lisp
+//! and milliseconds here.
+
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_THREE, ARITY_TWO};
+use crate::help::HelpEntry;
+use crate::value::Value;
+use std::collections::BTreeMap;
+
+/// Read a list of numbers, failing with a type error naming `position`
+pub(crate) fn numbers(
+    function: &str,
+    value: &Value,
+    position: usize,
+) -> Result<Vec<f64>, EvalError> {
+    match value {
+        Value::List(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Number(n) => Ok(*n),
+                _ => Err(EvalError::type_error(
+                    function,
+                    "list of numbers",
+                    value,
+                    position,
+                )),
+            })
+            .collect(),
+        other => Err(EvalError::type_error(
+            function,
+            "list of numbers",
+            other,
+            position,
+        )),
+    }
+}
+
+/// Fail unless two vectors have the same length
+pub(crate) fn same_length(function: &str, a: &[f64], b: &[f64]) -> Result<(), EvalError> {
+    if a.len() != b.len() {
+        return Err(EvalError::runtime_error(
+            function,
+            format!("vectors differ in length ({} and {})", a.len(), b.len()),
+        ));
+    }
+    Ok(())
+}
+
+pub(crate) fn dot(a: &[f64], b: &[f64]) -> f64 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+fn norm(v: &[f64]) -> f64 {
+    dot(v, v).sqrt()
+}
+
+/// Cosine similarity, or 0 when either vector has no length
+pub(crate) fn cosine(a: &[f64], b: &[f64]) -> f64 {
+    let norms = norm(a) * norm(b);
+    if norms == 0.0 {
+        0.0
+    } else {
+        dot(a, b) / norms
+    }
+}
+
+/// `v` scaled to length 1; a zero vector is returned as it is
+pub(crate) fn normalize(v: &[f64]) -> Vec<f64> {
+    let length = norm(v);
+    if length == 0.0 {
+        v.to_vec()
+    } else {
+        v.iter().map(|x| x / length).collect()
+    }
+}
+
+/// Indexes and cosine scores of the `k` candidates most similar to `query`,
+/// best first; equal scores keep the candidates' order
+pub(crate) fn top_k<'a>(
+    query: &[f64],
+    candidates: impl Iterator<Item = &'a [f64]>,
+    k: usize,
+) -> Vec<(usize, f64)> {
+    let mut scored: Vec<(usize, f64)> = candidates
+        .map(|candidate| cosine(query, candidate))
+        .enumerate()
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored.truncate(k);
+    scored
+}
+
+fn number_list(v: Vec<f64>) -> Value {
+    Value::List(v.into_iter().map(Value::Number).collect())
+}
+
+/// Read a count such as `k`: a whole number of at least 0
+pub(crate) fn count(function: &str, value: &Value, position: usize) -> Result<usize, EvalError> {
+    match value {
+        Value::Number(n) if *n >= 0.0 && n.fract() == 0.0 => Ok(*n as usize),
+        other => Err(EvalError::type_error(
+            function,
+            "non-negative integer",
+            other,
+            position,
+        )),
+    }
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// vec:dot - Sum of the products of matching elements
+fn vec_dot(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("vec:dot", ARITY_TWO, args.len()));
+    }
+    let a = numbers("vec:dot", &args[0], 1)?;
+    let b = numbers("vec:dot", &args[1], 2)?;
+    same_length("vec:dot", &a, &b)?;
+    Ok(Value::Number(dot(&a, &b)))
+}
+
+/// vec:cosine-similarity - Cosine of the angle between two vectors
+fn vec_cosine_similarity(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error(
+            "vec:cosine-similarity",
+            ARITY_TWO,
+            args.len(),
+        ));
+    }
+    let a = numbers("vec:cosine-similarity", &args[0], 1)?;
+    let b = numbers("vec:cosine-similarity", &args[1], 2)?;
+    same_length("vec:cosine-similarity", &a, &b)?;
+    Ok(Value::Number(cosine(&a, &b)))
+}
+
+/// vec:normalize - Scale a vector to length 1
+fn vec_normalize(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error(
+            "vec:normalize",
+            ARITY_ONE,
+            args.len(),
+        ));
+    }
+    let v = numbers("vec:normalize", &args[0], 1)?;
+    Ok(number_list(normalize(&v)))
+}
+
+/// vec:top-k - The k candidates most similar to a query, as {:index :score} maps
+fn vec_top_k(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::arity_error("vec:top-k", ARITY_THREE, args.len()));
+    }
+    let query = numbers("vec:top-k", &args[0], 1)?;
+    let candidates = match &args[1] {
+        Value::List(items) => items
+            .iter()
+            .map(|item| {
+                let candidate = numbers("vec:top-k", item, 2)?;
+                same_length("vec:top-k", &query, &candidate)?;
+                Ok(candidate)
+            })
+            .collect::<Result<Vec<_>, EvalError>>()?,
+        other => return Err(EvalError::type_error("vec:top-k", "list", other, 2)),
+    };
+    let k = count("vec:top-k", &args[2], 3)?;
+
+    let best = top_k(&query, candidates.iter().map(Vec::as_slice), k);
+    Ok(Value::List(
+        best.into_iter()
+            .map(|(index, score)| {
+                let mut map = BTreeMap::new();
+                map.insert("index".to_string(), Value::Number(index as f64));
+                map.insert("score".to_string(), Value::Number(score));
+                Value::Map(map)
+            })
+            .collect(),
+    ))
+}
+
+/// Register vector functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("vec:dot".to_string(), Value::BuiltIn(vec_dot));
+    env.define(
+        "vec:cosine-similarity".to_string(),
+        Value::BuiltIn(vec_cosine_similarity),
+    );
+    env.define("vec:normalize".to_string(), Value::BuiltIn(vec_normalize));
+    env.define("vec:top-k".to_string(), Value::BuiltIn(vec_top_k));
+
+    crate::help::register_help(HelpEntry {
+        name: "vec:dot".to_string(),
+        signature: "(vec:dot a b)".to_string(),
+        description: "Dot product of two vectors: the sum of the products of matching elements.
+
+**Parameters:**
+- a, b: Lists of numbers of the same length
+
+**Returns:** A number
+
+**Examples:**
+```lisp
+(vec:dot '(1 2 3) '(4 5 6))
+=> 32
+```
+
+**Error Conditions:**
+- The vectors differ in length"
+            .to_string(),
+        examples: vec!["(vec:dot '(1 2 3) '(4 5 6)) => 32".to_string()],
+        related: vec![
+            "vec:cosine-similarity".to_string(),
+            "vec:normalize".to_string(),
+        ],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vec:cosine-similarity".to_string(),
+        signature: "(vec:cosine-similarity a b)".to_string(),
+        description: "Cosine of the angle between two vectors, the usual way to compare embeddings.
+
+**Parameters:**
+- a, b: Lists of numbers of the same length
+
+**Returns:** A number from -1 (opposite) to 1 (same direction); 0 when
+either vector is all zeros
+
+**Examples:**
+```lisp
+(vec:cosine-similarity '(1 0) '(1 1))
+=> 0.7071067811865475
+```
+
+**Error Conditions:**
+- The vectors differ in length"
+            .to_string(),
+        examples: vec!["(vec:cosine-similarity '(1 0) '(2 0)) => 1".to_string()],
+        related: vec!["vec:top-k".to_string(), "vec:dot".to_string()],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vec:normalize".to_string(),
+        signature: "(vec:normalize v)".to_string(),
+        description: "Scale a vector to length 1, keeping its direction.
+
+For normalized vectors the dot product equals the cosine similarity.
+
+**Parameters:**
+- v: List of numbers
+
+**Returns:** A list of numbers; a vector of zeros is returned unchanged
+
+**Examples:**
+```lisp
+(vec:normalize '(3 4))
+=> (0.6 0.8)
+```"
+        .to_string(),
+        examples: vec!["(vec:normalize '(3 4)) => (0.6 0.8)".to_string()],
+        related: vec!["vec:dot".to_string()],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vec:top-k".to_string(),
+        signature: "(vec:top-k query candidates k)".to_string(),
+        description: "Find the k candidates most similar to a query by cosine similarity.
+
+**Parameters:**
+- query: List of numbers
+- candidates: List of vectors, each as long as the query
+- k: How many results to return (fewer when there are fewer candidates)
+
+**Returns:** A list of {:index :score} maps, best first, where :index is the
+candidate's position (from 0) and :score its cosine similarity
+
+**Examples:**
+```lisp
+(vec:top-k '(1 0) '((0 1) (1 1) (1 0)) 2)
+=> ({:index 2 :score 1} {:index 1 :score 0.7071067811865475})
+
+(map (lambda (hit) (nth (map-get hit :index) docs))
+     (vec:top-k query-embedding doc-embeddings 3))
+```
+
+**Error Conditions:**
+- A candidate differs in length from the query"
+            .to_string(),
+        examples: vec!["(vec:top-k '(1 0) '((0 1) (1 0)) 1) => ({:index 1 :score 1})".to_string()],
+        related: vec![
+            "vec:cosine-similarity".to_string(),
+            "vstore:search".to_string(),
+        ],
+        category: "Standard Library: Vectors".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn list(values: &[f64]) -> Value {
+        number_list(values.to_vec())
+    }
+
+    fn number(result: Result<Value, EvalError>) -> f64 {
+        match result {
+            Ok(Value::Number(n)) => n,
+            other => panic!("expected number, got {:?}", other.map(|v| v.to_string())),
+        }
+    }
+
+    #[test]
+    fn test_dot_and_cosine() {
+        assert_eq!(
+            number(vec_dot(&[list(&[1.0, 2.0, 3.0]), list(&[4.0, 5.0, 6.0])])),
+            32.0
+        );
+        let same = vec_cosine_similarity(&[list(&[1.0, 0.0]), list(&[2.0, 0.0])]);
+        assert_eq!(number(same), 1.0);
+        let opposite = vec_cosine_similarity(&[list(&[1.0, 1.0]), list(&[-1.0, -1.0])]);
+        assert!((number(opposite) + 1.0).abs() < 1e-12);
+        let zero = vec_cosine_similarity(&[list(&[0.0, 0.0]), list(&[1.0, 0.0])]);
+        assert_eq!(number(zero), 0.0);
+        assert!(vec_dot(&[list(&[1.0]), list(&[1.0, 2.0])]).is_err());
+        assert!(vec_dot(&[list(&[1.0]), Value::List(vec![Value::Nil].into())]).is_err());
+    }
+
+    #[test]
+    fn test_normalize() {
+        let result = vec_normalize(&[list(&[3.0, 4.0])]).unwrap();
+        assert_eq!(result.to_string(), "(0.6 0.8)");
+        assert_eq!(normalize(&[0.0, 0.0]), vec![0.0, 0.0]);
+    }
+
+    #[test]
+    fn test_top_k_orders_by_score() {
+        let candidates =
+            Value::List(vec![list(&[0.0, 1.0]), list(&[1.0, 1.0]), list(&[1.0, 0.0])].into());
+        let result = vec_top_k(&[list(&[1.0, 0.0]), candidates.clone(), Value::Number(2.0)]);
+        let indexes: Vec<String> = match result.unwrap() {
+            Value::List(hits) => hits
+                .iter()
+                .map(|hit| match hit {
+                    Value::Map(map) => map["index"].to_string(),
+                    other => panic!("expected map, got {}", other),
+                })
+                .collect(),
+            other => panic!("expected list, got {}", other),
+        };
+        assert_eq!(indexes, vec!["2", "1"]);
+
+        let all = vec_top_k(&[list(&[1.0, 0.0]), candidates, Value::Number(10.0)]);
+        assert!(matches!(all, Ok(Value::List(hits)) if hits.len() == 3));
+        assert!(vec_top_k(&[
+            list(&[1.0]),
+            Value::List(vec![list(&[1.0, 2.0])].into()),
+            Value::Number(1.0)
+        ])
+        .is_err());
+        assert!(
+            vec_top_k(&[list(&[1.0]), Value::List(vec![].into()), Value::Number(1.5)]).is_err()
+        );
+    }
+}
@@ -0,0 +1,492 @@
+//! In-memory vector store
+//!
+//! - `vstore:create`: Create an empty store
+//! - `vstore:add`: Add or replace an entry with an id, a vector and a metadata map
+//! - `vstore:search`: The k entries most similar to a query vector
+//! - `vstore:save`: Write a store to a file in the sandbox
+//! - `vstore:load`: Read a store written by `vstore:save`
+//!
+//! A small local retrieval index for agents: embed documents once, keep the
+//! vectors with their metadata, and look up the closest ones by cosine
+//! similarity. Search is a linear scan, which stays fast into the tens of
+//! thousands of entries. Files go through the sandbox like `write-file`, and
+//! the save and load functions are filed under Filesystem I/O so a policy
+//! that turns off the filesystem removes them too.
+
+use crate::builtins::current_sandbox;
+use crate::env::EnvRef;
+use crate::error::{
+    EvalError, ARITY_ONE, ARITY_THREE, ARITY_THREE_OR_FOUR, ARITY_TWO, ARITY_ZERO,
+    ERR_SANDBOX_NOT_INIT,
+};
+use crate::help::HelpEntry;
+use crate::stdlib::json::{json_to_value, value_to_json};
+use crate::stdlib::vec::{count, numbers, same_length, top_k};
+use crate::value::Value;
+use std::sync::{Arc, Mutex};
+
+/// A list of vectors with ids and metadata, searched by cosine similarity
+#[derive(Debug, Default)]
+pub struct VectorStore {
+    entries: Mutex<Vec<Entry>>,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    id: String,
+    vector: Vec<f64>,
+    /// Kept as JSON so every store can be saved
+    metadata: serde_json::Value,
+}
+
+impl VectorStore {
+    /// Number of entries in the store
+    pub(crate) fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add `entry`, replacing any entry with the same id; every vector in a
+    /// store must have the same length
+    fn add(&self, function: &str, entry: Entry) -> Result<usize, EvalError> {
+        let mut entries = self.lock();
+        if let Some(first) = entries.first() {
+            same_length(function, &first.vector, &entry.vector)?;
+        }
+        match entries.iter_mut().find(|e| e.id == entry.id) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+        Ok(entries.len())
+    }
+
+    fn to_json(&self) -> serde_json::Value {
+        let entries = self
+            .lock()
+            .iter()
+            .map(|entry| {
+                serde_json::json!({
+                    "id": entry.id,
+                    "vector": entry.vector,
+                    "metadata": entry.metadata,
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::json!({ "entries": entries })
+    }
+
+    fn from_json(json: &serde_json::Value) -> Option<Self> {
+        let entries = json
+            .get("entries")?
+            .as_array()?
+            .iter()
+            .map(|entry| {
+                Some(Entry {
+                    id: entry.get("id")?.as_str()?.to_string(),
+                    vector: entry
+                        .get("vector")?
+                        .as_array()?
+                        .iter()
+                        .map(serde_json::Value::as_f64)
+                        .collect::<Option<Vec<_>>>()?,
+                    metadata: entry
+                        .get("metadata")
+                        .cloned()
+                        .unwrap_or(serde_json::Value::Null),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(VectorStore {
+            entries: Mutex::new(entries),
+        })
+    }
+}
+
+fn store<'a>(function: &str, value: &'a Value) -> Result<&'a Arc<VectorStore>, EvalError> {
+    match value {
+        Value::VectorStore(store) => Ok(store),
+        other => Err(EvalError::type_error(function, "vector store", other, 1)),
+    }
+}
+
+fn path<'a>(function: &str, value: &'a Value, position: usize) -> Result<&'a str, EvalError> {
+    match value {
+        Value::String(path) => Ok(path),
+        other => Err(EvalError::type_error(function, "string", other, position)),
+    }
+}
+
+// ============================================================================
+// Builtins
+// ============================================================================
+
+/// vstore:create - Create an empty vector store
+fn vstore_create(args: &[Value]) -> Result<Value, EvalError> {
+    if !args.is_empty() {
+        return Err(EvalError::arity_error(
+            "vstore:create",
+            ARITY_ZERO,
+            args.len(),
+        ));
+    }
+    Ok(Value::VectorStore(Arc::new(VectorStore::default())))
+}
+
+/// vstore:add - Add or replace an entry, returning the number of entries
+fn vstore_add(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() < 3 || args.len() > 4 {
+        return Err(EvalError::arity_error(
+            "vstore:add",
+            ARITY_THREE_OR_FOUR,
+            args.len(),
+        ));
+    }
+    let store = store("vstore:add", &args[0])?;
+    let id = match &args[1] {
+        Value::String(id) => id.clone(),
+        other => return Err(EvalError::type_error("vstore:add", "string", other, 2)),
+    };
+    let vector = numbers("vstore:add", &args[2], 3)?;
+    let metadata = match args.get(3) {
+        None | Some(Value::Nil) => serde_json::Value::Null,
+        Some(value @ Value::Map(_)) => value_to_json(value).map_err(|_| {
+            EvalError::type_error("vstore:add", "map of JSON-encodable values", value, 4)
+        })?,
+        Some(other) => return Err(EvalError::type_error("vstore:add", "map", other, 4)),
+    };
+    let size = store.add(
+        "vstore:add",
+        Entry {
+            id: id.to_string(),
+            vector,
+            metadata,
+        },
+    )?;
+    Ok(Value::Number(size as f64))
+}
+
+/// vstore:search - The k entries most similar to a query, as {:id :score :metadata} maps
+fn vstore_search(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 3 {
+        return Err(EvalError::arity_error(
+            "vstore:search",
+            ARITY_THREE,
+            args.len(),
+        ));
+    }
+    let store = store("vstore:search", &args[0])?;
+    let query = numbers("vstore:search", &args[1], 2)?;
+    let k = count("vstore:search", &args[2], 3)?;
+
+    let entries = store.lock();
+    if let Some(first) = entries.first() {
+        same_length("vstore:search", &first.vector, &query)?;
+    }
+    let best = top_k(&query, entries.iter().map(|e| e.vector.as_slice()), k);
+    Ok(Value::List(
+        best.into_iter()
+            .map(|(index, score)| {
+                let entry = &entries[index];
+                let mut map = std::collections::BTreeMap::new();
+                map.insert("id".to_string(), Value::String(entry.id.as_str().into()));
+                map.insert("score".to_string(), Value::Number(score));
+                map.insert("metadata".to_string(), json_to_value(&entry.metadata));
+                Value::Map(map)
+            })
+            .collect(),
+    ))
+}
+
+/// vstore:save - Write a store to a file as JSON
+fn vstore_save(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("vstore:save", ARITY_TWO, args.len()));
+    }
+    let store = store("vstore:save", &args[0])?;
+    let path = path("vstore:save", &args[1], 2)?;
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("vstore:save", ERR_SANDBOX_NOT_INIT))?;
+    sandbox
+        .write_file(path, &store.to_json().to_string())
+        .map(|_| Value::Bool(true))
+        .map_err(|e| EvalError::sandbox_error("vstore:save", e))
+}
+
+/// vstore:load - Read a store written by vstore:save
+fn vstore_load(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("vstore:load", ARITY_ONE, args.len()));
+    }
+    let path = path("vstore:load", &args[0], 1)?;
+    let sandbox = current_sandbox()
+        .ok_or_else(|| EvalError::runtime_error("vstore:load", ERR_SANDBOX_NOT_INIT))?;
+    let contents = sandbox
+        .read_file(path)
+        .map_err(|e| EvalError::sandbox_error("vstore:load", e))?;
+    serde_json::from_str(&contents)
+        .ok()
+        .and_then(|json| VectorStore::from_json(&json))
+        .map(|store| Value::VectorStore(Arc::new(store)))
+        .ok_or_else(|| {
+            EvalError::runtime_error(
+                "vstore:load",
+                format!("{} is not a file written by vstore:save", path),
+            )
+        })
+}
+
+/// Register vector store functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("vstore:create".to_string(), Value::BuiltIn(vstore_create));
+    env.define("vstore:add".to_string(), Value::BuiltIn(vstore_add));
+    env.define("vstore:search".to_string(), Value::BuiltIn(vstore_search));
+    env.define("vstore:save".to_string(), Value::BuiltIn(vstore_save));
+    env.define("vstore:load".to_string(), Value::BuiltIn(vstore_load));
+    crate::help::register_capability("vstore:save", "filesystem");
+    crate::help::register_capability("vstore:load", "filesystem");
+
+    crate::help::register_help(HelpEntry {
+        name: "vstore:create".to_string(),
+        signature: "(vstore:create)".to_string(),
+        description: "Create an empty in-memory vector store.
+
+**Returns:** A vector store for vstore:add and vstore:search
+
+**Examples:**
+```lisp
+(define docs (vstore:create))
+```
+
+**Notes:** Stores are shared, not copied: threads given the same store see
+each other's additions."
+            .to_string(),
+        examples: vec!["(vstore:create) => #<vstore 0 entries>".to_string()],
+        related: vec!["vstore:add".to_string(), "vstore:load".to_string()],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vstore:add".to_string(),
+        signature: "(vstore:add store id vector [metadata])".to_string(),
+        description: "Add an entry to a vector store, replacing any entry with the same id.
+
+**Parameters:**
+- store: A store from vstore:create or vstore:load
+- id: A string naming the entry
+- vector: A list of numbers, such as an embedding
+- metadata: A map of JSON-encodable values kept with the entry (optional)
+
+**Returns:** The number of entries in the store
+
+**Examples:**
+```lisp
+(vstore:add docs \"intro.md\" (embed intro) {:title \"Introduction\"})
+=> 1
+```
+
+**Error Conditions:**
+- The vector's length differs from the vectors already in the store
+- The metadata holds a function or another value JSON cannot encode"
+            .to_string(),
+        examples: vec!["(vstore:add docs \"a\" '(1 0) {:title \"A\"}) => 1".to_string()],
+        related: vec!["vstore:search".to_string(), "vstore:create".to_string()],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vstore:search".to_string(),
+        signature: "(vstore:search store query k)".to_string(),
+        description: "Find the entries most similar to a query vector.
+
+**Parameters:**
+- store: A vector store
+- query: A list of numbers, as long as the stored vectors
+- k: How many entries to return at most
+
+**Returns:** A list of {:id :score :metadata} maps, best first, where :score
+is the cosine similarity
+
+**Examples:**
+```lisp
+(vstore:search docs (embed \"How do I start?\") 3)
+=> ({:id \"intro.md\" :score 0.91 :metadata {:title \"Introduction\"}} ...)
+```
+
+**Error Conditions:**
+- The query's length differs from the stored vectors
+
+**Notes:** Entries with equal scores come back in the order they were added."
+            .to_string(),
+        examples: vec![
+            "(vstore:search docs '(1 0) 1) => ({:id \"a\" :score 1 :metadata {:title \"A\"}})"
+                .to_string(),
+        ],
+        related: vec!["vstore:add".to_string(), "vec:top-k".to_string()],
+        category: "Standard Library: Vectors".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vstore:save".to_string(),
+        signature: "(vstore:save store path)".to_string(),
+        description: "Write a vector store to a file as JSON.
+
+**Parameters:**
+- store: A vector store
+- path: A file path inside the sandbox
+
+**Returns:** #t
+
+**Examples:**
+```lisp
+(vstore:save docs \"index.json\")
+```
+
+**Error Conditions:**
+- The path is outside the sandbox or the file is too large"
+            .to_string(),
+        examples: vec!["(vstore:save docs \"index.json\") => #t".to_string()],
+        related: vec!["vstore:load".to_string(), "write-file".to_string()],
+        category: "Filesystem I/O".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "vstore:load".to_string(),
+        signature: "(vstore:load path)".to_string(),
+        description: "Read a vector store written by vstore:save.
+
+**Parameters:**
+- path: A file path inside the sandbox
+
+**Returns:** A new vector store with the saved entries
+
+**Examples:**
+```lisp
+(define docs (vstore:load \"index.json\"))
+```
+
+**Error Conditions:**
+- The file does not exist or is outside the sandbox
+- The file was not written by vstore:save"
+            .to_string(),
+        examples: vec!["(vstore:load \"index.json\") => #<vstore 12 entries>".to_string()],
+        related: vec!["vstore:save".to_string(), "vstore:search".to_string()],
+        category: "Filesystem I/O".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::swap_sandbox;
+    use crate::config::{FsConfig, NetConfig};
+    use crate::fs_backend::MemoryFs;
+    use crate::sandbox::Sandbox;
+    use std::collections::BTreeMap;
+
+    fn list(values: &[f64]) -> Value {
+        Value::List(values.iter().copied().map(Value::Number).collect())
+    }
+
+    fn title(text: &str) -> Value {
+        let mut map = BTreeMap::new();
+        map.insert("title".to_string(), Value::String(text.into()));
+        Value::Map(map)
+    }
+
+    fn filled() -> Value {
+        let store = vstore_create(&[]).unwrap();
+        for (id, vector) in [("x", [1.0, 0.0]), ("y", [0.0, 1.0]), ("xy", [1.0, 1.0])] {
+            vstore_add(&[
+                store.clone(),
+                Value::String(id.into()),
+                list(&vector),
+                title(id),
+            ])
+            .unwrap();
+        }
+        store
+    }
+
+    fn ids(result: Result<Value, EvalError>) -> Vec<String> {
+        match result.unwrap() {
+            Value::List(hits) => hits
+                .iter()
+                .map(|hit| match hit {
+                    Value::Map(map) => match &map["id"] {
+                        Value::String(id) => id.to_string(),
+                        other => panic!("expected string id, got {}", other),
+                    },
+                    other => panic!("expected map, got {}", other),
+                })
+                .collect(),
+            other => panic!("expected list, got {}", other),
+        }
+    }
+
+    #[test]
+    fn test_add_and_search() {
+        let store = filled();
+        let hits = vstore_search(&[store.clone(), list(&[1.0, 0.1]), Value::Number(2.0)]);
+        assert_eq!(ids(hits), vec!["x", "xy"]);
+
+        // Adding an existing id replaces the entry
+        let size = vstore_add(&[store.clone(), Value::String("y".into()), list(&[1.0, 0.0])]);
+        assert!(matches!(size, Ok(Value::Number(n)) if n == 3.0));
+        let hits = vstore_search(&[store.clone(), list(&[1.0, 0.0]), Value::Number(2.0)]);
+        assert_eq!(ids(hits), vec!["x", "y"]);
+        assert_eq!(store.to_string(), "#<vstore 3 entries>");
+    }
+
+    #[test]
+    fn test_dimension_and_argument_errors() {
+        let store = filled();
+        assert!(vstore_add(&[store.clone(), Value::String("z".into()), list(&[1.0])]).is_err());
+        assert!(vstore_search(&[store.clone(), list(&[1.0]), Value::Number(1.0)]).is_err());
+        let lambda = Value::BuiltIn(vstore_create);
+        let mut bad = BTreeMap::new();
+        bad.insert("f".to_string(), lambda);
+        assert!(vstore_add(&[
+            store.clone(),
+            Value::String("z".into()),
+            list(&[1.0, 0.0]),
+            Value::Map(bad)
+        ])
+        .is_err());
+        assert!(vstore_search(&[Value::Nil, list(&[1.0]), Value::Number(1.0)]).is_err());
+        assert!(vstore_create(&[Value::Nil]).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load_round_trip() {
+        let sandbox = Sandbox::with_backend(
+            Arc::new(MemoryFs::new()),
+            FsConfig::default(),
+            NetConfig::default(),
+        );
+        let previous = swap_sandbox(Some(Arc::new(sandbox)));
+
+        let store = filled();
+        let saved = vstore_save(&[store, Value::String("index.json".into())]);
+        let loaded = vstore_load(&[Value::String("index.json".into())]);
+        let missing = vstore_load(&[Value::String("missing.json".into())]);
+        swap_sandbox(previous);
+
+        assert!(matches!(saved, Ok(Value::Bool(true))));
+        let loaded = loaded.unwrap();
+        let hits = vstore_search(&[loaded, list(&[0.0, 1.0]), Value::Number(1.0)]).unwrap();
+        match &hits {
+            Value::List(hits) => match &hits[0] {
+                Value::Map(map) => {
+                    assert_eq!(map["id"].to_string(), "\"y\"");
+                    assert!(matches!(&map["metadata"], Value::Map(m) if m.contains_key("title")));
+                }
+                other => panic!("expected map, got {}", other),
+            },
+            other => panic!("expected list, got {}", other),
+        }
+        assert!(matches!(missing, Err(EvalError::Sandbox { .. })));
+    }
+}
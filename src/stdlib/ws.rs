@@ -0,0 +1,310 @@
+//! WebSocket client
+//!
+//! - `ws:connect`: Open a connection to a ws:// or wss:// URL
+//! - `ws:send`: Send a text message
+//! - `ws:recv`: Wait for the next message, with an optional timeout
+//! - `ws:close`: Close a connection
+//!
+//! Connections live in the sandbox, which checks the network allowlist on
+//! connect. WebSockets stay off unless the host passes `--allow-websocket`
+//! (or sets `websocket = true` in the policy file), even with network access.
+
+use crate::builtins::current_sandbox;
+use crate::env::EnvRef;
+use crate::error::{EvalError, ARITY_ONE, ARITY_ONE_OR_TWO, ARITY_TWO, ERR_SANDBOX_NOT_INIT};
+use crate::help::HelpEntry;
+use crate::sandbox::Sandbox;
+use crate::value::{Value, WebSocketHandle};
+use std::sync::Arc;
+
+fn sandbox(function: &str) -> Result<Arc<Sandbox>, EvalError> {
+    current_sandbox().ok_or_else(|| EvalError::runtime_error(function, ERR_SANDBOX_NOT_INIT))
+}
+
+fn handle<'a>(function: &str, value: &'a Value) -> Result<&'a WebSocketHandle, EvalError> {
+    match value {
+        Value::WebSocket(socket) => Ok(socket),
+        other => Err(EvalError::type_error(function, "websocket", other, 1)),
+    }
+}
+
+/// ws:connect - Open a WebSocket connection
+fn ws_connect(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("ws:connect", ARITY_ONE, args.len()));
+    }
+    let url = match &args[0] {
+        Value::String(url) => url,
+        other => return Err(EvalError::type_error("ws:connect", "string", other, 1)),
+    };
+    sandbox("ws:connect")?
+        .ws_connect(url)
+        .map(Value::WebSocket)
+        .map_err(|e| EvalError::sandbox_error("ws:connect", e))
+}
+
+/// ws:send - Send a text message
+fn ws_send(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 2 {
+        return Err(EvalError::arity_error("ws:send", ARITY_TWO, args.len()));
+    }
+    let socket = handle("ws:send", &args[0])?;
+    let text = match &args[1] {
+        Value::String(text) => text,
+        other => return Err(EvalError::type_error("ws:send", "string", other, 2)),
+    };
+    sandbox("ws:send")?
+        .ws_send(socket, text)
+        .map(|_| Value::Nil)
+        .map_err(|e| EvalError::sandbox_error("ws:send", e))
+}
+
+/// ws:recv - Wait for the next message, returning nil on timeout
+fn ws_recv(args: &[Value]) -> Result<Value, EvalError> {
+    if args.is_empty() || args.len() > 2 {
+        return Err(EvalError::arity_error(
+            "ws:recv",
+            ARITY_ONE_OR_TWO,
+            args.len(),
+        ));
+    }
+    let socket = handle("ws:recv", &args[0])?;
+    let timeout = match args.get(1) {
+        None | Some(Value::Nil) => None,
+        Some(Value::Number(ms)) if *ms >= 0.0 => Some(*ms as u64),
+        Some(other) => {
+            return Err(EvalError::type_error(
+                "ws:recv",
+                "non-negative number",
+                other,
+                2,
+            ))
+        }
+    };
+    sandbox("ws:recv")?
+        .ws_recv(socket, timeout)
+        .map(|message| message.map_or(Value::Nil, |s| Value::String(s.into())))
+        .map_err(|e| EvalError::sandbox_error("ws:recv", e))
+}
+
+/// ws:close - Close a connection
+fn ws_close(args: &[Value]) -> Result<Value, EvalError> {
+    if args.len() != 1 {
+        return Err(EvalError::arity_error("ws:close", ARITY_ONE, args.len()));
+    }
+    let socket = handle("ws:close", &args[0])?;
+    sandbox("ws:close")?
+        .ws_close(socket)
+        .map(|_| Value::Nil)
+        .map_err(|e| EvalError::sandbox_error("ws:close", e))
+}
+
+/// Register WebSocket functions in the environment
+pub fn register(env: &EnvRef) {
+    env.define("ws:connect".to_string(), Value::BuiltIn(ws_connect));
+    env.define("ws:send".to_string(), Value::BuiltIn(ws_send));
+    env.define("ws:recv".to_string(), Value::BuiltIn(ws_recv));
+    env.define("ws:close".to_string(), Value::BuiltIn(ws_close));
+    for name in ["ws:connect", "ws:send", "ws:recv", "ws:close"] {
+        crate::help::register_capability(name, "network");
+    }
+
+    crate::help::register_help(HelpEntry {
+        name: "ws:connect".to_string(),
+        signature: "(ws:connect url)".to_string(),
+        description: "Open a WebSocket connection.
+
+**Parameters:**
+- url: A ws:// or wss:// URL
+
+**Returns:** A websocket for ws:send, ws:recv and ws:close
+
+**Examples:**
+```lisp
+(define feed (ws:connect \"wss://stream.example.com/events\"))
+```
+
+**Error Conditions:**
+- WebSockets are disabled (start with --allow-websocket)
+- The address is not in the network allowlist
+- The request quota is used up (each connection counts as one request)
+- The server refuses the connection or the handshake
+
+**Notes:** Allowlist entries without a scheme allow wss but not ws; name
+the scheme for plain connections, like `ws://localhost:8080`."
+            .to_string(),
+        examples: vec!["(ws:connect \"wss://stream.example.com/events\")".to_string()],
+        related: vec![
+            "ws:send".to_string(),
+            "ws:recv".to_string(),
+            "ws:close".to_string(),
+        ],
+        category: "Standard Library: WebSocket".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "ws:send".to_string(),
+        signature: "(ws:send socket text)".to_string(),
+        description: "Send a text message over a WebSocket.
+
+**Parameters:**
+- socket: A websocket from ws:connect
+- text: The message; encode data first, e.g. with json:encode
+
+**Returns:** nil
+
+**Examples:**
+```lisp
+(ws:send feed (json:encode {:subscribe \"prices\"}))
+```
+
+**Error Conditions:**
+- The connection is closed"
+            .to_string(),
+        examples: vec!["(ws:send feed \"hello\") => nil".to_string()],
+        related: vec!["ws:recv".to_string(), "ws:connect".to_string()],
+        category: "Standard Library: WebSocket".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "ws:recv".to_string(),
+        signature: "(ws:recv socket [timeout-ms])".to_string(),
+        description: "Wait for the next message on a WebSocket.
+
+**Parameters:**
+- socket: A websocket from ws:connect
+- timeout-ms: Longest wait in milliseconds (optional, waits forever without it)
+
+**Returns:** The message as a string, or nil if none arrived in time
+
+**Examples:**
+```lisp
+(define msg (ws:recv feed 5000))
+(if (nil? msg) \"quiet\" (json:decode msg))
+```
+
+**Error Conditions:**
+- The connection is closed, by either side
+
+**Notes:** Binary messages are decoded as UTF-8. Pings are answered
+automatically and never returned."
+            .to_string(),
+        examples: vec!["(ws:recv feed 1000) => \"hello\"".to_string()],
+        related: vec!["ws:send".to_string(), "ws:close".to_string()],
+        category: "Standard Library: WebSocket".to_string(),
+    });
+
+    crate::help::register_help(HelpEntry {
+        name: "ws:close".to_string(),
+        signature: "(ws:close socket)".to_string(),
+        description: "Close a WebSocket connection.
+
+**Parameters:**
+- socket: A websocket from ws:connect
+
+**Returns:** nil
+
+**Examples:**
+```lisp
+(ws:close feed)
+```
+
+**Notes:** Closing a connection twice is not an error."
+            .to_string(),
+        examples: vec!["(ws:close feed) => nil".to_string()],
+        related: vec!["ws:connect".to_string()],
+        category: "Standard Library: WebSocket".to_string(),
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtins::swap_sandbox;
+    use crate::config::{FsConfig, NetConfig};
+    use crate::fs_backend::MemoryFs;
+    use std::net::TcpListener;
+
+    /// Serve one WebSocket connection on localhost, echoing messages back
+    fn echo_server() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut socket = tungstenite::accept(stream).unwrap();
+            while let Ok(message) = socket.read() {
+                if message.is_text() && socket.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+        format!("ws://127.0.0.1:{}", port)
+    }
+
+    fn with_sandbox<T>(allow_websocket: bool, run: impl FnOnce() -> T) -> T {
+        let net_config = NetConfig {
+            enabled: true,
+            allowed_addresses: vec!["ws://127.0.0.1".to_string()],
+            allow_websocket,
+            ..NetConfig::default()
+        };
+        let sandbox =
+            Sandbox::with_backend(Arc::new(MemoryFs::new()), FsConfig::default(), net_config);
+        let previous = swap_sandbox(Some(Arc::new(sandbox)));
+        let result = run();
+        swap_sandbox(previous);
+        result
+    }
+
+    #[test]
+    fn test_echo_round_trip() {
+        let url = echo_server();
+        with_sandbox(true, || {
+            let socket = ws_connect(&[Value::String(url.into())]).unwrap();
+            assert!(matches!(&socket, Value::WebSocket(_)));
+            ws_send(&[socket.clone(), Value::String("hello".into())]).unwrap();
+            let reply = ws_recv(&[socket.clone(), Value::Number(2000.0)]);
+            assert!(matches!(reply, Ok(Value::String(s)) if s == "hello".into()));
+
+            // Nothing more is coming, so a short wait gives nil
+            let quiet = ws_recv(&[socket.clone(), Value::Number(50.0)]);
+            assert!(matches!(quiet, Ok(Value::Nil)));
+
+            ws_close(std::slice::from_ref(&socket)).unwrap();
+            ws_close(std::slice::from_ref(&socket)).unwrap();
+            assert!(ws_send(&[socket, Value::String("late".into())]).is_err());
+        });
+    }
+
+    #[test]
+    fn test_connect_needs_flag_and_allowlist() {
+        let denied = with_sandbox(false, || {
+            ws_connect(&[Value::String("ws://127.0.0.1:9".into())])
+        });
+        assert!(matches!(
+            denied,
+            Err(EvalError::Sandbox {
+                error: crate::sandbox::SandboxError::WebSocketDisabled,
+                ..
+            })
+        ));
+
+        let denied = with_sandbox(true, || {
+            ws_connect(&[Value::String("ws://example.com".into())])
+        });
+        assert!(matches!(
+            denied,
+            Err(EvalError::Sandbox {
+                error: crate::sandbox::SandboxError::AddressNotAllowed { .. },
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_argument_errors() {
+        assert!(ws_connect(&[]).is_err());
+        assert!(ws_send(&[Value::Nil, Value::String("x".into())]).is_err());
+        assert!(ws_recv(&[Value::Nil]).is_err());
+    }
+}
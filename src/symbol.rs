@@ -0,0 +1,217 @@
+// ABOUTME: Interned symbol type shared by Value, Environment, and lambda parameters
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::ops::Deref;
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, Weak};
+
+/// Process-wide interner, so symbols compare by pointer across threads too
+static INTERNER: OnceLock<Mutex<Interner>> = OnceLock::new();
+
+/// Entries the interner holds before it first sweeps out dropped names
+const MIN_PRUNE_AT: usize = 1024;
+
+/// Names of the symbols alive anywhere in the process
+///
+/// The interner only holds weak references, so a name is freed once the last
+/// symbol using it is dropped; otherwise code sent to a long-running server
+/// could grow it without limit by making up identifiers. Dead entries are
+/// swept out when the map has doubled since the last sweep.
+#[derive(Default)]
+struct Interner {
+    names: HashMap<Box<str>, Weak<str>>,
+    prune_at: usize,
+}
+
+impl Interner {
+    fn get(&self, name: &str) -> Option<Arc<str>> {
+        self.names.get(name).and_then(Weak::upgrade)
+    }
+
+    fn insert(&mut self, name: &str) -> Arc<str> {
+        if self.names.len() >= self.prune_at.max(MIN_PRUNE_AT) {
+            self.names.retain(|_, symbol| symbol.strong_count() > 0);
+            self.prune_at = self.names.len() * 2;
+        }
+        let interned: Arc<str> = Arc::from(name);
+        self.names.insert(name.into(), Arc::downgrade(&interned));
+        interned
+    }
+}
+
+fn interner() -> MutexGuard<'static, Interner> {
+    INTERNER
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+/// An interned symbol name
+///
+/// Every symbol with the same name shares one allocation, so cloning is a
+/// reference-count bump and equality is usually a pointer comparison.
+#[derive(Clone)]
+pub struct Symbol(Arc<str>);
+
+impl Symbol {
+    /// Intern a name, reusing the existing allocation if it was seen before
+    pub fn new(name: &str) -> Self {
+        let mut interner = interner();
+        match interner.get(name) {
+            Some(existing) => Symbol(existing),
+            None => Symbol(interner.insert(name)),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Deref for Symbol {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Borrow<str> for Symbol {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0, &other.0) || self.0 == other.0
+    }
+}
+
+impl Eq for Symbol {}
+
+impl PartialEq<str> for Symbol {
+    fn eq(&self, other: &str) -> bool {
+        &*self.0 == other
+    }
+}
+
+impl PartialEq<&str> for Symbol {
+    fn eq(&self, other: &&str) -> bool {
+        &*self.0 == *other
+    }
+}
+
+impl Hash for Symbol {
+    // Hash the text so lookups by &str (via Borrow) find the same entry
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state)
+    }
+}
+
+impl PartialOrd for Symbol {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Symbol {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.0.cmp(&other.0)
+    }
+}
+
+impl From<&str> for Symbol {
+    fn from(name: &str) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<String> for Symbol {
+    fn from(name: String) -> Self {
+        Symbol::new(&name)
+    }
+}
+
+impl From<&String> for Symbol {
+    fn from(name: &String) -> Self {
+        Symbol::new(name)
+    }
+}
+
+impl From<Symbol> for String {
+    fn from(symbol: Symbol) -> Self {
+        symbol.0.to_string()
+    }
+}
+
+impl fmt::Display for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(&*self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_same_name_shares_allocation() {
+        let a = Symbol::new("shared-name");
+        let b = Symbol::from("shared-name".to_string());
+        assert!(Arc::ptr_eq(&a.0, &b.0));
+        assert_eq!(a, b);
+    }
+
+    #[test]
+    fn test_dropped_names_are_freed() {
+        let held = |prefix: &str| {
+            let interner = interner();
+            interner
+                .names
+                .keys()
+                .filter(|name| name.starts_with(prefix))
+                .count()
+        };
+
+        let names: Vec<_> = (0..5_000)
+            .map(|i| Symbol::new(&format!("dropped-name-{}", i)))
+            .collect();
+        assert_eq!(held("dropped-name-"), 5_000);
+        drop(names);
+        assert!(interner().get("dropped-name-1").is_none());
+
+        // Interning more names sweeps out the dead entries
+        for i in 0..100_000 {
+            Symbol::new(&format!("churn-name-{}", i));
+            if i % 1_000 == 0 && held("dropped-name-") == 0 {
+                break;
+            }
+        }
+        assert_eq!(held("dropped-name-"), 0);
+        assert!(held("churn-name-") < 100_000);
+    }
+
+    #[test]
+    fn test_compares_with_str() {
+        let sym = Symbol::new("define");
+        assert!(sym == "define");
+        assert!(sym != "lambda");
+        assert_eq!(sym.to_string(), "define");
+    }
+
+    #[test]
+    fn test_lookup_by_str() {
+        let mut map = std::collections::HashMap::new();
+        map.insert(Symbol::new("x"), 1);
+        assert_eq!(map.get("x"), Some(&1));
+    }
+}
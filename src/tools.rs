@@ -1,7 +1,10 @@
 // ABOUTME: Tool trait system for extending Lisp functionality with Rust code
+// Also renders documented functions as LLM tool (function-calling) schemas
 
 use crate::error::EvalError;
+use crate::help::{HelpEntry, HelpRegistry};
 use crate::value::Value;
+use serde_json::json;
 
 /// A tool is a Rust function that can be called from Lisp
 /// This provides an extensible way to add native capabilities to the interpreter
@@ -78,6 +81,304 @@ impl Tool for SimpleTool {
     }
 }
 
+// ============================================================================
+// LLM Tool Manifests
+// ============================================================================
+
+/// Schema dialects understood by LLM function-calling APIs
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ToolFormat {
+    /// `{"type": "function", "function": {"name", "description", "parameters"}}`
+    OpenAi,
+    /// `{"name", "description", "input_schema"}`
+    Anthropic,
+}
+
+/// One parameter of a tool, read from a help signature such as `(f x [y] rest...)`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ToolParam {
+    pub name: String,
+    pub optional: bool,
+    /// Collects any number of trailing arguments, passed as a JSON array
+    pub variadic: bool,
+}
+
+/// A Lisp function described as an LLM tool
+///
+/// `name` is restricted to the characters tool APIs accept (`[a-zA-Z0-9_-]`);
+/// `lisp_name` is the function it calls. Arguments arrive as a JSON object with
+/// one property per parameter and are turned back into a call with
+/// [`ToolSchema::call_expr`].
+#[derive(Debug, Clone)]
+pub struct ToolSchema {
+    pub name: String,
+    pub lisp_name: String,
+    pub description: String,
+    pub params: Vec<ToolParam>,
+}
+
+impl ToolSchema {
+    /// Describe a documented function as a tool, or None for special forms and macros
+    pub fn from_help(entry: &HelpEntry) -> Option<Self> {
+        if entry.category.eq_ignore_ascii_case("Special forms") || entry.category == "Macro" {
+            return None;
+        }
+
+        let mut description = format!("Lisp function `{}`.", entry.name);
+        description.push_str(&format!("\n\nSignature: {}", entry.signature));
+        if !entry.description.is_empty() {
+            description.push_str("\n\n");
+            description.push_str(entry.description.trim());
+        }
+        if !entry.examples.is_empty() {
+            description.push_str("\n\nExamples:");
+            for line in entry.examples.iter().flat_map(|e| e.lines()) {
+                description.push('\n');
+                description.push_str(line.trim());
+            }
+        }
+
+        Some(ToolSchema {
+            name: tool_name(&entry.name),
+            lisp_name: entry.name.clone(),
+            description,
+            params: signature_params(&entry.name, &entry.signature),
+        })
+    }
+
+    /// JSON Schema for the tool's input object
+    pub fn input_schema(&self) -> serde_json::Value {
+        let mut properties = serde_json::Map::new();
+        for param in &self.params {
+            let schema = if param.variadic {
+                json!({
+                    "type": "array",
+                    "description": format!("Any number of `{}` arguments, each a JSON value", param.name),
+                })
+            } else {
+                json!({ "description": format!("The `{}` argument as a JSON value", param.name) })
+            };
+            properties.insert(param.name.clone(), schema);
+        }
+        let required: Vec<_> = self
+            .params
+            .iter()
+            .filter(|p| !p.optional)
+            .map(|p| p.name.clone())
+            .collect();
+
+        json!({
+            "type": "object",
+            "properties": properties,
+            "required": required,
+        })
+    }
+
+    /// Render the tool in the given API dialect
+    pub fn render(&self, format: ToolFormat) -> serde_json::Value {
+        match format {
+            ToolFormat::OpenAi => json!({
+                "type": "function",
+                "function": {
+                    "name": self.name,
+                    "description": self.description,
+                    "parameters": self.input_schema(),
+                },
+            }),
+            ToolFormat::Anthropic => json!({
+                "name": self.name,
+                "description": self.description,
+                "input_schema": self.input_schema(),
+            }),
+        }
+    }
+
+    /// Build the Lisp call for a tool invocation's JSON input
+    ///
+    /// Arguments are converted with the same mapping as `json:decode` and quoted,
+    /// so lists arrive as data rather than being evaluated as calls.
+    #[allow(dead_code)]
+    pub fn call_expr(&self, input: &serde_json::Value) -> Result<Value, String> {
+        let empty = serde_json::Map::new();
+        let fields = match input {
+            serde_json::Value::Object(fields) => fields,
+            serde_json::Value::Null => &empty,
+            _ => return Err(format!("{}: tool input must be a JSON object", self.name)),
+        };
+
+        let quote = |json: &serde_json::Value| {
+            Value::List(
+                vec![
+                    Value::Symbol("quote".into()),
+                    crate::stdlib::json::json_to_value(json),
+                ]
+                .into(),
+            )
+        };
+
+        let mut call = vec![Value::Symbol(self.lisp_name.as_str().into())];
+        let mut missing: Option<&str> = None;
+        for param in &self.params {
+            match fields.get(&param.name) {
+                Some(value) => {
+                    if let Some(skipped) = missing {
+                        return Err(format!(
+                            "{}: '{}' given without '{}'",
+                            self.name, param.name, skipped
+                        ));
+                    }
+                    match (param.variadic, value) {
+                        (true, serde_json::Value::Array(items)) => {
+                            call.extend(items.iter().map(quote))
+                        }
+                        (true, _) => {
+                            return Err(format!("{}: '{}' must be an array", self.name, param.name))
+                        }
+                        (false, value) => call.push(quote(value)),
+                    }
+                }
+                None if param.optional => missing = missing.or(Some(&param.name)),
+                None => {
+                    return Err(format!(
+                        "{}: missing required argument '{}'",
+                        self.name, param.name
+                    ))
+                }
+            }
+        }
+
+        if let Some(unknown) = fields
+            .keys()
+            .find(|key| !self.params.iter().any(|p| &p.name == *key))
+        {
+            return Err(format!("{}: unknown argument '{}'", self.name, unknown));
+        }
+
+        Ok(Value::List(call.into()))
+    }
+}
+
+/// Every documented function in the registry as a tool, sorted by name
+///
+/// Functions whose tool names would collide keep only the first by Lisp name.
+pub fn manifest(registry: &HelpRegistry) -> Vec<ToolSchema> {
+    let mut tools: Vec<_> = registry
+        .entries()
+        .filter_map(ToolSchema::from_help)
+        .collect();
+    tools.sort_by(|a, b| a.lisp_name.cmp(&b.lisp_name));
+
+    let mut seen = std::collections::HashSet::new();
+    tools.retain(|tool| seen.insert(tool.name.clone()));
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools
+}
+
+/// The manifest as a pretty-printed JSON array in the given dialect
+pub fn export_manifest(registry: &HelpRegistry, format: ToolFormat) -> String {
+    let tools: Vec<_> = manifest(registry)
+        .iter()
+        .map(|tool| tool.render(format))
+        .collect();
+    serde_json::to_string_pretty(&tools).expect("tool schemas serialize to JSON")
+}
+
+/// Map a Lisp function name onto the characters tool APIs allow
+///
+/// Operators get words (`+` → `add`), `->` becomes `-to-`, a trailing `?`
+/// becomes `_p` and `!` becomes `_bang`; anything else outside
+/// `[a-zA-Z0-9_-]` becomes `_`.
+pub fn tool_name(lisp_name: &str) -> String {
+    let operator = match lisp_name {
+        "+" => Some("add"),
+        "-" => Some("subtract"),
+        "*" => Some("multiply"),
+        "/" => Some("divide"),
+        "%" => Some("modulo"),
+        "=" => Some("equal"),
+        "<" => Some("less_than"),
+        ">" => Some("greater_than"),
+        "<=" => Some("less_or_equal"),
+        ">=" => Some("greater_or_equal"),
+        _ => None,
+    };
+    if let Some(word) = operator {
+        return word.to_string();
+    }
+
+    let mut name = String::new();
+    for c in lisp_name.replace("->", "-to-").chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '_' | '-' => name.push(c),
+            '?' => name.push_str("_p"),
+            '!' => name.push_str("_bang"),
+            _ => name.push('_'),
+        }
+    }
+    name.truncate(64);
+    name
+}
+
+/// Read parameters from a signature, merging alternatives like `(f) or (f x)`
+///
+/// Parameters present only in longer alternatives are optional. Signatures
+/// that give no names, such as `(f ...)`, yield a single variadic `args`.
+fn signature_params(name: &str, signature: &str) -> Vec<ToolParam> {
+    let prefix = format!("({}", name);
+    let forms: Vec<Vec<ToolParam>> = signature
+        .split(" or ")
+        .filter_map(|form| {
+            let rest = form.trim().strip_prefix(&prefix)?.strip_suffix(')')?;
+            if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+                return None;
+            }
+            Some(form_params(rest))
+        })
+        .collect();
+
+    let shortest = forms.iter().map(Vec::len).min().unwrap_or(0);
+    let mut params = forms.into_iter().max_by_key(Vec::len).unwrap_or_default();
+    for param in params.iter_mut().skip(shortest) {
+        param.optional = true;
+    }
+    params
+}
+
+fn form_params(rest: &str) -> Vec<ToolParam> {
+    let mut params: Vec<ToolParam> = Vec::new();
+    for token in rest.split_whitespace() {
+        if token == "..." {
+            match params.last_mut() {
+                Some(last) => {
+                    last.variadic = true;
+                    last.optional = true;
+                }
+                None => params.push(ToolParam {
+                    name: "args".to_string(),
+                    optional: true,
+                    variadic: true,
+                }),
+            }
+            continue;
+        }
+
+        let (token, optional) = match token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            Some(inner) => (inner, true),
+            None => (token, false),
+        };
+        let (token, variadic) = match token.strip_suffix("...") {
+            Some(inner) => (inner, true),
+            None => (token, false),
+        };
+        params.push(ToolParam {
+            name: token.trim_matches(|c| c == '\'' || c == '"').to_string(),
+            optional: optional || variadic,
+            variadic,
+        });
+    }
+    params
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -131,4 +432,82 @@ mod tests {
         let result = tool.call(&[Value::Number(1.0), Value::Number(2.0)]);
         assert!(matches!(result, Err(EvalError::ArityError { .. })));
     }
+
+    fn entry(name: &str, signature: &str, category: &str) -> HelpEntry {
+        HelpEntry {
+            name: name.to_string(),
+            signature: signature.to_string(),
+            description: "Does things.".to_string(),
+            examples: vec!["(f 1) => 1".to_string()],
+            related: vec![],
+            category: category.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_tool_names_use_allowed_characters() {
+        assert_eq!(tool_name("+"), "add");
+        assert_eq!(tool_name("<="), "less_or_equal");
+        assert_eq!(tool_name("empty?"), "empty_p");
+        assert_eq!(tool_name("string->number"), "string-to-number");
+        assert_eq!(tool_name("http:get-many"), "http_get-many");
+    }
+
+    #[test]
+    fn test_signature_params() {
+        let params = signature_params("http:get-many", "(http:get-many urls [options])");
+        assert_eq!(params.len(), 2);
+        assert!(!params[0].optional);
+        assert!(params[1].optional && !params[1].variadic);
+
+        let params = signature_params("+", "(+ ...)");
+        assert_eq!(params[0].name, "args");
+        assert!(params[0].variadic);
+
+        let params = signature_params("help", "(help) or (help 'function-name)");
+        assert_eq!(params[0].name, "function-name");
+        assert!(params[0].optional);
+    }
+
+    #[test]
+    fn test_render_formats() {
+        let tool = ToolSchema::from_help(&entry("nth", "(nth n lst)", "List operations")).unwrap();
+
+        let openai = tool.render(ToolFormat::OpenAi);
+        assert_eq!(openai["function"]["name"], "nth");
+        assert_eq!(
+            openai["function"]["parameters"]["required"],
+            json!(["n", "lst"])
+        );
+
+        let anthropic = tool.render(ToolFormat::Anthropic);
+        assert_eq!(anthropic["name"], "nth");
+        assert!(anthropic["description"]
+            .as_str()
+            .unwrap()
+            .contains("Signature: (nth n lst)"));
+        assert_eq!(anthropic["input_schema"]["type"], "object");
+    }
+
+    #[test]
+    fn test_special_forms_are_not_tools() {
+        assert!(ToolSchema::from_help(&entry("if", "(if c a b)", "Special forms")).is_none());
+        assert!(ToolSchema::from_help(&entry("when", "(when c b)", "Macro")).is_none());
+    }
+
+    #[test]
+    fn test_call_expr_quotes_arguments() {
+        let tool = ToolSchema::from_help(&entry("nth", "(nth n lst)", "Lists")).unwrap();
+        let call = tool.call_expr(&json!({"n": 1, "lst": [1, 2, 3]})).unwrap();
+        assert_eq!(call.to_string(), "(nth (quote 1) (quote (1 2 3)))");
+
+        assert!(tool.call_expr(&json!({"n": 1})).is_err());
+        assert!(tool
+            .call_expr(&json!({"n": 1, "lst": [], "extra": 2}))
+            .is_err());
+
+        let variadic = ToolSchema::from_help(&entry("+", "(+ ...)", "Arithmetic")).unwrap();
+        let call = variadic.call_expr(&json!({"args": [1, 2]})).unwrap();
+        assert_eq!(call.to_string(), "(+ (quote 1) (quote 2))");
+    }
 }
@@ -0,0 +1,45 @@
+// ABOUTME: Resource usage of an interpreter: evaluation steps, sandbox I/O and memory
+// Read by Interpreter::resource_usage for hosts and by the memory-usage and step-count builtins
+
+use crate::env::Environment;
+use crate::sandbox::Sandbox;
+
+/// What an interpreter has used so far
+///
+/// Steps count the current top-level evaluation (or the last one, read
+/// between evaluations), including threads it started with `spawn`. I/O
+/// totals cover the sandbox's whole life. Memory is an estimate from the
+/// global bindings, plus the resident size of the process where the OS
+/// reports it; several interpreters in one process share that figure.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ResourceUsage {
+    pub steps: u64,
+    /// Bytes read from files and received over the network
+    pub bytes_read: u64,
+    /// Bytes written to files and sent over WebSockets
+    pub bytes_written: u64,
+    /// Number of global bindings, builtins included
+    pub bindings: usize,
+    /// Approximate bytes held by global values, see [`crate::value::Value::estimated_size`]
+    pub global_bytes: usize,
+    /// Resident memory of the whole process, when known (Linux only)
+    pub resident_bytes: Option<u64>,
+}
+
+impl ResourceUsage {
+    /// Usage of the interpreter with global environment `env`, sandbox and step count
+    pub fn measure(env: &Environment, sandbox: Option<&Sandbox>, steps: u64) -> Self {
+        let bindings = env.bindings();
+        ResourceUsage {
+            steps,
+            bytes_read: sandbox.map_or(0, Sandbox::bytes_read),
+            bytes_written: sandbox.map_or(0, Sandbox::bytes_written),
+            bindings: bindings.len(),
+            global_bytes: bindings
+                .iter()
+                .map(|(name, value)| name.len() + value.estimated_size())
+                .sum(),
+            resident_bytes: crate::platform::resident_bytes(),
+        }
+    }
+}
@@ -1,83 +1,324 @@
 // ABOUTME: Value types representing Lisp data structures and expressions
 
-use crate::env::Environment;
+use crate::env::EnvRef;
 use crate::error::EvalError;
-use std::collections::HashMap;
+use crate::symbol::Symbol;
+use std::collections::BTreeMap;
 use std::fmt;
-use std::rc::Rc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
 
 #[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
     Bool(bool),
-    Symbol(String),
+    Symbol(Symbol),
     Keyword(String), // For :key syntax - keywords are self-evaluating
-    String(String),
-    List(Vec<Value>),
-    Map(HashMap<String, Value>), // Key-value maps
+    String(Arc<str>),
+    List(Arc<[Value]>),
+    Values(Arc<[Value]>), // Multiple values from `values`, spread by `call-with-values`
+    Map(BTreeMap<String, Value>), // Key-value maps, sorted by key
     Lambda {
-        params: Vec<String>,
-        body: Box<Value>,
-        env: Rc<Environment>,
+        params: Vec<Symbol>,
+        /// Default expressions of the trailing optional parameters, evaluated at each call
+        defaults: Vec<Value>,
+        body: Arc<Value>,
+        env: EnvRef,
         docstring: Option<String>,
+        /// The `define` that created the function, shown by `source`: its text
+        /// as a string when it was read from source, else the form
+        source: Option<Arc<Value>>,
     },
     Macro {
-        params: Vec<String>,
-        body: Box<Value>,
+        params: Vec<Symbol>,
+        body: Arc<Value>,
     },
     BuiltIn(fn(&[Value]) -> Result<Value, EvalError>),
-    Error(String), // Error values that can be caught
+    Promise(Arc<Mutex<Promise>>), // Memoized delayed computation created by `delay`
+    Thread(Arc<Mutex<Task>>),     // Thread started by `spawn`
+    Channel(Channel),             // Message queue created by `make-channel`
+    RateLimiter(Arc<crate::stdlib::rate::RateLimiter>), // Created by `rate:limiter`
+    WebSocket(WebSocketHandle),   // Connection opened by `ws:connect`
+    VectorStore(Arc<crate::stdlib::vstore::VectorStore>), // Created by `vstore:create`
+    Matrix(Arc<crate::stdlib::matrix::Matrix>), // Created by `matrix:from-list`
+    Decimal(Arc<bigdecimal::BigDecimal>), // Exact base-10 number from `decimal:parse`
+    Error(Box<ErrorValue>),       // Error values that can be caught
     Nil,
 }
 
+/// The contents of an error value: a kind, a message and optional data
+///
+/// `kind` is a keyword name without the colon (`"not-found"`). Errors made
+/// from a plain message have kind `error`; errors caught by `try` get the
+/// kind of the [`EvalError`] that raised them.
+#[derive(Debug, Clone)]
+pub struct ErrorValue {
+    pub kind: String,
+    pub message: String,
+    pub data: Option<BTreeMap<String, Value>>,
+}
+
+/// Kind given to errors created without one
+pub const DEFAULT_ERROR_KIND: &str = "error";
+
+impl ErrorValue {
+    pub fn new(kind: impl Into<String>, message: impl Into<String>) -> Self {
+        ErrorValue {
+            kind: kind.into(),
+            message: message.into(),
+            data: None,
+        }
+    }
+
+    pub fn with_data(mut self, data: BTreeMap<String, Value>) -> Self {
+        self.data = Some(data);
+        self
+    }
+}
+
+impl From<&EvalError> for ErrorValue {
+    fn from(error: &EvalError) -> Self {
+        let data = error.data();
+        let value = ErrorValue::new(error.kind(), error.to_string());
+        if data.is_empty() {
+            value
+        } else {
+            value.with_data(data)
+        }
+    }
+}
+
+/// State of a promise created by `delay`
+///
+/// A promise starts out holding the unevaluated expression and the environment it
+/// was created in. The first `force` evaluates it and replaces the state with the
+/// result, so later forces return the cached value without re-evaluating.
+#[derive(Debug, Clone)]
+pub enum Promise {
+    Delayed { expr: Value, env: EnvRef },
+    Forced(Value),
+}
+
+/// State of a thread started by `spawn`
+///
+/// The spawned thread reports its result over a channel. The first `join` that
+/// receives it stores the result, so every later `join` returns the same value.
+#[derive(Debug)]
+pub enum Task {
+    Running(Receiver<Result<Value, EvalError>>),
+    Finished(Result<Value, EvalError>),
+}
+
+/// A WebSocket connection, held by the sandbox that opened it
+///
+/// The socket itself stays inside the sandbox, so a handle is only usable with
+/// the sandbox (and the threads sharing it) that returned it.
+#[derive(Debug, Clone)]
+pub struct WebSocketHandle {
+    pub id: u64,
+    pub url: String,
+}
+
+/// A multi-producer, multi-consumer queue of values shared between threads
+///
+/// Clones refer to the same queue. Values are moved through it as-is; lambdas
+/// keep sharing their captured environment with the sending thread.
+#[derive(Debug, Clone)]
+pub struct Channel {
+    pub sender: Sender<Value>,
+    pub receiver: Arc<Mutex<Receiver<Value>>>,
+}
+
+impl Channel {
+    pub fn new() -> Self {
+        let (sender, receiver) = std::sync::mpsc::channel();
+        Channel {
+            sender,
+            receiver: Arc::new(Mutex::new(receiver)),
+        }
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl fmt::Display for Value {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        match self {
-            Value::Number(n) => {
-                // Format numbers cleanly - if it's a whole number, display without decimal
-                if n.fract() == 0.0 && n.is_finite() {
-                    write!(f, "{}", *n as i64)
-                } else {
-                    write!(f, "{}", n)
-                }
+        write_value(f, self, PrintStyle::Write)
+    }
+}
+
+/// How [`write_value`] prints strings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PrintStyle {
+    /// Strings as their raw characters, even inside lists and maps; what `print` and `println` show
+    Display,
+    /// Strings quoted and escaped so the text parses back to the same value; what `Display` shows
+    Write,
+}
+
+/// Print `value` to `out`, writing each piece directly instead of building strings for sub-values
+///
+/// Lists and maps are trees: a sub-value that appears in several places is
+/// printed in full at each, and since nothing changes a list or map in place
+/// no value can contain itself. Functions, promises, threads and channels
+/// print as opaque `#<...>` handles without looking inside. Map entries are
+/// printed in key order. Lists and maps nested more than [`MAX_PRINT_DEPTH`]
+/// deep print as `(...)` and `{...}`.
+pub fn write_value(out: &mut impl fmt::Write, value: &Value, style: PrintStyle) -> fmt::Result {
+    write_value_limited(out, value, style, PrintLimits::default())
+}
+
+/// Deepest nesting any printer descends into, so that printing cannot exhaust the stack
+pub const MAX_PRINT_DEPTH: usize = 512;
+
+/// How much of a value printers show
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrintLimits {
+    /// Elements shown of each list and map; the rest are summarised as `... (N more)`
+    pub length: Option<usize>,
+    /// Levels of nested lists and maps shown; deeper ones print as `(...)` or `{...}`
+    pub depth: Option<usize>,
+}
+
+impl PrintLimits {
+    /// Levels that may still be shown, never more than [`MAX_PRINT_DEPTH`]
+    pub fn levels(&self) -> usize {
+        self.depth
+            .map_or(MAX_PRINT_DEPTH, |d| d.min(MAX_PRINT_DEPTH))
+    }
+
+    /// The limits for the elements of a list or map printed under these
+    pub fn nested(&self) -> Self {
+        PrintLimits {
+            length: self.length,
+            depth: Some(self.levels().saturating_sub(1)),
+        }
+    }
+}
+
+/// Print `value` like [`write_value`], within `limits`
+///
+/// The rest of a longer list or map is summarised as `... (N more)`, so a
+/// huge result costs no more to print than its first elements; lists and maps
+/// below the depth limit print as `(...)` and `{...}`.
+pub fn write_value_limited(
+    out: &mut impl fmt::Write,
+    value: &Value,
+    style: PrintStyle,
+    limits: PrintLimits,
+) -> fmt::Result {
+    match value {
+        Value::Number(n) => {
+            // Format numbers cleanly - if it's a whole number, display without decimal
+            if n.fract() == 0.0 && n.is_finite() {
+                write!(out, "{}", *n as i64)
+            } else {
+                write!(out, "{}", n)
+            }
+        }
+        Value::Decimal(d) => out.write_str(&d.to_plain_string()),
+        Value::Bool(b) => out.write_str(if *b { "#t" } else { "#f" }),
+        Value::Symbol(s) => out.write_str(s),
+        Value::Keyword(k) => write!(out, ":{}", k),
+        Value::String(s) => match style {
+            PrintStyle::Display => out.write_str(s),
+            PrintStyle::Write => write_escaped(out, s),
+        },
+        Value::List(_) if limits.levels() == 0 => out.write_str("(...)"),
+        Value::Map(_) if limits.levels() == 0 => out.write_str("{...}"),
+        Value::Values(items) => {
+            out.write_str("(values")?;
+            for item in items.iter() {
+                out.write_char(' ')?;
+                write_value_limited(out, item, style, limits.nested())?;
             }
-            Value::Bool(b) => write!(f, "{}", if *b { "#t" } else { "#f" }),
-            Value::Symbol(s) => write!(f, "{}", s),
-            Value::Keyword(k) => write!(f, ":{}", k),
-            Value::String(s) => write!(f, "\"{}\"", s),
-            Value::List(items) => {
-                write!(f, "(")?;
-                for (i, item) in items.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, "{}", item)?;
+            out.write_char(')')
+        }
+        Value::List(items) => {
+            out.write_char('(')?;
+            let shown = limits.length.map_or(items.len(), |n| n.min(items.len()));
+            for (i, item) in items[..shown].iter().enumerate() {
+                if i > 0 {
+                    out.write_char(' ')?;
                 }
-                write!(f, ")")
+                write_value_limited(out, item, style, limits.nested())?;
             }
-            Value::Map(map) => {
-                write!(f, "{{")?;
-                let mut entries: Vec<_> = map.iter().collect();
-                entries.sort_by_key(|(k, _)| *k); // Sort for consistent display
-                for (i, (key, value)) in entries.iter().enumerate() {
-                    if i > 0 {
-                        write!(f, " ")?;
-                    }
-                    write!(f, ":{} {}", key, value)?;
+            write_more(out, shown, items.len())?;
+            out.write_char(')')
+        }
+        Value::Map(map) => {
+            out.write_char('{')?;
+            let entries: Vec<_> = map.iter().collect();
+            let shown = limits
+                .length
+                .map_or(entries.len(), |n| n.min(entries.len()));
+            for (i, (key, value)) in entries[..shown].iter().enumerate() {
+                if i > 0 {
+                    out.write_char(' ')?;
                 }
-                write!(f, "}}")
+                write!(out, ":{} ", key)?;
+                write_value_limited(out, value, style, limits.nested())?;
             }
-            Value::Lambda { .. } => write!(f, "#<lambda>"),
-            Value::Macro { .. } => write!(f, "#<macro>"),
-            Value::BuiltIn(_) => write!(f, "#<builtin>"),
-            Value::Error(msg) => write!(f, "#<error: {}>", msg),
-            Value::Nil => write!(f, "nil"),
+            write_more(out, shown, entries.len())?;
+            out.write_char('}')
         }
+        Value::Lambda { .. } => out.write_str("#<lambda>"),
+        Value::Macro { .. } => out.write_str("#<macro>"),
+        Value::BuiltIn(_) => out.write_str("#<builtin>"),
+        Value::Promise(_) => out.write_str("#<promise>"),
+        Value::Thread(_) => out.write_str("#<thread>"),
+        Value::Channel(_) => out.write_str("#<channel>"),
+        Value::RateLimiter(limiter) => write!(out, "#<rate-limiter {}/s>", limiter.per_sec()),
+        Value::WebSocket(socket) => write!(out, "#<websocket {}>", socket.url),
+        Value::VectorStore(store) => write!(out, "#<vstore {} entries>", store.len()),
+        Value::Matrix(m) => write!(out, "#<matrix {}x{}>", m.rows(), m.cols()),
+        Value::Error(err) if err.kind == DEFAULT_ERROR_KIND => {
+            write!(out, "#<error: {}>", err.message)
+        }
+        Value::Error(err) => write!(out, "#<error :{} {}>", err.kind, err.message),
+        Value::Nil => out.write_str("nil"),
+    }
+}
+
+/// The `... (N more)` summary of elements left out, after `shown` of `total`
+pub fn more_marker(shown: usize, total: usize) -> Option<String> {
+    (shown < total).then(|| format!("... ({} more)", total - shown))
+}
+
+fn write_more(out: &mut impl fmt::Write, shown: usize, total: usize) -> fmt::Result {
+    match more_marker(shown, total) {
+        Some(marker) if shown > 0 => write!(out, " {}", marker),
+        Some(marker) => out.write_str(&marker),
+        None => Ok(()),
     }
 }
 
+/// A string literal with the escapes the parser understands: `\"`, `\\`, `\n` and `\t`
+fn write_escaped(out: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    out.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => out.write_str("\\\""),
+            '\\' => out.write_str("\\\\"),
+            '\n' => out.write_str("\\n"),
+            '\t' => out.write_str("\\t"),
+            c => out.write_char(c),
+        }?;
+    }
+    out.write_char('"')
+}
+
 impl Value {
+    /// An error value of the default kind
+    pub fn error(message: impl Into<String>) -> Value {
+        Value::Error(Box::new(ErrorValue::new(DEFAULT_ERROR_KIND, message)))
+    }
+
     /// Get user-friendly type name for error messages
     pub fn type_name(&self) -> String {
         match self {
@@ -87,14 +328,77 @@ impl Value {
             Value::Keyword(_) => "keyword".to_string(),
             Value::Bool(_) => "boolean".to_string(),
             Value::List(_) => "list".to_string(),
+            Value::Values(_) => "multiple values".to_string(),
             Value::Map(_) => "map".to_string(),
             Value::Lambda { .. } => "function".to_string(),
             Value::Macro { .. } => "macro".to_string(),
             Value::BuiltIn(_) => "builtin function".to_string(),
+            Value::Promise(_) => "promise".to_string(),
+            Value::Thread(_) => "thread".to_string(),
+            Value::Channel(_) => "channel".to_string(),
+            Value::RateLimiter(_) => "rate limiter".to_string(),
+            Value::WebSocket(_) => "websocket".to_string(),
+            Value::VectorStore(_) => "vector store".to_string(),
+            Value::Matrix(_) => "matrix".to_string(),
+            Value::Decimal(_) => "decimal".to_string(),
             Value::Error(_) => "error".to_string(),
             Value::Nil => "nil".to_string(),
         }
     }
+
+    /// Approximate bytes this value holds: the value itself plus the strings, lists and maps it owns
+    ///
+    /// Data shared between values is counted once per reference. Functions
+    /// count their body but not the environment they closed over, and handles
+    /// such as threads, channels and vector stores count only themselves.
+    pub fn estimated_size(&self) -> usize {
+        let owned = match self {
+            Value::String(s) => s.len(),
+            Value::Keyword(k) => k.len(),
+            Value::List(items) | Value::Values(items) => {
+                items.iter().map(Value::estimated_size).sum()
+            }
+            Value::Map(map) => map
+                .iter()
+                .map(|(key, value)| key.len() + value.estimated_size())
+                .sum(),
+            Value::Lambda { body, .. } | Value::Macro { body, .. } => body.estimated_size(),
+            Value::Matrix(m) => m.rows() * m.cols() * std::mem::size_of::<f64>(),
+            Value::Error(e) => e.message.len() + e.kind.len(),
+            _ => 0,
+        };
+        std::mem::size_of::<Value>() + owned
+    }
+}
+
+/// Recursively compare two values for equality: lists element by element, maps by keys and values,
+/// matrices by shape and elements, decimals by value (1.50 equals 1.5)
+///
+/// Functions, promises, threads, channels, rate limiters, websockets and vector stores are never equal.
+pub fn values_equal(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Number(x), Value::Number(y)) => x == y,
+        (Value::Decimal(x), Value::Decimal(y)) => x == y,
+        (Value::Bool(x), Value::Bool(y)) => x == y,
+        (Value::String(x), Value::String(y)) => x == y,
+        (Value::Symbol(x), Value::Symbol(y)) => x == y,
+        (Value::Keyword(x), Value::Keyword(y)) => x == y,
+        (Value::Nil, Value::Nil) => true,
+        (Value::List(x), Value::List(y)) | (Value::Values(x), Value::Values(y)) => {
+            x.len() == y.len() && x.iter().zip(y.iter()).all(|(a, b)| values_equal(a, b))
+        }
+        (Value::Map(x), Value::Map(y)) => {
+            // Compare maps: same keys with equal values
+            if x.len() != y.len() {
+                return false;
+            }
+            x.iter()
+                .all(|(k, v)| y.get(k).is_some_and(|v2| values_equal(v, v2)))
+        }
+        (Value::Matrix(x), Value::Matrix(y)) => x == y,
+        (Value::Error(x), Value::Error(y)) => x.kind == y.kind && x.message == y.message,
+        _ => false,
+    }
 }
 
 #[cfg(test)]
@@ -113,6 +417,33 @@ mod tests {
         assert_eq!(format!("{}", zero), "0");
     }
 
+    #[test]
+    fn test_write_value_styles() {
+        let value = Value::List(
+            vec![
+                Value::String("say \"hi\"\n".into()),
+                Value::Map(BTreeMap::from([(
+                    "k".to_string(),
+                    Value::String("v".into()),
+                )])),
+            ]
+            .into(),
+        );
+
+        let mut written = String::new();
+        write_value(&mut written, &value, PrintStyle::Write).unwrap();
+        assert_eq!(written, r#"("say \"hi\"\n" {:k "v"})"#);
+        assert_eq!(value.to_string(), written);
+
+        let mut displayed = String::new();
+        write_value(&mut displayed, &value, PrintStyle::Display).unwrap();
+        assert_eq!(displayed, "(say \"hi\"\n {:k v})");
+
+        // Written strings read back as the same value
+        let parsed = crate::parser::parse(&written).unwrap();
+        assert!(values_equal(&parsed, &value));
+    }
+
     #[test]
     fn test_bool_display() {
         let t = Value::Bool(true);
@@ -124,21 +455,21 @@ mod tests {
 
     #[test]
     fn test_list_display_with_nested_lists() {
-        let simple = Value::List(vec![
-            Value::Number(1.0),
-            Value::Number(2.0),
-            Value::Number(3.0),
-        ]);
+        let simple =
+            Value::List(vec![Value::Number(1.0), Value::Number(2.0), Value::Number(3.0)].into());
         assert_eq!(format!("{}", simple), "(1 2 3)");
 
-        let nested = Value::List(vec![
-            Value::Number(1.0),
-            Value::List(vec![Value::Number(2.0), Value::Number(3.0)]),
-            Value::Number(4.0),
-        ]);
+        let nested = Value::List(
+            vec![
+                Value::Number(1.0),
+                Value::List(vec![Value::Number(2.0), Value::Number(3.0)].into()),
+                Value::Number(4.0),
+            ]
+            .into(),
+        );
         assert_eq!(format!("{}", nested), "(1 (2 3) 4)");
 
-        let empty = Value::List(vec![]);
+        let empty = Value::List(vec![].into());
         assert_eq!(format!("{}", empty), "()");
     }
 
@@ -150,10 +481,26 @@ mod tests {
 
     #[test]
     fn test_symbol_and_string_display() {
-        let symbol = Value::Symbol("foo".to_string());
+        let symbol = Value::Symbol("foo".into());
         assert_eq!(format!("{}", symbol), "foo");
 
-        let string = Value::String("hello".to_string());
+        let string = Value::String("hello".into());
         assert_eq!(format!("{}", string), "\"hello\"");
     }
+
+    #[test]
+    fn test_clone_shares_list_and_string_storage() {
+        let text: Arc<str> = "a long quoted string".into();
+        let items: Arc<[Value]> = vec![Value::Number(1.0), Value::String(text.clone())].into();
+        let list = Value::List(items.clone());
+
+        match list.clone() {
+            Value::List(copy) => assert!(Arc::ptr_eq(&copy, &items)),
+            other => panic!("expected list, got {}", other),
+        }
+        match &items[1] {
+            Value::String(s) => assert!(Arc::ptr_eq(s, &text)),
+            other => panic!("expected string, got {}", other),
+        }
+    }
 }
@@ -1,12 +1,12 @@
 // ABOUTME: Comprehensive integration tests verifying all features work together
 
 use lisp_llm_sandbox::*;
-use std::rc::Rc;
+use std::sync::Arc;
 
 /// Set up environment with builtins and stdlib loaded
-fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
+fn setup() -> (Arc<env::Environment>, macros::MacroRegistry) {
     let env = env::Environment::new();
-    let mut macro_reg = macros::MacroRegistry::new();
+    let macro_reg = macros::MacroRegistry::new();
     builtins::register_builtins(env.clone());
 
     // Load modular stdlib (core, math, string, test, http)
@@ -17,7 +17,7 @@ fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
     let http = include_str!("../src/stdlib/lisp/http.lisp");
 
     for stdlib in &[core, math, strings, test, http] {
-        load_stdlib(stdlib, env.clone(), &mut macro_reg).expect("Failed to load stdlib module");
+        load_stdlib(stdlib, env.clone(), &macro_reg).expect("Failed to load stdlib module");
     }
 
     (env, macro_reg)
@@ -26,8 +26,8 @@ fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
 /// Load stdlib code into environment
 fn load_stdlib(
     code: &str,
-    env: Rc<env::Environment>,
-    macro_reg: &mut macros::MacroRegistry,
+    env: Arc<env::Environment>,
+    macro_reg: &macros::MacroRegistry,
 ) -> Result<(), String> {
     let mut remaining = code.trim();
 
@@ -128,8 +128,8 @@ fn find_expr_end(input: &str) -> Result<usize, String> {
 
 fn eval_code(
     code: &str,
-    env: Rc<env::Environment>,
-    macro_reg: &mut macros::MacroRegistry,
+    env: Arc<env::Environment>,
+    macro_reg: &macros::MacroRegistry,
 ) -> Result<value::Value, String> {
     let expr = parser::parse(code).map_err(|e| format!("Parse error: {}", e))?;
     eval::eval_with_macros(expr, env, macro_reg).map_err(|e| format!("Eval error: {:?}", e))
@@ -141,7 +141,7 @@ fn eval_code(
 
 #[test]
 fn test_factorial_program() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define factorial using recursion
     let code = r#"
@@ -150,17 +150,17 @@ fn test_factorial_program() {
           1
           (* n (factorial (- n 1)))))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
     // Test factorial(5)
-    let result = eval_code("(factorial 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(factorial 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 120.0),
         _ => panic!("Expected Number(120), got {:?}", result),
     }
 
     // Test factorial(0)
-    let result = eval_code("(factorial 0)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(factorial 0)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 1.0),
         _ => panic!("Expected Number(1), got {:?}", result),
@@ -169,7 +169,7 @@ fn test_factorial_program() {
 
 #[test]
 fn test_fibonacci_program() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define fibonacci using recursion
     let code = r#"
@@ -178,17 +178,17 @@ fn test_fibonacci_program() {
           n
           (+ (fib (- n 1)) (fib (- n 2)))))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
     // Test fib(10) = 55
-    let result = eval_code("(fib 10)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(fib 10)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 55.0),
         _ => panic!("Expected Number(55), got {:?}", result),
     }
 
     // Test fib(0) = 0
-    let result = eval_code("(fib 0)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(fib 0)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 0.0),
         _ => panic!("Expected Number(0), got {:?}", result),
@@ -197,13 +197,13 @@ fn test_fibonacci_program() {
 
 #[test]
 fn test_higher_order_functions() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test map from stdlib
     let result = eval_code(
         "(map (lambda (x) (* x 2)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     match result {
@@ -220,7 +220,7 @@ fn test_higher_order_functions() {
     let result = eval_code(
         "(filter (lambda (x) (> x 2)) '(1 2 3 4 5))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     match result {
@@ -232,7 +232,7 @@ fn test_higher_order_functions() {
     }
 
     // Test reduce from stdlib
-    let result = eval_code("(reduce + 0 '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(reduce + 0 '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10), got {:?}", result),
@@ -241,7 +241,7 @@ fn test_higher_order_functions() {
 
 #[test]
 fn test_macro_expansion() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define a simple test macro
     eval_code(
@@ -250,19 +250,19 @@ fn test_macro_expansion() {
       `(if ,test ,expr nil))
     "#,
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
     // Test when macro (expands to if)
-    let result = eval_code("(when #t 42)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(when #t 42)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 42.0),
         _ => panic!("Expected Number(42), got {:?}", result),
     }
 
     // Test when with false condition
-    let result = eval_code("(when #f 42)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(when #f 42)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Nil));
 
     // Define unless macro
@@ -272,12 +272,12 @@ fn test_macro_expansion() {
       `(if ,test nil ,expr))
     "#,
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
     // Test unless macro
-    let result = eval_code("(unless #f 100)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(unless #f 100)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 100.0),
         _ => panic!("Expected Number(100), got {:?}", result),
@@ -286,7 +286,7 @@ fn test_macro_expansion() {
 
 #[test]
 fn test_tco_deep_recursion() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define tail-recursive sum function
     let code = r#"
@@ -295,10 +295,10 @@ fn test_tco_deep_recursion() {
           acc
           (sum (- n 1) (+ acc n))))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
     // Test with 10000 iterations - would stack overflow without TCO
-    let result = eval_code("(sum 10000 0)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(sum 10000 0)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => {
             // Sum of 1 to 10000 = 10000 * 10001 / 2 = 50005000
@@ -308,35 +308,66 @@ fn test_tco_deep_recursion() {
     }
 }
 
+#[test]
+fn test_define_binds_in_the_current_frame() {
+    let (env, macro_reg) = setup();
+
+    // A body may start with inner defines; they stay local to each call
+    eval_code("(define total 0)", env.clone(), &macro_reg).unwrap();
+    let code = r#"
+    (define (sum-of-squares a b)
+      (define (sq x) (* x x))
+      (define total (+ (sq a) (sq b)))
+      total)
+    "#;
+    eval_code(code, env.clone(), &macro_reg).unwrap();
+    let result = eval_code("(sum-of-squares 3 4)", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "25");
+    assert!(!env.defines("sq"));
+    assert_eq!(env.get("total").unwrap().to_string(), "0");
+
+    let result = eval_code(
+        "(let ((a 1)) (define b 2) (+ a b))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "3");
+    assert!(!env.defines("b"));
+
+    let result = eval_code(
+        "((lambda (x) \"doubles then adds\" (define y (* x 2)) (+ x y)) 5)",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "15");
+}
+
 #[test]
 fn test_closures() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define a function that returns a closure
     let code = r#"
     (define (make-adder n)
       (lambda (x) (+ n x)))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
     // Create an adder that adds 5
-    eval_code("(define add5 (make-adder 5))", env.clone(), &mut macro_reg).unwrap();
+    eval_code("(define add5 (make-adder 5))", env.clone(), &macro_reg).unwrap();
 
     // Test the closure
-    let result = eval_code("(add5 10)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(add5 10)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 15.0),
         _ => panic!("Expected Number(15), got {:?}", result),
     }
 
     // Create another adder with different value
-    eval_code(
-        "(define add100 (make-adder 100))",
-        env.clone(),
-        &mut macro_reg,
-    )
-    .unwrap();
-    let result = eval_code("(add100 23)", env.clone(), &mut macro_reg).unwrap();
+    eval_code("(define add100 (make-adder 100))", env.clone(), &macro_reg).unwrap();
+    let result = eval_code("(add100 23)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 123.0),
         _ => panic!("Expected Number(123), got {:?}", result),
@@ -345,10 +376,10 @@ fn test_closures() {
 
 #[test]
 fn test_list_operations() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test cons
-    let result = eval_code("(cons 1 (list 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(cons 1 (list 2 3 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 4);
@@ -359,14 +390,14 @@ fn test_list_operations() {
     }
 
     // Test car
-    let result = eval_code("(car '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(car '(1 2 3))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 1.0),
         _ => panic!("Expected Number(1), got {:?}", result),
     }
 
     // Test cdr
-    let result = eval_code("(cdr '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(cdr '(1 2 3))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 2);
@@ -376,7 +407,7 @@ fn test_list_operations() {
     }
 
     // Test append
-    let result = eval_code("(append '(1 2) '(3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(append '(1 2) '(3 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 4);
@@ -387,17 +418,17 @@ fn test_list_operations() {
 
 #[test]
 fn test_quoting() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test simple quote
-    let result = eval_code("'(1 2 3)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("'(1 2 3)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => assert_eq!(items.len(), 3),
         _ => panic!("Expected List, got {:?}", result),
     }
 
     // Test quasiquote with unquote
-    let result = eval_code("`(1 ,(+ 2 3) 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("`(1 ,(+ 2 3) 4)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 3);
@@ -409,7 +440,7 @@ fn test_quoting() {
     }
 
     // Test quasiquote with unquote-splicing
-    let result = eval_code("`(1 ,@(list 2 3) 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("`(1 ,@(list 2 3) 4)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 4);
@@ -424,10 +455,10 @@ fn test_quoting() {
 
 #[test]
 fn test_let_bindings() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define outer x
-    eval_code("(define x 10)", env.clone(), &mut macro_reg).unwrap();
+    eval_code("(define x 10)", env.clone(), &macro_reg).unwrap();
 
     // Test let with shadowing
     let result = eval_code(
@@ -436,7 +467,7 @@ fn test_let_bindings() {
           (+ x 5))
     "#,
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
@@ -446,7 +477,7 @@ fn test_let_bindings() {
     }
 
     // Verify outer x is still 10
-    let result = eval_code("x", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("x", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10), got {:?}", result),
@@ -455,7 +486,7 @@ fn test_let_bindings() {
 
 #[test]
 fn test_complex_nested_expressions() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Complex expression combining multiple features
     let code = r#"
@@ -463,9 +494,9 @@ fn test_complex_nested_expressions() {
       (let ((doubled (map (lambda (x) (* x 2)) lst)))
         (filter (lambda (x) (> x 5)) doubled)))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
-    let result = eval_code("(process-list '(1 2 3 4 5))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(process-list '(1 2 3 4 5))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             // Original: [1, 2, 3, 4, 5]
@@ -482,23 +513,18 @@ fn test_complex_nested_expressions() {
 
 #[test]
 fn test_curry_and_composition() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test function composition from stdlib
     eval_code(
         "(define double (lambda (x) (* x 2)))",
         env.clone(),
-        &mut macro_reg,
-    )
-    .unwrap();
-    eval_code(
-        "(define inc (lambda (x) (+ x 1)))",
-        env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
+    eval_code("(define inc (lambda (x) (+ x 1)))", env.clone(), &macro_reg).unwrap();
 
-    let result = eval_code("((compose double inc) 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("((compose double inc) 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 12.0), // (5 + 1) * 2 = 12
         _ => panic!("Expected Number(12), got {:?}", result),
@@ -507,53 +533,53 @@ fn test_curry_and_composition() {
 
 #[test]
 fn test_predicates_and_logic() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test type predicates from builtins
-    let result = eval_code("(list? '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(list? '(1 2 3))", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(number? 42)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(number? 42)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(string? \"hello\")", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(string? \"hello\")", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
     // Test logical operations
-    let result = eval_code("(and #t #t)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(and #t #t)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(or #f #t)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(or #f #t)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(not #f)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(not #f)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 }
 
 #[test]
 fn test_arithmetic_operations() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test basic arithmetic
-    let result = eval_code("(+ 1 2 3 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(+ 1 2 3 4)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10), got {:?}", result),
     }
 
-    let result = eval_code("(* 2 3 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(* 2 3 4)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 24.0),
         _ => panic!("Expected Number(24), got {:?}", result),
     }
 
-    let result = eval_code("(- 10 3)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(- 10 3)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 7.0),
         _ => panic!("Expected Number(7), got {:?}", result),
     }
 
-    let result = eval_code("(/ 20 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(/ 20 4)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 5.0),
         _ => panic!("Expected Number(5), got {:?}", result),
@@ -562,7 +588,7 @@ fn test_arithmetic_operations() {
 
 #[test]
 fn test_quicksort_algorithm() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Implement quicksort in Lisp - chain append calls since it takes only 2 args
     let code = r#"
@@ -575,15 +601,10 @@ fn test_quicksort_algorithm() {
               (list (car lst))
               (quicksort (filter (lambda (x) (>= x (car lst))) (cdr lst)))))))
     "#;
-    eval_code(code, env.clone(), &mut macro_reg).unwrap();
+    eval_code(code, env.clone(), &macro_reg).unwrap();
 
     // Test quicksort
-    let result = eval_code(
-        "(quicksort '(3 1 4 1 5 9 2 6))",
-        env.clone(),
-        &mut macro_reg,
-    )
-    .unwrap();
+    let result = eval_code("(quicksort '(3 1 4 1 5 9 2 6))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::List(items) => {
             assert_eq!(items.len(), 8);
@@ -602,31 +623,31 @@ fn test_quicksort_algorithm() {
 
 #[test]
 fn test_error_conditions() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test undefined variable
-    let result = eval_code("undefined-var", env.clone(), &mut macro_reg);
+    let result = eval_code("undefined-var", env.clone(), &macro_reg);
     assert!(result.is_err());
 
     // Test division by zero
-    let result = eval_code("(/ 1 0)", env.clone(), &mut macro_reg);
+    let result = eval_code("(/ 1 0)", env.clone(), &macro_reg);
     assert!(result.is_err());
 
     // Test invalid function application
-    let result = eval_code("(42)", env.clone(), &mut macro_reg);
+    let result = eval_code("(42)", env.clone(), &macro_reg);
     assert!(result.is_err());
 }
 
 #[test]
 fn test_multiple_definitions() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Define multiple functions and use them together
-    eval_code("(define (add a b) (+ a b))", env.clone(), &mut macro_reg).unwrap();
-    eval_code("(define (mul a b) (* a b))", env.clone(), &mut macro_reg).unwrap();
-    eval_code("(define (square x) (mul x x))", env.clone(), &mut macro_reg).unwrap();
+    eval_code("(define (add a b) (+ a b))", env.clone(), &macro_reg).unwrap();
+    eval_code("(define (mul a b) (* a b))", env.clone(), &macro_reg).unwrap();
+    eval_code("(define (square x) (mul x x))", env.clone(), &macro_reg).unwrap();
 
-    let result = eval_code("(add (square 3) (square 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(add (square 3) (square 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 25.0), // 9 + 16 = 25
         _ => panic!("Expected Number(25), got {:?}", result),
@@ -635,7 +656,7 @@ fn test_multiple_definitions() {
 
 #[test]
 fn test_begin_sequencing() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Test begin with side effects
     let result = eval_code(
@@ -646,7 +667,7 @@ fn test_begin_sequencing() {
           (+ x y))
     "#,
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
@@ -656,9 +677,678 @@ fn test_begin_sequencing() {
     }
 
     // Verify variables were defined
-    let result = eval_code("x", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("x", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10), got {:?}", result),
     }
 }
+
+#[test]
+fn test_delay_and_force() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(define p (delay (* 6 7)))", env.clone(), &macro_reg).unwrap();
+    let result = eval_code("(promise? p)", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Bool(true)));
+
+    let result = eval_code("(+ (force p) (force p))", env.clone(), &macro_reg).unwrap();
+    match result {
+        value::Value::Number(n) => assert_eq!(n, 84.0),
+        _ => panic!("Expected Number(84), got {:?}", result),
+    }
+
+    // Non-promises force to themselves
+    let result = eval_code("(force 5)", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 5.0));
+}
+
+#[test]
+fn test_force_expands_user_macros() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(defmacro twice (x) `(* 2 ,x))", env.clone(), &macro_reg).unwrap();
+
+    // The delayed body is evaluated from inside the force builtin
+    let result = eval_code("(force (delay (+ 1 (twice 4))))", env.clone(), &macro_reg).unwrap();
+    match result {
+        value::Value::Number(n) => assert_eq!(n, 9.0),
+        _ => panic!("Expected Number(9), got {:?}", result),
+    }
+}
+
+#[test]
+fn test_quasiquote_fills_map_templates() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(define x 5)", env.clone(), &macro_reg).unwrap();
+    let result = eval_code("`(1 {:a ,x :b (x ,(+ x 1))})", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "(1 {:a 5 :b (x 6)})");
+
+    // A macro that builds structured data from its arguments
+    eval_code(
+        "(defmacro record (name value) `{:name ',name :value ,value})",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    let result = eval_code("(record width (* 2 x))", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "{:name width :value 10}");
+
+    let result = eval_code(
+        "(error-msg (try `{:a ,@(list 1 2)}))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(
+        result.to_string(),
+        "\"unquote-splicing: cannot splice into the value of map key :a\""
+    );
+}
+
+#[test]
+fn test_macro_bodies_use_earlier_macros() {
+    let (env, macro_reg) = setup();
+
+    eval_code(
+        "(defmacro backwards (x y) `(list ,y ,x))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    // The expander of `swap-args` uses `backwards` while it computes the expansion
+    eval_code(
+        "(defmacro swap-args (f a b) (cons f (backwards a b)))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+
+    let result = eval_code("(swap-args - 10 3)", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "-7");
+}
+
+#[test]
+fn test_defmacro_docstring_shows_in_help() {
+    let (env, macro_reg) = setup();
+
+    eval_code(
+        r#"(defmacro my-when (c body) "Evaluate body when c is true" `(if ,c ,body nil))"#,
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+
+    // The docstring is not mistaken for the expansion
+    let result = eval_code("(my-when #t 42)", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 42.0));
+
+    let entry = help::get_help("my-when").expect("macro should have help");
+    assert_eq!(entry.signature, "(my-when c body)");
+    assert_eq!(entry.description, "Evaluate body when c is true");
+    assert_eq!(entry.category, "Macro");
+    assert_eq!(
+        macro_reg.get("my-when").unwrap().docstring.as_deref(),
+        Some("Evaluate body when c is true")
+    );
+}
+
+#[test]
+fn test_builtins_declare_their_parameters() {
+    let (env, macro_reg) = setup();
+
+    assert_eq!(
+        help::get_help("map-get").unwrap().signature,
+        "(map-get map key [default])"
+    );
+    assert_eq!(help::get_help("+").unwrap().signature, "(+ numbers...)");
+
+    let pad = builtins::find_builtin("string-pad-left").unwrap();
+    assert_eq!(pad.params, ["string", "width", "[pad]"]);
+    assert_eq!((pad.min_arity, pad.max_arity), (2, Some(3)));
+    let map = builtins::find_builtin("map").unwrap();
+    assert_eq!((map.min_arity, map.max_arity), (2, None));
+
+    // Arity is checked before the builtin runs
+    for (code, message) in [
+        (
+            "(string-pad-left \"a\")",
+            "string-pad-left: expected 2-3 arguments, got 1",
+        ),
+        ("(map-new {})", "map-new: expected 0 arguments, got 1"),
+        (
+            "(reduce + 0)",
+            "reduce: expected at least 3 arguments, got 2",
+        ),
+    ] {
+        let result = eval_code(
+            &format!("(error-msg (try {}))", code),
+            env.clone(),
+            &macro_reg,
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), format!("{:?}", message));
+    }
+}
+
+#[test]
+fn test_builtins_taking_a_context_report_missing_state() {
+    let (env, macro_reg) = setup();
+
+    // The help builtins are declared with #[builtin] like every other builtin
+    let help_entry = builtins::find_builtin("help").unwrap();
+    assert_eq!((help_entry.min_arity, help_entry.max_arity), (0, Some(1)));
+    assert_eq!(
+        help::get_help("help").unwrap().signature,
+        "(help) or (help 'function-name)"
+    );
+
+    // No interpreter or sandbox is installed on this thread
+    for (code, message) in [
+        ("(help 'car 'cdr)", "help: expected 0-1 arguments, got 2"),
+        ("(bindings)", "bindings: no interpreter is running"),
+        ("(defined? 'car)", "defined?: no interpreter is running"),
+        (
+            "(read-file \"data.txt\")",
+            "read-file: Sandbox not initialized",
+        ),
+    ] {
+        let result = eval_code(
+            &format!("(error-msg (try {}))", code),
+            env.clone(),
+            &macro_reg,
+        )
+        .unwrap();
+        assert_eq!(result.to_string(), format!("{:?}", message));
+    }
+}
+
+#[test]
+fn test_help_finds_undocumented_macros() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(defmacro twice (x) `(* 2 ,x))", env.clone(), &macro_reg).unwrap();
+
+    let result = eval_code("(help 'twice)", env.clone(), &macro_reg);
+    assert!(matches!(result, Ok(value::Value::Nil)));
+}
+
+#[test]
+fn test_help_search_category_and_apropos() {
+    let (env, macro_reg) = setup();
+
+    let result = eval_code(r#"(help-category "concurrency")"#, env.clone(), &macro_reg).unwrap();
+    assert_eq!(
+        result.to_string(),
+        "(channel-recv channel-send join make-channel spawn)"
+    );
+
+    let result = eval_code(r#"(help-search "channel")"#, env.clone(), &macro_reg).unwrap();
+    assert!(result.to_string().contains("make-channel"));
+
+    let result = eval_code("(car (apropos 'spawn))", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "spawn");
+
+    assert!(eval_code(r#"(help-category "No such")"#, env.clone(), &macro_reg).is_err());
+}
+
+#[test]
+fn test_spawn_runs_in_parallel_with_channels() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(defmacro twice (x) `(* 2 ,x))", env.clone(), &macro_reg).unwrap();
+    eval_code("(define results (make-channel))", env.clone(), &macro_reg).unwrap();
+    eval_code(
+        "(define (worker n) (lambda () (channel-send results (twice n))))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+
+    // Workers see globals and user macros, and report back over the channel
+    let result = eval_code(
+        "(begin (for-each join (map spawn (map worker (list 1 2 3)))) \
+                (+ (channel-recv results) (channel-recv results) (channel-recv results)))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    match result {
+        value::Value::Number(n) => assert_eq!(n, 12.0),
+        _ => panic!("Expected Number(12), got {:?}", result),
+    }
+}
+
+#[test]
+fn test_join_returns_result_and_reraises_errors() {
+    let (env, macro_reg) = setup();
+
+    let result = eval_code(
+        "(join (spawn (lambda () (+ 40 2))))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 42.0));
+
+    let result = eval_code(
+        "(join (spawn (lambda () (car 1))))",
+        env.clone(),
+        &macro_reg,
+    );
+    assert!(result.is_err());
+
+    // Timeouts are error values, and the thread can still be joined later
+    eval_code("(define gate (make-channel))", env.clone(), &macro_reg).unwrap();
+    eval_code(
+        "(define t (spawn (lambda () (channel-recv gate))))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    let result = eval_code("(error? (join t 10))", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Bool(true)));
+    eval_code("(channel-send gate :open)", env.clone(), &macro_reg).unwrap();
+    let result = eval_code("(join t)", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Keyword(k) if k == "open"));
+}
+
+#[test]
+fn test_structured_errors() {
+    let (env, macro_reg) = setup();
+    let check = |code: &str, expected: &str, macro_reg: &macros::MacroRegistry| {
+        let result = eval_code(code, env.clone(), macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    };
+
+    check("(error-kind (error \"plain\"))", ":error", &macro_reg);
+    check("(error-data (error \"plain\"))", "nil", &macro_reg);
+    check(
+        "(error :not-found {:path \"x\"})",
+        "#<error :not-found not-found>",
+        &macro_reg,
+    );
+    check(
+        "(let ((e (error :bad-input \"age\" {:age -1}))) (list (error-kind e) (error-msg e) (error-data e)))",
+        "(:bad-input \"age\" {:age -1})",
+        &macro_reg,
+    );
+    assert!(eval_code("(error :kind 5)", env.clone(), &macro_reg).is_err());
+
+    // try turns raised errors into values with stable kinds
+    check("(try 1 (+ 1 2))", "3", &macro_reg);
+    check("(error-kind (try (car 5)))", ":type-error", &macro_reg);
+    check(
+        "(map-get (error-data (try (car 5))) :function)",
+        "\"car\"",
+        &macro_reg,
+    );
+    check("(error-kind (try (car)))", ":arity-error", &macro_reg);
+
+    // type errors show a short sketch of the value received
+    check(
+        "(error-msg (try (+ 1 \"two\")))",
+        "\"+: expected number, got string \\\"two\\\" at argument 2\"",
+        &macro_reg,
+    );
+    check(
+        "(map-get (error-data (try (car 5))) :value)",
+        "\"5\"",
+        &macro_reg,
+    );
+    check(
+        "(error-msg (try (car (string-repeat \"ab\" 50))))",
+        "\"car: expected list, got string \\\"abababababababababababababababababababa... at argument 1\"",
+        &macro_reg,
+    );
+    check(
+        "(error-msg (try (car nil)))",
+        "\"car: expected list, got nil at argument 1\"",
+        &macro_reg,
+    );
+
+    // calling a non-function names its type and the builtin that called it
+    check(
+        "(error-msg (try (partition 2 '(1 2 3))))",
+        "\"partition: number 2 is not callable\"",
+        &macro_reg,
+    );
+    check(
+        "(map-get (error-data (try (map \"f\" '(1)))) :function)",
+        "\"map\"",
+        &macro_reg,
+    );
+    check(
+        "(error-msg (try (map (lambda (f) (f 1)) '(5))))",
+        "\"number 5 is not callable\"",
+        &macro_reg,
+    );
+    check(
+        "(error-kind (try undefined-thing))",
+        ":undefined-symbol",
+        &macro_reg,
+    );
+    check(
+        "(error-msg (try (strng-length \"abc\")))",
+        "\"Undefined symbol: strng-length (did you mean `string-length`?)\"",
+        &macro_reg,
+    );
+    check(
+        "(map-get (error-data (try (mapp inc '(1)))) :suggestion)",
+        "\"map\"",
+        &macro_reg,
+    );
+    // One- and two-letter names are a single edit from too many builtins to hint
+    check("(error-msg (try x))", "\"Undefined symbol: x\"", &macro_reg);
+    check(
+        "(if (= (error-kind (try (car 5))) :type-error) 'handled 'other)",
+        "handled",
+        &macro_reg,
+    );
+    check(
+        "(dotimes (i 5) (try (if (= i 2) (break i))))",
+        "2",
+        &macro_reg,
+    );
+}
+
+#[test]
+fn test_results_and_threading() {
+    let (env, macro_reg) = setup();
+    let check = |code: &str, expected: &str, macro_reg: &macros::MacroRegistry| {
+        let result = eval_code(code, env.clone(), macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    };
+
+    check(
+        "(list (ok 1) (err \"bad\"))",
+        "((:ok 1) (:err \"bad\"))",
+        &macro_reg,
+    );
+    check(
+        "(list (ok? (ok 1)) (ok? (err 1)) (ok? 5) (err? (err 1)) (err? (error \"x\")) (err? '()))",
+        "(#t #f #f #t #t #f)",
+        &macro_reg,
+    );
+    check(
+        "(list (unwrap-or (ok 5) 0) (unwrap-or (err 1) 0) (unwrap-or (try (car 5)) 0) (unwrap-or 7 0))",
+        "(5 0 0 7)",
+        &macro_reg,
+    );
+
+    check("(-> 5 (- 1) (* 2))", "8", &macro_reg);
+    check("(-> '(1 2) car)", "1", &macro_reg);
+    check("(->> 5 (- 1))", "-4", &macro_reg);
+    check(
+        "(->> '(1 2 3 4) (filter even?) (map (lambda (x) (* x x))))",
+        "(4 16)",
+        &macro_reg,
+    );
+    // Inside a function body the steps must not be folded as complete calls
+    eval_code(
+        "(define (inc-double x) (-> x (+ 1) (* 2)))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    check("(inc-double 3)", "8", &macro_reg);
+
+    check(
+        "(some-> {:a {:b 1}} (map-get :a) (map-get :b))",
+        "1",
+        &macro_reg,
+    );
+    check(
+        "(some-> {:a 1} (map-get :missing) (+ 1))",
+        "nil",
+        &macro_reg,
+    );
+    check("(some-> (err \"bad\") car)", "(:err \"bad\")", &macro_reg);
+    check(
+        "(error-kind (some-> (try (car 5)) (+ 1)))",
+        ":type-error",
+        &macro_reg,
+    );
+}
+
+#[test]
+fn test_loops_inside_function_bodies() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(defmacro twice (x) `(* 2 ,x))", env.clone(), &macro_reg).unwrap();
+    eval_code(
+        "(define (first-over limit items) \
+           (dolist (x items) (if (> (twice x) limit) (break x))))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    eval_code(
+        "(define (count-down n) \
+           (let ((steps 0)) (while (> n 0) (set! n (- n 1)) (set! steps (+ steps 1))) steps))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+
+    let result = eval_code("(first-over 5 (list 1 2 3 4))", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 3.0));
+
+    let result = eval_code("(first-over 100 (list 1 2))", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Nil));
+
+    let result = eval_code("(count-down 4)", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 4.0));
+
+    // break leaves the loop even from inside a function the body calls
+    let result = eval_code(
+        "(dotimes (i (twice 5)) (for-each (lambda (x) (if (= x i) (break i))) (list 3 7)))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert!(matches!(result, value::Value::Number(n) if n == 3.0));
+}
+
+#[test]
+fn test_native_higher_order_functions() {
+    let (env, macro_reg) = setup();
+
+    let result = eval_code("(map + '(1 2 3) '(10 20))", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "(11 22)");
+
+    let result = eval_code(
+        "(reduce (lambda (acc x y) (+ acc (* x y))) 0 '(1 2) '(3 4))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "11");
+
+    let result = eval_code(
+        "(some (lambda (x) (if (> x 2) x nil)) '(1 2 3 4))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "3");
+
+    let result = eval_code("(every < '(1 2) '(3 4))", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "#t");
+
+    let result = eval_code("(for-each + '(1 2))", env.clone(), &macro_reg).unwrap();
+    assert!(matches!(result, value::Value::Nil));
+}
+
+#[test]
+fn test_native_list_reshaping() {
+    let (env, macro_reg) = setup();
+
+    let cases = [
+        ("(flatten '(1 (2 (3 4)) () 5))", "(1 2 3 4 5)"),
+        ("(partition even? '(1 2 3 4 5))", "((2 4) (1 3 5))"),
+        (
+            "(group-by (lambda (x) (if (even? x) :even :odd)) '(1 2 3 4))",
+            "{:even (2 4) :odd (1 3)}",
+        ),
+        ("(frequencies '(a b a \"a\" 1))", "{:1 1 :a 3 :b 1}"),
+        ("(distinct '(1 (2) 1 (2) \"1\"))", "(1 (2) \"1\")"),
+        ("(interleave '(1 2 3) '(a b) '(x y z))", "(1 a x 2 b y)"),
+        ("(chunk 2 '(1 2 3 4 5))", "((1 2) (3 4) (5))"),
+        ("(chunk 3 nil)", "()"),
+    ];
+    for (code, expected) in cases {
+        let result = eval_code(code, env.clone(), &macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    }
+
+    assert!(eval_code("(chunk 0 '(1 2))", env.clone(), &macro_reg).is_err());
+    assert!(eval_code("(flatten 5)", env.clone(), &macro_reg).is_err());
+}
+
+#[test]
+fn test_multiple_values() {
+    let (env, macro_reg) = setup();
+
+    let cases = [
+        ("(divmod 17 5)", "(values 3 2)"),
+        ("(divmod -7 2)", "(values -3 -1)"),
+        ("(let (((q r) (divmod 17 5))) (+ (* q 5) r))", "17"),
+        ("(values 5)", "5"),
+        ("(call-with-values (lambda () (values 1 2)) +)", "3"),
+        (
+            "(call-with-values (lambda () 4) (lambda (x) (* x x)))",
+            "16",
+        ),
+        ("(call-with-values (lambda () (values)) list)", "()"),
+        ("(values)", "(values)"),
+        ("(values '(1 2))", "(1 2)"),
+        // A single list is one value, not two
+        ("(call-with-values (lambda () (values '(1 2))) length)", "2"),
+        ("(call-with-values (lambda () '(1 2 3)) list)", "((1 2 3))"),
+        ("(call-with-values (lambda () nil) list)", "(nil)"),
+        (
+            "(call-with-values (lambda () (values)) (lambda () 'none))",
+            "none",
+        ),
+        ("(let (((a b) (values '(1 2) 3))) (list a b))", "((1 2) 3)"),
+        ("((lambda ((q r)) (list q r)) (divmod 7 2))", "(3 1)"),
+    ];
+    for (code, expected) in cases {
+        let result = eval_code(code, env.clone(), &macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    }
+
+    assert!(eval_code("(divmod 1 0)", env.clone(), &macro_reg).is_err());
+}
+
+#[test]
+fn test_some_and_every_stop_early() {
+    let (env, macro_reg) = setup();
+
+    // car of a string errors, so reaching the third element would fail
+    let result = eval_code(
+        "(some (lambda (x) (if (number? x) (= x 2) (car x))) '(1 2 \"boom\"))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "#t");
+
+    let result = eval_code(
+        "(every (lambda (x) (if (number? x) (< x 2) (car x))) '(1 2 \"boom\"))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "#f");
+}
+
+#[test]
+fn test_map_callback_expands_user_macros() {
+    let (env, macro_reg) = setup();
+
+    eval_code("(defmacro twice (x) `(* 2 ,x))", env.clone(), &macro_reg).unwrap();
+
+    let result = eval_code(
+        "(map (lambda (n) (twice n)) '(1 2 3))",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    assert_eq!(result.to_string(), "(2 4 6)");
+}
+
+#[test]
+fn test_units_parse_and_format() {
+    let (env, macro_reg) = setup();
+
+    let cases = [
+        (r#"(parse-bytes "10MB")"#, "10485760"),
+        (r#"(parse-bytes "1.5 kb")"#, "1536"),
+        (r#"(parse-bytes "2GiB")"#, "2147483648"),
+        (r#"(parse-bytes "512")"#, "512"),
+        ("(format-bytes 10485760)", r#""10MB""#),
+        ("(format-bytes 1536)", r#""1.5KB""#),
+        ("(format-bytes 12)", r#""12B""#),
+        (r#"(parse-duration "1h30m")"#, "5400"),
+        (r#"(parse-duration "2m 15s")"#, "135"),
+        (r#"(parse-duration "250ms")"#, "0.25"),
+        (r#"(parse-duration "45")"#, "45"),
+        ("(format-duration 5400)", r#""1h30m""#),
+        ("(format-duration 90.5)", r#""1m30.5s""#),
+        ("(format-duration 0.25)", r#""250ms""#),
+        ("(format-duration 0)", r#""0s""#),
+        ("(format-duration 93784)", r#""1d2h3m4s""#),
+        (r#"(format-duration (parse-duration "1d1s"))"#, r#""1d1s""#),
+    ];
+    for (code, expected) in cases {
+        let result = eval_code(code, env.clone(), &macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    }
+
+    for code in [
+        r#"(parse-bytes "10XB")"#,
+        r#"(parse-bytes "-5")"#,
+        r#"(parse-duration "1h30")"#,
+        r#"(parse-duration "")"#,
+        "(format-duration -1)",
+    ] {
+        assert!(
+            eval_code(code, env.clone(), &macro_reg).is_err(),
+            "{}",
+            code
+        );
+    }
+}
+
+#[test]
+fn test_maps_iterate_in_key_order() {
+    let (env, macro_reg) = setup();
+
+    let cases = [
+        (
+            "(map-keys {:zeta 1 :alpha 2 :mid 3})",
+            "(:alpha :mid :zeta)",
+        ),
+        ("(map-values {:zeta 1 :alpha 2 :mid 3})", "(2 3 1)"),
+        ("(map-entries (map-set {:b 2} :a 1))", "((:a 1) (:b 2))"),
+        ("(map-merge {:y 1} {:x 2})", "{:x 2 :y 1}"),
+    ];
+    for (code, expected) in cases {
+        let result = eval_code(code, env.clone(), &macro_reg).unwrap();
+        assert_eq!(result.to_string(), expected, "{}", code);
+    }
+
+    // Values of a map literal are evaluated in key order too
+    eval_code("(define order '())", env.clone(), &macro_reg).unwrap();
+    eval_code(
+        "(define m {:zeta (set! order (cons 'z order)) :alpha (set! order (cons 'a order))})",
+        env.clone(),
+        &macro_reg,
+    )
+    .unwrap();
+    let result = eval_code("(reverse order)", env.clone(), &macro_reg).unwrap();
+    assert_eq!(result.to_string(), "(a z)");
+}
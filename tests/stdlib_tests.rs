@@ -1,11 +1,11 @@
 // ABOUTME: Tests for standard library functions
 
 use lisp_llm_sandbox::*;
-use std::rc::Rc;
+use std::sync::Arc;
 
-fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
+fn setup() -> (Arc<env::Environment>, macros::MacroRegistry) {
     let env = env::Environment::new();
-    let mut macro_reg = macros::MacroRegistry::new();
+    let macro_reg = macros::MacroRegistry::new();
     builtins::register_builtins(env.clone());
 
     // Load modular stdlib (core, math, string, test, http)
@@ -16,8 +16,7 @@ fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
     let http = include_str!("../src/stdlib/lisp/http.lisp");
 
     for stdlib in &[core, math, strings, test, http] {
-        load_stdlib_test(stdlib, env.clone(), &mut macro_reg)
-            .expect("Failed to load stdlib module");
+        load_stdlib_test(stdlib, env.clone(), &macro_reg).expect("Failed to load stdlib module");
     }
 
     (env, macro_reg)
@@ -25,8 +24,8 @@ fn setup() -> (Rc<env::Environment>, macros::MacroRegistry) {
 
 fn load_stdlib_test(
     code: &str,
-    env: Rc<env::Environment>,
-    macro_reg: &mut macros::MacroRegistry,
+    env: Arc<env::Environment>,
+    macro_reg: &macros::MacroRegistry,
 ) -> Result<(), String> {
     let mut remaining = code.trim();
 
@@ -127,8 +126,8 @@ fn find_expr_end_test(input: &str) -> Result<usize, String> {
 
 fn eval_code(
     code: &str,
-    env: Rc<env::Environment>,
-    macro_reg: &mut macros::MacroRegistry,
+    env: Arc<env::Environment>,
+    macro_reg: &macros::MacroRegistry,
 ) -> Result<value::Value, String> {
     let expr = parser::parse(code).map_err(|e| format!("Parse error: {}", e))?;
     eval::eval_with_macros(expr, env, macro_reg).map_err(|e| format!("Eval error: {:?}", e))
@@ -140,12 +139,12 @@ fn eval_code(
 
 #[test]
 fn test_map() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     let result = eval_code(
         "(map (lambda (x) (* x 2)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
@@ -171,12 +170,12 @@ fn test_map() {
 
 #[test]
 fn test_filter() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     let result = eval_code(
         "(filter (lambda (x) (> x 2)) '(1 2 3 4 5))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
 
@@ -194,9 +193,9 @@ fn test_filter() {
 
 #[test]
 fn test_reduce() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(reduce + 0 '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(reduce + 0 '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
@@ -210,9 +209,9 @@ fn test_reduce() {
 
 #[test]
 fn test_reverse() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(reverse '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(reverse '(1 2 3))", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::List(items) => {
@@ -232,9 +231,9 @@ fn test_reverse() {
 
 #[test]
 fn test_append() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(append '(1 2) '(3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(append '(1 2) '(3 4))", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::List(items) => {
@@ -246,26 +245,26 @@ fn test_append() {
 
 #[test]
 fn test_member() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(member 2 '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(member 2 '(1 2 3))", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(member 5 '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(member 5 '(1 2 3))", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(false)));
 }
 
 #[test]
 fn test_nth() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(nth 0 '(10 20 30))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(nth 0 '(10 20 30))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10)"),
     }
 
-    let result = eval_code("(nth 2 '(10 20 30))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(nth 2 '(10 20 30))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 30.0),
         _ => panic!("Expected Number(30)"),
@@ -274,9 +273,9 @@ fn test_nth() {
 
 #[test]
 fn test_last() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(last '(1 2 3))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(last '(1 2 3))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 3.0),
         _ => panic!("Expected Number(3)"),
@@ -285,9 +284,9 @@ fn test_last() {
 
 #[test]
 fn test_take() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(take 2 '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(take 2 '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::List(items) => {
@@ -299,9 +298,9 @@ fn test_take() {
 
 #[test]
 fn test_drop() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(drop 2 '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(drop 2 '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::List(items) => {
@@ -321,12 +320,12 @@ fn test_drop() {
 
 #[test]
 fn test_all() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     let result = eval_code(
         "(all (lambda (x) (> x 0)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
@@ -334,7 +333,7 @@ fn test_all() {
     let result = eval_code(
         "(all (lambda (x) (> x 2)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     assert!(matches!(result, value::Value::Bool(false)));
@@ -342,12 +341,12 @@ fn test_all() {
 
 #[test]
 fn test_any() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     let result = eval_code(
         "(any (lambda (x) (> x 2)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
@@ -355,7 +354,7 @@ fn test_any() {
     let result = eval_code(
         "(any (lambda (x) (> x 5)) '(1 2 3))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     assert!(matches!(result, value::Value::Bool(false)));
@@ -363,12 +362,12 @@ fn test_any() {
 
 #[test]
 fn test_count() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     let result = eval_code(
         "(count (lambda (x) (> x 2)) '(1 2 3 4 5))",
         env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
     match result {
@@ -383,9 +382,9 @@ fn test_count() {
 
 #[test]
 fn test_range() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(range 0 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(range 0 5)", env.clone(), &macro_reg).unwrap();
 
     match result {
         value::Value::List(items) => {
@@ -409,15 +408,15 @@ fn test_range() {
 
 #[test]
 fn test_abs() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(abs -5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(abs -5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 5.0),
         _ => panic!("Expected Number(5)"),
     }
 
-    let result = eval_code("(abs 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(abs 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 5.0),
         _ => panic!("Expected Number(5)"),
@@ -426,15 +425,15 @@ fn test_abs() {
 
 #[test]
 fn test_min_max() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(min 3 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(min 3 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 3.0),
         _ => panic!("Expected Number(3)"),
     }
 
-    let result = eval_code("(max 3 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(max 3 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 5.0),
         _ => panic!("Expected Number(5)"),
@@ -443,15 +442,15 @@ fn test_min_max() {
 
 #[test]
 fn test_square_cube() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(square 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(square 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 25.0),
         _ => panic!("Expected Number(25)"),
     }
 
-    let result = eval_code("(cube 3)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(cube 3)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 27.0),
         _ => panic!("Expected Number(27)"),
@@ -460,29 +459,29 @@ fn test_square_cube() {
 
 #[test]
 fn test_even_odd() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(even? 4)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(even? 4)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(odd? 3)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(odd? 3)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(true)));
 
-    let result = eval_code("(even? 3)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(even? 3)", env.clone(), &macro_reg).unwrap();
     assert!(matches!(result, value::Value::Bool(false)));
 }
 
 #[test]
 fn test_sum_product() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(sum '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(sum '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 10.0),
         _ => panic!("Expected Number(10)"),
     }
 
-    let result = eval_code("(product '(1 2 3 4))", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(product '(1 2 3 4))", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 24.0),
         _ => panic!("Expected Number(24)"),
@@ -491,15 +490,15 @@ fn test_sum_product() {
 
 #[test]
 fn test_factorial() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
-    let result = eval_code("(factorial 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(factorial 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 120.0, "Got {} instead of 120", n),
         _ => panic!("Expected Number(120), got {:?}", result),
     }
 
-    let result = eval_code("(factorial 0)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("(factorial 0)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 1.0, "Got {} instead of 1", n),
         _ => panic!("Expected Number(1), got {:?}", result),
@@ -512,23 +511,18 @@ fn test_factorial() {
 
 #[test]
 fn test_compose() {
-    let (env, mut macro_reg) = setup();
+    let (env, macro_reg) = setup();
 
     // Set up functions and test composition
     eval_code(
         "(define double (lambda (x) (* x 2)))",
         env.clone(),
-        &mut macro_reg,
-    )
-    .unwrap();
-    eval_code(
-        "(define inc (lambda (x) (+ x 1)))",
-        env.clone(),
-        &mut macro_reg,
+        &macro_reg,
     )
     .unwrap();
+    eval_code("(define inc (lambda (x) (+ x 1)))", env.clone(), &macro_reg).unwrap();
 
-    let result = eval_code("((compose double inc) 5)", env.clone(), &mut macro_reg).unwrap();
+    let result = eval_code("((compose double inc) 5)", env.clone(), &macro_reg).unwrap();
     match result {
         value::Value::Number(n) => assert_eq!(n, 12.0), // (5 + 1) * 2 = 12
         _ => panic!("Expected Number(12)"),
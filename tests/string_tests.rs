@@ -1,20 +1,19 @@
 // Tests for string manipulation and testing assertion functions
 
-use lisp_llm_sandbox::env::Environment;
+use lisp_llm_sandbox::env::{EnvRef, Environment};
 use lisp_llm_sandbox::error::EvalError;
 use lisp_llm_sandbox::eval::eval;
 use lisp_llm_sandbox::parser::parse;
 use lisp_llm_sandbox::value::Value;
-use std::rc::Rc;
 
 /// Helper to parse and evaluate an expression
-fn eval_expr(expr: &str, env: &Rc<Environment>) -> Result<Value, EvalError> {
+fn eval_expr(expr: &str, env: &EnvRef) -> Result<Value, EvalError> {
     let parsed = parse(expr).map_err(|e| EvalError::runtime_error("eval_expr", e.to_string()))?;
     eval(parsed, env.clone())
 }
 
 /// Helper to get a test environment with builtins
-fn test_env() -> Rc<Environment> {
+fn test_env() -> EnvRef {
     let env = Environment::new();
     lisp_llm_sandbox::builtins::register_builtins(env.clone());
     env
@@ -43,7 +42,7 @@ fn test_assert_with_message() {
     let env = test_env();
     let result = eval_expr("(assert #f \"custom message\")", &env).unwrap();
     match result {
-        Value::Error(msg) => assert_eq!(msg, "custom message"),
+        Value::Error(err) => assert_eq!(err.message, "custom message"),
         _ => panic!("Expected error value"),
     }
 }
@@ -99,9 +98,9 @@ fn test_string_split() {
     match result {
         Value::List(items) => {
             assert_eq!(items.len(), 3);
-            assert!(matches!(&items[0], Value::String(s) if s == "a"));
-            assert!(matches!(&items[1], Value::String(s) if s == "b"));
-            assert!(matches!(&items[2], Value::String(s) if s == "c"));
+            assert!(matches!(&items[0], Value::String(s) if &**s == "a"));
+            assert!(matches!(&items[1], Value::String(s) if &**s == "b"));
+            assert!(matches!(&items[2], Value::String(s) if &**s == "c"));
         }
         _ => panic!("Expected list"),
     }
@@ -112,7 +111,7 @@ fn test_string_join() {
     let env = test_env();
     let result = eval_expr("(string-join '(\"a\" \"b\" \"c\") \",\")", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "a,b,c"),
+        Value::String(s) => assert_eq!(s, "a,b,c".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -122,13 +121,13 @@ fn test_substring() {
     let env = test_env();
     let result = eval_expr("(substring \"hello\" 0 3)", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "hel"),
+        Value::String(s) => assert_eq!(s, "hel".into()),
         _ => panic!("Expected string"),
     }
 
     let result = eval_expr("(substring \"hello\" 2 5)", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "llo"),
+        Value::String(s) => assert_eq!(s, "llo".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -138,7 +137,7 @@ fn test_string_trim() {
     let env = test_env();
     let result = eval_expr("(string-trim \"  hello  \")", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "hello"),
+        Value::String(s) => assert_eq!(s, "hello".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -148,7 +147,7 @@ fn test_string_upper() {
     let env = test_env();
     let result = eval_expr("(string-upper \"hello\")", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "HELLO"),
+        Value::String(s) => assert_eq!(s, "HELLO".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -158,7 +157,7 @@ fn test_string_lower() {
     let env = test_env();
     let result = eval_expr("(string-lower \"HELLO\")", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "hello"),
+        Value::String(s) => assert_eq!(s, "hello".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -168,7 +167,7 @@ fn test_string_replace() {
     let env = test_env();
     let result = eval_expr("(string-replace \"hello\" \"l\" \"L\")", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "heLLo"),
+        Value::String(s) => assert_eq!(s, "heLLo".into()),
         _ => panic!("Expected string"),
     }
 }
@@ -255,17 +254,92 @@ fn test_number_to_string() {
     let env = test_env();
     let result = eval_expr("(number->string 42)", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "42"),
+        Value::String(s) => assert_eq!(s, "42".into()),
         _ => panic!("Expected string"),
     }
 
     let result = eval_expr("(number->string 3.14)", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "3.14"),
+        Value::String(s) => assert_eq!(s, "3.14".into()),
         _ => panic!("Expected string"),
     }
 }
 
+#[test]
+fn test_number_to_string_options() {
+    let env = test_env();
+    let cases = [
+        ("(number->string 3.14159 {:precision 2})", "3.14"),
+        ("(number->string 2 {:precision 3})", "2.000"),
+        ("(number->string 255 {:radix 16})", "ff"),
+        ("(number->string -5 {:radix 2})", "-101"),
+        ("(number->string 0 {:radix 36})", "0"),
+    ];
+    for (code, expected) in cases {
+        match eval_expr(code, &env).unwrap() {
+            Value::String(s) => assert_eq!(s, expected.into(), "{}", code),
+            other => panic!("Expected string from {}, got {:?}", code, other),
+        }
+    }
+
+    assert!(eval_expr("(number->string 1.5 {:radix 16})", &env).is_err());
+    assert!(eval_expr("(number->string 10 {:radix 1})", &env).is_err());
+    assert!(eval_expr("(number->string 10 {:precision 1 :radix 8})", &env).is_err());
+}
+
+#[test]
+fn test_string_padding_and_repeat() {
+    let env = test_env();
+    let cases = [
+        ("(string-pad-left \"42\" 5)", "   42"),
+        ("(string-pad-left \"42\" 5 \"0\")", "00042"),
+        ("(string-pad-left \"text\" 2)", "text"),
+        ("(string-pad-right \"né\" 4 \".\")", "né.."),
+        ("(string-repeat \"ab\" 3)", "ababab"),
+        ("(string-repeat \"x\" 0)", ""),
+        ("(string-reverse \"héllo\")", "olléh"),
+    ];
+    for (code, expected) in cases {
+        match eval_expr(code, &env).unwrap() {
+            Value::String(s) => assert_eq!(s, expected.into(), "{}", code),
+            other => panic!("Expected string from {}, got {:?}", code, other),
+        }
+    }
+
+    assert!(eval_expr("(string-pad-left \"a\" 3 \"ab\")", &env).is_err());
+    assert!(eval_expr("(string-repeat \"a\" -1)", &env).is_err());
+}
+
+#[test]
+fn test_string_index_of_and_chars() {
+    let env = test_env();
+    let index = |code: &str| eval_expr(code, &env).unwrap();
+    assert!(
+        matches!(index("(string-index-of \"hello world\" \"o\")"), Value::Number(n) if n == 4.0)
+    );
+    assert!(
+        matches!(index("(string-index-of \"hello world\" \"o\" 5)"), Value::Number(n) if n == 7.0)
+    );
+    assert!(matches!(index("(string-index-of \"héllo\" \"l\")"), Value::Number(n) if n == 2.0));
+    assert!(matches!(index("(string-index-of \"abc\" \"\" 3)"), Value::Number(n) if n == 3.0));
+    assert!(matches!(
+        index("(string-index-of \"hello\" \"z\")"),
+        Value::Nil
+    ));
+    assert!(matches!(
+        index("(string-index-of \"hello\" \"h\" 9)"),
+        Value::Nil
+    ));
+
+    match index("(string-chars \"héy\")") {
+        Value::List(items) => assert_eq!(
+            items.iter().map(|v| v.to_string()).collect::<Vec<_>>(),
+            vec!["\"h\"", "\"é\"", "\"y\""]
+        ),
+        other => panic!("Expected list, got {:?}", other),
+    }
+}
+
 #[test]
 fn test_string_to_list() {
     let env = test_env();
@@ -273,9 +347,9 @@ fn test_string_to_list() {
     match result {
         Value::List(items) => {
             assert_eq!(items.len(), 3);
-            assert!(matches!(&items[0], Value::String(s) if s == "a"));
-            assert!(matches!(&items[1], Value::String(s) if s == "b"));
-            assert!(matches!(&items[2], Value::String(s) if s == "c"));
+            assert!(matches!(&items[0], Value::String(s) if &**s == "a"));
+            assert!(matches!(&items[1], Value::String(s) if &**s == "b"));
+            assert!(matches!(&items[2], Value::String(s) if &**s == "c"));
         }
         _ => panic!("Expected list"),
     }
@@ -286,7 +360,7 @@ fn test_list_to_string() {
     let env = test_env();
     let result = eval_expr("(list->string '(\"h\" \"e\" \"l\" \"l\" \"o\"))", &env).unwrap();
     match result {
-        Value::String(s) => assert_eq!(s, "hello"),
+        Value::String(s) => assert_eq!(s, "hello".into()),
         _ => panic!("Expected string"),
     }
 }